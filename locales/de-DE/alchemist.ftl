@@ -0,0 +1,60 @@
+# Agent UX strings and prompts, in German
+
+system-prompt =
+    Du bist der Alchemist, ein KI-Assistent, der Menschen hilft, die Architektur der Composable Information Machine (CIM) zu verstehen und mit ihr zu arbeiten.
+
+    Deine Fachgebiete:
+    - Ereignisgesteuerte Architektur mit Event Sourcing und CQRS
+    - Prinzipien und Muster des Domain-Driven Design
+    - Entity Component Systems (ECS) mit Bevy
+    - Graphbasierte Workflows und visuelle Programmierung
+    - Konzeptuelle Räume für semantisches Verständnis
+    - NATS-Messaging und verteilte Systeme
+    - Bewährte Praktiken der Rust-Programmierung
+
+    Deine Aufgaben:
+    - Erkläre CIM-Konzepte klar und korrekt
+    - Nutze Beispiele aus der tatsächlichen CIM-Codebasis, wo es passt
+    - Führe durch Implementierungsmuster
+    - Schlage bewährte Praktiken und Verbesserungen vor
+    - Hilf beim Debuggen und Lösen von Architekturproblemen
+
+    Sei stets hilfsbereit, präzise und lehrreich. Antworte auf Deutsch.
+
+answered-by-peer = Beantwortet vom Partner-Agenten { $agent }
+dialog-summary = Zusammenfassung des bisherigen Gesprächs: { $summary }
+pinned-context = Vom Nutzer angeheftet, im ganzen Gespräch zu beachten:
+
+## Errors
+
+budget-exceeded =
+    { $scope ->
+        [dialog] Dieser Dialog hat sein Token-Budget aufgebraucht ({ $used } von { $limit } Tokens). Beginne einen neuen Dialog, um fortzufahren.
+       *[user] Du hast dein Token-Budget aufgebraucht ({ $used } von { $limit } Tokens). Versuche es später erneut.
+    }
+unknown-workflow = Unbekannter Workflow-Typ: { $workflow }
+partial-answer = Das dauert etwas länger, deshalb hier schon mal, was ich bisher habe. Die vollständige Antwort folgt.
+model-unavailable = Das Sprachmodell ist gerade nicht erreichbar, deshalb kann ich keine Fragen beantworten. Konzeptlisten, Workflow-Status und Dialogverläufe funktionieren weiterhin, und sobald das Modell wieder erreichbar ist, bin ich voll einsatzbereit.
+
+## Workflow steps
+
+create-agent-title = Projektstruktur anlegen
+create-agent-description = Lege ein neues cim-agent-*-Verzeichnis mit der Standardstruktur an
+create-agent-action-1 = Cargo.toml mit Abhängigkeiten erstellen
+create-agent-action-2 = Verzeichnisstruktur unter src/ anlegen
+create-agent-action-3 = Konfigurationsvorlagen erstellen
+create-agent-action-4 = Git-Repository initialisieren
+
+implement-domain-title = Domänenmodell entwerfen
+implement-domain-description = Lege die Grenzen der Domäne und ihre Kernkonzepte fest
+implement-domain-action-1 = Aggregate und Entitäten identifizieren
+implement-domain-action-2 = Wertobjekte definieren
+implement-domain-action-3 = Beziehungen abbilden
+implement-domain-action-4 = Ubiquitäre Sprache dokumentieren
+
+add-event-title = Ereignisstruktur definieren
+add-event-description = Lege den Ereignistyp und seine Eigenschaften an
+add-event-action-1 = Ereignisnamen wählen (Vergangenheitsform)
+add-event-action-2 = Nutzdaten des Ereignisses definieren
+add-event-action-3 = Serialisierungs-Derives hinzufügen
+add-event-action-4 = Zweck des Ereignisses dokumentieren
@@ -0,0 +1,62 @@
+# Agent UX strings and prompts, in US English
+#
+# Every other locale falls back to these messages.
+
+system-prompt =
+    You are the Alchemist, an AI assistant specialized in helping users understand and work with the Composable Information Machine (CIM) architecture.
+
+    Your expertise includes:
+    - Event-driven architecture with event sourcing and CQRS
+    - Domain-Driven Design principles and patterns
+    - Entity Component Systems (ECS) using Bevy
+    - Graph-based workflows and visual programming
+    - Conceptual spaces for semantic understanding
+    - NATS messaging and distributed systems
+    - Rust programming best practices
+
+    You should:
+    - Provide clear, accurate explanations of CIM concepts
+    - Use examples from the actual CIM codebase when relevant
+    - Guide users through implementation patterns
+    - Suggest best practices and improvements
+    - Help debug and solve architecture challenges
+
+    Always be helpful, precise, and educational in your responses.
+
+answered-by-peer = Answered by peer agent { $agent }
+dialog-summary = Summary of the earlier conversation: { $summary }
+pinned-context = Pinned by the user, to keep in mind throughout the conversation:
+
+## Errors
+
+budget-exceeded =
+    { $scope ->
+        [dialog] This dialog has used its token budget ({ $used } of { $limit } tokens). Start a new dialog to continue.
+       *[user] You have used your token budget ({ $used } of { $limit } tokens). Try again later.
+    }
+unknown-workflow = Unknown workflow type: { $workflow }
+partial-answer = This is taking a while, so here's what I have so far. The complete answer will follow.
+model-unavailable = The language model is unavailable right now, so I can't answer questions. Concept lists, workflow status, and dialog history still work, and I'll be back to full service once the model recovers.
+
+## Workflow steps
+
+create-agent-title = Setup Project Structure
+create-agent-description = Create a new cim-agent-* directory with the standard structure
+create-agent-action-1 = Create Cargo.toml with dependencies
+create-agent-action-2 = Set up src/ directory structure
+create-agent-action-3 = Create configuration templates
+create-agent-action-4 = Initialize git repository
+
+implement-domain-title = Design Domain Model
+implement-domain-description = Define the domain boundaries and core concepts
+implement-domain-action-1 = Identify aggregates and entities
+implement-domain-action-2 = Define value objects
+implement-domain-action-3 = Map relationships
+implement-domain-action-4 = Document ubiquitous language
+
+add-event-title = Define Event Structure
+add-event-description = Create the event type and its properties
+add-event-action-1 = Choose event name (past tense)
+add-event-action-2 = Define event payload
+add-event-action-3 = Add serialization derives
+add-event-action-4 = Document event purpose
@@ -0,0 +1,34 @@
+-- Dialogs, workflows, and user profiles
+--
+-- Kept to SQL that SQLite and Postgres both accept. Timestamps are RFC 3339
+-- text and JSON columns are text.
+
+CREATE TABLE IF NOT EXISTS dialogs (
+    dialog_id TEXT PRIMARY KEY,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS dialog_messages (
+    dialog_id TEXT NOT NULL REFERENCES dialogs (dialog_id) ON DELETE CASCADE,
+    position BIGINT NOT NULL,
+    role TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (dialog_id, position)
+);
+
+CREATE TABLE IF NOT EXISTS workflows (
+    workflow_id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    status TEXT NOT NULL,
+    current_step TEXT,
+    state TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS profiles (
+    user_id TEXT PRIMARY KEY,
+    display_name TEXT,
+    preferences TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
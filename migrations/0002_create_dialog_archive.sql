@@ -0,0 +1,11 @@
+-- Dialog turns taken out of a dialog's history, such as those a summary
+-- replaced by `compact_dialog`
+
+CREATE TABLE IF NOT EXISTS dialog_archive (
+    dialog_id TEXT NOT NULL,
+    position BIGINT NOT NULL,
+    role TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (dialog_id, position)
+);
@@ -0,0 +1,7 @@
+-- The knowledge graph, as one JSON document of concepts and relations
+
+CREATE TABLE IF NOT EXISTS knowledge_graph (
+    graph_id TEXT PRIMARY KEY,
+    graph TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
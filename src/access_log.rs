@@ -0,0 +1,217 @@
+//! Access logs of commands, queries, and dialog messages
+//!
+//! Each request handled becomes one JSON entry with its `kind`, `name`,
+//! `origin`, `dialog_id`, `status`, `error`, `duration_ms`, and `tokens`,
+//! appended to a file or published on a NATS subject, apart from the
+//! application logs. Only `sample_rate` of the requests are logged, except
+//! failed ones unless `always_log_errors` is off, and `fields` narrows the
+//! entries to the fields named. Payloads are never part of an entry.
+
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::config::{AccessLogConfig, AccessLogSink, NatsConfig};
+use crate::error::{AgentError, Result};
+
+/// Fields an entry can have
+const FIELDS: &[&str] = &[
+    "timestamp",
+    "kind",
+    "name",
+    "origin",
+    "dialog_id",
+    "status",
+    "error",
+    "duration_ms",
+    "tokens",
+];
+
+/// One request as logged
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessEntry {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+
+    /// `command`, `query`, or `dialog_message`
+    pub kind: &'static str,
+
+    /// Command or query type
+    pub name: String,
+
+    pub origin: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialog_id: Option<String>,
+
+    /// `ok` or `error`
+    pub status: &'static str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    pub duration_ms: u64,
+    pub tokens: usize,
+}
+
+impl AccessEntry {
+    pub fn new<T>(
+        kind: &'static str,
+        name: &str,
+        origin: &str,
+        duration: Duration,
+        tokens: usize,
+        result: &Result<T>,
+    ) -> Self {
+        Self {
+            timestamp: chrono::Utc::now(),
+            kind,
+            name: name.to_string(),
+            origin: origin.to_string(),
+            dialog_id: None,
+            status: if result.is_ok() { "ok" } else { "error" },
+            error: result.as_ref().err().map(|e| e.to_string()),
+            duration_ms: duration.as_millis() as u64,
+            tokens,
+        }
+    }
+
+    pub fn with_dialog(mut self, dialog_id: Option<&str>) -> Self {
+        self.dialog_id = dialog_id.map(str::to_string);
+        self
+    }
+}
+
+enum Sink {
+    File {
+        path: PathBuf,
+
+        /// Keeps concurrent requests from interleaving their lines
+        lock: tokio::sync::Mutex<()>,
+    },
+    Subject {
+        client: async_nats::Client,
+        subject: String,
+    },
+}
+
+/// Writes sampled entries to the configured sink
+pub struct AccessLog {
+    config: AccessLogConfig,
+    sink: Sink,
+}
+
+impl AccessLog {
+    /// Fails on a sample rate outside 0 to 1, an unknown field, or a NATS
+    /// subject sink without a connection
+    pub async fn open(config: &AccessLogConfig, nats: &NatsConfig) -> Result<Self> {
+        if !(0.0..=1.0).contains(&config.sample_rate) {
+            return Err(AgentError::Configuration(format!(
+                "Access log sample_rate must be between 0 and 1, not {}",
+                config.sample_rate
+            )));
+        }
+        if let Some(field) = config.fields.iter().find(|field| !FIELDS.contains(&field.as_str())) {
+            return Err(AgentError::Configuration(format!(
+                "Unknown access log field {}; expected one of {}",
+                field,
+                FIELDS.join(", ")
+            )));
+        }
+
+        let sink = match &config.sink {
+            AccessLogSink::File { path } => Sink::File {
+                path: path.clone(),
+                lock: tokio::sync::Mutex::new(()),
+            },
+            AccessLogSink::Subject { subject } => Sink::Subject {
+                client: crate::nats_integration::connect(nats).await?,
+                subject: subject.clone(),
+            },
+        };
+
+        Ok(Self {
+            config: config.clone(),
+            sink,
+        })
+    }
+
+    /// Log `entry` if it is sampled
+    ///
+    /// A failure to write is reported in the application logs rather than
+    /// failing the request.
+    pub async fn record(&self, entry: AccessEntry) {
+        let failed = entry.status == "error" && self.config.always_log_errors;
+        if !failed && !sampled(self.config.sample_rate) {
+            return;
+        }
+
+        if let Err(e) = self.write(&select(&entry, &self.config.fields)).await {
+            tracing::warn!("Failed to write access log entry: {}", e);
+        }
+    }
+
+    async fn write(&self, entry: &serde_json::Value) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        match &self.sink {
+            Sink::File { path, lock } => {
+                line.push(b'\n');
+
+                let _lock = lock.lock().await;
+                if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+                file.write_all(&line).await?;
+            }
+            Sink::Subject { client, subject } => {
+                client.publish(subject.clone(), line.into()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether to log a request when `rate` of them are
+fn sampled(rate: f64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    let draw = uuid::Uuid::new_v4().as_u128() % 1_000_000;
+    (draw as f64) < rate * 1_000_000.0
+}
+
+/// `entry` with only `fields`, or all of them when `fields` is empty
+fn select(entry: &AccessEntry, fields: &[String]) -> serde_json::Value {
+    let mut value = serde_json::json!(entry);
+    if let Some(object) = value.as_object_mut().filter(|_| !fields.is_empty()) {
+        object.retain(|name, _| fields.contains(name));
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_fields() {
+        let result: Result<()> = Err(AgentError::NotFound("Dialog d-1".to_string()));
+        let entry = AccessEntry::new("query", "get_dialog_history", "user-456", Duration::from_millis(12), 0, &result)
+            .with_dialog(Some("d-1"));
+
+        let all = select(&entry, &[]);
+        assert_eq!(all["status"], "error");
+        assert_eq!(all["dialog_id"], "d-1");
+        assert_eq!(all["duration_ms"], 12);
+
+        let some = select(&entry, &["name".to_string(), "status".to_string()]);
+        assert_eq!(some, serde_json::json!({"name": "get_dialog_history", "status": "error"}));
+    }
+
+    #[test]
+    fn test_sampling_extremes() {
+        assert!((0..100).all(|_| sampled(1.0)));
+        assert!((0..100).all(|_| !sampled(0.0)));
+    }
+}
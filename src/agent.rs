@@ -3,18 +3,59 @@
 //! This module implements the main agent logic that composes multiple CIM domains
 //! to provide intelligent assistance for understanding CIM architecture.
 
+use crate::access_log::{AccessEntry, AccessLog};
+use crate::artifacts::{self, ArtifactStore};
+use crate::backfill::{self, Persisted, ReplayReport};
+use crate::budget::{estimate_tokens, TokenBudgets};
+use crate::cache::Caches;
+#[cfg(feature = "chaos")]
+use crate::chaos::{FaultInjector, Faulty};
+use crate::codegen::{self, GeneratedFile};
+use crate::comparison;
+use crate::conceptual::ConceptSpace;
+use crate::config::BudgetAction;
+use crate::diagnose::{parse_diagnosis, ErrorClues};
 use crate::error::{AgentError, Result};
-use crate::model::{ModelProvider, Message as ModelMessage};
+use crate::evaluation::{self, Evaluation};
+use crate::events::{AgentEventKind, GraphEdit};
+use crate::glossary::{self, GlossaryEntry};
+use crate::guard::PromptGuard;
+use crate::import::{self, ImportFormat};
+use crate::knowledge::{parse_mutation, ConceptGraph, GraphMutation, GraphOperation};
+use crate::limits;
+use crate::locale::Localizer;
+use crate::logging;
+use crate::metrics::{DialogMetrics, RetrievalMetrics};
+use crate::model::{ModelProvider, ModelTurn, Message as ModelMessage, ToolExchange};
+use crate::nats_integration::{AgentEvent, HealthResponse, SubsystemHealth};
+use crate::page::{Page, DEFAULT_LIMIT};
+use crate::peers::{DelegatedAnswer, Peers};
+use crate::pins::{self, Pins};
+use crate::plan::{parse_plan, WorkflowPlan};
+use crate::priority::{Priority, PriorityLanes};
+use crate::quiz::{self, Difficulty, QuizQuestion, Quizzes};
+use crate::replay::{ReplayCase, ReplayRecorder};
+use crate::retrieval::{self, DocumentIndex};
+use crate::schemas::SchemaRegistry;
+use crate::sources::{retrieval_context, CodeIndex, SourceCitation};
+use crate::storage::{StoredWorkflow, Stores};
+use crate::tools::{Citation, ToolRegistry};
+use crate::topology::{self, Topology};
+use crate::usage::{metered, Metered, UsageKind, UsageLog};
+use futures::future::BoxFuture;
+use futures::StreamExt;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
+use tracing::Instrument;
 
 // Domain imports
 use cim_domain_agent::aggregate::Agent;
 use cim_domain_dialog::aggregate::{Dialog, DialogStatus};
 use cim_domain_dialog::value_objects::{Message, MessageContent, Turn, TurnType};
 use cim_domain_graph::aggregate::Graph;
-use cim_domain_conceptualspaces::ConceptualSpaceAggregate;
 use cim_domain_workflow::WorkflowStatus;
 
 /// The Alchemist agent - helps users understand and work with CIM
@@ -25,22 +66,275 @@ pub struct AlchemistAgent {
     /// Active dialogs
     dialogs: Arc<RwLock<HashMap<String, Dialog>>>,
     
+    /// Held while a dialog's message is answered, so its turns stay in
+    /// order while other dialogs go on
+    dialog_turns: std::sync::Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    
     /// Knowledge graph of CIM concepts
     knowledge_graph: Arc<RwLock<Graph>>,
     
-    /// Conceptual space for semantic understanding
-    conceptual_space: Arc<RwLock<ConceptualSpaceAggregate>>,
+    /// Concepts and their relations, editable from dialogs
+    concept_graph: RwLock<ConceptGraph>,
+    
+    /// Graph edits the model proposed, waiting for the user to confirm them
+    graph_edits: RwLock<HashMap<String, PendingGraphEdit>>,
+    
+    /// Quiz scores and open questions
+    quizzes: Quizzes,
+    
+    /// Turns and facts pinned to dialogs, kept in every prompt
+    pins: Pins,
     
-    /// Active workflows
+    /// Embeddings of the knowledge graph's concepts
+    conceptual_space: RwLock<ConceptSpace>,
+    
+    /// Running workflows; finished ones are dropped
     workflows: Arc<RwLock<HashMap<String, Workflow>>>,
     
-    /// AI model provider
-    model_provider: Box<dyn ModelProvider>,
+    /// Code indexed from configured sources
+    code_index: Arc<RwLock<CodeIndex>>,
+    
+    /// Embedded document chunks for retrieval into dialog prompts
+    documents: RwLock<DocumentIndex>,
+    
+    /// Domain event schemas, built when first asked for and again when the
+    /// code index changes
+    event_schemas: RwLock<Option<Arc<SchemaRegistry>>>,
+    
+    /// AI model provider, replaced when switching models; see [`Self::model`]
+    model_provider: RwLock<Arc<dyn ModelProvider>>,
+    
+    /// Events emitted as the agent's state changes
+    events: broadcast::Sender<AgentEvent>,
+    
+    /// Where dialogs outlive the process
+    stores: Stores,
+    
+    /// Where domain workflows' scaffolds are stored, when configured
+    artifacts: Option<Arc<dyn ArtifactStore>>,
+    
+    /// Responses, embeddings, and rate limits, possibly shared with other replicas
+    caches: Caches,
+    
+    /// Tools the model may call while answering dialog messages
+    tools: ToolRegistry,
+    
+    /// Other agents questions can be delegated to
+    peers: Arc<Peers>,
+    
+    /// Screens retrieved content before it reaches a prompt
+    guard: PromptGuard,
+    
+    /// Tokens used by each dialog and user
+    budgets: TokenBudgets,
+    
+    /// Requests and tokens per origin
+    usage: UsageLog,
+    
+    /// Slots for running requests, keeping some free for dialogs
+    lanes: PriorityLanes,
+    
+    /// Where dialog answers are recorded for replays, when they are
+    replays: Option<ReplayRecorder>,
+    
+    /// Where requests are logged for traffic analysis, when they are
+    access_log: Option<AccessLog>,
+    
+    /// How often retrieval finds excerpts and answers cite them
+    retrieval_metrics: RetrievalMetrics,
+    
+    /// Dialogs expired for inactivity
+    dialog_metrics: DialogMetrics,
+    
+    /// Prompts and user-facing messages in each locale
+    localizer: Localizer,
+    
+    /// Smaller model answering messages over budget, if configured
+    fallback_provider: Option<Box<dyn ModelProvider>>,
+    
+    /// Whether the model provider answered the last health check
+    model_healthy: AtomicBool,
+    
+    /// Whether the last health check found the model unreachable, so only
+    /// operations without the model are served
+    model_degraded: AtomicBool,
+    
+    /// Faults injected by `inject_faults`
+    #[cfg(feature = "chaos")]
+    faults: Arc<FaultInjector>,
+    
+    /// Last probe of the model provider and storage
+    subsystems: std::sync::Mutex<std::collections::BTreeMap<String, SubsystemHealth>>,
+    
+    /// When the agent was created, for uptime
+    started: std::time::Instant,
     
     /// Agent configuration
     config: crate::config::AgentConfig,
 }
 
+/// Events kept for slow subscribers before they start missing some
+const EVENT_CAPACITY: usize = 256;
+
+/// Concepts the agent explains
+pub(crate) const CIM_CONCEPTS: &[&str] = &[
+    "Event Sourcing",
+    "CQRS",
+    "Domain-Driven Design",
+    "Entity Component System",
+    "Conceptual Spaces",
+    "Graph Workflows",
+    "NATS Messaging",
+    "CID Chains",
+    "Aggregate",
+    "Value Object",
+    "Domain Event",
+    "Command Handler",
+    "Query Handler",
+    "Projection",
+    "Bounded Context",
+];
+
+/// A parameter of a command or query: name, JSON type, and whether it is required
+type Parameter = (&'static str, &'static str, bool);
+
+/// Commands `process_command` handles, with their parameters
+const COMMANDS: &[(&str, &[Parameter])] = &[
+    ("start_dialog", &[("user_id", "string", false), ("context", "object", false), ("metadata", "object", false)]),
+    ("explain_concept", &[("concept", "string", true)]),
+    ("visualize_architecture", &[("scope", "string", false)]),
+    (
+        "guide_workflow",
+        &[("workflow_type", "string", true), ("owner", "string", false), ("locale", "string", false), ("domain", "string", false)],
+    ),
+    (
+        "analyze_pattern",
+        &[("pattern_type", "string", false), ("code", "string", false), ("code_key", "string", false), ("focus", "string", false)],
+    ),
+    ("advance_workflow", &[("workflow_id", "string", true), ("to", "string", false)]),
+    ("complete_step", &[("workflow_id", "string", true), ("step", "string", true), ("output", "object", false), ("to", "string", false)]),
+    ("abort_workflow", &[("workflow_id", "string", true), ("reason", "string", false)]),
+    ("switch_model", &[("model", "string", true)]),
+    ("announce", &[("message", "string", true)]),
+    ("end_dialog", &[("dialog_id", "string", true)]),
+    ("import_dialog", &[("export", "object", true), ("format", "string", false)]),
+    ("compact_dialog", &[("dialog_id", "string", true), ("keep", "integer", false), ("locale", "string", false)]),
+    ("pin_context", &[("dialog_id", "string", true), ("turn", "integer", false), ("text", "string", false)]),
+    ("unpin", &[("dialog_id", "string", true), ("pin_id", "string", true)]),
+    ("explain_error", &[("error", "string", true), ("code", "string", false), ("code_key", "string", false)]),
+    ("generate_code", &[("description", "string", true), ("domain", "string", false)]),
+    ("propose_graph_edit", &[("request", "string", true), ("dialog_id", "string", false)]),
+    ("confirm_graph_edit", &[("proposal_id", "string", true), ("confirm", "boolean", false)]),
+    ("create_workflow_from_dialog", &[("dialog_id", "string", true), ("owner", "string", false)]),
+    ("batch", &[("commands", "array", true), ("continue_on_error", "boolean", false)]),
+    ("replay_stream", &[("from", "string", true), ("to", "string", false), ("apply", "boolean", false)]),
+    ("start_quiz", &[("user_id", "string", true), ("difficulty", "string", false)]),
+    ("answer_quiz", &[("quiz_id", "string", true), ("answer", "string", true), ("next", "boolean", false)]),
+    ("generate_glossary", &[("format", "string", false)]),
+    ("compare_models", &[("concept", "string", true), ("models", "array", false)]),
+];
+
+/// Parameters of every paginated query; see [`crate::page`]
+const PAGE_PARAMETERS: &[Parameter] = &[("cursor", "string", false), ("limit", "integer", false)];
+
+/// Queries `process_query` handles, with their parameters
+const QUERIES: &[(&str, &[Parameter])] = &[
+    ("list_concepts", PAGE_PARAMETERS),
+    ("find_similar_concepts", &[("concept", "string", true), ("top_k", "integer", false), ("threshold", "number", false)]),
+    ("get_dialog_history", &[("dialog_id", "string", true), ("cursor", "string", false), ("limit", "integer", false)]),
+    ("list_dialogs", PAGE_PARAMETERS),
+    ("list_pins", &[("dialog_id", "string", true)]),
+    ("suggest_follow_ups", &[("dialog_id", "string", true), ("count", "integer", false)]),
+    ("get_workflow_status", &[("workflow_id", "string", true)]),
+    ("list_workflows", PAGE_PARAMETERS),
+    ("list_models", PAGE_PARAMETERS),
+    (
+        "search_code",
+        &[("query", "string", true), ("limit", "integer", false), ("cursor", "string", false), ("repo", "string", false)],
+    ),
+    ("list_peers", PAGE_PARAMETERS),
+    ("get_capabilities", &[]),
+    ("get_usage_report", &[("from", "string", false), ("to", "string", false), ("origin", "string", false)]),
+    ("get_quiz_score", &[("user_id", "string", true)]),
+    ("describe_self", &[("prose", "boolean", false)]),
+    ("get_event_schema", &[("name", "string", true)]),
+    (
+        "list_event_schemas",
+        &[("filter", "string", false), ("cursor", "string", false), ("limit", "integer", false)],
+    ),
+];
+
+/// Cargo features that change what a deployment can do
+const FEATURES: &[(&str, bool)] = &[
+    ("bevy", cfg!(feature = "bevy")),
+    ("voice", cfg!(feature = "voice")),
+    ("slack", cfg!(feature = "slack")),
+    ("github", cfg!(feature = "github")),
+    ("webhooks", cfg!(feature = "webhooks")),
+    ("sql", cfg!(feature = "sql")),
+    ("redis", cfg!(feature = "redis")),
+    ("s3", cfg!(feature = "s3")),
+    ("oidc", cfg!(feature = "oidc")),
+    ("signing", cfg!(feature = "signing")),
+    ("email", cfg!(feature = "email")),
+    ("openapi", cfg!(feature = "openapi")),
+    ("chaos", cfg!(feature = "chaos")),
+];
+
+/// Commands and queries of the `chaos` feature
+#[cfg(feature = "chaos")]
+const CHAOS_COMMANDS: &[(&str, &[Parameter])] = &[
+    (
+        "inject_faults",
+        &[("drop_percent", "integer", false), ("model_latency_ms", "integer", false), ("model_error_percent", "integer", false)],
+    ),
+    ("clear_faults", &[]),
+];
+#[cfg(feature = "chaos")]
+const CHAOS_QUERIES: &[(&str, &[Parameter])] = &[("get_faults", &[])];
+
+/// Matches per page of `search_code` results unless a `limit` is given
+const SEARCH_LIMIT: usize = 5;
+
+/// Most commands in one `batch`
+const MAX_BATCH: usize = 100;
+
+/// Recent turns `compact_dialog` keeps verbatim unless a `keep` is given
+const COMPACT_KEEP: usize = 6;
+
+/// Commands and queries that need the model, refused while degraded
+const MODEL_OPERATIONS: &[&str] = &[
+    "explain_concept",
+    "find_similar_concepts",
+    "visualize_architecture",
+    "analyze_pattern",
+    "explain_error",
+    "generate_code",
+    "propose_graph_edit",
+    "create_workflow_from_dialog",
+    "compact_dialog",
+    "suggest_follow_ups",
+    "start_quiz",
+    "answer_quiz",
+    "generate_glossary",
+    "compare_models",
+];
+
+/// Prompt timed by model health checks
+const MODEL_PROBE: &str = "Reply with the single word OK.";
+
+/// Questions quoted per dialog in an activity summary
+const SUMMARY_QUESTIONS: usize = 3;
+
+/// Indexed excerpts retrieved per concept for a prompt
+const RETRIEVED_EXCERPTS: usize = 3;
+
+/// Most characters of retrieved excerpts added to a prompt
+const RETRIEVED_CHARS: usize = 6000;
+
+/// Most event schemas added to a prompt
+const MENTIONED_SCHEMAS: usize = 3;
+
 /// Capabilities of the Alchemist agent
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct AlchemistCapabilities {
@@ -85,146 +379,1542 @@ impl AlchemistAgent {
         agent.add_component(metadata).ok();
         
         // Add capabilities component
-        let capabilities = cim_domain_agent::CapabilitiesComponent::new(vec![
-            "explain_concepts".to_string(),
-            "visualize_architecture".to_string(),
-            "guide_workflows".to_string(),
-            "analyze_patterns".to_string(),
-            "suggest_improvements".to_string(),
-        ]);
+        let enabled = &config.capabilities;
+        let capabilities = cim_domain_agent::CapabilitiesComponent::new(
+            [
+                ("explain_concepts", enabled.explain_concepts),
+                ("visualize_architecture", enabled.visualize_architecture),
+                ("guide_workflows", enabled.guide_workflows),
+                ("analyze_patterns", enabled.analyze_patterns),
+                ("suggest_improvements", enabled.suggest_improvements),
+            ]
+            .into_iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(capability, _)| capability.to_string())
+            .collect(),
+        );
         agent.add_component(capabilities).ok();
         
+        let stores = crate::storage::open(&config.storage, &config.nats).await?;
+        let concept_graph = match stores.graph.load_graph().await? {
+            Some(graph) => graph,
+            None => ConceptGraph::seeded(),
+        };
+        let workflows = if config.domains.workflow.persist {
+            restore_workflows(&stores).await?
+        } else {
+            HashMap::new()
+        };
+        let caches = crate::cache::open(&config.cache).await?;
+        let artifacts = match &config.storage.artifacts {
+            Some(backend) => Some(artifacts::open(backend, &config.nats).await?),
+            None => None,
+        };
+        let access_log = match &config.service.logging.access {
+            Some(access) => Some(AccessLog::open(access, &config.nats).await?),
+            None => None,
+        };
+        
+        let fallback_provider = match &config.budgets.on_exceeded {
+            BudgetAction::Downgrade { model } => {
+                let mut model_config = config.model.clone();
+                model_config.set_model(model);
+                let provider: Box<dyn ModelProvider> = Box::new(Metered::new(crate::model::create_provider(&model_config)?));
+                Some(provider)
+            }
+            BudgetAction::Refuse => None,
+        };
+        
+        #[cfg(feature = "chaos")]
+        let faults = Arc::new(FaultInjector::default());
+        #[cfg(feature = "chaos")]
+        let model_provider: Box<dyn ModelProvider> = Box::new(Faulty::new(model_provider, faults.clone()));
+        
         Ok(Self {
             agent,
             dialogs: Arc::new(RwLock::new(HashMap::new())),
+            dialog_turns: std::sync::Mutex::new(HashMap::new()),
             knowledge_graph: Arc::new(RwLock::new(Graph::new(
                 cim_domain_graph::GraphId::new(),
                 "CIM Knowledge Graph".to_string(),
                 "Knowledge graph of CIM concepts and relationships".to_string(),
             ))),
-            conceptual_space: Arc::new(RwLock::new(ConceptualSpaceAggregate::new(
-                "CIM Conceptual Space".to_string(),
-                vec![], // No dimensions initially
-                cim_domain_conceptualspaces::ConceptualMetric::default(),
-            ))),
-            workflows: Arc::new(RwLock::new(HashMap::new())),
-            model_provider,
+            conceptual_space: RwLock::new(ConceptSpace::default()),
+            concept_graph: RwLock::new(concept_graph),
+            graph_edits: RwLock::new(HashMap::new()),
+            quizzes: Quizzes::default(),
+            pins: Pins::default(),
+            workflows: Arc::new(RwLock::new(workflows)),
+            code_index: Arc::new(RwLock::new(CodeIndex::default())),
+            documents: RwLock::new(DocumentIndex::default()),
+            event_schemas: RwLock::new(None),
+            model_provider: RwLock::new(Arc::new(Metered::new(model_provider))),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            stores,
+            artifacts,
+            caches,
+            tools: ToolRegistry::from_config(&config.tools)?,
+            peers: Arc::new(Peers::new(&config)),
+            guard: PromptGuard::new(&config.prompt_guard),
+            budgets: TokenBudgets::new(&config.budgets),
+            usage: UsageLog::new(&config.usage),
+            lanes: PriorityLanes::new(&config.priority),
+            replays: config.replay.record.then(|| ReplayRecorder::new(&config.replay.path)),
+            access_log,
+            retrieval_metrics: RetrievalMetrics::default(),
+            dialog_metrics: DialogMetrics::default(),
+            localizer: Localizer::new(&config.localization)?,
+            fallback_provider,
+            model_healthy: AtomicBool::new(false),
+            model_degraded: AtomicBool::new(false),
+            #[cfg(feature = "chaos")]
+            faults,
+            subsystems: std::sync::Mutex::new(
+                [("model", SubsystemHealth::unknown()), ("storage", SubsystemHealth::unknown())]
+                    .into_iter()
+                    .map(|(name, health)| (name.to_string(), health))
+                    .collect(),
+            ),
+            started: std::time::Instant::now(),
             config,
         })
     }
     
-    /// Get agent capabilities
+    /// Get agent capabilities, as configured
     pub fn capabilities(&self) -> AlchemistCapabilities {
+        let enabled = &self.config.capabilities;
         AlchemistCapabilities {
-            explain_concepts: true,
-            visualize_architecture: true,
-            guide_workflows: true,
-            analyze_patterns: true,
-            suggest_improvements: true,
+            explain_concepts: enabled.explain_concepts,
+            visualize_architecture: enabled.visualize_architecture,
+            guide_workflows: enabled.guide_workflows,
+            analyze_patterns: enabled.analyze_patterns,
+            suggest_improvements: enabled.suggest_improvements,
+        }
+    }
+    
+    /// The disabled capability `command_type` belongs to, if any
+    fn disabled_capability(&self, command_type: &str) -> Option<&'static str> {
+        let capabilities = self.capabilities();
+        let (capability, enabled) = match command_type {
+            "explain_concept" | "explain_error" | "generate_glossary" | "compare_models" => {
+                ("explain_concepts", capabilities.explain_concepts)
+            }
+            "visualize_architecture" => ("visualize_architecture", capabilities.visualize_architecture),
+            "guide_workflow" | "advance_workflow" | "complete_step" | "abort_workflow" | "create_workflow_from_dialog" => {
+                ("guide_workflows", capabilities.guide_workflows)
+            }
+            "analyze_pattern" => ("analyze_patterns", capabilities.analyze_patterns),
+            "generate_code" => ("suggest_improvements", capabilities.suggest_improvements),
+            _ => return None,
+        };
+        (!enabled).then_some(capability)
+    }
+    
+    /// Index that sources feed code into
+    pub fn code_index(&self) -> Arc<RwLock<CodeIndex>> {
+        self.code_index.clone()
+    }
+    
+    /// Embed the configured documents that changed since they were last
+    /// indexed for retrieval, returning how many chunks were embedded
+    pub async fn index_documents(&self) -> Result<usize> {
+        let config = &self.config.retrieval;
+        let directories = config.directories.clone();
+        let extensions = config.extensions.clone();
+        let mut documents = tokio::task::spawn_blocking(move || retrieval::collect_directories(&directories, &extensions))
+            .await
+            .map_err(|e| AgentError::Internal(format!("Indexing task failed: {}", e)))?;
+        if config.include_sources {
+            documents.extend(self.code_index.read().await.files());
+        }
+        
+        let provider = self.model().await;
+        let model = embedding_key(provider.as_ref());
+        let embedded = retrieval::refresh(&self.documents, provider.as_ref(), &model, documents, config.chunk_size).await?;
+        
+        if embedded > 0 {
+            let chunks = self.documents.read().await.len();
+            self.emit(AgentEventKind::KnowledgeUpdated {
+                source: "retrieval".to_string(),
+                embedded: Some(embedded),
+                chunks: Some(chunks),
+                repo: None,
+                files: None,
+            });
+        }
+        Ok(embedded)
+    }
+    
+    /// Schemas of the domain events declared in the code sources and the
+    /// configured crates, rebuilt whenever the code index has changed
+    async fn event_schemas(&self) -> Result<Arc<SchemaRegistry>> {
+        let (version, files) = {
+            let index = self.code_index.read().await;
+            let version = index.version();
+            if let Some(registry) = &*self.event_schemas.read().await {
+                if registry.version() == version {
+                    return Ok(registry.clone());
+                }
+            }
+            (version, index.files())
+        };
+        
+        let crates = self.config.event_schemas.crates.clone();
+        let registry = tokio::task::spawn_blocking(move || {
+            let mut files = files;
+            files.extend(retrieval::collect_directories(&crates, &["rs".to_string(), "md".to_string()]));
+            SchemaRegistry::build(version, &files)
+        })
+        .await
+        .map_err(|e| AgentError::Internal(format!("Schema indexing task failed: {}", e)))?;
+        
+        let registry = Arc::new(registry);
+        *self.event_schemas.write().await = Some(registry.clone());
+        Ok(registry)
+    }
+    
+    /// The concept graph as it is now
+    pub async fn concept_graph(&self) -> ConceptGraph {
+        self.concept_graph.read().await.clone()
+    }
+    
+    /// Add a concept to the graph
+    pub async fn add_concept(&self, name: &str, description: &str) -> Result<()> {
+        self.edit_concept_graph(&[GraphOperation::AddConcept {
+            name: name.to_string(),
+            description: description.to_string(),
+        }])
+        .await
+    }
+    
+    /// Remove a concept and its relations from the graph
+    pub async fn remove_concept(&self, name: &str) -> Result<()> {
+        self.edit_concept_graph(&[GraphOperation::RemoveConcept { name: name.to_string() }])
+            .await
+    }
+    
+    /// Apply `operations` to the concept graph, all of them or none
+    pub async fn edit_concept_graph(&self, operations: &[GraphOperation]) -> Result<()> {
+        self.apply_graph_operations(operations, None, None).await
+    }
+    
+    /// Apply `operations` and save the graph, publishing an event per
+    /// operation naming the proposal and dialog it came from, if any
+    ///
+    /// Nothing changes unless every operation applies and the graph is saved.
+    async fn apply_graph_operations(
+        &self,
+        operations: &[GraphOperation],
+        proposal_id: Option<&str>,
+        dialog_id: Option<&str>,
+    ) -> Result<()> {
+        let mut graph = self.concept_graph.write().await;
+        let mut edited = graph.clone();
+        for operation in operations {
+            edited.apply(operation)?;
+        }
+        self.stores.graph.save_graph(&edited).await?;
+        *graph = edited;
+        drop(graph);
+        
+        for operation in operations {
+            self.emit(AgentEventKind::graph_edit(GraphEdit {
+                operation: operation.clone(),
+                proposal_id: proposal_id.map(str::to_string),
+                dialog_id: dialog_id.map(str::to_string),
+            }));
+        }
+        Ok(())
+    }
+    
+    /// Receive events emitted from now on, such as `workflow_completed`
+    pub fn subscribe_events(&self) -> broadcast::Receiver<AgentEvent> {
+        self.events.subscribe()
+    }
+    
+    /// Storage the agent persists its state to
+    pub fn stores(&self) -> &Stores {
+        &self.stores
+    }
+    
+    /// Caches shared by everything the agent runs
+    pub fn caches(&self) -> &Caches {
+        &self.caches
+    }
+    
+    /// The current model provider
+    ///
+    /// Cloned out of its lock, so a generation holds no lock while it runs
+    /// and `switch_model` waits for none of them.
+    async fn model(&self) -> Arc<dyn ModelProvider> {
+        self.model_provider.read().await.clone()
+    }
+    
+    /// Check the model provider, remembering the result for `health`
+    ///
+    /// With `service.probe_model`, a short generation is timed as well, so
+    /// a provider that answers but cannot generate counts as degraded.
+    pub async fn check_model_health(&self) -> bool {
+        let provider = self.model().await;
+        let started = std::time::Instant::now();
+        let mut checked = provider.health_check().await;
+        if checked.is_ok() && self.config.service.probe_model {
+            let probe = tokio::time::timeout(self.config.service.health_check_interval, provider.generate(MODEL_PROBE));
+            checked = match probe.await {
+                Ok(generated) => generated.map(|_| ()),
+                Err(_) => Err(AgentError::Timeout("The probe generation timed out".to_string())),
+            };
+        }
+        drop(provider);
+        
+        let health = match checked {
+            Ok(()) => SubsystemHealth::healthy(started.elapsed()),
+            Err(e) => {
+                tracing::warn!("Model provider health check failed: {}", e);
+                SubsystemHealth::degraded(e.to_string())
+            }
+        };
+        let healthy = !health.is_degraded();
+        self.model_healthy.store(healthy, Ordering::Relaxed);
+        let reason = health.reason.clone();
+        self.subsystems.lock().unwrap().insert("model".to_string(), health);
+        
+        // Announce entering and leaving degraded mode, not every check
+        let was_degraded = self.model_degraded.swap(!healthy, Ordering::Relaxed);
+        if was_degraded != !healthy {
+            if healthy {
+                tracing::info!("Model provider recovered; leaving degraded mode");
+                self.emit(AgentEventKind::ModelRecovered {});
+            } else {
+                tracing::warn!("Model provider unreachable; serving without the model");
+                self.emit(AgentEventKind::ModelUnavailable { reason });
+            }
+        }
+        healthy
+    }
+    
+    /// Check that stored dialogs can be listed, remembering the result for
+    /// `health`
+    pub async fn check_storage_health(&self) -> bool {
+        let started = std::time::Instant::now();
+        let health = match self.stores.dialogs.list_dialogs().await {
+            Ok(_) => SubsystemHealth::healthy(started.elapsed()),
+            Err(e) => {
+                tracing::warn!("Storage health check failed: {}", e);
+                SubsystemHealth::degraded(format!("Dialog store: {}", e))
+            }
+        };
+        let healthy = !health.is_degraded();
+        self.subsystems.lock().unwrap().insert("storage".to_string(), health);
+        healthy
+    }
+    
+    /// Whether the model provider answered the last health check
+    pub fn model_healthy(&self) -> bool {
+        self.model_healthy.load(Ordering::Relaxed)
+    }
+    
+    /// Whether the agent is serving without its model, until a health
+    /// check finds it again
+    pub fn model_degraded(&self) -> bool {
+        self.model_degraded.load(Ordering::Relaxed)
+    }
+    
+    /// Refuse `operation` quickly while degraded if it needs the model
+    fn require_model(&self, operation: &str, locale: Option<&str>) -> Result<()> {
+        if self.model_degraded() && MODEL_OPERATIONS.contains(&operation) {
+            return Err(AgentError::ServiceUnavailable(self.localizer.text(locale, "model-unavailable", &[])));
+        }
+        Ok(())
+    }
+    
+    /// Current health, as answered on the health subject and `/readyz`
+    pub async fn health(&self) -> HealthResponse {
+        let model_healthy = self.model_healthy();
+        
+        let mut health = HealthResponse {
+            status: if model_healthy { "Running" } else { "Degraded" }.to_string(),
+            version: crate::VERSION.to_string(),
+            uptime_seconds: self.started.elapsed().as_secs(),
+            model_status: if model_healthy { "healthy" } else { "unhealthy" }.to_string(),
+            active_dialogs: self.dialogs.read().await.len(),
+            subsystems: Default::default(),
+            metadata: serde_json::json!({
+                "agent_name": self.config.identity.name,
+                "model": self.model().await.model_info().model,
+                "capabilities": self.capabilities(),
+            }),
+        };
+        
+        let subsystems = self.subsystems.lock().unwrap().clone();
+        for (name, subsystem) in subsystems {
+            health.add_subsystem(&name, subsystem);
+        }
+        health
+    }
+    
+    /// Configuration the agent was created with
+    pub fn config(&self) -> &crate::config::AgentConfig {
+        &self.config
+    }
+    
+    /// Peer agents this agent has discovered
+    pub fn peers(&self) -> Arc<Peers> {
+        self.peers.clone()
+    }
+    
+    /// Sender for components that emit events on the agent's behalf
+    pub fn event_sender(&self) -> broadcast::Sender<AgentEvent> {
+        self.events.clone()
+    }
+    
+    fn emit(&self, event: AgentEventKind) {
+        // Nobody listening is not an error
+        let _ = self.events.send(AgentEvent::new(event));
+    }
+    
+    /// Names and contents of a dialog message's attachments, each given
+    /// inline as `content` within `limits.max_attachment_bytes` or as the
+    /// `key` of an artifact
+    async fn attachments(&self, metadata: &serde_json::Value) -> Result<Vec<(String, String)>> {
+        let mut attachments = Vec::new();
+        for attachment in metadata["attachments"].as_array().into_iter().flatten() {
+            let name = attachment["name"].as_str().unwrap_or("attachment").to_string();
+            let content = match attachment["key"].as_str() {
+                Some(key) => self.referenced_artifact(key).await?,
+                None => {
+                    let content = attachment["content"].as_str().unwrap_or_default();
+                    let limit = self.config.limits.max_attachment_bytes;
+                    limits::check(&format!("attachment {}", name), content.len(), limit)?;
+                    content.to_string()
+                }
+            };
+            attachments.push((name, content));
+        }
+        Ok(attachments)
+    }
+    
+    /// Code given inline as `code` within `limits.max_code_bytes`, or as the
+    /// `code_key` of an artifact
+    async fn code_parameter(&self, payload: &serde_json::Value) -> Result<Option<String>> {
+        if let Some(key) = payload["code_key"].as_str() {
+            return self.referenced_artifact(key).await.map(Some);
+        }
+        let Some(code) = payload["code"].as_str() else {
+            return Ok(None);
+        };
+        limits::check("code", code.len(), self.config.limits.max_code_bytes)?;
+        Ok(Some(code.to_string()))
+    }
+    
+    /// Text a caller stored under `key` in the artifact store, for content
+    /// too large to send inline
+    async fn referenced_artifact(&self, key: &str) -> Result<String> {
+        let store = self.artifacts.as_ref().ok_or_else(|| {
+            AgentError::Configuration("Content by reference needs storage.artifacts".to_string())
+        })?;
+        let data = store
+            .get(key)
+            .await?
+            .ok_or_else(|| AgentError::NotFound(format!("Artifact {}", key)))?;
+        String::from_utf8(data).map_err(|_| AgentError::InvalidRequest(format!("Artifact {} is not text", key)))
+    }
+    
+    /// Screen `text` from `source` for prompt injection before it goes into
+    /// a prompt, recording any attempt in the audit log
+    fn screen(&self, source: &str, text: String) -> String {
+        let (text, findings) = self.guard.sanitize(source, text);
+        if !findings.is_empty() {
+            tracing::warn!(
+                target: "audit",
+                source,
+                findings = findings.len(),
+                "Possible prompt injection in retrieved content"
+            );
+            self.emit(AgentEventKind::PromptInjectionDetected {
+                source: source.to_string(),
+                action: format!("{:?}", self.guard.action()),
+                findings: serde_json::json!(findings),
+            });
         }
+        text
     }
     
     /// Process a generic command
     pub async fn process_command(&self, command_type: &str, payload: serde_json::Value) -> Result<serde_json::Value> {
-        match command_type {
+        let request = self
+            .config
+            .evaluation
+            .applies_to(command_type)
+            .then(|| format!("{} {}", command_type, payload));
+        if let Some(capability) = self.disabled_capability(command_type) {
+            return Err(AgentError::PermissionDenied(format!(
+                "{} is unavailable: the {} capability is disabled in this deployment",
+                command_type, capability
+            )));
+        }
+        self.require_model(command_type, payload["locale"].as_str())?;
+        
+        let mut result = match command_type {
+            "start_dialog" => self.start_dialog(payload).await,
             "explain_concept" => self.explain_concept(payload).await,
             "visualize_architecture" => self.visualize_architecture(payload).await,
             "guide_workflow" => self.guide_workflow(payload).await,
             "analyze_pattern" => self.analyze_pattern(payload).await,
+            "advance_workflow" => self.advance_workflow(payload).await,
+            "complete_step" => self.complete_step(payload).await,
+            "abort_workflow" => self.abort_workflow(payload).await,
+            "switch_model" => self.switch_model(payload).await,
+            "announce" => self.announce(payload).await,
+            "end_dialog" => self.end_dialog(payload).await,
+            "import_dialog" => self.import_dialog(payload).await,
+            "compact_dialog" => self.compact_dialog(payload).await,
+            "pin_context" => self.pin_context(payload).await,
+            "unpin" => self.unpin(payload),
+            "explain_error" => self.explain_error(payload).await,
+            "generate_code" => self.generate_code(payload).await,
+            "propose_graph_edit" => self.propose_graph_edit(payload).await,
+            "confirm_graph_edit" => self.confirm_graph_edit(payload).await,
+            "create_workflow_from_dialog" => self.create_workflow_from_dialog(payload).await,
+            "batch" => self.run_batch(payload).await,
+            "replay_stream" => self.replay_stream(payload).await,
+            "start_quiz" => self.start_quiz(payload).await,
+            "answer_quiz" => self.answer_quiz(payload).await,
+            "generate_glossary" => self.generate_glossary(payload).await,
+            "compare_models" => self.compare_models(payload).await,
+            #[cfg(feature = "chaos")]
+            "inject_faults" => self.inject_faults(payload),
+            #[cfg(feature = "chaos")]
+            "clear_faults" => self.clear_faults(),
             _ => Err(AgentError::InvalidRequest(format!("Unknown command: {}", command_type))),
+        }?;
+        
+        // Only results with a textual answer are reviewed
+        if let Some((request, (answer, sources))) = request.zip(evaluation::answer_and_sources(&result)) {
+            if let Some(evaluation) = self.evaluate(&request, &answer, &sources).await {
+                result["evaluation"] = serde_json::json!(evaluation);
+            }
+        }
+        
+        Ok(result)
+    }
+    
+    /// Run the commands of a batch in order
+    ///
+    /// A failed command stops the batch, skipping the rest, unless it or the
+    /// batch sets `continue_on_error`.
+    async fn run_batch(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let commands = payload["commands"]
+            .as_array()
+            .ok_or_else(|| AgentError::Configuration("Missing commands parameter".to_string()))?;
+        if commands.len() > MAX_BATCH {
+            return Err(AgentError::InvalidRequest(format!(
+                "A batch holds at most {} commands, not {}",
+                MAX_BATCH,
+                commands.len()
+            )));
+        }
+        let continue_on_error = payload["continue_on_error"].as_bool().unwrap_or(false);
+        
+        let mut results = Vec::with_capacity(commands.len());
+        let (mut succeeded, mut failed, mut skipped) = (0, 0, 0);
+        let mut stopped = false;
+        for (index, item) in commands.iter().enumerate() {
+            let command_type = item["command_type"].as_str().unwrap_or_default();
+            if stopped {
+                skipped += 1;
+                results.push(serde_json::json!({
+                    "index": index,
+                    "command_type": command_type,
+                    "status": "skipped",
+                }));
+                continue;
+            }
+            
+            let outcome = match command_type {
+                "" => Err(AgentError::Configuration("Missing command_type parameter".to_string())),
+                "batch" => Err(AgentError::InvalidRequest("Batches cannot be nested".to_string())),
+                _ => {
+                    let command: BoxFuture<'_, Result<serde_json::Value>> =
+                        Box::pin(self.process_command(command_type, item["payload"].clone()));
+                    command.await
+                }
+            };
+            
+            match outcome {
+                Ok(result) => {
+                    succeeded += 1;
+                    results.push(serde_json::json!({
+                        "index": index,
+                        "command_type": command_type,
+                        "status": "succeeded",
+                        "result": result,
+                    }));
+                }
+                Err(e) => {
+                    failed += 1;
+                    results.push(serde_json::json!({
+                        "index": index,
+                        "command_type": command_type,
+                        "status": "failed",
+                        "error": e.to_string(),
+                    }));
+                    stopped = !item["continue_on_error"].as_bool().unwrap_or(continue_on_error);
+                }
+            }
+        }
+        
+        Ok(serde_json::json!({
+            "results": results,
+            "succeeded": succeeded,
+            "failed": failed,
+            "skipped": skipped,
+        }))
+    }
+    
+    /// Replay the commands and dialog messages JetStream kept between the
+    /// RFC 3339 times `from` and `to` (now by default)
+    ///
+    /// Without `apply` this only reports what would be replayed. With it,
+    /// each message runs again in order; one failing again is reported and
+    /// the replay carries on. See [`crate::backfill`].
+    async fn replay_stream(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let from = time_parameter(&payload, "from")?
+            .ok_or_else(|| AgentError::Configuration("Missing from parameter".to_string()))?;
+        let to = time_parameter(&payload, "to")?.unwrap_or_else(chrono::Utc::now);
+        if to <= from {
+            return Err(AgentError::InvalidRequest(format!("Nothing to replay between {} and {}", from, to)));
+        }
+        let apply = payload["apply"].as_bool().unwrap_or(false);
+        
+        let mut range = backfill::read(&self.config.nats, from, to).await?;
+        range.drop_unverified(crate::nats_integration::command_check(&self.config)?);
+        let mut report = ReplayReport::plan(from, to, &range);
+        if !apply {
+            return Ok(serde_json::to_value(report)?);
+        }
+        
+        tracing::warn!(target: "audit", "Replaying {} persisted messages from {} to {}", range.entries.len(), from, to);
+        report.applied = true;
+        for entry in &range.entries {
+            let Some(replayable) = entry.entry.replayable() else {
+                continue;
+            };
+            // Run directly, like batches, so a replay holds one slot at most
+            let outcome = match replayable {
+                Persisted::Command(command) => {
+                    let replayed: BoxFuture<'_, Result<serde_json::Value>> =
+                        Box::pin(self.process_command(&command.command_type, command.payload));
+                    replayed.await.map(|_| ())
+                }
+                Persisted::Dialog(message) => self.answer_dialog_message(message.into(), |_| {}).await.map(|_| ()),
+            };
+            report.record(entry, outcome);
+        }
+        tracing::info!(
+            "Replayed {} persisted messages from {} to {}, {} failing again",
+            report.replayed,
+            from,
+            to,
+            report.failures.len()
+        );
+        
+        Ok(serde_json::to_value(report)?)
+    }
+    
+    /// Process a command on behalf of `origin`, counting it in usage reports
+    pub async fn process_command_from(
+        &self,
+        origin: &str,
+        command_type: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        self.process_command_in(Priority::for_command(command_type), origin, command_type, payload)
+            .await
+    }
+    
+    /// Process a command once a slot in `priority`'s lane is free
+    async fn process_command_in(
+        &self,
+        priority: Priority,
+        origin: &str,
+        command_type: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let span = logging::request_span(&self.config.service.logging, "command", command_type, origin, &payload);
+        let started = std::time::Instant::now();
+        let dialog_id = payload["dialog_id"].as_str().map(str::to_string);
+        
+        let (result, tokens) = async {
+            let _slot = self.lanes.acquire(priority).await;
+            metered(self.process_command(command_type, payload)).await
+        }
+        .instrument(span.clone())
+        .await;
+        
+        logging::finish(&span, started, tokens, &result);
+        self.log_access(
+            AccessEntry::new("command", command_type, origin, started.elapsed(), tokens, &result)
+                .with_dialog(dialog_id.as_deref()),
+        )
+        .await;
+        self.usage.record(origin, UsageKind::Command, command_type, tokens, result.is_ok());
+        result
+    }
+    
+    /// Process a command on behalf of `origin` at most once per `idempotency_key`
+    ///
+    /// Retries with the same key get the first successful result back for
+    /// `cache.idempotency_ttl`, so redelivered commands do not start a second
+    /// dialog or workflow. Failed commands are not remembered and run again.
+    /// Without a `priority`, the command runs in its usual lane.
+    pub async fn process_idempotent_command(
+        &self,
+        origin: &str,
+        idempotency_key: Option<&str>,
+        priority: Option<Priority>,
+        command_type: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let priority = priority.unwrap_or_else(|| Priority::for_command(command_type));
+        let Some(idempotency_key) = idempotency_key else {
+            return self.process_command_in(priority, origin, command_type, payload).await;
+        };
+        
+        if let Some(result) = self.caches.command_result(origin, idempotency_key).await {
+            tracing::debug!("Returning the earlier result of {} for key {}", command_type, idempotency_key);
+            return Ok(result);
+        }
+        
+        let result = self.process_command_in(priority, origin, command_type, payload).await?;
+        self.caches.store_command_result(origin, idempotency_key, &result).await;
+        Ok(result)
+    }
+    
+    /// Process a query on behalf of `origin`, counting it in usage reports
+    pub async fn process_query_from(
+        &self,
+        origin: &str,
+        query_type: &str,
+        parameters: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let span = logging::request_span(&self.config.service.logging, "query", query_type, origin, &parameters);
+        let started = std::time::Instant::now();
+        let dialog_id = parameters["dialog_id"].as_str().map(str::to_string);
+        
+        let (result, tokens) = metered(self.process_query(query_type, parameters))
+            .instrument(span.clone())
+            .await;
+        
+        logging::finish(&span, started, tokens, &result);
+        self.log_access(
+            AccessEntry::new("query", query_type, origin, started.elapsed(), tokens, &result)
+                .with_dialog(dialog_id.as_deref()),
+        )
+        .await;
+        self.usage.record(origin, UsageKind::Query, query_type, tokens, result.is_ok());
+        result
+    }
+    
+    /// Add a request to the access log, when one is kept
+    async fn log_access(&self, entry: AccessEntry) {
+        if let Some(access_log) = &self.access_log {
+            access_log.record(entry).await;
         }
     }
     
     /// Process a generic query
     pub async fn process_query(&self, query_type: &str, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        self.require_model(query_type, parameters["locale"].as_str())?;
+        
         match query_type {
             "list_concepts" => self.list_concepts(parameters).await,
             "find_similar_concepts" => self.find_similar_concepts(parameters).await,
             "get_dialog_history" => self.get_dialog_history(parameters).await,
+            "list_dialogs" => self.list_dialogs(parameters).await,
+            "list_pins" => self.list_pins(parameters),
+            "suggest_follow_ups" => self.suggest_follow_ups(parameters).await,
             "get_workflow_status" => self.get_workflow_status(parameters).await,
+            "list_workflows" => self.list_workflows(parameters).await,
+            "list_models" => self.list_models(parameters).await,
+            "search_code" => self.search_code(parameters).await,
+            "list_peers" => self.list_peers(parameters).await,
+            "get_capabilities" => self.get_capabilities(parameters).await,
+            "get_quiz_score" => self.get_quiz_score(parameters),
+            "describe_self" => self.describe_self(parameters).await,
+            "get_usage_report" => self.get_usage_report(parameters).await,
+            "get_event_schema" => self.get_event_schema(parameters).await,
+            "list_event_schemas" => self.list_event_schemas(parameters).await,
+            #[cfg(feature = "chaos")]
+            "get_faults" => Ok(serde_json::json!(self.faults.faults())),
             _ => Err(AgentError::InvalidRequest(format!("Unknown query: {}", query_type))),
         }
     }
     
     /// Process a dialog message
     pub async fn process_dialog_message(&self, message: DialogMessage) -> Result<String> {
-        // Get or create dialog
-        let mut dialogs = self.dialogs.write().await;
-        let dialog = dialogs
-            .entry(message.dialog_id.clone())
-            .or_insert_with(|| {
-                let participant = cim_domain_dialog::Participant {
-                    id: uuid::Uuid::new_v4(),
-                    name: "User".to_string(),
-                    participant_type: cim_domain_dialog::ParticipantType::Human,
-                    role: cim_domain_dialog::ParticipantRole::Primary,
-                    metadata: HashMap::new(),
-                };
-                Dialog::new(
-                    uuid::Uuid::new_v4(),
-                    cim_domain_dialog::DialogType::Direct,
-                    participant,
-                )
+        self.process_dialog_message_streaming(message, |_| {}).await
+    }
+
+    /// Process a dialog message, passing each piece of the response to
+    /// `on_chunk` as the model generates it
+    ///
+    /// Returns the complete response once generation finishes.
+    pub async fn process_dialog_message_streaming<F>(
+        &self,
+        message: DialogMessage,
+        on_chunk: F,
+    ) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        Ok(self.reply_to_dialog_message(message, on_chunk).await?.content)
+    }
+    
+    /// Process a dialog message like `process_dialog_message_streaming`,
+    /// also returning the sources the tools consulted
+    ///
+    /// The message counts in usage reports under `metadata.origin`,
+    /// `metadata.source`, or `metadata.user`.
+    pub async fn reply_to_dialog_message<F>(
+        &self,
+        message: DialogMessage,
+        on_chunk: F,
+    ) -> Result<DialogReply>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let origin = ["origin", "source", "user"]
+            .iter()
+            .find_map(|key| message.metadata[*key].as_str())
+            .unwrap_or("unknown")
+            .to_string();
+        
+        let logged = serde_json::json!({
+            "dialog_id": message.dialog_id,
+            "content": message.content,
+            "metadata": message.metadata,
+        });
+        let span = logging::request_span(&self.config.service.logging, "dialog_message", "dialog_message", &origin, &logged);
+        let started = std::time::Instant::now();
+        let dialog_id = message.dialog_id.clone();
+        
+        let (result, tokens) = async {
+            let _slot = self.lanes.acquire(Priority::Interactive).await;
+            metered(self.answer_dialog_message(message, on_chunk)).await
+        }
+        .instrument(span.clone())
+        .await;
+        
+        logging::finish(&span, started, tokens, &result);
+        self.log_access(
+            AccessEntry::new("dialog_message", "dialog_message", &origin, started.elapsed(), tokens, &result)
+                .with_dialog(Some(&dialog_id)),
+        )
+        .await;
+        self.usage.record(&origin, UsageKind::DialogMessage, "dialog_message", tokens, result.is_ok());
+        result
+    }
+    
+    /// Reply like `reply_to_dialog_message`, but once
+    /// `domains.dialog.soft_deadline` passes, return what the model has
+    /// written so far as a partial reply
+    ///
+    /// Generation carries on in the background. The complete reply is
+    /// recorded in the dialog as usual and published as a
+    /// `dialog_follow_up` event, for frontends whose requests time out
+    /// before a long answer would finish.
+    pub async fn reply_within_deadline(self: &Arc<Self>, message: DialogMessage) -> Result<DialogReply> {
+        let Some(deadline) = self.config.domains.dialog.soft_deadline else {
+            return self.reply_to_dialog_message(message, |_| {}).await;
+        };
+        
+        let dialog_id = message.dialog_id.clone();
+        let locale = message.metadata["locale"].as_str().map(str::to_string);
+        let written = Arc::new(std::sync::Mutex::new(String::new()));
+        let mut generating = {
+            let agent = self.clone();
+            let written = written.clone();
+            tokio::spawn(
+                async move {
+                    agent
+                        .reply_to_dialog_message(message, move |chunk| written.lock().unwrap().push_str(chunk))
+                        .await
+                }
+                .in_current_span(),
+            )
+        };
+        
+        let joined = match tokio::time::timeout(deadline, &mut generating).await {
+            Ok(joined) => joined,
+            Err(_) => {
+                let so_far = written.lock().unwrap().clone();
+                tracing::info!("Dialog {} passed its soft deadline; sending {} characters so far", dialog_id, so_far.len());
+                
+                let agent = self.clone();
+                let follow_up_dialog = dialog_id.clone();
+                tokio::spawn(async move {
+                    let (content, metadata, error) = match generating.await {
+                        Ok(Ok(reply)) => (Some(reply.content.clone()), Some(reply.metadata()), None),
+                        Ok(Err(e)) => (None, None, Some(e.to_string())),
+                        Err(e) => (None, None, Some(e.to_string())),
+                    };
+                    agent.emit(AgentEventKind::DialogFollowUp {
+                        dialog_id: follow_up_dialog,
+                        content,
+                        metadata,
+                        error,
+                    });
+                });
+                
+                let notice = self.localizer.text(locale.as_deref(), "partial-answer", &[]);
+                return Ok(DialogReply {
+                    content: if so_far.is_empty() { notice } else { format!("{}\n\n{}", notice, so_far) },
+                    partial: true,
+                    ..DialogReply::default()
+                });
+            }
+        };
+        joined.map_err(|e| AgentError::Internal(format!("Dialog reply task failed: {}", e)))?
+    }
+    
+    async fn answer_dialog_message<F>(
+        &self,
+        message: DialogMessage,
+        mut on_chunk: F,
+    ) -> Result<DialogReply>
+    where
+        F: FnMut(&str) + Send,
+    {
+        // Without the model, say so rather than fail
+        let user = message.metadata["user"].as_str();
+        let locale = message.metadata["locale"].as_str();
+        if self.model_degraded() {
+            return Ok(DialogReply {
+                content: self.localizer.text(locale, "model-unavailable", &[]),
+                degraded: true,
+                ..DialogReply::default()
             });
+        }
         
-        // Add user turn
-        let user_turn = Turn::new(
-            dialog.turns().len() as u32 + 1,
-            dialog.participants().keys().next().copied().unwrap_or_else(uuid::Uuid::new_v4),
-            Message::text(message.content.clone()),
-            cim_domain_dialog::TurnType::UserQuery,
-        );
+        // Over budget, refuse before the message is recorded, or answer
+        // with the smaller model
+        let over_budget = self.budgets.check(&message.dialog_id, user);
+        if let Some(exceeded) = &over_budget {
+            tracing::info!("{}", exceeded);
+            self.emit(AgentEventKind::BudgetExceeded {
+                dialog_id: message.dialog_id.clone(),
+                user: user.map(str::to_string),
+                scope: exceeded.scope.to_string(),
+                used: exceeded.used,
+                limit: exceeded.limit,
+                action: if self.fallback_provider.is_some() { "downgrade" } else { "refuse" }.to_string(),
+            });
+            if self.fallback_provider.is_none() {
+                return Err(AgentError::PermissionDenied(self.localizer.text(locale, "budget-exceeded", &[
+                    ("scope", exceeded.scope.to_string()),
+                    ("used", exceeded.used.to_string()),
+                    ("limit", exceeded.limit.to_string()),
+                ])));
+            }
+        }
         
-        dialog.add_turn(user_turn).ok();
+        // Oversized attachments are refused before the message is recorded
+        let attachments = self.attachments(&message.metadata).await?;
         
-        // Build conversation history for model
-        let history: Vec<ModelMessage> = dialog
-            .turns()
-            .iter()
-            .map(|turn| ModelMessage {
-                role: match turn.metadata.turn_type {
-                    cim_domain_dialog::TurnType::UserQuery => "user".to_string(),
-                    cim_domain_dialog::TurnType::AgentResponse => "assistant".to_string(),
-                    cim_domain_dialog::TurnType::SystemMessage => "system".to_string(),
-                    _ => "user".to_string(),
-                },
-                content: match &turn.message.content {
-                    MessageContent::Text(text) => text.clone(),
-                    MessageContent::Structured(json) => json.to_string(),
-                    MessageContent::Multimodal { text, .. } => text.clone().unwrap_or_default(),
-                },
-                timestamp: turn.timestamp,
-            })
-            .collect();
+        // Pick up dialogs stored by an earlier run
+        self.resume_stored_dialog(&message.dialog_id).await?;
+        
+        // One message of a dialog at a time; the dialogs themselves are not
+        // locked while the model generates, so other dialogs go on
+        let turn_lock = self.dialog_turn_lock(&message.dialog_id);
+        let _turn = turn_lock.lock().await;
+        
+        // Get or create dialog, and add the user turn
+        let mut history = {
+            let mut dialogs = self.dialogs.write().await;
+            let dialog = dialogs
+                .entry(message.dialog_id.clone())
+                .or_insert_with(user_dialog);
+            
+            let user_turn = Turn::new(
+                next_turn_number(dialog),
+                dialog.participants().keys().next().copied().unwrap_or_else(uuid::Uuid::new_v4),
+                Message::text(message.content.clone()),
+                cim_domain_dialog::TurnType::UserQuery,
+            );
+            dialog.add_turn(user_turn).ok();
+            
+            model_history(dialog)
+        };
+        
+        // Build conversation history for model, from the latest turns only
+        let context_window = self.config.domains.dialog.context_window;
+        if context_window > 0 && history.len() > context_window {
+            history.drain(..history.len() - context_window);
+        }
         
         // Add system prompt as first message if history is empty
         let mut context = vec![ModelMessage {
             role: "system".to_string(),
-            content: self.get_system_prompt(),
+            content: self.get_system_prompt(locale),
             timestamp: chrono::Utc::now(),
         }];
+        
+        // Pins stay in the prompt after their turns leave the context window
+        let heading = self.localizer.text(locale, "pinned-context", &[]);
+        if let Some(pinned) = pins::pinned_context(&heading, &self.pins.list(&message.dialog_id)) {
+            context.push(ModelMessage {
+                role: "system".to_string(),
+                content: pinned,
+                timestamp: chrono::Utc::now(),
+            });
+        }
         context.extend(history);
         
-        // Generate response using AI model
-        let response = self.model_provider
-            .generate_with_context(&message.content, &context)
-            .await?;
+        // Attachments are outside text, so they are screened first
+        let mut prompt = message.content.clone();
+        for (name, content) in attachments {
+            let content = self.screen(&format!("attachment {}", name), content);
+            prompt.push_str(&format!("\n\nAttachment {}:\n{}", name, content));
+        }
         
-        // Add assistant turn
-        let assistant_turn = Turn::new(
-            dialog.turns().len() as u32 + 1,
-            self.agent.id(),
-            Message::text(response.clone()),
-            cim_domain_dialog::TurnType::AgentResponse,
-        );
+        // Indexed documents on the concepts the message mentions
+        let lowered = message.content.to_lowercase();
+        let mentioned: Vec<&str> = CIM_CONCEPTS
+            .iter()
+            .copied()
+            .filter(|concept| lowered.contains(&concept.to_lowercase()))
+            .collect();
+        let (excerpts, mut sources) = self.retrieve(&mentioned, Some(&message.content)).await;
+        if !excerpts.is_empty() {
+            prompt.push_str(&format!(
+                "\n\nExcerpts from CIM sources that may help, to refer to by number:\n\n{}",
+                excerpts
+            ));
+        }
         
-        dialog.add_turn(assistant_turn).ok();
+        // Declared fields of the events the message names
+        match self.event_schemas().await {
+            Ok(registry) => {
+                let declared: Vec<String> = registry
+                    .mentioned(&message.content, MENTIONED_SCHEMAS)
+                    .into_iter()
+                    .map(|schema| format!("- {} (declared at {})", schema.signature(), schema.source))
+                    .collect();
+                if !declared.is_empty() {
+                    prompt.push_str(&format!(
+                        "\n\nDeclared schemas of the events mentioned, to answer from rather than guess:\n{}",
+                        declared.join("\n")
+                    ));
+                }
+            }
+            Err(e) => tracing::warn!("Failed to read event schemas: {}", e),
+        }
         
-        Ok(response)
-    }
-    
+        // Generate response using AI model, unless a peer knows better
+        let primary = self.model().await;
+        let provider = match (&over_budget, &self.fallback_provider) {
+            (Some(_), Some(fallback)) => fallback.as_ref(),
+            _ => primary.as_ref(),
+        };
+        let mut citations = Vec::new();
+        let delegated = self.delegate(&message).await;
+        let response = if let Some(delegated) = &delegated {
+            on_chunk(&delegated.answer);
+            delegated.answer.clone()
+        } else if self.tools.is_empty() {
+            let mut stream = provider.generate_stream(&prompt, &context).await?;
+            
+            let mut response = String::new();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk?;
+                if !chunk.content.is_empty() {
+                    on_chunk(&chunk.content);
+                    response.push_str(&chunk.content);
+                }
+                if chunk.done {
+                    break;
+                }
+            }
+            response
+        } else {
+            // Tool calls come before the answer, so it arrives in one piece
+            let response = self.generate_with_tools(provider, &prompt, &context, &mut citations).await?;
+            on_chunk(&response);
+            response
+        };
+        let model = provider.model_info().model;
+        drop(primary);
+        
+        // A peer answered without the excerpts
+        if delegated.is_some() {
+            sources.clear();
+        }
+        
+        // A peer's answer cost this agent nothing
+        if delegated.is_none() {
+            let tokens = estimate_tokens(&prompt)
+                + context.iter().map(|message| estimate_tokens(&message.content)).sum::<usize>()
+                + estimate_tokens(&response);
+            self.budgets.record(&message.dialog_id, user, tokens);
+            self.retrieval_metrics.record_answer(sources.len(), &response);
+            
+            if let Some(replays) = &self.replays {
+                let case = ReplayCase::new(&message.dialog_id, &model, &prompt, &context, &response);
+                if let Err(e) = replays.record(&case).await {
+                    tracing::warn!("Failed to record dialog {} for replay: {}", message.dialog_id, e);
+                }
+            }
+        }
+        
+        // A dialog ended while its answer was generated is not recorded again
+        let mut dialogs = self.dialogs.write().await;
+        if let Some(dialog) = dialogs.get_mut(&message.dialog_id) {
+            // Record where a relayed answer came from
+            if let Some(delegated) = &delegated {
+                let provenance = Turn::new(
+                    next_turn_number(dialog),
+                    self.agent.id(),
+                    Message::text(self.localizer.text(locale, "answered-by-peer", &[("agent", delegated.agent_id.clone())])),
+                    cim_domain_dialog::TurnType::SystemMessage,
+                );
+                
+                dialog.add_turn(provenance).ok();
+            }
+            
+            // Add assistant turn
+            let assistant_turn = Turn::new(
+                next_turn_number(dialog),
+                self.agent.id(),
+                Message::text(response.clone()),
+                cim_domain_dialog::TurnType::AgentResponse,
+            );
+            
+            dialog.add_turn(assistant_turn).ok();
+            self.cap_history(&message.dialog_id, dialog);
+            
+            self.stores
+                .dialogs
+                .save_dialog(&message.dialog_id, &model_history(dialog))
+                .await?;
+        }
+        drop(dialogs);
+        
+        // A peer's answer is the peer's to review
+        let evaluation = if delegated.is_none() && self.config.evaluation.applies_to("dialog_message") {
+            let mut reviewed: Vec<String> = citations
+                .iter()
+                .map(|citation| format!("{} ({})", citation.title, citation.url))
+                .collect();
+            if !excerpts.is_empty() {
+                reviewed.push(excerpts);
+            }
+            self.evaluate(&message.content, &response, &reviewed).await
+        } else {
+            None
+        };
+        
+        Ok(DialogReply {
+            content: response,
+            citations,
+            sources,
+            delegated_to: delegated.map(|delegated| delegated.agent_id),
+            evaluation,
+            degraded: false,
+            partial: false,
+        })
+    }
+    
+    /// Summarize dialogs, concepts discussed, and workflow progress since
+    /// `since` with the model
+    ///
+    /// Returns `None` when nothing happened, so there is nothing to report.
+    pub async fn summarize_activity(&self, since: chrono::DateTime<chrono::Utc>) -> Result<Option<String>> {
+        let mut dialogs = Vec::new();
+        let mut discussed: Vec<&str> = Vec::new();
+        for (dialog_id, dialog) in self.dialogs.read().await.iter() {
+            let turns: Vec<&Turn> = dialog.turns().iter().filter(|turn| turn.timestamp >= since).collect();
+            if turns.is_empty() {
+                continue;
+            }
+            
+            let questions: Vec<String> = turns
+                .iter()
+                .filter(|turn| matches!(turn.metadata.turn_type, TurnType::UserQuery))
+                .take(SUMMARY_QUESTIONS)
+                .map(|turn| turn_text(turn))
+                .collect();
+            for turn in &turns {
+                let text = turn_text(turn).to_lowercase();
+                for concept in CIM_CONCEPTS {
+                    if !discussed.contains(concept) && text.contains(&concept.to_lowercase()) {
+                        discussed.push(concept);
+                    }
+                }
+            }
+            
+            dialogs.push(serde_json::json!({
+                "dialog_id": dialog_id,
+                "turns": turns.len(),
+                "questions": questions,
+            }));
+        }
+        
+        let workflows = self.list_workflows(serde_json::json!({})).await?;
+        if dialogs.is_empty() && workflows["total"].as_u64() == Some(0) {
+            return Ok(None);
+        }
+        
+        let activity = serde_json::json!({
+            "since": since,
+            "dialogs": dialogs,
+            "concepts_discussed": discussed,
+            "workflows": workflows["workflows"],
+        });
+        let prompt = format!(
+            "Write a short digest of this CIM assistant activity for the team: what people asked about, \
+            which concepts came up, and how the workflows are progressing. Use plain text, no greeting.\n\n{}",
+            serde_json::to_string_pretty(&activity)?
+        );
+        
+        Ok(Some(self.model().await.generate(&prompt).await?))
+    }
+    
+    /// Have the model write a message from `instructions` in the agent's
+    /// voice, for messages nobody asked for such as scheduled tips
+    pub async fn compose(&self, instructions: &str) -> Result<String> {
+        let context = vec![ModelMessage {
+            role: "system".to_string(),
+            content: self.get_system_prompt(None),
+            timestamp: chrono::Utc::now(),
+        }];
+        
+        self.model().await.generate_with_context(instructions, &context).await
+    }
+    
+    /// Indexed excerpts matching `queries` for a prompt, with their citations
+    ///
+    /// With a `message`, the embedded chunks most similar to it come first.
+    /// Excerpts are screened like any other outside text, and each appears
+    /// once even if several queries find it.
+    async fn retrieve(&self, queries: &[&str], message: Option<&str>) -> (String, Vec<SourceCitation>) {
+        let mut matches: Vec<crate::sources::CodeMatch> = Vec::new();
+        let mut similarities: Vec<f32> = Vec::new();
+        if let Some(message) = message {
+            for (found, similarity) in self.retrieve_similar(message).await {
+                similarities.push(similarity);
+                matches.push(found);
+            }
+        }
+        {
+            let index = self.code_index.read().await;
+            for query in queries {
+                let terms = query.split_whitespace().count().max(1);
+                for found in index.search(query, None, RETRIEVED_EXCERPTS) {
+                    let seen = matches.iter().any(|known| {
+                        known.repo == found.repo
+                            && known.path == found.path
+                            && (known.start_line..=known.end_line).contains(&found.line)
+                    });
+                    if !seen {
+                        similarities.push(found.score as f32 / terms as f32);
+                        matches.push(found);
+                    }
+                }
+            }
+        }
+        
+        for code_match in &mut matches {
+            let source = format!("{}/{}:{}", code_match.repo, code_match.path, code_match.line);
+            code_match.snippet = self.screen(&source, std::mem::take(&mut code_match.snippet));
+        }
+        
+        let (excerpts, citations) = retrieval_context(&matches, RETRIEVED_CHARS);
+        if !matches.is_empty() || !queries.is_empty() {
+            self.retrieval_metrics.record_retrieval(&similarities[..citations.len()]);
+        }
+        (excerpts, citations)
+    }
+    
+    /// Embedded chunks most similar to `message`, with their similarity
+    ///
+    /// Nothing is retrieved while the index is empty or was embedded by
+    /// another model than the current one, nor when the model cannot embed.
+    async fn retrieve_similar(&self, message: &str) -> Vec<(crate::sources::CodeMatch, f32)> {
+        let config = &self.config.retrieval;
+        let provider = self.model().await;
+        let model = embedding_key(provider.as_ref());
+        {
+            let documents = self.documents.read().await;
+            if documents.is_empty() || documents.model() != model {
+                return Vec::new();
+            }
+        }
+        
+        let embedding = match provider.embed(message).await {
+            Ok(embedding) => embedding,
+            Err(e) => {
+                tracing::debug!("Skipping document retrieval: {}", e);
+                return Vec::new();
+            }
+        };
+        drop(provider);
+        
+        self.documents.read().await.search(&embedding, config.top_k, config.min_similarity)
+    }
+    
+    /// Retrieval hit rates and how often answers cite what was retrieved
+    pub fn retrieval_metrics(&self) -> &RetrievalMetrics {
+        &self.retrieval_metrics
+    }
+    
+    /// How many dialogs were expired for inactivity
+    pub fn dialog_metrics(&self) -> &DialogMetrics {
+        &self.dialog_metrics
+    }
+    
+    /// Have the model grade its own answer against `sources` and the rubric
+    ///
+    /// A failed review leaves the answer unreviewed rather than failing it.
+    async fn evaluate(&self, request: &str, answer: &str, sources: &[String]) -> Option<Evaluation> {
+        let rubric = self
+            .config
+            .evaluation
+            .rubric
+            .as_deref()
+            .unwrap_or(evaluation::DEFAULT_RUBRIC);
+        let prompt = evaluation::evaluation_prompt(request, answer, sources, rubric);
+        
+        let reply = self.model().await.generate(&prompt).await;
+        match reply.and_then(|reply| evaluation::parse_evaluation(&reply)) {
+            Ok(evaluation) => Some(evaluation),
+            Err(e) => {
+                tracing::warn!("Answer was not evaluated: {}", e);
+                None
+            }
+        }
+    }
+    
+    /// Running workflows that have not advanced for `idle`, oldest first
+    pub async fn stalled_workflows(&self, idle: std::time::Duration) -> Vec<serde_json::Value> {
+        let now = chrono::Utc::now();
+        let mut stalled: Vec<(chrono::DateTime<chrono::Utc>, serde_json::Value)> = self
+            .workflows
+            .read()
+            .await
+            .iter()
+            .filter(|(_, workflow)| matches!(workflow.status, WorkflowStatus::Running))
+            .filter(|(_, workflow)| (now - workflow.updated_at).to_std().is_ok_and(|elapsed| elapsed >= idle))
+            .map(|(workflow_id, workflow)| {
+                (workflow.updated_at, serde_json::json!({
+                    "workflow_id": workflow_id,
+                    "name": workflow.name,
+                    "owner": workflow.owner,
+                    "current_step": workflow.current_node,
+                    "idle_seconds": (now - workflow.updated_at).num_seconds(),
+                }))
+            })
+            .collect();
+        
+        stalled.sort_by_key(|(updated_at, _)| *updated_at);
+        stalled.into_iter().map(|(_, workflow)| workflow).collect()
+    }
+    
+    /// Each dialog in memory with when its last turn was taken, or `None`
+    /// for dialogs without turns
+    pub async fn dialog_activity(&self) -> Vec<(String, Option<chrono::DateTime<chrono::Utc>>)> {
+        self.dialogs
+            .read()
+            .await
+            .iter()
+            .map(|(dialog_id, dialog)| (dialog_id.clone(), dialog.turns().last().map(|turn| turn.timestamp)))
+            .collect()
+    }
+    
+    /// End a dialog idle for `idle` and drop it from memory, publishing a
+    /// `dialog_expired` event
+    ///
+    /// A dialog with a turn newer than `last_turn` is left alone, as it was
+    /// not idle after all. Its stored history is kept, so a later message
+    /// resumes it.
+    pub async fn expire_dialog(
+        &self,
+        dialog_id: &str,
+        last_turn: Option<chrono::DateTime<chrono::Utc>>,
+        idle: chrono::Duration,
+    ) -> bool {
+        let mut dialogs = self.dialogs.write().await;
+        let Some(dialog) = dialogs.get_mut(dialog_id) else {
+            return false;
+        };
+        if dialog.turns().last().map(|turn| turn.timestamp) != last_turn {
+            return false;
+        }
+        
+        dialog.status = DialogStatus::Ended;
+        let expired = AgentEventKind::DialogExpired {
+            dialog_id: dialog_id.to_string(),
+            status: format!("{:?}", dialog.status),
+            turn_count: dialog.turns().len(),
+            last_activity: last_turn,
+            idle_seconds: idle.num_seconds(),
+        };
+        dialogs.remove(dialog_id);
+        drop(dialogs);
+        
+        tracing::debug!("Expired dialog {} after {} idle seconds", dialog_id, idle.num_seconds());
+        self.dialog_metrics.record_expired();
+        self.emit(expired);
+        true
+    }
+    
+    /// Forward `message` to a peer whose topics match it better, if any
+    ///
+    /// Questions a peer delegated to us are always answered here. When the
+    /// peer fails, the question is answered locally instead.
+    async fn delegate(&self, message: &DialogMessage) -> Option<DelegatedAnswer> {
+        if !self.peers.is_enabled() || message.metadata.get("delegated_from").is_some() {
+            return None;
+        }
+        
+        let peer = self.peers.best_match(&message.content).await?;
+        match self.peers.delegate(&peer, &message.dialog_id, &message.content).await {
+            Ok(answer) => {
+                self.emit(AgentEventKind::QuestionDelegated {
+                    dialog_id: message.dialog_id.clone(),
+                    agent_id: answer.agent_id.clone(),
+                });
+                Some(answer)
+            }
+            Err(e) => {
+                tracing::warn!("Delegation to {} failed, answering locally: {}", peer.agent_id, e);
+                None
+            }
+        }
+    }
+    
+    /// Answer `prompt` with `provider`, running the tools the model calls along the way
+    ///
+    /// Sources the tools report are added to `citations`. After
+    /// `max_rounds` rounds of calls the model must answer without tools.
+    async fn generate_with_tools(
+        &self,
+        provider: &dyn ModelProvider,
+        prompt: &str,
+        context: &[ModelMessage],
+        citations: &mut Vec<Citation>,
+    ) -> Result<String> {
+        let specs = self.tools.specs();
+        let mut exchanges: Vec<ToolExchange> = Vec::new();
+        
+        for _ in 0..self.config.tools.max_rounds {
+            match provider.generate_with_tools(prompt, context, &specs, &exchanges).await? {
+                ModelTurn::Text(response) => return Ok(response),
+                ModelTurn::ToolCalls(calls) => {
+                    for call in calls {
+                        let output = self.tools.call(&call).await;
+                        for citation in output.citations {
+                            if !citations.contains(&citation) {
+                                citations.push(citation);
+                            }
+                        }
+                        let text = self.screen(&call.name, output.text);
+                        exchanges.push(ToolExchange { call, output: text });
+                    }
+                }
+            }
+        }
+        
+        match provider.generate_with_tools(prompt, context, &[], &exchanges).await? {
+            ModelTurn::Text(response) => Ok(response),
+            ModelTurn::ToolCalls(_) => Err(AgentError::ModelError(
+                "Model kept calling tools after it ran out of rounds".to_string()
+            )),
+        }
+    }
+    
+    /// Recreate a dialog from saved history so later messages keep its context
+    ///
+    /// Replaces any dialog already known under `dialog_id`. Messages with
+    /// the `assistant` role become agent turns, `system` ones system turns,
+    /// and all others user turns.
+    pub async fn restore_dialog(&self, dialog_id: &str, history: &[ModelMessage]) {
+        let dialog = self.dialog_from_history(history);
+        self.dialogs.write().await.insert(dialog_id.to_string(), dialog);
+    }
+
+    /// Restore `dialog_id` from the dialog store unless it is already in
+    /// memory
+    ///
+    /// A message answered meanwhile may have added to the dialog while its
+    /// history loaded; that dialog is kept rather than replaced.
+    async fn resume_stored_dialog(&self, dialog_id: &str) -> Result<()> {
+        if self.dialogs.read().await.contains_key(dialog_id) {
+            return Ok(());
+        }
+        if let Some(history) = self.stores.dialogs.load_dialog(dialog_id).await? {
+            let dialog = self.dialog_from_history(&history);
+            self.dialogs.write().await.entry(dialog_id.to_string()).or_insert(dialog);
+        }
+        Ok(())
+    }
+
+    fn dialog_from_history(&self, history: &[ModelMessage]) -> Dialog {
+        let mut dialog = user_dialog();
+        let user = dialog.participants().keys().next().copied().unwrap_or_else(uuid::Uuid::new_v4);
+
+        for message in history {
+            let (speaker, turn_type) = match message.role.as_str() {
+                "assistant" => (self.agent.id(), cim_domain_dialog::TurnType::AgentResponse),
+                "system" => (self.agent.id(), cim_domain_dialog::TurnType::SystemMessage),
+                _ => (user, cim_domain_dialog::TurnType::UserQuery),
+            };
+
+            let mut turn = Turn::new(
+                next_turn_number(&dialog),
+                speaker,
+                Message::text(message.content.clone()),
+                turn_type,
+            );
+            // Keep when it was said, so the reaper sees the dialog's real idle time
+            turn.timestamp = message.timestamp;
+            dialog.add_turn(turn).ok();
+        }
+
+        dialog
+    }
+
+    /// The lock answering one message of `dialog_id` at a time
+    fn dialog_turn_lock(&self, dialog_id: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut locks = self.dialog_turns.lock().unwrap();
+        // Locks nobody holds or waits for go as others are handed out
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        locks.entry(dialog_id.to_string()).or_default().clone()
+    }
+
+    /// Drop the oldest turns of `dialog` beyond `domains.dialog.max_history`,
+    /// announcing the eviction with a `dialog_truncated` event
+    ///
+    /// The dialog keeps its identity, participants, and status, and the
+    /// turns it keeps their numbers and timestamps.
+    fn cap_history(&self, dialog_id: &str, dialog: &mut Dialog) {
+        let max_history = self.config.domains.dialog.max_history;
+        let Some(evicted) = dialog.turns().len().checked_sub(max_history).filter(|evicted| max_history > 0 && *evicted > 0) else {
+            return;
+        };
+
+        dialog.turns.drain(..evicted);
+        tracing::debug!("Evicted {} turns from dialog {}", evicted, dialog_id);
+        self.emit(AgentEventKind::DialogTruncated {
+            dialog_id: dialog_id.to_string(),
+            evicted_turns: evicted,
+            kept_turns: max_history,
+        });
+    }
+
     /// Start a new dialog
-    async fn start_dialog(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+    async fn start_dialog(&self, _payload: serde_json::Value) -> Result<serde_json::Value> {
         let dialog_id = uuid::Uuid::new_v4();
         
         let participant = cim_domain_dialog::Participant {
@@ -249,11 +1939,7 @@ impl AlchemistAgent {
             "agent": {
                 "id": self.agent.id(),
                 "name": "Alchemist",
-                "capabilities": {
-                    "explain_concepts": true,
-                    "visualize_architecture": true,
-                    "guide_workflows": true,
-                },
+                "capabilities": self.capabilities(),
             },
         }))
     }
@@ -263,24 +1949,383 @@ impl AlchemistAgent {
         let concept = payload["concept"]
             .as_str()
             .ok_or_else(|| AgentError::Configuration("Missing concept parameter".to_string()))?;
+        let concept = &self.canonical_concept(concept).await;
+        let (description, prompt, sources) = self.explanation_prompt(concept).await;
         
-        // Look up concept in knowledge graph
-        let _graph = self.knowledge_graph.read().await;
+        let response = {
+            let provider = self.model().await;
+            let model = provider.model_info().model;
+            
+            match self.caches.response(&model, &prompt).await {
+                Some(response) => response,
+                None => {
+                    let response = provider.generate(&prompt).await?;
+                    self.caches.store_response(&model, &prompt, &response).await;
+                    response
+                }
+            }
+        };
+        self.retrieval_metrics.record_answer(sources.len(), &response);
         
-        // Generate explanation using model
-        let prompt = format!(
+        Ok(serde_json::json!({
+            "concept": concept,
+            "description": description,
+            "explanation": response,
+            "sources": sources,
+            "related_concepts": self.find_related_concepts(concept).await?,
+            "examples": self.find_concept_examples(concept).await?,
+        }))
+    }
+    
+    /// The knowledge graph's name for `concept`, such as "Event Sourcing"
+    /// for "es" or "event-sourcing", or `concept` as given if it has none
+    async fn canonical_concept(&self, concept: &str) -> String {
+        self.concept_graph
+            .read()
+            .await
+            .concept(concept)
+            .map_or_else(|| concept.to_string(), |known| known.name.clone())
+    }
+    
+    /// The knowledge graph's description of `concept`, and the prompt
+    /// explaining it with the sources it cites
+    async fn explanation_prompt(&self, concept: &str) -> (String, String, Vec<SourceCitation>) {
+        // What the knowledge graph says about it
+        let description = self
+            .concept_graph
+            .read()
+            .await
+            .concept(concept)
+            .map(|known| known.description.clone())
+            .unwrap_or_default();
+        
+        // Generate explanation using model, grounded in indexed documents
+        let mut prompt = format!(
             "Explain the CIM concept '{}' in detail, including its purpose, \
              how it fits into the overall architecture, and provide examples.",
             concept
         );
+        if !description.is_empty() {
+            prompt.push_str(&format!("\n\nIn short, {} is: {}.", concept, description));
+        }
+        let (excerpts, sources) = self.retrieve(&[concept], None).await;
+        if !excerpts.is_empty() {
+            prompt.push_str(&format!(
+                "\n\nBase the explanation on these excerpts from CIM sources, referring to them by number:\n\n{}",
+                excerpts
+            ));
+        }
+        
+        (description, prompt, sources)
+    }
+    
+    /// Explain a concept with two models and have the current one compare
+    /// the answers
+    ///
+    /// `models` names two entries of `comparison.providers` or models of the
+    /// configured provider, defaulting to the first two configured providers.
+    async fn compare_models(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let concept = payload["concept"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing concept parameter".to_string()))?;
+        let concept = &self.canonical_concept(concept).await;
+        let configured = &self.config.comparison.providers;
+        let models: Vec<String> = match payload["models"].as_array() {
+            Some(models) => models.iter().filter_map(|model| model.as_str()).map(str::to_string).collect(),
+            None => configured.keys().take(2).cloned().collect(),
+        };
+        let [first, second] = models.as_slice() else {
+            return Err(AgentError::InvalidRequest(format!(
+                "compare_models needs two models, got {}; pass models or configure comparison.providers",
+                models.len()
+            )));
+        };
+        
+        let providers = [first, second]
+            .into_iter()
+            .map(|name| -> Result<Box<dyn ModelProvider>> {
+                let model_config = match configured.get(name.as_str()) {
+                    Some(model_config) => model_config.clone(),
+                    None => {
+                        let mut model_config = self.config.model.clone();
+                        model_config.set_model(name.as_str());
+                        model_config
+                    }
+                };
+                Ok(Box::new(Metered::new(crate::model::create_provider(&model_config)?)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        
+        // The same prompt for both, timed separately
+        let (description, prompt, sources) = self.explanation_prompt(concept).await;
+        let ((first_answer, first_ms), (second_answer, second_ms)) = futures::join!(
+            timed_generate(providers[0].as_ref(), &prompt),
+            timed_generate(providers[1].as_ref(), &prompt)
+        );
+        let answers = vec![(first.clone(), first_answer?), (second.clone(), second_answer?)];
+        
+        let reply = self
+            .model()
+            .await
+            .generate(&comparison::comparison_prompt(concept, &answers))
+            .await?;
+        let comparison = comparison::parse_comparison(&reply, &[first.as_str(), second.as_str()]);
         
-        let response = self.model_provider.generate(&prompt).await?;
+        let answers: Vec<serde_json::Value> = answers
+            .into_iter()
+            .zip(&providers)
+            .zip([first_ms, second_ms])
+            .map(|(((name, explanation), provider), duration_ms)| {
+                serde_json::json!({
+                    "model": name,
+                    "provider": provider.model_info().provider,
+                    "explanation": explanation,
+                    "duration_ms": duration_ms,
+                })
+            })
+            .collect();
         
         Ok(serde_json::json!({
             "concept": concept,
-            "explanation": response,
-            "related_concepts": self.find_related_concepts(concept).await?,
-            "examples": self.find_concept_examples(concept).await?,
+            "description": description,
+            "sources": sources,
+            "answers": answers,
+            "comparison": comparison.summary,
+            "preferred": comparison.preferred,
+            "judged_by": self.model().await.model_info().model,
+        }))
+    }
+    
+    /// Diagnose a compiler or runtime error and suggest how to fix it
+    ///
+    /// `error` is rustc output, an error message, or a structured error
+    /// payload; `code` is optional source around the failure.
+    async fn explain_error(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let error = match &payload["error"] {
+            serde_json::Value::String(error) => error.clone(),
+            serde_json::Value::Null => {
+                return Err(AgentError::Configuration("Missing error parameter".to_string()));
+            }
+            structured => serde_json::to_string_pretty(structured)?,
+        };
+        let clues = ErrorClues::parse(&error);
+        
+        // Concepts the error touches, and what the knowledge graph relates to them
+        let lowered = error.to_lowercase();
+        let mut concepts: Vec<String> = CIM_CONCEPTS
+            .iter()
+            .filter(|concept| lowered.contains(&concept.to_lowercase()))
+            .map(|concept| concept.to_string())
+            .collect();
+        for concept in concepts.clone() {
+            for related in self.find_related_concepts(&concept).await? {
+                if !concepts.contains(&related) {
+                    concepts.push(related);
+                }
+            }
+        }
+        
+        // Where the named types and functions live in indexed code
+        let references: Vec<serde_json::Value> = {
+            let index = self.code_index.read().await;
+            clues
+                .identifiers
+                .iter()
+                .flat_map(|identifier| index.search(identifier, None, 2))
+                .map(|m| {
+                    let source = format!("{}/{}:{}", m.repo, m.path, m.line);
+                    let snippet = self.screen(&source, m.snippet);
+                    serde_json::json!({ "source": source, "snippet": snippet })
+                })
+                .collect()
+        };
+        
+        let mut prompt = format!(
+            "A developer working on a CIM project hit this {} error:\n\n{}\n\n",
+            clues.kind, error
+        );
+        if let Some(code) = self.code_parameter(&payload).await? {
+            let code = self.screen("error context", code);
+            prompt.push_str(&format!("The code around it:\n\n{}\n\n", code));
+        }
+        if !concepts.is_empty() {
+            prompt.push_str(&format!("Related CIM concepts: {}\n\n", concepts.join(", ")));
+        }
+        for reference in &references {
+            prompt.push_str(&format!(
+                "Indexed code at {}:\n{}\n\n",
+                reference["source"].as_str().unwrap_or_default(),
+                reference["snippet"].as_str().unwrap_or_default()
+            ));
+        }
+        prompt.push_str(
+            "Reply with a line starting \"Diagnosis:\" explaining the cause, then a line \"Fix:\" \
+             followed by numbered steps to fix it.",
+        );
+        
+        let reply = self.model().await.generate(&prompt).await?;
+        let (diagnosis, fix_steps) = parse_diagnosis(&reply);
+        
+        Ok(serde_json::json!({
+            "kind": clues.kind,
+            "error_code": clues.code,
+            "locations": clues.locations,
+            "diagnosis": diagnosis,
+            "fix_steps": fix_steps,
+            "related_concepts": concepts,
+            "code_references": references,
+        }))
+    }
+    
+    /// Scaffold a CIM domain from a description, one file per step of the
+    /// `implement_domain` workflow
+    async fn generate_code(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let description = payload["description"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing description parameter".to_string()))?;
+        
+        let provider = self.model().await;
+        let domain = match payload["domain"].as_str() {
+            Some(domain) => codegen::domain_name(domain),
+            None => {
+                let prompt = format!(
+                    "Name the CIM domain described below in one to three words. \
+                     Reply with the name only.\n\n{}",
+                    description
+                );
+                codegen::domain_name(&provider.generate(&prompt).await?)
+            }
+        };
+        if domain.is_empty() {
+            return Err(AgentError::InvalidRequest("Could not name the domain".to_string()));
+        }
+        
+        // Walk the workflow's steps in order
+        let workflow = self.create_domain_workflow().await?;
+        let mut steps = Vec::new();
+        let mut node = workflow.current_node.clone();
+        while let Some(current) = node {
+            let step = workflow.nodes.get(&current).and_then(|node| node["step"].as_str()).unwrap_or_default();
+            steps.push((current.clone(), step.to_string()));
+            node = workflow.next_node(&current);
+        }
+        
+        let mut files: Vec<GeneratedFile> = Vec::new();
+        for (node, step) in steps {
+            let Some((path, language, conventions)) = codegen::file_for_step(&node, &domain) else {
+                continue;
+            };
+            
+            let prompt = codegen::file_prompt(&domain, description, &step, &path, conventions, &files);
+            let content = codegen::strip_fences(&provider.generate(&prompt).await?);
+            files.push(GeneratedFile {
+                path,
+                step: node,
+                language: language.to_string(),
+                content,
+            });
+        }
+        files.push(codegen::lib_rs(&domain, &files));
+        
+        Ok(serde_json::json!({
+            "domain": domain,
+            "crate": format!("cim-domain-{}", domain.replace('_', "-")),
+            "workflow_type": "implement_domain",
+            "files": files,
+        }))
+    }
+    
+    /// Have the model turn a request such as "add a concept Saga related to
+    /// Aggregate" into graph changes, held until the user confirms them
+    async fn propose_graph_edit(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let request = payload["request"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing request parameter".to_string()))?;
+        let dialog_id = payload["dialog_id"].as_str();
+        
+        let known: Vec<String> = self
+            .concept_graph
+            .read()
+            .await
+            .concepts()
+            .map(|concept| concept.name.clone())
+            .collect();
+        let prompt = format!(
+            "The CIM knowledge graph has these concepts: {}.\n\n\
+             Turn this request into graph changes: {}\n\n\
+             Reply with JSON only, shaped as {{\"summary\": \"one sentence\", \"operations\": [...]}}, where each \
+             operation is one of {{\"op\": \"add_concept\", \"name\", \"description\"}}, \
+             {{\"op\": \"remove_concept\", \"name\"}}, {{\"op\": \"add_relation\", \"from\", \"to\", \"relation\"}}, \
+             or {{\"op\": \"remove_relation\", \"from\", \"to\"}}. Use existing concept names exactly.",
+            known.join(", "),
+            request
+        );
+        
+        let reply = self.model().await.generate(&prompt).await?;
+        let mutation = parse_mutation(&reply)?;
+        self.concept_graph.read().await.validate(&mutation)?;
+        
+        let proposal_id = uuid::Uuid::new_v4().to_string();
+        let proposal = serde_json::json!({
+            "proposal_id": proposal_id,
+            "dialog_id": dialog_id,
+            "summary": mutation.summary,
+            "operations": mutation.operations,
+        });
+        
+        // Keep the exchange in the dialog it came from
+        if let Some(dialog_id) = dialog_id {
+            if let Some(dialog) = self.dialogs.write().await.get_mut(dialog_id) {
+                let user = dialog.participants().keys().next().copied().unwrap_or_else(uuid::Uuid::new_v4);
+                let ask = Turn::new(next_turn_number(dialog), user, Message::text(request.to_string()), TurnType::UserQuery);
+                dialog.add_turn(ask).ok();
+                
+                let confirm = format!("Proposed graph change: {} Confirm proposal {} to apply it.", mutation.summary, proposal_id);
+                let answer = Turn::new(next_turn_number(dialog), self.agent.id(), Message::text(confirm), TurnType::AgentResponse);
+                dialog.add_turn(answer).ok();
+            }
+        }
+        
+        let proposed = AgentEventKind::GraphEditProposed {
+            proposal_id: proposal_id.clone(),
+            dialog_id: dialog_id.map(str::to_string),
+            summary: mutation.summary.clone(),
+            operations: mutation.operations.clone(),
+        };
+        self.graph_edits.write().await.insert(proposal_id.clone(), PendingGraphEdit {
+            dialog_id: dialog_id.map(str::to_string),
+            mutation,
+        });
+        self.emit(proposed);
+        
+        Ok(proposal)
+    }
+    
+    /// Apply a proposed graph edit, or with `confirm: false` discard it
+    async fn confirm_graph_edit(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let proposal_id = payload["proposal_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing proposal_id parameter".to_string()))?;
+        let confirm = payload["confirm"].as_bool().unwrap_or(true);
+        
+        let edit = self
+            .graph_edits
+            .write()
+            .await
+            .remove(proposal_id)
+            .ok_or_else(|| AgentError::NotFound(format!("Graph edit proposal {}", proposal_id)))?;
+        if !confirm {
+            return Ok(serde_json::json!({ "proposal_id": proposal_id, "applied": false }));
+        }
+        
+        // The graph may have changed since the proposal, so it is checked again
+        self.apply_graph_operations(&edit.mutation.operations, Some(proposal_id), edit.dialog_id.as_deref())
+            .await?;
+        
+        Ok(serde_json::json!({
+            "proposal_id": proposal_id,
+            "applied": true,
+            "operations": edit.mutation.operations,
         }))
     }
     
@@ -293,216 +2338,1402 @@ impl AlchemistAgent {
         // Generate graph representation
         let graph = self.knowledge_graph.read().await;
         
-        // Create visualization data
-        let visualization = match scope {
-            "overview" => self.generate_overview_visualization(&graph).await?,
-            "domains" => self.generate_domain_visualization(&graph).await?,
-            "events" => self.generate_event_flow_visualization(&graph).await?,
-            _ => self.generate_custom_visualization(&graph, scope).await?,
+        // Create visualization data
+        let visualization = match scope {
+            "overview" => self.generate_overview_visualization(&graph).await?,
+            "domains" => self.generate_domain_visualization(&graph).await?,
+            "events" => self.generate_event_flow_visualization(&graph).await?,
+            "messaging" | "visualize_messaging" => self.generate_messaging_visualization().await?,
+            _ => self.generate_custom_visualization(&graph, scope).await?,
+        };
+        
+        Ok(serde_json::json!({
+            "scope": scope,
+            "visualization": visualization,
+            "description": self.generate_visualization_description(scope).await?,
+        }))
+    }
+    
+    /// Guide through a workflow
+    async fn guide_workflow(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let workflow_type = payload["workflow_type"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing workflow_type parameter".to_string()))?;
+        let locale = payload["locale"].as_str();
+        
+        let workflow_id = uuid::Uuid::new_v4().to_string();
+        
+        // Create workflow based on type
+        let mut workflow = match workflow_type {
+            "create_agent" => self.create_agent_workflow().await?,
+            "implement_domain" => self.create_domain_workflow().await?,
+            "add_event" => self.create_event_workflow().await?,
+            _ => {
+                return Err(AgentError::Domain(self.localizer.text(
+                    locale,
+                    "unknown-workflow",
+                    &[("workflow", workflow_type.to_string())],
+                )))
+            }
+        };
+        workflow.owner = payload["owner"].as_str().map(str::to_string);
+        
+        // Completed steps of a domain workflow produce the crate's skeleton
+        if workflow_type == "implement_domain" {
+            let domain = payload["domain"].as_str().map(codegen::domain_name).unwrap_or_default();
+            workflow.metadata["domain"] = serde_json::json!(if domain.is_empty() { "example".to_string() } else { domain });
+        }
+        
+        self.start_workflow(&workflow_id, workflow).await?;
+        
+        Ok(serde_json::json!({
+            "workflow_id": workflow_id,
+            "workflow_type": workflow_type,
+            "status": "started",
+            "first_step": self.get_workflow_first_step(workflow_type, locale).await?,
+        }))
+    }
+    
+    /// Turn the steps agreed on in a dialog into a tracked workflow
+    async fn create_workflow_from_dialog(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let dialog_id = payload["dialog_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing dialog_id parameter".to_string()))?;
+        
+        // Pick up dialogs stored by an earlier run
+        self.resume_stored_dialog(dialog_id).await?;
+        
+        let transcript: Vec<String> = {
+            let dialogs = self.dialogs.read().await;
+            let dialog = dialogs
+                .get(dialog_id)
+                .ok_or_else(|| AgentError::NotFound(format!("Dialog {}", dialog_id)))?;
+            
+            model_history(dialog)
+                .into_iter()
+                .map(|message| format!("{}: {}", message.role, message.content))
+                .collect()
+        };
+        if transcript.is_empty() {
+            return Err(AgentError::Workflow(format!("Dialog {} has no turns", dialog_id)));
+        }
+        
+        let prompt = format!(
+            "Here is a conversation about implementing something with CIM:\n\n{}\n\n\
+             List the implementation steps the user and assistant agreed on, in the order they should be done. \
+             Leave out ideas that were rejected or only mentioned in passing. Reply with JSON only, shaped as \
+             {{\"name\": \"short plan name\", \"description\": \"one sentence\", \"steps\": \
+             [{{\"id\": \"snake_case_id\", \"step\": \"what to do\"}}]}}.",
+            transcript.join("\n\n")
+        );
+        
+        let reply = self.model().await.generate(&prompt).await?;
+        let plan = parse_plan(&reply)?;
+        
+        let workflow_id = uuid::Uuid::new_v4().to_string();
+        let mut workflow = Workflow::from_plan(&plan, dialog_id);
+        workflow.owner = payload["owner"].as_str().map(str::to_string);
+        self.start_workflow(&workflow_id, workflow).await?;
+        
+        let created = serde_json::json!({
+            "workflow_id": workflow_id,
+            "workflow_type": "from_dialog",
+            "dialog_id": dialog_id,
+            "name": plan.name,
+            "description": plan.description,
+            "status": "started",
+            "steps": plan.steps,
+            "first_step": plan.steps[0],
+        });
+        self.emit(AgentEventKind::WorkflowCreatedFromDialog {
+            workflow_id,
+            workflow_type: "from_dialog".to_string(),
+            dialog_id: dialog_id.to_string(),
+            name: plan.name.clone(),
+            description: plan.description.clone(),
+            status: "started".to_string(),
+            steps: plan.steps.clone(),
+            first_step: plan.steps[0].clone(),
+        });
+        
+        Ok(created)
+    }
+    
+    /// Advance a workflow to its next step
+    ///
+    /// A step with edges to several steps needs `to`, naming the one taken.
+    async fn advance_workflow(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let workflow_id = payload["workflow_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing workflow_id parameter".to_string()))?;
+        
+        self.move_workflow(workflow_id, payload["to"].as_str(), None).await
+    }
+    
+    /// Mark the active step of a workflow done, keeping its `output`, and
+    /// move on to the next step
+    async fn complete_step(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let workflow_id = payload["workflow_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing workflow_id parameter".to_string()))?;
+        let step = payload["step"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing step parameter".to_string()))?;
+        
+        self.move_workflow(workflow_id, payload["to"].as_str(), Some((step, payload["output"].clone())))
+            .await
+    }
+    
+    /// Stop a running workflow for good
+    async fn abort_workflow(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let workflow_id = payload["workflow_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing workflow_id parameter".to_string()))?;
+        let reason = payload["reason"].as_str();
+        
+        let mut workflows = self.workflows.write().await;
+        let workflow = workflows
+            .get_mut(workflow_id)
+            .ok_or_else(|| AgentError::NotFound(format!("Workflow {}", workflow_id)))?;
+        if !matches!(workflow.status, WorkflowStatus::Running) {
+            return Err(AgentError::Workflow(format!(
+                "Workflow {} is {:?} and cannot be aborted",
+                workflow_id, workflow.status
+            )));
+        }
+        
+        let step = workflow.current_node.take();
+        workflow.status = WorkflowStatus::Cancelled;
+        workflow.updated_at = chrono::Utc::now();
+        if let Some(reason) = reason {
+            workflow.metadata["abort_reason"] = serde_json::json!(reason);
+        }
+        self.emit(AgentEventKind::WorkflowAborted {
+            workflow_id: workflow_id.to_string(),
+            step: step.clone(),
+            reason: reason.map(str::to_string),
+        });
+        let aborted = workflow.clone();
+        workflows.remove(workflow_id);
+        drop(workflows);
+        self.persist_workflow(workflow_id, &aborted).await;
+        
+        Ok(serde_json::json!({
+            "workflow_id": workflow_id,
+            "aborted_step": step,
+            "reason": reason,
+            "status": format!("{:?}", aborted.status),
+        }))
+    }
+    
+    /// Track a new workflow, refusing it while `domains.workflow.max_concurrent`
+    /// workflows are running
+    async fn start_workflow(&self, workflow_id: &str, workflow: Workflow) -> Result<()> {
+        let max_concurrent = self.config.domains.workflow.max_concurrent;
+        {
+            let mut workflows = self.workflows.write().await;
+            let running = workflows
+                .values()
+                .filter(|workflow| matches!(workflow.status, WorkflowStatus::Running))
+                .count();
+            if max_concurrent > 0 && running >= max_concurrent {
+                return Err(AgentError::Workflow(format!(
+                    "{} workflows are running, the most allowed; complete or abort one first",
+                    running
+                )));
+            }
+            workflows.insert(workflow_id.to_string(), workflow.clone());
+        }
+        
+        self.persist_workflow(workflow_id, &workflow).await;
+        Ok(())
+    }
+    
+    /// Save a running workflow to the workflow store, or remove one that
+    /// has finished, when `domains.workflow.persist` is set
+    ///
+    /// A failure is logged rather than failing the change to the workflow.
+    async fn persist_workflow(&self, workflow_id: &str, workflow: &Workflow) {
+        if !self.config.domains.workflow.persist {
+            return;
+        }
+        
+        let saved = if matches!(workflow.status, WorkflowStatus::Running) {
+            self.stores.workflows.save_workflow(&workflow.to_stored(workflow_id)).await
+        } else {
+            self.stores.workflows.delete_workflow(workflow_id).await
+        };
+        if let Err(e) = saved {
+            tracing::warn!("Failed to persist workflow {}: {}", workflow_id, e);
+        }
+    }
+    
+    /// Fail and drop the running workflows that have not advanced for
+    /// `domains.workflow.timeout`, publishing a `workflow_timed_out` event
+    /// for each, and return how many there were
+    pub async fn expire_workflows(&self) -> usize {
+        let timeout = self.config.domains.workflow.timeout;
+        let now = chrono::Utc::now();
+        
+        let mut workflows = self.workflows.write().await;
+        let mut expired = Vec::new();
+        for (workflow_id, workflow) in workflows.iter_mut() {
+            let idle = now - workflow.updated_at;
+            if !matches!(workflow.status, WorkflowStatus::Running) || !idle.to_std().is_ok_and(|idle| idle >= timeout) {
+                continue;
+            }
+            
+            self.emit(AgentEventKind::WorkflowTimedOut {
+                workflow_id: workflow_id.clone(),
+                name: workflow.name.clone(),
+                owner: workflow.owner.clone(),
+                step: workflow.current_node.clone(),
+                idle_seconds: idle.num_seconds(),
+            });
+            let step = workflow.current_node.take();
+            workflow.status = WorkflowStatus::Failed;
+            workflow.metadata["timed_out_step"] = serde_json::json!(step);
+            workflow.updated_at = now;
+            expired.push((workflow_id.clone(), workflow.clone()));
+        }
+        for (workflow_id, _) in &expired {
+            workflows.remove(workflow_id);
+        }
+        drop(workflows);
+        
+        for (workflow_id, workflow) in &expired {
+            self.persist_workflow(workflow_id, workflow).await;
+        }
+        expired.len()
+    }
+    
+    /// Move a workflow from its active step along the edge to `to`, or its
+    /// only edge; `completed` names the step the caller finished, which must
+    /// be the active one, and its output
+    async fn move_workflow(
+        &self,
+        workflow_id: &str,
+        to: Option<&str>,
+        completed: Option<(&str, serde_json::Value)>,
+    ) -> Result<serde_json::Value> {
+        let mut workflows = self.workflows.write().await;
+        let workflow = workflows
+            .get_mut(workflow_id)
+            .ok_or_else(|| AgentError::NotFound(format!("Workflow {}", workflow_id)))?;
+        
+        let previous_step = workflow
+            .current_node
+            .clone()
+            .ok_or_else(|| AgentError::Workflow(format!("Workflow {} has no active step", workflow_id)))?;
+        if let Some((step, _)) = &completed {
+            if *step != previous_step {
+                return Err(AgentError::Workflow(format!(
+                    "Step {} is not the active step of workflow {}, {} is",
+                    step, workflow_id, previous_step
+                )));
+            }
+        }
+        
+        // Only edges out of the active step are valid transitions
+        let options = workflow.next_nodes(&previous_step);
+        let next = match (to, options.as_slice()) {
+            (Some(to), _) if options.iter().any(|option| option == to) => Some(to.to_string()),
+            (Some(to), []) => {
+                return Err(AgentError::Workflow(format!(
+                    "Step {} of workflow {} is the last one and cannot lead to {}",
+                    previous_step, workflow_id, to
+                )));
+            }
+            (Some(to), _) => {
+                return Err(AgentError::Workflow(format!(
+                    "Workflow {} cannot go from {} to {}; next steps: {}",
+                    workflow_id,
+                    previous_step,
+                    to,
+                    options.join(", ")
+                )));
+            }
+            (None, []) => None,
+            (None, [only]) => Some(only.clone()),
+            (None, several) => {
+                return Err(AgentError::InvalidRequest(format!(
+                    "Step {} of workflow {} leads to {}; pass `to` to choose",
+                    previous_step,
+                    workflow_id,
+                    several.join(", ")
+                )));
+            }
+        };
+        
+        if let Some((step, output)) = completed {
+            if !output.is_null() {
+                workflow.metadata["outputs"][step] = output;
+            }
+        }
+        workflow.current_node = next;
+        workflow.updated_at = chrono::Utc::now();
+        self.emit(AgentEventKind::WorkflowStepCompleted {
+            workflow_id: workflow_id.to_string(),
+            step: previous_step.clone(),
+            next_step: workflow.current_node.clone(),
+        });
+        
+        // No edge means the workflow is done
+        let step = match &workflow.current_node {
+            Some(node) => {
+                let step = workflow.nodes.get(node).cloned().unwrap_or(serde_json::Value::Null);
+                self.emit(AgentEventKind::WorkflowStepStarted {
+                    workflow_id: workflow_id.to_string(),
+                    step: node.clone(),
+                    instructions: step["step"].clone(),
+                });
+                step
+            }
+            None => {
+                workflow.status = WorkflowStatus::Completed;
+                self.emit(AgentEventKind::WorkflowCompleted {
+                    workflow_id: workflow_id.to_string(),
+                    last_step: previous_step.clone(),
+                });
+                serde_json::Value::Null
+            }
+        };
+        
+        let mut result = serde_json::json!({
+            "workflow_id": workflow_id,
+            "previous_step": previous_step,
+            "current_step": workflow.current_node.clone().unwrap_or_else(|| "none".to_string()),
+            "step": step,
+            "instructions": step["step"],
+            "next_steps": workflow.current_node.as_ref().map(|node| workflow.next_nodes(node)).unwrap_or_default(),
+            "status": format!("{:?}", workflow.status),
+            "progress": workflow.progress_percentage(),
+        });
+        let skeletons = workflow.metadata["domain"]
+            .as_str()
+            .map(|domain| codegen::skeletons(&previous_step, domain))
+            .unwrap_or_default();
+        let moved = workflow.clone();
+        if !matches!(moved.status, WorkflowStatus::Running) {
+            workflows.remove(workflow_id);
+        }
+        drop(workflows);
+        self.persist_workflow(workflow_id, &moved).await;
+        
+        if !skeletons.is_empty() {
+            result["artifacts"] = serde_json::json!(self.store_skeletons(workflow_id, skeletons).await);
+        }
+        Ok(result)
+    }
+    
+    /// Store the skeleton files of a completed step, returning their keys,
+    /// or the files themselves without an artifact store
+    async fn store_skeletons(&self, workflow_id: &str, files: Vec<GeneratedFile>) -> Vec<serde_json::Value> {
+        let mut links = Vec::with_capacity(files.len());
+        for file in files {
+            let key = artifacts::scaffold_key(workflow_id, &file.path);
+            let stored = match &self.artifacts {
+                Some(store) => match store.put(&key, file.content.clone().into_bytes()).await {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::warn!("Failed to store scaffold {}: {}", key, e);
+                        false
+                    }
+                },
+                None => false,
+            };
+            
+            links.push(match stored {
+                true => serde_json::json!({ "path": file.path, "key": key }),
+                false => serde_json::json!({ "path": file.path, "content": file.content }),
+            });
+        }
+        links
+    }
+    
+    /// Analyze a pattern in CIM
+    async fn analyze_pattern(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let pattern_type = payload["pattern_type"]
+            .as_str()
+            .unwrap_or("general");
+        
+        let code = self.code_parameter(&payload).await?.unwrap_or_default();
+        
+        // Analyze the pattern using model
+        let mut prompt = format!(
+            "Analyze this {} pattern in the context of CIM architecture:\n\n{}\n\n\
+             Identify strengths, potential issues, and suggest improvements.",
+            pattern_type, code
+        );
+        
+        // Callers can point the analysis at specific concerns
+        if let Some(focus) = payload["focus"].as_str() {
+            prompt.push_str("\n\nFocus on: ");
+            prompt.push_str(focus);
+        }
+        
+        let response = self.model().await.generate(&prompt).await?;
+        
+        Ok(serde_json::json!({
+            "pattern_type": pattern_type,
+            "analysis": response,
+            "recommendations": self.generate_pattern_recommendations(pattern_type, &code).await?,
+        }))
+    }
+    
+    /// List available CIM concepts
+    async fn list_concepts(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        // Built-in concepts and those added from dialogs
+        let concepts: Vec<String> = self
+            .concept_graph
+            .read()
+            .await
+            .concepts()
+            .map(|concept| concept.name.clone())
+            .collect();
+        
+        Ok(Page::new(concepts, &parameters, DEFAULT_LIMIT)?.into_json("concepts"))
+    }
+    
+    /// Find similar concepts
+    async fn find_similar_concepts(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let concept = parameters["concept"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing concept parameter".to_string()))?;
+        let concept = &self.canonical_concept(concept).await;
+        
+        let defaults = &self.config.domains.conceptual_space;
+        let top_k = parameters["top_k"].as_u64().map_or(defaults.top_k, |top_k| top_k as usize);
+        let threshold = parameters["threshold"].as_f64().map_or(defaults.threshold, |threshold| threshold as f32);
+        if !(-1.0..=1.0).contains(&threshold) {
+            return Err(AgentError::InvalidRequest(format!(
+                "threshold must be between -1 and 1, not {}",
+                threshold
+            )));
+        }
+        
+        let provider = self.model().await;
+        let mut space = self.conceptual_space.write().await;
+        space.use_model(&embedding_key(provider.as_ref()));
+        
+        // Embed concepts added or edited since the last query
+        let stale = space.stale(&*self.concept_graph.read().await);
+        for (name, text) in stale {
+            let embedding = provider.embed(&text).await?;
+            space.insert(&name, text, embedding);
+        }
+        
+        // A concept outside the graph is placed by its name alone
+        let embedding = match space.embedding(concept) {
+            Some(embedding) => embedding.to_vec(),
+            None => provider.embed(concept).await?,
+        };
+        
+        let similar: Vec<serde_json::Value> = space
+            .similar(&embedding, top_k, threshold, concept)
+            .into_iter()
+            .map(|(name, similarity)| serde_json::json!({ "concept": name, "similarity": similarity }))
+            .collect();
+        
+        Ok(serde_json::json!({
+            "concept": concept,
+            "similar": similar,
+        }))
+    }
+    
+    /// Get dialog history
+    async fn get_dialog_history(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let dialog_id = parameters["dialog_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing dialog_id parameter".to_string()))?;
+        
+        let dialogs = self.dialogs.read().await;
+        let dialog = dialogs
+            .get(dialog_id)
+            .ok_or_else(|| AgentError::Domain(format!("Dialog {} not found", dialog_id)))?;
+        
+        let history: Vec<serde_json::Value> = dialog
+            .turns()
+            .iter()
+            .map(|turn| {
+                serde_json::json!({
+                    "turn_type": format!("{:?}", turn.metadata.turn_type),
+                    "content": match &turn.message.content {
+                        MessageContent::Text(text) => text.clone(),
+                        MessageContent::Structured(json) => json.to_string(),
+                        MessageContent::Multimodal { text, .. } => text.clone().unwrap_or_default(),
+                    },
+                    "timestamp": turn.timestamp,
+                })
+            })
+            .collect();
+        
+        let turn_count = history.len();
+        let mut result = Page::new(history, &parameters, DEFAULT_LIMIT)?.into_json("history");
+        result["dialog_id"] = serde_json::json!(dialog_id);
+        result["status"] = serde_json::json!(format!("{:?}", dialog.status));
+        result["turn_count"] = serde_json::json!(turn_count);
+        Ok(result)
+    }
+    
+    /// End a dialog, forgetting its history
+    async fn end_dialog(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let dialog_id = payload["dialog_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing dialog_id parameter".to_string()))?;
+        
+        let dialog = self
+            .dialogs
+            .write()
+            .await
+            .remove(dialog_id)
+            .ok_or_else(|| AgentError::NotFound(format!("Dialog {}", dialog_id)))?;
+        
+        let ended = AgentEventKind::DialogEnded {
+            dialog_id: dialog_id.to_string(),
+            turn_count: dialog.turns().len(),
+            last_activity: dialog.turns().last().map(|turn| turn.timestamp),
+        };
+        self.stores.dialogs.delete_dialog(dialog_id).await?;
+        self.budgets.forget_dialog(dialog_id);
+        self.pins.forget_dialog(dialog_id);
+        let summary = ended.payload();
+        self.emit(ended);
+        
+        Ok(summary)
+    }
+    
+    /// Start a dialog from a chat exported from another assistant, so it
+    /// can be continued here with that conversation as context
+    async fn import_dialog(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let export = payload
+            .get("export")
+            .filter(|export| !export.is_null())
+            .ok_or_else(|| AgentError::Configuration("Missing export parameter".to_string()))?;
+        let format = payload["format"].as_str().map(str::parse::<ImportFormat>).transpose()?;
+        let imported = import::parse(export, format)?;
+        
+        let dialog_id = uuid::Uuid::new_v4().to_string();
+        let mut dialog = self.dialog_from_history(&imported.messages);
+        self.cap_history(&dialog_id, &mut dialog);
+        self.stores.dialogs.save_dialog(&dialog_id, &model_history(&dialog)).await?;
+        let turns = dialog.turns().len();
+        self.dialogs.write().await.insert(dialog_id.clone(), dialog);
+        
+        tracing::info!("Imported {} turns of a {} chat as dialog {}", turns, imported.format.name(), dialog_id);
+        let imported = AgentEventKind::DialogImported {
+            dialog_id,
+            format: imported.format.name().to_string(),
+            title: imported.title,
+            turns,
+            skipped: imported.skipped,
+        };
+        let summary = imported.payload();
+        self.emit(imported);
+        
+        Ok(summary)
+    }
+    
+    /// Replace all but the most recent turns of a dialog with a summary turn
+    ///
+    /// The replaced turns are archived in the dialog store rather than
+    /// deleted, so the full conversation can still be recovered.
+    async fn compact_dialog(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let dialog_id = payload["dialog_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing dialog_id parameter".to_string()))?;
+        let keep = payload["keep"].as_u64().map_or(COMPACT_KEEP, |keep| keep as usize);
+        
+        // Pick up dialogs stored by an earlier run
+        self.resume_stored_dialog(dialog_id).await?;
+        
+        // No turn lands between summarizing and replacing; the dialogs
+        // themselves are not locked while the model summarizes
+        let turn_lock = self.dialog_turn_lock(dialog_id);
+        let _turn = turn_lock.lock().await;
+        
+        let (history, last_compacted) = {
+            let dialogs = self.dialogs.read().await;
+            let dialog = dialogs
+                .get(dialog_id)
+                .ok_or_else(|| AgentError::NotFound(format!("Dialog {}", dialog_id)))?;
+            let compacted = dialog.turns().len().saturating_sub(keep);
+            let last_compacted = compacted.checked_sub(1).map(|last| dialog.turns()[last].turn_number);
+            (model_history(dialog), last_compacted)
+        };
+        let compacted = history.len().saturating_sub(keep);
+        let Some(last_compacted) = last_compacted else {
+            return Ok(serde_json::json!({
+                "dialog_id": dialog_id,
+                "compacted_turns": 0,
+                "kept_turns": history.len(),
+            }));
+        };
+        let (earlier, recent) = history.split_at(compacted);
+        
+        let transcript: Vec<String> = earlier
+            .iter()
+            .map(|message| format!("{}: {}", message.role, message.content))
+            .collect();
+        let prompt = format!(
+            "Here is the earlier part of a conversation about the Composable Information Machine (CIM):\n\n{}\n\n\
+             Summarize it so the conversation can continue without it. Keep the user's goals, the decisions made, \
+             open questions, and any names, versions, or code identifiers that were mentioned. Reply with the \
+             summary only, in a few short paragraphs.",
+            transcript.join("\n\n")
+        );
+        let summary = self.model().await.generate(&prompt).await?;
+        let summary = summary.trim().to_string();
+        
+        self.stores.dialogs.archive_turns(dialog_id, earlier).await?;
+        
+        // The summary takes the place of the turns it covers, which the
+        // kept turns follow unchanged, numbers and timestamps included
+        let mut summary_turn = Turn::new(
+            last_compacted,
+            self.agent.id(),
+            Message::text(self.localizer.text(payload["locale"].as_str(), "dialog-summary", &[("summary", summary.clone())])),
+            cim_domain_dialog::TurnType::SystemMessage,
+        );
+        summary_turn.timestamp = earlier[earlier.len() - 1].timestamp;
+        
+        let compacted_history = {
+            let mut dialogs = self.dialogs.write().await;
+            let dialog = dialogs
+                .get_mut(dialog_id)
+                .ok_or_else(|| AgentError::NotFound(format!("Dialog {}", dialog_id)))?;
+            dialog.turns.retain(|turn| turn.turn_number > last_compacted);
+            dialog.turns.insert(0, summary_turn);
+            model_history(dialog)
+        };
+        self.stores.dialogs.save_dialog(dialog_id, &compacted_history).await?;
+        
+        let compacted_event = AgentEventKind::DialogCompacted {
+            dialog_id: dialog_id.to_string(),
+            compacted_turns: compacted,
+            kept_turns: recent.len(),
+        };
+        let mut result = compacted_event.payload();
+        self.emit(compacted_event);
+        
+        result["summary"] = serde_json::json!(summary);
+        Ok(result)
+    }
+    
+    /// Pin a turn of a dialog, by its 1-based number, or a stated fact so the
+    /// model sees it in every later prompt of the dialog
+    async fn pin_context(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let dialog_id = payload["dialog_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing dialog_id parameter".to_string()))?;
+        
+        // Pick up dialogs stored by an earlier run
+        self.resume_stored_dialog(dialog_id).await?;
+        
+        let (turn, role, content) = {
+            let dialogs = self.dialogs.read().await;
+            let dialog = dialogs
+                .get(dialog_id)
+                .ok_or_else(|| AgentError::NotFound(format!("Dialog {}", dialog_id)))?;
+            
+            match (payload["turn"].as_u64(), payload["text"].as_str()) {
+                (Some(turn), None) => {
+                    let history = model_history(dialog);
+                    let message = (turn as usize)
+                        .checked_sub(1)
+                        .and_then(|index| history.get(index))
+                        .ok_or_else(|| {
+                            AgentError::InvalidRequest(format!(
+                                "Dialog {} has no turn {}; turns are numbered 1 to {}",
+                                dialog_id,
+                                turn,
+                                history.len()
+                            ))
+                        })?;
+                    (Some(turn as usize), message.role.clone(), message.content.clone())
+                }
+                (None, Some(text)) => (None, "user".to_string(), text.to_string()),
+                _ => {
+                    return Err(AgentError::InvalidRequest(
+                        "pin_context takes either a turn or a text".to_string(),
+                    ));
+                }
+            }
+        };
+        
+        let pin = self.pins.pin(dialog_id, turn, &role, &content)?;
+        Ok(serde_json::json!({
+            "dialog_id": dialog_id,
+            "pin": pin,
+            "pin_count": self.pins.list(dialog_id).len(),
+        }))
+    }
+    
+    /// Remove a pin from a dialog
+    fn unpin(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let dialog_id = payload["dialog_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing dialog_id parameter".to_string()))?;
+        let pin_id = payload["pin_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing pin_id parameter".to_string()))?;
+        
+        let pin = self.pins.unpin(dialog_id, pin_id)?;
+        Ok(serde_json::json!({
+            "dialog_id": dialog_id,
+            "unpinned": pin,
+            "pin_count": self.pins.list(dialog_id).len(),
+        }))
+    }
+    
+    /// The pins of a dialog, oldest first
+    fn list_pins(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let dialog_id = parameters["dialog_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing dialog_id parameter".to_string()))?;
+        
+        Ok(serde_json::json!({
+            "dialog_id": dialog_id,
+            "pins": self.pins.list(dialog_id),
+        }))
+    }
+    
+    /// List all known dialogs
+    async fn list_dialogs(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let dialogs = self.dialogs.read().await;
+        
+        // Sorted so cursors follow on from each other
+        let mut dialog_ids: Vec<&String> = dialogs.keys().collect();
+        dialog_ids.sort();
+        
+        let summaries: Vec<serde_json::Value> = dialog_ids
+            .into_iter()
+            .map(|dialog_id| (dialog_id, &dialogs[dialog_id]))
+            .map(|(dialog_id, dialog)| {
+                serde_json::json!({
+                    "dialog_id": dialog_id,
+                    "status": format!("{:?}", dialog.status),
+                    "turn_count": dialog.turns().len(),
+                    "last_activity": dialog.turns().last().map(|turn| turn.timestamp),
+                })
+            })
+            .collect();
+        
+        Ok(Page::new(summaries, &parameters, DEFAULT_LIMIT)?.into_json("dialogs"))
+    }
+    
+    /// Suggest follow-up questions for the latest exchange in a dialog
+    async fn suggest_follow_ups(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let dialog_id = parameters["dialog_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing dialog_id parameter".to_string()))?;
+        let count = parameters["count"].as_u64().unwrap_or(3) as usize;
+        
+        let exchange: Vec<String> = {
+            let dialogs = self.dialogs.read().await;
+            let dialog = dialogs
+                .get(dialog_id)
+                .ok_or_else(|| AgentError::NotFound(format!("Dialog {}", dialog_id)))?;
+            
+            let turns = dialog.turns();
+            turns[turns.len().saturating_sub(2)..]
+                .iter()
+                .map(|turn| match &turn.message.content {
+                    MessageContent::Text(text) => text.clone(),
+                    MessageContent::Structured(json) => json.to_string(),
+                    MessageContent::Multimodal { text, .. } => text.clone().unwrap_or_default(),
+                })
+                .collect()
+        };
+        
+        if exchange.is_empty() {
+            return Ok(serde_json::json!({ "dialog_id": dialog_id, "suggestions": [] }));
+        }
+        
+        let prompt = format!(
+            "Here is the latest exchange in a conversation about CIM:\n\n{}\n\n\
+             Suggest {} short follow-up questions the user might ask next. \
+             Reply with one question per line and nothing else.",
+            exchange.join("\n\n"),
+            count
+        );
+        
+        let response = self.model().await.generate(&prompt).await?;
+        
+        // Drop list markers and anything that is not a question
+        let suggestions: Vec<String> = response
+            .lines()
+            .map(|line| {
+                line.trim()
+                    .trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '-' | '*' | '.' | ')'))
+                    .trim()
+                    .to_string()
+            })
+            .filter(|line| line.ends_with('?'))
+            .take(count)
+            .collect();
+        
+        Ok(serde_json::json!({
+            "dialog_id": dialog_id,
+            "suggestions": suggestions,
+        }))
+    }
+    
+    /// Get workflow status
+    async fn get_workflow_status(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let workflow_id = parameters["workflow_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing workflow_id parameter".to_string()))?;
+        
+        let workflows = self.workflows.read().await;
+        let workflow = workflows
+            .get(workflow_id)
+            .ok_or_else(|| AgentError::Domain(format!("Workflow {} not found", workflow_id)))?;
+        
+        Ok(serde_json::json!({
+            "workflow_id": workflow_id,
+            "status": format!("{:?}", workflow.status),
+            "current_step": workflow.current_node.clone().unwrap_or_else(|| "none".to_string()),
+            "progress": workflow.progress_percentage(),
+        }))
+    }
+    
+    /// List all known workflows
+    async fn list_workflows(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let workflows = self.workflows.read().await;
+        
+        // Sorted so cursors follow on from each other
+        let mut workflow_ids: Vec<&String> = workflows.keys().collect();
+        workflow_ids.sort();
+        
+        let summaries: Vec<serde_json::Value> = workflow_ids
+            .into_iter()
+            .map(|workflow_id| (workflow_id, &workflows[workflow_id]))
+            .map(|(workflow_id, workflow)| {
+                serde_json::json!({
+                    "workflow_id": workflow_id,
+                    "name": workflow.name,
+                    "status": format!("{:?}", workflow.status),
+                    "current_step": workflow.current_node.clone().unwrap_or_else(|| "none".to_string()),
+                    "progress": workflow.progress_percentage(),
+                })
+            })
+            .collect();
+        
+        Ok(Page::new(summaries, &parameters, DEFAULT_LIMIT)?.into_json("workflows"))
+    }
+    
+    /// Search indexed source files for real code
+    async fn search_code(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let query = parameters["query"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing query parameter".to_string()))?;
+        let repo = parameters["repo"].as_str();
+        
+        let index = self.code_index.read().await;
+        let matches = index.search(query, repo, usize::MAX);
+        
+        let mut result = Page::new(matches, &parameters, SEARCH_LIMIT)?.into_json("results");
+        result["query"] = serde_json::json!(query);
+        result["indexed_files"] = serde_json::json!(index.len());
+        Ok(result)
+    }
+    
+    /// Fields of one domain event, as declared
+    async fn get_event_schema(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let name = parameters["name"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing name parameter".to_string()))?;
+        
+        let registry = self.event_schemas().await?;
+        let schema = registry.get(name)?;
+        
+        let mut result = serde_json::json!(schema);
+        result["signature"] = serde_json::json!(schema.signature());
+        Ok(result)
+    }
+    
+    /// List the known domain events, optionally those whose name contains `filter`
+    async fn list_event_schemas(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let filter = parameters["filter"].as_str().map(str::to_lowercase);
+        
+        let registry = self.event_schemas().await?;
+        let names: Vec<String> = registry
+            .schemas()
+            .filter(|schema| {
+                filter
+                    .as_deref()
+                    .is_none_or(|filter| schema.name.to_lowercase().contains(filter))
+            })
+            .map(|schema| schema.name.clone())
+            .collect();
+        
+        Ok(Page::new(names, &parameters, DEFAULT_LIMIT)?.into_json("schemas"))
+    }
+    
+    /// List the peer agents questions can be delegated to
+    async fn list_peers(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let peers = self.peers.list().await;
+        
+        let mut result = Page::new(peers, &parameters, DEFAULT_LIMIT)?.into_json("peers");
+        result["enabled"] = serde_json::json!(self.peers.is_enabled());
+        result["self"] = serde_json::json!(self.peers.own());
+        Ok(result)
+    }
+    
+    /// Describe what this deployment supports, so clients can adapt to it
+    async fn get_capabilities(&self, _parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let operations = |operations: &[(&str, &[Parameter])]| -> serde_json::Map<String, serde_json::Value> {
+            operations
+                .iter()
+                .map(|(name, parameters)| (name.to_string(), parameter_schema(parameters)))
+                .collect()
         };
+        #[allow(unused_mut)]
+        let (mut commands, mut queries) = (operations(COMMANDS), operations(QUERIES));
+        commands.retain(|command, _| self.disabled_capability(command).is_none());
+        #[cfg(feature = "chaos")]
+        {
+            commands.extend(operations(CHAOS_COMMANDS));
+            queries.extend(operations(CHAOS_QUERIES));
+        }
+        let features: serde_json::Map<String, serde_json::Value> = FEATURES
+            .iter()
+            .map(|(feature, enabled)| (feature.to_string(), serde_json::json!(enabled)))
+            .collect();
         
         Ok(serde_json::json!({
-            "scope": scope,
-            "visualization": visualization,
-            "description": self.generate_visualization_description(scope).await?,
+            "agent": {
+                "name": self.config.identity.name,
+                "version": crate::VERSION,
+            },
+            "capabilities": self.capabilities(),
+            "commands": commands,
+            "queries": queries,
+            "model": self.model().await.model_info(),
+            "features": features,
+            "tools": self.tools.specs().into_iter().map(|spec| spec.name).collect::<Vec<_>>(),
+            "events": crate::events::EVENT_TYPES,
+            "locales": self.localizer.locales(),
+            "subject_versions": {
+                "served": self.config.nats.versions,
+                "unversioned": self.config.nats.serve_unversioned,
+                "current": crate::nats_integration::subjects::CURRENT_VERSION,
+            },
+            "model_available": !self.model_degraded(),
+            "peers_enabled": self.peers.is_enabled(),
+            "http_api": self.config.service.http_api,
         }))
     }
     
-    /// Guide through a workflow
-    async fn guide_workflow(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
-        let workflow_type = payload["workflow_type"]
-            .as_str()
-            .ok_or_else(|| AgentError::Configuration("Missing workflow_type parameter".to_string()))?;
+    /// What this instance is actually running: its configuration without
+    /// secrets, prompts, knowledge, model, and features, and unless `prose`
+    /// is false the model's own summary of it
+    async fn describe_self(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let mut redacted_fields = self.config.service.logging.redact.clone();
+        redacted_fields.push("jwt".to_string());
+        let config = logging::redact_url_credentials(&logging::redact(&serde_json::to_value(&self.config)?, &redacted_fields));
         
-        let workflow_id = uuid::Uuid::new_v4().to_string();
+        let graph = self.concept_graph.read().await.clone();
+        let index = self.code_index.read().await;
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        serde_json::to_string(&graph.concepts().collect::<Vec<_>>())?.hash(&mut hasher);
+        serde_json::to_string(graph.relations())?.hash(&mut hasher);
+        index.version().hash(&mut hasher);
+        let knowledge = serde_json::json!({
+            "version": format!("{:016x}", hasher.finish()),
+            "concepts": graph.concepts().count(),
+            "relations": graph.relations().len(),
+            "indexed_files": index.len(),
+        });
+        drop(index);
         
-        // Create workflow based on type
-        let workflow = match workflow_type {
-            "create_agent" => self.create_agent_workflow().await?,
-            "implement_domain" => self.create_domain_workflow().await?,
-            "add_event" => self.create_event_workflow().await?,
-            _ => return Err(AgentError::Domain(format!("Unknown workflow type: {}", workflow_type))),
-        };
+        let default_locale = &self.config.localization.default_locale;
+        let features: serde_json::Map<String, serde_json::Value> = FEATURES
+            .iter()
+            .map(|(feature, enabled)| (feature.to_string(), serde_json::json!(enabled)))
+            .collect();
+        let mut description = serde_json::json!({
+            "agent": {
+                "name": self.config.identity.name,
+                "agent_id": self.config.identity.agent_id,
+                "version": crate::VERSION,
+                "uptime_seconds": self.started.elapsed().as_secs(),
+            },
+            "config": config,
+            "prompts": {
+                "default_locale": default_locale,
+                "locales": self.localizer.locales(),
+                "system_prompt": self.get_system_prompt(Some(default_locale)),
+            },
+            "knowledge": knowledge,
+            "model": self.model().await.model_info(),
+            "fallback_model": self.fallback_provider.as_ref().map(|provider| provider.model_info()),
+            "model_available": !self.model_degraded(),
+            "capabilities": self.capabilities(),
+            "features": features,
+            "tools": self.tools.specs().into_iter().map(|spec| spec.name).collect::<Vec<_>>(),
+        });
         
-        self.workflows.write().await.insert(workflow_id.clone(), workflow);
+        // The structured part is what matters when the model is away
+        if parameters["prose"].as_bool() != Some(false) && !self.model_degraded() {
+            let mut facts = description.clone();
+            facts["config"] = serde_json::Value::Null;
+            let prompt = format!(
+                "In one short paragraph for an operator debugging this deployment, describe what this CIM Alchemist \
+                 instance is running: its model, knowledge, enabled capabilities, and anything unusual. \
+                 Use only these facts:\n\n{}",
+                serde_json::to_string_pretty(&facts)?
+            );
+            description["prose"] = serde_json::json!(self.model().await.generate(&prompt).await?);
+        }
+        
+        Ok(description)
+    }
+    
+    /// Requests and tokens per origin, optionally between RFC 3339 times
+    /// `from` and `to` and for one `origin`
+    async fn get_usage_report(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let (from, to) = (time_parameter(&parameters, "from")?, time_parameter(&parameters, "to")?);
+        let origin = parameters["origin"].as_str();
         
+        let origins = self.usage.report(from, to, origin);
         Ok(serde_json::json!({
-            "workflow_id": workflow_id,
-            "workflow_type": workflow_type,
-            "status": "started",
-            "first_step": self.get_workflow_first_step(workflow_type).await?,
+            "from": from,
+            "to": to,
+            "origin": origin,
+            "total_requests": origins.iter().map(|usage| usage.commands + usage.queries + usage.dialog_messages).sum::<u64>(),
+            "total_tokens": origins.iter().map(|usage| usage.tokens).sum::<u64>(),
+            "origins": origins,
         }))
     }
     
-    /// Analyze a pattern in CIM
-    async fn analyze_pattern(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
-        let pattern_type = payload["pattern_type"]
-            .as_str()
-            .unwrap_or("general");
-        
-        let code = payload["code"]
+    /// List the models the agent can switch to
+    async fn list_models(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let provider = self.model().await;
+        let models = provider.list_models().await?;
+
+        let mut result = Page::new(models, &parameters, DEFAULT_LIMIT)?.into_json("models");
+        result["current"] = serde_json::json!(provider.model_info().model);
+        Ok(result)
+    }
+
+    /// Answer with a different model from now on
+    ///
+    /// The new model uses the configured provider settings and must be
+    /// reachable before it replaces the current one.
+    async fn switch_model(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let model = payload["model"]
             .as_str()
-            .unwrap_or("");
+            .ok_or_else(|| AgentError::Configuration("Missing model parameter".to_string()))?;
+
+        let available = self.model().await.list_models().await?;
+        if !available.iter().any(|name| name == model) {
+            return Err(AgentError::NotFound(format!("Model {}", model)));
+        }
+
+        let mut model_config = self.config.model.clone();
+        model_config.set_model(model);
+        let provider = crate::model::create_provider(&model_config)?;
+        #[cfg(feature = "chaos")]
+        let provider: Box<dyn ModelProvider> = Box::new(Faulty::new(provider, self.faults.clone()));
+        let provider: Arc<dyn ModelProvider> = Arc::new(Metered::new(provider));
+        provider.health_check().await?;
+
+        let previous = std::mem::replace(&mut *self.model_provider.write().await, provider)
+            .model_info()
+            .model;
+
+        Ok(serde_json::json!({
+            "previous": previous,
+            "model": model,
+        }))
+    }
+
+    /// Walk the knowledge graph into a glossary, as Markdown or JSON
+    ///
+    /// Concepts without a description are defined by the model, all in one
+    /// request.
+    async fn generate_glossary(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let format = payload["format"].as_str().unwrap_or("markdown");
+        if !matches!(format, "markdown" | "md" | "json") {
+            return Err(AgentError::InvalidRequest(format!(
+                "Unknown glossary format: {} (expected markdown or json)",
+                format
+            )));
+        }
         
-        // Analyze the pattern using model
-        let prompt = format!(
-            "Analyze this {} pattern in the context of CIM architecture:\n\n{}\n\n\
-             Identify strengths, potential issues, and suggest improvements.",
-            pattern_type, code
-        );
+        let graph = self.concept_graph.read().await.clone();
+        let undefined: Vec<String> = graph
+            .concepts()
+            .filter(|concept| concept.description.trim().is_empty())
+            .map(|concept| concept.name.clone())
+            .collect();
+        let definitions = if undefined.is_empty() {
+            HashMap::new()
+        } else {
+            let reply = self
+                .model()
+                .await
+                .generate(&glossary::definitions_prompt(&undefined))
+                .await?;
+            glossary::parse_definitions(&reply)?
+        };
         
-        let response = self.model_provider.generate(&prompt).await?;
+        let entries: Vec<GlossaryEntry> = graph
+            .concepts()
+            .map(|concept| {
+                let definition = match concept.description.trim() {
+                    "" => definitions
+                        .get(&concept.name.to_lowercase())
+                        .cloned()
+                        .unwrap_or_else(|| "Not yet defined.".to_string()),
+                    description => description.to_string(),
+                };
+                let mut see_also = graph.related(&concept.name);
+                see_also.sort_by_key(|term| term.to_lowercase());
+                see_also.dedup();
+                GlossaryEntry {
+                    term: concept.name.clone(),
+                    definition,
+                    see_also,
+                }
+            })
+            .collect();
         
-        Ok(serde_json::json!({
-            "pattern_type": pattern_type,
-            "analysis": response,
-            "recommendations": self.generate_pattern_recommendations(pattern_type, code).await?,
-        }))
+        Ok(match format {
+            "json" => serde_json::json!({
+                "format": "json",
+                "terms": entries.len(),
+                "entries": entries,
+            }),
+            _ => serde_json::json!({
+                "format": "markdown",
+                "terms": entries.len(),
+                "content": glossary::render_markdown(&entries),
+            }),
+        })
     }
     
-    /// List available CIM concepts
-    async fn list_concepts(&self, _parameters: serde_json::Value) -> Result<serde_json::Value> {
-        // Return predefined CIM concepts
-        let concepts = vec![
-            "Event Sourcing",
-            "CQRS",
-            "Domain-Driven Design",
-            "Entity Component System",
-            "Conceptual Spaces",
-            "Graph Workflows",
-            "NATS Messaging",
-            "CID Chains",
-            "Aggregate",
-            "Value Object",
-            "Domain Event",
-            "Command Handler",
-            "Query Handler",
-            "Projection",
-            "Bounded Context",
-        ];
+    /// Ask `user_id` a question about a concept from the knowledge graph,
+    /// at their level or the `difficulty` given
+    async fn start_quiz(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let user_id = payload["user_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing user_id parameter".to_string()))?;
         
+        if let Some(difficulty) = payload.get("difficulty").filter(|difficulty| !difficulty.is_null()) {
+            let difficulty: Difficulty = serde_json::from_value(difficulty.clone()).map_err(|_| {
+                AgentError::InvalidRequest(format!(
+                    "Unknown difficulty {} (expected beginner, intermediate, or advanced)",
+                    difficulty
+                ))
+            })?;
+            self.quizzes.set_difficulty(user_id, difficulty);
+        }
+        
+        let question = self.ask_quiz_question(&uuid::Uuid::new_v4().to_string(), user_id).await?;
         Ok(serde_json::json!({
-            "concepts": concepts,
-            "total": concepts.len(),
+            "question": question,
+            "progress": self.quizzes.learner(user_id),
         }))
     }
     
-    /// Find similar concepts
-    async fn find_similar_concepts(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
-        let concept = parameters["concept"]
+    /// Grade the answer to a quiz's open question and, unless `next` is
+    /// false, ask the next one
+    async fn answer_quiz(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let quiz_id = payload["quiz_id"]
             .as_str()
-            .ok_or_else(|| AgentError::Configuration("Missing concept parameter".to_string()))?;
+            .ok_or_else(|| AgentError::Configuration("Missing quiz_id parameter".to_string()))?;
+        let answer = payload["answer"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing answer parameter".to_string()))?;
         
-        // Use conceptual space to find similar concepts
-        let _space = self.conceptual_space.read().await;
+        let question = self.quizzes.pending(quiz_id)?;
+        let reply = self
+            .model()
+            .await
+            .generate(&quiz::grading_prompt(&question, answer))
+            .await?;
+        let grade = quiz::parse_grade(&reply)?;
+        let progress = self.quizzes.grade(&question, grade.correct);
+        self.emit(AgentEventKind::QuizAnswered {
+            quiz_id: quiz_id.to_string(),
+            user_id: question.user_id.clone(),
+            concept: question.concept.clone(),
+            difficulty: question.difficulty,
+            correct: grade.correct,
+        });
         
-        // For now, return mock similar concepts
-        let similar = match concept {
-            "Event Sourcing" => vec!["Event Store", "Event Stream", "CQRS"],
-            "Domain-Driven Design" => vec!["Bounded Context", "Aggregate", "Value Object"],
-            "Graph Workflows" => vec!["Workflow Engine", "Process Automation", "Visual Programming"],
-            _ => vec![],
+        let next_question = match payload["next"].as_bool().unwrap_or(true) {
+            true => Some(self.ask_quiz_question(quiz_id, &question.user_id).await?),
+            false => None,
         };
         
         Ok(serde_json::json!({
-            "concept": concept,
-            "similar": similar,
+            "quiz_id": quiz_id,
+            "correct": grade.correct,
+            "feedback": grade.feedback,
+            "progress": progress,
+            "next_question": next_question,
         }))
     }
     
-    /// Get dialog history
-    async fn get_dialog_history(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
-        let dialog_id = parameters["dialog_id"]
-            .as_str()
-            .ok_or_else(|| AgentError::Configuration("Missing dialog_id parameter".to_string()))?;
+    /// Have the model write a question for `user_id` at their level
+    async fn ask_quiz_question(&self, quiz_id: &str, user_id: &str) -> Result<QuizQuestion> {
+        let graph = self.concept_graph.read().await;
+        let (concept, related) = self
+            .quizzes
+            .choose_topic(user_id, &graph)
+            .ok_or_else(|| AgentError::Graph("The knowledge graph has no concepts to ask about".to_string()))?;
+        let description = graph
+            .concept(&concept)
+            .map(|concept| concept.description.clone())
+            .unwrap_or_default();
+        drop(graph);
         
-        let dialogs = self.dialogs.read().await;
-        let dialog = dialogs
-            .get(dialog_id)
-            .ok_or_else(|| AgentError::Domain(format!("Dialog {} not found", dialog_id)))?;
+        let difficulty = self.quizzes.learner(user_id).difficulty;
+        let prompt = quiz::question_prompt(&concept, &description, related.as_deref(), difficulty);
+        let reply = self.model().await.generate(&prompt).await?;
         
-        let history: Vec<serde_json::Value> = dialog
-            .turns()
-            .iter()
-            .map(|turn| {
-                serde_json::json!({
-                    "turn_type": format!("{:?}", turn.metadata.turn_type),
-                    "content": match &turn.message.content {
-                        MessageContent::Text(text) => text.clone(),
-                        MessageContent::Structured(json) => json.to_string(),
-                        MessageContent::Multimodal { text, .. } => text.clone().unwrap_or_default(),
-                    },
-                    "timestamp": turn.timestamp,
-                })
-            })
-            .collect();
+        let question = QuizQuestion {
+            quiz_id: quiz_id.to_string(),
+            user_id: user_id.to_string(),
+            concept,
+            related,
+            difficulty,
+            question: reply.trim().to_string(),
+        };
+        self.quizzes.ask(question.clone());
+        Ok(question)
+    }
+    
+    /// A user's quiz score and current level
+    fn get_quiz_score(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        let user_id = parameters["user_id"]
+            .as_str()
+            .ok_or_else(|| AgentError::Configuration("Missing user_id parameter".to_string()))?;
         
-        Ok(serde_json::json!({
-            "dialog_id": dialog_id,
-            "status": format!("{:?}", dialog.status),
-            "turn_count": history.len(),
-            "history": history,
-        }))
+        let mut result = serde_json::json!(self.quizzes.learner(user_id));
+        result["user_id"] = serde_json::json!(user_id);
+        Ok(result)
     }
     
-    /// Get workflow status
-    async fn get_workflow_status(&self, parameters: serde_json::Value) -> Result<serde_json::Value> {
-        let workflow_id = parameters["workflow_id"]
+    /// Add a message to every active dialog as a system turn and publish it
+    /// as an `announcement` event
+    async fn announce(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let message = payload["message"]
             .as_str()
-            .ok_or_else(|| AgentError::Configuration("Missing workflow_id parameter".to_string()))?;
+            .map(str::trim)
+            .filter(|message| !message.is_empty())
+            .ok_or_else(|| AgentError::Configuration("Missing message parameter".to_string()))?;
         
-        let workflows = self.workflows.read().await;
-        let workflow = workflows
-            .get(workflow_id)
-            .ok_or_else(|| AgentError::Domain(format!("Workflow {} not found", workflow_id)))?;
+        let limits = &self.config.announcements;
+        if !self.caches.allow("announce", limits.limit, limits.window).await {
+            tracing::warn!(target: "audit", "Announcement refused over the rate limit: {}", message);
+            return Err(AgentError::PermissionDenied(format!(
+                "At most {} announcements per {} seconds",
+                limits.limit,
+                limits.window.as_secs()
+            )));
+        }
         
-        Ok(serde_json::json!({
-            "workflow_id": workflow_id,
-            "status": format!("{:?}", workflow.status),
-            "current_step": workflow.current_node.clone().unwrap_or_else(|| "none".to_string()),
-            "progress": workflow.progress_percentage(),
-        }))
+        // Saving waits on the store, so histories are collected under the
+        // lock and saved once it is released
+        let mut histories = Vec::new();
+        {
+            let mut dialogs = self.dialogs.write().await;
+            for (dialog_id, dialog) in dialogs.iter_mut() {
+                if !matches!(dialog.status, DialogStatus::Active) {
+                    continue;
+                }
+                
+                let turn = Turn::new(
+                    next_turn_number(dialog),
+                    self.agent.id(),
+                    Message::text(message.to_string()),
+                    cim_domain_dialog::TurnType::SystemMessage,
+                );
+                dialog.add_turn(turn).ok();
+                histories.push((dialog_id.clone(), model_history(dialog)));
+            }
+        }
+        
+        let mut announced = Vec::new();
+        for (dialog_id, history) in histories {
+            if let Err(e) = self.stores.dialogs.save_dialog(&dialog_id, &history).await {
+                tracing::warn!("Failed to save announcement in dialog {}: {}", dialog_id, e);
+            }
+            announced.push(dialog_id);
+        }
+        announced.sort();
+        
+        tracing::info!(target: "audit", dialogs = announced.len(), "Announcement sent: {}", message);
+        let announcement = AgentEventKind::Announcement {
+            message: message.to_string(),
+            dialogs: announced,
+            announced_at: chrono::Utc::now(),
+        };
+        let result = announcement.payload();
+        self.emit(announcement);
+        
+        Ok(result)
     }
     
-    /// Get the system prompt for the AI model
-    fn get_system_prompt(&self) -> String {
-        format!(
-            "You are the Alchemist, an AI assistant specialized in helping users understand \
-             and work with the Composable Information Machine (CIM) architecture. \
-             \
-             Your expertise includes:\
-             - Event-driven architecture with event sourcing and CQRS\
-             - Domain-Driven Design principles and patterns\
-             - Entity Component Systems (ECS) using Bevy\
-             - Graph-based workflows and visual programming\
-             - Conceptual spaces for semantic understanding\
-             - NATS messaging and distributed systems\
-             - Rust programming best practices\
-             \
-             You should:\
-             - Provide clear, accurate explanations of CIM concepts\
-             - Use examples from the actual CIM codebase when relevant\
-             - Guide users through implementation patterns\
-             - Suggest best practices and improvements\
-             - Help debug and solve architecture challenges\
-             \
-             Always be helpful, precise, and educational in your responses."
-        )
+    /// Change the injected faults named in `payload`
+    #[cfg(feature = "chaos")]
+    fn inject_faults(&self, payload: serde_json::Value) -> Result<serde_json::Value> {
+        let faults = self.faults.update(&payload)?;
+        tracing::warn!("Injecting faults: {:?}", faults);
+        Ok(serde_json::json!(faults))
+    }
+    
+    /// Stop injecting faults
+    #[cfg(feature = "chaos")]
+    fn clear_faults(&self) -> Result<serde_json::Value> {
+        self.faults.clear();
+        tracing::info!("Cleared injected faults");
+        Ok(serde_json::json!(self.faults.faults()))
+    }
+    
+    /// Faults shared with the NATS subscriptions
+    #[cfg(feature = "chaos")]
+    pub fn faults(&self) -> Arc<FaultInjector> {
+        self.faults.clone()
+    }
+    
+    /// Get the system prompt for the AI model, in `locale`
+    fn get_system_prompt(&self, locale: Option<&str>) -> String {
+        self.localizer.text(locale, "system-prompt", &[])
     }
     
     // Helper methods
     
     async fn find_related_concepts(&self, concept: &str) -> Result<Vec<String>> {
-        // Mock implementation - would use knowledge graph
-        Ok(match concept {
-            "Event Sourcing" => vec!["CQRS", "Event Store", "Domain Events"],
-            "Domain-Driven Design" => vec!["Bounded Context", "Aggregate", "Ubiquitous Language"],
-            _ => vec![],
-        })
+        // Concepts related both ways appear once
+        let mut related = Vec::new();
+        for name in self.concept_graph.read().await.related(concept) {
+            if !related.contains(&name) {
+                related.push(name);
+            }
+        }
+        Ok(related)
     }
     
     async fn find_concept_examples(&self, concept: &str) -> Result<Vec<String>> {
-        // Mock implementation - would search codebase
+        // Prefer real code from indexed sources
+        let matches = self.code_index.read().await.search(concept, None, 3);
+        if !matches.is_empty() {
+            return Ok(matches
+                .into_iter()
+                .map(|m| {
+                    let source = format!("{}/{}:{}", m.repo, m.path, m.line);
+                    let snippet = self.screen(&source, m.snippet);
+                    format!("{}\n{}", source, snippet)
+                })
+                .collect());
+        }
+        
         Ok(match concept {
             "Event Sourcing" => vec![
                 "GraphEvent::NodeAdded in cim-domain-graph",
@@ -560,6 +3791,16 @@ impl AlchemistAgent {
         }))
     }
     
+    /// Subjects, buckets, streams, and consumers as they really are
+    async fn generate_messaging_visualization(&self) -> Result<serde_json::Value> {
+        let mut topology = Topology::configured(&self.config);
+        match topology::jetstream_streams(&self.config.nats).await {
+            Ok(streams) => topology.add_streams(&streams),
+            Err(e) => topology.jetstream_error = Some(e.to_string()),
+        }
+        Ok(serde_json::to_value(topology)?)
+    }
+    
     async fn generate_custom_visualization(&self, _graph: &Graph, scope: &str) -> Result<serde_json::Value> {
         Ok(serde_json::json!({
             "error": format!("Custom visualization for '{}' not yet implemented", scope),
@@ -573,7 +3814,7 @@ impl AlchemistAgent {
             scope
         );
         
-        let response = self.model_provider.generate(&prompt).await?;
+        let response = self.model().await.generate(&prompt).await?;
         Ok(response)
     }
     
@@ -606,6 +3847,8 @@ impl AlchemistAgent {
             metadata: serde_json::json!({
                 "description": "Workflow for creating a new CIM agent",
             }),
+            owner: None,
+            updated_at: chrono::Utc::now(),
         })
     }
     
@@ -638,6 +3881,8 @@ impl AlchemistAgent {
             metadata: serde_json::json!({
                 "description": "Workflow for implementing a new CIM domain",
             }),
+            owner: None,
+            updated_at: chrono::Utc::now(),
         })
     }
     
@@ -666,50 +3911,36 @@ impl AlchemistAgent {
             metadata: serde_json::json!({
                 "description": "Workflow for adding a new domain event",
             }),
+            owner: None,
+            updated_at: chrono::Utc::now(),
         })
     }
     
-    async fn get_workflow_first_step(&self, workflow_type: &str) -> Result<serde_json::Value> {
-        let step_info = match workflow_type {
-            "create_agent" => serde_json::json!({
-                "step": "setup",
-                "title": "Setup Project Structure",
-                "description": "Create a new cim-agent-* directory with the standard structure",
-                "actions": [
-                    "Create Cargo.toml with dependencies",
-                    "Set up src/ directory structure",
-                    "Create configuration templates",
-                    "Initialize git repository",
-                ],
-            }),
-            "implement_domain" => serde_json::json!({
-                "step": "design",
-                "title": "Design Domain Model",
-                "description": "Define the domain boundaries and core concepts",
-                "actions": [
-                    "Identify aggregates and entities",
-                    "Define value objects",
-                    "Map relationships",
-                    "Document ubiquitous language",
-                ],
-            }),
-            "add_event" => serde_json::json!({
-                "step": "define",
-                "title": "Define Event Structure",
-                "description": "Create the event type and its properties",
-                "actions": [
-                    "Choose event name (past tense)",
-                    "Define event payload",
-                    "Add serialization derives",
-                    "Document event purpose",
-                ],
-            }),
-            _ => serde_json::json!({
-                "error": "Unknown workflow type",
-            }),
+    /// Instructions for the first step of a workflow, in `locale`
+    ///
+    /// Each step has a title, a description, and four actions in the
+    /// message files, keyed by the workflow type.
+    async fn get_workflow_first_step(&self, workflow_type: &str, locale: Option<&str>) -> Result<serde_json::Value> {
+        let step = match workflow_type {
+            "create_agent" => "setup",
+            "implement_domain" => "design",
+            "add_event" => "define",
+            _ => {
+                return Ok(serde_json::json!({
+                    "error": self.localizer.text(locale, "unknown-workflow", &[("workflow", workflow_type.to_string())]),
+                }))
+            }
         };
         
-        Ok(step_info)
+        let prefix = workflow_type.replace('_', "-");
+        let text = |suffix: &str| self.localizer.text(locale, &format!("{}-{}", prefix, suffix), &[]);
+        
+        Ok(serde_json::json!({
+            "step": step,
+            "title": text("title"),
+            "description": text("description"),
+            "actions": (1..=4).map(|n| text(&format!("action-{}", n))).collect::<Vec<_>>(),
+        }))
     }
     
     async fn generate_pattern_recommendations(&self, pattern_type: &str, code: &str) -> Result<Vec<String>> {
@@ -720,7 +3951,7 @@ impl AlchemistAgent {
             pattern_type, code
         );
         
-        let response = self.model_provider.generate(&prompt).await?;
+        let response = self.model().await.generate(&prompt).await?;
         
         // Parse recommendations from response
         let recommendations: Vec<String> = response
@@ -750,6 +3981,184 @@ pub struct DialogMessage {
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
+/// A graph edit waiting for the user's confirmation
+struct PendingGraphEdit {
+    dialog_id: Option<String>,
+    mutation: GraphMutation,
+}
+
+/// The agent's reply to a dialog message
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DialogReply {
+    pub content: String,
+    
+    /// Sources consulted through tools, such as web search results
+    pub citations: Vec<Citation>,
+    
+    /// Indexed excerpts included in the prompt
+    pub sources: Vec<SourceCitation>,
+    
+    /// Peer agent the question was delegated to, if any
+    pub delegated_to: Option<String>,
+    
+    /// The model's review of its answer, when configured for dialogs
+    pub evaluation: Option<Evaluation>,
+    
+    /// Whether the reply is a notice that the model is unavailable
+    pub degraded: bool,
+    
+    /// Whether the reply is what the model had written by the soft
+    /// deadline, with the rest to follow
+    pub partial: bool,
+}
+
+impl DialogReply {
+    /// Metadata for the published reply: citations, provenance, and review
+    pub fn metadata(&self) -> serde_json::Value {
+        let mut metadata = serde_json::Map::new();
+        if !self.citations.is_empty() {
+            metadata.insert("citations".to_string(), serde_json::json!(self.citations));
+        }
+        if !self.sources.is_empty() {
+            metadata.insert("sources".to_string(), serde_json::json!(self.sources));
+        }
+        if let Some(agent_id) = &self.delegated_to {
+            metadata.insert("delegated_to".to_string(), serde_json::json!(agent_id));
+        }
+        if let Some(evaluation) = &self.evaluation {
+            metadata.insert("evaluation".to_string(), serde_json::json!(evaluation));
+        }
+        if self.degraded {
+            metadata.insert("degraded".to_string(), serde_json::json!(true));
+        }
+        if self.partial {
+            metadata.insert("partial".to_string(), serde_json::json!(true));
+        }
+        serde_json::Value::Object(metadata)
+    }
+}
+
+/// A new direct dialog with a human participant
+fn user_dialog() -> Dialog {
+    let participant = cim_domain_dialog::Participant {
+        id: uuid::Uuid::new_v4(),
+        name: "User".to_string(),
+        participant_type: cim_domain_dialog::ParticipantType::Human,
+        role: cim_domain_dialog::ParticipantRole::Primary,
+        metadata: HashMap::new(),
+    };
+    Dialog::new(
+        uuid::Uuid::new_v4(),
+        cim_domain_dialog::DialogType::Direct,
+        participant,
+    )
+}
+
+/// JSON schema of an object with `parameters`
+fn parameter_schema(parameters: &[Parameter]) -> serde_json::Value {
+    let properties: serde_json::Map<String, serde_json::Value> = parameters
+        .iter()
+        .map(|(name, kind, _)| (name.to_string(), serde_json::json!({ "type": kind })))
+        .collect();
+    let required: Vec<&str> = parameters
+        .iter()
+        .filter(|(_, _, required)| *required)
+        .map(|(name, _, _)| *name)
+        .collect();
+    
+    serde_json::json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+/// The RFC 3339 time under `name` in `parameters`, if given
+fn time_parameter(parameters: &serde_json::Value, name: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+    parameters[name]
+        .as_str()
+        .map(|value| {
+            chrono::DateTime::parse_from_rfc3339(value)
+                .map(|time| time.with_timezone(&chrono::Utc))
+                .map_err(|e| AgentError::InvalidRequest(format!("Invalid {} time {}: {}", name, value, e)))
+        })
+        .transpose()
+}
+
+/// Provider and model embeddings came from, to notice when they must be
+/// computed again
+fn embedding_key(provider: &dyn ModelProvider) -> String {
+    let info = provider.model_info();
+    format!("{}/{}", info.provider, info.model)
+}
+
+/// The running workflows saved in `stores`, to resume them after a restart;
+/// one that cannot be read is skipped with a warning
+async fn restore_workflows(stores: &Stores) -> Result<HashMap<String, Workflow>> {
+    let mut workflows = HashMap::new();
+    for stored in stores.workflows.list_workflows().await? {
+        if stored.status != "Running" {
+            continue;
+        }
+        match Workflow::from_stored(&stored) {
+            Ok(workflow) => {
+                workflows.insert(stored.workflow_id, workflow);
+            }
+            Err(e) => tracing::warn!("Failed to restore workflow {}: {}", stored.workflow_id, e),
+        }
+    }
+    
+    if !workflows.is_empty() {
+        tracing::info!("Resumed {} workflows", workflows.len());
+    }
+    Ok(workflows)
+}
+
+/// `provider`'s answer to `prompt`, with how many milliseconds it took
+async fn timed_generate(provider: &dyn ModelProvider, prompt: &str) -> (Result<String>, u64) {
+    let started = std::time::Instant::now();
+    let answer = provider.generate(prompt).await;
+    (answer, started.elapsed().as_millis() as u64)
+}
+
+/// Text of a turn, whatever form its message takes
+fn turn_text(turn: &Turn) -> String {
+    match &turn.message.content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::Structured(json) => json.to_string(),
+        MessageContent::Multimodal { text, .. } => text.clone().unwrap_or_default(),
+    }
+}
+
+/// Number of the turn to add to `dialog`, following its last one even
+/// after older turns were evicted
+fn next_turn_number(dialog: &Dialog) -> u32 {
+    dialog.turns().last().map_or(1, |turn| turn.turn_number + 1)
+}
+
+/// A dialog's turns as model messages
+fn model_history(dialog: &Dialog) -> Vec<ModelMessage> {
+    dialog
+        .turns()
+        .iter()
+        .map(|turn| ModelMessage {
+            role: match turn.metadata.turn_type {
+                cim_domain_dialog::TurnType::UserQuery => "user".to_string(),
+                cim_domain_dialog::TurnType::AgentResponse => "assistant".to_string(),
+                cim_domain_dialog::TurnType::SystemMessage => "system".to_string(),
+                _ => "user".to_string(),
+            },
+            content: match &turn.message.content {
+                MessageContent::Text(text) => text.clone(),
+                MessageContent::Structured(json) => json.to_string(),
+                MessageContent::Multimodal { text, .. } => text.clone().unwrap_or_default(),
+            },
+            timestamp: turn.timestamp,
+        })
+        .collect()
+}
+
 // Custom workflow representation for the agent
 #[derive(Debug, Clone)]
 struct Workflow {
@@ -760,9 +4169,96 @@ struct Workflow {
     nodes: HashMap<String, serde_json::Value>,
     edges: HashMap<(String, String), serde_json::Value>,
     metadata: serde_json::Value,
+    
+    /// Who started the workflow, to be reminded when it stalls
+    owner: Option<String>,
+    
+    /// When the workflow last started or advanced
+    updated_at: chrono::DateTime<chrono::Utc>,
 }
 
 impl Workflow {
+    /// A running workflow through the steps of `plan`, in order
+    fn from_plan(plan: &WorkflowPlan, dialog_id: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4(),
+            name: plan.name.clone(),
+            status: WorkflowStatus::Running,
+            current_node: plan.steps.first().map(|step| step.id.clone()),
+            nodes: plan
+                .steps
+                .iter()
+                .map(|step| (step.id.clone(), serde_json::json!({"step": step.step})))
+                .collect(),
+            edges: plan
+                .steps
+                .windows(2)
+                .map(|pair| ((pair[0].id.clone(), pair[1].id.clone()), serde_json::json!({"label": "next"})))
+                .collect(),
+            metadata: serde_json::json!({
+                "description": plan.description,
+                "dialog_id": dialog_id,
+            }),
+            owner: None,
+            updated_at: chrono::Utc::now(),
+        }
+    }
+    
+    /// The workflow as the workflow store keeps it
+    fn to_stored(&self, workflow_id: &str) -> StoredWorkflow {
+        // JSON object keys are strings, so edges are kept as a list
+        let edges: Vec<(&String, &String, &serde_json::Value)> =
+            self.edges.iter().map(|((from, to), edge)| (from, to, edge)).collect();
+        StoredWorkflow {
+            workflow_id: workflow_id.to_string(),
+            name: self.name.clone(),
+            status: format!("{:?}", self.status),
+            current_step: self.current_node.clone(),
+            state: serde_json::json!({
+                "id": self.id,
+                "nodes": self.nodes,
+                "edges": edges,
+                "metadata": self.metadata,
+                "owner": self.owner,
+            }),
+            updated_at: self.updated_at,
+        }
+    }
+    
+    /// A running workflow back from the workflow store
+    fn from_stored(stored: &StoredWorkflow) -> Result<Self> {
+        let state = &stored.state;
+        let edges: Vec<(String, String, serde_json::Value)> = serde_json::from_value(state["edges"].clone())?;
+        Ok(Self {
+            id: serde_json::from_value(state["id"].clone())?,
+            name: stored.name.clone(),
+            status: WorkflowStatus::Running,
+            current_node: stored.current_step.clone(),
+            nodes: serde_json::from_value(state["nodes"].clone())?,
+            edges: edges.into_iter().map(|(from, to, edge)| ((from, to), edge)).collect(),
+            metadata: state["metadata"].clone(),
+            owner: state["owner"].as_str().map(str::to_string),
+            updated_at: stored.updated_at,
+        })
+    }
+    
+    /// Find the step that follows `from` along the workflow edges
+    fn next_node(&self, from: &str) -> Option<String> {
+        self.next_nodes(from).into_iter().next()
+    }
+    
+    /// Every step an edge leads to from `from`, sorted
+    fn next_nodes(&self, from: &str) -> Vec<String> {
+        let mut next: Vec<String> = self
+            .edges
+            .keys()
+            .filter(|(source, _)| source == from)
+            .map(|(_, target)| target.clone())
+            .collect();
+        next.sort();
+        next
+    }
+    
     fn progress_percentage(&self) -> f32 {
         if self.nodes.is_empty() {
             return 0.0;
@@ -778,4 +4274,41 @@ impl Workflow {
         
         0.0
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_running_workflows_are_restored() {
+        let plan: WorkflowPlan = serde_json::from_value(serde_json::json!({
+            "name": "add_event",
+            "steps": [
+                { "id": "define", "step": "Define the event" },
+                { "id": "handler", "step": "Create event handler" },
+            ],
+        }))
+        .unwrap();
+        let mut workflow = Workflow::from_plan(&plan, "dialog-1");
+        workflow.owner = Some("alice".to_string());
+        let mut finished = workflow.clone();
+        finished.status = WorkflowStatus::Completed;
+
+        let stores = Stores::memory();
+        stores.workflows.save_workflow(&workflow.to_stored("running")).await.unwrap();
+        stores.workflows.save_workflow(&finished.to_stored("finished")).await.unwrap();
+
+        let restored = restore_workflows(&stores).await.unwrap();
+        assert_eq!(restored.len(), 1);
+        let resumed = &restored["running"];
+        assert_eq!(resumed.id, workflow.id);
+        assert_eq!(resumed.name, workflow.name);
+        assert!(matches!(resumed.status, WorkflowStatus::Running));
+        assert_eq!(resumed.current_node.as_deref(), Some("define"));
+        assert_eq!(resumed.nodes, workflow.nodes);
+        assert_eq!(resumed.edges, workflow.edges);
+        assert_eq!(resumed.metadata, workflow.metadata);
+        assert_eq!(resumed.owner.as_deref(), Some("alice"));
+        assert_eq!(resumed.updated_at, workflow.updated_at);
+    }
+}
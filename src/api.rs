@@ -0,0 +1,337 @@
+//! JSON API over HTTP, mirroring the NATS subjects
+//!
+//! `POST /api/commands/{command_type}` and `POST /api/queries/{query_type}`
+//! take the same payloads as their NATS counterparts and answer with the
+//! same envelope. `POST /api/dialogs/{dialog_id}/messages` sends a dialog
+//! message and returns the reply. Built with the `openapi` feature, the API
+//! is described at `/openapi.json` and browsable at `/swagger-ui`.
+//!
+//! With `service.api_auth` configured, `/api` routes require credentials;
+//! see [`crate::auth`].
+
+use axum::extract::{DefaultBodyLimit, Path, Request, State};
+use axum::Extension;
+use axum::middleware;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::Instrument;
+
+use crate::agent::{AlchemistAgent, DialogMessage, DialogReply};
+use crate::auth::{self, ApiAuth, Caller};
+use crate::config::ApiAuthConfig;
+use crate::error::{AgentError, Result};
+
+/// Header naming a command's idempotency key, as `idempotency_key` does over NATS
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Header carrying a request's correlation ID, generated when missing
+const CORRELATION_ID: &str = "x-correlation-id";
+
+/// Routes for the API, and its description with the `openapi` feature
+pub fn router(agent: Arc<AlchemistAgent>, auth: Option<&ApiAuthConfig>) -> Result<Router> {
+    let max_message_bytes = agent.config().limits.max_message_bytes;
+    let mut router = Router::new()
+        .route("/api/commands/{command_type}", post(run_command))
+        .route("/api/queries/{query_type}", post(run_query))
+        .route("/api/dialogs/{dialog_id}/messages", post(send_dialog_message))
+        .with_state(agent)
+        .route_layer(middleware::from_fn_with_state(max_message_bytes, refuse_oversized));
+
+    // Bodies without a length are cut off at the limit as they stream in
+    router = match max_message_bytes {
+        0 => router.layer(DefaultBodyLimit::disable()),
+        limit => router.layer(DefaultBodyLimit::max(limit)),
+    };
+
+    if let Some(config) = auth {
+        let auth = Arc::new(ApiAuth::new(config)?);
+        router = router.route_layer(middleware::from_fn_with_state(auth, auth::require_scope));
+    }
+    let router = router.layer(middleware::from_fn(correlate));
+
+    #[cfg(feature = "openapi")]
+    let router = router.merge(
+        utoipa_swagger_ui::SwaggerUi::new("/swagger-ui").url("/openapi.json", <ApiDoc as utoipa::OpenApi>::openapi()),
+    );
+
+    Ok(router)
+}
+
+/// The OpenAPI document as JSON
+#[cfg(feature = "openapi")]
+pub fn openapi_json() -> Result<String> {
+    Ok(<ApiDoc as utoipa::OpenApi>::openapi().to_pretty_json()?)
+}
+
+/// OpenAPI description of the API, derived when the crate is built
+#[cfg(feature = "openapi")]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    info(title = "CIM Alchemist Agent"),
+    paths(run_command, run_query, send_dialog_message),
+    components(schemas(ApiResponse, DialogMessageRequest, DialogReply, crate::tools::Citation))
+)]
+pub struct ApiDoc;
+
+/// Reply envelope, the same as on NATS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiResponse {
+    pub success: bool,
+
+    /// Handler result when `success` is true
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub result: Option<serde_json::Value>,
+
+    /// What went wrong when `success` is false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Machine-readable kind of error, such as `payload_too_large`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+
+    /// Particulars of a coded error, such as the size and limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ApiResponse {
+    pub fn from_error(error: &AgentError) -> Self {
+        Self {
+            success: false,
+            result: None,
+            error: Some(error.to_string()),
+            code: error.code().map(str::to_string),
+            details: error.details(),
+        }
+    }
+
+    fn error(error: &AgentError) -> (StatusCode, Json<Self>) {
+        (status(error), Json(Self::from_error(error)))
+    }
+}
+
+/// A dialog message sent over HTTP
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DialogMessageRequest {
+    pub content: String,
+
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi", schema(value_type = Object))]
+    pub metadata: serde_json::Value,
+}
+
+/// HTTP status for a failed request
+fn status(error: &AgentError) -> StatusCode {
+    match error {
+        AgentError::InvalidRequest(_) | AgentError::Configuration(_) | AgentError::Serialization(_) => {
+            StatusCode::BAD_REQUEST
+        }
+        AgentError::NotFound(_) => StatusCode::NOT_FOUND,
+        AgentError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+        AgentError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        AgentError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        AgentError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Refuse requests whose declared length is over `max_message_bytes`
+/// before their body is read
+async fn refuse_oversized(State(max_message_bytes): State<usize>, request: Request, next: Next) -> Response {
+    let size = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<usize>().ok())
+        .unwrap_or_default();
+    match crate::limits::check("message", size, max_message_bytes) {
+        Ok(()) => next.run(request).await,
+        Err(e) => ApiResponse::error(&e).into_response(),
+    }
+}
+
+/// Log everything done for a request under its correlation ID, and return
+/// the ID to the caller
+async fn correlate(request: Request, next: Next) -> Response {
+    let correlation_id = request
+        .headers()
+        .get(CORRELATION_ID)
+        .and_then(|id| id.to_str().ok())
+        .map_or_else(|| uuid::Uuid::new_v4().to_string(), str::to_string);
+
+    let span = tracing::info_span!("http", correlation_id = %correlation_id);
+    let mut response = next.run(request).instrument(span).await;
+    if let Ok(value) = HeaderValue::from_str(&correlation_id) {
+        response.headers_mut().insert(CORRELATION_ID, value);
+    }
+    response
+}
+
+/// Origin usage is counted under: the authenticated caller, or `http`
+fn origin(caller: Option<Extension<Caller>>) -> String {
+    caller.map_or_else(|| "http".to_string(), |Extension(caller)| caller.name)
+}
+
+fn respond(result: Result<serde_json::Value>) -> (StatusCode, Json<ApiResponse>) {
+    match result {
+        Ok(result) => (
+            StatusCode::OK,
+            Json(ApiResponse {
+                success: true,
+                result: Some(result),
+                error: None,
+                code: None,
+                details: None,
+            }),
+        ),
+        Err(e) => ApiResponse::error(&e),
+    }
+}
+
+/// Run a command
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/commands/{command_type}",
+    params(
+        ("command_type" = String, Path, description = "Command such as `explain_concept`"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first result back"),
+    ),
+    request_body(content = Object, description = "Command payload, as sent over NATS"),
+    responses(
+        (status = 200, description = "Command result", body = ApiResponse),
+        (status = 400, description = "Unknown command or missing parameter", body = ApiResponse),
+    )
+))]
+async fn run_command(
+    State(agent): State<Arc<AlchemistAgent>>,
+    caller: Option<Extension<Caller>>,
+    Path(command_type): Path<String>,
+    headers: HeaderMap,
+    Json(payload): Json<serde_json::Value>,
+) -> (StatusCode, Json<ApiResponse>) {
+    if let Some(Extension(caller)) = caller.as_ref().filter(|_| command_type == "batch") {
+        let scope = auth::batch_scope(&payload);
+        if !caller.allows(scope) {
+            tracing::warn!(target: "audit", caller = %caller.name, "Batch denied: needs {:?} scope", scope);
+            let denied = AgentError::PermissionDenied(format!("This batch requires the {:?} scope", scope));
+            return ApiResponse::error(&denied);
+        }
+    }
+
+    let idempotency_key = headers.get(IDEMPOTENCY_KEY).and_then(|key| key.to_str().ok());
+    respond(
+        agent
+            .process_idempotent_command(&origin(caller), idempotency_key, None, &command_type, payload)
+            .await,
+    )
+}
+
+/// Run a query
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/queries/{query_type}",
+    params(("query_type" = String, Path, description = "Query such as `list_concepts`")),
+    request_body(content = Object, description = "Query parameters, as sent over NATS"),
+    responses(
+        (status = 200, description = "Query result", body = ApiResponse),
+        (status = 400, description = "Unknown query or missing parameter", body = ApiResponse),
+    )
+))]
+async fn run_query(
+    State(agent): State<Arc<AlchemistAgent>>,
+    caller: Option<Extension<Caller>>,
+    Path(query_type): Path<String>,
+    Json(parameters): Json<serde_json::Value>,
+) -> (StatusCode, Json<ApiResponse>) {
+    respond(agent.process_query_from(&origin(caller), &query_type, parameters).await)
+}
+
+/// Send a dialog message and wait for the reply
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/dialogs/{dialog_id}/messages",
+    params(("dialog_id" = String, Path, description = "Dialog to continue or start")),
+    request_body = DialogMessageRequest,
+    responses(
+        (status = 200, description = "The agent's reply", body = DialogReply),
+        (status = 500, description = "The message could not be answered", body = ApiResponse),
+    )
+))]
+async fn send_dialog_message(
+    State(agent): State<Arc<AlchemistAgent>>,
+    caller: Option<Extension<Caller>>,
+    Path(dialog_id): Path<String>,
+    Json(request): Json<DialogMessageRequest>,
+) -> std::result::Result<Json<DialogReply>, (StatusCode, Json<ApiResponse>)> {
+    // An authenticated caller is the origin, whatever the message claims
+    let mut metadata = request.metadata;
+    if metadata.is_null() {
+        metadata = serde_json::json!({});
+    }
+    if let Some(fields) = metadata.as_object_mut() {
+        match caller {
+            Some(Extension(caller)) => {
+                fields.insert("origin".to_string(), serde_json::json!(caller.name));
+            }
+            None => {
+                fields.entry("origin").or_insert_with(|| serde_json::json!("http"));
+            }
+        }
+    }
+
+    let message = DialogMessage {
+        dialog_id,
+        content: request.content,
+        metadata,
+        timestamp: chrono::Utc::now(),
+    };
+
+    agent
+        .reply_within_deadline(message)
+        .await
+        .map(Json)
+        .map_err(|e| ApiResponse::error(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_matches_nats() {
+        let (code, Json(response)) = respond(Err(AgentError::InvalidRequest("Unknown query: nope".to_string())));
+
+        assert_eq!(code, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::to_value(response).unwrap(),
+            crate::nats_integration::response_envelope(&Err(AgentError::InvalidRequest(
+                "Unknown query: nope".to_string()
+            )))
+        );
+    }
+
+    #[cfg(feature = "openapi")]
+    #[test]
+    fn test_openapi_lists_every_route() {
+        let document: serde_json::Value = serde_json::from_str(&openapi_json().unwrap()).unwrap();
+
+        assert!(document["openapi"].as_str().unwrap().starts_with("3.1"));
+        for path in [
+            "/api/commands/{command_type}",
+            "/api/queries/{query_type}",
+            "/api/dialogs/{dialog_id}/messages",
+        ] {
+            assert!(document["paths"].get(path).is_some(), "missing {}", path);
+        }
+    }
+}
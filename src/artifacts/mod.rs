@@ -0,0 +1,81 @@
+//! Artifact storage for exports, ingested documents, and snapshots
+//!
+//! Artifacts are opaque blobs under slash-separated keys. They go to a NATS
+//! object store bucket or, with the `s3` feature, to an S3-compatible bucket
+//! such as MinIO, chosen by `storage.artifacts` in the configuration.
+
+pub mod nats;
+#[cfg(feature = "s3")]
+pub mod s3;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::config::{ArtifactBackend, NatsConfig};
+use crate::error::Result;
+
+/// Key prefix of dialog exports
+pub const EXPORTS: &str = "exports/";
+
+/// Key prefix of ingested documents
+pub const DOCUMENTS: &str = "documents/";
+
+/// Key prefix of state snapshots
+pub const SNAPSHOTS: &str = "snapshots/";
+
+/// Key prefix of domain scaffolds from `implement_domain` workflows
+pub const SCAFFOLDS: &str = "scaffolds/";
+
+/// Blob storage addressed by key
+#[async_trait]
+pub trait ArtifactStore: Send + Sync {
+    /// Store `data` under `key`, replacing what was there
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()>;
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Keys starting with `prefix`
+    async fn list(&self, prefix: &str) -> Result<Vec<String>>;
+
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// Connect to the configured artifact backend
+///
+/// The NATS object store uses its own connection, so `nats` is only
+/// needed for that backend.
+pub async fn open(backend: &ArtifactBackend, nats: &NatsConfig) -> Result<Arc<dyn ArtifactStore>> {
+    match backend {
+        ArtifactBackend::ObjectStore { bucket } => {
+            Ok(Arc::new(nats::NatsArtifactStore::connect(nats, bucket).await?))
+        }
+        ArtifactBackend::S3 { .. } => open_s3(backend),
+    }
+}
+
+#[cfg(feature = "s3")]
+fn open_s3(backend: &ArtifactBackend) -> Result<Arc<dyn ArtifactStore>> {
+    Ok(Arc::new(s3::S3ArtifactStore::new(backend)?))
+}
+
+#[cfg(not(feature = "s3"))]
+fn open_s3(_backend: &ArtifactBackend) -> Result<Arc<dyn ArtifactStore>> {
+    Err(crate::error::AgentError::Configuration(
+        "S3 artifact storage is configured but the agent was built without the `s3` feature".to_string(),
+    ))
+}
+
+/// Key of a dialog export
+pub fn export_key(dialog_id: &str, extension: &str) -> String {
+    format!("{}{}.{}", EXPORTS, dialog_id, extension)
+}
+
+/// Key prefix of one workflow's scaffold files
+pub fn scaffold_prefix(workflow_id: &str) -> String {
+    format!("{}{}/", SCAFFOLDS, workflow_id)
+}
+
+/// Key of a scaffold file at `path` in the domain crate
+pub fn scaffold_key(workflow_id: &str, path: &str) -> String {
+    format!("{}{}", scaffold_prefix(workflow_id), path)
+}
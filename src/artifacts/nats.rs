@@ -0,0 +1,82 @@
+//! Artifacts in a NATS JetStream object store
+
+use async_nats::jetstream::object_store::{self, GetErrorKind, ObjectStore};
+use async_trait::async_trait;
+use futures::StreamExt;
+use tokio::io::AsyncReadExt;
+
+use super::ArtifactStore;
+use crate::config::NatsConfig;
+use crate::error::{AgentError, Result};
+
+fn storage_error(action: &str, error: impl std::fmt::Display) -> AgentError {
+    AgentError::Storage(format!("Object store {} failed: {}", action, error))
+}
+
+/// Keeps artifacts in an object store bucket
+pub struct NatsArtifactStore {
+    store: ObjectStore,
+}
+
+impl NatsArtifactStore {
+    /// Open `bucket`, creating it if it does not exist
+    pub async fn connect(config: &NatsConfig, bucket: &str) -> Result<Self> {
+        let client = crate::nats_integration::connect(config).await?;
+        let jetstream = async_nats::jetstream::new(client);
+
+        let store = match jetstream.get_object_store(bucket).await {
+            Ok(store) => store,
+            Err(_) => jetstream
+                .create_object_store(object_store::Config {
+                    bucket: bucket.to_string(),
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| storage_error("bucket creation", e))?,
+        };
+
+        Ok(Self { store })
+    }
+}
+
+#[async_trait]
+impl ArtifactStore for NatsArtifactStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        self.store
+            .put(key, &mut data.as_slice())
+            .await
+            .map_err(|e| storage_error("put", e))?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let mut object = match self.store.get(key).await {
+            Ok(object) => object,
+            Err(e) if e.kind() == GetErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(storage_error("get", e)),
+        };
+
+        let mut data = Vec::new();
+        object.read_to_end(&mut data).await?;
+        Ok(Some(data))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut objects = self.store.list().await.map_err(|e| storage_error("list", e))?;
+
+        let mut keys = Vec::new();
+        while let Some(info) = objects.next().await {
+            let info = info.map_err(|e| storage_error("list", e))?;
+            if !info.deleted && info.name.starts_with(prefix) {
+                keys.push(info.name);
+            }
+        }
+
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.store.delete(key).await.map_err(|e| storage_error("delete", e))
+    }
+}
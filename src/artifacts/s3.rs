@@ -0,0 +1,107 @@
+//! Artifacts in S3 or an S3-compatible service such as MinIO
+
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path;
+use object_store::prefix::PrefixStore;
+use object_store::{ObjectStore, PutPayload};
+
+use super::ArtifactStore;
+use crate::config::ArtifactBackend;
+use crate::error::{AgentError, Result};
+
+impl From<object_store::Error> for AgentError {
+    fn from(error: object_store::Error) -> Self {
+        AgentError::Storage(format!("S3: {}", error))
+    }
+}
+
+/// Keeps artifacts in an S3 bucket, optionally under a key prefix
+pub struct S3ArtifactStore {
+    store: Box<dyn ObjectStore>,
+}
+
+impl S3ArtifactStore {
+    /// Build a store for an `S3` backend configuration
+    pub fn new(backend: &ArtifactBackend) -> Result<Self> {
+        let ArtifactBackend::S3 {
+            bucket,
+            region,
+            endpoint,
+            access_key_id,
+            secret_access_key,
+            prefix,
+        } = backend
+        else {
+            return Err(AgentError::Configuration("Not an S3 artifact backend".to_string()));
+        };
+
+        // Credentials fall back to the usual AWS_* environment variables
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .with_region(region);
+        if let Some(endpoint) = endpoint {
+            // MinIO and friends often run without TLS inside a cluster
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+        if let Some(access_key_id) = access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key);
+        }
+
+        let s3 = builder.build()?;
+        let store: Box<dyn ObjectStore> = match prefix {
+            Some(prefix) => Box::new(PrefixStore::new(s3, prefix.as_str())),
+            None => Box::new(s3),
+        };
+
+        Ok(Self { store })
+    }
+}
+
+fn path(key: &str) -> Result<Path> {
+    Path::parse(key).map_err(|e| AgentError::InvalidRequest(format!("Invalid artifact key {}: {}", key, e)))
+}
+
+#[async_trait]
+impl ArtifactStore for S3ArtifactStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        self.store.put(&path(key)?, PutPayload::from(data)).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match self.store.get(&path(key)?).await {
+            Ok(object) => Ok(Some(object.bytes().await?.to_vec())),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        // S3 lists by whole path segments; narrow to the exact prefix after
+        let directory = prefix.rsplit_once('/').map(|(directory, _)| directory).unwrap_or("");
+        let directory = if directory.is_empty() { None } else { Some(path(directory)?) };
+
+        let mut keys: Vec<String> = self
+            .store
+            .list(directory.as_ref())
+            .map_ok(|meta| meta.location.to_string())
+            .try_filter(|key| futures::future::ready(key.starts_with(prefix)))
+            .try_collect()
+            .await?;
+
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.store.delete(&path(key)?).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+//! Authentication for the HTTP API
+//!
+//! With `service.api_auth` configured, every API request must carry an API
+//! key, in `X-API-Key` or as a bearer token, or an OIDC access token as a
+//! bearer token. Queries and `/ws/events` need the `Read` scope, commands
+//! and dialog messages `Command`, and administrative commands `Admin`.
+//! Missing or invalid credentials get 401, insufficient scopes 403.
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use crate::api::ApiResponse;
+use crate::config::{ApiAuthConfig, ApiKeyConfig, ApiScope};
+use crate::error::{AgentError, Result};
+
+/// Commands that change how the agent runs for everyone
+pub const ADMIN_COMMANDS: &[&str] = &["switch_model", "announce", "replay_stream", "inject_faults", "clear_faults"];
+
+/// A caller whose credentials checked out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caller {
+    /// API key name or token subject
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+}
+
+impl Caller {
+    /// Whether the caller holds `scope` or one that includes it
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.scopes.iter().any(|granted| *granted >= scope)
+    }
+}
+
+/// Checks the credentials on API requests
+pub struct ApiAuth {
+    api_keys: Vec<ApiKeyConfig>,
+    #[cfg(feature = "oidc")]
+    oidc: Option<oidc::OidcValidator>,
+}
+
+impl ApiAuth {
+    /// Fails if OIDC is configured without the `oidc` feature, or with an
+    /// unknown algorithm
+    pub fn new(config: &ApiAuthConfig) -> Result<Self> {
+        #[cfg(not(feature = "oidc"))]
+        if config.oidc.is_some() {
+            return Err(AgentError::Configuration(
+                "OIDC is configured but the agent was built without the `oidc` feature".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            api_keys: config.api_keys.clone(),
+            #[cfg(feature = "oidc")]
+            oidc: config.oidc.clone().map(oidc::OidcValidator::new).transpose()?,
+        })
+    }
+
+    /// The caller presenting `headers`, or `None` without valid credentials
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Option<Caller> {
+        let bearer = header(headers, "authorization").and_then(|value| value.strip_prefix("Bearer "));
+        let presented = header(headers, "x-api-key").or(bearer)?.trim();
+
+        if let Some(key) = self.api_keys.iter().find(|key| constant_time_eq(&key.key, presented)) {
+            return Some(Caller {
+                name: key.name.clone(),
+                scopes: key.scopes.clone(),
+            });
+        }
+
+        #[cfg(feature = "oidc")]
+        if let (Some(oidc), Some(token)) = (&self.oidc, bearer) {
+            match oidc.validate(token.trim()).await {
+                Ok(caller) => return Some(caller),
+                Err(e) => debug!("Rejected bearer token: {}", e),
+            }
+        }
+
+        None
+    }
+}
+
+/// Scope needed for an API path
+pub fn required_scope(path: &str) -> ApiScope {
+    if path.starts_with("/api/queries/") {
+        return ApiScope::Read;
+    }
+
+    match path.strip_prefix("/api/commands/") {
+        Some(command) if ADMIN_COMMANDS.contains(&command) => ApiScope::Admin,
+        _ => ApiScope::Command,
+    }
+}
+
+/// Scope needed for the commands inside a `batch` payload
+///
+/// The batch path itself only needs [`ApiScope::Command`], so administrative
+/// commands cannot be slipped in through it.
+pub fn batch_scope(payload: &serde_json::Value) -> ApiScope {
+    let commands = payload["commands"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let has_admin = commands
+        .iter()
+        .filter_map(|item| item["command_type"].as_str())
+        .any(|command| ADMIN_COMMANDS.contains(&command));
+    if has_admin {
+        ApiScope::Admin
+    } else {
+        ApiScope::Command
+    }
+}
+
+/// Middleware rejecting requests without credentials for their path
+///
+/// Handlers find the authenticated [`Caller`] in the request extensions.
+pub async fn require_scope(State(auth): State<Arc<ApiAuth>>, mut request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let Some(caller) = auth.authenticate(request.headers()).await else {
+        debug!("Unauthenticated request to {}", path);
+        let denied = ApiResponse::from_error(&AgentError::PermissionDenied("Missing or invalid credentials".to_string()));
+        return (StatusCode::UNAUTHORIZED, Json(denied)).into_response();
+    };
+
+    let scope = required_scope(&path);
+    if !caller.allows(scope) {
+        warn!(target: "audit", caller = %caller.name, path = %path, "API request denied: needs {:?} scope", scope);
+        let denied = AgentError::PermissionDenied(format!("{} requires the {:?} scope", path, scope));
+        return (StatusCode::FORBIDDEN, Json(ApiResponse::from_error(&denied))).into_response();
+    }
+
+    debug!(caller = %caller.name, "Authorized request to {}", path);
+    request.extensions_mut().insert(caller);
+    next.run(request).await
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Compare keys without leaking how much of a guess was right
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(feature = "oidc")]
+mod oidc {
+    use jsonwebtoken::jwk::{Jwk, JwkSet};
+    use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+    use std::str::FromStr;
+    use std::time::{Duration, Instant};
+    use tokio::sync::{Mutex, RwLock};
+
+    use super::Caller;
+    use crate::config::{ApiScope, OidcConfig};
+    use crate::error::{AgentError, Result};
+
+    /// Least time between fetches of the issuer's keys, so tokens naming
+    /// unknown keys cannot make the agent fetch on every request
+    pub const KEY_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Validates access tokens against the issuer's published keys
+    pub struct OidcValidator {
+        config: OidcConfig,
+        algorithms: Vec<Algorithm>,
+        http: reqwest::Client,
+        keys: RwLock<Option<JwkSet>>,
+
+        /// When the keys were last fetched; held while fetching
+        fetched_at: Mutex<Option<Instant>>,
+    }
+
+    impl OidcValidator {
+        /// Fails if an algorithm in `config.algorithms` is unknown
+        pub fn new(config: OidcConfig) -> Result<Self> {
+            let algorithms = config
+                .algorithms
+                .iter()
+                .map(|name| {
+                    Algorithm::from_str(name)
+                        .map_err(|_| AgentError::Configuration(format!("Unknown OIDC signing algorithm {}", name)))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if algorithms.is_empty() {
+                return Err(AgentError::Configuration("OIDC needs at least one signing algorithm".to_string()));
+            }
+
+            Ok(Self {
+                config,
+                algorithms,
+                http: reqwest::Client::new(),
+                keys: RwLock::new(None),
+                fetched_at: Mutex::new(None),
+            })
+        }
+
+        pub async fn validate(&self, token: &str) -> Result<Caller> {
+            let header = decode_header(token).map_err(invalid)?;
+            let kid = header
+                .kid
+                .ok_or_else(|| AgentError::PermissionDenied("Token has no key ID".to_string()))?;
+
+            let jwk = match self.known_key(&kid).await {
+                Some(jwk) => jwk,
+                None => self.refetch_key(&kid).await?,
+            };
+
+            let mut validation = Validation::new(algorithm(header.alg, &jwk, &self.algorithms)?);
+            validation.set_issuer(&[&self.config.issuer]);
+            validation.set_audience(&[&self.config.audience]);
+            let key = DecodingKey::from_jwk(&jwk).map_err(invalid)?;
+            let claims = decode::<serde_json::Value>(token, &key, &validation).map_err(invalid)?.claims;
+
+            Ok(Caller {
+                name: claims["sub"].as_str().unwrap_or("unknown").to_string(),
+                scopes: scopes(&claims[&self.config.scopes_claim]),
+            })
+        }
+
+        async fn known_key(&self, kid: &str) -> Option<Jwk> {
+            self.keys.read().await.as_ref().and_then(|keys| keys.find(kid).cloned())
+        }
+
+        /// Fetch the keys again for an unknown `kid`, in case the issuer
+        /// rotated them, at most once per [`KEY_REFETCH_INTERVAL`]
+        async fn refetch_key(&self, kid: &str) -> Result<Jwk> {
+            let unknown = || AgentError::PermissionDenied(format!("Unknown signing key {}", kid));
+            let mut fetched_at = self.fetched_at.lock().await;
+            // Another request may have fetched them while this one waited
+            if let Some(jwk) = self.known_key(kid).await {
+                return Ok(jwk);
+            }
+            if !refetch_due(*fetched_at, Instant::now()) {
+                return Err(unknown());
+            }
+
+            *fetched_at = Some(Instant::now());
+            let keys = self.fetch_keys().await?;
+            let jwk = keys.find(kid).cloned();
+            *self.keys.write().await = Some(keys);
+            jwk.ok_or_else(unknown)
+        }
+
+        async fn fetch_keys(&self) -> Result<JwkSet> {
+            let discovery = format!("{}/.well-known/openid-configuration", self.config.issuer.trim_end_matches('/'));
+            let document: serde_json::Value = self.http.get(&discovery).send().await?.error_for_status()?.json().await?;
+            let jwks_uri = document["jwks_uri"]
+                .as_str()
+                .ok_or_else(|| AgentError::Configuration(format!("No jwks_uri in {}", discovery)))?;
+
+            Ok(self.http.get(jwks_uri).send().await?.error_for_status()?.json().await?)
+        }
+    }
+
+    /// Whether keys last fetched at `fetched_at` may be fetched again `now`
+    pub fn refetch_due(fetched_at: Option<Instant>, now: Instant) -> bool {
+        fetched_at.map_or(true, |fetched_at| now.duration_since(fetched_at) >= KEY_REFETCH_INTERVAL)
+    }
+
+    /// The algorithm to check a token with: the one its header names, if
+    /// it is `allowed` and the key, when the key names one, was made for it
+    pub fn algorithm(named: Algorithm, jwk: &Jwk, allowed: &[Algorithm]) -> Result<Algorithm> {
+        if !allowed.contains(&named) {
+            return Err(AgentError::PermissionDenied(format!("Token algorithm {:?} is not accepted", named)));
+        }
+        if let Some(key_algorithm) = jwk.common.key_algorithm {
+            // Both name algorithms the same way, such as "RS256"
+            if serde_json::to_value(key_algorithm).ok() != serde_json::to_value(named).ok() {
+                return Err(AgentError::PermissionDenied(format!(
+                    "Token algorithm {:?} does not match its key's {:?}",
+                    named, key_algorithm
+                )));
+            }
+        }
+        Ok(named)
+    }
+
+    /// Scopes named in a claim, ignoring ones the agent does not know
+    pub fn scopes(claim: &serde_json::Value) -> Vec<ApiScope> {
+        let names: Vec<&str> = match claim {
+            serde_json::Value::String(names) => names.split_whitespace().collect(),
+            serde_json::Value::Array(names) => names.iter().filter_map(|name| name.as_str()).collect(),
+            _ => Vec::new(),
+        };
+
+        names
+            .into_iter()
+            .filter_map(|name| match name.to_lowercase().as_str() {
+                "read" => Some(ApiScope::Read),
+                "command" => Some(ApiScope::Command),
+                "admin" => Some(ApiScope::Admin),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn invalid(e: jsonwebtoken::errors::Error) -> AgentError {
+        AgentError::PermissionDenied(format!("Invalid token: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth() -> ApiAuth {
+        ApiAuth::new(&ApiAuthConfig {
+            api_keys: vec![ApiKeyConfig {
+                name: "dashboard".to_string(),
+                key: "read-key".to_string(),
+                scopes: vec![ApiScope::Read],
+            }],
+            oidc: None,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_api_key_from_either_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(auth().authenticate(&headers).await, None);
+
+        headers.insert("x-api-key", "read-key".parse().unwrap());
+        assert_eq!(auth().authenticate(&headers).await.map(|caller| caller.name), Some("dashboard".to_string()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer read-key".parse().unwrap());
+        assert!(auth().authenticate(&headers).await.is_some());
+
+        headers.insert("authorization", "Bearer wrong-key".parse().unwrap());
+        assert_eq!(auth().authenticate(&headers).await, None);
+    }
+
+    #[test]
+    fn test_scopes_by_path() {
+        let reader = Caller {
+            name: "dashboard".to_string(),
+            scopes: vec![ApiScope::Read],
+        };
+        let admin = Caller {
+            name: "ops".to_string(),
+            scopes: vec![ApiScope::Admin],
+        };
+
+        assert!(reader.allows(required_scope("/api/queries/list_concepts")));
+        assert!(!reader.allows(required_scope("/api/commands/explain_concept")));
+        assert!(!reader.allows(required_scope("/api/dialogs/d1/messages")));
+        assert_eq!(required_scope("/api/commands/switch_model"), ApiScope::Admin);
+        assert!(admin.allows(required_scope("/api/commands/switch_model")));
+        assert!(admin.allows(required_scope("/api/queries/list_concepts")));
+
+        let batch = serde_json::json!({ "commands": [
+            { "command_type": "explain_concept", "payload": { "concept": "CQRS" } },
+            { "command_type": "switch_model", "payload": { "model": "llama3" } },
+        ]});
+        assert_eq!(batch_scope(&batch), ApiScope::Admin);
+        assert_eq!(batch_scope(&serde_json::json!({ "commands": [] })), ApiScope::Command);
+    }
+
+    #[cfg(feature = "oidc")]
+    #[test]
+    fn test_oidc_algorithms_and_refetches() {
+        use jsonwebtoken::Algorithm;
+        use std::time::{Duration, Instant};
+
+        let jwk = |alg: Option<&str>| {
+            let mut jwk = serde_json::json!({ "kty": "RSA", "kid": "k1", "n": "sXchDaQebHnPiGvyDOAT4saGEUetSyo9MKLOoWFsueri23bOdgWp4Dy1WlUzewbgBHod5pcM9H95GQRV3JDXboIRROSBigeC5yjU1hGzHHyXss8UDprecbAYxknTcQkhslANGRUZmdTOQ5qTRsLAt6BTYuyvVRdhS8exSZEy_c4gs_7svlJJQ4H9_NxsiIoLwAEk7-Q3UXERGYw_75IDrGA84-lA_-Ct4eTlXHBIY2EaV7t7LjJaynVJCpkv4LKjTTAumiGUIuQhrNhZLuF_RJLqHpM2kgWFLU7-VTdL1VbC2tejvcI2BlMkEpk1BzBZI0KQB0GaDWFLN-aEAw3vRw", "e": "AQAB" });
+            if let Some(alg) = alg {
+                jwk["alg"] = serde_json::json!(alg);
+            }
+            serde_json::from_value::<jsonwebtoken::jwk::Jwk>(jwk).unwrap()
+        };
+        let allowed = [Algorithm::RS256];
+
+        assert_eq!(oidc::algorithm(Algorithm::RS256, &jwk(Some("RS256")), &allowed).unwrap(), Algorithm::RS256);
+        assert!(oidc::algorithm(Algorithm::RS256, &jwk(None), &allowed).is_ok());
+        // A header cannot switch the key to a shared-secret algorithm
+        assert!(oidc::algorithm(Algorithm::HS256, &jwk(None), &allowed).is_err());
+        assert!(oidc::algorithm(Algorithm::RS384, &jwk(Some("RS256")), &[Algorithm::RS256, Algorithm::RS384]).is_err());
+
+        let now = Instant::now();
+        assert!(oidc::refetch_due(None, now));
+        assert!(!oidc::refetch_due(Some(now), now + Duration::from_secs(1)));
+        assert!(oidc::refetch_due(Some(now), now + oidc::KEY_REFETCH_INTERVAL));
+    }
+}
@@ -0,0 +1,459 @@
+//! Replays of persisted traffic from JetStream, for `replay_stream`
+//!
+//! With `nats.jetstream` configured, the agent's stream keeps every command
+//! sent on its served `commands.>` subjects and every dialog message sent on
+//! `cim.dialog.alchemist.>`. `replay_stream` reads back those published
+//! between `from` and `to`, oldest first. By default it is a dry run that
+//! reports what would be replayed; with `apply`, each command runs again and
+//! each dialog message is answered again by this agent, to rebuild
+//! projections after a bug fix or to fill a newly configured storage backend.
+//!
+//! Replays do not go through NATS, so the stream does not capture them a
+//! second time and the original senders get no replies. Administrative
+//! commands, `replay_stream` itself among them, are left out, also from
+//! inside a `batch`, as are the agent's own dialog messages. The stream
+//! keeps commands the agent refused too, so with `command_signing`
+//! configured each command is checked again as it was stored, and those
+//! failing are left out. Applying a range the agent already holds adds it
+//! again: point the replay at fresh storage or a range it lacks.
+
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use crate::auth::ADMIN_COMMANDS;
+use crate::config::NatsConfig;
+use crate::error::{AgentError, Result};
+use crate::nats_integration::{served_subjects, subjects, AgentCommand, DialogMessage, AGENT_SENDER};
+use crate::topology::captures;
+
+/// Most messages one replay reads; a longer range is resumed from
+/// `resume_from`
+pub const MAX_REPLAY_MESSAGES: usize = 10_000;
+
+/// How long JetStream gets to deliver the next message of the range
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A command or dialog message as the stream kept it
+#[derive(Debug, Clone)]
+pub enum Persisted {
+    Command(AgentCommand),
+    Dialog(DialogMessage),
+}
+
+impl Persisted {
+    /// Read the payload of a message captured on `subject`, or none if it
+    /// is not a command or dialog message
+    pub fn parse(subject: &str, payload: &[u8]) -> Option<Self> {
+        if captures(subjects::DIALOG, subject) {
+            serde_json::from_slice(payload).ok().map(Self::Dialog)
+        } else {
+            serde_json::from_slice(payload).ok().map(Self::Command)
+        }
+    }
+
+    /// What applying the replay runs again, if anything
+    ///
+    /// Administrative commands change how the agent runs rather than what
+    /// it knows, so they are never replayed, and a `batch` is replayed
+    /// without the ones it holds.
+    pub fn replayable(&self) -> Option<Self> {
+        match self {
+            Self::Command(command) if is_admin(&command.command_type) => None,
+            Self::Command(command) if command.command_type == "batch" => {
+                let items = command.payload["commands"].as_array()?;
+                let kept: Vec<serde_json::Value> = items
+                    .iter()
+                    .filter(|item| !is_admin(item["command_type"].as_str().unwrap_or_default()))
+                    .cloned()
+                    .collect();
+                if kept.is_empty() {
+                    return None;
+                }
+                let mut command = command.clone();
+                command.payload["commands"] = serde_json::Value::Array(kept);
+                Some(Self::Command(command))
+            }
+            Self::Command(_) => Some(self.clone()),
+            Self::Dialog(message) if message.sender == AGENT_SENDER => None,
+            Self::Dialog(_) => Some(self.clone()),
+        }
+    }
+}
+
+fn is_admin(command_type: &str) -> bool {
+    ADMIN_COMMANDS.contains(&command_type)
+}
+
+/// One message of the replayed range
+#[derive(Debug, Clone)]
+pub struct StreamEntry {
+    /// Stream sequence, to find the message again
+    pub sequence: u64,
+    pub published: chrono::DateTime<chrono::Utc>,
+    pub entry: Persisted,
+}
+
+/// The messages of a time range
+#[derive(Debug, Clone, Default)]
+pub struct StreamRange {
+    pub entries: Vec<StreamEntry>,
+
+    /// Messages on the replayed subjects that could not be read
+    pub unreadable: usize,
+
+    /// Commands left out for failing the signature check
+    pub unverified: usize,
+
+    /// Publication time of the first message left unread when the range
+    /// holds more than [`MAX_REPLAY_MESSAGES`]
+    pub resume_from: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl StreamRange {
+    /// Leave out the commands `check` refuses, given when each was
+    /// published, as the agent refused them when they were sent
+    ///
+    /// A `batch` is checked as it was stored, before any administrative
+    /// commands are dropped from it, since its signature covers every
+    /// command it holds.
+    pub fn drop_unverified(&mut self, check: impl Fn(&AgentCommand, chrono::DateTime<chrono::Utc>) -> Result<()>) {
+        let before = self.entries.len();
+        self.entries.retain(|stream_entry| match &stream_entry.entry {
+            Persisted::Command(command) => match check(command, stream_entry.published) {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::warn!(target: "audit", "Not replaying message {}: {}", stream_entry.sequence, e);
+                    false
+                }
+            },
+            Persisted::Dialog(_) => true,
+        });
+        self.unverified += before - self.entries.len();
+    }
+}
+
+/// A replayed message that failed again
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayFailure {
+    pub sequence: u64,
+
+    /// Command type, or `dialog_message`
+    pub kind: String,
+
+    pub error: String,
+}
+
+/// What a replay found and, when applied, how it went
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReplayReport {
+    pub from: chrono::DateTime<chrono::Utc>,
+    pub to: chrono::DateTime<chrono::Utc>,
+    pub applied: bool,
+
+    /// Replayable commands by type
+    pub commands: BTreeMap<String, usize>,
+
+    pub dialog_messages: usize,
+
+    /// Dialogs the replayable messages belong to
+    pub dialogs: usize,
+
+    /// Administrative commands, the agent's own messages, unreadable
+    /// messages, and commands failing the signature check
+    pub skipped: usize,
+
+    /// Messages run again, successfully or not
+    pub replayed: usize,
+
+    pub failures: Vec<ReplayFailure>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resume_from: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ReplayReport {
+    /// The dry run of `range`: what applying it would replay
+    pub fn plan(from: chrono::DateTime<chrono::Utc>, to: chrono::DateTime<chrono::Utc>, range: &StreamRange) -> Self {
+        let mut report = Self {
+            from,
+            to,
+            skipped: range.unreadable + range.unverified,
+            resume_from: range.resume_from,
+            ..Default::default()
+        };
+        let mut dialogs = BTreeSet::new();
+        for stream_entry in &range.entries {
+            let Some(entry) = stream_entry.entry.replayable() else {
+                report.skipped += 1;
+                continue;
+            };
+            match &entry {
+                Persisted::Command(command) => {
+                    *report.commands.entry(command.command_type.clone()).or_default() += 1;
+                    if let Some(dialog_id) = command.payload["dialog_id"].as_str() {
+                        dialogs.insert(dialog_id.to_string());
+                    }
+                }
+                Persisted::Dialog(message) => {
+                    report.dialog_messages += 1;
+                    dialogs.insert(message.dialog_id.clone());
+                }
+            }
+        }
+        report.dialogs = dialogs.len();
+        report
+    }
+
+    /// Count a replayed message, with its failure if it failed
+    pub fn record(&mut self, stream_entry: &StreamEntry, outcome: Result<()>) {
+        self.replayed += 1;
+        if let Err(e) = outcome {
+            let kind = match &stream_entry.entry {
+                Persisted::Command(command) => command.command_type.clone(),
+                Persisted::Dialog(_) => "dialog_message".to_string(),
+            };
+            self.failures.push(ReplayFailure {
+                sequence: stream_entry.sequence,
+                kind,
+                error: e.to_string(),
+            });
+        }
+    }
+}
+
+/// Subjects a replay reads: every served command subject and the dialog
+/// subject
+pub fn replayed_subjects(config: &NatsConfig) -> Vec<String> {
+    let mut replayed = served_subjects(&config.subject_prefix, &config.versions, config.serve_unversioned, "commands.>");
+    replayed.push(subjects::DIALOG.to_string());
+    replayed
+}
+
+fn stream_error(error: impl std::fmt::Display) -> AgentError {
+    AgentError::ServiceUnavailable(format!("Cannot read the JetStream stream: {}", error))
+}
+
+/// Read the commands and dialog messages the agent's stream captured
+/// between `from` and `to`
+pub async fn read(
+    config: &NatsConfig,
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+) -> Result<StreamRange> {
+    let js_config = config
+        .jetstream
+        .as_ref()
+        .ok_or_else(|| AgentError::Configuration("replay_stream needs nats.jetstream configured".to_string()))?;
+    let start_time = from
+        .timestamp_nanos_opt()
+        .and_then(|nanos| time::OffsetDateTime::from_unix_timestamp_nanos(nanos as i128).ok())
+        .ok_or_else(|| AgentError::InvalidRequest(format!("Cannot replay from {}", from)))?;
+
+    let client = crate::nats_integration::connect(config).await?;
+    let jetstream = async_nats::jetstream::new(client);
+    let stream = jetstream
+        .get_stream(&js_config.stream_name)
+        .await
+        .map_err(|e| stream_error(e))?;
+    let consumer = stream
+        .create_consumer(async_nats::jetstream::consumer::pull::OrderedConfig {
+            filter_subjects: replayed_subjects(config),
+            deliver_policy: async_nats::jetstream::consumer::DeliverPolicy::ByStartTime { start_time },
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| stream_error(e))?;
+
+    let mut range = StreamRange::default();
+    // An ordered consumer never ends, so stop once nothing is pending
+    if consumer.cached_info().num_pending == 0 {
+        return Ok(range);
+    }
+    let mut messages = consumer.messages().await.map_err(|e| stream_error(e))?;
+    loop {
+        let message = tokio::time::timeout(READ_TIMEOUT, messages.next())
+            .await
+            .map_err(|_| AgentError::Timeout("JetStream stopped delivering the replayed range".to_string()))?;
+        let Some(message) = message else {
+            break;
+        };
+        let message = message.map_err(|e| stream_error(e))?;
+        let info = message.info().map_err(|e| stream_error(e))?;
+        let published = chrono::DateTime::from_timestamp_nanos(info.published.unix_timestamp_nanos() as i64);
+        if published > to {
+            break;
+        }
+        if range.entries.len() + range.unreadable >= MAX_REPLAY_MESSAGES {
+            range.resume_from = Some(published);
+            break;
+        }
+
+        match Persisted::parse(message.subject.as_str(), &message.payload) {
+            Some(entry) => range.entries.push(StreamEntry {
+                sequence: info.stream_sequence,
+                published,
+                entry,
+            }),
+            None => range.unreadable += 1,
+        }
+        if info.pending == 0 {
+            break;
+        }
+    }
+
+    Ok(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn command(command_type: &str, payload: serde_json::Value) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "id": "cmd-1",
+            "command_type": command_type,
+            "payload": payload,
+            "timestamp": "2025-03-01T09:00:00Z",
+            "origin": "ops",
+        }))
+        .unwrap()
+    }
+
+    fn dialog_message(dialog_id: &str, sender: &str) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "dialog_id": dialog_id,
+            "content": "What is a saga?",
+            "sender": sender,
+            "metadata": {},
+            "timestamp": "2025-03-01T09:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_by_subject() {
+        let dialog = Persisted::parse("cim.dialog.alchemist.dlg-1", &dialog_message("dlg-1", "user")).unwrap();
+        assert!(matches!(dialog, Persisted::Dialog(ref message) if message.dialog_id == "dlg-1"));
+        assert!(dialog.replayable().is_some());
+        assert!(Persisted::parse("cim.dialog.alchemist.dlg-1", &dialog_message("dlg-1", AGENT_SENDER)).unwrap().replayable().is_none());
+
+        let import = Persisted::parse("cim.agent.alchemist.v1.commands.import_dialog", &command("import_dialog", json!({}))).unwrap();
+        assert!(matches!(import, Persisted::Command(ref command) if command.command_type == "import_dialog"));
+        assert!(import.replayable().is_some());
+        let replay = Persisted::parse("cim.agent.alchemist.commands.replay_stream", &command("replay_stream", json!({}))).unwrap();
+        assert!(replay.replayable().is_none());
+
+        assert!(Persisted::parse("cim.agent.alchemist.commands.x", b"not json").is_none());
+        assert!(Persisted::parse("cim.dialog.alchemist.dlg-1", &command("end_dialog", json!({}))).is_none());
+    }
+
+    #[test]
+    fn test_plan_and_record() {
+        let entry = |sequence: u64, subject: &str, payload: Vec<u8>| StreamEntry {
+            sequence,
+            published: chrono::Utc::now(),
+            entry: Persisted::parse(subject, &payload).unwrap(),
+        };
+        let range = StreamRange {
+            entries: vec![
+                entry(1, "cim.dialog.alchemist.dlg-1", dialog_message("dlg-1", "user")),
+                entry(2, "cim.dialog.alchemist.dlg-1", dialog_message("dlg-1", AGENT_SENDER)),
+                entry(3, "cim.agent.alchemist.commands.pin_context", command("pin_context", json!({"dialog_id": "dlg-2"}))),
+                entry(4, "cim.agent.alchemist.commands.pin_context", command("pin_context", json!({"dialog_id": "dlg-1"}))),
+                entry(5, "cim.agent.alchemist.commands.announce", command("announce", json!({"message": "hi"}))),
+            ],
+            unreadable: 1,
+            unverified: 0,
+            resume_from: None,
+        };
+
+        let now = chrono::Utc::now();
+        let mut report = ReplayReport::plan(now - chrono::Duration::hours(1), now, &range);
+        assert_eq!(report.commands.get("pin_context"), Some(&2));
+        assert!(!report.commands.contains_key("announce"));
+        assert_eq!(report.dialog_messages, 1);
+        assert_eq!(report.dialogs, 2);
+        assert_eq!(report.skipped, 3);
+
+        report.record(&range.entries[0], Ok(()));
+        report.record(&range.entries[2], Err(AgentError::NotFound("dlg-2".to_string())));
+        assert_eq!(report.replayed, 2);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].sequence, 3);
+        assert_eq!(report.failures[0].kind, "pin_context");
+    }
+
+    #[test]
+    fn test_batches_replay_without_admin_commands() {
+        let subject = "cim.agent.alchemist.commands.batch";
+        let batch = Persisted::parse(subject, &command("batch", json!({"commands": [
+            {"command_type": "announce", "payload": {"message": "Knowledge base updated"}},
+            {"command_type": "pin_context", "payload": {"dialog_id": "dlg-1", "text": "We bill monthly"}},
+            {"command_type": "replay_stream", "payload": {"from": "2025-03-01T00:00:00Z", "apply": true}},
+            {"command_type": "inject_faults", "payload": {"drop_percent": 50}},
+        ]})))
+        .unwrap();
+        let Some(Persisted::Command(replayed)) = batch.replayable() else {
+            panic!("The batch was not replayed");
+        };
+        let items = replayed.payload["commands"].as_array().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["command_type"], "pin_context");
+
+        let admin_only = Persisted::parse(subject, &command("batch", json!({"commands": [
+            {"command_type": "switch_model", "payload": {"model": "llama3"}},
+            {"command_type": "replay_stream", "payload": {"from": "2025-03-01T00:00:00Z"}},
+        ]})))
+        .unwrap();
+        assert!(admin_only.replayable().is_none());
+
+        let range = StreamRange {
+            entries: vec![StreamEntry {
+                sequence: 1,
+                published: chrono::Utc::now(),
+                entry: admin_only,
+            }],
+            ..Default::default()
+        };
+        let now = chrono::Utc::now();
+        let report = ReplayReport::plan(now - chrono::Duration::hours(1), now, &range);
+        assert!(report.commands.is_empty());
+        assert_eq!(report.skipped, 1);
+    }
+
+    #[test]
+    fn test_unverified_commands_are_not_replayed() {
+        let entry = |sequence: u64, subject: &str, payload: Vec<u8>| StreamEntry {
+            sequence,
+            published: chrono::Utc::now(),
+            entry: Persisted::parse(subject, &payload).unwrap(),
+        };
+        let forged_batch = command("batch", json!({"commands": [
+            {"command_type": "pin_context", "payload": {"dialog_id": "dlg-1", "text": "We bill monthly"}},
+        ]}));
+        let mut range = StreamRange {
+            entries: vec![
+                entry(1, "cim.agent.alchemist.commands.pin_context", command("pin_context", json!({"dialog_id": "dlg-1"}))),
+                entry(2, "cim.agent.alchemist.commands.batch", forged_batch),
+                entry(3, "cim.dialog.alchemist.dlg-1", dialog_message("dlg-1", "user")),
+            ],
+            ..Default::default()
+        };
+
+        // Refuses every batch, as a check refuses a forged one
+        range.drop_unverified(|command, _| match command.command_type.as_str() {
+            "batch" => Err(AgentError::PermissionDenied(format!("Command {} is not signed", command.id))),
+            _ => Ok(()),
+        });
+        assert_eq!(range.entries.iter().map(|entry| entry.sequence).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(range.unverified, 1);
+
+        let now = chrono::Utc::now();
+        let report = ReplayReport::plan(now - chrono::Duration::hours(1), now, &range);
+        assert!(!report.commands.contains_key("batch"));
+        assert_eq!(report.skipped, 1);
+    }
+}
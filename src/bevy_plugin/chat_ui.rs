@@ -0,0 +1,391 @@
+//! Chat widget built from Bevy UI nodes
+//!
+//! A scrollable message list above a single-line input field. Typing edits
+//! the input, Enter sends it as an `AgentQuestionEvent`, and agent
+//! responses and errors are appended to the list as they arrive. Streamed
+//! chunks fill in a response line while the model is still generating.
+//! The input prompt reflects the agent status, and Enter is ignored until
+//! the agent is ready. Suggested follow-up questions appear as buttons
+//! under the latest response. A newly spawned widget starts with the
+//! conversation recorded so far.
+
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+
+use super::conversation::{AgentConversation, Speaker};
+use super::{
+    ask_agent, AgentChatUI, AgentErrorEvent, AgentInputField, AgentQuestionEvent,
+    AgentResponseChunkEvent, AgentResponseDisplay, AgentResponseEvent, AgentStatus,
+    AgentSuggestionsEvent, FollowUpSuggestion,
+};
+
+/// Pixels scrolled per mouse wheel line
+const LINE_HEIGHT: f32 = 20.0;
+
+const USER_COLOR: Color = Color::srgb(0.6, 0.8, 1.0);
+const AGENT_COLOR: Color = Color::srgb(0.6, 0.9, 0.6);
+const ERROR_COLOR: Color = Color::srgb(1.0, 0.5, 0.5);
+const BUSY_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+const SUGGESTION_COLOR: Color = Color::srgb(0.22, 0.26, 0.32);
+
+/// Text being typed into the chat input
+#[derive(Component, Default, Debug)]
+pub struct ChatInput {
+    pub buffer: String,
+}
+
+/// A response line still receiving streamed chunks
+#[derive(Component, Debug)]
+pub struct StreamingMessage {
+    pub question_id: String,
+}
+
+/// Row holding the current follow-up suggestion buttons
+#[derive(Component, Debug)]
+pub struct SuggestionRow;
+
+/// The chat widget: a root node holding the message list and input field
+///
+/// Spawned automatically by the plugin; spawn it yourself with
+/// `commands.spawn(chat_widget())` when `spawn_chat_ui` is disabled.
+pub fn chat_widget() -> impl Bundle {
+    (
+        AgentChatUI,
+        Name::new("Agent Chat UI"),
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(12.0)),
+            row_gap: Val::Px(8.0),
+            ..default()
+        },
+        children![
+            (
+                AgentResponseDisplay,
+                Name::new("Agent Messages"),
+                Node {
+                    flex_grow: 1.0,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.0),
+                    overflow: Overflow::scroll_y(),
+                    ..default()
+                },
+                ScrollPosition::default(),
+            ),
+            (
+                AgentInputField,
+                ChatInput::default(),
+                Name::new("Agent Input"),
+                Text::new("~ "),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                TextColor(BUSY_COLOR),
+                Node {
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb(0.15, 0.15, 0.18)),
+            ),
+        ],
+    )
+}
+
+/// Edit the input buffer from keyboard events and send on Enter
+pub(super) fn handle_chat_input(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut inputs: Query<(&mut ChatInput, &mut Text), With<AgentInputField>>,
+    mut questions: EventWriter<AgentQuestionEvent>,
+    mut commands: Commands,
+    lists: Query<Entity, With<AgentResponseDisplay>>,
+    status: Res<AgentStatus>,
+) {
+    let Ok((mut input, mut text)) = inputs.single_mut() else {
+        keyboard_events.clear();
+        return;
+    };
+
+    for event in keyboard_events.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+
+        match &event.logical_key {
+            // Keep the typed text until the agent can take it
+            Key::Enter if !status.is_ready() => {}
+            Key::Enter => {
+                let question = input.buffer.trim().to_string();
+                if !question.is_empty() {
+                    for list in &lists {
+                        push_message(&mut commands, list, format!("You: {}", question), USER_COLOR);
+                    }
+                    ask_agent(question, &mut questions);
+                }
+                input.buffer.clear();
+            }
+            Key::Backspace => {
+                input.buffer.pop();
+            }
+            Key::Space => input.buffer.push(' '),
+            Key::Character(chars) => {
+                input.buffer.extend(chars.chars().filter(|c| !c.is_control()));
+            }
+            _ => {}
+        }
+    }
+
+    text.0 = format!("{} {}", prompt(&status), input.buffer);
+}
+
+/// Input prompt for the agent status
+fn prompt(status: &AgentStatus) -> &'static str {
+    match status {
+        AgentStatus::Connecting => "~",
+        AgentStatus::Ready => ">",
+        AgentStatus::Generating => "…",
+        AgentStatus::Error(_) => "!",
+    }
+}
+
+/// Dim the input field while the agent cannot take questions
+pub(super) fn show_agent_status(
+    status: Res<AgentStatus>,
+    mut inputs: Query<&mut TextColor, With<AgentInputField>>,
+) {
+    let color = match *status {
+        AgentStatus::Ready => Color::WHITE,
+        AgentStatus::Error(_) => ERROR_COLOR,
+        AgentStatus::Connecting | AgentStatus::Generating => BUSY_COLOR,
+    };
+
+    for mut text_color in &mut inputs {
+        text_color.0 = color;
+    }
+}
+
+/// Grow the in-progress response line as chunks arrive
+pub(super) fn display_chat_chunks(
+    mut chunks: EventReader<AgentResponseChunkEvent>,
+    mut streaming: Query<(&StreamingMessage, &mut Text)>,
+    mut commands: Commands,
+    lists: Query<Entity, With<AgentResponseDisplay>>,
+) {
+    // Lines spawned this frame are not queryable yet, so gather their text
+    let mut started: Vec<(String, String)> = Vec::new();
+
+    for chunk in chunks.read() {
+        let mut found = false;
+        for (message, mut text) in &mut streaming {
+            if message.question_id == chunk.question_id {
+                text.0.push_str(&chunk.content);
+                found = true;
+            }
+        }
+
+        if found {
+            continue;
+        }
+
+        match started.iter_mut().find(|(id, _)| *id == chunk.question_id) {
+            Some((_, content)) => content.push_str(&chunk.content),
+            None => started.push((chunk.question_id.clone(), chunk.content.clone())),
+        }
+    }
+
+    for (question_id, content) in started {
+        for list in &lists {
+            let line = push_message(
+                &mut commands,
+                list,
+                format!("Alchemist: {}", content),
+                AGENT_COLOR,
+            );
+            commands.entity(line).insert(StreamingMessage {
+                question_id: question_id.clone(),
+            });
+        }
+    }
+}
+
+/// Append agent responses and errors to the message list
+pub(super) fn display_chat_messages(
+    mut responses: EventReader<AgentResponseEvent>,
+    mut errors: EventReader<AgentErrorEvent>,
+    mut streaming: Query<(Entity, &StreamingMessage, &mut Text)>,
+    mut commands: Commands,
+    lists: Query<Entity, With<AgentResponseDisplay>>,
+) {
+    for response in responses.read() {
+        let line = format!("Alchemist: {}", response.response);
+
+        // Replace the streamed line with the final text
+        let mut streamed = false;
+        for (entity, message, mut text) in &mut streaming {
+            if message.question_id == response.question_id {
+                text.0 = line.clone();
+                commands.entity(entity).remove::<StreamingMessage>();
+                streamed = true;
+            }
+        }
+
+        if !streamed {
+            for list in &lists {
+                push_message(&mut commands, list, line.clone(), AGENT_COLOR);
+            }
+        }
+    }
+
+    for error in errors.read() {
+        for list in &lists {
+            push_message(&mut commands, list, format!("Error: {}", error.error), ERROR_COLOR);
+        }
+    }
+}
+
+/// Fill a newly spawned message list with the conversation so far, e.g.
+/// one restored from a session file
+pub(super) fn replay_conversation(
+    lists: Query<Entity, Added<AgentResponseDisplay>>,
+    conversation: Res<AgentConversation>,
+    mut commands: Commands,
+) {
+    for list in &lists {
+        for turn in conversation.with_agent(None) {
+            let (line, color) = match turn.speaker {
+                Speaker::User => (format!("You: {}", turn.content), USER_COLOR),
+                Speaker::Agent => (format!("Alchemist: {}", turn.content), AGENT_COLOR),
+                Speaker::Error => (format!("Error: {}", turn.content), ERROR_COLOR),
+            };
+            push_message(&mut commands, list, line, color);
+        }
+    }
+}
+
+/// Replace the suggestion buttons with the newest suggestions
+pub(super) fn display_chat_suggestions(
+    mut events: EventReader<AgentSuggestionsEvent>,
+    rows: Query<Entity, With<SuggestionRow>>,
+    mut commands: Commands,
+    lists: Query<Entity, With<AgentResponseDisplay>>,
+) {
+    let Some(event) = events.read().filter(|event| !event.suggestions.is_empty()).last() else {
+        return;
+    };
+
+    for row in &rows {
+        commands.entity(row).despawn();
+    }
+
+    for list in &lists {
+        let row = commands
+            .spawn((
+                SuggestionRow,
+                Node {
+                    flex_wrap: FlexWrap::Wrap,
+                    column_gap: Val::Px(6.0),
+                    row_gap: Val::Px(6.0),
+                    ..default()
+                },
+            ))
+            .id();
+        commands.entity(list).add_child(row);
+
+        for question in &event.suggestions {
+            commands.entity(row).with_child((
+                Button,
+                FollowUpSuggestion {
+                    question: question.clone(),
+                    target: event.agent,
+                },
+                Node {
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                    ..default()
+                },
+                BackgroundColor(SUGGESTION_COLOR),
+                children![(
+                    Text::new(question.clone()),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                    TextColor(USER_COLOR),
+                )],
+            ));
+        }
+    }
+}
+
+/// Ask a suggested question when its button is pressed
+pub(super) fn handle_suggestion_clicks(
+    buttons: Query<(&Interaction, &FollowUpSuggestion), Changed<Interaction>>,
+    rows: Query<Entity, With<SuggestionRow>>,
+    status: Res<AgentStatus>,
+    mut questions: EventWriter<AgentQuestionEvent>,
+    mut commands: Commands,
+    lists: Query<Entity, With<AgentResponseDisplay>>,
+) {
+    for (interaction, suggestion) in &buttons {
+        if *interaction != Interaction::Pressed || !status.is_ready() {
+            continue;
+        }
+
+        for row in &rows {
+            commands.entity(row).despawn();
+        }
+        for list in &lists {
+            push_message(&mut commands, list, format!("You: {}", suggestion.question), USER_COLOR);
+        }
+
+        questions.write(AgentQuestionEvent {
+            id: uuid::Uuid::new_v4().to_string(),
+            question: suggestion.question.clone(),
+            target: suggestion.target,
+        });
+
+        // One question per click
+        break;
+    }
+}
+
+/// Scroll the message list with the mouse wheel
+pub(super) fn scroll_chat_messages(
+    mut wheel_events: EventReader<MouseWheel>,
+    mut lists: Query<&mut ScrollPosition, With<AgentResponseDisplay>>,
+) {
+    for event in wheel_events.read() {
+        let delta = match event.unit {
+            MouseScrollUnit::Line => event.y * LINE_HEIGHT,
+            MouseScrollUnit::Pixel => event.y,
+        };
+
+        for mut scroll in &mut lists {
+            scroll.offset_y = (scroll.offset_y - delta).max(0.0);
+        }
+    }
+}
+
+/// Add a message line and keep the list pinned to the newest entry
+fn push_message(commands: &mut Commands, list: Entity, line: String, color: Color) -> Entity {
+    let message = commands
+        .spawn((
+            Text::new(line),
+            TextFont {
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(color),
+        ))
+        .id();
+    commands.entity(list).add_child(message);
+
+    // Layout clamps the offset to the content height
+    commands.entity(list).insert(ScrollPosition {
+        offset_x: 0.0,
+        offset_y: f32::MAX,
+    });
+
+    message
+}
@@ -0,0 +1,189 @@
+//! Conversation history kept in the Bevy world
+//!
+//! Questions, responses, and errors are events that live for a frame or
+//! two; `AgentConversation` records them as turns so any system can render,
+//! save, or script against the full history.
+
+use bevy::prelude::*;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    AgentErrorEvent, AgentQuestionEvent, AgentResponseChunkEvent, AgentResponseEvent,
+    AgentSuggestionsEvent,
+};
+
+/// Who produced a turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Speaker {
+    User,
+    Agent,
+    Error,
+}
+
+/// One entry in the conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationTurn {
+    pub speaker: Speaker,
+    pub content: String,
+    /// Question this turn asks or answers; errors may have none
+    pub question_id: Option<String>,
+    /// Agent entity addressed or answering, `None` for the default agent
+    #[serde(skip)]
+    pub agent: Option<Entity>,
+    pub timestamp: DateTime<Utc>,
+    /// Follow-up questions suggested after an agent response
+    #[serde(default)]
+    pub suggestions: Vec<String>,
+}
+
+/// Full turn history across all agents
+#[derive(Resource, Debug, Default)]
+pub struct AgentConversation {
+    turns: Vec<ConversationTurn>,
+    /// Streamed text of responses still being generated
+    pending: Vec<(String, String)>,
+}
+
+impl AgentConversation {
+    /// All turns, oldest first
+    pub fn turns(&self) -> &[ConversationTurn] {
+        &self.turns
+    }
+
+    /// Turns exchanged with one agent
+    pub fn with_agent(&self, agent: Option<Entity>) -> impl Iterator<Item = &ConversationTurn> {
+        self.turns.iter().filter(move |turn| turn.agent == agent)
+    }
+
+    /// The most recent complete agent response
+    pub fn last_response(&self) -> Option<&ConversationTurn> {
+        self.turns.iter().rev().find(|turn| turn.speaker == Speaker::Agent)
+    }
+
+    /// Text streamed so far for a response still being generated
+    pub fn partial_response(&self, question_id: &str) -> Option<&str> {
+        self.pending
+            .iter()
+            .find(|(id, _)| id == question_id)
+            .map(|(_, content)| content.as_str())
+    }
+
+    /// Append a turn
+    pub fn push(&mut self, turn: ConversationTurn) {
+        if turn.speaker == Speaker::Agent {
+            if let Some(question_id) = &turn.question_id {
+                self.pending.retain(|(id, _)| id != question_id);
+            }
+        }
+        self.turns.push(turn);
+    }
+
+    /// Forget the whole history
+    pub fn clear(&mut self) {
+        self.turns.clear();
+        self.pending.clear();
+    }
+
+    /// Attach follow-up suggestions to the response to a question
+    pub fn set_suggestions(&mut self, question_id: &str, suggestions: Vec<String>) {
+        if let Some(turn) = self.turns.iter_mut().rev().find(|turn| {
+            turn.speaker == Speaker::Agent && turn.question_id.as_deref() == Some(question_id)
+        }) {
+            turn.suggestions = suggestions;
+        }
+    }
+
+    fn push_chunk(&mut self, question_id: &str, content: &str) {
+        match self.pending.iter_mut().find(|(id, _)| id == question_id) {
+            Some((_, text)) => text.push_str(content),
+            None => self.pending.push((question_id.to_string(), content.to_string())),
+        }
+    }
+}
+
+/// Record questions, chunks, responses, suggestions, and errors as they pass
+pub(super) fn record_conversation(
+    mut questions: EventReader<AgentQuestionEvent>,
+    mut chunks: EventReader<AgentResponseChunkEvent>,
+    mut responses: EventReader<AgentResponseEvent>,
+    mut errors: EventReader<AgentErrorEvent>,
+    mut suggestions: EventReader<AgentSuggestionsEvent>,
+    mut conversation: ResMut<AgentConversation>,
+) {
+    for question in questions.read() {
+        conversation.push(ConversationTurn {
+            speaker: Speaker::User,
+            content: question.question.clone(),
+            question_id: Some(question.id.clone()),
+            agent: question.target,
+            timestamp: Utc::now(),
+            suggestions: Vec::new(),
+        });
+    }
+
+    for chunk in chunks.read() {
+        conversation.push_chunk(&chunk.question_id, &chunk.content);
+    }
+
+    for response in responses.read() {
+        conversation.push(ConversationTurn {
+            speaker: Speaker::Agent,
+            content: response.response.clone(),
+            question_id: Some(response.question_id.clone()),
+            agent: response.agent,
+            timestamp: Utc::now(),
+            suggestions: Vec::new(),
+        });
+    }
+
+    for event in suggestions.read() {
+        conversation.set_suggestions(&event.question_id, event.suggestions.clone());
+    }
+
+    for error in errors.read() {
+        conversation.push(ConversationTurn {
+            speaker: Speaker::Error,
+            content: error.error.clone(),
+            question_id: None,
+            agent: error.agent,
+            timestamp: Utc::now(),
+            suggestions: Vec::new(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(speaker: Speaker, content: &str, question_id: &str) -> ConversationTurn {
+        ConversationTurn {
+            speaker,
+            content: content.to_string(),
+            question_id: Some(question_id.to_string()),
+            agent: None,
+            timestamp: Utc::now(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_response_replaces_partial_text() {
+        let mut conversation = AgentConversation::default();
+        conversation.push(turn(Speaker::User, "What is CQRS?", "q1"));
+        conversation.push_chunk("q1", "Command Query ");
+        conversation.push_chunk("q1", "Responsibility");
+
+        assert_eq!(conversation.partial_response("q1"), Some("Command Query Responsibility"));
+
+        conversation.push(turn(Speaker::Agent, "Command Query Responsibility Segregation.", "q1"));
+
+        assert_eq!(conversation.partial_response("q1"), None);
+        assert_eq!(conversation.turns().len(), 2);
+        assert_eq!(
+            conversation.last_response().map(|t| t.content.as_str()),
+            Some("Command Query Responsibility Segregation.")
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Agent performance diagnostics
+//!
+//! Registers Bevy diagnostics for how quickly the agent answers, so any
+//! diagnostics overlay or `LogDiagnosticsPlugin` shows them next to FPS:
+//!
+//! - `alchemist/first_token_latency`: question to first streamed chunk, ms
+//! - `alchemist/generation_time`: question to complete response, ms
+//! - `alchemist/queue_depth`: questions asked but not yet answered
+//!
+//! Remote agents do not stream, so their first token arrives with the
+//! complete response.
+
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics};
+use bevy::prelude::*;
+use std::time::Duration;
+
+use super::{AgentErrorEvent, AgentQuestionEvent, AgentResponseChunkEvent, AgentResponseEvent};
+
+/// Time from a question to the first chunk of its response, in milliseconds
+pub const FIRST_TOKEN_LATENCY: DiagnosticPath = DiagnosticPath::const_new("alchemist/first_token_latency");
+
+/// Time from a question to its complete response, in milliseconds
+pub const GENERATION_TIME: DiagnosticPath = DiagnosticPath::const_new("alchemist/generation_time");
+
+/// Number of questions waiting for a response
+pub const QUEUE_DEPTH: DiagnosticPath = DiagnosticPath::const_new("alchemist/queue_depth");
+
+/// The agent diagnostics with their display suffixes
+pub(super) fn agent_diagnostics() -> [Diagnostic; 3] {
+    [
+        Diagnostic::new(FIRST_TOKEN_LATENCY).with_suffix("ms"),
+        Diagnostic::new(GENERATION_TIME).with_suffix("ms"),
+        Diagnostic::new(QUEUE_DEPTH),
+    ]
+}
+
+/// A question still waiting for its response
+#[derive(Debug)]
+struct PendingQuestion {
+    question_id: String,
+    agent: Option<Entity>,
+    asked_at: Duration,
+    first_token: Option<Duration>,
+}
+
+/// Questions in flight, in the order they were asked
+#[derive(Resource, Debug, Default)]
+pub(super) struct LatencyTracker {
+    pending: Vec<PendingQuestion>,
+}
+
+impl LatencyTracker {
+    fn asked(&mut self, question_id: &str, agent: Option<Entity>, now: Duration) {
+        self.pending.push(PendingQuestion {
+            question_id: question_id.to_string(),
+            agent,
+            asked_at: now,
+            first_token: None,
+        });
+    }
+
+    /// Latency of the first chunk for a question, once
+    fn chunk(&mut self, question_id: &str, now: Duration) -> Option<Duration> {
+        let pending = self
+            .pending
+            .iter_mut()
+            .find(|pending| pending.question_id == question_id && pending.first_token.is_none())?;
+
+        let latency = now.saturating_sub(pending.asked_at);
+        pending.first_token = Some(latency);
+        Some(latency)
+    }
+
+    /// First-token latency and total time of an answered question
+    fn answered(&mut self, question_id: &str, now: Duration) -> Option<(Duration, Duration)> {
+        let index = self.pending.iter().position(|pending| pending.question_id == question_id)?;
+        let pending = self.pending.remove(index);
+
+        let total = now.saturating_sub(pending.asked_at);
+        Some((pending.first_token.unwrap_or(total), total))
+    }
+
+    /// Drop the question an agent failed on
+    ///
+    /// Errors carry no question id, but each agent answers in order, so the
+    /// failed question is its oldest pending one.
+    fn failed(&mut self, agent: Option<Entity>) {
+        if let Some(index) = self.pending.iter().position(|pending| pending.agent == agent) {
+            self.pending.remove(index);
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Record agent latency and queue depth from the events passing by
+pub(super) fn measure_agent_latency(
+    mut questions: EventReader<AgentQuestionEvent>,
+    mut chunks: EventReader<AgentResponseChunkEvent>,
+    mut responses: EventReader<AgentResponseEvent>,
+    mut errors: EventReader<AgentErrorEvent>,
+    mut tracker: ResMut<LatencyTracker>,
+    mut diagnostics: Diagnostics,
+    time: Res<Time<Real>>,
+) {
+    let now = time.elapsed();
+
+    for question in questions.read() {
+        tracker.asked(&question.id, question.target, now);
+    }
+
+    for chunk in chunks.read() {
+        if let Some(latency) = tracker.chunk(&chunk.question_id, now) {
+            diagnostics.add_measurement(&FIRST_TOKEN_LATENCY, || latency.as_secs_f64() * 1000.0);
+        }
+    }
+
+    for response in responses.read() {
+        if let Some((first_token, total)) = tracker.answered(&response.question_id, now) {
+            diagnostics.add_measurement(&FIRST_TOKEN_LATENCY, || first_token.as_secs_f64() * 1000.0);
+            diagnostics.add_measurement(&GENERATION_TIME, || total.as_secs_f64() * 1000.0);
+        }
+    }
+
+    for error in errors.read() {
+        tracker.failed(error.agent);
+    }
+
+    let depth = tracker.depth() as f64;
+    diagnostics.add_measurement(&QUEUE_DEPTH, || depth);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_tracker() {
+        let mut tracker = LatencyTracker::default();
+        tracker.asked("q1", None, Duration::from_millis(100));
+        tracker.asked("q2", None, Duration::from_millis(150));
+        assert_eq!(tracker.depth(), 2);
+
+        assert_eq!(tracker.chunk("q1", Duration::from_millis(400)), Some(Duration::from_millis(300)));
+        // Only the first chunk counts
+        assert_eq!(tracker.chunk("q1", Duration::from_millis(500)), None);
+
+        assert_eq!(
+            tracker.answered("q1", Duration::from_millis(1100)),
+            Some((Duration::from_millis(300), Duration::from_millis(1000)))
+        );
+
+        tracker.failed(None);
+        assert_eq!(tracker.depth(), 0);
+        assert_eq!(tracker.answered("q2", Duration::from_millis(2000)), None);
+    }
+}
@@ -0,0 +1,327 @@
+//! Dockable chat panel built with egui
+//!
+//! Add `AgentChatPanelPlugin` next to `AlchemistAgentPlugin` for a ready
+//! made chat: the conversation with light markdown rendering, copy and
+//! follow-up suggestion buttons, an input box, a status light, and a
+//! selector for which agent (and so which model) answers.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+
+use super::conversation::{AgentConversation, Speaker};
+use super::{AgentHandle, AgentPluginConfig, AgentQuestionEvent, AgentStatus};
+
+/// Where the chat panel is docked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PanelDock {
+    Left,
+    #[default]
+    Right,
+    Bottom,
+}
+
+/// State of the chat panel
+#[derive(Resource, Debug, Default)]
+pub struct AgentChatPanel {
+    pub dock: PanelDock,
+    /// Hide the panel without losing its state
+    pub hidden: bool,
+    /// Text in the input box
+    pub input: String,
+    /// Agent questions go to; `None` is the plugin's default agent
+    pub target: Option<Entity>,
+}
+
+/// Plugin adding the egui chat panel
+///
+/// Requires `AlchemistAgentPlugin`. Adds `EguiPlugin` when the app has not.
+#[derive(Default)]
+pub struct AgentChatPanelPlugin {
+    pub dock: PanelDock,
+}
+
+impl Plugin for AgentChatPanelPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin {
+                enable_multipass_for_primary_context: false,
+            });
+        }
+
+        app.insert_resource(AgentChatPanel {
+            dock: self.dock,
+            ..default()
+        })
+        .add_systems(Update, draw_chat_panel);
+    }
+}
+
+/// A run of lines rendered the same way
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Block {
+    Heading(String),
+    Bullet(String),
+    Code(String),
+    Paragraph(String),
+}
+
+/// Split message text into the markdown blocks the panel understands:
+/// `#` headings, `-`/`*` bullets, and fenced code
+fn markdown_blocks(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut code: Option<Vec<&str>> = None;
+
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            match code.take() {
+                Some(lines) => blocks.push(Block::Code(lines.join("\n"))),
+                None => code = Some(Vec::new()),
+            }
+            continue;
+        }
+
+        if let Some(lines) = code.as_mut() {
+            lines.push(line);
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let block = if trimmed.starts_with('#') {
+            Block::Heading(trimmed.trim_start_matches('#').trim().to_string())
+        } else if let Some(item) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* ")) {
+            Block::Bullet(item.to_string())
+        } else {
+            Block::Paragraph(trimmed.to_string())
+        };
+        blocks.push(block);
+    }
+
+    // An unterminated fence still shows its code
+    if let Some(lines) = code {
+        blocks.push(Block::Code(lines.join("\n")));
+    }
+
+    blocks
+}
+
+fn show_markdown(ui: &mut egui::Ui, text: &str) {
+    for block in markdown_blocks(text) {
+        match block {
+            Block::Heading(heading) => {
+                ui.label(egui::RichText::new(heading).strong().size(16.0));
+            }
+            Block::Bullet(item) => {
+                ui.label(format!("• {}", item));
+            }
+            Block::Code(code) => {
+                egui::Frame::group(ui.style()).show(ui, |ui| {
+                    ui.label(egui::RichText::new(code).monospace());
+                });
+            }
+            Block::Paragraph(paragraph) => {
+                ui.label(paragraph);
+            }
+        }
+    }
+}
+
+fn status_light(status: &AgentStatus) -> (egui::Color32, &'static str) {
+    match status {
+        AgentStatus::Connecting => (egui::Color32::YELLOW, "Connecting"),
+        AgentStatus::Ready => (egui::Color32::GREEN, "Ready"),
+        AgentStatus::Generating => (egui::Color32::LIGHT_BLUE, "Generating"),
+        AgentStatus::Error(_) => (egui::Color32::RED, "Error"),
+    }
+}
+
+fn draw_chat_panel(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<AgentChatPanel>,
+    conversation: Res<AgentConversation>,
+    config: Res<AgentPluginConfig>,
+    default_status: Res<AgentStatus>,
+    handles: Query<(Entity, &AgentHandle, &AgentStatus)>,
+    mut questions: EventWriter<AgentQuestionEvent>,
+) {
+    if panel.hidden {
+        return;
+    }
+
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    // Fall back to the default agent if the selected one went away
+    if panel.target.is_some_and(|target| handles.get(target).is_err()) {
+        panel.target = None;
+    }
+
+    let status = match panel.target {
+        Some(target) => handles.get(target).map(|(_, _, status)| status).unwrap_or(&*default_status),
+        None => &*default_status,
+    };
+
+    let dock = panel.dock;
+    let contents = |ui: &mut egui::Ui| {
+        let panel = &mut *panel;
+
+        ui.horizontal(|ui| {
+            let (color, label) = status_light(status);
+            ui.colored_label(color, "●");
+            ui.label(label);
+
+            let selected = match panel.target.and_then(|target| handles.get(target).ok()) {
+                Some((_, handle, _)) => format!("{} ({})", handle.name, handle.model),
+                None => format!("Alchemist ({})", config.model_name),
+            };
+
+            egui::ComboBox::from_id_salt("alchemist_agent_selector")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut panel.target, None, format!("Alchemist ({})", config.model_name));
+                    for (entity, handle, _) in &handles {
+                        ui.selectable_value(
+                            &mut panel.target,
+                            Some(entity),
+                            format!("{} ({})", handle.name, handle.model),
+                        );
+                    }
+                });
+        });
+
+        ui.separator();
+
+        // Suggestion clicked this frame; only the latest response offers them
+        let mut asked: Option<String> = None;
+        let latest = conversation
+            .with_agent(panel.target)
+            .filter(|turn| turn.speaker == Speaker::Agent)
+            .last();
+
+        let input_height = 36.0;
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .auto_shrink([false, false])
+            .max_height(ui.available_height() - input_height)
+            .show(ui, |ui| {
+                for turn in conversation.with_agent(panel.target) {
+                    match turn.speaker {
+                        Speaker::User => {
+                            ui.label(egui::RichText::new(format!("You: {}", turn.content)).strong());
+                        }
+                        Speaker::Agent => {
+                            show_markdown(ui, &turn.content);
+                            if ui.small_button("Copy").clicked() {
+                                ui.ctx().copy_text(turn.content.clone());
+                            }
+
+                            if latest.is_some_and(|latest| std::ptr::eq(latest, turn)) {
+                                ui.horizontal_wrapped(|ui| {
+                                    for suggestion in &turn.suggestions {
+                                        if ui.add_enabled(status.is_ready(), egui::Button::new(suggestion)).clicked() {
+                                            asked = Some(suggestion.clone());
+                                        }
+                                    }
+                                });
+                            }
+                        }
+                        Speaker::Error => {
+                            ui.colored_label(egui::Color32::LIGHT_RED, &turn.content);
+                        }
+                    }
+
+                    // Show the response streaming in under its question
+                    if turn.speaker == Speaker::User {
+                        if let Some(partial) = turn
+                            .question_id
+                            .as_deref()
+                            .and_then(|id| conversation.partial_response(id))
+                        {
+                            show_markdown(ui, partial);
+                        }
+                    }
+
+                    ui.add_space(6.0);
+                }
+            });
+
+        if let Some(question) = asked {
+            questions.write(AgentQuestionEvent {
+                id: uuid::Uuid::new_v4().to_string(),
+                question,
+                target: panel.target,
+            });
+        }
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            let edit = ui.add(
+                egui::TextEdit::singleline(&mut panel.input)
+                    .hint_text("Ask the Alchemist…")
+                    .desired_width(ui.available_width() - 60.0),
+            );
+            let entered = edit.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+            let clicked = ui
+                .add_enabled(status.is_ready(), egui::Button::new("Send"))
+                .clicked();
+
+            let question = panel.input.trim().to_string();
+            if (entered || clicked) && status.is_ready() && !question.is_empty() {
+                questions.write(AgentQuestionEvent {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    question,
+                    target: panel.target,
+                });
+                panel.input.clear();
+                edit.request_focus();
+            }
+        });
+    };
+
+    match dock {
+        PanelDock::Left => {
+            egui::SidePanel::left("alchemist_chat_panel")
+                .resizable(true)
+                .default_width(360.0)
+                .show(ctx, contents);
+        }
+        PanelDock::Right => {
+            egui::SidePanel::right("alchemist_chat_panel")
+                .resizable(true)
+                .default_width(360.0)
+                .show(ctx, contents);
+        }
+        PanelDock::Bottom => {
+            egui::TopBottomPanel::bottom("alchemist_chat_panel")
+                .resizable(true)
+                .default_height(280.0)
+                .show(ctx, contents);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_blocks() {
+        let text = "# Event Sourcing\n\nEvents are facts.\n- append only\n* replayable\n```rust\nlet x = 1;\n```";
+
+        assert_eq!(
+            markdown_blocks(text),
+            vec![
+                Block::Heading("Event Sourcing".to_string()),
+                Block::Paragraph("Events are facts.".to_string()),
+                Block::Bullet("append only".to_string()),
+                Block::Bullet("replayable".to_string()),
+                Block::Code("let x = 1;".to_string()),
+            ]
+        );
+    }
+}
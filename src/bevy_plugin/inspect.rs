@@ -0,0 +1,165 @@
+//! "Ask about this entity"
+//!
+//! An `AskAboutEntity` event gathers what the world knows about an entity
+//! (its name, components, architecture graph data, and caller metadata) and
+//! asks the agent to explain it, e.g. from an editor's "Explain this graph
+//! node" context menu.
+
+use bevy::ecs::event::EventCursor;
+use bevy::prelude::*;
+use tracing::warn;
+
+use super::visualization::{ArchitectureEdge, ArchitectureNode};
+use super::AgentQuestionEvent;
+
+/// Ask the agent to explain an entity
+#[derive(Event, Debug, Clone)]
+pub struct AskAboutEntity {
+    pub entity: Entity,
+    /// Specific question; defaults to a general explanation
+    pub question: Option<String>,
+    /// Extra selection context from the caller, merged into the prompt
+    pub metadata: serde_json::Value,
+    /// Agent to ask, or the plugin's default agent
+    pub target: Option<Entity>,
+}
+
+impl AskAboutEntity {
+    /// Ask for a general explanation of `entity`
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            question: None,
+            metadata: serde_json::Value::Null,
+            target: None,
+        }
+    }
+}
+
+/// Short type name without module paths or generics
+fn short_name(type_name: &str) -> &str {
+    let base = type_name.split('<').next().unwrap_or(type_name);
+    base.rsplit("::").next().unwrap_or(base)
+}
+
+/// Serialize what the world knows about an entity
+fn entity_context(world: &World, entity: Entity, metadata: &serde_json::Value) -> Option<serde_json::Value> {
+    let components: Vec<String> = world
+        .inspect_entity(entity)
+        .ok()?
+        .map(|info| short_name(info.name()).to_string())
+        .collect();
+
+    let entity_ref = world.entity(entity);
+    let mut context = serde_json::json!({
+        "entity": entity.to_string(),
+        "components": components,
+    });
+
+    if let Some(name) = entity_ref.get::<Name>() {
+        context["name"] = serde_json::json!(name.as_str());
+    }
+
+    if let Some(node) = entity_ref.get::<ArchitectureNode>() {
+        context["concept"] = serde_json::json!({
+            "id": node.concept_id,
+            "label": node.label,
+            "type": node.node_type,
+        });
+    }
+
+    if let Some(edge) = entity_ref.get::<ArchitectureEdge>() {
+        context["relationship"] = serde_json::json!({
+            "source": edge.source_id,
+            "target": edge.target_id,
+            "label": edge.label,
+        });
+    }
+
+    if let Some(transform) = entity_ref.get::<Transform>() {
+        let t = transform.translation;
+        context["position"] = serde_json::json!([t.x, t.y, t.z]);
+    }
+
+    if !metadata.is_null() {
+        context["metadata"] = metadata.clone();
+    }
+
+    Some(context)
+}
+
+/// Build the question sent to the agent
+fn entity_prompt(context: &serde_json::Value, question: Option<&str>) -> String {
+    format!(
+        "The user selected this entity in the CIM editor:\n\n{}\n\n{}",
+        serde_json::to_string_pretty(context).unwrap_or_default(),
+        question.unwrap_or("What is it, and how does it fit into the CIM architecture?")
+    )
+}
+
+/// Turn `AskAboutEntity` events into agent questions
+///
+/// Exclusive so it can inspect every component of the selected entity.
+pub(super) fn handle_ask_about_entity(
+    world: &mut World,
+    mut cursor: Local<EventCursor<AskAboutEntity>>,
+) {
+    let requests: Vec<AskAboutEntity> = cursor
+        .read(world.resource::<Events<AskAboutEntity>>())
+        .cloned()
+        .collect();
+
+    for request in requests {
+        let Some(context) = entity_context(world, request.entity, &request.metadata) else {
+            warn!("Cannot ask about {}: entity does not exist", request.entity);
+            continue;
+        };
+
+        world.send_event(AgentQuestionEvent {
+            id: uuid::Uuid::new_v4().to_string(),
+            question: entity_prompt(&context, request.question.as_deref()),
+            target: request.target,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_name() {
+        assert_eq!(short_name("bevy_transform::components::transform::Transform"), "Transform");
+        assert_eq!(short_name("my_app::Selected<my_app::Tool>"), "Selected");
+    }
+
+    #[test]
+    fn test_entity_context_includes_concept() {
+        let mut world = World::new();
+        let entity = world
+            .spawn((
+                Name::new("Aggregate"),
+                ArchitectureNode {
+                    concept_id: "aggregate".to_string(),
+                    label: "Aggregate".to_string(),
+                    node_type: "domain".to_string(),
+                },
+            ))
+            .id();
+
+        let context = entity_context(&world, entity, &serde_json::json!({"graph": "events"})).unwrap();
+
+        assert_eq!(context["name"], "Aggregate");
+        assert_eq!(context["concept"]["id"], "aggregate");
+        assert_eq!(context["metadata"]["graph"], "events");
+        assert!(context["components"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|c| c == "ArchitectureNode"));
+
+        let prompt = entity_prompt(&context, None);
+        assert!(prompt.contains("\"aggregate\""));
+        assert!(prompt.ends_with("CIM architecture?"));
+    }
+}
@@ -0,0 +1,1157 @@
+//! Bevy plugin for CIM Alchemist Agent
+//!
+//! This plugin integrates the AI assistant into the Bevy ECS system,
+//! allowing it to interact with the graph editor and workflow components.
+//! The agent either runs embedded in the app or is reached over NATS.
+
+use bevy::diagnostic::RegisterDiagnostic;
+use bevy::prelude::*;
+use crate::agent::{AlchemistAgent, DialogMessage};
+use crate::client::AgentClient;
+use crate::error::Result;
+use crate::model::{create_provider, Message as ModelMessage};
+use async_compat::Compat;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+#[cfg(feature = "bevy-ui")]
+pub mod chat_ui;
+pub mod conversation;
+pub mod diagnostics;
+#[cfg(feature = "bevy_egui")]
+pub mod egui_panel;
+pub mod inspect;
+pub mod models;
+pub mod session;
+pub mod visualization;
+#[cfg(feature = "voice")]
+pub mod voice;
+pub mod workflow_hud;
+
+use models::{AvailableModelsEvent, ListModelsEvent, ModelSwitchedEvent, SwitchModelEvent};
+use session::RestoredDialog;
+use visualization::{ArchitectureVisualizationEvent, VisualizeArchitectureEvent};
+use workflow_hud::{
+    ActiveWorkflow, AdvanceWorkflowEvent, StartWorkflowEvent, WorkflowProgress, WorkflowUpdatedEvent,
+};
+
+/// Events for agent communication
+#[derive(Event, Debug, Clone)]
+pub struct AgentQuestionEvent {
+    pub id: String,
+    pub question: String,
+    /// Entity with an `AgentHandle` to ask, or the plugin's default agent
+    pub target: Option<Entity>,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct AgentResponseEvent {
+    pub id: String,
+    pub response: String,
+    pub question_id: String,
+    /// Entity of the answering `AgentHandle`, `None` for the default agent
+    pub agent: Option<Entity>,
+}
+
+/// Partial response text, sent as the model generates it
+///
+/// Chunks for a question arrive in order and are followed by a single
+/// `AgentResponseEvent` carrying the complete response.
+#[derive(Event, Debug, Clone)]
+pub struct AgentResponseChunkEvent {
+    pub question_id: String,
+    /// Text generated since the previous chunk
+    pub content: String,
+    pub agent: Option<Entity>,
+}
+
+/// Follow-up questions suggested after a response
+#[derive(Event, Debug, Clone)]
+pub struct AgentSuggestionsEvent {
+    /// Question whose response the suggestions follow
+    pub question_id: String,
+    pub suggestions: Vec<String>,
+    pub agent: Option<Entity>,
+}
+
+/// A suggested follow-up question shown in the UI
+///
+/// Clicking a `Button` carrying this component asks the question of
+/// `target` (requires the `bevy-ui` feature; other front ends can send
+/// `ask_agent` themselves).
+#[derive(Component, Debug, Clone)]
+pub struct FollowUpSuggestion {
+    pub question: String,
+    pub target: Option<Entity>,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct AgentErrorEvent {
+    pub error: String,
+    pub agent: Option<Entity>,
+}
+
+/// What an agent is currently doing
+///
+/// The default agent's status is a resource; each `AgentHandle` entity
+/// carries its own as a component. Both only change when the status does,
+/// so `resource_changed` and `Changed<AgentStatus>` drive status lights.
+#[derive(Resource, Component, Debug, Clone, Default, PartialEq, Eq)]
+pub enum AgentStatus {
+    /// Starting the agent or connecting to the remote service
+    #[default]
+    Connecting,
+    /// Waiting for a question
+    Ready,
+    /// Producing a response
+    Generating,
+    /// The service stopped and will not answer
+    Error(String),
+}
+
+impl AgentStatus {
+    /// Whether the agent can take a new question right now
+    pub fn is_ready(&self) -> bool {
+        matches!(self, Self::Ready)
+    }
+}
+
+/// Run condition: the default agent is ready for a question
+pub fn agent_is_ready(status: Res<AgentStatus>) -> bool {
+    status.is_ready()
+}
+
+/// An agent persona living on an entity
+///
+/// Adding this component starts a dedicated agent for the entity, so an app
+/// can run several personas side by side. Address it through
+/// `AgentQuestionEvent::target`; its events carry the entity in `agent`.
+#[derive(Component, Debug, Clone)]
+pub struct AgentHandle {
+    /// Persona name, used as the agent identity
+    pub name: String,
+    /// Model to run when the agent is embedded
+    pub model: String,
+    /// Subject prefix of the remote agent when the backend is NATS
+    pub subject_prefix: String,
+}
+
+impl AgentHandle {
+    /// Create a handle using the default subject prefix
+    pub fn new(name: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            model: model.into(),
+            subject_prefix: crate::config::AgentConfig::default().nats.subject_prefix,
+        }
+    }
+
+    /// Point the handle at a different remote agent
+    pub fn with_subject_prefix(mut self, subject_prefix: impl Into<String>) -> Self {
+        self.subject_prefix = subject_prefix.into();
+        self
+    }
+}
+
+/// Where the plugin's agent runs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AgentBackend {
+    /// Run the agent and its model inside the app
+    #[default]
+    Embedded,
+
+    /// Talk to a running Alchemist service over NATS
+    Nats {
+        /// Subject prefix of the remote agent
+        subject_prefix: String,
+    },
+}
+
+/// Resource for agent configuration
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct AgentPluginConfig {
+    pub nats_url: String,
+    pub ollama_url: String,
+    pub model_name: String,
+    /// Spawn the chat widget on startup (requires the `bevy-ui` feature)
+    pub spawn_chat_ui: bool,
+    /// Embedded agent or remote service
+    pub backend: AgentBackend,
+    /// Ask the agent for follow-up questions after each response
+    pub suggest_follow_ups: bool,
+    /// Sidecar file the conversation and these settings are saved to and
+    /// restored from; `None` keeps the session in memory only
+    #[serde(skip)]
+    pub session_file: Option<PathBuf>,
+}
+
+impl Default for AgentPluginConfig {
+    fn default() -> Self {
+        Self {
+            nats_url: "nats://localhost:4222".to_string(),
+            ollama_url: "http://localhost:11434".to_string(),
+            model_name: "vicuna:latest".to_string(),
+            spawn_chat_ui: true,
+            backend: AgentBackend::Embedded,
+            suggest_follow_ups: true,
+            session_file: None,
+        }
+    }
+}
+
+impl AgentPluginConfig {
+    /// Build the full agent configuration used by the embedded agent
+    pub fn agent_config(&self) -> crate::config::AgentConfig {
+        let mut config = crate::config::AgentConfig::default();
+        config.nats.servers = vec![self.nats_url.clone()];
+
+        if let crate::config::ModelConfig::Ollama { base_url, .. } = &mut config.model {
+            *base_url = self.ollama_url.clone();
+        }
+        config.model.set_model(self.model_name.clone());
+
+        if let AgentBackend::Nats { subject_prefix } = &self.backend {
+            config.nats.subject_prefix = subject_prefix.clone();
+        }
+
+        config
+    }
+
+    /// Plugin settings for the agent behind a handle
+    fn for_handle(&self, handle: &AgentHandle) -> Self {
+        let backend = match self.backend {
+            AgentBackend::Embedded => AgentBackend::Embedded,
+            AgentBackend::Nats { .. } => AgentBackend::Nats {
+                subject_prefix: handle.subject_prefix.clone(),
+            },
+        };
+
+        Self {
+            model_name: handle.model.clone(),
+            backend,
+            ..self.clone()
+        }
+    }
+}
+
+/// Channel for communication between Bevy and async agent
+///
+/// The default agent's channels are a resource; each `AgentHandle` entity
+/// gets its own as a component. Dropping them cancels the agent's task.
+#[derive(Resource, Component)]
+struct AgentChannels {
+    question_sender: mpsc::Sender<AgentQuestionEvent>,
+    response_receiver: Receiver<AgentResponseEvent>,
+    chunk_receiver: Receiver<AgentResponseChunkEvent>,
+    error_receiver: Receiver<AgentErrorEvent>,
+    visualize_sender: mpsc::Sender<VisualizeArchitectureEvent>,
+    visualization_receiver: Receiver<ArchitectureVisualizationEvent>,
+    status_receiver: Receiver<AgentStatus>,
+    suggestions_receiver: Receiver<AgentSuggestionsEvent>,
+    workflow_sender: mpsc::Sender<WorkflowRequest>,
+    workflow_receiver: Receiver<WorkflowUpdatedEvent>,
+    model_sender: mpsc::Sender<ModelRequest>,
+    model_receiver: Receiver<ModelUpdate>,
+    /// Dialog all of this agent's questions belong to
+    dialog_id: String,
+    _task: Task<()>,
+}
+
+/// Component for agent UI elements
+#[derive(Component)]
+pub struct AgentChatUI;
+
+#[derive(Component)]
+pub struct AgentInputField;
+
+#[derive(Component)]
+pub struct AgentResponseDisplay;
+
+/// Plugin for the CIM Alchemist Agent
+#[derive(Default)]
+pub struct AlchemistAgentPlugin {
+    /// Configuration for the embedded agent
+    pub config: AgentPluginConfig,
+}
+
+impl AlchemistAgentPlugin {
+    /// Create the plugin with a custom configuration
+    pub fn new(config: AgentPluginConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Plugin for AlchemistAgentPlugin {
+    fn build(&self, app: &mut App) {
+        for diagnostic in diagnostics::agent_diagnostics() {
+            app.register_diagnostic(diagnostic);
+        }
+
+        app
+            // Resources
+            .insert_resource(self.config.clone())
+            .init_resource::<AgentStatus>()
+            .init_resource::<conversation::AgentConversation>()
+            .init_resource::<diagnostics::LatencyTracker>()
+            // Events
+            .add_event::<AgentQuestionEvent>()
+            .add_event::<AgentResponseEvent>()
+            .add_event::<AgentResponseChunkEvent>()
+            .add_event::<AgentErrorEvent>()
+            .add_event::<AgentSuggestionsEvent>()
+            .add_event::<inspect::AskAboutEntity>()
+            .add_event::<StartWorkflowEvent>()
+            .add_event::<AdvanceWorkflowEvent>()
+            .add_event::<WorkflowUpdatedEvent>()
+            .init_resource::<ActiveWorkflow>()
+            .init_resource::<workflow_hud::WorkflowKeys>()
+            .add_event::<ListModelsEvent>()
+            .add_event::<AvailableModelsEvent>()
+            .add_event::<SwitchModelEvent>()
+            .add_event::<ModelSwitchedEvent>()
+            .add_event::<VisualizeArchitectureEvent>()
+            .add_event::<ArchitectureVisualizationEvent>()
+            // Systems
+            .add_systems(Startup, (session::load_session, setup_agent_service).chain())
+            .add_systems(Update, (
+                start_agent_handles,
+                poll_agent_status,
+                handle_question_events,
+                poll_agent_chunks,
+                poll_agent_responses,
+                poll_agent_errors,
+                poll_agent_suggestions,
+                conversation::record_conversation,
+                update_agent_ui,
+            ).chain())
+            .add_systems(Update, inspect::handle_ask_about_entity.before(handle_question_events))
+            .add_systems(Update, diagnostics::measure_agent_latency.after(poll_agent_errors))
+            .add_systems(
+                Update,
+                session::save_session
+                    .after(conversation::record_conversation)
+                    .run_if(on_event::<AgentResponseEvent>.or(on_event::<AppExit>)),
+            )
+            .add_systems(Update, (
+                workflow_hud::advance_workflow_on_key,
+                handle_workflow_events,
+                poll_agent_workflows,
+                workflow_hud::track_active_workflow,
+            ).chain())
+            .add_systems(Update, (
+                handle_model_events,
+                poll_agent_models,
+                models::apply_model_switches,
+            ).chain())
+            .add_systems(Update, (
+                handle_visualize_events,
+                poll_agent_visualizations,
+                visualization::spawn_architecture_graphs,
+            ).chain());
+
+        #[cfg(feature = "bevy-ui")]
+        app.add_systems(Startup, workflow_hud::spawn_workflow_hud)
+            .add_systems(
+                Update,
+                workflow_hud::update_workflow_hud
+                    .after(workflow_hud::track_active_workflow)
+                    .run_if(resource_changed::<ActiveWorkflow>),
+            );
+
+        #[cfg(feature = "bevy-ui")]
+        app.add_systems(Update, (
+            chat_ui::replay_conversation.before(chat_ui::handle_chat_input),
+            chat_ui::handle_chat_input,
+            chat_ui::display_chat_chunks.after(poll_agent_chunks),
+            chat_ui::display_chat_messages
+                .after(poll_agent_errors)
+                .after(chat_ui::display_chat_chunks),
+            chat_ui::display_chat_suggestions
+                .after(poll_agent_suggestions)
+                .after(chat_ui::display_chat_messages),
+            chat_ui::handle_suggestion_clicks,
+            chat_ui::scroll_chat_messages,
+            chat_ui::show_agent_status
+                .after(poll_agent_status)
+                .run_if(resource_changed::<AgentStatus>),
+        ));
+    }
+}
+
+impl AgentChannels {
+    /// Start an agent service on the async compute pool and keep the
+    /// Bevy-side ends
+    ///
+    /// The agent continues `restored` if given, otherwise starts a new dialog.
+    fn spawn(config: &AgentPluginConfig, name: &str, restored: Option<RestoredDialog>) -> Self {
+        // The agent wakes on incoming requests instead of polling
+        let (question_tx, question_rx) = mpsc::channel::<AgentQuestionEvent>(100);
+        let (visualize_tx, visualize_rx) = mpsc::channel::<VisualizeArchitectureEvent>(100);
+        // Unbounded so sending never blocks a pool thread; Bevy drains them
+        // every frame
+        let (response_tx, response_rx) = unbounded::<AgentResponseEvent>();
+        let (chunk_tx, chunk_rx) = unbounded::<AgentResponseChunkEvent>();
+        let (error_tx, error_rx) = unbounded::<AgentErrorEvent>();
+        let (visualization_tx, visualization_rx) = unbounded::<ArchitectureVisualizationEvent>();
+        let (status_tx, status_rx) = unbounded::<AgentStatus>();
+        let (suggestions_tx, suggestions_rx) = unbounded::<AgentSuggestionsEvent>();
+        let (workflow_request_tx, workflow_request_rx) = mpsc::channel::<WorkflowRequest>(16);
+        let (workflow_tx, workflow_rx) = unbounded::<WorkflowUpdatedEvent>();
+        let (model_request_tx, model_request_rx) = mpsc::channel::<ModelRequest>(16);
+        let (model_tx, model_rx) = unbounded::<ModelUpdate>();
+
+        let mut agent_config = config.agent_config();
+        agent_config.identity.name = name.to_string();
+        let backend = config.backend.clone();
+        let RestoredDialog { dialog_id, history } = restored.unwrap_or_else(|| RestoredDialog {
+            dialog_id: uuid::Uuid::new_v4().to_string(),
+            history: Vec::new(),
+        });
+        let service_dialog_id = dialog_id.clone();
+        let suggest_follow_ups = config.suggest_follow_ups;
+        let service_channels = ServiceChannels {
+            question_receiver: question_rx,
+            response_sender: response_tx,
+            chunk_sender: chunk_tx,
+            error_sender: error_tx.clone(),
+            visualize_receiver: visualize_rx,
+            visualization_sender: visualization_tx,
+            status_sender: status_tx.clone(),
+            suggestions_sender: suggest_follow_ups.then_some(suggestions_tx),
+            workflow_receiver: workflow_request_rx,
+            workflow_sender: workflow_tx,
+            model_receiver: model_request_rx,
+            model_sender: model_tx,
+        };
+
+        // NATS and HTTP clients need a Tokio reactor, which Compat provides
+        // from a single shared background thread
+        let task = AsyncComputeTaskPool::get().spawn(Compat::new(async move {
+            if let Err(e) = run_agent_service(agent_config, backend, service_dialog_id, history, service_channels).await {
+                error!("Agent service failed: {}", e);
+                let _ = error_tx.send(AgentErrorEvent {
+                    error: format!("Agent service failed: {}", e),
+                    agent: None,
+                });
+                let _ = status_tx.send(AgentStatus::Error(e.to_string()));
+            }
+        }));
+
+        Self {
+            question_sender: question_tx,
+            response_receiver: response_rx,
+            chunk_receiver: chunk_rx,
+            error_receiver: error_rx,
+            visualize_sender: visualize_tx,
+            visualization_receiver: visualization_rx,
+            status_receiver: status_rx,
+            suggestions_receiver: suggestions_rx,
+            workflow_sender: workflow_request_tx,
+            workflow_receiver: workflow_rx,
+            model_sender: model_request_tx,
+            model_receiver: model_rx,
+            dialog_id,
+            _task: task,
+        }
+    }
+}
+
+/// Start a dedicated agent for each new `AgentHandle`
+///
+/// The service stops once the entity's channels are dropped.
+fn start_agent_handles(
+    mut commands: Commands,
+    handles: Query<(Entity, &AgentHandle), Added<AgentHandle>>,
+    config: Res<AgentPluginConfig>,
+) {
+    for (entity, handle) in &handles {
+        info!("Starting agent {} with model {}", handle.name, handle.model);
+
+        let channels = AgentChannels::spawn(&config.for_handle(handle), &handle.name, None);
+        commands.entity(entity).insert((channels, AgentStatus::Connecting));
+    }
+}
+
+/// Setup the agent service
+///
+/// Runs at startup so the task pools exist before the agent is spawned.
+fn setup_agent_service(
+    mut commands: Commands,
+    config: Res<AgentPluginConfig>,
+    restored: Option<Res<RestoredDialog>>,
+) {
+    match &config.backend {
+        AgentBackend::Embedded => {
+            info!("Setting up CIM Alchemist Agent service with model {}", config.model_name);
+        }
+        AgentBackend::Nats { subject_prefix } => {
+            info!("Connecting to CIM Alchemist Agent at {} ({})", config.nats_url, subject_prefix);
+        }
+    }
+
+    commands.remove_resource::<RestoredDialog>();
+    commands.insert_resource(AgentChannels::spawn(&config, "Alchemist", restored.as_deref().cloned()));
+
+    if !config.spawn_chat_ui {
+        return;
+    }
+
+    #[cfg(feature = "bevy-ui")]
+    commands.spawn(chat_ui::chat_widget());
+
+    // Without UI support only a marker entity is spawned
+    #[cfg(not(feature = "bevy-ui"))]
+    commands.spawn((
+        AgentChatUI,
+        Name::new("Agent Chat UI"),
+    ));
+}
+
+/// Handle question events from the UI, routing each to its target agent
+fn handle_question_events(
+    mut events: EventReader<AgentQuestionEvent>,
+    channels: Res<AgentChannels>,
+    handles: Query<&AgentChannels>,
+    mut error_events: EventWriter<AgentErrorEvent>,
+) {
+    for event in events.read() {
+        let target = match event.target {
+            None => &*channels,
+            Some(entity) => match handles.get(entity) {
+                Ok(target) => target,
+                Err(_) => {
+                    error_events.write(AgentErrorEvent {
+                        error: format!("No agent on entity {}", entity),
+                        agent: Some(entity),
+                    });
+                    continue;
+                }
+            },
+        };
+
+        if let Err(e) = target.question_sender.try_send(event.clone()) {
+            error!("Failed to send question to agent: {}", e);
+        }
+    }
+}
+
+/// Apply status updates from the agent services
+fn poll_agent_status(
+    channels: Res<AgentChannels>,
+    mut status: ResMut<AgentStatus>,
+    mut handles: Query<(&AgentChannels, &mut AgentStatus)>,
+) {
+    while let Ok(update) = channels.status_receiver.try_recv() {
+        status.set_if_neq(update);
+    }
+
+    for (channels, mut status) in &mut handles {
+        while let Ok(update) = channels.status_receiver.try_recv() {
+            status.set_if_neq(update);
+        }
+    }
+}
+
+/// Poll for follow-up suggestions from the agent
+fn poll_agent_suggestions(
+    channels: Res<AgentChannels>,
+    handles: Query<(Entity, &AgentChannels)>,
+    mut suggestion_events: EventWriter<AgentSuggestionsEvent>,
+) {
+    for (agent, channels) in all_channels(&channels, &handles) {
+        while let Ok(suggestions) = channels.suggestions_receiver.try_recv() {
+            suggestion_events.write(AgentSuggestionsEvent { agent, ..suggestions });
+        }
+    }
+}
+
+/// Forward workflow starts and advances to the default agent
+fn handle_workflow_events(
+    mut starts: EventReader<StartWorkflowEvent>,
+    mut advances: EventReader<AdvanceWorkflowEvent>,
+    active: Res<ActiveWorkflow>,
+    channels: Res<AgentChannels>,
+) {
+    let mut requests: Vec<WorkflowRequest> = starts
+        .read()
+        .map(|event| WorkflowRequest::Start(event.workflow_type.clone()))
+        .collect();
+
+    if advances.read().count() > 0 {
+        match &active.progress {
+            Some(progress) if !progress.completed => {
+                requests.push(WorkflowRequest::Advance(progress.workflow_id.clone()));
+            }
+            _ => warn!("No active workflow to advance"),
+        }
+    }
+
+    for request in requests {
+        if let Err(e) = channels.workflow_sender.try_send(request) {
+            error!("Failed to send workflow request to agent: {}", e);
+        }
+    }
+}
+
+/// Poll for workflow updates from the agents
+fn poll_agent_workflows(
+    channels: Res<AgentChannels>,
+    handles: Query<(Entity, &AgentChannels)>,
+    mut workflow_events: EventWriter<WorkflowUpdatedEvent>,
+) {
+    for (_, channels) in all_channels(&channels, &handles) {
+        while let Ok(update) = channels.workflow_receiver.try_recv() {
+            workflow_events.write(update);
+        }
+    }
+}
+
+/// Forward model listing and switching requests to their target agents
+fn handle_model_events(
+    mut lists: EventReader<ListModelsEvent>,
+    mut switches: EventReader<SwitchModelEvent>,
+    channels: Res<AgentChannels>,
+    handles: Query<&AgentChannels>,
+    mut error_events: EventWriter<AgentErrorEvent>,
+) {
+    let requests = lists
+        .read()
+        .map(|event| (event.agent, ModelRequest::List))
+        .chain(switches.read().map(|event| (event.agent, ModelRequest::Switch(event.model.clone()))));
+
+    for (agent, request) in requests {
+        let target = match agent {
+            None => &*channels,
+            Some(entity) => match handles.get(entity) {
+                Ok(target) => target,
+                Err(_) => {
+                    error_events.write(AgentErrorEvent {
+                        error: format!("No agent on entity {}", entity),
+                        agent: Some(entity),
+                    });
+                    continue;
+                }
+            },
+        };
+
+        if let Err(e) = target.model_sender.try_send(request) {
+            error!("Failed to send model request to agent: {}", e);
+        }
+    }
+}
+
+/// Poll for model listings and switches from the agents
+fn poll_agent_models(
+    channels: Res<AgentChannels>,
+    handles: Query<(Entity, &AgentChannels)>,
+    mut available_events: EventWriter<AvailableModelsEvent>,
+    mut switched_events: EventWriter<ModelSwitchedEvent>,
+) {
+    for (agent, channels) in all_channels(&channels, &handles) {
+        while let Ok(update) = channels.model_receiver.try_recv() {
+            match update {
+                ModelUpdate::Available { current, models } => {
+                    available_events.write(AvailableModelsEvent { current, models, agent });
+                }
+                ModelUpdate::Switched(model) => {
+                    switched_events.write(ModelSwitchedEvent { model, agent });
+                }
+            }
+        }
+    }
+}
+
+/// Forward visualization requests to the agent
+fn handle_visualize_events(
+    mut events: EventReader<VisualizeArchitectureEvent>,
+    channels: Res<AgentChannels>,
+) {
+    for event in events.read() {
+        if let Err(e) = channels.visualize_sender.try_send(event.clone()) {
+            error!("Failed to send visualization request to agent: {}", e);
+        }
+    }
+}
+
+/// Poll for visualizations produced by the agent
+fn poll_agent_visualizations(
+    channels: Res<AgentChannels>,
+    mut visualization_events: EventWriter<ArchitectureVisualizationEvent>,
+) {
+    while let Ok(visualization) = channels.visualization_receiver.try_recv() {
+        visualization_events.write(visualization);
+    }
+}
+
+/// The default agent's channels followed by those of every handle
+fn all_channels<'a>(
+    channels: &'a AgentChannels,
+    handles: &'a Query<(Entity, &AgentChannels)>,
+) -> Vec<(Option<Entity>, &'a AgentChannels)> {
+    std::iter::once((None, channels))
+        .chain(handles.iter().map(|(entity, channels)| (Some(entity), channels)))
+        .collect()
+}
+
+/// Poll for partial responses from the agent
+fn poll_agent_chunks(
+    channels: Res<AgentChannels>,
+    handles: Query<(Entity, &AgentChannels)>,
+    mut chunk_events: EventWriter<AgentResponseChunkEvent>,
+) {
+    for (agent, channels) in all_channels(&channels, &handles) {
+        while let Ok(chunk) = channels.chunk_receiver.try_recv() {
+            chunk_events.write(AgentResponseChunkEvent { agent, ..chunk });
+        }
+    }
+}
+
+/// Poll for responses from the agent
+fn poll_agent_responses(
+    channels: Res<AgentChannels>,
+    handles: Query<(Entity, &AgentChannels)>,
+    mut response_events: EventWriter<AgentResponseEvent>,
+) {
+    for (agent, channels) in all_channels(&channels, &handles) {
+        while let Ok(response) = channels.response_receiver.try_recv() {
+            response_events.write(AgentResponseEvent { agent, ..response });
+        }
+    }
+}
+
+/// Poll for errors from the agent
+fn poll_agent_errors(
+    channels: Res<AgentChannels>,
+    handles: Query<(Entity, &AgentChannels)>,
+    mut error_events: EventWriter<AgentErrorEvent>,
+) {
+    for (agent, channels) in all_channels(&channels, &handles) {
+        while let Ok(error) = channels.error_receiver.try_recv() {
+            error_events.write(AgentErrorEvent { agent, ..error });
+        }
+    }
+}
+
+/// Update the agent UI based on events
+fn update_agent_ui(
+    mut response_events: EventReader<AgentResponseEvent>,
+    mut error_events: EventReader<AgentErrorEvent>,
+) {
+    for response in response_events.read() {
+        info!("Agent response: {}", response.response);
+        // TODO: Update UI components with response
+    }
+
+    for error in error_events.read() {
+        error!("Agent error: {}", error.error);
+        // TODO: Show error in UI
+    }
+}
+
+/// The background service's ends of the plugin channels
+struct ServiceChannels {
+    question_receiver: mpsc::Receiver<AgentQuestionEvent>,
+    response_sender: Sender<AgentResponseEvent>,
+    chunk_sender: Sender<AgentResponseChunkEvent>,
+    error_sender: Sender<AgentErrorEvent>,
+    visualize_receiver: mpsc::Receiver<VisualizeArchitectureEvent>,
+    visualization_sender: Sender<ArchitectureVisualizationEvent>,
+    status_sender: Sender<AgentStatus>,
+    /// Present only when follow-up suggestions are enabled
+    suggestions_sender: Option<Sender<AgentSuggestionsEvent>>,
+    workflow_receiver: mpsc::Receiver<WorkflowRequest>,
+    workflow_sender: Sender<WorkflowUpdatedEvent>,
+    model_receiver: mpsc::Receiver<ModelRequest>,
+    model_sender: Sender<ModelUpdate>,
+}
+
+/// Workflow commands sent to the agent service
+#[derive(Debug)]
+enum WorkflowRequest {
+    /// Start a workflow of the given type
+    Start(String),
+    /// Advance the workflow with the given id
+    Advance(String),
+}
+
+/// Model commands sent to the agent service
+#[derive(Debug)]
+enum ModelRequest {
+    List,
+    Switch(String),
+}
+
+/// Model results sent back from the agent service
+#[derive(Debug)]
+enum ModelUpdate {
+    Available { current: String, models: Vec<String> },
+    Switched(String),
+}
+
+/// The agent behind the plugin
+enum AgentConnection {
+    Embedded(AlchemistAgent),
+    Remote(AgentClient),
+}
+
+impl AgentConnection {
+    /// Create the embedded agent or connect to the remote one
+    async fn open(config: crate::config::AgentConfig, backend: &AgentBackend) -> Result<Self> {
+        match backend {
+            AgentBackend::Embedded => {
+                // Same provider factory the service uses
+                let model_provider = create_provider(&config.model)?;
+                Ok(Self::Embedded(AlchemistAgent::new(config, model_provider).await?))
+            }
+            AgentBackend::Nats { .. } => {
+                // Remote generation can take a while
+                let client = AgentClient::connect(&config.nats)
+                    .await?
+                    .with_origin("alchemist-bevy")
+                    .with_timeout(std::time::Duration::from_secs(300));
+                Ok(Self::Remote(client))
+            }
+        }
+    }
+
+    /// Answer a dialog message, streaming chunks when the agent is local
+    async fn ask<F>(&self, message: DialogMessage, on_chunk: F) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        match self {
+            Self::Embedded(agent) => agent.process_dialog_message_streaming(message, on_chunk).await,
+            Self::Remote(client) => client.dialog(&message.dialog_id, message.content).await,
+        }
+    }
+
+    /// Give the agent the history of a dialog it continues
+    ///
+    /// A remote service keeps its dialogs itself, so only the embedded
+    /// agent needs them.
+    async fn restore(&self, dialog_id: &str, history: &[ModelMessage]) {
+        if let Self::Embedded(agent) = self {
+            agent.restore_dialog(dialog_id, history).await;
+        }
+    }
+
+    async fn command(&self, command_type: &str, payload: serde_json::Value) -> Result<serde_json::Value> {
+        match self {
+            Self::Embedded(agent) => agent.process_command(command_type, payload).await,
+            Self::Remote(client) => client.command(command_type, payload).await,
+        }
+    }
+
+    async fn query(&self, query_type: &str, parameters: serde_json::Value) -> Result<serde_json::Value> {
+        match self {
+            Self::Embedded(agent) => agent.process_query(query_type, parameters).await,
+            Self::Remote(client) => client.query(query_type, parameters).await,
+        }
+    }
+}
+
+/// Run the agent service in the background
+async fn run_agent_service(
+    config: crate::config::AgentConfig,
+    backend: AgentBackend,
+    dialog_id: String,
+    history: Vec<ModelMessage>,
+    channels: ServiceChannels,
+) -> Result<()> {
+    let ServiceChannels {
+        mut question_receiver,
+        response_sender,
+        chunk_sender,
+        error_sender,
+        mut visualize_receiver,
+        visualization_sender,
+        status_sender,
+        suggestions_sender,
+        mut workflow_receiver,
+        workflow_sender,
+        mut model_receiver,
+        model_sender,
+    } = channels;
+
+    let _ = status_sender.send(AgentStatus::Connecting);
+    let agent = AgentConnection::open(config, &backend).await?;
+
+    // All questions from this app share one dialog so context carries over
+    if !history.is_empty() {
+        agent.restore(&dialog_id, &history).await;
+    }
+    let _ = status_sender.send(AgentStatus::Ready);
+
+    // Main service loop: sleep until Bevy sends work, stop once it is gone
+    loop {
+        tokio::select! {
+            Some(question) = question_receiver.recv() => {
+                let message = DialogMessage {
+                    dialog_id: dialog_id.clone(),
+                    content: question.question,
+                    metadata: serde_json::json!({ "question_id": question.id }),
+                    timestamp: chrono::Utc::now(),
+                };
+
+                let question_id = question.id.clone();
+                let chunk_sender = chunk_sender.clone();
+                let on_chunk = move |content: &str| {
+                    let _ = chunk_sender.send(AgentResponseChunkEvent {
+                        question_id: question_id.clone(),
+                        content: content.to_string(),
+                        agent: None,
+                    });
+                };
+
+                let _ = status_sender.send(AgentStatus::Generating);
+                let result = agent.ask(message, on_chunk).await;
+                let _ = status_sender.send(AgentStatus::Ready);
+
+                match result {
+                    Ok(response) => {
+                        let response_event = AgentResponseEvent {
+                            id: uuid::Uuid::new_v4().to_string(),
+                            response,
+                            question_id: question.id.clone(),
+                            agent: None,
+                        };
+
+                        if let Err(e) = response_sender.send(response_event) {
+                            error!("Failed to send response: {}", e);
+                        }
+
+                        if let Some(suggestions_sender) = &suggestions_sender {
+                            let parameters = serde_json::json!({ "dialog_id": dialog_id });
+
+                            // Suggestions are optional; a failure only costs the buttons
+                            match agent.query("suggest_follow_ups", parameters).await {
+                                Ok(result) => {
+                                    let suggestions = result["suggestions"]
+                                        .as_array()
+                                        .into_iter()
+                                        .flatten()
+                                        .filter_map(|s| s.as_str().map(String::from))
+                                        .collect();
+
+                                    let _ = suggestions_sender.send(AgentSuggestionsEvent {
+                                        question_id: question.id,
+                                        suggestions,
+                                        agent: None,
+                                    });
+                                }
+                                Err(e) => debug!("No follow-up suggestions: {}", e),
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let error_event = AgentErrorEvent {
+                            error: format!("Failed to process question: {}", e),
+                            agent: None,
+                        };
+
+                        if let Err(e) = error_sender.send(error_event) {
+                            error!("Failed to send error: {}", e);
+                        }
+                    }
+                }
+            }
+            Some(request) = visualize_receiver.recv() => {
+                let payload = serde_json::json!({ "scope": request.scope });
+
+                let _ = status_sender.send(AgentStatus::Generating);
+                let result = agent.command("visualize_architecture", payload).await;
+                let _ = status_sender.send(AgentStatus::Ready);
+
+                match result {
+                    Ok(result) => {
+                        let visualization_event = ArchitectureVisualizationEvent {
+                            scope: request.scope,
+                            visualization: result["visualization"].clone(),
+                        };
+
+                        if let Err(e) = visualization_sender.send(visualization_event) {
+                            error!("Failed to send visualization: {}", e);
+                        }
+                    }
+                    Err(e) => {
+                        let error_event = AgentErrorEvent {
+                            error: format!("Failed to visualize {}: {}", request.scope, e),
+                            agent: None,
+                        };
+
+                        if let Err(e) = error_sender.send(error_event) {
+                            error!("Failed to send error: {}", e);
+                        }
+                    }
+                }
+            }
+            Some(request) = workflow_receiver.recv() => {
+                let result = match &request {
+                    WorkflowRequest::Start(workflow_type) => agent
+                        .command("guide_workflow", serde_json::json!({ "workflow_type": workflow_type }))
+                        .await
+                        .map(|result| WorkflowProgress::from_started(&result)),
+                    WorkflowRequest::Advance(workflow_id) => agent
+                        .command("advance_workflow", serde_json::json!({ "workflow_id": workflow_id }))
+                        .await
+                        .map(|result| WorkflowProgress::from_advanced(&result)),
+                };
+
+                match result {
+                    Ok(Some(progress)) => {
+                        let _ = workflow_sender.send(WorkflowUpdatedEvent { progress });
+                    }
+                    Ok(None) => error!("Unexpected workflow result for {:?}", request),
+                    Err(e) => {
+                        let error_event = AgentErrorEvent {
+                            error: format!("Workflow request failed: {}", e),
+                            agent: None,
+                        };
+
+                        if let Err(e) = error_sender.send(error_event) {
+                            error!("Failed to send error: {}", e);
+                        }
+                    }
+                }
+            }
+            Some(request) = model_receiver.recv() => {
+                let result = match &request {
+                    ModelRequest::List => agent
+                        .query("list_models", serde_json::json!({}))
+                        .await
+                        .map(|result| ModelUpdate::Available {
+                            current: result["current"].as_str().unwrap_or_default().to_string(),
+                            models: result["models"]
+                                .as_array()
+                                .into_iter()
+                                .flatten()
+                                .filter_map(|m| m.as_str().map(String::from))
+                                .collect(),
+                        }),
+                    ModelRequest::Switch(model) => {
+                        let _ = status_sender.send(AgentStatus::Connecting);
+                        let result = agent
+                            .command("switch_model", serde_json::json!({ "model": model }))
+                            .await
+                            .map(|_| ModelUpdate::Switched(model.clone()));
+                        let _ = status_sender.send(AgentStatus::Ready);
+                        result
+                    }
+                };
+
+                match result {
+                    Ok(update) => {
+                        let _ = model_sender.send(update);
+                    }
+                    Err(e) => {
+                        let error_event = AgentErrorEvent {
+                            error: format!("Model request {:?} failed: {}", request, e),
+                            agent: None,
+                        };
+
+                        if let Err(e) = error_sender.send(error_event) {
+                            error!("Failed to send error: {}", e);
+                        }
+                    }
+                }
+            }
+            else => return Ok(()),
+        }
+    }
+}
+
+/// Helper function to send a question to the agent
+pub fn ask_agent(
+    question: impl Into<String>,
+    events: &mut EventWriter<AgentQuestionEvent>,
+) {
+    events.write(AgentQuestionEvent {
+        id: uuid::Uuid::new_v4().to_string(),
+        question: question.into(),
+        target: None,
+    });
+}
+
+/// Helper function to send a question to the agent on `target`
+pub fn ask_agent_handle(
+    target: Entity,
+    question: impl Into<String>,
+    events: &mut EventWriter<AgentQuestionEvent>,
+) {
+    events.write(AgentQuestionEvent {
+        id: uuid::Uuid::new_v4().to_string(),
+        question: question.into(),
+        target: Some(target),
+    });
+}
+
+/// System to handle keyboard input for agent questions
+pub fn handle_agent_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut events: EventWriter<AgentQuestionEvent>,
+) {
+    // Example: Press F1 to ask about CIM
+    if keyboard.just_pressed(KeyCode::F1) {
+        ask_agent("What is CIM?", &mut events);
+    }
+
+    // Example: Press F2 to ask about current graph
+    if keyboard.just_pressed(KeyCode::F2) {
+        ask_agent("Can you explain the current graph structure?", &mut events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_config_default() {
+        let config = AgentPluginConfig::default();
+        assert_eq!(config.nats_url, "nats://localhost:4222");
+        assert_eq!(config.ollama_url, "http://localhost:11434");
+        assert_eq!(config.model_name, "vicuna:latest");
+    }
+
+    #[test]
+    fn test_agent_config_maps_to_ollama() {
+        let config = AgentPluginConfig::default().agent_config();
+        assert_eq!(config.model.model_name(), "vicuna:latest");
+        assert!(matches!(
+            config.model,
+            crate::config::ModelConfig::Ollama { ref base_url, .. } if base_url == "http://localhost:11434"
+        ));
+    }
+
+    #[test]
+    fn test_handle_overrides_model_and_prefix() {
+        let config = AgentPluginConfig {
+            backend: AgentBackend::Nats {
+                subject_prefix: "cim.agent.alchemist".to_string(),
+            },
+            ..Default::default()
+        };
+        let handle = AgentHandle::new("Reviewer", "llama3:8b").with_subject_prefix("cim.agent.reviewer");
+
+        let handle_config = config.for_handle(&handle);
+        assert_eq!(handle_config.model_name, "llama3:8b");
+        assert_eq!(handle_config.agent_config().nats.subject_prefix, "cim.agent.reviewer");
+    }
+
+    #[test]
+    fn test_nats_backend_sets_subject_prefix() {
+        let config = AgentPluginConfig {
+            backend: AgentBackend::Nats {
+                subject_prefix: "cim.agent.remote".to_string(),
+            },
+            ..Default::default()
+        }
+        .agent_config();
+
+        assert_eq!(config.nats.subject_prefix, "cim.agent.remote");
+    }
+}
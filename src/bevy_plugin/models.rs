@@ -0,0 +1,63 @@
+//! Switching models at runtime
+//!
+//! Send `ListModelsEvent` to learn which models an agent can use and
+//! `SwitchModelEvent` to change the one answering, e.g. from a model picker
+//! in the editor that trades speed for quality. Results arrive as
+//! `AvailableModelsEvent` and `ModelSwitchedEvent`; failures as
+//! `AgentErrorEvent`.
+
+use bevy::prelude::*;
+use tracing::info;
+
+use super::{AgentHandle, AgentPluginConfig};
+
+/// Ask an agent for the models it can switch to
+#[derive(Event, Debug, Clone, Default)]
+pub struct ListModelsEvent {
+    /// Agent to ask, or the plugin's default agent
+    pub agent: Option<Entity>,
+}
+
+/// Models an agent can switch to
+#[derive(Event, Debug, Clone)]
+pub struct AvailableModelsEvent {
+    /// Model answering now
+    pub current: String,
+    pub models: Vec<String>,
+    pub agent: Option<Entity>,
+}
+
+/// Make an agent answer with another model
+#[derive(Event, Debug, Clone)]
+pub struct SwitchModelEvent {
+    pub model: String,
+    /// Agent to switch, or the plugin's default agent
+    pub agent: Option<Entity>,
+}
+
+/// An agent now answers with `model`
+#[derive(Event, Debug, Clone)]
+pub struct ModelSwitchedEvent {
+    pub model: String,
+    pub agent: Option<Entity>,
+}
+
+/// Keep the plugin config and agent handles naming the model in use
+pub(super) fn apply_model_switches(
+    mut events: EventReader<ModelSwitchedEvent>,
+    mut config: ResMut<AgentPluginConfig>,
+    mut handles: Query<&mut AgentHandle>,
+) {
+    for event in events.read() {
+        info!("Agent now uses model {}", event.model);
+
+        match event.agent {
+            None => config.model_name = event.model.clone(),
+            Some(entity) => {
+                if let Ok(mut handle) = handles.get_mut(entity) {
+                    handle.model = event.model.clone();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Saving and restoring the conversation across app restarts
+//!
+//! With `AgentPluginConfig::session_file` set, the plugin keeps a JSON
+//! sidecar holding the plugin configuration, the default agent's dialog id,
+//! and its conversation. The file is rewritten after every response and on
+//! exit, and read back at startup before the agent starts, so reopening the
+//! editor continues the same conversation with the same settings.
+//!
+//! Conversations with `AgentHandle` agents are tied to entities and are not
+//! saved.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::{info, warn};
+
+use super::conversation::{AgentConversation, ConversationTurn, Speaker};
+use super::{AgentChannels, AgentPluginConfig};
+use crate::error::Result;
+use crate::model::Message as ModelMessage;
+
+/// Contents of the session sidecar file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSession {
+    /// Dialog the default agent continues
+    pub dialog_id: String,
+    pub config: AgentPluginConfig,
+    /// Turns with the default agent, oldest first
+    pub turns: Vec<ConversationTurn>,
+}
+
+impl SavedSession {
+    /// Read a session file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Write the session file, replacing it only once fully written
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    /// Questions and responses in the form the agent keeps dialog history
+    pub fn history(&self) -> Vec<ModelMessage> {
+        self.turns
+            .iter()
+            .filter_map(|turn| {
+                let role = match turn.speaker {
+                    Speaker::User => "user",
+                    Speaker::Agent => "assistant",
+                    Speaker::Error => return None,
+                };
+
+                Some(ModelMessage {
+                    role: role.to_string(),
+                    content: turn.content.clone(),
+                    timestamp: turn.timestamp,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Dialog loaded from the session file, handed to the default agent
+#[derive(Resource, Clone)]
+pub(super) struct RestoredDialog {
+    pub(super) dialog_id: String,
+    pub(super) history: Vec<ModelMessage>,
+}
+
+/// Restore the configuration and conversation before the agent starts
+pub(super) fn load_session(
+    mut commands: Commands,
+    mut config: ResMut<AgentPluginConfig>,
+    mut conversation: ResMut<AgentConversation>,
+) {
+    let Some(path) = config.session_file.clone() else {
+        return;
+    };
+
+    if !path.exists() {
+        return;
+    }
+
+    let session = match SavedSession::load(&path) {
+        Ok(session) => session,
+        Err(e) => {
+            warn!("Ignoring session file {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    info!("Restoring {} turns from {}", session.turns.len(), path.display());
+
+    commands.insert_resource(RestoredDialog {
+        dialog_id: session.dialog_id.clone(),
+        history: session.history(),
+    });
+
+    // The path itself always comes from the app
+    *config = AgentPluginConfig {
+        session_file: Some(path),
+        ..session.config
+    };
+
+    conversation.clear();
+    for turn in session.turns {
+        conversation.push(turn);
+    }
+}
+
+/// Write the default agent's conversation to the session file
+pub(super) fn save_session(
+    config: Res<AgentPluginConfig>,
+    conversation: Res<AgentConversation>,
+    channels: Option<Res<AgentChannels>>,
+) {
+    let (Some(path), Some(channels)) = (&config.session_file, channels) else {
+        return;
+    };
+
+    let session = SavedSession {
+        dialog_id: channels.dialog_id.clone(),
+        config: config.clone(),
+        turns: conversation.with_agent(None).cloned().collect(),
+    };
+
+    if let Err(e) = session.save(path) {
+        warn!("Failed to save session to {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_session_round_trip() {
+        let turn = |speaker, content: &str| ConversationTurn {
+            speaker,
+            content: content.to_string(),
+            question_id: Some("q1".to_string()),
+            agent: None,
+            timestamp: Utc::now(),
+            suggestions: Vec::new(),
+        };
+
+        let session = SavedSession {
+            dialog_id: "dialog-1".to_string(),
+            config: AgentPluginConfig {
+                model_name: "llama3".to_string(),
+                ..default()
+            },
+            turns: vec![
+                turn(Speaker::User, "What is an aggregate?"),
+                turn(Speaker::Agent, "A consistency boundary."),
+                turn(Speaker::Error, "Timed out"),
+            ],
+        };
+
+        let restored: SavedSession =
+            serde_json::from_str(&serde_json::to_string(&session).unwrap()).unwrap();
+
+        assert_eq!(restored.dialog_id, "dialog-1");
+        assert_eq!(restored.config.model_name, "llama3");
+        assert_eq!(restored.turns.len(), 3);
+
+        let history = restored.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].role, "assistant");
+    }
+}
@@ -0,0 +1,203 @@
+//! Architecture graphs spawned into the Bevy world
+//!
+//! Turns the `visualize_architecture` command output into node and edge
+//! entities. Nodes are laid out on a circle in the XY plane and carry the
+//! concept id and label; edges reference their endpoint entities. Attach
+//! meshes, sprites, or gizmos to these components to render them.
+
+use bevy::prelude::*;
+use crate::error::{AgentError, Result};
+use serde::Deserialize;
+use tracing::warn;
+
+/// Distance of nodes from the graph origin
+const LAYOUT_RADIUS: f32 = 200.0;
+
+/// Ask the agent to visualize part of the CIM architecture
+#[derive(Event, Debug, Clone)]
+pub struct VisualizeArchitectureEvent {
+    /// `overview`, `domains`, or `events`
+    pub scope: String,
+}
+
+/// A `visualize_architecture` result ready to be spawned
+///
+/// Written by the plugin when the agent answers a
+/// `VisualizeArchitectureEvent`; apps may also write it directly with JSON
+/// received from a remote agent.
+#[derive(Event, Debug, Clone)]
+pub struct ArchitectureVisualizationEvent {
+    pub scope: String,
+    /// The `visualization` object: `{"nodes": [...], "edges": [...]}`
+    pub visualization: serde_json::Value,
+}
+
+/// Root of a spawned architecture graph
+#[derive(Component, Debug, Clone)]
+pub struct ArchitectureGraph {
+    pub scope: String,
+}
+
+/// A concept in an architecture graph
+#[derive(Component, Debug, Clone)]
+pub struct ArchitectureNode {
+    pub concept_id: String,
+    pub label: String,
+    pub node_type: String,
+}
+
+/// A relationship between two concepts in an architecture graph
+#[derive(Component, Debug, Clone)]
+pub struct ArchitectureEdge {
+    pub source: Entity,
+    pub target: Entity,
+    pub source_id: String,
+    pub target_id: String,
+    pub label: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeSpec {
+    id: String,
+    label: String,
+    #[serde(default, rename = "type")]
+    node_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EdgeSpec {
+    source: String,
+    target: String,
+    #[serde(default)]
+    label: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphSpec {
+    nodes: Vec<NodeSpec>,
+    #[serde(default)]
+    edges: Vec<EdgeSpec>,
+}
+
+/// Parse the visualization JSON, rejecting error payloads
+fn parse_visualization(visualization: &serde_json::Value) -> Result<GraphSpec> {
+    if let Some(error) = visualization["error"].as_str() {
+        return Err(AgentError::InvalidRequest(error.to_string()));
+    }
+
+    Ok(serde_json::from_value(visualization.clone())?)
+}
+
+/// Position of the `index`th of `count` nodes
+fn circle_position(index: usize, count: usize) -> Vec3 {
+    if count <= 1 {
+        return Vec3::ZERO;
+    }
+
+    let angle = std::f32::consts::TAU * index as f32 / count as f32;
+    Vec3::new(angle.cos() * LAYOUT_RADIUS, angle.sin() * LAYOUT_RADIUS, 0.0)
+}
+
+/// Replace the graph for each visualized scope with fresh entities
+pub(super) fn spawn_architecture_graphs(
+    mut events: EventReader<ArchitectureVisualizationEvent>,
+    existing: Query<(Entity, &ArchitectureGraph)>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let spec = match parse_visualization(&event.visualization) {
+            Ok(spec) => spec,
+            Err(e) => {
+                warn!("Cannot visualize {}: {}", event.scope, e);
+                continue;
+            }
+        };
+
+        for (entity, graph) in &existing {
+            if graph.scope == event.scope {
+                commands.entity(entity).despawn();
+            }
+        }
+
+        let root = commands
+            .spawn((
+                ArchitectureGraph { scope: event.scope.clone() },
+                Name::new(format!("Architecture: {}", event.scope)),
+                Transform::default(),
+            ))
+            .id();
+
+        let count = spec.nodes.len();
+        let mut nodes = std::collections::HashMap::new();
+
+        for (index, node) in spec.nodes.into_iter().enumerate() {
+            let entity = commands
+                .spawn((
+                    Name::new(node.label.clone()),
+                    Transform::from_translation(circle_position(index, count)),
+                    ArchitectureNode {
+                        concept_id: node.id.clone(),
+                        label: node.label,
+                        node_type: node.node_type,
+                    },
+                    ChildOf(root),
+                ))
+                .id();
+            nodes.insert(node.id, entity);
+        }
+
+        for edge in spec.edges {
+            let (Some(&source), Some(&target)) = (nodes.get(&edge.source), nodes.get(&edge.target)) else {
+                warn!("Skipping edge {} -> {}: unknown node", edge.source, edge.target);
+                continue;
+            };
+
+            commands.spawn((
+                Name::new(format!("{} -> {}", edge.source, edge.target)),
+                ArchitectureEdge {
+                    source,
+                    target,
+                    source_id: edge.source,
+                    target_id: edge.target,
+                    label: edge.label,
+                },
+                ChildOf(root),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_visualization() {
+        let spec = parse_visualization(&serde_json::json!({
+            "nodes": [
+                {"id": "command", "label": "Command", "type": "input"},
+                {"id": "event", "label": "Domain Event", "type": "output"},
+            ],
+            "edges": [
+                {"source": "command", "target": "event", "label": "emits"},
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(spec.nodes.len(), 2);
+        assert_eq!(spec.edges[0].label, "emits");
+
+        let error = serde_json::json!({"error": "Custom visualization for 'x' not yet implemented"});
+        assert!(parse_visualization(&error).is_err());
+    }
+
+    #[test]
+    fn test_circle_layout_spreads_nodes() {
+        assert_eq!(circle_position(0, 1), Vec3::ZERO);
+
+        let first = circle_position(0, 4);
+        let third = circle_position(2, 4);
+        assert!((first + third).length() < 1e-3);
+        assert!((first.length() - LAYOUT_RADIUS).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,357 @@
+//! Push-to-talk voice input
+//!
+//! Hold the talk key to record from the default microphone; on release the
+//! recording is transcribed with a local Whisper model on the async compute
+//! pool and sent to the agent as an `AgentQuestionEvent`.
+//!
+//! Audio capture runs on its own thread because input streams are not
+//! `Send` on every platform.
+
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, info, warn};
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+use super::AgentQuestionEvent;
+use crate::error::{AgentError, Result};
+
+/// Sample rate Whisper expects
+const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+/// Recordings shorter than this are treated as accidental presses
+const MIN_SAMPLES: usize = WHISPER_SAMPLE_RATE as usize / 2;
+
+/// Voice input settings
+#[derive(Resource, Debug, Clone)]
+pub struct VoiceInputConfig {
+    /// Path to a ggml Whisper model, e.g. `ggml-base.en.bin`
+    pub model_path: PathBuf,
+    /// Hold to record, release to ask
+    pub talk_key: KeyCode,
+    /// Spoken language; `None` lets Whisper detect it
+    pub language: Option<String>,
+}
+
+impl Default for VoiceInputConfig {
+    fn default() -> Self {
+        Self {
+            model_path: PathBuf::from("models/ggml-base.en.bin"),
+            talk_key: KeyCode::F8,
+            language: Some("en".to_string()),
+        }
+    }
+}
+
+/// A transcription was produced, before it is asked
+#[derive(Event, Debug, Clone)]
+pub struct VoiceTranscriptEvent {
+    pub text: String,
+}
+
+/// Whether the microphone is recording or a transcription is running
+#[derive(Resource, Default)]
+pub struct VoiceInputState {
+    pub recording: bool,
+    transcription: Option<Task<Result<String>>>,
+}
+
+impl VoiceInputState {
+    pub fn is_transcribing(&self) -> bool {
+        self.transcription.is_some()
+    }
+}
+
+/// Plugin adding push-to-talk voice input
+///
+/// Requires `AlchemistAgentPlugin`.
+#[derive(Default)]
+pub struct VoiceInputPlugin {
+    pub config: VoiceInputConfig,
+}
+
+impl Plugin for VoiceInputPlugin {
+    fn build(&self, app: &mut App) {
+        let context = match WhisperContext::new_with_params(
+            &self.config.model_path.to_string_lossy(),
+            WhisperContextParameters::default(),
+        ) {
+            Ok(context) => context,
+            Err(e) => {
+                error!(
+                    "Voice input disabled: cannot load Whisper model {}: {}",
+                    self.config.model_path.display(),
+                    e
+                );
+                return;
+            }
+        };
+
+        app.insert_resource(self.config.clone())
+            .insert_resource(Transcriber(Arc::new(context)))
+            .insert_resource(Recorder::spawn())
+            .init_resource::<VoiceInputState>()
+            .add_event::<VoiceTranscriptEvent>()
+            .add_systems(Update, (push_to_talk, poll_transcription).chain());
+    }
+}
+
+/// Shared Whisper model
+#[derive(Resource, Clone)]
+struct Transcriber(Arc<WhisperContext>);
+
+/// Raw microphone capture
+struct Recording {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+enum RecorderCommand {
+    Start,
+    Stop,
+}
+
+/// Handle to the capture thread
+#[derive(Resource)]
+struct Recorder {
+    commands: Sender<RecorderCommand>,
+    recordings: Receiver<Result<Recording>>,
+}
+
+impl Recorder {
+    fn spawn() -> Self {
+        let (command_tx, command_rx) = unbounded::<RecorderCommand>();
+        let (recording_tx, recording_rx) = unbounded::<Result<Recording>>();
+
+        let spawned = std::thread::Builder::new()
+            .name("alchemist-voice".to_string())
+            .spawn(move || {
+                while let Ok(command) = command_rx.recv() {
+                    if let RecorderCommand::Start = command {
+                        let _ = recording_tx.send(record_until_stop(&command_rx));
+                    }
+                }
+            });
+
+        if let Err(e) = spawned {
+            error!("Failed to start voice capture thread: {}", e);
+        }
+
+        Self {
+            commands: command_tx,
+            recordings: recording_rx,
+        }
+    }
+
+    fn start(&self) {
+        let _ = self.commands.send(RecorderCommand::Start);
+    }
+
+    /// Stop recording and wait briefly for the captured audio
+    fn stop(&self) -> Result<Recording> {
+        let _ = self.commands.send(RecorderCommand::Stop);
+
+        self.recordings
+            .recv_timeout(Duration::from_secs(2))
+            .map_err(|_| AgentError::Timeout("Voice capture did not stop".to_string()))?
+    }
+}
+
+/// Capture from the default input device until told to stop
+fn record_until_stop(commands: &Receiver<RecorderCommand>) -> Result<Recording> {
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or_else(|| AgentError::ServiceUnavailable("No microphone available".to_string()))?;
+    let config = device
+        .default_input_config()
+        .map_err(|e| AgentError::ServiceUnavailable(format!("Microphone unavailable: {}", e)))?;
+
+    let sample_rate = config.sample_rate().0;
+    let channels = config.channels();
+    let buffer = Arc::new(Mutex::new(Vec::<f32>::new()));
+    let on_error = |e: cpal::StreamError| warn!("Voice capture error: {}", e);
+
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => {
+            let buffer = buffer.clone();
+            device.build_input_stream(
+                &config.config(),
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    buffer.lock().unwrap_or_else(|e| e.into_inner()).extend_from_slice(data);
+                },
+                on_error,
+                None,
+            )
+        }
+        cpal::SampleFormat::I16 => {
+            let buffer = buffer.clone();
+            device.build_input_stream(
+                &config.config(),
+                move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                    buffer
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .extend(data.iter().map(|s| *s as f32 / i16::MAX as f32));
+                },
+                on_error,
+                None,
+            )
+        }
+        other => {
+            return Err(AgentError::Configuration(format!(
+                "Unsupported microphone sample format: {:?}",
+                other
+            )))
+        }
+    }
+    .map_err(|e| AgentError::ServiceUnavailable(format!("Failed to open microphone: {}", e)))?;
+
+    stream
+        .play()
+        .map_err(|e| AgentError::ServiceUnavailable(format!("Failed to start microphone: {}", e)))?;
+
+    // Keep recording through repeated presses; stop on release or shutdown
+    while let Ok(RecorderCommand::Start) = commands.recv() {}
+    drop(stream);
+
+    let samples = std::mem::take(&mut *buffer.lock().unwrap_or_else(|e| e.into_inner()));
+    Ok(Recording {
+        samples,
+        sample_rate,
+        channels,
+    })
+}
+
+/// Downmix to mono and resample to 16 kHz for Whisper
+fn to_whisper_input(samples: &[f32], channels: u16, sample_rate: u32) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    let mono: Vec<f32> = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+
+    if sample_rate == WHISPER_SAMPLE_RATE || mono.is_empty() {
+        return mono;
+    }
+
+    // Linear interpolation is plenty for speech
+    let ratio = sample_rate as f64 / WHISPER_SAMPLE_RATE as f64;
+    let len = (mono.len() as f64 / ratio) as usize;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let index = position as usize;
+            let fraction = (position - index as f64) as f32;
+            let current = mono[index];
+            let next = mono.get(index + 1).copied().unwrap_or(current);
+            current + (next - current) * fraction
+        })
+        .collect()
+}
+
+fn transcribe(context: &WhisperContext, audio: &[f32], language: Option<&str>) -> Result<String> {
+    let whisper_error = |e: whisper_rs::WhisperError| AgentError::Internal(format!("Transcription failed: {}", e));
+
+    let mut state = context.create_state().map_err(whisper_error)?;
+
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_language(language);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_special(false);
+    params.set_print_timestamps(false);
+
+    state.full(params, audio).map_err(whisper_error)?;
+
+    let mut text = String::new();
+    for segment in 0..state.full_n_segments().map_err(whisper_error)? {
+        text.push_str(&state.full_get_segment_text(segment).map_err(whisper_error)?);
+    }
+
+    Ok(text.trim().to_string())
+}
+
+/// Record while the talk key is held and transcribe on release
+fn push_to_talk(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    config: Res<VoiceInputConfig>,
+    recorder: Res<Recorder>,
+    transcriber: Res<Transcriber>,
+    mut state: ResMut<VoiceInputState>,
+) {
+    if keyboard.just_pressed(config.talk_key) && !state.recording && !state.is_transcribing() {
+        info!("Listening…");
+        recorder.start();
+        state.recording = true;
+    }
+
+    if keyboard.just_released(config.talk_key) && state.recording {
+        state.recording = false;
+
+        let recording = match recorder.stop() {
+            Ok(recording) => recording,
+            Err(e) => {
+                error!("Voice capture failed: {}", e);
+                return;
+            }
+        };
+
+        let audio = to_whisper_input(&recording.samples, recording.channels, recording.sample_rate);
+        if audio.len() < MIN_SAMPLES {
+            return;
+        }
+
+        let context = transcriber.0.clone();
+        let language = config.language.clone();
+        state.transcription = Some(
+            AsyncComputeTaskPool::get()
+                .spawn(async move { transcribe(&context, &audio, language.as_deref()) }),
+        );
+    }
+}
+
+/// Ask the agent once a transcription finishes
+fn poll_transcription(
+    mut state: ResMut<VoiceInputState>,
+    mut transcripts: EventWriter<VoiceTranscriptEvent>,
+    mut questions: EventWriter<AgentQuestionEvent>,
+) {
+    let Some(task) = state.transcription.as_mut() else {
+        return;
+    };
+
+    let Some(result) = block_on(future::poll_once(task)) else {
+        return;
+    };
+    state.transcription = None;
+
+    match result {
+        Ok(text) if !text.is_empty() => {
+            transcripts.write(VoiceTranscriptEvent { text: text.clone() });
+            super::ask_agent(text, &mut questions);
+        }
+        Ok(_) => info!("Heard nothing to ask"),
+        Err(e) => error!("{}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whisper_input_downmixes_and_resamples() {
+        // One second of stereo 48 kHz audio
+        let samples: Vec<f32> = (0..48_000).flat_map(|_| [0.5, -0.5]).collect();
+
+        let audio = to_whisper_input(&samples, 2, 48_000);
+
+        assert_eq!(audio.len(), 16_000);
+        assert!(audio.iter().all(|s| s.abs() < f32::EPSILON));
+    }
+}
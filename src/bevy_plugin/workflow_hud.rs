@@ -0,0 +1,296 @@
+//! Workflow guidance in Bevy
+//!
+//! Start and advance the agent's guided workflows from the app, track the
+//! active one in `ActiveWorkflow`, and (with `bevy-ui`) show its current
+//! step, instructions, and progress in a HUD overlay. The advance key
+//! moves to the next step; advancing the last step completes the workflow.
+
+use bevy::prelude::*;
+
+/// Start a guided workflow (`create_agent`, `implement_domain`, `add_event`)
+#[derive(Event, Debug, Clone)]
+pub struct StartWorkflowEvent {
+    pub workflow_type: String,
+}
+
+/// Advance the active workflow to its next step
+#[derive(Event, Debug, Clone, Default)]
+pub struct AdvanceWorkflowEvent;
+
+/// The agent reported new workflow state
+#[derive(Event, Debug, Clone)]
+pub struct WorkflowUpdatedEvent {
+    pub progress: WorkflowProgress,
+}
+
+/// Where a workflow stands
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkflowProgress {
+    pub workflow_id: String,
+    /// Id of the current step; `None` once completed
+    pub current_step: Option<String>,
+    pub title: String,
+    pub instructions: Vec<String>,
+    /// Percent complete, 0–100
+    pub progress: f32,
+    pub completed: bool,
+}
+
+impl WorkflowProgress {
+    /// Read the result of the `guide_workflow` command
+    pub fn from_started(result: &serde_json::Value) -> Option<Self> {
+        let step = &result["first_step"];
+
+        Some(Self {
+            workflow_id: result["workflow_id"].as_str()?.to_string(),
+            current_step: step["step"].as_str().map(String::from),
+            title: step["title"].as_str().unwrap_or_default().to_string(),
+            instructions: instructions(step),
+            progress: 0.0,
+            completed: false,
+        })
+    }
+
+    /// Read the result of the `advance_workflow` command
+    pub fn from_advanced(result: &serde_json::Value) -> Option<Self> {
+        let completed = result["status"].as_str() == Some("Completed");
+        let step = &result["step"];
+
+        Some(Self {
+            workflow_id: result["workflow_id"].as_str()?.to_string(),
+            current_step: (!completed)
+                .then(|| result["current_step"].as_str().map(String::from))
+                .flatten(),
+            title: if completed {
+                "Workflow complete".to_string()
+            } else {
+                step["title"]
+                    .as_str()
+                    .or_else(|| step["step"].as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            },
+            instructions: instructions(step),
+            progress: if completed {
+                100.0
+            } else {
+                result["progress"].as_f64().unwrap_or(0.0) as f32
+            },
+            completed,
+        })
+    }
+}
+
+/// Description and action list of a step, in display order
+fn instructions(step: &serde_json::Value) -> Vec<String> {
+    step["description"]
+        .as_str()
+        .into_iter()
+        .chain(step["actions"].as_array().into_iter().flatten().filter_map(|a| a.as_str()))
+        .map(String::from)
+        .collect()
+}
+
+/// The workflow being guided, if any
+#[derive(Resource, Debug, Default)]
+pub struct ActiveWorkflow {
+    pub progress: Option<WorkflowProgress>,
+}
+
+/// Key bindings for workflow guidance
+#[derive(Resource, Debug, Clone)]
+pub struct WorkflowKeys {
+    /// Advance or complete the current step
+    pub advance: KeyCode,
+}
+
+impl Default for WorkflowKeys {
+    fn default() -> Self {
+        Self {
+            advance: KeyCode::F9,
+        }
+    }
+}
+
+/// Track the latest state of the active workflow
+pub(super) fn track_active_workflow(
+    mut updates: EventReader<WorkflowUpdatedEvent>,
+    mut active: ResMut<ActiveWorkflow>,
+) {
+    for update in updates.read() {
+        active.progress = Some(update.progress.clone());
+    }
+}
+
+/// Advance the active workflow on the configured key
+pub(super) fn advance_workflow_on_key(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    keys: Res<WorkflowKeys>,
+    active: Res<ActiveWorkflow>,
+    mut events: EventWriter<AdvanceWorkflowEvent>,
+) {
+    let running = active.progress.as_ref().is_some_and(|progress| !progress.completed);
+
+    if running && keyboard.just_pressed(keys.advance) {
+        events.write(AdvanceWorkflowEvent);
+    }
+}
+
+#[cfg(feature = "bevy-ui")]
+pub use hud::WorkflowHud;
+
+#[cfg(feature = "bevy-ui")]
+mod hud {
+    use super::ActiveWorkflow;
+    use bevy::prelude::*;
+
+    /// Root of the workflow HUD overlay
+    #[derive(Component, Debug)]
+    pub struct WorkflowHud;
+
+    #[derive(Component)]
+    pub(in super::super) struct WorkflowHudTitle;
+
+    #[derive(Component)]
+    pub(in super::super) struct WorkflowHudInstructions;
+
+    #[derive(Component)]
+    pub(in super::super) struct WorkflowHudProgress;
+
+    fn hud() -> impl Bundle {
+        (
+            WorkflowHud,
+            Name::new("Workflow HUD"),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                right: Val::Px(12.0),
+                width: Val::Px(320.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.1, 0.1, 0.12, 0.9)),
+            Visibility::Hidden,
+            children![
+                (
+                    WorkflowHudTitle,
+                    Text::default(),
+                    TextFont {
+                        font_size: 18.0,
+                        ..default()
+                    },
+                ),
+                (
+                    WorkflowHudInstructions,
+                    Text::default(),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                ),
+                (
+                    Node {
+                        height: Val::Px(8.0),
+                        width: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.25, 0.25, 0.28)),
+                    children![(
+                        WorkflowHudProgress,
+                        Node {
+                            height: Val::Percent(100.0),
+                            width: Val::Percent(0.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.4, 0.8, 0.5)),
+                    )],
+                ),
+            ],
+        )
+    }
+
+    pub(in super::super) fn spawn_workflow_hud(mut commands: Commands) {
+        commands.spawn(hud());
+    }
+
+    /// Mirror `ActiveWorkflow` into the HUD
+    pub(in super::super) fn update_workflow_hud(
+        active: Res<ActiveWorkflow>,
+        mut huds: Query<&mut Visibility, With<WorkflowHud>>,
+        mut titles: Query<&mut Text, (With<WorkflowHudTitle>, Without<WorkflowHudInstructions>)>,
+        mut instructions: Query<&mut Text, (With<WorkflowHudInstructions>, Without<WorkflowHudTitle>)>,
+        mut bars: Query<&mut Node, With<WorkflowHudProgress>>,
+    ) {
+        let Some(progress) = &active.progress else {
+            for mut visibility in &mut huds {
+                *visibility = Visibility::Hidden;
+            }
+            return;
+        };
+
+        for mut visibility in &mut huds {
+            *visibility = Visibility::Inherited;
+        }
+        for mut text in &mut titles {
+            text.0 = progress.title.clone();
+        }
+        for mut text in &mut instructions {
+            text.0 = progress
+                .instructions
+                .iter()
+                .map(|line| format!("• {}", line))
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        for mut node in &mut bars {
+            node.width = Val::Percent(progress.progress.clamp(0.0, 100.0));
+        }
+    }
+}
+
+#[cfg(feature = "bevy-ui")]
+pub(super) use hud::{spawn_workflow_hud, update_workflow_hud};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_progress_from_started() {
+        let progress = WorkflowProgress::from_started(&json!({
+            "workflow_id": "wf-1",
+            "status": "started",
+            "first_step": {
+                "step": "define",
+                "title": "Define Event Structure",
+                "description": "Create the event type and its properties",
+                "actions": ["Choose event name (past tense)"],
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(progress.current_step.as_deref(), Some("define"));
+        assert_eq!(progress.title, "Define Event Structure");
+        assert_eq!(progress.instructions.len(), 2);
+    }
+
+    #[test]
+    fn test_progress_from_completed() {
+        let progress = WorkflowProgress::from_advanced(&json!({
+            "workflow_id": "wf-1",
+            "previous_step": "deploy",
+            "current_step": "none",
+            "step": null,
+            "status": "Completed",
+            "progress": 0.0,
+        }))
+        .unwrap();
+
+        assert!(progress.completed);
+        assert_eq!(progress.current_step, None);
+        assert_eq!(progress.progress, 100.0);
+    }
+}
@@ -0,0 +1,167 @@
+//! Token budgets for dialogs and users
+//!
+//! Providers do not all report usage, so tokens are estimated from the text
+//! sent to and received from the model. A dialog counts against its own
+//! budget for its whole life and against its user's budget, taken from
+//! `metadata.user` on dialog messages, for the current period.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::config::BudgetConfig;
+
+/// Rough token count of `text`, at about four characters per token
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// A budget that has been used up
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Exceeded {
+    /// `dialog` or `user`
+    pub scope: &'static str,
+
+    /// Dialog ID or user name
+    pub key: String,
+
+    pub used: usize,
+    pub limit: usize,
+}
+
+impl std::fmt::Display for Exceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The token budget for {} {} is used up ({} of {} tokens)",
+            self.scope, self.key, self.used, self.limit
+        )
+    }
+}
+
+/// Tokens used so far, by dialog and by user
+pub struct TokenBudgets {
+    config: BudgetConfig,
+    dialogs: Mutex<HashMap<String, usize>>,
+    users: Mutex<HashMap<String, (usize, Instant)>>,
+}
+
+impl TokenBudgets {
+    pub fn new(config: &BudgetConfig) -> Self {
+        Self {
+            config: config.clone(),
+            dialogs: Mutex::new(HashMap::new()),
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The first budget `dialog_id` or `user` has used up, if any
+    pub fn check(&self, dialog_id: &str, user: Option<&str>) -> Option<Exceeded> {
+        if let Some(limit) = self.config.per_dialog {
+            let used = self.dialogs.lock().unwrap().get(dialog_id).copied().unwrap_or(0);
+            if used >= limit {
+                return Some(Exceeded {
+                    scope: "dialog",
+                    key: dialog_id.to_string(),
+                    used,
+                    limit,
+                });
+            }
+        }
+
+        if let (Some(limit), Some(user)) = (self.config.per_user, user) {
+            let used = self.user_usage(user);
+            if used >= limit {
+                return Some(Exceeded {
+                    scope: "user",
+                    key: user.to_string(),
+                    used,
+                    limit,
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Count `tokens` against `dialog_id` and `user`
+    pub fn record(&self, dialog_id: &str, user: Option<&str>, tokens: usize) {
+        *self.dialogs.lock().unwrap().entry(dialog_id.to_string()).or_insert(0) += tokens;
+
+        if let Some(user) = user {
+            let mut users = self.users.lock().unwrap();
+            let (used, started) = users.entry(user.to_string()).or_insert((0, Instant::now()));
+            if started.elapsed() >= self.config.period {
+                *used = 0;
+                *started = Instant::now();
+            }
+            *used += tokens;
+        }
+    }
+
+    /// Tokens `dialog_id` has used
+    pub fn dialog_usage(&self, dialog_id: &str) -> usize {
+        self.dialogs.lock().unwrap().get(dialog_id).copied().unwrap_or(0)
+    }
+
+    /// Tokens `user` has used this period
+    pub fn user_usage(&self, user: &str) -> usize {
+        match self.users.lock().unwrap().get(user) {
+            Some((used, started)) if started.elapsed() < self.config.period => *used,
+            _ => 0,
+        }
+    }
+
+    /// Forget an ended dialog
+    pub fn forget_dialog(&self, dialog_id: &str) {
+        self.dialogs.lock().unwrap().remove(dialog_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn budgets(per_dialog: Option<usize>, per_user: Option<usize>, period: Duration) -> TokenBudgets {
+        TokenBudgets::new(&BudgetConfig {
+            per_dialog,
+            per_user,
+            period,
+            ..BudgetConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_estimate_rounds_up() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("CQRS"), 1);
+        assert_eq!(estimate_tokens("Event Sourcing"), 4);
+    }
+
+    #[test]
+    fn test_dialog_and_user_budgets() {
+        let budgets = budgets(Some(100), Some(150), Duration::from_secs(3600));
+
+        budgets.record("d1", Some("ada"), 99);
+        assert_eq!(budgets.check("d1", Some("ada")), None);
+
+        budgets.record("d1", Some("ada"), 1);
+        assert_eq!(budgets.check("d1", Some("ada")).map(|exceeded| exceeded.scope), Some("dialog"));
+
+        budgets.record("d2", Some("ada"), 50);
+        let exceeded = budgets.check("d2", Some("ada")).unwrap();
+        assert_eq!((exceeded.scope, exceeded.used, exceeded.limit), ("user", 150, 150));
+        assert_eq!(budgets.check("d2", Some("grace")), None);
+        assert_eq!(budgets.check("d2", None), None);
+    }
+
+    #[test]
+    fn test_user_budget_resets_each_period() {
+        let budgets = budgets(None, Some(10), Duration::ZERO);
+
+        budgets.record("d1", Some("ada"), 50);
+        assert_eq!(budgets.user_usage("ada"), 0);
+        assert_eq!(budgets.check("d1", Some("ada")), None);
+    }
+}
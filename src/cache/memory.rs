@@ -0,0 +1,57 @@
+//! In-memory cache, private to one process
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use super::CacheStore;
+use crate::error::Result;
+
+/// Entries kept before expired ones are swept out
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// Keeps values and counters in process memory
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    values: Mutex<HashMap<String, (Vec<u8>, Instant)>>,
+    counters: Mutex<HashMap<String, (u64, Instant)>>,
+}
+
+#[async_trait]
+impl CacheStore for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let values = self.values.lock().await;
+        Ok(values
+            .get(key)
+            .filter(|(_, expires)| *expires > Instant::now())
+            .map(|(value, _)| value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        let now = Instant::now();
+        let mut values = self.values.lock().await;
+        if values.len() >= SWEEP_THRESHOLD {
+            values.retain(|_, (_, expires)| *expires > now);
+        }
+
+        values.insert(key.to_string(), (value.to_vec(), now + ttl));
+        Ok(())
+    }
+
+    async fn increment(&self, key: &str, window: Duration) -> Result<u64> {
+        let now = Instant::now();
+        let mut counters = self.counters.lock().await;
+        if counters.len() >= SWEEP_THRESHOLD {
+            counters.retain(|_, (_, expires)| *expires > now);
+        }
+
+        let counter = counters.entry(key.to_string()).or_insert((0, now + window));
+        if counter.1 <= now {
+            *counter = (0, now + window);
+        }
+        counter.0 += 1;
+
+        Ok(counter.0)
+    }
+}
@@ -0,0 +1,195 @@
+//! Caches for model responses, embeddings, and the results of idempotent
+//! commands, and rate-limit counters
+//!
+//! Values live in a `CacheStore` chosen by `cache.backend`: process memory
+//! by default, or Redis so that replicas share caches and limits. A failing
+//! cache never fails a request; lookups miss and limits allow instead.
+
+pub mod memory;
+#[cfg(feature = "redis")]
+pub mod redis;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::{CacheBackend, CacheConfig};
+use crate::error::Result;
+
+pub use memory::MemoryCache;
+
+/// Expiring key-value storage and counters
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()>;
+
+    /// Add one to a counter that expires `window` after its first increment
+    ///
+    /// Returns the count including this increment.
+    async fn increment(&self, key: &str, window: Duration) -> Result<u64>;
+}
+
+/// The agent's caches over one store
+#[derive(Clone)]
+pub struct Caches {
+    store: Arc<dyn CacheStore>,
+    response_ttl: Duration,
+    embedding_ttl: Duration,
+    idempotency_ttl: Duration,
+}
+
+impl Caches {
+    pub fn new(store: Arc<dyn CacheStore>, config: &CacheConfig) -> Self {
+        Self {
+            store,
+            response_ttl: config.response_ttl,
+            embedding_ttl: config.embedding_ttl,
+            idempotency_ttl: config.idempotency_ttl,
+        }
+    }
+
+    /// A response `model` gave to `prompt` before
+    pub async fn response(&self, model: &str, prompt: &str) -> Option<String> {
+        if self.response_ttl.is_zero() {
+            return None;
+        }
+
+        let value = self.get(&key("response", model, prompt)).await?;
+        String::from_utf8(value).ok()
+    }
+
+    pub async fn store_response(&self, model: &str, prompt: &str, response: &str) {
+        if self.response_ttl.is_zero() {
+            return;
+        }
+
+        self.set(&key("response", model, prompt), response.as_bytes(), self.response_ttl)
+            .await;
+    }
+
+    /// The embedding `model` computed for `text` before
+    pub async fn embedding(&self, model: &str, text: &str) -> Option<Vec<f32>> {
+        let value = self.get(&key("embedding", model, text)).await?;
+        serde_json::from_slice(&value).ok()
+    }
+
+    pub async fn store_embedding(&self, model: &str, text: &str, embedding: &[f32]) {
+        if let Ok(value) = serde_json::to_vec(embedding) {
+            self.set(&key("embedding", model, text), &value, self.embedding_ttl).await;
+        }
+    }
+
+    /// The result of a command `origin` sent before with `idempotency_key`
+    pub async fn command_result(&self, origin: &str, idempotency_key: &str) -> Option<serde_json::Value> {
+        let value = self.get(&key("command", origin, idempotency_key)).await?;
+        serde_json::from_slice(&value).ok()
+    }
+
+    pub async fn store_command_result(&self, origin: &str, idempotency_key: &str, result: &serde_json::Value) {
+        if let Ok(value) = serde_json::to_vec(result) {
+            self.set(&key("command", origin, idempotency_key), &value, self.idempotency_ttl)
+                .await;
+        }
+    }
+
+    /// Count a use of `subject` and whether it stays within `limit` per `window`
+    pub async fn allow(&self, subject: &str, limit: u64, window: Duration) -> bool {
+        match self.store.increment(&format!("ratelimit:{}", subject), window).await {
+            Ok(count) => count <= limit,
+            Err(e) => {
+                warn!("Rate limit counter unavailable, allowing {}: {}", subject, e);
+                true
+            }
+        }
+    }
+
+    async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        match self.store.get(key).await {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("Cache lookup failed: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Duration) {
+        if let Err(e) = self.store.set(key, value, ttl).await {
+            warn!("Cache update failed: {}", e);
+        }
+    }
+}
+
+/// Connect to the configured cache backend
+pub async fn open(config: &CacheConfig) -> Result<Caches> {
+    let store: Arc<dyn CacheStore> = match &config.backend {
+        CacheBackend::Memory => Arc::new(MemoryCache::default()),
+        CacheBackend::Redis { url, key_prefix } => open_redis(url, key_prefix).await?,
+    };
+
+    Ok(Caches::new(store, config))
+}
+
+#[cfg(feature = "redis")]
+async fn open_redis(url: &str, key_prefix: &str) -> Result<Arc<dyn CacheStore>> {
+    Ok(Arc::new(self::redis::RedisCache::connect(url, key_prefix).await?))
+}
+
+#[cfg(not(feature = "redis"))]
+async fn open_redis(_url: &str, _key_prefix: &str) -> Result<Arc<dyn CacheStore>> {
+    Err(crate::error::AgentError::Configuration(
+        "Redis caching is configured but the agent was built without the `redis` feature".to_string(),
+    ))
+}
+
+/// Cache key for `input` to `model`
+///
+/// Inputs are hashed with FNV-1a, which unlike `DefaultHasher` gives the
+/// same key in every build, so replicas agree on keys.
+fn key(kind: &str, model: &str, input: &str) -> String {
+    let hash = input.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{}:{}:{:016x}:{}", kind, model, hash, input.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_is_stable() {
+        assert_eq!(key("response", "vicuna", ""), "response:vicuna:cbf29ce484222325:0");
+        assert_ne!(key("response", "vicuna", "CQRS"), key("response", "llama3", "CQRS"));
+    }
+
+    #[tokio::test]
+    async fn test_responses_and_limits() {
+        let caches = Caches::new(Arc::new(MemoryCache::default()), &CacheConfig::default());
+
+        assert_eq!(caches.response("vicuna", "Explain CQRS").await, None);
+        caches.store_response("vicuna", "Explain CQRS", "Separate reads from writes.").await;
+        assert_eq!(
+            caches.response("vicuna", "Explain CQRS").await.as_deref(),
+            Some("Separate reads from writes.")
+        );
+
+        let window = Duration::from_secs(60);
+        assert!(caches.allow("dialog-1", 2, window).await);
+        assert!(caches.allow("dialog-1", 2, window).await);
+        assert!(!caches.allow("dialog-1", 2, window).await);
+    }
+
+    #[tokio::test]
+    async fn test_command_results_by_origin() {
+        let caches = Caches::new(Arc::new(MemoryCache::default()), &CacheConfig::default());
+        let result = serde_json::json!({ "workflow_id": "wf-1" });
+
+        caches.store_command_result("ci", "seed-42", &result).await;
+        assert_eq!(caches.command_result("ci", "seed-42").await, Some(result));
+        assert_eq!(caches.command_result("billing", "seed-42").await, None);
+    }
+}
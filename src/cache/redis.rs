@@ -0,0 +1,71 @@
+//! Redis cache, shared by every replica pointed at the same server
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use std::time::Duration;
+
+use super::CacheStore;
+use crate::error::{AgentError, Result};
+
+impl From<redis::RedisError> for AgentError {
+    fn from(error: redis::RedisError) -> Self {
+        AgentError::ServiceUnavailable(format!("Redis: {}", error))
+    }
+}
+
+/// Keeps values and counters in Redis under a key prefix
+#[derive(Clone)]
+pub struct RedisCache {
+    connection: ConnectionManager,
+    key_prefix: String,
+}
+
+impl RedisCache {
+    /// Connect to `url`; the connection is re-established if it drops
+    pub async fn connect(url: &str, key_prefix: &str) -> Result<Self> {
+        let client = redis::Client::open(url)
+            .map_err(|e| AgentError::Configuration(format!("Invalid Redis URL {}: {}", url, e)))?;
+
+        Ok(Self {
+            connection: ConnectionManager::new(client).await?,
+            key_prefix: key_prefix.to_string(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}:{}", self.key_prefix, key)
+    }
+}
+
+/// Redis expiries are whole seconds and must be positive
+fn seconds(duration: Duration) -> u64 {
+    duration.as_secs().max(1)
+}
+
+#[async_trait]
+impl CacheStore for RedisCache {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let mut connection = self.connection.clone();
+        Ok(connection.get(self.key(key)).await?)
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let _: () = connection.set_ex(self.key(key), value, seconds(ttl)).await?;
+        Ok(())
+    }
+
+    async fn increment(&self, key: &str, window: Duration) -> Result<u64> {
+        let key = self.key(key);
+        let mut connection = self.connection.clone();
+
+        let count: u64 = connection.incr(&key, 1).await?;
+        if count == 1 {
+            // First use in this window starts its clock
+            let _: () = connection.expire(&key, seconds(window) as i64).await?;
+        }
+
+        Ok(count)
+    }
+}
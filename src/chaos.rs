@@ -0,0 +1,186 @@
+//! Fault injection for exercising retries and degraded mode
+//!
+//! Enabled with the `chaos` feature, and inactive until the admin command
+//! `inject_faults` sets some faults. Incoming commands, queries, and dialog
+//! messages can be dropped before they are handled, and model calls,
+//! health checks included, can be delayed or fail as if the model server
+//! had answered 500. `clear_faults` turns everything off again.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::error::{AgentError, Result};
+use crate::model::{ChunkStream, Message, ModelInfo, ModelProvider, ModelTurn, ToolExchange, ToolSpec};
+
+/// Faults currently injected
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Faults {
+    /// Share of incoming NATS messages dropped, from 0 to 100
+    #[serde(default)]
+    pub drop_percent: u8,
+
+    /// Delay added to every model call
+    #[serde(default)]
+    pub model_latency_ms: u64,
+
+    /// Share of model calls failing with a server error, from 0 to 100
+    #[serde(default)]
+    pub model_error_percent: u8,
+}
+
+/// Faults shared by the agent's model and its NATS subscriptions
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    faults: RwLock<Faults>,
+}
+
+impl FaultInjector {
+    pub fn faults(&self) -> Faults {
+        self.faults.read().unwrap().clone()
+    }
+
+    /// Change the fields `changes` names, keeping the others
+    pub fn update(&self, changes: &serde_json::Value) -> Result<Faults> {
+        let mut merged = serde_json::to_value(self.faults())?;
+        for (field, value) in changes.as_object().into_iter().flatten() {
+            if merged.get(field).is_none() {
+                return Err(AgentError::InvalidRequest(format!("Unknown fault {}", field)));
+            }
+            merged[field] = value.clone();
+        }
+
+        let faults: Faults = serde_json::from_value(merged)
+            .map_err(|e| AgentError::InvalidRequest(format!("Invalid faults: {}", e)))?;
+        if faults.drop_percent > 100 || faults.model_error_percent > 100 {
+            return Err(AgentError::InvalidRequest("Fault percentages go up to 100".to_string()));
+        }
+
+        *self.faults.write().unwrap() = faults.clone();
+        Ok(faults)
+    }
+
+    pub fn clear(&self) {
+        *self.faults.write().unwrap() = Faults::default();
+    }
+
+    /// Whether to drop the next incoming message
+    pub fn drop_message(&self) -> bool {
+        roll(self.faults.read().unwrap().drop_percent)
+    }
+
+    /// Wait out the injected latency, then fail if the model call should
+    async fn before_model_call(&self) -> Result<()> {
+        let faults = self.faults();
+        if faults.model_latency_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(faults.model_latency_ms)).await;
+        }
+        if roll(faults.model_error_percent) {
+            return Err(AgentError::ModelProvider(
+                "Injected fault: model server returned 500 Internal Server Error".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// True `percent` times out of 100
+fn roll(percent: u8) -> bool {
+    percent > 0 && (uuid::Uuid::new_v4().as_u128() % 100) < u128::from(percent)
+}
+
+/// A model provider subject to injected faults
+pub struct Faulty {
+    provider: Box<dyn ModelProvider>,
+    injector: Arc<FaultInjector>,
+}
+
+impl Faulty {
+    pub fn new(provider: Box<dyn ModelProvider>, injector: Arc<FaultInjector>) -> Self {
+        Self { provider, injector }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for Faulty {
+    async fn generate(&self, prompt: &str) -> Result<String> {
+        self.injector.before_model_call().await?;
+        self.provider.generate(prompt).await
+    }
+
+    async fn generate_with_context(&self, prompt: &str, context: &[Message]) -> Result<String> {
+        self.injector.before_model_call().await?;
+        self.provider.generate_with_context(prompt, context).await
+    }
+
+    async fn generate_stream(&self, prompt: &str, context: &[Message]) -> Result<ChunkStream> {
+        self.injector.before_model_call().await?;
+        self.provider.generate_stream(prompt, context).await
+    }
+
+    async fn generate_with_tools(
+        &self,
+        prompt: &str,
+        context: &[Message],
+        tools: &[ToolSpec],
+        exchanges: &[ToolExchange],
+    ) -> Result<ModelTurn> {
+        self.injector.before_model_call().await?;
+        self.provider.generate_with_tools(prompt, context, tools, exchanges).await
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.injector.before_model_call().await?;
+        self.provider.embed(text).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.injector.before_model_call().await?;
+        self.provider.health_check().await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        self.provider.list_models().await
+    }
+
+    fn model_info(&self) -> ModelInfo {
+        self.provider.model_info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::MockProvider;
+
+    #[test]
+    fn test_update_merges_and_validates() {
+        let injector = FaultInjector::default();
+
+        let faults = injector.update(&serde_json::json!({ "drop_percent": 100 })).unwrap();
+        assert_eq!(faults.drop_percent, 100);
+        let faults = injector.update(&serde_json::json!({ "model_latency_ms": 250 })).unwrap();
+        assert_eq!((faults.drop_percent, faults.model_latency_ms), (100, 250));
+        assert!(injector.drop_message());
+
+        assert!(injector.update(&serde_json::json!({ "model_error_percent": 101 })).is_err());
+        assert!(injector.update(&serde_json::json!({ "drop_everything": true })).is_err());
+
+        injector.clear();
+        assert!(!injector.drop_message());
+    }
+
+    #[tokio::test]
+    async fn test_faulty_model_fails_and_recovers() {
+        let injector = Arc::new(FaultInjector::default());
+        let model = Faulty::new(Box::new(MockProvider::new("OK".to_string())), injector.clone());
+
+        injector.update(&serde_json::json!({ "model_error_percent": 100 })).unwrap();
+        assert!(model.health_check().await.is_err());
+        assert!(model.generate("Hello").await.is_err());
+
+        injector.clear();
+        assert_eq!(model.generate("Hello").await.unwrap(), "OK");
+    }
+}
@@ -0,0 +1,322 @@
+//! Client for driving a running Alchemist agent over NATS
+//!
+//! This module lets tools outside the agent process (the CLI, CI scripts,
+//! other services) send commands and queries using the same request-reply
+//! envelope the agent answers with, and hold conversations over the dialog
+//! subjects.
+
+use crate::config::NatsConfig;
+use crate::error::{AgentError, Result};
+use crate::nats_integration::{AgentCommand, AgentQuery, DialogMessage, HealthResponse, REPLY_TO_HEADER};
+use crate::priority::Priority;
+use futures::StreamExt;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Default time to wait for the agent to answer
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Request-reply client for a remote Alchemist agent
+pub struct AgentClient {
+    /// NATS connection
+    connection: async_nats::Client,
+
+    /// Subject prefix of the target agent
+    subject_prefix: String,
+
+    /// Subject version addressed, or none for the unversioned subjects
+    version: Option<String>,
+
+    /// Origin reported on every request
+    origin: String,
+
+    /// Time to wait for a reply
+    timeout: Duration,
+
+    /// Lane commands run in, if not their usual one
+    priority: Option<Priority>,
+
+    /// Whether the agent reads commands from its JetStream stream
+    durable_commands: bool,
+
+    /// Key commands are signed with, if the agent requires signatures
+    #[cfg(feature = "signing")]
+    signing_key: Option<ed25519_dalek::SigningKey>,
+}
+
+impl AgentClient {
+    /// Connect to the agent described by the NATS configuration
+    pub async fn connect(config: &NatsConfig) -> Result<Self> {
+        let connection = crate::nats_integration::connect(config).await?;
+
+        Ok(Self {
+            connection,
+            subject_prefix: config.subject_prefix.clone(),
+            version: config.versions.last().cloned(),
+            origin: "alchemist-cli".to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            priority: None,
+            durable_commands: config.jetstream.is_some(),
+            #[cfg(feature = "signing")]
+            signing_key: None,
+        })
+    }
+
+    /// Set the origin reported to the agent
+    pub fn with_origin(mut self, origin: impl Into<String>) -> Self {
+        self.origin = origin.into();
+        self
+    }
+
+    /// Sign commands with `key`
+    #[cfg(feature = "signing")]
+    pub fn with_signing_key(mut self, key: ed25519_dalek::SigningKey) -> Self {
+        self.signing_key = Some(key);
+        self
+    }
+
+    /// Run commands in `priority`'s lane, such as [`Priority::Bulk`] for
+    /// automation that can wait behind dialogs
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Address subject `version`, or the unversioned subjects with `None`,
+    /// as agents from before subject versioning need
+    pub fn with_version(mut self, version: Option<String>) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Set the reply timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send a command and wait for its result
+    pub async fn command(
+        &self,
+        command_type: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        self.send_command(command_type, payload, None).await
+    }
+
+    /// Send a command that runs at most once per `idempotency_key`
+    ///
+    /// Sending it again with the same key, say after a timeout, returns the
+    /// first result instead of running the command twice.
+    pub async fn idempotent_command(
+        &self,
+        command_type: &str,
+        payload: serde_json::Value,
+        idempotency_key: &str,
+    ) -> Result<serde_json::Value> {
+        self.send_command(command_type, payload, Some(idempotency_key.to_string()))
+            .await
+    }
+
+    async fn send_command(
+        &self,
+        command_type: &str,
+        payload: serde_json::Value,
+        idempotency_key: Option<String>,
+    ) -> Result<serde_json::Value> {
+        #[cfg_attr(not(feature = "signing"), allow(unused_mut))]
+        let mut command = AgentCommand {
+            id: uuid::Uuid::new_v4().to_string(),
+            command_type: command_type.to_string(),
+            payload,
+            timestamp: chrono::Utc::now(),
+            origin: self.origin.clone(),
+            signature: None,
+            idempotency_key,
+            priority: self.priority,
+        };
+
+        #[cfg(feature = "signing")]
+        if let Some(key) = &self.signing_key {
+            crate::signing::sign(&mut command, key);
+        }
+
+        let subject = self.subject(&format!("commands.{}", command_type));
+        if self.durable_commands {
+            return self.durable_request(subject, &command).await;
+        }
+        self.request(subject, &command).await
+    }
+
+    /// Run a query and return its result
+    pub async fn query(
+        &self,
+        query_type: &str,
+        parameters: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let query = AgentQuery {
+            id: uuid::Uuid::new_v4().to_string(),
+            query_type: query_type.to_string(),
+            parameters,
+            timestamp: chrono::Utc::now(),
+            origin: self.origin.clone(),
+        };
+
+        let subject = self.subject(&format!("queries.{}", query_type));
+        self.request(subject, &query).await
+    }
+
+    /// Run a paginated query, following cursors until the last page
+    ///
+    /// Returns the first page's result with every page's items under `field`.
+    pub async fn query_all(
+        &self,
+        query_type: &str,
+        parameters: serde_json::Value,
+        field: &str,
+    ) -> Result<serde_json::Value> {
+        let mut result = self.query(query_type, parameters.clone()).await?;
+        let mut items = result[field].as_array().cloned().unwrap_or_default();
+
+        while let Some(cursor) = result["next_cursor"].as_str().map(str::to_string) {
+            let mut parameters = parameters.clone();
+            parameters["cursor"] = serde_json::json!(cursor);
+            let page = self.query(query_type, parameters).await?;
+            items.extend(page[field].as_array().cloned().unwrap_or_default());
+            result["next_cursor"] = page["next_cursor"].clone();
+        }
+
+        result[field] = serde_json::json!(items);
+        Ok(result)
+    }
+
+    /// Ask the agent for its health
+    pub async fn health(&self) -> Result<HealthResponse> {
+        let subject = self.subject("health");
+        let response = tokio::time::timeout(self.timeout, self.connection.request(subject.clone(), "".into()))
+            .await
+            .map_err(|_| AgentError::Timeout(format!("Request to {} timed out", subject)))?
+            .map_err(|e| AgentError::ServiceUnavailable(format!("Request to {} failed: {}", subject, e)))?;
+
+        Ok(serde_json::from_slice(&response.payload)?)
+    }
+
+    /// Subject for `suffix` in the addressed version
+    fn subject(&self, suffix: &str) -> String {
+        match &self.version {
+            Some(version) => format!("{}.{}.{}", self.subject_prefix, version, suffix),
+            None => format!("{}.{}", self.subject_prefix, suffix),
+        }
+    }
+
+    /// Send a dialog message and wait for the agent's reply
+    pub async fn dialog(&self, dialog_id: &str, content: impl Into<String>) -> Result<String> {
+        let message = DialogMessage {
+            dialog_id: dialog_id.to_string(),
+            content: content.into(),
+            sender: self.origin.clone(),
+            metadata: serde_json::json!({}),
+            timestamp: chrono::Utc::now(),
+        };
+
+        // Subscribe before publishing so a fast reply is not missed
+        let reply_subject = format!("cim.dialog.{}.response", dialog_id);
+        let mut replies = self
+            .connection
+            .subscribe(reply_subject.clone())
+            .await
+            .map_err(|e| AgentError::ServiceUnavailable(format!("Failed to subscribe to {}: {}", reply_subject, e)))?;
+
+        let subject = format!("cim.dialog.alchemist.{}", dialog_id);
+        self.connection
+            .publish(subject.clone(), serde_json::to_vec(&message)?.into())
+            .await
+            .map_err(|e| AgentError::ServiceUnavailable(format!("Failed to publish to {}: {}", subject, e)))?;
+
+        let reply = tokio::time::timeout(self.timeout, replies.next())
+            .await
+            .map_err(|_| AgentError::Timeout(format!("Dialog {} timed out", dialog_id)))?
+            .ok_or_else(|| AgentError::ServiceUnavailable(format!("Subscription to {} closed", reply_subject)))?;
+
+        let reply: DialogMessage = serde_json::from_slice(&reply.payload)?;
+        Ok(reply.content)
+    }
+
+    /// Send a request and unwrap the agent's reply envelope
+    async fn request<T: Serialize>(&self, subject: String, message: &T) -> Result<serde_json::Value> {
+        let payload = serde_json::to_vec(message)?;
+
+        let response = tokio::time::timeout(
+            self.timeout,
+            self.connection.request(subject.clone(), payload.into()),
+        )
+        .await
+        .map_err(|_| AgentError::Timeout(format!("Request to {} timed out", subject)))?
+        .map_err(|e| AgentError::ServiceUnavailable(format!("Request to {} failed: {}", subject, e)))?;
+
+        let envelope: serde_json::Value = serde_json::from_slice(&response.payload)?;
+        unwrap_envelope(envelope)
+    }
+
+    /// Store a command in the agent's stream and wait for the reply it
+    /// publishes on the inbox named in [`REPLY_TO_HEADER`]
+    async fn durable_request<T: Serialize>(&self, subject: String, message: &T) -> Result<serde_json::Value> {
+        let inbox = self.connection.new_inbox();
+        let mut replies = self
+            .connection
+            .subscribe(inbox.clone())
+            .await
+            .map_err(|e| AgentError::ServiceUnavailable(format!("Failed to subscribe to {}: {}", inbox, e)))?;
+
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert(REPLY_TO_HEADER, inbox.as_str());
+        // Once the stream has the command, it survives an agent restart
+        async_nats::jetstream::new(self.connection.clone())
+            .publish_with_headers(subject.clone(), headers, serde_json::to_vec(message)?.into())
+            .await
+            .map_err(|e| AgentError::ServiceUnavailable(format!("Failed to publish to {}: {}", subject, e)))?
+            .await
+            .map_err(|e| AgentError::ServiceUnavailable(format!("Command on {} was not stored: {}", subject, e)))?;
+
+        let reply = tokio::time::timeout(self.timeout, replies.next())
+            .await
+            .map_err(|_| AgentError::Timeout(format!("Request to {} timed out", subject)))?
+            .ok_or_else(|| AgentError::ServiceUnavailable(format!("Subscription to {} closed", inbox)))?;
+
+        let envelope: serde_json::Value = serde_json::from_slice(&reply.payload)?;
+        unwrap_envelope(envelope)
+    }
+}
+
+/// Extract the result from a `{ success, result | error }` reply
+fn unwrap_envelope(envelope: serde_json::Value) -> Result<serde_json::Value> {
+    if envelope["success"].as_bool().unwrap_or(false) {
+        Ok(envelope["result"].clone())
+    } else {
+        Err(AgentError::InvalidRequest(
+            envelope["error"]
+                .as_str()
+                .unwrap_or("Agent returned an unknown error")
+                .to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_unwrap_envelope_success() {
+        let result = unwrap_envelope(json!({"success": true, "result": {"id": "wf-1"}})).unwrap();
+        assert_eq!(result["id"], "wf-1");
+    }
+
+    #[test]
+    fn test_unwrap_envelope_error() {
+        let err = unwrap_envelope(json!({"success": false, "error": "Workflow wf-1 not found"}))
+            .unwrap_err();
+        assert!(err.to_string().contains("wf-1 not found"));
+    }
+}
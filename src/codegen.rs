@@ -0,0 +1,375 @@
+//! Scaffolding for new CIM domains
+//!
+//! `generate_code` follows the steps of the `implement_domain` workflow. Each
+//! step that produces a file (design notes, events, commands, the aggregate,
+//! handlers, and tests) is written by the model from [`FILE_TEMPLATE`], with
+//! the files generated so far as context so names line up. `src/lib.rs` is
+//! assembled from the generated modules rather than generated.
+//!
+//! Guided `implement_domain` workflows get [`skeletons`] instead: fixed
+//! templates for each completed step, compiling together into a crate to
+//! fill in, with no model involved.
+
+use serde::Serialize;
+
+/// Prompt for one generated file
+///
+/// `{domain}`, `{description}`, `{step}`, `{path}`, `{conventions}`, and
+/// `{context}` are filled in.
+pub const FILE_TEMPLATE: &str = "You are scaffolding the CIM domain crate `cim-domain-{domain}`.\n\n\
+Domain description: {description}\n\n\
+Current step: {step}\n\
+Write the complete contents of `{path}`. {conventions}\n\n\
+{context}\
+Reply with the file contents only, without explanations.";
+
+/// A file produced by `generate_code`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GeneratedFile {
+    /// Path relative to the domain crate root
+    pub path: String,
+
+    /// Workflow step the file belongs to
+    pub step: String,
+
+    /// `rust` or `markdown`
+    pub language: String,
+
+    pub content: String,
+}
+
+/// The file a workflow step produces, with what the file must follow
+pub fn file_for_step(step: &str, domain: &str) -> Option<(String, &'static str, &'static str)> {
+    let (path, language, conventions) = match step {
+        "design" => (
+            "README.md".to_string(),
+            "markdown",
+            "Describe the bounded context, its aggregates, value objects, and ubiquitous language.",
+        ),
+        "events" => (
+            "src/events.rs".to_string(),
+            "rust",
+            "Define one enum of domain events named in the past tense, each variant carrying the \
+             aggregate ID, deriving Debug, Clone, Serialize, and Deserialize.",
+        ),
+        "commands" => (
+            "src/commands.rs".to_string(),
+            "rust",
+            "Define command structs named in the imperative, each carrying the aggregate ID it targets, \
+             deriving Debug, Clone, Serialize, and Deserialize.",
+        ),
+        "aggregate" => (
+            "src/aggregate.rs".to_string(),
+            "rust",
+            "Implement the aggregate: state changes only by applying events, and commands are validated \
+             against invariants before events are emitted.",
+        ),
+        "handlers" => (
+            "src/handlers.rs".to_string(),
+            "rust",
+            "Implement command handlers that load the aggregate, handle the command, and return the \
+             resulting events for publishing on NATS.",
+        ),
+        "tests" => (
+            format!("tests/{}.rs", domain),
+            "rust",
+            "Test each command's events and each invariant, using the crate's public API.",
+        ),
+        _ => return None,
+    };
+
+    Some((path, language, conventions))
+}
+
+/// Prompt for the file of `step`, given the files generated before it
+pub fn file_prompt(
+    domain: &str,
+    description: &str,
+    step: &str,
+    path: &str,
+    conventions: &str,
+    previous: &[GeneratedFile],
+) -> String {
+    let context: String = previous
+        .iter()
+        .map(|file| format!("Already generated `{}`:\n{}\n\n", file.path, file.content))
+        .collect();
+
+    [
+        ("{domain}", domain),
+        ("{description}", description),
+        ("{step}", step),
+        ("{path}", path),
+        ("{conventions}", conventions),
+        ("{context}", context.as_str()),
+    ]
+    .iter()
+    .fold(FILE_TEMPLATE.to_string(), |prompt, (placeholder, value)| {
+        prompt.replace(placeholder, value)
+    })
+}
+
+/// Model output without the code fence it is often wrapped in
+pub fn strip_fences(reply: &str) -> String {
+    let trimmed = reply.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return format!("{}\n", trimmed);
+    };
+
+    // Drop the language tag line and the closing fence
+    let body = rest.split_once('\n').map(|(_, body)| body).unwrap_or_default();
+    format!("{}\n", body.trim_end().trim_end_matches("```").trim_end())
+}
+
+/// A `snake_case` crate name suffix for `name`
+pub fn domain_name(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// `src/lib.rs` declaring the generated modules
+pub fn lib_rs(domain: &str, files: &[GeneratedFile]) -> GeneratedFile {
+    let modules: String = files
+        .iter()
+        .filter_map(|file| file.path.strip_prefix("src/")?.strip_suffix(".rs"))
+        .map(|module| format!("pub mod {};\n", module))
+        .collect();
+
+    GeneratedFile {
+        path: "src/lib.rs".to_string(),
+        step: "design".to_string(),
+        language: "rust".to_string(),
+        content: format!("//! The {} domain\n\n{}", domain.replace('_', " "), modules),
+    }
+}
+
+/// Skeleton files of a domain crate, by the workflow step completing them
+///
+/// `{domain}`, `{crate}`, and `{Name}` are filled in.
+const SKELETONS: &[(&str, &str, &str)] = &[
+    (
+        "design",
+        "Cargo.toml",
+        r#"[package]
+name = "{crate}"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+thiserror = "2.0"
+uuid = { version = "1.10", features = ["v4", "serde"] }
+"#,
+    ),
+    (
+        "design",
+        "src/lib.rs",
+        r#"//! The {domain} domain
+
+pub mod aggregate;
+pub mod commands;
+pub mod events;
+pub mod handlers;
+
+pub use aggregate::{{Name}, {Name}Error};
+pub use commands::{Name}Command;
+pub use events::{Name}Event;
+"#,
+    ),
+    (
+        "events",
+        "src/events.rs",
+        r#"//! Events of the {domain} domain, named in the past tense
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum {Name}Event {
+    Created { id: Uuid },
+    // TODO: one variant per fact the domain records
+}
+"#,
+    ),
+    (
+        "commands",
+        "src/commands.rs",
+        r#"//! Commands of the {domain} domain, named in the imperative
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum {Name}Command {
+    Create { id: Uuid },
+    // TODO: one variant per request the domain accepts
+}
+"#,
+    ),
+    (
+        "aggregate",
+        "src/aggregate.rs",
+        r#"//! The {domain} aggregate: state changes only by applying events
+
+use uuid::Uuid;
+
+use crate::commands::{Name}Command;
+use crate::events::{Name}Event;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum {Name}Error {
+    #[error("{Name} {0} already exists")]
+    AlreadyExists(Uuid),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct {Name} {
+    pub id: Option<Uuid>,
+
+    /// Events applied so far
+    pub version: u64,
+}
+
+impl {Name} {
+    /// Check `command` against the invariants and return the events it causes
+    pub fn handle(&self, command: &{Name}Command) -> Result<Vec<{Name}Event>, {Name}Error> {
+        match command {
+            {Name}Command::Create { id } => match self.id {
+                Some(existing) => Err({Name}Error::AlreadyExists(existing)),
+                None => Ok(vec![{Name}Event::Created { id: *id }]),
+            },
+        }
+    }
+
+    pub fn apply(&mut self, event: &{Name}Event) {
+        match event {
+            {Name}Event::Created { id } => self.id = Some(*id),
+        }
+        self.version += 1;
+    }
+}
+"#,
+    ),
+    (
+        "handlers",
+        "src/handlers.rs",
+        r#"//! Command handlers of the {domain} domain
+
+use crate::aggregate::{{Name}, {Name}Error};
+use crate::commands::{Name}Command;
+use crate::events::{Name}Event;
+
+/// Handle `command` on `aggregate` and apply the resulting events, which
+/// the caller publishes on NATS
+pub fn handle(aggregate: &mut {Name}, command: &{Name}Command) -> Result<Vec<{Name}Event>, {Name}Error> {
+    let events = aggregate.handle(command)?;
+    for event in &events {
+        aggregate.apply(event);
+    }
+    Ok(events)
+}
+"#,
+    ),
+    (
+        "tests",
+        "tests/{domain}.rs",
+        r#"use {module}::{handlers, {Name}, {Name}Command, {Name}Error, {Name}Event};
+use uuid::Uuid;
+
+#[test]
+fn create_emits_created_once() {
+    let mut aggregate = {Name}::default();
+    let id = Uuid::new_v4();
+
+    let events = handlers::handle(&mut aggregate, &{Name}Command::Create { id }).unwrap();
+    assert_eq!(events, vec![{Name}Event::Created { id }]);
+    assert_eq!(
+        handlers::handle(&mut aggregate, &{Name}Command::Create { id }),
+        Err({Name}Error::AlreadyExists(id))
+    );
+}
+"#,
+    ),
+];
+
+/// Skeleton files completing `step` of an `implement_domain` workflow for
+/// `domain`, a [`domain_name`]
+pub fn skeletons(step: &str, domain: &str) -> Vec<GeneratedFile> {
+    let crate_name = format!("cim-domain-{}", domain.replace('_', "-"));
+    let module = format!("cim_domain_{}", domain);
+    let name: String = domain
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect();
+    let fill = |template: &str| {
+        template
+            .replace("{domain}", domain)
+            .replace("{crate}", &crate_name)
+            .replace("{module}", &module)
+            .replace("{Name}", &name)
+    };
+
+    SKELETONS
+        .iter()
+        .filter(|(skeleton_step, _, _)| *skeleton_step == step)
+        .map(|(_, path, content)| GeneratedFile {
+            path: fill(path),
+            step: step.to_string(),
+            language: if path.ends_with(".rs") { "rust" } else { "toml" }.to_string(),
+            content: fill(content),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str) -> GeneratedFile {
+        GeneratedFile {
+            path: path.to_string(),
+            step: "events".to_string(),
+            language: "rust".to_string(),
+            content: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_strip_fences() {
+        assert_eq!(strip_fences("```rust\npub struct Order;\n```\n"), "pub struct Order;\n");
+        assert_eq!(strip_fences("pub struct Order;"), "pub struct Order;\n");
+    }
+
+    #[test]
+    fn test_lib_rs_declares_generated_modules() {
+        let files = [file("README.md"), file("src/events.rs"), file("src/aggregate.rs"), file("tests/orders.rs")];
+        let lib = lib_rs("order_management", &files);
+
+        assert_eq!(lib.content, "//! The order management domain\n\npub mod events;\npub mod aggregate;\n");
+        assert_eq!(domain_name("Order Management!"), "order_management");
+        assert_eq!(file_for_step("tests", "orders").map(|(path, _, _)| path), Some("tests/orders.rs".to_string()));
+        assert_eq!(file_for_step("deploy", "orders"), None);
+    }
+
+    #[test]
+    fn test_skeletons_per_step() {
+        let design = skeletons("design", "order_management");
+        assert_eq!(design.iter().map(|file| file.path.as_str()).collect::<Vec<_>>(), ["Cargo.toml", "src/lib.rs"]);
+        assert!(design[0].content.contains("name = \"cim-domain-order-management\""));
+        assert!(design[1].content.contains("pub use events::OrderManagementEvent;"));
+
+        let tests = skeletons("tests", "order_management");
+        assert_eq!(tests[0].path, "tests/order_management.rs");
+        assert!(tests[0].content.starts_with("use cim_domain_order_management::{handlers, OrderManagement,"));
+        assert!(skeletons("deploy", "order_management").is_empty());
+    }
+}
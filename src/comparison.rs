@@ -0,0 +1,90 @@
+//! Side-by-side answers of two models, for `compare_models`
+//!
+//! Both models get the same `explain_concept` prompt, grounded in the same
+//! excerpts. The agent's current model then reads both explanations and
+//! says where they differ and which it would rather ship, to help choose
+//! the default model for a deployment.
+
+use serde::Serialize;
+
+/// What the judging model made of two answers
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comparison {
+    /// Where the answers differ in accuracy, depth, and clarity
+    pub summary: String,
+
+    /// Name of the model whose answer was better, when one was
+    pub preferred: Option<String>,
+}
+
+/// Prompt asking the model to compare the explanations of `concept` given
+/// by each named model
+pub fn comparison_prompt(concept: &str, answers: &[(String, String)]) -> String {
+    let mut prompt = format!(
+        "Two models explained the CIM concept '{}'. Compare their explanations.\n\n",
+        concept
+    );
+    for (model, answer) in answers {
+        prompt.push_str(&format!("Explanation by {}:\n{}\n\n", model, answer));
+    }
+    prompt.push_str(
+        "Say where they differ in accuracy for CIM's event-sourced, NATS-based architecture, in depth, \
+         and in clarity, then end with a line \"Preferred:\" followed by the name of the model whose \
+         explanation is better, or \"none\" if neither is.",
+    );
+    prompt
+}
+
+/// Read the comparison out of a model reply, `models` being the names
+/// compared
+pub fn parse_comparison(reply: &str, models: &[&str]) -> Comparison {
+    let mut summary = Vec::new();
+    let mut preferred = None;
+
+    for line in reply.lines() {
+        let trimmed = line.trim().trim_start_matches(['*', '_']);
+        match trimmed.get(..10) {
+            Some(label) if label.eq_ignore_ascii_case("preferred:") => {
+                let choice = trimmed[10..].trim().trim_matches(['*', '_', '.', '`', ' ']);
+                preferred = models
+                    .iter()
+                    .find(|model| model.eq_ignore_ascii_case(choice))
+                    .map(|model| model.to_string());
+            }
+            _ => summary.push(line),
+        }
+    }
+
+    Comparison {
+        summary: summary.join("\n").trim().to_string(),
+        preferred,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt_names_both_models() {
+        let answers = vec![
+            ("llama3".to_string(), "An aggregate guards invariants.".to_string()),
+            ("gpt-4o".to_string(), "An aggregate is a consistency boundary.".to_string()),
+        ];
+        let prompt = comparison_prompt("Aggregate", &answers);
+        assert!(prompt.contains("Explanation by llama3:\nAn aggregate guards invariants."));
+        assert!(prompt.contains("Explanation by gpt-4o:"));
+        assert!(prompt.contains("Preferred:"));
+    }
+
+    #[test]
+    fn test_parse_comparison() {
+        let reply = "llama3 misses the NATS subjects.\ngpt-4o gives an example.\n**Preferred:** GPT-4o.";
+        let comparison = parse_comparison(reply, &["llama3", "gpt-4o"]);
+        assert_eq!(comparison.preferred.as_deref(), Some("gpt-4o"));
+        assert_eq!(comparison.summary, "llama3 misses the NATS subjects.\ngpt-4o gives an example.");
+
+        let comparison = parse_comparison("Both are fine.\nPreferred: none", &["llama3", "gpt-4o"]);
+        assert_eq!(comparison.preferred, None);
+    }
+}
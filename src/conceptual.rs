@@ -0,0 +1,149 @@
+//! The conceptual space of the knowledge graph, for `find_similar_concepts`
+//!
+//! Every concept is placed by the embedding of its name and description,
+//! as the model provider computes it. Similar concepts are those whose
+//! embeddings have the highest cosine similarity. Embeddings are computed
+//! when first needed and again when a concept's description or the
+//! embedding model changes, and live in memory only.
+
+use std::collections::HashMap;
+
+use crate::knowledge::{Concept, ConceptGraph};
+
+/// A concept placed in the space
+#[derive(Debug, Clone)]
+struct Point {
+    name: String,
+
+    /// What was embedded, to notice edits to the concept
+    text: String,
+
+    embedding: Vec<f32>,
+}
+
+/// Concept embeddings, keyed by lowercase name
+#[derive(Debug, Default)]
+pub struct ConceptSpace {
+    /// Model that computed the embeddings
+    model: String,
+
+    points: HashMap<String, Point>,
+}
+
+impl ConceptSpace {
+    /// Forget every embedding if they came from another model than `model`
+    pub fn use_model(&mut self, model: &str) {
+        if self.model != model {
+            self.model = model.to_string();
+            self.points.clear();
+        }
+    }
+
+    /// Concepts of `graph` without an up-to-date embedding, with the text
+    /// to embed for each; concepts no longer in the graph are dropped
+    pub fn stale(&mut self, graph: &ConceptGraph) -> Vec<(String, String)> {
+        self.points.retain(|key, _| graph.concept(key).is_some());
+
+        graph
+            .concepts()
+            .map(|concept| (concept.name.clone(), concept_text(concept)))
+            .filter(|(name, text)| {
+                self.points
+                    .get(&name.to_lowercase())
+                    .is_none_or(|point| &point.text != text)
+            })
+            .collect()
+    }
+
+    pub fn insert(&mut self, name: &str, text: String, embedding: Vec<f32>) {
+        self.points.insert(
+            name.to_lowercase(),
+            Point {
+                name: name.to_string(),
+                text,
+                embedding,
+            },
+        );
+    }
+
+    /// Embedding of the concept `name`, if it has one
+    pub fn embedding(&self, name: &str) -> Option<&[f32]> {
+        self.points.get(&name.to_lowercase()).map(|point| point.embedding.as_slice())
+    }
+
+    /// Up to `top_k` concepts at least `threshold` similar to `embedding`,
+    /// most similar first, leaving out `exclude`
+    pub fn similar(&self, embedding: &[f32], top_k: usize, threshold: f32, exclude: &str) -> Vec<(String, f32)> {
+        let exclude = exclude.to_lowercase();
+        let mut similar: Vec<(String, f32)> = self
+            .points
+            .iter()
+            .filter(|(key, _)| **key != exclude)
+            .map(|(_, point)| (point.name.clone(), cosine_similarity(embedding, &point.embedding)))
+            .filter(|(_, similarity)| *similarity >= threshold)
+            .collect();
+
+        similar.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        similar.truncate(top_k);
+        similar
+    }
+}
+
+/// What is embedded for `concept`
+pub fn concept_text(concept: &Concept) -> String {
+    if concept.description.is_empty() {
+        concept.name.clone()
+    } else {
+        format!("{}: {}", concept.name, concept.description)
+    }
+}
+
+/// Cosine similarity of two vectors, 0 when they differ in length or one
+/// of them is zero
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_similar_ranks_and_filters() {
+        let graph = ConceptGraph::with_concepts(&["CQRS", "Event Sourcing", "Saga"]);
+        let mut space = ConceptSpace::default();
+        space.use_model("nomic-embed-text");
+        assert_eq!(space.stale(&graph).len(), 3);
+
+        space.insert("CQRS", "CQRS".to_string(), vec![1.0, 0.0]);
+        space.insert("Event Sourcing", "Event Sourcing".to_string(), vec![0.8, 0.6]);
+        space.insert("Saga", "Saga".to_string(), vec![0.0, 1.0]);
+        assert!(space.stale(&graph).is_empty());
+
+        let query = space.embedding("cqrs").unwrap().to_vec();
+        let similar = space.similar(&query, 5, 0.5, "CQRS");
+        assert_eq!(similar.len(), 1);
+        assert_eq!(similar[0].0, "Event Sourcing");
+
+        space.use_model("mxbai-embed-large");
+        assert!(space.embedding("CQRS").is_none());
+    }
+}
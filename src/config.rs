@@ -1,11 +1,23 @@
 //! Configuration types for the Alchemist agent
 
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use std::time::Duration;
 
+/// Version of the configuration schema this release reads
+///
+/// Older files are brought up to date by `alchemist config migrate`.
+pub const CONFIG_VERSION: u32 = 2;
+
 /// Main configuration for the Alchemist agent
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AgentConfig {
+    /// Schema version the file was written for; files without one predate
+    /// versioning
+    #[serde(default = "unversioned_config")]
+    pub config_version: u32,
+    
     /// Agent identity configuration
     pub identity: IdentityConfig,
     
@@ -20,6 +32,94 @@ pub struct AgentConfig {
     
     /// Domain-specific configurations
     pub domains: DomainConfigs,
+    
+    /// Storage backend configuration
+    #[serde(default)]
+    pub storage: StorageConfig,
+    
+    /// Response, embedding, and rate-limit caches
+    #[serde(default)]
+    pub cache: CacheConfig,
+    
+    /// Chat platform integrations
+    #[serde(default)]
+    pub integrations: IntegrationsConfig,
+    
+    /// Code and documentation sources indexed for examples
+    #[serde(default)]
+    pub sources: SourcesConfig,
+    
+    /// Documents embedded and retrieved into dialog prompts
+    #[serde(default)]
+    pub retrieval: RetrievalConfig,
+    
+    /// Crates whose domain events `get_event_schema` describes
+    #[serde(default)]
+    pub event_schemas: EventSchemasConfig,
+    
+    /// Tools the model may call while answering
+    #[serde(default)]
+    pub tools: ToolsConfig,
+    
+    /// Discovery of and delegation to other CIM agents
+    #[serde(default)]
+    pub peers: PeersConfig,
+    
+    /// Screening of retrieved content for prompt injection
+    #[serde(default)]
+    pub prompt_guard: PromptGuardConfig,
+    
+    /// Require signed commands (requires the `signing` feature)
+    #[serde(default)]
+    pub command_signing: Option<CommandSigningConfig>,
+    
+    /// Token budgets for dialogs and users
+    #[serde(default)]
+    pub budgets: BudgetConfig,
+    
+    /// Locale of prompts and user-facing messages
+    #[serde(default)]
+    pub localization: LocalizationConfig,
+    
+    /// Messages the agent sends on its own schedule
+    #[serde(default)]
+    pub schedule: Vec<ScheduledTask>,
+    
+    /// Second-pass review of answers
+    #[serde(default)]
+    pub evaluation: EvaluationConfig,
+    
+    /// Models `compare_models` can run side by side
+    #[serde(default)]
+    pub comparison: ComparisonConfig,
+    
+    /// Usage reports per origin
+    #[serde(default)]
+    pub usage: UsageConfig,
+    
+    #[serde(default)]
+    pub priority: PriorityConfig,
+    
+    /// Recording dialog answers for `alchemist replay`
+    #[serde(default)]
+    pub replay: ReplayConfig,
+    
+    /// Limits on the `announce` command
+    #[serde(default)]
+    pub announcements: AnnouncementConfig,
+    
+    /// Size limits on incoming messages, attachments, and code
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    
+    /// What this deployment offers; disabled capabilities' commands are
+    /// refused
+    #[serde(default)]
+    pub capabilities: CapabilitiesConfig,
+}
+
+fn unversioned_config() -> u32 {
+    1
 }
 
 /// Identity configuration for the agent
@@ -58,6 +158,9 @@ pub enum ModelConfig {
         temperature: f32,
         /// Maximum tokens to generate
         max_tokens: usize,
+        /// Model for embeddings (e.g., "nomic-embed-text"); the chat model when unset
+        #[serde(default)]
+        embedding_model: Option<String>,
     },
     
     /// OpenAI configuration
@@ -71,6 +174,12 @@ pub enum ModelConfig {
         /// Request timeout
         #[serde(with = "humantime_serde")]
         timeout: Duration,
+        /// Temperature for generation
+        #[serde(default = "default_temperature")]
+        temperature: f32,
+        /// Maximum tokens to generate
+        #[serde(default = "default_max_tokens")]
+        max_tokens: usize,
     },
     
     /// Anthropic configuration
@@ -82,10 +191,94 @@ pub enum ModelConfig {
         /// Request timeout
         #[serde(with = "humantime_serde")]
         timeout: Duration,
+        /// Temperature for generation
+        #[serde(default = "default_temperature")]
+        temperature: f32,
+        /// Maximum tokens to generate
+        #[serde(default = "default_max_tokens")]
+        max_tokens: usize,
     },
 }
 
+fn default_temperature() -> f32 {
+    0.7
+}
+
+fn default_max_tokens() -> usize {
+    2048
+}
+
+/// Model provider kinds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    Ollama,
+    OpenAI,
+    Anthropic,
+}
+
+impl ProviderKind {
+    /// Accepted names, in prompt order
+    pub const NAMES: &'static [&'static str] = &["ollama", "openai", "anthropic"];
+}
+
+impl std::str::FromStr for ProviderKind {
+    type Err = crate::error::AgentError;
+    
+    fn from_str(s: &str) -> crate::error::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "ollama" => Ok(Self::Ollama),
+            "openai" => Ok(Self::OpenAI),
+            "anthropic" => Ok(Self::Anthropic),
+            other => Err(crate::error::AgentError::Configuration(format!(
+                "Unknown provider: {} (expected one of {})",
+                other,
+                Self::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
 impl ModelConfig {
+    /// Default settings for a provider
+    ///
+    /// Hosted providers get `api_key` if given, otherwise a placeholder.
+    pub fn for_provider(provider: ProviderKind, api_key: Option<String>) -> Self {
+        match provider {
+            ProviderKind::Ollama => ModelConfig::Ollama {
+                base_url: "http://localhost:11434".to_string(),
+                model: "vicuna".to_string(),
+                timeout: Duration::from_secs(30),
+                temperature: default_temperature(),
+                max_tokens: default_max_tokens(),
+                embedding_model: None,
+            },
+            ProviderKind::OpenAI => ModelConfig::OpenAI {
+                api_key: api_key.unwrap_or_else(|| "<OPENAI_API_KEY>".to_string()),
+                model: "gpt-4o".to_string(),
+                organization: None,
+                timeout: Duration::from_secs(60),
+                temperature: default_temperature(),
+                max_tokens: default_max_tokens(),
+            },
+            ProviderKind::Anthropic => ModelConfig::Anthropic {
+                api_key: api_key.unwrap_or_else(|| "<ANTHROPIC_API_KEY>".to_string()),
+                model: "claude-3-5-sonnet-latest".to_string(),
+                timeout: Duration::from_secs(60),
+                temperature: default_temperature(),
+                max_tokens: default_max_tokens(),
+            },
+        }
+    }
+    
+    /// Which provider this configuration targets
+    pub fn provider(&self) -> ProviderKind {
+        match self {
+            ModelConfig::Ollama { .. } => ProviderKind::Ollama,
+            ModelConfig::OpenAI { .. } => ProviderKind::OpenAI,
+            ModelConfig::Anthropic { .. } => ProviderKind::Anthropic,
+        }
+    }
+    
     /// Get the model name being used
     pub fn model_name(&self) -> String {
         match self {
@@ -94,6 +287,51 @@ impl ModelConfig {
             ModelConfig::Anthropic { model, .. } => model.clone(),
         }
     }
+    
+    /// Get the generation temperature
+    pub fn temperature(&self) -> f32 {
+        match self {
+            ModelConfig::Ollama { temperature, .. }
+            | ModelConfig::OpenAI { temperature, .. }
+            | ModelConfig::Anthropic { temperature, .. } => *temperature,
+        }
+    }
+    
+    /// Get the maximum number of tokens to generate
+    pub fn max_tokens(&self) -> usize {
+        match self {
+            ModelConfig::Ollama { max_tokens, .. }
+            | ModelConfig::OpenAI { max_tokens, .. }
+            | ModelConfig::Anthropic { max_tokens, .. } => *max_tokens,
+        }
+    }
+    
+    /// Override the model name
+    pub fn set_model(&mut self, name: impl Into<String>) {
+        match self {
+            ModelConfig::Ollama { model, .. }
+            | ModelConfig::OpenAI { model, .. }
+            | ModelConfig::Anthropic { model, .. } => *model = name.into(),
+        }
+    }
+    
+    /// Override the generation temperature
+    pub fn set_temperature(&mut self, value: f32) {
+        match self {
+            ModelConfig::Ollama { temperature, .. }
+            | ModelConfig::OpenAI { temperature, .. }
+            | ModelConfig::Anthropic { temperature, .. } => *temperature = value,
+        }
+    }
+    
+    /// Override the maximum number of tokens to generate
+    pub fn set_max_tokens(&mut self, value: usize) {
+        match self {
+            ModelConfig::Ollama { max_tokens, .. }
+            | ModelConfig::OpenAI { max_tokens, .. }
+            | ModelConfig::Anthropic { max_tokens, .. } => *max_tokens = value,
+        }
+    }
 }
 
 /// NATS messaging configuration
@@ -113,6 +351,28 @@ pub struct NatsConfig {
     
     /// JetStream configuration
     pub jetstream: Option<JetStreamConfig>,
+    
+    /// Subject versions served and advertised, oldest first, such as `v1`
+    /// for `cim.agent.alchemist.v1.commands.>`
+    #[serde(default = "default_subject_versions")]
+    pub versions: Vec<String>,
+    
+    /// Also serve the unversioned subjects clients used before versioning
+    #[serde(default = "default_serve_unversioned")]
+    pub serve_unversioned: bool,
+    
+    /// Origins whose administrative commands are accepted over NATS while
+    /// `command_signing` is off; none when empty
+    #[serde(default)]
+    pub admin_origins: Vec<String>,
+}
+
+fn default_subject_versions() -> Vec<String> {
+    vec![crate::nats_integration::subjects::CURRENT_VERSION.to_string()]
+}
+
+fn default_serve_unversioned() -> bool {
+    true
 }
 
 /// NATS authentication options
@@ -128,6 +388,13 @@ pub enum NatsAuth {
     /// JWT authentication
     Jwt { jwt: String, seed: String },
     
+    /// Decentralized JWT authentication from a `.creds` file, such as one
+    /// written by `nsc`, holding both the user JWT and its NKey seed
+    CredentialsFile { path: String },
+    
+    /// NKey authentication with a user seed
+    NKey { seed: String },
+    
     /// TLS certificate authentication
     Tls { cert_path: String, key_path: String },
 }
@@ -150,6 +417,17 @@ pub struct RetryConfig {
     pub multiplier: f64,
 }
 
+impl RetryConfig {
+    /// Delay before retry number `attempt`, counting from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent);
+        Duration::try_from_secs_f64(delay)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+}
+
 /// JetStream configuration
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct JetStreamConfig {
@@ -161,6 +439,45 @@ pub struct JetStreamConfig {
     
     /// Enable message deduplication
     pub dedupe_window: Option<Duration>,
+    
+    /// Deliveries of a command before it goes to the dead-letter subject
+    #[serde(default = "default_max_deliver")]
+    pub max_deliver: i64,
+    
+    /// Time a command may run before it is delivered again
+    #[serde(default = "default_ack_wait", with = "humantime_serde")]
+    pub ack_wait: Duration,
+    
+    /// Subject failed commands are published on, by default
+    /// `<subject_prefix>.dead_letter.commands`
+    #[serde(default)]
+    pub dead_letter_subject: Option<String>,
+}
+
+fn default_max_deliver() -> i64 {
+    5
+}
+
+fn default_ack_wait() -> Duration {
+    Duration::from_secs(120)
+}
+
+impl JetStreamConfig {
+    /// Fail on settings the durable consumer cannot run with
+    pub fn validate(&self) -> crate::error::Result<()> {
+        if self.ack_wait.is_zero() {
+            return Err(crate::error::AgentError::Configuration(
+                "nats.jetstream.ack_wait must be longer than zero".to_string(),
+            ));
+        }
+        if self.max_deliver < 1 {
+            return Err(crate::error::AgentError::Configuration(format!(
+                "nats.jetstream.max_deliver must be at least 1, not {}",
+                self.max_deliver
+            )));
+        }
+        Ok(())
+    }
 }
 
 /// Service configuration
@@ -181,6 +498,122 @@ pub struct ServiceConfig {
     
     /// Logging configuration
     pub logging: LoggingConfig,
+    
+    /// Pidfile used in daemon mode (optional)
+    #[serde(default)]
+    pub pid_file: Option<String>,
+    
+    /// Serve commands, queries, and dialog messages over HTTP too
+    #[serde(default)]
+    pub http_api: bool,
+    
+    /// Serve `/healthz` and `/readyz` for orchestrators
+    #[serde(default = "default_health_endpoints")]
+    pub health_endpoints: bool,
+    
+    /// Time a short generation in each model health check
+    #[serde(default = "default_probe_model")]
+    pub probe_model: bool,
+    
+    /// Credentials required by the HTTP API; without them it is open
+    #[serde(default)]
+    pub api_auth: Option<ApiAuthConfig>,
+    
+    /// Relay agent events to browsers on `/ws/events`; requires `api_auth`
+    #[serde(default)]
+    pub event_stream: Option<EventStreamConfig>,
+}
+
+/// Agent events relayed over a WebSocket; see [`crate::event_stream`]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EventStreamConfig {
+    /// Event types relayed, each possibly ending in `*`; every type when empty
+    #[serde(default)]
+    pub event_types: Vec<String>,
+    
+    /// Most events sent to one connection each second (0 for no limit)
+    #[serde(default = "default_events_per_second")]
+    pub max_events_per_second: u32,
+}
+
+fn default_events_per_second() -> u32 {
+    20
+}
+
+/// Authentication for the HTTP API
+///
+/// Callers present an API key in `X-API-Key` or as a bearer token, or an
+/// OIDC access token as a bearer token.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ApiAuthConfig {
+    /// Static keys and what each may do
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    
+    /// Accept access tokens from an OIDC provider (requires the `oidc` feature)
+    #[serde(default)]
+    pub oidc: Option<OidcConfig>,
+}
+
+/// An API key
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ApiKeyConfig {
+    /// Who the key belongs to, for the audit log
+    pub name: String,
+    
+    /// The key itself
+    pub key: String,
+    
+    /// What the key grants
+    pub scopes: Vec<ApiScope>,
+}
+
+/// OIDC access token validation
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OidcConfig {
+    /// Issuer URL; keys are found through its discovery document
+    pub issuer: String,
+    
+    /// Audience tokens must be issued for
+    pub audience: String,
+    
+    /// Claim listing the caller's scopes, as a space-separated string or array
+    #[serde(default = "default_scopes_claim")]
+    pub scopes_claim: String,
+    
+    /// Signing algorithms accepted, such as `RS256` or `ES256`; a token
+    /// naming any other is rejected whatever its header says
+    #[serde(default = "default_oidc_algorithms")]
+    pub algorithms: Vec<String>,
+}
+
+fn default_scopes_claim() -> String {
+    "scope".to_string()
+}
+
+fn default_oidc_algorithms() -> Vec<String> {
+    vec!["RS256".to_string()]
+}
+
+/// What an API caller may do; each scope includes the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum ApiScope {
+    /// Run queries
+    Read,
+    
+    /// Run commands and send dialog messages
+    Command,
+    
+    /// Run administrative commands such as `switch_model`
+    Admin,
+}
+
+fn default_health_endpoints() -> bool {
+    true
+}
+
+fn default_probe_model() -> bool {
+    true
 }
 
 /// Metrics configuration
@@ -210,129 +643,1471 @@ pub struct LoggingConfig {
     
     /// Log file path (optional)
     pub file: Option<String>,
+    
+    /// Log the payload of each request, with secrets redacted
+    #[serde(default)]
+    pub include_payloads: bool,
+    
+    /// Payload fields to redact besides passwords, tokens, keys, and the like
+    #[serde(default)]
+    pub redact: Vec<String>,
+    
+    /// Access log of requests, kept apart from these logs
+    #[serde(default)]
+    pub access: Option<AccessLogConfig>,
 }
 
-/// Domain-specific configurations
+/// Access log configuration; see [`crate::access_log`]
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct DomainConfigs {
-    /// Dialog domain configuration
-    pub dialog: DialogConfig,
+pub struct AccessLogConfig {
+    /// Where entries are written
+    pub sink: AccessLogSink,
     
-    /// Graph domain configuration
-    pub graph: GraphConfig,
+    /// Share of requests logged, from 0 to 1
+    #[serde(default = "default_access_sample_rate")]
+    pub sample_rate: f64,
     
-    /// Workflow domain configuration
-    pub workflow: WorkflowConfig,
+    /// Fields of each entry; every field when empty
+    #[serde(default)]
+    pub fields: Vec<String>,
+    
+    /// Log every failed request, whatever the sample rate
+    #[serde(default = "default_access_log_errors")]
+    pub always_log_errors: bool,
 }
 
-/// Dialog domain configuration
+fn default_access_sample_rate() -> f64 {
+    1.0
+}
+
+fn default_access_log_errors() -> bool {
+    true
+}
+
+/// Access log destinations
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct DialogConfig {
-    /// Maximum conversation history to maintain
-    pub max_history: usize,
+#[serde(tag = "type")]
+pub enum AccessLogSink {
+    /// Append JSON lines to a file
+    File {
+        path: PathBuf,
+    },
     
-    /// Context window size
-    pub context_window: usize,
+    /// Publish each entry on a NATS subject
+    Subject {
+        subject: String,
+    },
+}
+
+/// Storage backend configuration
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StorageConfig {
+    /// Where dialogs and workflows are kept
+    pub backend: StorageBackend,
     
-    /// Session timeout
-    #[serde(with = "humantime_serde")]
-    pub session_timeout: Duration,
+    /// Where exports, ingested documents, and snapshots are kept
+    #[serde(default)]
+    pub artifacts: Option<ArtifactBackend>,
 }
 
-/// Graph domain configuration
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct GraphConfig {
-    /// Maximum nodes in visualization
-    pub max_nodes: usize,
+/// Storage backend options
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum StorageBackend {
+    /// Keep state in process memory only
+    #[default]
+    Memory,
     
-    /// Enable auto-layout
-    pub auto_layout: bool,
+    /// Persist state to a JetStream key-value bucket
+    JetStream {
+        /// Bucket name
+        bucket: String,
+    },
     
-    /// Default layout algorithm
-    pub layout_algorithm: String,
+    /// Persist state to SQLite or Postgres (requires the `sql` feature)
+    Sql {
+        /// Connection URL, such as `sqlite://alchemist.db?mode=rwc` or `postgres://host/db`
+        url: String,
+        
+        /// Most connections kept in the pool
+        #[serde(default = "default_sql_max_connections")]
+        max_connections: u32,
+    },
 }
 
-/// Workflow domain configuration
+fn default_sql_max_connections() -> u32 {
+    5
+}
+
+/// Artifact storage options
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct WorkflowConfig {
-    /// Maximum concurrent workflows
-    pub max_concurrent: usize,
+#[serde(tag = "type")]
+pub enum ArtifactBackend {
+    /// A NATS JetStream object store bucket
+    ObjectStore {
+        /// Bucket name, created if missing
+        bucket: String,
+    },
     
-    /// Workflow timeout
-    #[serde(with = "humantime_serde")]
-    pub timeout: Duration,
+    /// An S3-compatible bucket, such as AWS S3 or MinIO (requires the `s3` feature)
+    S3 {
+        /// Bucket name
+        bucket: String,
+        
+        /// Region of the bucket
+        #[serde(default = "default_s3_region")]
+        region: String,
+        
+        /// Endpoint for S3-compatible services such as MinIO; AWS when unset
+        #[serde(default)]
+        endpoint: Option<String>,
+        
+        /// Access key ID; taken from `AWS_ACCESS_KEY_ID` when unset
+        #[serde(default)]
+        access_key_id: Option<String>,
+        
+        /// Secret access key; taken from `AWS_SECRET_ACCESS_KEY` when unset
+        #[serde(default)]
+        secret_access_key: Option<String>,
+        
+        /// Key prefix every artifact is stored under
+        #[serde(default)]
+        prefix: Option<String>,
+    },
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+/// Cache configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheConfig {
+    /// Where cached values and counters are kept
+    #[serde(default)]
+    pub backend: CacheBackend,
     
-    /// Enable workflow persistence
-    pub persist: bool,
+    /// How long model responses are reused; `0s` disables the response cache
+    #[serde(default = "default_response_ttl", with = "humantime_serde")]
+    pub response_ttl: Duration,
+    
+    /// How long embeddings are reused
+    #[serde(default = "default_embedding_ttl", with = "humantime_serde")]
+    pub embedding_ttl: Duration,
+    
+    /// How long the result of a command sent with an idempotency key is
+    /// returned to retries instead of running the command again
+    #[serde(default = "default_idempotency_ttl", with = "humantime_serde")]
+    pub idempotency_ttl: Duration,
 }
 
-impl Default for AgentConfig {
+impl Default for CacheConfig {
     fn default() -> Self {
         Self {
-            identity: IdentityConfig {
-                agent_id: uuid::Uuid::new_v4().to_string(),
-                name: "Alchemist".to_string(),
-                description: "CIM Architecture Assistant".to_string(),
-                version: crate::VERSION.to_string(),
-                organization: "CIM".to_string(),
-            },
-            model: ModelConfig::Ollama {
-                base_url: "http://localhost:11434".to_string(),
-                model: "vicuna".to_string(),
-                timeout: Duration::from_secs(30),
-                temperature: 0.7,
-                max_tokens: 2048,
-            },
-            nats: NatsConfig {
-                servers: vec!["nats://localhost:4222".to_string()],
-                subject_prefix: "cim.agent.alchemist".to_string(),
-                auth: None,
-                retry: RetryConfig {
-                    max_attempts: 5,
-                    initial_delay: Duration::from_millis(100),
-                    max_delay: Duration::from_secs(30),
-                    multiplier: 2.0,
-                },
-                jetstream: Some(JetStreamConfig {
-                    stream_name: "ALCHEMIST_EVENTS".to_string(),
-                    consumer_name: "alchemist-consumer".to_string(),
-                    dedupe_window: Some(Duration::from_secs(120)),
-                }),
-            },
-            service: ServiceConfig {
-                bind_address: "0.0.0.0".to_string(),
-                port: 8080,
-                health_check_interval: Duration::from_secs(30),
-                metrics: MetricsConfig {
-                    enabled: true,
-                    endpoint: "/metrics".to_string(),
-                    push_gateway: None,
-                },
-                logging: LoggingConfig {
-                    level: "info".to_string(),
-                    format: "json".to_string(),
-                    colors: false,
-                    file: None,
-                },
-            },
-            domains: DomainConfigs {
-                dialog: DialogConfig {
-                    max_history: 100,
-                    context_window: 10,
-                    session_timeout: Duration::from_secs(3600),
-                },
-                graph: GraphConfig {
-                    max_nodes: 1000,
-                    auto_layout: true,
-                    layout_algorithm: "force-directed".to_string(),
+            backend: CacheBackend::default(),
+            response_ttl: default_response_ttl(),
+            embedding_ttl: default_embedding_ttl(),
+            idempotency_ttl: default_idempotency_ttl(),
+        }
+    }
+}
+
+/// Cache backend options
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum CacheBackend {
+    /// Cache in process memory, per replica
+    #[default]
+    Memory,
+    
+    /// Share caches and limits between replicas through Redis (requires the `redis` feature)
+    Redis {
+        /// Connection URL, such as `redis://localhost:6379`
+        url: String,
+        
+        /// Prefix of every key, so agents can share a Redis
+        #[serde(default = "default_cache_key_prefix")]
+        key_prefix: String,
+    },
+}
+
+fn default_response_ttl() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_embedding_ttl() -> Duration {
+    Duration::from_secs(7 * 24 * 3600)
+}
+
+fn default_idempotency_ttl() -> Duration {
+    Duration::from_secs(24 * 3600)
+}
+
+fn default_cache_key_prefix() -> String {
+    "alchemist".to_string()
+}
+
+/// Chat platform integrations
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct IntegrationsConfig {
+    /// Slack bot (requires the `slack` feature)
+    #[serde(default)]
+    pub slack: Option<SlackConfig>,
+    
+    /// GitHub pull request reviews (requires the `github` feature)
+    #[serde(default)]
+    pub github: Option<GitHubConfig>,
+    
+    /// Endpoints agent events are POSTed to (requires the `webhooks` feature)
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    
+    /// Activity digests sent by email (requires the `email` feature)
+    #[serde(default)]
+    pub email: Option<EmailDigestConfig>,
+}
+
+/// Slack Socket Mode bot configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SlackConfig {
+    /// App-level token (`xapp-...`) with `connections:write`
+    pub app_token: String,
+    
+    /// Bot token (`xoxb-...`) used to post and edit messages
+    pub bot_token: String,
+    
+    /// Slash command the bot answers
+    #[serde(default = "default_slash_command")]
+    pub slash_command: String,
+    
+    /// Minimum time between edits of a streaming response
+    #[serde(default = "default_edit_interval", with = "humantime_serde")]
+    pub edit_interval: Duration,
+}
+
+/// GitHub webhook configuration
+///
+/// The webhook is served at `/webhooks/github` on the service address.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GitHubConfig {
+    /// Secret the webhook payloads are signed with
+    pub webhook_secret: String,
+    
+    /// Token allowed to read pull requests and write reviews
+    pub token: String,
+    
+    /// Repositories to review, as `owner/name` or `owner/*`; others are ignored
+    #[serde(default)]
+    pub allowed_repos: Vec<String>,
+    
+    /// Diffs longer than this many bytes are cut before analysis
+    #[serde(default = "default_max_diff_bytes")]
+    pub max_diff_bytes: usize,
+    
+    /// API base URL, for GitHub Enterprise
+    #[serde(default = "default_github_api_url")]
+    pub api_url: String,
+}
+
+/// Outgoing webhook configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookConfig {
+    /// HTTPS endpoint events are POSTed to
+    pub url: String,
+    
+    /// Secret the `X-Alchemist-Signature-256` header is computed with
+    pub secret: String,
+    
+    /// Event types to deliver, such as `workflow_completed`; empty delivers all
+    #[serde(default)]
+    pub events: Vec<String>,
+    
+    /// Attempts after the first before a delivery is dropped
+    #[serde(default = "default_webhook_retries")]
+    pub max_retries: u32,
+    
+    /// Timeout for each attempt
+    #[serde(default = "default_webhook_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+/// Email digest configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EmailDigestConfig {
+    /// SMTP relay host
+    pub smtp_host: String,
+    
+    /// SMTP relay port
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+    
+    /// SMTP username, if the relay needs one
+    #[serde(default)]
+    pub username: Option<String>,
+    
+    /// SMTP password, if the relay needs one
+    #[serde(default)]
+    pub password: Option<String>,
+    
+    /// Send without TLS, for a local relay such as MailHog
+    #[serde(default)]
+    pub insecure: bool,
+    
+    /// Sender, such as `Alchemist <alchemist@example.com>`
+    pub from: String,
+    
+    /// Addresses the digest is sent to
+    pub recipients: Vec<String>,
+    
+    /// Subject line of the digest
+    #[serde(default = "default_digest_subject")]
+    pub subject: String,
+    
+    /// Time between digests, and the period each one covers
+    #[serde(default = "default_digest_interval", with = "humantime_serde")]
+    pub interval: Duration,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_digest_subject() -> String {
+    "CIM Alchemist weekly digest".to_string()
+}
+
+fn default_digest_interval() -> Duration {
+    Duration::from_secs(7 * 24 * 3600)
+}
+
+fn default_webhook_retries() -> u32 {
+    5
+}
+
+fn default_webhook_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_max_diff_bytes() -> usize {
+    60_000
+}
+
+fn default_github_api_url() -> String {
+    "https://api.github.com".to_string()
+}
+
+fn default_slash_command() -> String {
+    "/alchemist".to_string()
+}
+
+fn default_edit_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+/// Code and documentation sources
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SourcesConfig {
+    /// Git repositories kept checked out and indexed
+    #[serde(default)]
+    pub git: GitSourcesConfig,
+}
+
+/// Git repositories to index
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GitSourcesConfig {
+    /// Repositories to clone
+    #[serde(default)]
+    pub repos: Vec<GitRepoConfig>,
+    
+    /// Directory the repositories are checked out under
+    #[serde(default = "default_checkout_dir")]
+    pub checkout_dir: String,
+    
+    /// How often repositories are pulled and reindexed
+    #[serde(default = "default_refresh_interval", with = "humantime_serde")]
+    pub refresh_interval: Duration,
+    
+    /// Files larger than this are not indexed
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+}
+
+impl Default for GitSourcesConfig {
+    fn default() -> Self {
+        Self {
+            repos: Vec::new(),
+            checkout_dir: default_checkout_dir(),
+            refresh_interval: default_refresh_interval(),
+            max_file_bytes: default_max_file_bytes(),
+        }
+    }
+}
+
+/// Retrieval-augmented generation over embedded documents
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RetrievalConfig {
+    /// Directories of documents to index, such as CIM docs checkouts
+    #[serde(default)]
+    pub directories: Vec<PathBuf>,
+    
+    /// File extensions to index under `directories`
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    
+    /// Also index the files of the code sources
+    #[serde(default)]
+    pub include_sources: bool,
+    
+    /// Characters per chunk, the unit embedded and retrieved
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+    
+    /// Chunks retrieved per dialog message
+    #[serde(default = "default_retrieval_top_k")]
+    pub top_k: usize,
+    
+    /// Least cosine similarity of a retrieved chunk to the message
+    #[serde(default = "default_min_similarity")]
+    pub min_similarity: f32,
+    
+    /// How often changed documents are embedded again
+    #[serde(default = "default_reindex_interval", with = "humantime_serde")]
+    pub refresh_interval: Duration,
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            directories: Vec::new(),
+            extensions: default_extensions(),
+            include_sources: false,
+            chunk_size: default_chunk_size(),
+            top_k: default_retrieval_top_k(),
+            min_similarity: default_min_similarity(),
+            refresh_interval: default_reindex_interval(),
+        }
+    }
+}
+
+/// Where domain event schemas are read from, besides the code sources
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct EventSchemasConfig {
+    /// Checkouts of CIM crates, such as `../cim-domain-graph`, whose Rust
+    /// files and Markdown docs declare events
+    #[serde(default)]
+    pub crates: Vec<PathBuf>,
+}
+
+fn default_chunk_size() -> usize {
+    1500
+}
+
+fn default_retrieval_top_k() -> usize {
+    4
+}
+
+fn default_min_similarity() -> f32 {
+    0.3
+}
+
+fn default_reindex_interval() -> Duration {
+    Duration::from_secs(600)
+}
+
+/// One git repository to index
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GitRepoConfig {
+    /// Clone URL
+    pub url: String,
+    
+    /// Name used in search results; defaults to the last segment of the URL
+    #[serde(default)]
+    pub name: Option<String>,
+    
+    /// Branch to index; defaults to the remote's default branch
+    #[serde(default)]
+    pub branch: Option<String>,
+    
+    /// File extensions to index
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+}
+
+fn default_checkout_dir() -> String {
+    ".alchemist/sources".to_string()
+}
+
+fn default_refresh_interval() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_max_file_bytes() -> u64 {
+    200_000
+}
+
+fn default_extensions() -> Vec<String> {
+    ["rs", "md", "toml"].iter().map(|s| s.to_string()).collect()
+}
+
+/// Tools the model may call
+///
+/// Every tool is off unless enabled here.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ToolsConfig {
+    /// Directory the file and cargo tools are confined to
+    #[serde(default = "default_tools_workspace")]
+    pub workspace: PathBuf,
+    
+    /// Let the model read files in the workspace
+    #[serde(default)]
+    pub read_file: bool,
+    
+    /// Let the model list directories in the workspace
+    #[serde(default)]
+    pub list_directory: bool,
+    
+    /// Let the model run `cargo check` in the workspace
+    #[serde(default)]
+    pub cargo_check: bool,
+    
+    /// Let the model fetch URLs
+    #[serde(default)]
+    pub fetch_url: bool,
+    
+    /// Hosts `fetch_url` may reach, required with it
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    
+    /// Let the model search the web
+    #[serde(default)]
+    pub web_search: Option<WebSearchConfig>,
+    
+    /// Never reach the network, whatever else is enabled
+    #[serde(default)]
+    pub offline: bool,
+    
+    /// Tool output beyond this many bytes is cut
+    #[serde(default = "default_tool_output_bytes")]
+    pub max_output_bytes: usize,
+    
+    /// Longest a tool may run
+    #[serde(default = "default_tool_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+    
+    /// Rounds of tool calls before the model must answer
+    #[serde(default = "default_tool_rounds")]
+    pub max_rounds: usize,
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            workspace: default_tools_workspace(),
+            read_file: false,
+            list_directory: false,
+            cargo_check: false,
+            fetch_url: false,
+            allowed_hosts: Vec::new(),
+            web_search: None,
+            offline: false,
+            max_output_bytes: default_tool_output_bytes(),
+            timeout: default_tool_timeout(),
+            max_rounds: default_tool_rounds(),
+        }
+    }
+}
+
+/// Web search tool configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebSearchConfig {
+    /// Search service to query
+    pub engine: SearchEngine,
+    
+    /// Results given to the model per search
+    #[serde(default = "default_search_results")]
+    pub max_results: usize,
+}
+
+/// Search services
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum SearchEngine {
+    /// A SearxNG instance with the JSON format enabled
+    Searxng {
+        /// Instance URL, such as `http://searxng:8080`
+        url: String,
+    },
+    
+    /// Any search API answering GET requests with JSON
+    Api {
+        /// Endpoint the query is sent to
+        url: String,
+        
+        /// Bearer token, if the API needs one
+        #[serde(default)]
+        api_key: Option<String>,
+        
+        /// Query string parameter carrying the search terms
+        #[serde(default = "default_query_param")]
+        query_param: String,
+        
+        /// JSON pointer to the array of results
+        #[serde(default = "default_results_pointer")]
+        results_pointer: String,
+        
+        /// Field of a result holding its title
+        #[serde(default = "default_title_field")]
+        title_field: String,
+        
+        /// Field of a result holding its URL
+        #[serde(default = "default_url_field")]
+        url_field: String,
+        
+        /// Field of a result holding its summary
+        #[serde(default = "default_snippet_field")]
+        snippet_field: String,
+    },
+}
+
+fn default_search_results() -> usize {
+    5
+}
+
+fn default_query_param() -> String {
+    "q".to_string()
+}
+
+fn default_results_pointer() -> String {
+    "/results".to_string()
+}
+
+fn default_title_field() -> String {
+    "title".to_string()
+}
+
+fn default_url_field() -> String {
+    "url".to_string()
+}
+
+fn default_snippet_field() -> String {
+    "content".to_string()
+}
+
+fn default_tools_workspace() -> PathBuf {
+    PathBuf::from(".")
+}
+
+fn default_tool_output_bytes() -> usize {
+    20_000
+}
+
+fn default_tool_timeout() -> Duration {
+    Duration::from_secs(120)
+}
+
+fn default_tool_rounds() -> usize {
+    4
+}
+
+/// A proactive behavior run by the scheduler
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScheduledTask {
+    /// Name used in logs and on published events
+    pub name: String,
+    
+    /// When the task runs
+    pub when: Schedule,
+    
+    /// What the task does
+    pub action: ProactiveAction,
+}
+
+/// When a scheduled task runs
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum Schedule {
+    /// Every day at `at`, as `HH:MM` in UTC
+    Daily { at: String },
+    
+    /// Repeatedly, the first time one interval after startup
+    Every {
+        #[serde(with = "humantime_serde")]
+        interval: Duration,
+    },
+}
+
+/// What a scheduled task does
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum ProactiveAction {
+    /// Publish what the model writes from `prompt` as an `event_type` event
+    ///
+    /// `{date}` and `{concept}`, a CIM concept that changes daily, are filled in.
+    Post { event_type: String, prompt: String },
+    
+    /// Publish a `workflow_reminder` for each running workflow that has not
+    /// advanced for `stalled_after`
+    ///
+    /// `{workflow}`, `{step}`, `{owner}`, and `{idle}` are filled in.
+    PingStalledWorkflows {
+        #[serde(with = "humantime_serde")]
+        stalled_after: Duration,
+        
+        #[serde(default = "default_reminder_prompt")]
+        prompt: String,
+    },
+}
+
+fn default_reminder_prompt() -> String {
+    "Write a short, friendly reminder to {owner} that the workflow \"{workflow}\" has been waiting at \
+    step \"{step}\" for {idle}. Suggest how to get the step done."
+        .to_string()
+}
+
+/// Localization of prompts and messages
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LocalizationConfig {
+    /// Locale used when a message or command does not name one
+    #[serde(default = "default_locale")]
+    pub default_locale: String,
+    
+    /// Directory of `<locale>/alchemist.ftl` files adding or overriding messages
+    #[serde(default)]
+    pub resources_dir: Option<PathBuf>,
+}
+
+impl Default for LocalizationConfig {
+    fn default() -> Self {
+        Self {
+            default_locale: default_locale(),
+            resources_dir: None,
+        }
+    }
+}
+
+fn default_locale() -> String {
+    "en-US".to_string()
+}
+
+/// Token budgets; unset limits are unlimited
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BudgetConfig {
+    /// Tokens a single dialog may use
+    #[serde(default)]
+    pub per_dialog: Option<usize>,
+    
+    /// Tokens each user, named by `metadata.user` on dialog messages, may use per `period`
+    #[serde(default)]
+    pub per_user: Option<usize>,
+    
+    /// How often user budgets start over
+    #[serde(default = "default_budget_period", with = "humantime_serde")]
+    pub period: Duration,
+    
+    /// What happens to messages once a budget is used up
+    #[serde(default)]
+    pub on_exceeded: BudgetAction,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            per_dialog: None,
+            per_user: None,
+            period: default_budget_period(),
+            on_exceeded: BudgetAction::default(),
+        }
+    }
+}
+
+fn default_budget_period() -> Duration {
+    Duration::from_secs(86400)
+}
+
+/// Handling of messages over budget
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum BudgetAction {
+    /// Decline to answer, explaining why
+    #[default]
+    Refuse,
+    
+    /// Answer with a smaller model of the same provider
+    Downgrade { model: String },
+}
+
+/// Second-pass review of answers
+///
+/// The model grades its answer against the sources it drew on and a rubric;
+/// the confidence and caveats it reports are attached to the response.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct EvaluationConfig {
+    /// Command types whose answers are reviewed; `dialog_message` covers
+    /// dialog replies
+    #[serde(default)]
+    pub commands: Vec<String>,
+    
+    /// What a good answer does, replacing the built-in rubric
+    #[serde(default)]
+    pub rubric: Option<String>,
+}
+
+impl EvaluationConfig {
+    /// Whether answers to `command_type` are reviewed
+    pub fn applies_to(&self, command_type: &str) -> bool {
+        self.commands.iter().any(|command| command == command_type)
+    }
+}
+
+/// Models `compare_models` can run side by side
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ComparisonConfig {
+    /// Providers by the name `compare_models` is given; other names are
+    /// models of the configured provider
+    #[serde(default)]
+    pub providers: BTreeMap<String, ModelConfig>,
+}
+
+/// Usage reports per origin
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UsageConfig {
+    /// How long hourly usage counts are kept
+    #[serde(default = "default_usage_retention", with = "humantime_serde")]
+    pub retention: Duration,
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            retention: default_usage_retention(),
+        }
+    }
+}
+
+fn default_usage_retention() -> Duration {
+    Duration::from_secs(30 * 86400)
+}
+
+/// Slots for running commands and dialog messages; see [`crate::priority`]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PriorityConfig {
+    /// Commands and dialog messages handled at once
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+    
+    /// Slots bulk work such as batches never takes, kept for dialogs and
+    /// interactive commands
+    #[serde(default = "default_reserved_interactive")]
+    pub reserved_interactive: usize,
+}
+
+impl Default for PriorityConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: default_max_concurrent(),
+            reserved_interactive: default_reserved_interactive(),
+        }
+    }
+}
+
+fn default_max_concurrent() -> usize {
+    8
+}
+
+fn default_reserved_interactive() -> usize {
+    2
+}
+
+/// Recorded dialog answers; see [`crate::replay`]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReplayConfig {
+    /// Append every answer the agent's model gives to `path`
+    #[serde(default)]
+    pub record: bool,
+    
+    #[serde(default = "default_replay_path")]
+    pub path: PathBuf,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            record: false,
+            path: default_replay_path(),
+        }
+    }
+}
+
+fn default_replay_path() -> PathBuf {
+    PathBuf::from("replays/dialogs.jsonl")
+}
+
+/// Broadcast announcements to active dialogs
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AnnouncementConfig {
+    /// Most announcements per `window`, across replicas sharing a cache
+    #[serde(default = "default_announcement_limit")]
+    pub limit: u64,
+    
+    #[serde(default = "default_announcement_window", with = "humantime_serde")]
+    pub window: Duration,
+}
+
+impl Default for AnnouncementConfig {
+    fn default() -> Self {
+        Self {
+            limit: default_announcement_limit(),
+            window: default_announcement_window(),
+        }
+    }
+}
+
+fn default_announcement_limit() -> u64 {
+    3
+}
+
+fn default_announcement_window() -> Duration {
+    Duration::from_secs(3600)
+}
+
+/// Size limits on incoming payloads, in bytes (0 for no limit); see
+/// [`crate::limits`]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LimitsConfig {
+    /// Largest command, query, or dialog message, over NATS or HTTP
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+    
+    /// Largest attachment sent inline with a dialog message
+    #[serde(default = "default_max_attachment_bytes")]
+    pub max_attachment_bytes: usize,
+    
+    /// Largest `code` sent inline to `analyze_pattern` or `explain_error`
+    #[serde(default = "default_max_code_bytes")]
+    pub max_code_bytes: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_message_bytes: default_max_message_bytes(),
+            max_attachment_bytes: default_max_attachment_bytes(),
+            max_code_bytes: default_max_code_bytes(),
+        }
+    }
+}
+
+/// The default `max_payload` of a NATS server
+fn default_max_message_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_max_attachment_bytes() -> usize {
+    256 * 1024
+}
+
+fn default_max_code_bytes() -> usize {
+    64 * 1024
+}
+
+/// Capabilities a deployment offers, all enabled by default
+///
+/// A read-only explainer, for instance, keeps `explain_concepts` and turns
+/// the rest off.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CapabilitiesConfig {
+    /// `explain_concept`, `explain_error`, `generate_glossary`, and
+    /// `compare_models`
+    #[serde(default = "default_capability")]
+    pub explain_concepts: bool,
+    
+    /// `visualize_architecture`
+    #[serde(default = "default_capability")]
+    pub visualize_architecture: bool,
+    
+    /// `guide_workflow`, `advance_workflow`, `complete_step`,
+    /// `abort_workflow`, and `create_workflow_from_dialog`
+    #[serde(default = "default_capability")]
+    pub guide_workflows: bool,
+    
+    /// `analyze_pattern`
+    #[serde(default = "default_capability")]
+    pub analyze_patterns: bool,
+    
+    /// `generate_code`
+    #[serde(default = "default_capability")]
+    pub suggest_improvements: bool,
+}
+
+impl Default for CapabilitiesConfig {
+    fn default() -> Self {
+        Self {
+            explain_concepts: true,
+            visualize_architecture: true,
+            guide_workflows: true,
+            analyze_patterns: true,
+            suggest_improvements: true,
+        }
+    }
+}
+
+fn default_capability() -> bool {
+    true
+}
+
+/// Command signature verification
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CommandSigningConfig {
+    /// Hex Ed25519 public keys allowed to sign commands, by command origin
+    pub keys: HashMap<String, Vec<String>>,
+    
+    /// Oldest (or furthest ahead) a command's timestamp may be
+    #[serde(default = "default_command_max_age", with = "humantime_serde")]
+    pub max_age: Duration,
+}
+
+fn default_command_max_age() -> Duration {
+    Duration::from_secs(300)
+}
+
+/// Prompt injection screening for tool output, indexed code, and attachments
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PromptGuardConfig {
+    /// Screen content before it reaches a prompt
+    #[serde(default = "default_prompt_guard_enabled")]
+    pub enabled: bool,
+    
+    /// What to do with content that looks like an injection attempt
+    #[serde(default)]
+    pub action: InjectionAction,
+}
+
+impl Default for PromptGuardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_prompt_guard_enabled(),
+            action: InjectionAction::default(),
+        }
+    }
+}
+
+/// Handling of suspected prompt injection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum InjectionAction {
+    /// Replace the offending lines
+    #[default]
+    Strip,
+    
+    /// Keep the content but warn the model not to follow it
+    Flag,
+}
+
+fn default_prompt_guard_enabled() -> bool {
+    true
+}
+
+/// Peer agent discovery and delegation
+///
+/// Agents announce their capabilities on `cim.agent.<agent_id>.capabilities`.
+/// Questions matching a peer's topics better than this agent's are forwarded
+/// to that peer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PeersConfig {
+    /// Announce capabilities and delegate questions
+    #[serde(default)]
+    pub enabled: bool,
+    
+    /// Topics this agent answers best, announced to peers
+    #[serde(default = "default_peer_topics")]
+    pub topics: Vec<String>,
+    
+    /// How often capabilities are announced
+    #[serde(default = "default_announce_interval", with = "humantime_serde")]
+    pub announce_interval: Duration,
+    
+    /// Longest to wait for a peer's answer before answering locally
+    #[serde(default = "default_delegate_timeout", with = "humantime_serde")]
+    pub delegate_timeout: Duration,
+}
+
+impl Default for PeersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            topics: default_peer_topics(),
+            announce_interval: default_announce_interval(),
+            delegate_timeout: default_delegate_timeout(),
+        }
+    }
+}
+
+fn default_peer_topics() -> Vec<String> {
+    [
+        "cim",
+        "event sourcing",
+        "cqrs",
+        "domain driven design",
+        "aggregate",
+        "nats",
+        "conceptual space",
+        "workflow",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+fn default_announce_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_delegate_timeout() -> Duration {
+    Duration::from_secs(60)
+}
+
+/// Domain-specific configurations
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DomainConfigs {
+    /// Dialog domain configuration
+    pub dialog: DialogConfig,
+    
+    /// Graph domain configuration
+    pub graph: GraphConfig,
+    
+    /// Workflow domain configuration
+    pub workflow: WorkflowConfig,
+    
+    /// Concept similarity search configuration
+    #[serde(default)]
+    pub conceptual_space: ConceptualSpaceConfig,
+}
+
+/// Dialog domain configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DialogConfig {
+    /// Maximum conversation history to maintain
+    pub max_history: usize,
+    
+    /// Context window size
+    pub context_window: usize,
+    
+    /// Session timeout
+    #[serde(with = "humantime_serde")]
+    pub session_timeout: Duration,
+    
+    /// Time a dialog reply may take before the text so far is sent as a
+    /// partial answer and the rest follows as a `dialog_follow_up` event
+    #[serde(default, with = "humantime_serde")]
+    pub soft_deadline: Option<Duration>,
+}
+
+/// Graph domain configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GraphConfig {
+    /// Maximum nodes in visualization
+    pub max_nodes: usize,
+    
+    /// Enable auto-layout
+    pub auto_layout: bool,
+    
+    /// Default layout algorithm
+    pub layout_algorithm: String,
+}
+
+/// Concept similarity search; see [`crate::conceptual`]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConceptualSpaceConfig {
+    /// Most similar concepts returned unless a query asks for another number
+    #[serde(default = "default_similar_top_k")]
+    pub top_k: usize,
+    
+    /// Least cosine similarity for a concept to count as similar
+    #[serde(default = "default_similarity_threshold")]
+    pub threshold: f32,
+}
+
+impl Default for ConceptualSpaceConfig {
+    fn default() -> Self {
+        Self {
+            top_k: default_similar_top_k(),
+            threshold: default_similarity_threshold(),
+        }
+    }
+}
+
+fn default_similar_top_k() -> usize {
+    5
+}
+
+fn default_similarity_threshold() -> f32 {
+    0.5
+}
+
+/// Workflow domain configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WorkflowConfig {
+    /// Maximum running workflows; 0 allows any number
+    pub max_concurrent: usize,
+    
+    /// How long a workflow may go without advancing before it fails with a
+    /// `workflow_timed_out` event; 0 never times out
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    
+    /// Keep running workflows in the workflow store, resuming them on restart
+    pub persist: bool,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            config_version: CONFIG_VERSION,
+            identity: IdentityConfig {
+                agent_id: uuid::Uuid::new_v4().to_string(),
+                name: "Alchemist".to_string(),
+                description: "CIM Architecture Assistant".to_string(),
+                version: crate::VERSION.to_string(),
+                organization: "CIM".to_string(),
+            },
+            model: ModelConfig::for_provider(ProviderKind::Ollama, None),
+            nats: NatsConfig {
+                servers: vec!["nats://localhost:4222".to_string()],
+                subject_prefix: "cim.agent.alchemist".to_string(),
+                auth: None,
+                retry: RetryConfig {
+                    max_attempts: 5,
+                    initial_delay: Duration::from_millis(100),
+                    max_delay: Duration::from_secs(30),
+                    multiplier: 2.0,
+                },
+                jetstream: Some(JetStreamConfig {
+                    stream_name: "ALCHEMIST_EVENTS".to_string(),
+                    consumer_name: "alchemist-consumer".to_string(),
+                    dedupe_window: Some(Duration::from_secs(120)),
+                    max_deliver: default_max_deliver(),
+                    ack_wait: default_ack_wait(),
+                    dead_letter_subject: None,
+                }),
+                versions: default_subject_versions(),
+                serve_unversioned: default_serve_unversioned(),
+                admin_origins: Vec::new(),
+            },
+            service: ServiceConfig {
+                bind_address: "0.0.0.0".to_string(),
+                port: 8080,
+                health_check_interval: Duration::from_secs(30),
+                metrics: MetricsConfig {
+                    enabled: true,
+                    endpoint: "/metrics".to_string(),
+                    push_gateway: None,
+                },
+                logging: LoggingConfig {
+                    level: "info".to_string(),
+                    format: "json".to_string(),
+                    colors: false,
+                    file: None,
+                    include_payloads: false,
+                    redact: Vec::new(),
+                    access: None,
+                },
+                pid_file: None,
+                http_api: false,
+                health_endpoints: true,
+                probe_model: true,
+                api_auth: None,
+                event_stream: None,
+            },
+            domains: DomainConfigs {
+                dialog: DialogConfig {
+                    max_history: 100,
+                    context_window: 10,
+                    session_timeout: Duration::from_secs(3600),
+                    soft_deadline: None,
+                },
+                graph: GraphConfig {
+                    max_nodes: 1000,
+                    auto_layout: true,
+                    layout_algorithm: "force-directed".to_string(),
                 },
                 workflow: WorkflowConfig {
                     max_concurrent: 10,
                     timeout: Duration::from_secs(300),
                     persist: true,
                 },
+                conceptual_space: ConceptualSpaceConfig::default(),
             },
+            storage: StorageConfig::default(),
+            cache: CacheConfig::default(),
+            integrations: IntegrationsConfig::default(),
+            sources: SourcesConfig::default(),
+            retrieval: RetrievalConfig::default(),
+            event_schemas: EventSchemasConfig::default(),
+            tools: ToolsConfig::default(),
+            peers: PeersConfig::default(),
+            prompt_guard: PromptGuardConfig::default(),
+            command_signing: None,
+            budgets: BudgetConfig::default(),
+            localization: LocalizationConfig::default(),
+            schedule: Vec::new(),
+            evaluation: EvaluationConfig::default(),
+            comparison: ComparisonConfig::default(),
+            usage: UsageConfig::default(),
+            priority: PriorityConfig::default(),
+            replay: ReplayConfig::default(),
+            announcements: AnnouncementConfig::default(),
+            limits: LimitsConfig::default(),
+            capabilities: CapabilitiesConfig::default(),
+        }
+    }
+}
+
+/// File formats a configuration can be read from or written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Guess the format from a file extension
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| ext.parse().ok())
+    }
+    
+    /// Preferred file extension
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "yaml",
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+        }
+    }
+}
+
+impl std::str::FromStr for ConfigFormat {
+    type Err = crate::error::AgentError;
+    
+    fn from_str(s: &str) -> crate::error::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            other => Err(crate::error::AgentError::Configuration(format!(
+                "Unknown config format: {} (expected yaml, toml or json)",
+                other
+            ))),
+        }
+    }
+}
+
+impl ConfigFormat {
+    /// Guess the format from file contents when the extension is unknown
+    pub fn detect(contents: &str) -> Self {
+        if contents.trim_start().starts_with('{') {
+            ConfigFormat::Json
+        } else if contents.contains(':') && !contents.contains('=') {
+            ConfigFormat::Yaml
+        } else {
+            ConfigFormat::Toml
+        }
+    }
+    
+    /// Parse contents into a format-neutral value
+    pub fn parse_value(&self, contents: &str) -> crate::error::Result<serde_json::Value> {
+        let value = match self {
+            ConfigFormat::Yaml => serde_yaml::from_str(contents)
+                .map_err(|e| crate::error::AgentError::Configuration(e.to_string()))?,
+            ConfigFormat::Toml => toml::from_str(contents)
+                .map_err(|e| crate::error::AgentError::Configuration(e.to_string()))?,
+            ConfigFormat::Json => serde_json::from_str(contents)?,
+        };
+        
+        Ok(value)
+    }
+}
+
+/// Environment variable selecting the configuration profile
+pub const PROFILE_ENV: &str = "ALCHEMIST_PROFILE";
+
+impl AgentConfig {
+    /// Load a configuration file, applying the named profile if given
+    ///
+    /// Profiles live under a top-level `profiles` map. The selected profile
+    /// is merged over the rest of the file, so it only needs the settings
+    /// that differ from the base.
+    pub fn from_file(path: &std::path::Path, profile: Option<&str>) -> crate::error::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let format = ConfigFormat::from_path(path).unwrap_or_else(|| ConfigFormat::detect(&contents));
+        
+        Self::from_value(format.parse_value(&contents)?, profile)
+    }
+    
+    /// Build a configuration from a parsed document, applying the named profile
+    pub fn from_value(mut document: serde_json::Value, profile: Option<&str>) -> crate::error::Result<Self> {
+        let profiles = document
+            .as_object_mut()
+            .and_then(|root| root.remove("profiles"))
+            .unwrap_or(serde_json::Value::Null);
+        
+        if let Some(name) = profile {
+            let overlay = profiles.get(name).cloned().ok_or_else(|| {
+                let available: Vec<&String> = profiles
+                    .as_object()
+                    .map(|map| map.keys().collect())
+                    .unwrap_or_default();
+                crate::error::AgentError::Configuration(format!(
+                    "Unknown profile '{}' (available: {:?})",
+                    name, available
+                ))
+            })?;
+            merge_values(&mut document, overlay);
+        }
+        
+        let config: Self = serde_json::from_value(document)?;
+        if let Some(jetstream) = &config.nats.jetstream {
+            jetstream.validate()?;
+        }
+        Ok(config)
+    }
+    
+    /// Serialize the configuration in the given format
+    pub fn render(&self, format: ConfigFormat) -> crate::error::Result<String> {
+        format.render(self)
+    }
+}
+
+impl ConfigFormat {
+    /// Serialize a configuration or configuration document
+    pub fn render<T: Serialize + ?Sized>(&self, value: &T) -> crate::error::Result<String> {
+        let rendered = match self {
+            ConfigFormat::Yaml => serde_yaml::to_string(value)
+                .map_err(|e| crate::error::AgentError::Configuration(e.to_string()))?,
+            ConfigFormat::Toml => toml::to_string_pretty(value)
+                .map_err(|e| crate::error::AgentError::Configuration(e.to_string()))?,
+            ConfigFormat::Json => serde_json::to_string_pretty(value)?,
+        };
+        
+        Ok(rendered)
+    }
+}
+
+/// Recursively merge `overlay` into `base`; non-object values replace
+fn merge_values(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
         }
+        (base, overlay) => *base = overlay,
     }
 }
 
@@ -364,4 +2139,80 @@ mod humantime_serde {
             Err(serde::de::Error::custom("Invalid duration format"))
         }
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn document() -> serde_json::Value {
+        let mut document = serde_json::to_value(AgentConfig::default()).unwrap();
+        document["profiles"] = serde_json::json!({
+            "prod": {
+                "nats": { "servers": ["nats://prod:4222"] },
+                "service": { "logging": { "level": "warn" } },
+            },
+        });
+        document
+    }
+    
+    #[test]
+    fn test_retry_backoff() {
+        let retry = AgentConfig::default().nats.retry;
+        assert_eq!(retry.delay(1), Duration::from_millis(100));
+        assert_eq!(retry.delay(3), Duration::from_millis(400));
+        assert_eq!(retry.delay(30), retry.max_delay);
+    }
+    
+    #[test]
+    fn test_profile_overrides_base() {
+        let config = AgentConfig::from_value(document(), Some("prod")).unwrap();
+        assert_eq!(config.nats.servers, vec!["nats://prod:4222".to_string()]);
+        assert_eq!(config.service.logging.level, "warn");
+        // Untouched settings are inherited from the base section
+        assert_eq!(config.service.logging.format, "json");
+    }
+    
+    #[test]
+    fn test_no_profile_uses_base() {
+        let config = AgentConfig::from_value(document(), None).unwrap();
+        assert_eq!(config.nats.servers, vec!["nats://localhost:4222".to_string()]);
+    }
+    
+    #[test]
+    fn test_overrides_apply_to_every_provider() {
+        for provider in [ProviderKind::Ollama, ProviderKind::OpenAI, ProviderKind::Anthropic] {
+            let mut model = ModelConfig::for_provider(provider, None);
+            model.set_model("custom");
+            model.set_temperature(0.2);
+            model.set_max_tokens(512);
+            
+            assert_eq!(model.provider(), provider);
+            assert_eq!(model.model_name(), "custom");
+            assert_eq!(model.temperature(), 0.2);
+            assert_eq!(model.max_tokens(), 512);
+        }
+    }
+    
+    #[test]
+    fn test_jetstream_settings_are_validated() {
+        let mut document = document();
+        document["nats"]["jetstream"]["ack_wait"] = serde_json::json!("0s");
+        let err = AgentConfig::from_value(document.clone(), None).unwrap_err();
+        assert!(err.to_string().contains("ack_wait"));
+        
+        document["nats"]["jetstream"]["ack_wait"] = serde_json::json!("30s");
+        document["nats"]["jetstream"]["max_deliver"] = serde_json::json!(0);
+        let err = AgentConfig::from_value(document.clone(), None).unwrap_err();
+        assert!(err.to_string().contains("max_deliver"));
+        
+        document["nats"]["jetstream"]["max_deliver"] = serde_json::json!(3);
+        assert!(AgentConfig::from_value(document, None).is_ok());
+    }
+    
+    #[test]
+    fn test_unknown_profile_is_rejected() {
+        let err = AgentConfig::from_value(document(), Some("staging")).unwrap_err();
+        assert!(err.to_string().contains("staging"));
+    }
+}
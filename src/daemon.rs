@@ -0,0 +1,104 @@
+//! Daemon mode support
+//!
+//! Detaches the agent from its terminal, manages the pidfile, and lets a
+//! later `alchemist stop` signal the running instance to drain and exit.
+
+use crate::error::{AgentError, Result};
+use std::fs::OpenOptions;
+use std::path::Path;
+
+/// Pidfile used when neither the CLI nor the config names one
+pub const DEFAULT_PID_FILE: &str = "/tmp/cim-agent-alchemist.pid";
+
+/// Detach the current process and record its pid
+///
+/// Must be called before the async runtime or any other threads start.
+/// Stdout and stderr are appended to `log_file` when one is configured.
+pub fn daemonize(pid_file: &Path, log_file: Option<&Path>) -> Result<()> {
+    if let Some(pid) = running_pid(pid_file)? {
+        return Err(AgentError::Configuration(format!(
+            "Agent already running with pid {} ({})",
+            pid,
+            pid_file.display()
+        )));
+    }
+
+    // Keep relative config and log paths meaningful after detaching
+    let mut daemon = daemonize::Daemonize::new()
+        .pid_file(pid_file)
+        .working_directory(std::env::current_dir()?);
+
+    if let Some(log_file) = log_file {
+        let stdout = OpenOptions::new().create(true).append(true).open(log_file)?;
+        let stderr = stdout.try_clone()?;
+        daemon = daemon.stdout(stdout).stderr(stderr);
+    }
+
+    daemon
+        .start()
+        .map_err(|e| AgentError::Internal(format!("Failed to daemonize: {}", e)))
+}
+
+/// Signal the running instance to drain and stop, returning its pid
+pub fn stop(pid_file: &Path) -> Result<i32> {
+    let pid = running_pid(pid_file)?.ok_or_else(|| {
+        AgentError::NotFound(format!("No running agent recorded in {}", pid_file.display()))
+    })?;
+
+    // SAFETY: kill(2) has no memory-safety preconditions
+    if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(pid)
+}
+
+/// Read the pidfile, returning the pid only if that process is alive
+pub fn running_pid(pid_file: &Path) -> Result<Option<i32>> {
+    let contents = match std::fs::read_to_string(pid_file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let pid = parse_pid(&contents)?;
+
+    // Signal 0 only checks that the process exists
+    // SAFETY: kill(2) has no memory-safety preconditions
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        Ok(Some(pid))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Remove the pidfile after a clean shutdown
+pub fn remove_pid_file(pid_file: &Path) -> Result<()> {
+    match std::fs::remove_file(pid_file) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn parse_pid(contents: &str) -> Result<i32> {
+    contents
+        .trim()
+        .parse::<i32>()
+        .ok()
+        .filter(|pid| *pid > 0)
+        .ok_or_else(|| AgentError::Configuration(format!("Invalid pidfile contents: {:?}", contents)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pid() {
+        assert_eq!(parse_pid("4242\n").unwrap(), 4242);
+        assert!(parse_pid("").is_err());
+        assert!(parse_pid("-1").is_err());
+        assert!(parse_pid("not-a-pid").is_err());
+    }
+}
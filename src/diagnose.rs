@@ -0,0 +1,124 @@
+//! Reading compiler and runtime errors for `explain_error`
+//!
+//! The clues pulled from an error, such as its code, the source locations it
+//! points at, and the identifiers it names, decide which concepts and indexed
+//! code the agent looks up before asking the model for a diagnosis.
+
+use serde::Serialize;
+
+/// Identifiers looked up in indexed code per error
+const MAX_IDENTIFIERS: usize = 5;
+
+/// What an error message points at
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ErrorClues {
+    /// `compiler` for rustc output, otherwise `runtime`
+    pub kind: &'static str,
+
+    /// rustc error code, such as `E0277`
+    pub code: Option<String>,
+
+    /// `path:line:column` locations from `-->` lines
+    pub locations: Vec<String>,
+
+    /// Names quoted in backticks, such as types and traits
+    pub identifiers: Vec<String>,
+}
+
+impl ErrorClues {
+    pub fn parse(error: &str) -> Self {
+        let code = error.find("error[E").and_then(|start| {
+            let rest = &error[start + "error[".len()..];
+            rest.find(']').map(|end| rest[..end].to_string())
+        });
+        let kind = if code.is_some() || error.contains("\n  --> ") || error.starts_with("error: ") {
+            "compiler"
+        } else {
+            "runtime"
+        };
+
+        let locations = error
+            .lines()
+            .filter_map(|line| line.trim_start().strip_prefix("--> "))
+            .map(|location| location.trim().to_string())
+            .collect();
+
+        // Every other piece of a backticked string is inside the backticks
+        let mut identifiers: Vec<String> = Vec::new();
+        for quoted in error.split('`').skip(1).step_by(2) {
+            let name = quoted
+                .trim_start_matches('&')
+                .trim_start_matches("mut ")
+                .split(['<', '(', ' ', ':'])
+                .next()
+                .unwrap_or_default();
+            let is_identifier = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+            if is_identifier && !identifiers.iter().any(|known| known == name) {
+                identifiers.push(name.to_string());
+            }
+        }
+        identifiers.truncate(MAX_IDENTIFIERS);
+
+        Self {
+            kind,
+            code,
+            locations,
+            identifiers,
+        }
+    }
+}
+
+/// Split a model reply into its diagnosis and numbered fix steps
+///
+/// The model is asked for `Diagnosis:` and `Fix:` sections; a reply without
+/// them is all diagnosis.
+pub fn parse_diagnosis(reply: &str) -> (String, Vec<String>) {
+    let Some((diagnosis, fix)) = reply.split_once("\nFix:") else {
+        return (reply.trim().trim_start_matches("Diagnosis:").trim().to_string(), Vec::new());
+    };
+
+    let steps = fix
+        .lines()
+        .map(|line| {
+            line.trim()
+                .trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '-' | '*' | '.' | ')'))
+                .trim()
+                .to_string()
+        })
+        .filter(|step| !step.is_empty())
+        .collect();
+
+    (diagnosis.trim().trim_start_matches("Diagnosis:").trim().to_string(), steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rustc_error() {
+        let error = "error[E0277]: the trait bound `Workflow: Serialize` is not satisfied\n  \
+            --> src/agent.rs:42:9\n   |\n42 |     serde_json::to_value(&workflow)?;\n   \
+            = note: required by a bound in `to_value`";
+        let clues = ErrorClues::parse(error);
+
+        assert_eq!(clues.kind, "compiler");
+        assert_eq!(clues.code.as_deref(), Some("E0277"));
+        assert_eq!(clues.locations, vec!["src/agent.rs:42:9"]);
+        assert_eq!(clues.identifiers, vec!["Workflow", "to_value"]);
+
+        let runtime = ErrorClues::parse("Nats error: no responders on `cim.agent.alchemist.commands`");
+        assert_eq!(runtime.kind, "runtime");
+        assert!(runtime.identifiers.is_empty());
+    }
+
+    #[test]
+    fn test_parse_diagnosis() {
+        let reply = "Diagnosis: Workflow does not derive Serialize.\n\nFix:\n1. Add `#[derive(Serialize)]`\n2. Rebuild";
+        let (diagnosis, steps) = parse_diagnosis(reply);
+
+        assert_eq!(diagnosis, "Workflow does not derive Serialize.");
+        assert_eq!(steps, vec!["Add `#[derive(Serialize)]`", "Rebuild"]);
+        assert_eq!(parse_diagnosis("Just prose").0, "Just prose");
+    }
+}
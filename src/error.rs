@@ -40,6 +40,10 @@ pub enum AgentError {
     #[error("Workflow error: {0}")]
     Workflow(String),
 
+    /// Storage backend errors
+    #[error("Storage error: {0}")]
+    Storage(String),
+
     /// Serialization/deserialization errors
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
@@ -76,6 +80,10 @@ pub enum AgentError {
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
 
+    /// Payload over a configured size limit
+    #[error("Payload too large: {what} is {size} bytes, over the limit of {limit}; put it in the object store and send its key instead")]
+    PayloadTooLarge { what: String, size: usize, limit: usize },
+
     /// IO error
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -98,11 +106,32 @@ impl AgentError {
         )
     }
 
+    /// Machine-readable code for errors callers are expected to handle,
+    /// sent in reply envelopes beside the message
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            Self::PayloadTooLarge { .. } => Some("payload_too_large"),
+            _ => None,
+        }
+    }
+
+    /// Details of an error with a [`code`](Self::code)
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            Self::PayloadTooLarge { what, size, limit } => Some(serde_json::json!({
+                "what": what,
+                "size": size,
+                "limit": limit,
+            })),
+            _ => None,
+        }
+    }
+
     /// Get the error severity for logging
     pub fn severity(&self) -> &'static str {
         match self {
             Self::Configuration(_) | Self::PermissionDenied(_) => "critical",
-            Self::Domain { .. } | Self::Dialog(_) | Self::Identity(_) => "error",
+            Self::Domain { .. } | Self::Dialog(_) | Self::Identity(_) | Self::Storage(_) => "error",
             Self::Nats(_) | Self::Network(_) | Self::ServiceUnavailable(_) => "warning",
             _ => "info",
         }
@@ -0,0 +1,308 @@
+//! Evaluation sets for catching knowledge-base and prompt regressions
+//!
+//! A set is a YAML file of questions, each with the facts a good answer
+//! states and content it must not contain. `alchemist eval` asks a running
+//! agent each question in a dialog of its own and scores the answers,
+//! either by matching the facts' words or by asking a model to judge.
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::Path;
+
+use crate::error::{AgentError, Result};
+use crate::model::ModelProvider;
+
+/// Questions with what their answers should and should not say
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalSet {
+    #[serde(default)]
+    pub name: String,
+
+    pub cases: Vec<EvalCase>,
+}
+
+/// One question
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalCase {
+    pub name: String,
+    pub question: String,
+
+    /// Facts a good answer states
+    #[serde(default)]
+    pub expected: Vec<String>,
+
+    /// Content a good answer never contains
+    #[serde(default)]
+    pub forbidden: Vec<String>,
+}
+
+/// Read an evaluation set from a YAML file
+pub fn load_set(path: &Path) -> Result<EvalSet> {
+    let contents = std::fs::read_to_string(path)?;
+    let set: EvalSet = serde_yaml::from_str(&contents)
+        .map_err(|e| AgentError::InvalidRequest(format!("Invalid evaluation set {}: {}", path.display(), e)))?;
+    if set.cases.is_empty() {
+        return Err(AgentError::InvalidRequest(format!("Evaluation set {} has no cases", path.display())));
+    }
+    Ok(set)
+}
+
+/// How answers are scored
+pub enum Scorer<'a> {
+    /// Facts are found when their words appear in the answer, forbidden
+    /// content when it appears verbatim, ignoring case
+    Heuristic,
+
+    /// The model decides, falling back to the heuristic for anything it
+    /// gives no verdict on
+    Judge(&'a dyn ModelProvider),
+}
+
+impl Scorer<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            Scorer::Heuristic => "heuristic",
+            Scorer::Judge(_) => "judge",
+        }
+    }
+}
+
+/// How one answer scored
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseScore {
+    pub name: String,
+
+    /// Share of expected facts the answer states, from 0 to 1
+    pub score: f32,
+
+    /// Every fact stated and nothing forbidden said
+    pub passed: bool,
+
+    pub missing: Vec<String>,
+    pub forbidden_found: Vec<String>,
+    pub answer: String,
+
+    /// Why the question could not be answered or scored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CaseScore {
+    fn new(case: &EvalCase, answer: String, found: Vec<bool>, forbidden: Vec<bool>) -> Self {
+        let missing: Vec<String> = case
+            .expected
+            .iter()
+            .zip(&found)
+            .filter(|(_, found)| !**found)
+            .map(|(fact, _)| fact.clone())
+            .collect();
+        let forbidden_found: Vec<String> = case
+            .forbidden
+            .iter()
+            .zip(&forbidden)
+            .filter(|(_, found)| **found)
+            .map(|(content, _)| content.clone())
+            .collect();
+
+        Self {
+            name: case.name.clone(),
+            score: if case.expected.is_empty() {
+                1.0
+            } else {
+                (case.expected.len() - missing.len()) as f32 / case.expected.len() as f32
+            },
+            passed: missing.is_empty() && forbidden_found.is_empty(),
+            missing,
+            forbidden_found,
+            answer,
+            error: None,
+        }
+    }
+
+    fn failed(case: &EvalCase, error: AgentError) -> Self {
+        Self {
+            name: case.name.clone(),
+            score: 0.0,
+            passed: false,
+            missing: case.expected.clone(),
+            forbidden_found: Vec::new(),
+            answer: String::new(),
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// Scores of a whole set
+#[derive(Debug, Clone, Serialize)]
+pub struct EvalReport {
+    pub set: String,
+    pub scorer: String,
+    pub passed: usize,
+    pub failed: usize,
+
+    /// Mean score over all cases
+    pub score: f32,
+
+    pub cases: Vec<CaseScore>,
+}
+
+/// Answer each case with `answer` and score the answers
+pub async fn run<F, Fut>(set: &EvalSet, answer: F, scorer: &Scorer<'_>) -> EvalReport
+where
+    F: Fn(&EvalCase) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let mut cases = Vec::with_capacity(set.cases.len());
+    for case in &set.cases {
+        let score = match answer(case).await {
+            Ok(answer) => score(case, answer, scorer).await,
+            Err(e) => CaseScore::failed(case, e),
+        };
+        cases.push(score);
+    }
+
+    let passed = cases.iter().filter(|case| case.passed).count();
+    EvalReport {
+        set: set.name.clone(),
+        scorer: scorer.name().to_string(),
+        passed,
+        failed: cases.len() - passed,
+        score: cases.iter().map(|case| case.score).sum::<f32>() / cases.len().max(1) as f32,
+        cases,
+    }
+}
+
+async fn score(case: &EvalCase, answer: String, scorer: &Scorer<'_>) -> CaseScore {
+    let mut found: Vec<bool> = case.expected.iter().map(|fact| states(&answer, fact)).collect();
+    let mut forbidden: Vec<bool> = case.forbidden.iter().map(|content| contains(&answer, content)).collect();
+
+    if let Scorer::Judge(provider) = scorer {
+        let prompt = judge_prompt(case, &answer);
+        match provider.generate(&prompt).await {
+            Ok(reply) => {
+                let verdicts = parse_verdicts(&reply);
+                apply_verdicts(&mut found, &verdicts, 'F');
+                apply_verdicts(&mut forbidden, &verdicts, 'X');
+            }
+            Err(e) => return CaseScore::failed(case, e),
+        }
+    }
+
+    CaseScore::new(case, answer, found, forbidden)
+}
+
+/// Whether `answer` states `fact`: verbatim, or with all of its longer words
+fn states(answer: &str, fact: &str) -> bool {
+    if contains(answer, fact) {
+        return true;
+    }
+
+    let answer = words(answer);
+    let significant: Vec<String> = words(fact).into_iter().filter(|word| word.chars().count() > 3).collect();
+    !significant.is_empty() && significant.iter().all(|word| answer.contains(word))
+}
+
+fn contains(answer: &str, content: &str) -> bool {
+    answer.to_lowercase().contains(&content.to_lowercase())
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Prompt asking the model for a verdict on each fact and forbidden item
+fn judge_prompt(case: &EvalCase, answer: &str) -> String {
+    let mut prompt = format!(
+        "Judge an answer to a question about CIM.\n\nQuestion:\n{}\n\nAnswer:\n{}\n\n",
+        case.question, answer
+    );
+    for (index, fact) in case.expected.iter().enumerate() {
+        prompt.push_str(&format!("F{}: {}\n", index + 1, fact));
+    }
+    for (index, content) in case.forbidden.iter().enumerate() {
+        prompt.push_str(&format!("X{}: {}\n", index + 1, content));
+    }
+    prompt.push_str(
+        "\nFor each item above, reply with a line of its label followed by \"yes\" if the answer states it, \
+         even in other words, or \"no\" if it does not, such as \"F1: yes\".",
+    );
+    prompt
+}
+
+/// Verdicts by label, such as `("F1", true)`
+fn parse_verdicts(reply: &str) -> Vec<(String, bool)> {
+    reply
+        .lines()
+        .filter_map(|line| {
+            let (label, verdict) = line.trim_start_matches(['-', '*', ' ']).split_once(':')?;
+            let label = label.trim().trim_matches('*').to_uppercase();
+            let verdict = verdict.split_whitespace().next()?.trim_matches(|c: char| !c.is_alphabetic());
+            match verdict.to_lowercase().as_str() {
+                "yes" => Some((label, true)),
+                "no" => Some((label, false)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn apply_verdicts(results: &mut [bool], verdicts: &[(String, bool)], prefix: char) {
+    for (index, result) in results.iter_mut().enumerate() {
+        let label = format!("{}{}", prefix, index + 1);
+        if let Some((_, verdict)) = verdicts.iter().find(|(known, _)| *known == label) {
+            *result = *verdict;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case() -> EvalCase {
+        serde_yaml::from_str(
+            "name: cqrs\n\
+             question: What is CQRS?\n\
+             expected: [\"separates commands from queries\", \"read models\"]\n\
+             forbidden: [\"two-phase commit\"]\n",
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_heuristic_scoring() {
+        let set = EvalSet {
+            name: "basics".to_string(),
+            cases: vec![case()],
+        };
+
+        let report = run(
+            &set,
+            |_| async { Ok("CQRS separates queries from commands, using a two-phase commit.".to_string()) },
+            &Scorer::Heuristic,
+        )
+        .await;
+        let scored = &report.cases[0];
+        assert_eq!(scored.score, 0.5);
+        assert_eq!(scored.missing, vec!["read models"]);
+        assert_eq!(scored.forbidden_found, vec!["two-phase commit"]);
+        assert_eq!((report.passed, report.failed), (0, 1));
+    }
+
+    #[test]
+    fn test_judge_verdicts_override_heuristic() {
+        let verdicts = parse_verdicts("**F1:** yes\n- F2: No, it never mentions them.\nX1: no");
+        assert_eq!(verdicts.len(), 3);
+
+        let mut found = vec![false, true, false];
+        apply_verdicts(&mut found, &verdicts, 'F');
+        assert_eq!(found, vec![true, false, false]);
+
+        let mut forbidden = vec![true];
+        apply_verdicts(&mut forbidden, &verdicts, 'X');
+        assert_eq!(forbidden, vec![false]);
+    }
+}
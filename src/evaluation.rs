@@ -0,0 +1,170 @@
+//! Second-pass review of the agent's own answers
+//!
+//! For command types listed under `evaluation.commands`, the model reads its
+//! answer again next to the sources it drew on and grades it against a
+//! rubric. The confidence and caveats it reports go out with the response,
+//! so callers can decide how far to trust it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AgentError, Result};
+
+/// What a good answer does, unless `evaluation.rubric` says otherwise
+pub const DEFAULT_RUBRIC: &str = "The answer is correct for CIM and its event-sourced, NATS-based architecture, \
+is supported by the sources where it relies on them, answers the whole request, and is honest about what it \
+does not know.";
+
+/// Fields of a command result holding the answer, in order of preference
+const ANSWER_FIELDS: &[&str] = &["explanation", "diagnosis", "analysis", "summary"];
+
+/// Fields of a command result holding the sources the answer drew on
+const SOURCE_FIELDS: &[&str] = &["examples", "code_references", "citations"];
+
+/// The model's verdict on an answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Evaluation {
+    /// From 0 (probably wrong) to 1 (well supported)
+    pub confidence: f32,
+
+    /// What the reader should double-check or keep in mind
+    pub caveats: Vec<String>,
+}
+
+/// Prompt asking the model to grade `answer` to `request`
+pub fn evaluation_prompt(request: &str, answer: &str, sources: &[String], rubric: &str) -> String {
+    let mut prompt = format!(
+        "Review an answer you gave before relying on it.\n\nRequest:\n{}\n\nAnswer:\n{}\n\n",
+        request, answer
+    );
+    if sources.is_empty() {
+        prompt.push_str("No sources were retrieved for this answer.\n\n");
+    } else {
+        for (index, source) in sources.iter().enumerate() {
+            prompt.push_str(&format!("Source {}:\n{}\n\n", index + 1, source));
+        }
+    }
+    prompt.push_str(&format!(
+        "Rubric: {}\n\n\
+         Reply with a line \"Confidence:\" followed by a number from 0 to 1, then a line \"Caveats:\" \
+         followed by one bullet per claim that is unsupported, doubtful, or missing. Write \"- none\" if \
+         there are none.",
+        rubric
+    ));
+    prompt
+}
+
+/// Read the confidence and caveats out of a model reply
+pub fn parse_evaluation(reply: &str) -> Result<Evaluation> {
+    let mut confidence = None;
+    let mut caveats = Vec::new();
+    let mut in_caveats = false;
+
+    for line in reply.lines().map(str::trim) {
+        if let Some(value) = strip_label(line, "confidence:") {
+            let value = value.trim_end_matches('.');
+            confidence = match value.strip_suffix('%') {
+                Some(percent) => percent.trim().parse::<f32>().ok().map(|percent| percent / 100.0),
+                None => value.parse::<f32>().ok(),
+            };
+            in_caveats = false;
+        } else if let Some(rest) = strip_label(line, "caveats:") {
+            in_caveats = true;
+            caveats.extend(caveat(rest));
+        } else if in_caveats {
+            caveats.extend(caveat(line));
+        }
+    }
+
+    let confidence = confidence
+        .ok_or_else(|| AgentError::ModelError("The model's evaluation has no confidence".to_string()))?;
+    Ok(Evaluation {
+        confidence: confidence.clamp(0.0, 1.0),
+        caveats,
+    })
+}
+
+/// The answer and sources in a command result, if it has a textual answer
+pub fn answer_and_sources(result: &serde_json::Value) -> Option<(String, Vec<String>)> {
+    let answer = ANSWER_FIELDS.iter().find_map(|field| result[*field].as_str())?;
+    let sources = SOURCE_FIELDS
+        .iter()
+        .filter_map(|field| result[*field].as_array())
+        .flatten()
+        .map(source_text)
+        .collect();
+
+    Some((answer.to_string(), sources))
+}
+
+/// A source as the model reads it
+fn source_text(source: &serde_json::Value) -> String {
+    match source {
+        serde_json::Value::String(text) => text.clone(),
+        _ if source["snippet"].is_string() => format!(
+            "{}\n{}",
+            source["source"].as_str().unwrap_or_default(),
+            source["snippet"].as_str().unwrap_or_default()
+        ),
+        _ if source["url"].is_string() => format!(
+            "{} ({})",
+            source["title"].as_str().unwrap_or_default(),
+            source["url"].as_str().unwrap_or_default()
+        ),
+        _ => source.to_string(),
+    }
+}
+
+fn strip_label<'a>(line: &'a str, label: &str) -> Option<&'a str> {
+    let line = line.trim_start_matches(['*', '#', ' ']);
+    let head = line.get(..label.len())?;
+    head.eq_ignore_ascii_case(label)
+        .then(|| line[label.len()..].trim_start_matches('*').trim())
+}
+
+fn caveat(line: &str) -> Option<String> {
+    let text = line.trim_start_matches(['-', '*', ' ']).trim();
+    let is_none = text.is_empty() || text.trim_end_matches('.').eq_ignore_ascii_case("none");
+    (!is_none).then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_evaluation() {
+        let reply = "**Confidence:** 0.65\n\nCaveats:\n- The example names a crate that was not retrieved\n\
+            * Snapshotting is not covered";
+        let evaluation = parse_evaluation(reply).unwrap();
+
+        assert_eq!(evaluation.confidence, 0.65);
+        assert_eq!(
+            evaluation.caveats,
+            vec!["The example names a crate that was not retrieved", "Snapshotting is not covered"]
+        );
+
+        let evaluation = parse_evaluation("Confidence: 90%\nCaveats: none").unwrap();
+        assert_eq!(evaluation.confidence, 0.9);
+        assert!(evaluation.caveats.is_empty());
+        assert!(parse_evaluation("Looks good to me").is_err());
+    }
+
+    #[test]
+    fn test_answer_and_sources() {
+        let result = serde_json::json!({
+            "concept": "CQRS",
+            "explanation": "Commands and queries take separate paths.",
+            "examples": ["GraphEvent::NodeAdded in cim-domain-graph"],
+            "code_references": [{"source": "cim-domain/src/cqrs.rs:12", "snippet": "pub trait Command"}],
+        });
+        let (answer, sources) = answer_and_sources(&result).unwrap();
+
+        assert_eq!(answer, "Commands and queries take separate paths.");
+        assert_eq!(
+            sources,
+            vec!["GraphEvent::NodeAdded in cim-domain-graph", "cim-domain/src/cqrs.rs:12\npub trait Command"]
+        );
+        assert!(answer_and_sources(&serde_json::json!({"files": []})).is_none());
+    }
+}
@@ -0,0 +1,285 @@
+//! Agent events relayed to browsers on `/ws/events`
+//!
+//! Dashboards open a WebSocket to `/ws/events` and get every agent event
+//! published on `<subject_prefix>.events.>` as a JSON text message, the
+//! same [`AgentEvent`] NATS subscribers see, without a NATS WebSocket
+//! gateway in between. `service.event_stream.event_types` limits which
+//! events are ever relayed, and the `types` query parameter narrows them
+//! further per connection, such as `?types=dialog_*,workflow_completed`.
+//!
+//! The endpoint needs credentials with the `Read` scope from
+//! `service.api_auth`. Browsers cannot set headers on a WebSocket, so the
+//! API key or bearer token may also come as `?access_token=`. Each
+//! connection gets at most `max_events_per_second` events; the rest are
+//! dropped, and the next event relayed is preceded by an `events_dropped`
+//! event with their count.
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures::StreamExt;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+use crate::api::ApiResponse;
+use crate::auth::ApiAuth;
+use crate::config::{ApiAuthConfig, ApiScope, EventStreamConfig, NatsConfig};
+use crate::error::{AgentError, Result};
+use crate::events::AgentEventKind;
+use crate::nats_integration::{served_subjects, AgentEvent};
+
+/// What the relay needs for every connection
+struct EventStream {
+    config: EventStreamConfig,
+    auth: ApiAuth,
+    nats: async_nats::Client,
+
+    /// Subject the events are read from
+    subject: String,
+}
+
+/// Query parameters of `/ws/events`
+#[derive(Debug, Default, Deserialize)]
+struct EventsParams {
+    /// Comma-separated event types, each possibly ending in `*`
+    types: Option<String>,
+
+    /// Credentials for browsers, which cannot set headers
+    access_token: Option<String>,
+}
+
+/// The `/ws/events` route, protected by `auth`
+pub fn router(
+    config: &EventStreamConfig,
+    auth: &ApiAuthConfig,
+    nats_config: &NatsConfig,
+    nats: async_nats::Client,
+) -> Result<Router> {
+    let stream = EventStream {
+        config: config.clone(),
+        auth: ApiAuth::new(auth)?,
+        nats,
+        subject: events_subject(nats_config),
+    };
+    Ok(Router::new()
+        .route("/ws/events", get(events))
+        .with_state(Arc::new(stream)))
+}
+
+/// One of the subjects events are published on, since every served
+/// version carries the same events
+fn events_subject(config: &NatsConfig) -> String {
+    served_subjects(&config.subject_prefix, &config.versions, config.serve_unversioned, "events.>")
+        .pop()
+        .unwrap_or_else(|| format!("{}.events.>", config.subject_prefix))
+}
+
+async fn events(
+    State(stream): State<Arc<EventStream>>,
+    Query(params): Query<EventsParams>,
+    mut headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    if let Some(token) = params.access_token.as_deref() {
+        insert_access_token(&mut headers, token);
+    }
+    let Some(caller) = stream.auth.authenticate(&headers).await else {
+        debug!("Unauthenticated request to /ws/events");
+        let denied = AgentError::PermissionDenied("Missing or invalid credentials".to_string());
+        return (StatusCode::UNAUTHORIZED, Json(ApiResponse::from_error(&denied))).into_response();
+    };
+    if !caller.allows(ApiScope::Read) {
+        warn!(target: "audit", caller = %caller.name, "Event stream denied: needs Read scope");
+        let denied = AgentError::PermissionDenied("/ws/events requires the Read scope".to_string());
+        return (StatusCode::FORBIDDEN, Json(ApiResponse::from_error(&denied))).into_response();
+    }
+
+    let events = match stream.nats.subscribe(stream.subject.clone()).await {
+        Ok(events) => events,
+        Err(e) => {
+            let unavailable = AgentError::ServiceUnavailable(format!("Cannot subscribe to agent events: {}", e));
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(ApiResponse::from_error(&unavailable))).into_response();
+        }
+    };
+
+    let requested: Vec<String> = params
+        .types
+        .as_deref()
+        .map(|types| {
+            types
+                .split(',')
+                .map(str::trim)
+                .filter(|event_type| !event_type.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    let allowed = stream.config.event_types.clone();
+    let throttle = Throttle::new(stream.config.max_events_per_second);
+    debug!(caller = %caller.name, "Relaying agent events over a WebSocket");
+
+    let wanted = move |event_type: &str| matches(&allowed, event_type) && matches(&requested, event_type);
+    upgrade.on_upgrade(move |socket| relay(socket, events, wanted, throttle))
+}
+
+/// Present `?access_token=` as a bearer token, so it authenticates as an
+/// API key or, with OIDC configured, as an access token
+fn insert_access_token(headers: &mut HeaderMap, token: &str) {
+    if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", token)) {
+        headers.insert("authorization", value);
+    }
+}
+
+/// Relay events to `socket` until either side closes
+async fn relay(
+    mut socket: WebSocket,
+    mut events: async_nats::Subscriber,
+    wanted: impl Fn(&str) -> bool,
+    mut throttle: Throttle,
+) {
+    let mut dropped = 0u64;
+    loop {
+        tokio::select! {
+            message = events.next() => {
+                let Some(message) = message else {
+                    break;
+                };
+                let Ok(event) = serde_json::from_slice::<AgentEvent>(&message.payload) else {
+                    continue;
+                };
+                if !wanted(event.event_type()) {
+                    continue;
+                }
+                if !throttle.allow(Instant::now()) {
+                    dropped += 1;
+                    continue;
+                }
+
+                if dropped > 0 {
+                    let notice = AgentEvent::new(AgentEventKind::EventsDropped { count: dropped });
+                    let Ok(notice) = serde_json::to_string(&notice) else {
+                        break;
+                    };
+                    if send(&mut socket, notice).await.is_err() {
+                        break;
+                    }
+                    dropped = 0;
+                }
+                // Relayed as published, with any fields this build does not know
+                if send(&mut socket, String::from_utf8_lossy(&message.payload).into_owned()).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Dashboards only listen; anything they send is ignored
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, text: String) -> Result<()> {
+    socket
+        .send(Message::Text(text.into()))
+        .await
+        .map_err(|e| AgentError::ServiceUnavailable(format!("WebSocket send failed: {}", e)))
+}
+
+/// Whether `event_type` matches one of `patterns`, a trailing `*` matching
+/// any rest; no patterns match everything
+fn matches(patterns: &[String], event_type: &str) -> bool {
+    patterns.is_empty()
+        || patterns.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => event_type.starts_with(prefix),
+            None => pattern == event_type,
+        })
+}
+
+/// At most `limit` events in each second; no limit when it is zero
+#[derive(Debug)]
+struct Throttle {
+    limit: u32,
+    window_start: Option<Instant>,
+    sent: u32,
+}
+
+impl Throttle {
+    fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            window_start: None,
+            sent: 0,
+        }
+    }
+
+    /// Count an event at `now`, saying whether it may be sent
+    fn allow(&mut self, now: Instant) -> bool {
+        if self.limit == 0 {
+            return true;
+        }
+        if !self.window_start.is_some_and(|start| now.duration_since(start) < Duration::from_secs(1)) {
+            self.window_start = Some(now);
+            self.sent = 0;
+        }
+        if self.sent >= self.limit {
+            return false;
+        }
+        self.sent += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_event_types() {
+        let patterns = vec!["dialog_*".to_string(), "workflow_completed".to_string()];
+        assert!(matches(&patterns, "dialog_started"));
+        assert!(matches(&patterns, "workflow_completed"));
+        assert!(!matches(&patterns, "workflow_started"));
+        assert!(matches(&[], "model_switched"));
+    }
+
+    #[tokio::test]
+    async fn test_access_token_as_bearer() {
+        let auth = ApiAuth::new(&ApiAuthConfig {
+            api_keys: vec![crate::config::ApiKeyConfig {
+                name: "dashboard".to_string(),
+                key: "read-key".to_string(),
+                scopes: vec![ApiScope::Read],
+            }],
+            oidc: None,
+        })
+        .unwrap();
+
+        let mut headers = HeaderMap::new();
+        insert_access_token(&mut headers, "read-key");
+        assert_eq!(headers["authorization"], "Bearer read-key");
+        assert!(!headers.contains_key("x-api-key"));
+        assert_eq!(auth.authenticate(&headers).await.map(|caller| caller.name), Some("dashboard".to_string()));
+
+        insert_access_token(&mut headers, "wrong-key");
+        assert_eq!(auth.authenticate(&headers).await, None);
+    }
+
+    #[test]
+    fn test_throttle_per_second() {
+        let start = Instant::now();
+        let mut throttle = Throttle::new(2);
+        assert!(throttle.allow(start));
+        assert!(throttle.allow(start + Duration::from_millis(100)));
+        assert!(!throttle.allow(start + Duration::from_millis(900)));
+        assert!(throttle.allow(start + Duration::from_millis(1000)));
+
+        let mut unlimited = Throttle::new(0);
+        assert!((0..100).all(|_| unlimited.allow(start)));
+    }
+}
@@ -0,0 +1,376 @@
+//! The catalog of agent events
+//!
+//! Every event the agent publishes, on `<subject_prefix>.events.<event_type>`
+//! and to webhooks and `/ws/events`, is an [`AgentEvent`] of one of these
+//! kinds, so emitters and Rust subscribers agree on each event's fields at
+//! compile time. On the wire the kind is the `event_type` tag beside a
+//! `payload` object. Events whose type is only known at runtime, such as
+//! `<command>_completed` or those posted by scheduled tasks, are
+//! [`AgentEventKind::Raw`], as is any event read back that no variant
+//! describes. `get_capabilities` lists the typed event types as `events`.
+//!
+//! [`AgentEvent`]: crate::nats_integration::AgentEvent
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::knowledge::GraphOperation;
+use crate::plan::PlannedStep;
+use crate::quiz::Difficulty;
+use crate::sources::refresh::RefreshStats;
+
+/// Types of the typed events, for `get_capabilities`
+pub const EVENT_TYPES: &[&str] = &[
+    "announcement",
+    "budget_exceeded",
+    "dialog_compacted",
+    "dialog_ended",
+    "dialog_expired",
+    "dialog_follow_up",
+    "dialog_imported",
+    "dialog_truncated",
+    "events_dropped",
+    "graph_concept_added",
+    "graph_concept_removed",
+    "graph_edit_proposed",
+    "graph_relation_added",
+    "graph_relation_removed",
+    "knowledge.refreshed",
+    "knowledge_updated",
+    "model_recovered",
+    "model_unavailable",
+    "prompt_injection_detected",
+    "question_delegated",
+    "quiz_answered",
+    "startup_progress",
+    "workflow_aborted",
+    "workflow_completed",
+    "workflow_created_from_dialog",
+    "workflow_reminder",
+    "workflow_step_completed",
+    "workflow_step_started",
+    "workflow_timed_out",
+];
+
+/// What happened, with the fields of its payload
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event_type", content = "payload", rename_all = "snake_case")]
+pub enum AgentEventKind {
+    /// A message was added to every active dialog
+    Announcement {
+        message: String,
+        dialogs: Vec<String>,
+        announced_at: DateTime<Utc>,
+    },
+
+    /// A dialog or user went over its token budget
+    BudgetExceeded {
+        dialog_id: String,
+        user: Option<String>,
+
+        /// `dialog` or `user`
+        scope: String,
+        used: usize,
+        limit: usize,
+
+        /// `downgrade` to the fallback model, or `refuse`
+        action: String,
+    },
+
+    DialogCompacted {
+        dialog_id: String,
+        compacted_turns: usize,
+        kept_turns: usize,
+    },
+
+    DialogEnded {
+        dialog_id: String,
+        turn_count: usize,
+        last_activity: Option<DateTime<Utc>>,
+    },
+
+    /// A dialog was idle past `session_timeout`
+    DialogExpired {
+        dialog_id: String,
+        status: String,
+        turn_count: usize,
+        last_activity: Option<DateTime<Utc>>,
+        idle_seconds: i64,
+    },
+
+    /// The full reply to a message answered partially at its soft deadline
+    DialogFollowUp {
+        dialog_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<serde_json::Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+
+    /// A chat exported from another assistant became a dialog
+    DialogImported {
+        dialog_id: String,
+        format: String,
+        title: Option<String>,
+        turns: usize,
+        skipped: usize,
+    },
+
+    /// Old turns were evicted beyond `max_history`
+    DialogTruncated {
+        dialog_id: String,
+        evicted_turns: usize,
+        kept_turns: usize,
+    },
+
+    /// Events a `/ws/events` connection missed over its rate limit
+    EventsDropped { count: u64 },
+
+    GraphConceptAdded(GraphEdit),
+    GraphConceptRemoved(GraphEdit),
+    GraphRelationAdded(GraphEdit),
+    GraphRelationRemoved(GraphEdit),
+
+    /// The model turned a request into graph changes awaiting confirmation
+    GraphEditProposed {
+        proposal_id: String,
+        dialog_id: Option<String>,
+        summary: String,
+        operations: Vec<GraphOperation>,
+    },
+
+    /// The git sources were pulled and reindexed
+    #[serde(rename = "knowledge.refreshed")]
+    KnowledgeRefreshed(RefreshStats),
+
+    /// Documents were embedded for retrieval, or a repository reindexed
+    KnowledgeUpdated {
+        /// `retrieval` or `git`
+        source: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        embedded: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        chunks: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        repo: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        files: Option<usize>,
+    },
+
+    /// The model provider answers again after being unreachable
+    ModelRecovered {},
+
+    ModelUnavailable { reason: Option<String> },
+
+    /// Retrieved content looked like an attempt to steer the model
+    PromptInjectionDetected {
+        source: String,
+        action: String,
+        findings: serde_json::Value,
+    },
+
+    /// A question was answered by a peer agent
+    QuestionDelegated { dialog_id: String, agent_id: String },
+
+    QuizAnswered {
+        quiz_id: String,
+        user_id: String,
+        concept: String,
+        difficulty: Difficulty,
+        correct: bool,
+    },
+
+    /// The agent is waiting for a dependency before it serves
+    StartupProgress {
+        dependency: String,
+        attempt: u32,
+        max_attempts: u32,
+        ready: bool,
+        reason: Option<String>,
+    },
+
+    WorkflowAborted {
+        workflow_id: String,
+        step: Option<String>,
+        reason: Option<String>,
+    },
+
+    WorkflowCompleted { workflow_id: String, last_step: String },
+
+    WorkflowCreatedFromDialog {
+        workflow_id: String,
+        workflow_type: String,
+        dialog_id: String,
+        name: String,
+        description: String,
+        status: String,
+        steps: Vec<PlannedStep>,
+        first_step: PlannedStep,
+    },
+
+    /// A scheduled nudge about a stalled workflow
+    WorkflowReminder {
+        workflow_id: String,
+        name: String,
+        owner: Option<String>,
+        current_step: Option<String>,
+        idle_seconds: i64,
+        task: String,
+        message: String,
+    },
+
+    WorkflowStepCompleted {
+        workflow_id: String,
+        step: String,
+        next_step: Option<String>,
+    },
+
+    WorkflowStepStarted {
+        workflow_id: String,
+        step: String,
+        instructions: serde_json::Value,
+    },
+
+    /// A workflow was not advanced within its `timeout`
+    WorkflowTimedOut {
+        workflow_id: String,
+        name: String,
+        owner: Option<String>,
+        step: Option<String>,
+        idle_seconds: i64,
+    },
+
+    /// An event no variant describes
+    #[serde(skip)]
+    Raw {
+        event_type: String,
+        payload: serde_json::Value,
+    },
+}
+
+/// A change applied to the concept graph, and the proposal it came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphEdit {
+    pub operation: GraphOperation,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proposal_id: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dialog_id: Option<String>,
+}
+
+impl AgentEventKind {
+    /// The event a graph `operation` was applied with
+    pub fn graph_edit(edit: GraphEdit) -> Self {
+        match edit.operation {
+            GraphOperation::AddConcept { .. } => Self::GraphConceptAdded(edit),
+            GraphOperation::RemoveConcept { .. } => Self::GraphConceptRemoved(edit),
+            GraphOperation::AddRelation { .. } => Self::GraphRelationAdded(edit),
+            GraphOperation::RemoveRelation { .. } => Self::GraphRelationRemoved(edit),
+        }
+    }
+
+    /// The kind of an event read as its type and payload: the typed
+    /// variant if one describes them, otherwise [`AgentEventKind::Raw`]
+    pub fn from_parts(event_type: String, payload: serde_json::Value) -> Self {
+        let parts = serde_json::json!({ "event_type": event_type, "payload": payload });
+        serde_json::from_value(parts).unwrap_or(Self::Raw { event_type, payload })
+    }
+
+    /// The `event_type` tag, also the last token of the event's subject
+    pub fn event_type(&self) -> &str {
+        match self {
+            Self::Announcement { .. } => "announcement",
+            Self::BudgetExceeded { .. } => "budget_exceeded",
+            Self::DialogCompacted { .. } => "dialog_compacted",
+            Self::DialogEnded { .. } => "dialog_ended",
+            Self::DialogExpired { .. } => "dialog_expired",
+            Self::DialogFollowUp { .. } => "dialog_follow_up",
+            Self::DialogImported { .. } => "dialog_imported",
+            Self::DialogTruncated { .. } => "dialog_truncated",
+            Self::EventsDropped { .. } => "events_dropped",
+            Self::GraphConceptAdded(_) => "graph_concept_added",
+            Self::GraphConceptRemoved(_) => "graph_concept_removed",
+            Self::GraphRelationAdded(_) => "graph_relation_added",
+            Self::GraphRelationRemoved(_) => "graph_relation_removed",
+            Self::GraphEditProposed { .. } => "graph_edit_proposed",
+            Self::KnowledgeRefreshed(_) => "knowledge.refreshed",
+            Self::KnowledgeUpdated { .. } => "knowledge_updated",
+            Self::ModelRecovered {} => "model_recovered",
+            Self::ModelUnavailable { .. } => "model_unavailable",
+            Self::PromptInjectionDetected { .. } => "prompt_injection_detected",
+            Self::QuestionDelegated { .. } => "question_delegated",
+            Self::QuizAnswered { .. } => "quiz_answered",
+            Self::StartupProgress { .. } => "startup_progress",
+            Self::WorkflowAborted { .. } => "workflow_aborted",
+            Self::WorkflowCompleted { .. } => "workflow_completed",
+            Self::WorkflowCreatedFromDialog { .. } => "workflow_created_from_dialog",
+            Self::WorkflowReminder { .. } => "workflow_reminder",
+            Self::WorkflowStepCompleted { .. } => "workflow_step_completed",
+            Self::WorkflowStepStarted { .. } => "workflow_step_started",
+            Self::WorkflowTimedOut { .. } => "workflow_timed_out",
+            Self::Raw { event_type, .. } => event_type,
+        }
+    }
+
+    /// The payload object sent beside the type
+    pub fn payload(&self) -> serde_json::Value {
+        match self {
+            Self::Raw { payload, .. } => payload.clone(),
+            typed => serde_json::to_value(typed)
+                .map(|mut parts| parts["payload"].take())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_typed_events_keep_their_wire_shape() {
+        let kinds = vec![
+            AgentEventKind::WorkflowCompleted {
+                workflow_id: "wf-1".to_string(),
+                last_step: "test".to_string(),
+            },
+            AgentEventKind::ModelRecovered {},
+            AgentEventKind::KnowledgeRefreshed(RefreshStats::default()),
+            AgentEventKind::graph_edit(GraphEdit {
+                operation: GraphOperation::RemoveConcept { name: "Saga".to_string() },
+                proposal_id: None,
+                dialog_id: None,
+            }),
+        ];
+        for kind in kinds {
+            let parts = serde_json::to_value(&kind).unwrap();
+            assert_eq!(parts["event_type"], kind.event_type());
+            assert!(EVENT_TYPES.contains(&kind.event_type()));
+            assert!(kind.payload().is_object());
+            assert_eq!(AgentEventKind::from_parts(kind.event_type().to_string(), kind.payload()), kind);
+        }
+
+        let completed = AgentEventKind::WorkflowCompleted {
+            workflow_id: "wf-1".to_string(),
+            last_step: "test".to_string(),
+        };
+        assert_eq!(completed.payload(), json!({ "workflow_id": "wf-1", "last_step": "test" }));
+    }
+
+    #[test]
+    fn test_untyped_events_are_raw() {
+        let completed = AgentEventKind::from_parts("explain_concept_completed".to_string(), json!({ "concept": "Aggregate" }));
+        assert_eq!(completed.event_type(), "explain_concept_completed");
+        assert_eq!(completed.payload()["concept"], "Aggregate");
+
+        // A typed event missing its fields is kept as it came
+        let partial = AgentEventKind::from_parts("workflow_completed".to_string(), json!({ "workflow_id": "wf-1" }));
+        assert!(matches!(partial, AgentEventKind::Raw { .. }));
+        assert_eq!(partial.event_type(), "workflow_completed");
+    }
+}
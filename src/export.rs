@@ -0,0 +1,375 @@
+//! Dialog export formats
+//!
+//! Renders the dialog history returned by the `get_dialog_history` query
+//! into archive-friendly documents. HTML reports are self-contained, with
+//! styles inline and architecture visualizations drawn as SVG, so they can be
+//! shared with people outside NATS and chat. PDF reports are the HTML report
+//! printed by `wkhtmltopdf`, which must be installed.
+
+use crate::error::{AgentError, Result};
+use std::fmt::Write;
+use std::process::Stdio;
+use std::str::FromStr;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Supported export formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Markdown transcript
+    Markdown,
+
+    /// Pretty-printed JSON
+    Json,
+
+    /// Styled, self-contained HTML report
+    Html,
+
+    /// The HTML report as PDF
+    Pdf,
+}
+
+impl FromStr for ExportFormat {
+    type Err = AgentError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            "html" => Ok(Self::Html),
+            "pdf" => Ok(Self::Pdf),
+            other => Err(AgentError::InvalidRequest(format!(
+                "Unknown export format: {} (expected md, json, html, or pdf)",
+                other
+            ))),
+        }
+    }
+}
+
+impl ExportFormat {
+    /// File extension for documents in this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Html => "html",
+            Self::Pdf => "pdf",
+        }
+    }
+}
+
+/// Render a dialog history in one of the text formats
+pub fn render_dialog(history: &serde_json::Value, format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(history)?),
+        ExportFormat::Markdown => Ok(render_markdown(history)),
+        ExportFormat::Html => Ok(render_html(history)),
+        ExportFormat::Pdf => Err(AgentError::InvalidRequest(
+            "PDF is not a text format; use export_dialog".to_string(),
+        )),
+    }
+}
+
+/// Render a dialog history in any format, as the bytes of the document
+pub async fn export_dialog(history: &serde_json::Value, format: ExportFormat) -> Result<Vec<u8>> {
+    match format {
+        ExportFormat::Pdf => html_to_pdf(&render_html(history)).await,
+        format => Ok(render_dialog(history, format)?.into_bytes()),
+    }
+}
+
+/// Human-readable speaker label for a turn type
+pub fn speaker(turn_type: &str) -> &'static str {
+    match turn_type {
+        "UserQuery" => "User",
+        "AgentResponse" => "Alchemist",
+        "SystemMessage" => "System",
+        _ => "Participant",
+    }
+}
+
+fn render_markdown(history: &serde_json::Value) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# Dialog {}", history["dialog_id"].as_str().unwrap_or("unknown"));
+    let _ = writeln!(out);
+    let _ = writeln!(out, "- Status: {}", history["status"].as_str().unwrap_or("unknown"));
+    let _ = writeln!(out, "- Turns: {}", history["turn_count"].as_u64().unwrap_or(0));
+
+    for turn in history["history"].as_array().into_iter().flatten() {
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "## {} — {}",
+            speaker(turn["turn_type"].as_str().unwrap_or_default()),
+            turn["timestamp"].as_str().unwrap_or_default()
+        );
+        let _ = writeln!(out);
+        let _ = writeln!(out, "{}", turn["content"].as_str().unwrap_or_default());
+    }
+
+    out
+}
+
+/// Styles for HTML reports, inlined so the file stands alone
+const REPORT_STYLE: &str = "
+body { font-family: system-ui, sans-serif; max-width: 860px; margin: 2rem auto; padding: 0 1rem; color: #1f2933; }
+h1 { border-bottom: 2px solid #3e4c59; padding-bottom: .5rem; }
+.summary { color: #52606d; }
+.turn { border-left: 4px solid #9aa5b1; margin: 1.5rem 0; padding: .25rem 1rem; }
+.turn.user { border-color: #2186eb; }
+.turn.agent { border-color: #27ab83; background: #f5fbf9; }
+.turn.system { border-color: #cbd2d9; color: #52606d; font-style: italic; }
+.turn header { font-weight: 600; margin: .5rem 0; }
+.turn header time { font-weight: normal; color: #7b8794; margin-left: .5rem; }
+pre { background: #1f2933; color: #f5f7fa; padding: .75rem; overflow-x: auto; border-radius: 4px; }
+svg { max-width: 100%; height: auto; }
+";
+
+fn render_html(history: &serde_json::Value) -> String {
+    let dialog_id = escape_html(history["dialog_id"].as_str().unwrap_or("unknown"));
+    let mut out = String::new();
+
+    let _ = writeln!(out, "<!DOCTYPE html>");
+    let _ = writeln!(out, "<html lang=\"en\">");
+    let _ = writeln!(out, "<head>");
+    let _ = writeln!(out, "<meta charset=\"utf-8\">");
+    let _ = writeln!(out, "<title>Dialog {}</title>", dialog_id);
+    let _ = writeln!(out, "<style>{}</style>", REPORT_STYLE);
+    let _ = writeln!(out, "</head>");
+    let _ = writeln!(out, "<body>");
+    let _ = writeln!(out, "<h1>Dialog {}</h1>", dialog_id);
+    let _ = writeln!(
+        out,
+        "<p class=\"summary\">Status: {} · Turns: {} · Exported {}</p>",
+        escape_html(history["status"].as_str().unwrap_or("unknown")),
+        history["turn_count"].as_u64().unwrap_or(0),
+        chrono::Utc::now().format("%Y-%m-%d %H:%M UTC")
+    );
+
+    for turn in history["history"].as_array().into_iter().flatten() {
+        let turn_type = turn["turn_type"].as_str().unwrap_or_default();
+        let class = match turn_type {
+            "UserQuery" => "user",
+            "AgentResponse" => "agent",
+            "SystemMessage" => "system",
+            _ => "participant",
+        };
+
+        let _ = writeln!(out, "<section class=\"turn {}\">", class);
+        let _ = writeln!(
+            out,
+            "<header>{}<time>{}</time></header>",
+            speaker(turn_type),
+            escape_html(turn["timestamp"].as_str().unwrap_or_default())
+        );
+        out.push_str(&render_content(turn["content"].as_str().unwrap_or_default()));
+        let _ = writeln!(out, "</section>");
+    }
+
+    let _ = writeln!(out, "</body>");
+    let _ = writeln!(out, "</html>");
+    out
+}
+
+/// Turn content as HTML: visualizations as SVG, code fences as `<pre>`,
+/// everything else as paragraphs
+fn render_content(content: &str) -> String {
+    if let Some(graph) = serde_json::from_str::<serde_json::Value>(content)
+        .ok()
+        .as_ref()
+        .and_then(visualization)
+    {
+        return render_graph_svg(graph);
+    }
+
+    let mut out = String::new();
+    for (index, block) in content.split("```").enumerate() {
+        if index % 2 == 1 {
+            // Drop the language tag after the opening fence
+            let code = block.split_once('\n').map(|(_, code)| code).unwrap_or(block);
+            let _ = writeln!(out, "<pre><code>{}</code></pre>", escape_html(code.trim_end()));
+            continue;
+        }
+
+        for paragraph in block.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+            let _ = writeln!(out, "<p>{}</p>", escape_html(paragraph).replace('\n', "<br>"));
+        }
+    }
+    out
+}
+
+/// The node and edge graph in a `visualize_architecture` result, if any
+fn visualization(value: &serde_json::Value) -> Option<&serde_json::Value> {
+    let graph = if value["visualization"].is_object() { &value["visualization"] } else { value };
+    (graph["nodes"].is_array() && graph["edges"].is_array()).then_some(graph)
+}
+
+/// Draw a node and edge graph with the nodes on a circle
+fn render_graph_svg(graph: &serde_json::Value) -> String {
+    const WIDTH: f64 = 640.0;
+    const HEIGHT: f64 = 420.0;
+    const RADIUS: f64 = 150.0;
+
+    let nodes = graph["nodes"].as_array().cloned().unwrap_or_default();
+    let positions: Vec<(String, f64, f64)> = nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| {
+            let angle = std::f64::consts::TAU * index as f64 / nodes.len() as f64 - std::f64::consts::FRAC_PI_2;
+            (
+                node["id"].as_str().unwrap_or_default().to_string(),
+                WIDTH / 2.0 + RADIUS * angle.cos(),
+                HEIGHT / 2.0 + RADIUS * angle.sin(),
+            )
+        })
+        .collect();
+    let position = |id: &str| positions.iter().find(|(node, _, _)| node == id).map(|(_, x, y)| (*x, *y));
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" font-size=\"12\" text-anchor=\"middle\">",
+        WIDTH, HEIGHT
+    );
+
+    for edge in graph["edges"].as_array().into_iter().flatten() {
+        let (Some((x1, y1)), Some((x2, y2))) = (
+            position(edge["source"].as_str().unwrap_or_default()),
+            position(edge["target"].as_str().unwrap_or_default()),
+        ) else {
+            continue;
+        };
+        let _ = writeln!(
+            out,
+            "<line x1=\"{:.0}\" y1=\"{:.0}\" x2=\"{:.0}\" y2=\"{:.0}\" stroke=\"#9aa5b1\"/>",
+            x1, y1, x2, y2
+        );
+        let _ = writeln!(
+            out,
+            "<text x=\"{:.0}\" y=\"{:.0}\" fill=\"#52606d\">{}</text>",
+            (x1 + x2) / 2.0,
+            (y1 + y2) / 2.0 - 4.0,
+            escape_html(edge["label"].as_str().unwrap_or_default())
+        );
+    }
+
+    for (node, (_, x, y)) in nodes.iter().zip(&positions) {
+        let label = node["label"].as_str().or(node["id"].as_str()).unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "<rect x=\"{:.0}\" y=\"{:.0}\" width=\"130\" height=\"32\" rx=\"6\" fill=\"#e6f6ff\" stroke=\"#2186eb\"/>",
+            x - 65.0,
+            y - 16.0
+        );
+        let _ = writeln!(out, "<text x=\"{:.0}\" y=\"{:.0}\">{}</text>", x, y + 4.0, escape_html(label));
+    }
+
+    let _ = writeln!(out, "</svg>");
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Print an HTML report to PDF with `wkhtmltopdf`
+async fn html_to_pdf(html: &str) -> Result<Vec<u8>> {
+    let mut child = Command::new("wkhtmltopdf")
+        .args(["--quiet", "-", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| AgentError::ServiceUnavailable(format!("Could not run wkhtmltopdf: {}", e)))?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| AgentError::Internal("wkhtmltopdf stdin unavailable".to_string()))?;
+    stdin.write_all(html.as_bytes()).await?;
+    drop(stdin);
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(AgentError::Internal(format!(
+            "wkhtmltopdf failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("md".parse::<ExportFormat>().unwrap(), ExportFormat::Markdown);
+        assert_eq!("JSON".parse::<ExportFormat>().unwrap(), ExportFormat::Json);
+        assert_eq!("html".parse::<ExportFormat>().unwrap(), ExportFormat::Html);
+        assert!("docx".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let history = json!({
+            "dialog_id": "dlg-1",
+            "status": "Active",
+            "turn_count": 2,
+            "history": [
+                {"turn_type": "UserQuery", "content": "What is CQRS?", "timestamp": "2024-01-15T10:00:00Z"},
+                {"turn_type": "AgentResponse", "content": "Command Query Responsibility Segregation.", "timestamp": "2024-01-15T10:00:02Z"},
+            ],
+        });
+
+        let md = render_dialog(&history, ExportFormat::Markdown).unwrap();
+        assert!(md.starts_with("# Dialog dlg-1"));
+        assert!(md.contains("## User — 2024-01-15T10:00:00Z"));
+        assert!(md.contains("## Alchemist — 2024-01-15T10:00:02Z"));
+        assert!(md.contains("Command Query Responsibility Segregation."));
+    }
+
+    #[test]
+    fn test_render_html_report() {
+        let visualization = json!({
+            "scope": "overview",
+            "visualization": {
+                "nodes": [{"id": "domains", "label": "CIM Domains"}, {"id": "bridge", "label": "Bridge <Layer>"}],
+                "edges": [{"source": "bridge", "target": "domains", "label": "connects"}],
+            },
+        });
+        let history = json!({
+            "dialog_id": "dlg-1",
+            "status": "Active",
+            "turn_count": 2,
+            "history": [
+                {"turn_type": "UserQuery", "content": "Show me <the> layers", "timestamp": "2024-01-15T10:00:00Z"},
+                {"turn_type": "AgentResponse", "content": visualization.to_string(), "timestamp": "2024-01-15T10:00:02Z"},
+            ],
+        });
+
+        let html = render_dialog(&history, ExportFormat::Html).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<style>"));
+        assert!(html.contains("Show me &lt;the&gt; layers"));
+        assert!(html.contains("<svg"));
+        assert!(html.contains("Bridge &lt;Layer&gt;"));
+    }
+
+    #[test]
+    fn test_render_code_fences() {
+        let html = render_content("Define it:\n\n```rust\nstruct Order;\n```\n\nDone.");
+
+        assert!(html.contains("<p>Define it:</p>"));
+        assert!(html.contains("<pre><code>struct Order;</code></pre>"));
+        assert!(html.contains("<p>Done.</p>"));
+    }
+}
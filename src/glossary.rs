@@ -0,0 +1,124 @@
+//! Glossaries of the knowledge graph for project docs
+//!
+//! Every concept gets a short definition, its own description when the
+//! graph has one and otherwise one the model writes, and cross-references
+//! to the concepts it relates to. The glossary renders as Markdown, with
+//! cross-references linking to the entries' headings, or as JSON.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::error::{AgentError, Result};
+
+/// One term of a glossary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GlossaryEntry {
+    pub term: String,
+    pub definition: String,
+
+    /// Related terms, in the glossary's order
+    pub see_also: Vec<String>,
+}
+
+/// Prompt asking the model to define `terms` in a sentence or two each
+pub fn definitions_prompt(terms: &[String]) -> String {
+    format!(
+        "Define each of these Composable Information Machine (CIM) concepts for a project glossary, in one or two \
+         plain sentences each: {}.\n\n\
+         Reply with JSON only, an object mapping each concept, spelled exactly as given, to its definition.",
+        terms.join(", ")
+    )
+}
+
+/// Read the definitions out of a model reply, ignoring any prose or code
+/// fence around the JSON
+pub fn parse_definitions(reply: &str) -> Result<HashMap<String, String>> {
+    let json = reply
+        .find('{')
+        .zip(reply.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| &reply[start..=end])
+        .ok_or_else(|| AgentError::ModelError("The model wrote no definitions".to_string()))?;
+
+    let definitions: HashMap<String, String> = serde_json::from_str(json)
+        .map_err(|e| AgentError::ModelError(format!("The model wrote malformed definitions: {}", e)))?;
+    Ok(definitions
+        .into_iter()
+        .map(|(term, definition)| (term.to_lowercase(), definition.trim().to_string()))
+        .collect())
+}
+
+/// Render entries as a Markdown document
+pub fn render_markdown(entries: &[GlossaryEntry]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# CIM Glossary");
+
+    for entry in entries {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "## {}", entry.term);
+        let _ = writeln!(out);
+        let _ = writeln!(out, "{}", entry.definition);
+
+        if !entry.see_also.is_empty() {
+            let links: Vec<String> = entry
+                .see_also
+                .iter()
+                .map(|term| format!("[{}](#{})", term, anchor(term)))
+                .collect();
+            let _ = writeln!(out);
+            let _ = writeln!(out, "See also: {}", links.join(", "));
+        }
+    }
+
+    out
+}
+
+/// GitHub-style anchor of a heading
+fn anchor(heading: &str) -> String {
+    heading
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_definitions_from_fenced_reply() {
+        let reply = "```json\n{\"CQRS\": \" Separates writes from reads. \", \"Saga\": \"Coordinates aggregates.\"}\n```";
+        let definitions = parse_definitions(reply).unwrap();
+
+        assert_eq!(definitions["cqrs"], "Separates writes from reads.");
+        assert_eq!(definitions["saga"], "Coordinates aggregates.");
+        assert!(parse_definitions("I don't know these").is_err());
+    }
+
+    #[test]
+    fn test_render_markdown_links_cross_references() {
+        let entries = vec![
+            GlossaryEntry {
+                term: "Domain Event".to_string(),
+                definition: "A fact that happened in a domain.".to_string(),
+                see_also: vec!["Event Sourcing".to_string()],
+            },
+            GlossaryEntry {
+                term: "Event Sourcing".to_string(),
+                definition: "State stored as a sequence of events.".to_string(),
+                see_also: Vec::new(),
+            },
+        ];
+
+        let markdown = render_markdown(&entries);
+        assert!(markdown.starts_with("# CIM Glossary\n\n## Domain Event\n\nA fact that happened in a domain.\n"));
+        assert!(markdown.contains("See also: [Event Sourcing](#event-sourcing)\n"));
+        assert!(!markdown.contains("## Event Sourcing\n\nState stored as a sequence of events.\n\nSee also"));
+    }
+}
@@ -0,0 +1,140 @@
+//! HTTP endpoints served next to NATS
+//!
+//! Most traffic reaches the agent over NATS; HTTP is only for callers that
+//! cannot speak it, such as webhooks and the JSON API, and for orchestrator
+//! health probes. The server listens on `service.bind_address:service.port`
+//! when at least one endpoint is configured.
+//!
+//! `/healthz` answers as long as the process serves requests. `/readyz`
+//! answers 200 with the agent's health once NATS is connected and the model
+//! provider passed its last check, and 503 otherwise. Either way the body
+//! says which subsystems are degraded and why.
+//!
+//! With `service.event_stream` configured, `/ws/events` relays agent events
+//! to browsers over a WebSocket; see [`crate::event_stream`].
+//!
+//! With `service.metrics.enabled`, `service.metrics.endpoint` serves the
+//! retrieval metrics in the Prometheus text format whenever another
+//! endpoint has the server running.
+
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::routing::get;
+use axum::{Json, Router};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use crate::agent::AlchemistAgent;
+use crate::config::{AgentConfig, GitHubConfig};
+use crate::error::{AgentError, Result};
+use crate::nats_integration::{connection_health, jetstream_health, HealthResponse};
+
+/// Routes for the configured endpoints, or `None` if there are none
+pub fn routes(config: &AgentConfig, agent: Arc<AlchemistAgent>, nats: async_nats::Client) -> Result<Option<Router>> {
+    let mut router: Option<Router> = None;
+
+    if config.service.health_endpoints {
+        router = Some(router.unwrap_or_default().merge(health_routes(agent.clone(), nats.clone())));
+    }
+
+    if config.service.http_api {
+        let api = crate::api::router(agent.clone(), config.service.api_auth.as_ref())?;
+        if config.service.api_auth.is_none() {
+            warn!("The HTTP API is enabled without `service.api_auth`; anyone who can reach it can run commands");
+        }
+        router = Some(router.unwrap_or_default().merge(api));
+    }
+
+    if let Some(event_stream) = &config.service.event_stream {
+        let auth = config.service.api_auth.as_ref().ok_or_else(|| {
+            AgentError::Configuration("service.event_stream needs service.api_auth to protect /ws/events".to_string())
+        })?;
+        let events = crate::event_stream::router(event_stream, auth, &config.nats, nats)?;
+        router = Some(router.unwrap_or_default().merge(events));
+    }
+
+    if let Some(github) = &config.integrations.github {
+        router = Some(router.unwrap_or_default().merge(github_routes(github, agent.clone())?));
+    }
+
+    // Metrics alone do not start a server
+    if config.service.metrics.enabled {
+        router = router.map(|router| router.merge(metrics_routes(&config.service.metrics.endpoint, agent)));
+    }
+
+    Ok(router)
+}
+
+fn metrics_routes(endpoint: &str, agent: Arc<AlchemistAgent>) -> Router {
+    Router::new()
+        .route(endpoint, get(metrics))
+        .with_state(agent)
+}
+
+async fn metrics(State(agent): State<Arc<AlchemistAgent>>) -> ([(header::HeaderName, &'static str); 1], String) {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        format!("{}{}", agent.retrieval_metrics().prometheus(), agent.dialog_metrics().prometheus()),
+    )
+}
+
+fn health_routes(agent: Arc<AlchemistAgent>, nats: async_nats::Client) -> Router {
+    Router::new()
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/readyz", get(ready))
+        .with_state((agent, nats))
+}
+
+async fn ready(
+    State((agent, nats)): State<(Arc<AlchemistAgent>, async_nats::Client)>,
+) -> (StatusCode, Json<HealthResponse>) {
+    let mut health = agent.health().await;
+    health.add_subsystem("nats", connection_health(&nats));
+    health.add_subsystem(
+        "jetstream",
+        jetstream_health(&nats, agent.config().nats.jetstream.as_ref()).await,
+    );
+
+    let connected = nats.connection_state() == async_nats::connection::State::Connected;
+    if !connected {
+        health.status = "Disconnected".to_string();
+    }
+
+    let code = if connected && agent.model_healthy() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(health))
+}
+
+#[cfg(feature = "github")]
+fn github_routes(config: &GitHubConfig, agent: Arc<AlchemistAgent>) -> Result<Router> {
+    Ok(Router::new().nest(
+        "/webhooks/github",
+        crate::integrations::github::router(config.clone(), agent),
+    ))
+}
+
+#[cfg(not(feature = "github"))]
+fn github_routes(_config: &GitHubConfig, _agent: Arc<AlchemistAgent>) -> Result<Router> {
+    Err(AgentError::Configuration(
+        "GitHub is configured but the agent was built without the `github` feature".to_string(),
+    ))
+}
+
+/// Bind the service address, failing early if it is taken
+pub async fn bind(config: &AgentConfig) -> Result<TcpListener> {
+    let address = format!("{}:{}", config.service.bind_address, config.service.port);
+    let listener = TcpListener::bind(&address).await?;
+    info!("HTTP endpoints listening on {}", address);
+    Ok(listener)
+}
+
+/// Serve `router` until the task is cancelled
+pub async fn serve(listener: TcpListener, router: Router) -> Result<()> {
+    axum::serve(listener, router)
+        .await
+        .map_err(|e| AgentError::ServiceUnavailable(format!("HTTP server failed: {}", e)))
+}
@@ -0,0 +1,316 @@
+//! Chat exports from other assistants, for `import_dialog`
+//!
+//! Conversations started elsewhere are read from the JSON their tools
+//! export, so they can be continued here with their history as context:
+//!
+//! - `openai`: a Chat Completions request, `{"messages": [...]}`, or just
+//!   its message array, contents being strings or lists of text parts
+//! - `chatgpt`: one conversation of ChatGPT's `conversations.json`, whose
+//!   `mapping` is followed from `current_node` back to the root
+//! - `anthropic`: a Messages API request, `{"system": ..., "messages": [...]}`
+//! - `claude`: a conversation exported from Claude.ai, with `chat_messages`
+//!   sent by `human` or `assistant`
+//!
+//! Only user and assistant text is kept. System prompts, tool calls and
+//! results, and images were written for the other assistant and are
+//! skipped, as are empty messages.
+
+use serde_json::Value;
+use std::str::FromStr;
+
+use crate::error::{AgentError, Result};
+use crate::model::Message;
+
+/// Supported chat export formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    OpenAi,
+    ChatGpt,
+    Anthropic,
+    Claude,
+}
+
+impl FromStr for ImportFormat {
+    type Err = AgentError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "openai" => Ok(Self::OpenAi),
+            "chatgpt" => Ok(Self::ChatGpt),
+            "anthropic" => Ok(Self::Anthropic),
+            "claude" => Ok(Self::Claude),
+            other => Err(AgentError::InvalidRequest(format!(
+                "Unknown import format: {} (expected openai, chatgpt, anthropic, or claude)",
+                other
+            ))),
+        }
+    }
+}
+
+impl ImportFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::OpenAi => "openai",
+            Self::ChatGpt => "chatgpt",
+            Self::Anthropic => "anthropic",
+            Self::Claude => "claude",
+        }
+    }
+
+    /// The format `export` looks like it is in
+    pub fn detect(export: &Value) -> Option<Self> {
+        let conversation = match export {
+            Value::Array(items) => match items.first() {
+                Some(first) if first.get("mapping").is_some() => first,
+                _ => return Some(Self::OpenAi),
+            },
+            conversation => conversation,
+        };
+
+        if conversation.get("mapping").is_some() {
+            Some(Self::ChatGpt)
+        } else if conversation.get("chat_messages").is_some() {
+            Some(Self::Claude)
+        } else if conversation.get("system").is_some() || messages_have_content_blocks(conversation) {
+            Some(Self::Anthropic)
+        } else if conversation.get("messages").is_some() {
+            Some(Self::OpenAi)
+        } else {
+            None
+        }
+    }
+}
+
+/// A conversation read from an export
+#[derive(Debug, Clone)]
+pub struct ImportedDialog {
+    pub format: ImportFormat,
+
+    /// Title the other assistant gave the conversation, if any
+    pub title: Option<String>,
+
+    /// User and assistant messages, oldest first
+    pub messages: Vec<Message>,
+
+    /// Messages left out: system prompts, tool traffic, and empty messages
+    pub skipped: usize,
+}
+
+/// Read the conversation in `export`, in `format` or the one detected
+pub fn parse(export: &Value, format: Option<ImportFormat>) -> Result<ImportedDialog> {
+    let format = format.or_else(|| ImportFormat::detect(export)).ok_or_else(|| {
+        AgentError::InvalidRequest(
+            "Unrecognized chat export; give its format (openai, chatgpt, anthropic, or claude)".to_string(),
+        )
+    })?;
+
+    let mut imported = ImportedDialog {
+        format,
+        title: None,
+        messages: Vec::new(),
+        skipped: 0,
+    };
+    match format {
+        ImportFormat::OpenAi | ImportFormat::Anthropic => {
+            let messages = match export {
+                Value::Array(messages) => messages,
+                _ => array(export, "messages")?,
+            };
+            if export.get("system").is_some_and(|system| !text(system).is_empty()) {
+                imported.skipped += 1;
+            }
+            for message in messages {
+                imported.push(message["role"].as_str(), text(&message["content"]), None);
+            }
+        }
+        ImportFormat::ChatGpt => {
+            let conversation = single_conversation(export)?;
+            imported.title = conversation["title"].as_str().map(str::to_string);
+            for message in chatgpt_thread(conversation) {
+                let timestamp = message["create_time"]
+                    .as_f64()
+                    .and_then(|seconds| chrono::DateTime::from_timestamp_millis((seconds * 1000.0) as i64));
+                imported.push(message["author"]["role"].as_str(), text(&message["content"]["parts"]), timestamp);
+            }
+        }
+        ImportFormat::Claude => {
+            let conversation = single_conversation(export)?;
+            imported.title = conversation["name"].as_str().map(str::to_string);
+            for message in array(conversation, "chat_messages")? {
+                let content = match message["text"].as_str() {
+                    Some(content) if !content.trim().is_empty() => content.to_string(),
+                    _ => text(&message["content"]),
+                };
+                let timestamp = message["created_at"]
+                    .as_str()
+                    .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+                    .map(|at| at.with_timezone(&chrono::Utc));
+                imported.push(message["sender"].as_str(), content, timestamp);
+            }
+        }
+    }
+
+    if imported.messages.is_empty() {
+        return Err(AgentError::InvalidRequest(format!(
+            "The {} export has no user or assistant messages",
+            format.name()
+        )));
+    }
+    Ok(imported)
+}
+
+impl ImportedDialog {
+    /// Keep a message if it is a user's or assistant's and has text
+    fn push(&mut self, role: Option<&str>, content: String, timestamp: Option<chrono::DateTime<chrono::Utc>>) {
+        let role = match role {
+            Some("user" | "human") => "user",
+            Some("assistant") => "assistant",
+            _ => {
+                self.skipped += 1;
+                return;
+            }
+        };
+        if content.trim().is_empty() {
+            self.skipped += 1;
+            return;
+        }
+
+        self.messages.push(Message {
+            role: role.to_string(),
+            content,
+            timestamp: timestamp.unwrap_or_else(chrono::Utc::now),
+        });
+    }
+}
+
+/// The text of a message content: a string, or the text of a list of
+/// strings and `{"type": "text", "text": ...}` parts
+fn text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part {
+                Value::String(text) => Some(text.as_str()),
+                part if part["type"] == "text" => part["text"].as_str(),
+                _ => None,
+            })
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        _ => String::new(),
+    }
+}
+
+fn array<'a>(value: &'a Value, field: &str) -> Result<&'a Vec<Value>> {
+    value[field]
+        .as_array()
+        .ok_or_else(|| AgentError::InvalidRequest(format!("Chat export has no {} array", field)))
+}
+
+/// The conversation of an export holding one, alone or in a list
+fn single_conversation(export: &Value) -> Result<&Value> {
+    match export {
+        Value::Array(conversations) if conversations.len() == 1 => Ok(&conversations[0]),
+        Value::Array(conversations) => Err(AgentError::InvalidRequest(format!(
+            "Chat export holds {} conversations; import one at a time",
+            conversations.len()
+        ))),
+        conversation => Ok(conversation),
+    }
+}
+
+/// Messages of a ChatGPT conversation on the branch ending at
+/// `current_node`, oldest first
+fn chatgpt_thread(conversation: &Value) -> Vec<&Value> {
+    let mapping = &conversation["mapping"];
+    let mut thread = Vec::new();
+    let mut node = conversation["current_node"].as_str();
+    // Edited prompts branch the tree; bounding the walk guards against cycles
+    let mut remaining = mapping.as_object().map_or(0, |nodes| nodes.len());
+
+    while let Some(id) = node.filter(|_| remaining > 0) {
+        let entry = &mapping[id];
+        if !entry["message"].is_null() {
+            thread.push(&entry["message"]);
+        }
+        node = entry["parent"].as_str();
+        remaining -= 1;
+    }
+    thread.reverse();
+    thread
+}
+
+/// Whether any message has a list of typed content blocks, as Anthropic
+/// messages do
+fn messages_have_content_blocks(conversation: &Value) -> bool {
+    conversation["messages"].as_array().is_some_and(|messages| {
+        messages
+            .iter()
+            .any(|message| message["content"].as_array().is_some_and(|blocks| blocks.iter().any(|block| block["type"].is_string())))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_openai_and_anthropic() {
+        let export = json!({"messages": [
+            {"role": "system", "content": "You are helpful."},
+            {"role": "user", "content": "What is an aggregate?"},
+            {"role": "assistant", "content": "A consistency boundary."},
+            {"role": "tool", "content": "{}"},
+        ]});
+        let imported = parse(&export, None).unwrap();
+        assert_eq!(imported.format, ImportFormat::OpenAi);
+        assert_eq!(imported.messages.len(), 2);
+        assert_eq!(imported.messages[1].role, "assistant");
+        assert_eq!(imported.skipped, 2);
+
+        let export = json!({"system": "Be brief.", "messages": [
+            {"role": "user", "content": [{"type": "text", "text": "Explain NATS subjects"}]},
+            {"role": "assistant", "content": [{"type": "text", "text": "Dot-separated tokens."}, {"type": "tool_use", "id": "t1"}]},
+        ]});
+        let imported = parse(&export, None).unwrap();
+        assert_eq!(imported.format, ImportFormat::Anthropic);
+        assert_eq!(imported.messages[1].content, "Dot-separated tokens.");
+        assert_eq!(imported.skipped, 1);
+
+        assert!(parse(&json!({"messages": []}), None).is_err());
+        assert!(parse(&json!({"turns": []}), None).is_err());
+    }
+
+    #[test]
+    fn test_parse_chatgpt_and_claude() {
+        let export = json!([{
+            "title": "Event sourcing",
+            "current_node": "c",
+            "mapping": {
+                "root": {"message": null, "parent": null},
+                "a": {"parent": "root", "message": {"author": {"role": "user"}, "content": {"parts": ["Why events?"]}, "create_time": 1700000000.5}},
+                "b": {"parent": "a", "message": {"author": {"role": "assistant"}, "content": {"parts": ["An old answer"]}}},
+                "c": {"parent": "a", "message": {"author": {"role": "assistant"}, "content": {"parts": ["They are the source of truth."]}}},
+            },
+        }]);
+        let imported = parse(&export, None).unwrap();
+        assert_eq!(imported.format, ImportFormat::ChatGpt);
+        assert_eq!(imported.title.as_deref(), Some("Event sourcing"));
+        let contents: Vec<&str> = imported.messages.iter().map(|message| message.content.as_str()).collect();
+        assert_eq!(contents, vec!["Why events?", "They are the source of truth."]);
+        assert_eq!(imported.messages[0].timestamp.timestamp(), 1700000000);
+
+        let export = json!({"name": "CIM domains", "chat_messages": [
+            {"sender": "human", "text": "How big is a domain?", "created_at": "2024-05-01T10:00:00Z"},
+            {"sender": "assistant", "text": "", "content": [{"type": "text", "text": "One bounded context."}]},
+        ]});
+        let imported = parse(&export, None).unwrap();
+        assert_eq!(imported.format, ImportFormat::Claude);
+        assert_eq!(imported.messages[0].role, "user");
+        assert_eq!(imported.messages[1].content, "One bounded context.");
+
+        assert!(parse(&json!([export.clone(), export]), Some(ImportFormat::Claude)).is_err());
+    }
+}
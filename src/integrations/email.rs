@@ -0,0 +1,118 @@
+//! Activity digests sent by email
+//!
+//! Every `interval` the model summarizes the dialogs, concepts discussed, and
+//! workflow progress of that period, and the summary is mailed to the
+//! configured recipients over SMTP. Quiet periods send nothing.
+
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::agent::AlchemistAgent;
+use crate::config::EmailDigestConfig;
+use crate::error::{AgentError, Result};
+
+/// Sends the periodic digest
+pub struct EmailDigest {
+    config: EmailDigestConfig,
+    agent: Arc<AlchemistAgent>,
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    recipients: Vec<Mailbox>,
+}
+
+impl EmailDigest {
+    /// Fails if an address is malformed or there are no recipients
+    pub fn new(config: EmailDigestConfig, agent: Arc<AlchemistAgent>) -> Result<Self> {
+        let from = mailbox(&config.from)?;
+        let recipients = config
+            .recipients
+            .iter()
+            .map(|recipient| mailbox(recipient))
+            .collect::<Result<Vec<_>>>()?;
+        if recipients.is_empty() {
+            return Err(AgentError::Configuration("Email digest has no recipients".to_string()));
+        }
+
+        let mut builder = if config.insecure {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host)
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)
+                .map_err(|e| AgentError::Configuration(format!("Invalid SMTP host {}: {}", config.smtp_host, e)))?
+        }
+        .port(config.smtp_port);
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            mailer: builder.build(),
+            config,
+            agent,
+            from,
+            recipients,
+        })
+    }
+
+    /// Send a digest at the end of every interval
+    pub async fn run(self) {
+        let start = tokio::time::Instant::now() + self.config.interval;
+        let mut interval = tokio::time::interval_at(start, self.config.interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.send_digest().await {
+                error!("Failed to send email digest: {}", e);
+            }
+        }
+    }
+
+    /// Summarize the last interval and mail it
+    pub async fn send_digest(&self) -> Result<()> {
+        let period = chrono::Duration::from_std(self.config.interval)
+            .map_err(|e| AgentError::Configuration(format!("Invalid digest interval: {}", e)))?;
+        let Some(summary) = self.agent.summarize_activity(chrono::Utc::now() - period).await? else {
+            info!("No activity to report; skipping email digest");
+            return Ok(());
+        };
+
+        let mut message = Message::builder().from(self.from.clone()).subject(&self.config.subject);
+        for recipient in &self.recipients {
+            message = message.to(recipient.clone());
+        }
+        let message = message
+            .header(ContentType::TEXT_PLAIN)
+            .body(summary)
+            .map_err(|e| AgentError::Internal(format!("Could not build digest email: {}", e)))?;
+
+        self.mailer
+            .send(message)
+            .await
+            .map_err(|e| AgentError::ServiceUnavailable(format!("SMTP delivery failed: {}", e)))?;
+
+        info!("Sent email digest to {} recipients", self.recipients.len());
+        Ok(())
+    }
+}
+
+fn mailbox(address: &str) -> Result<Mailbox> {
+    address
+        .parse()
+        .map_err(|e| AgentError::Configuration(format!("Invalid email address {}: {}", address, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mailbox_accepts_display_names() {
+        let from = mailbox("Alchemist <alchemist@example.com>").unwrap();
+
+        assert_eq!(from.name.as_deref(), Some("Alchemist"));
+        assert_eq!(from.email.to_string(), "alchemist@example.com");
+        assert!(matches!(mailbox("not an address"), Err(AgentError::Configuration(_))));
+    }
+}
@@ -0,0 +1,317 @@
+//! Pull request reviews from GitHub webhooks
+//!
+//! GitHub sends `pull_request` events to `/webhooks/github`. For allowed
+//! repositories the handler fetches the pull request diff, runs it through
+//! `analyze_pattern` with a CIM-specific focus, and posts the result as a
+//! review comment. Payloads must carry a valid `X-Hub-Signature-256`.
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+
+use crate::agent::AlchemistAgent;
+use crate::config::GitHubConfig;
+use crate::error::{AgentError, Result};
+
+/// What the analysis looks for in a diff
+const REVIEW_FOCUS: &str = "how the change fits CIM architecture: events must stay immutable \
+    and named in past tense, aggregates must enforce their invariants and only change through \
+    commands, commands and queries stay separated, NATS subjects follow the \
+    `<prefix>.<commands|queries|events>.<type>` convention, and domains stay decoupled \
+    behind events. Point to the files and hunks each finding concerns.";
+
+/// Actions that change what a review would say
+const REVIEWED_ACTIONS: &[&str] = &["opened", "reopened", "synchronize", "ready_for_review"];
+
+/// A pull request to review
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PullRequest {
+    repo: String,
+    number: u64,
+    head_sha: String,
+}
+
+impl PullRequest {
+    /// The pull request a `pull_request` event asks to review, if any
+    ///
+    /// Drafts and actions that do not change the code are skipped.
+    fn from_event(event: &serde_json::Value) -> Option<Self> {
+        let action = event["action"].as_str()?;
+        let pull_request = &event["pull_request"];
+
+        if !REVIEWED_ACTIONS.contains(&action) || pull_request["draft"].as_bool() == Some(true) {
+            return None;
+        }
+
+        Some(Self {
+            repo: event["repository"]["full_name"].as_str()?.to_string(),
+            number: pull_request["number"].as_u64()?,
+            head_sha: pull_request["head"]["sha"].as_str()?.to_string(),
+        })
+    }
+}
+
+/// Whether `repo` matches an allowlist entry (`owner/name` or `owner/*`)
+fn is_allowed(allowed_repos: &[String], repo: &str) -> bool {
+    allowed_repos.iter().any(|allowed| match allowed.strip_suffix("/*") {
+        Some(owner) => repo.split_once('/').is_some_and(|(repo_owner, _)| repo_owner.eq_ignore_ascii_case(owner)),
+        None => allowed.eq_ignore_ascii_case(repo),
+    })
+}
+
+/// Check an `X-Hub-Signature-256` header against the payload
+fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(expected) = signature.strip_prefix("sha256=").and_then(|digest| hex::decode(digest).ok()) else {
+        return false;
+    };
+
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+
+    // Constant-time comparison
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Cut a diff to at most `max_bytes`, at a line boundary
+///
+/// Returns the diff and whether anything was cut.
+fn truncate_diff(diff: &str, max_bytes: usize) -> (&str, bool) {
+    if diff.len() <= max_bytes {
+        return (diff, false);
+    }
+
+    let mut end = max_bytes;
+    while !diff.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = diff[..end].rfind('\n').map(|newline| newline + 1).unwrap_or(end);
+
+    (&diff[..end], true)
+}
+
+/// Markdown body of the posted review
+fn review_body(analysis: &serde_json::Value, truncated: bool) -> String {
+    let mut body = String::from("### Alchemist review\n\n");
+    body.push_str(analysis["analysis"].as_str().unwrap_or_default().trim());
+
+    let recommendations: Vec<&str> = analysis["recommendations"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|r| r.as_str())
+        .collect();
+
+    if !recommendations.is_empty() {
+        body.push_str("\n\n#### Recommendations\n");
+        for recommendation in recommendations {
+            body.push_str(&format!("\n- {}", recommendation));
+        }
+    }
+
+    if truncated {
+        body.push_str("\n\n_The diff was too large to review in full; only its beginning was analyzed._");
+    }
+
+    body
+}
+
+/// Reviews pull requests through the agent
+struct Reviewer {
+    config: GitHubConfig,
+    agent: Arc<AlchemistAgent>,
+    http: reqwest::Client,
+}
+
+/// Router for the webhook endpoint
+pub fn router(config: GitHubConfig, agent: Arc<AlchemistAgent>) -> Router {
+    if config.allowed_repos.is_empty() {
+        warn!("GitHub webhook has no allowed_repos; every pull request will be ignored");
+    }
+
+    let reviewer = Arc::new(Reviewer {
+        config,
+        agent,
+        http: reqwest::Client::new(),
+    });
+
+    Router::new().route("/", post(webhook)).with_state(reviewer)
+}
+
+async fn webhook(State(reviewer): State<Arc<Reviewer>>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    let signature = header("x-hub-signature-256").unwrap_or_default();
+    if !verify_signature(&reviewer.config.webhook_secret, &body, signature) {
+        warn!("Rejected GitHub webhook with an invalid signature");
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    match header("x-github-event") {
+        Some("ping") => return StatusCode::OK,
+        Some("pull_request") => {}
+        other => {
+            debug!("Ignoring GitHub event {:?}", other);
+            return StatusCode::ACCEPTED;
+        }
+    }
+
+    let event: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(event) => event,
+        Err(_) => return StatusCode::BAD_REQUEST,
+    };
+
+    let Some(pull_request) = PullRequest::from_event(&event) else {
+        return StatusCode::ACCEPTED;
+    };
+
+    if !is_allowed(&reviewer.config.allowed_repos, &pull_request.repo) {
+        debug!("Ignoring pull request in {}, not in allowed_repos", pull_request.repo);
+        return StatusCode::ACCEPTED;
+    }
+
+    // GitHub gives up on webhooks after 10 seconds; review in the background
+    tokio::spawn(async move {
+        info!("Reviewing {}#{}", pull_request.repo, pull_request.number);
+        if let Err(e) = reviewer.review(&pull_request).await {
+            error!("Failed to review {}#{}: {}", pull_request.repo, pull_request.number, e);
+        }
+    });
+
+    StatusCode::ACCEPTED
+}
+
+impl Reviewer {
+    async fn review(&self, pull_request: &PullRequest) -> Result<()> {
+        let diff = self.fetch_diff(pull_request).await?;
+        let (diff, truncated) = truncate_diff(&diff, self.config.max_diff_bytes);
+
+        let analysis = self
+            .agent
+            .process_command_from(
+                "github",
+                "analyze_pattern",
+                serde_json::json!({
+                    "pattern_type": "pull request diff",
+                    "code": diff,
+                    "focus": REVIEW_FOCUS,
+                }),
+            )
+            .await?;
+
+        self.post_review(pull_request, &review_body(&analysis, truncated)).await
+    }
+
+    async fn fetch_diff(&self, pull_request: &PullRequest) -> Result<String> {
+        let response = self
+            .http
+            .get(format!(
+                "{}/repos/{}/pulls/{}",
+                self.config.api_url, pull_request.repo, pull_request.number
+            ))
+            .bearer_auth(&self.config.token)
+            .header("Accept", "application/vnd.github.diff")
+            .header("User-Agent", "cim-agent-alchemist")
+            .send()
+            .await?;
+
+        check_status("fetch diff", &response)?;
+        Ok(response.text().await?)
+    }
+
+    async fn post_review(&self, pull_request: &PullRequest, body: &str) -> Result<()> {
+        let response = self
+            .http
+            .post(format!(
+                "{}/repos/{}/pulls/{}/reviews",
+                self.config.api_url, pull_request.repo, pull_request.number
+            ))
+            .bearer_auth(&self.config.token)
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "cim-agent-alchemist")
+            .json(&serde_json::json!({
+                "commit_id": pull_request.head_sha,
+                "body": body,
+                "event": "COMMENT",
+            }))
+            .send()
+            .await?;
+
+        check_status("post review", &response)
+    }
+}
+
+fn check_status(action: &str, response: &reqwest::Response) -> Result<()> {
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status @ (reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) => {
+            Err(AgentError::PermissionDenied(format!("GitHub {} failed: {}", action, status)))
+        }
+        reqwest::StatusCode::NOT_FOUND => Err(AgentError::NotFound(format!("GitHub {}", action))),
+        status => Err(AgentError::ServiceUnavailable(format!("GitHub {} failed: {}", action, status))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_signature() {
+        let body = br#"{"zen":"Keep it logically awesome."}"#;
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(body);
+        let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        assert!(verify_signature("secret", body, &signature));
+        assert!(!verify_signature("other", body, &signature));
+        assert!(!verify_signature("secret", body, "sha1=abc"));
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let allowed = vec!["thecowboyai/alchemist".to_string(), "cim-org/*".to_string()];
+
+        assert!(is_allowed(&allowed, "TheCowboyAI/alchemist"));
+        assert!(is_allowed(&allowed, "cim-org/cim-domain-graph"));
+        assert!(!is_allowed(&allowed, "someone/alchemist"));
+        assert!(!is_allowed(&[], "thecowboyai/alchemist"));
+    }
+
+    #[test]
+    fn test_pull_request_from_event() {
+        let event = serde_json::json!({
+            "action": "synchronize",
+            "repository": { "full_name": "cim-org/alchemist" },
+            "pull_request": { "number": 42, "draft": false, "head": { "sha": "abc123" } },
+        });
+
+        assert_eq!(
+            PullRequest::from_event(&event),
+            Some(PullRequest {
+                repo: "cim-org/alchemist".to_string(),
+                number: 42,
+                head_sha: "abc123".to_string(),
+            })
+        );
+
+        let mut closed = event.clone();
+        closed["action"] = "closed".into();
+        assert_eq!(PullRequest::from_event(&closed), None);
+    }
+
+    #[test]
+    fn test_truncate_diff_at_line() {
+        let diff = "+line one\n+line two\n+line three\n";
+
+        assert_eq!(truncate_diff(diff, 100), (diff, false));
+        assert_eq!(truncate_diff(diff, 15), ("+line one\n", true));
+    }
+}
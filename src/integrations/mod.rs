@@ -0,0 +1,13 @@
+//! Integrations with chat platforms, code hosts, webhooks, and other outside systems
+//!
+//! Each integration is behind its own feature and is started by the agent
+//! service when it has a section under `integrations` in the configuration.
+
+#[cfg(feature = "email")]
+pub mod email;
+#[cfg(feature = "github")]
+pub mod github;
+#[cfg(feature = "slack")]
+pub mod slack;
+#[cfg(feature = "webhooks")]
+pub mod webhooks;
@@ -0,0 +1,433 @@
+//! Slack bot using Socket Mode
+//!
+//! The bot holds a websocket to Slack instead of exposing a public HTTP
+//! endpoint. Each Slack thread is one dialog: mentioning the bot starts a
+//! thread, replies in that thread (or in a direct message) continue it, and
+//! responses stream in as edits of a single message. The slash command
+//! answers `explain <concept>` and free-form questions.
+//!
+//! The Slack app needs Socket Mode enabled, the `app_mention` and
+//! `message.*` event subscriptions, the slash command, and the
+//! `chat:write` scope.
+
+use futures::{SinkExt, StreamExt};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tracing::{debug, error, info, warn};
+
+use crate::agent::{AlchemistAgent, DialogMessage};
+use crate::config::SlackConfig;
+use crate::error::{AgentError, Result};
+use crate::model::Message as ModelMessage;
+
+const SLACK_API: &str = "https://slack.com/api";
+
+/// Delay before reconnecting after the websocket drops, doubled up to
+/// `MAX_RECONNECT_DELAY` while connecting keeps failing
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+const THINKING: &str = "_Thinking…_";
+
+/// What a slash command asks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SlashCommand {
+    /// `explain <concept>`
+    Explain(String),
+    /// Anything else is a question
+    Ask(String),
+    /// Empty text or `help`
+    Help,
+}
+
+impl SlashCommand {
+    fn parse(text: &str) -> Self {
+        let text = text.trim();
+        let (verb, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+
+        match verb.to_lowercase().as_str() {
+            "" | "help" => Self::Help,
+            "explain" if !rest.trim().is_empty() => Self::Explain(rest.trim().to_string()),
+            _ => Self::Ask(text.to_string()),
+        }
+    }
+}
+
+/// Dialog id for a Slack thread
+fn thread_dialog_id(channel: &str, thread_ts: &str) -> String {
+    format!("slack:{}:{}", channel, thread_ts)
+}
+
+/// Remove user mentions such as `<@U012AB3CD>` from message text
+fn strip_mentions(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("<@") {
+        stripped.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    stripped.push_str(rest);
+
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn help_text(command: &str) -> String {
+    format!(
+        "*Alchemist* answers questions about CIM architecture.\n\
+         • `{command} explain <concept>` explains a concept, e.g. `{command} explain CQRS`\n\
+         • `{command} <question>` starts a thread with your question\n\
+         • Mention me in a channel, or message me directly, to start a conversation; \
+         reply in the thread to continue it."
+    )
+}
+
+/// Slack bot answering through the agent's dialog pipeline
+#[derive(Clone)]
+pub struct SlackBot {
+    config: SlackConfig,
+    agent: Arc<AlchemistAgent>,
+    http: reqwest::Client,
+    /// Threads the bot takes part in
+    threads: Arc<Mutex<HashSet<String>>>,
+}
+
+impl SlackBot {
+    pub fn new(config: SlackConfig, agent: Arc<AlchemistAgent>) -> Self {
+        Self {
+            config,
+            agent,
+            http: reqwest::Client::new(),
+            threads: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Serve Slack until the task is cancelled, reconnecting as needed
+    pub async fn run(self) -> Result<()> {
+        let bot_user = self.bot_user_id().await?;
+        info!("Slack bot connected as {}", bot_user);
+
+        let mut delay = RECONNECT_DELAY;
+        loop {
+            match self.session(&bot_user).await {
+                // Slack asked us to reconnect
+                Ok(()) => delay = RECONNECT_DELAY,
+                Err(e) => {
+                    warn!("Slack connection lost: {}", e);
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+            }
+        }
+    }
+
+    /// User id of the bot, used to ignore mentions already seen as `app_mention`
+    async fn bot_user_id(&self) -> Result<String> {
+        let auth = self.api("auth.test", serde_json::json!({})).await?;
+        auth["user_id"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| AgentError::Configuration("Slack auth.test returned no user_id".to_string()))
+    }
+
+    /// One websocket session, ending when Slack disconnects
+    async fn session(&self, bot_user: &str) -> Result<()> {
+        let url = self.socket_url().await?;
+        let (socket, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .map_err(|e| AgentError::ServiceUnavailable(format!("Slack websocket: {}", e)))?;
+        let (mut sink, mut stream) = socket.split();
+
+        while let Some(frame) = stream.next().await {
+            let frame = frame.map_err(|e| AgentError::ServiceUnavailable(format!("Slack websocket: {}", e)))?;
+            let WsMessage::Text(text) = frame else {
+                continue;
+            };
+
+            let envelope: serde_json::Value = match serde_json::from_str(text.as_str()) {
+                Ok(envelope) => envelope,
+                Err(e) => {
+                    warn!("Ignoring malformed Slack envelope: {}", e);
+                    continue;
+                }
+            };
+
+            // Slack redelivers anything not acknowledged within 3 seconds,
+            // so acknowledge first and answer in the background
+            let mut ack = serde_json::json!({});
+            if let Some(envelope_id) = envelope["envelope_id"].as_str() {
+                ack["envelope_id"] = envelope_id.into();
+            }
+
+            match envelope["type"].as_str() {
+                Some("hello") => debug!("Slack socket ready"),
+                Some("disconnect") => {
+                    debug!("Slack requested reconnect: {}", envelope["reason"]);
+                    return Ok(());
+                }
+                Some("events_api") => {
+                    let bot = self.clone();
+                    let event = envelope["payload"]["event"].clone();
+                    let bot_user = bot_user.to_string();
+                    tokio::spawn(async move { bot.handle_event(event, &bot_user).await });
+                }
+                Some("slash_commands") => {
+                    let payload = &envelope["payload"];
+                    match SlashCommand::parse(payload["text"].as_str().unwrap_or_default()) {
+                        // Shown only to the user who asked
+                        SlashCommand::Help => {
+                            ack["payload"] = serde_json::json!({ "text": help_text(&self.config.slash_command) });
+                        }
+                        command => {
+                            let bot = self.clone();
+                            let channel = payload["channel_id"].as_str().unwrap_or_default().to_string();
+                            tokio::spawn(async move { bot.handle_slash_command(command, &channel).await });
+                        }
+                    }
+                }
+                other => debug!("Ignoring Slack envelope {:?}", other),
+            }
+
+            if ack.get("envelope_id").is_some() {
+                sink.send(WsMessage::Text(ack.to_string().into()))
+                    .await
+                    .map_err(|e| AgentError::ServiceUnavailable(format!("Slack websocket: {}", e)))?;
+            }
+        }
+
+        Err(AgentError::ServiceUnavailable("Slack websocket closed".to_string()))
+    }
+
+    /// Answer mentions, direct messages, and replies in the bot's threads
+    async fn handle_event(&self, event: serde_json::Value, bot_user: &str) {
+        // Never answer bots (including ourselves) or edits and joins
+        if event["bot_id"].is_string() || event["subtype"].is_string() {
+            return;
+        }
+
+        let (Some(channel), Some(ts), Some(text)) =
+            (event["channel"].as_str(), event["ts"].as_str(), event["text"].as_str())
+        else {
+            return;
+        };
+        let thread_ts = event["thread_ts"].as_str().unwrap_or(ts);
+        let dialog_id = thread_dialog_id(channel, thread_ts);
+
+        let respond = match event["type"].as_str() {
+            Some("app_mention") => {
+                self.threads.lock().await.insert(dialog_id.clone());
+                true
+            }
+            // Mentions also arrive as `app_mention`; answer them once
+            Some("message") if !text.contains(&format!("<@{}>", bot_user)) => {
+                event["channel_type"].as_str() == Some("im") || self.threads.lock().await.contains(&dialog_id)
+            }
+            _ => false,
+        };
+
+        if respond {
+            if let Err(e) = self.answer(channel, thread_ts, &dialog_id, &strip_mentions(text)).await {
+                error!("Failed to answer in Slack: {}", e);
+            }
+        }
+    }
+
+    async fn handle_slash_command(&self, command: SlashCommand, channel: &str) {
+        let result = match command {
+            SlashCommand::Explain(concept) => self.explain(channel, &concept).await,
+            SlashCommand::Ask(question) => self.ask_in_new_thread(channel, &question).await,
+            SlashCommand::Help => Ok(()),
+        };
+
+        if let Err(e) = result {
+            error!("Failed to run Slack command: {}", e);
+        }
+    }
+
+    /// Post a question and answer it in a thread under it
+    async fn ask_in_new_thread(&self, channel: &str, question: &str) -> Result<()> {
+        let posted = self
+            .api("chat.postMessage", serde_json::json!({ "channel": channel, "text": format!("> {}", question) }))
+            .await?;
+        let thread_ts = posted["ts"].as_str().unwrap_or_default().to_string();
+
+        let dialog_id = thread_dialog_id(channel, &thread_ts);
+        self.threads.lock().await.insert(dialog_id.clone());
+
+        self.answer(channel, &thread_ts, &dialog_id, question).await
+    }
+
+    /// Explain a concept; the thread under the explanation continues as a dialog
+    async fn explain(&self, channel: &str, concept: &str) -> Result<()> {
+        let placeholder = self
+            .api("chat.postMessage", serde_json::json!({ "channel": channel, "text": THINKING }))
+            .await?;
+        let ts = placeholder["ts"].as_str().unwrap_or_default().to_string();
+
+        let text = match self
+            .agent
+            .process_command_from("slack", "explain_concept", serde_json::json!({ "concept": concept }))
+            .await
+        {
+            Ok(result) => {
+                let explanation = result["explanation"].as_str().unwrap_or_default().to_string();
+
+                // Follow-up questions in the thread keep the explanation in context
+                let dialog_id = thread_dialog_id(channel, &ts);
+                let history = [
+                    ("user", format!("Explain {}", concept)),
+                    ("assistant", explanation.clone()),
+                ]
+                .map(|(role, content)| ModelMessage {
+                    role: role.to_string(),
+                    content,
+                    timestamp: chrono::Utc::now(),
+                });
+                self.agent.restore_dialog(&dialog_id, &history).await;
+                self.threads.lock().await.insert(dialog_id);
+
+                format!("*{}*\n{}", concept, explanation)
+            }
+            Err(e) => format!(":warning: Could not explain {}: {}", concept, e),
+        };
+
+        self.update(channel, &ts, &text).await
+    }
+
+    /// Answer a message in a thread, streaming the response as edits
+    async fn answer(&self, channel: &str, thread_ts: &str, dialog_id: &str, text: &str) -> Result<()> {
+        let placeholder = self
+            .api(
+                "chat.postMessage",
+                serde_json::json!({ "channel": channel, "thread_ts": thread_ts, "text": THINKING }),
+            )
+            .await?;
+        let ts = placeholder["ts"].as_str().unwrap_or_default().to_string();
+
+        let message = DialogMessage {
+            dialog_id: dialog_id.to_string(),
+            content: text.to_string(),
+            metadata: serde_json::json!({ "source": "slack", "channel": channel }),
+            timestamp: chrono::Utc::now(),
+        };
+
+        let (chunk_tx, mut chunk_rx) = mpsc::unbounded_channel::<String>();
+        let generate = self.agent.process_dialog_message_streaming(message, move |chunk| {
+            let _ = chunk_tx.send(chunk.to_string());
+        });
+
+        // Edits are rate limited, so show progress at most once per interval
+        let edits = async {
+            let mut partial = String::new();
+            let mut last_edit = Instant::now();
+            while let Some(chunk) = chunk_rx.recv().await {
+                partial.push_str(&chunk);
+                if last_edit.elapsed() >= self.config.edit_interval {
+                    if let Err(e) = self.update(channel, &ts, &format!("{} …", partial)).await {
+                        debug!("Skipped streaming edit: {}", e);
+                    }
+                    last_edit = Instant::now();
+                }
+            }
+        };
+
+        let (result, ()) = tokio::join!(generate, edits);
+
+        let text = match result {
+            Ok(response) => response,
+            Err(e) => format!(":warning: {}", e),
+        };
+        self.update(channel, &ts, &text).await
+    }
+
+    async fn update(&self, channel: &str, ts: &str, text: &str) -> Result<()> {
+        self.api("chat.update", serde_json::json!({ "channel": channel, "ts": ts, "text": text }))
+            .await
+            .map(|_| ())
+    }
+
+    /// Open a Socket Mode connection and return its websocket URL
+    async fn socket_url(&self) -> Result<String> {
+        let response: serde_json::Value = self
+            .http
+            .post(format!("{}/apps.connections.open", SLACK_API))
+            .bearer_auth(&self.config.app_token)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        check_ok("apps.connections.open", &response)?;
+        response["url"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| AgentError::ServiceUnavailable("Slack returned no socket URL".to_string()))
+    }
+
+    /// Call a Web API method with the bot token
+    async fn api(&self, method: &str, body: serde_json::Value) -> Result<serde_json::Value> {
+        let response: serde_json::Value = self
+            .http
+            .post(format!("{}/{}", SLACK_API, method))
+            .bearer_auth(&self.config.bot_token)
+            .json(&body)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        check_ok(method, &response)?;
+        Ok(response)
+    }
+}
+
+/// Slack reports failures in the body with `ok: false`
+fn check_ok(method: &str, response: &serde_json::Value) -> Result<()> {
+    if response["ok"].as_bool() == Some(true) {
+        return Ok(());
+    }
+
+    let error = response["error"].as_str().unwrap_or("unknown error");
+    match error {
+        "invalid_auth" | "not_authed" | "account_inactive" | "missing_scope" => Err(AgentError::PermissionDenied(
+            format!("Slack {} failed: {}", method, error),
+        )),
+        _ => Err(AgentError::ServiceUnavailable(format!("Slack {} failed: {}", method, error))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_slash_command() {
+        assert_eq!(SlashCommand::parse("explain CQRS"), SlashCommand::Explain("CQRS".to_string()));
+        assert_eq!(
+            SlashCommand::parse("  Explain event sourcing "),
+            SlashCommand::Explain("event sourcing".to_string())
+        );
+        assert_eq!(SlashCommand::parse(""), SlashCommand::Help);
+        assert_eq!(SlashCommand::parse("help"), SlashCommand::Help);
+        assert_eq!(
+            SlashCommand::parse("how do aggregates work?"),
+            SlashCommand::Ask("how do aggregates work?".to_string())
+        );
+    }
+
+    #[test]
+    fn test_strip_mentions() {
+        assert_eq!(strip_mentions("<@U012AB3CD> what is CQRS?"), "what is CQRS?");
+        assert_eq!(strip_mentions("ask <@U1> and <@U2> now"), "ask and now");
+        assert_eq!(strip_mentions("broken <@U1"), "broken <@U1");
+    }
+}
@@ -0,0 +1,206 @@
+//! Agent events POSTed to outgoing webhooks
+//!
+//! Systems that do not speak NATS can still react to the agent: each
+//! configured HTTPS endpoint receives the events it subscribed to as the
+//! JSON `AgentEvent`. Payloads are signed the way GitHub signs its webhooks,
+//! with `X-Alchemist-Signature-256: sha256=<hex HMAC-SHA256 of the body>`.
+//! Failed deliveries are retried with exponential backoff.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, error, warn};
+
+use crate::config::WebhookConfig;
+use crate::error::{AgentError, Result};
+use crate::nats_integration::AgentEvent;
+
+/// Delay before the first retry, doubled for each one after
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest delay between two attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Delivers agent events to the configured endpoints
+pub struct WebhookDispatcher {
+    webhooks: Vec<Arc<WebhookConfig>>,
+    http: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    /// Fails if an endpoint is not an HTTPS URL
+    pub fn new(webhooks: Vec<WebhookConfig>) -> Result<Self> {
+        for webhook in &webhooks {
+            let url = reqwest::Url::parse(&webhook.url).map_err(|e| {
+                AgentError::Configuration(format!("Invalid webhook URL {}: {}", webhook.url, e))
+            })?;
+            if url.scheme() != "https" {
+                return Err(AgentError::Configuration(format!(
+                    "Webhook URL {} must use https",
+                    webhook.url
+                )));
+            }
+        }
+
+        Ok(Self {
+            webhooks: webhooks.into_iter().map(Arc::new).collect(),
+            http: reqwest::Client::new(),
+        })
+    }
+
+    /// Deliver events until the agent's event channel closes
+    pub async fn run(self, mut events: broadcast::Receiver<AgentEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(event) => self.dispatch(&event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Webhook dispatcher fell behind and skipped {} events", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    fn dispatch(&self, event: &AgentEvent) {
+        let body = match serde_json::to_vec(event) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Failed to serialize {} event: {}", event.event_type(), e);
+                return;
+            }
+        };
+
+        for webhook in self.webhooks.iter().filter(|webhook| wants(webhook, event.event_type())) {
+            // Each delivery retries on its own so one slow endpoint holds up no other
+            let http = self.http.clone();
+            let webhook = webhook.clone();
+            let event = event.clone();
+            let body = body.clone();
+            tokio::spawn(async move { deliver(&http, &webhook, &event, body).await });
+        }
+    }
+}
+
+/// Whether `webhook` subscribed to events of `event_type`
+fn wants(webhook: &WebhookConfig, event_type: &str) -> bool {
+    webhook.events.is_empty() || webhook.events.iter().any(|wanted| wanted == event_type)
+}
+
+/// `X-Alchemist-Signature-256` header value for a body
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Delay before retry number `retry`, counting from 0
+fn backoff(retry: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(retry))
+        .min(MAX_BACKOFF)
+}
+
+/// Whether an attempt rejected with `status` is worth repeating
+fn is_retryable(status: reqwest::StatusCode) -> bool {
+    status.is_server_error()
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT
+}
+
+async fn deliver(http: &reqwest::Client, webhook: &WebhookConfig, event: &AgentEvent, body: Vec<u8>) {
+    let signature = sign(&webhook.secret, &body);
+    let mut retry = 0;
+
+    loop {
+        let result = http
+            .post(&webhook.url)
+            .timeout(webhook.timeout)
+            .header("Content-Type", "application/json")
+            .header("User-Agent", "cim-agent-alchemist")
+            .header("X-Alchemist-Event", event.event_type())
+            .header("X-Alchemist-Delivery", &event.id)
+            .header("X-Alchemist-Signature-256", &signature)
+            .body(body.clone())
+            .send()
+            .await;
+
+        let retryable = match result {
+            Ok(response) if response.status().is_success() => {
+                debug!("Delivered {} event {} to {}", event.event_type(), event.id, webhook.url);
+                return;
+            }
+            Ok(response) => {
+                warn!("Webhook {} answered {} event {} with {}", webhook.url, event.event_type(), event.id, response.status());
+                is_retryable(response.status())
+            }
+            Err(e) => {
+                warn!("Webhook {} unreachable for {} event {}: {}", webhook.url, event.event_type(), event.id, e);
+                true
+            }
+        };
+
+        if !retryable || retry >= webhook.max_retries {
+            error!(
+                "Dropped {} event {} for {} after {} attempts",
+                event.event_type(),
+                event.id,
+                webhook.url,
+                retry + 1
+            );
+            return;
+        }
+
+        tokio::time::sleep(backoff(retry)).await;
+        retry += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(url: &str, events: &[&str]) -> WebhookConfig {
+        WebhookConfig {
+            url: url.to_string(),
+            secret: "secret".to_string(),
+            events: events.iter().map(|event| event.to_string()).collect(),
+            max_retries: 5,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    #[test]
+    fn test_sign_matches_hmac() {
+        let body = br#"{"event_type":"workflow_completed"}"#;
+        let signature = sign("secret", body);
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(body);
+        let digest = hex::decode(signature.strip_prefix("sha256=").unwrap()).unwrap();
+        assert!(mac.verify_slice(&digest).is_ok());
+    }
+
+    #[test]
+    fn test_event_filter() {
+        assert!(wants(&webhook("https://example.com", &[]), "dialog_ended"));
+        assert!(wants(&webhook("https://example.com", &["dialog_ended"]), "dialog_ended"));
+        assert!(!wants(&webhook("https://example.com", &["workflow_completed"]), "dialog_ended"));
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        assert_eq!(backoff(0), Duration::from_secs(1));
+        assert_eq!(backoff(3), Duration::from_secs(8));
+        assert_eq!(backoff(40), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_requires_https() {
+        assert!(WebhookDispatcher::new(vec![webhook("https://example.com/hook", &[])]).is_ok());
+        assert!(WebhookDispatcher::new(vec![webhook("http://example.com/hook", &[])]).is_err());
+        assert!(!is_retryable(reqwest::StatusCode::BAD_REQUEST));
+        assert!(is_retryable(reqwest::StatusCode::BAD_GATEWAY));
+    }
+}
@@ -0,0 +1,488 @@
+//! The concepts the agent knows and how they relate
+//!
+//! The graph starts out seeded with the core CIM concepts, described and
+//! linked by typed relations such as `contains` or `emits`. Dialogs can
+//! edit it: the model turns a request such as "add a concept Saga related
+//! to Aggregate" into a [`GraphMutation`], the user confirms it, and the
+//! agent applies it and saves the graph.
+//!
+//! Names are matched without regard to case, punctuation, or spacing, so
+//! "cqrs", "C.Q.R.S", "event-sourcing", and "EventSourcing" find their
+//! concepts, and common abbreviations such as "DDD" or "ES" stand for the
+//! seeded concepts they abbreviate.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::error::{AgentError, Result};
+
+/// A concept in the graph
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Concept {
+    pub name: String,
+
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A typed edge between two concepts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relation {
+    pub from: String,
+    pub to: String,
+
+    /// Such as `related_to`, `part_of`, or `implements`
+    pub relation: String,
+}
+
+/// One change to the graph
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum GraphOperation {
+    AddConcept {
+        name: String,
+        #[serde(default)]
+        description: String,
+    },
+    RemoveConcept {
+        name: String,
+    },
+    AddRelation {
+        from: String,
+        to: String,
+        #[serde(default = "default_relation")]
+        relation: String,
+    },
+    RemoveRelation {
+        from: String,
+        to: String,
+    },
+}
+
+fn default_relation() -> String {
+    "related_to".to_string()
+}
+
+/// Changes the model proposed for a request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphMutation {
+    pub operations: Vec<GraphOperation>,
+
+    /// One sentence describing the change, shown to the user to confirm
+    #[serde(default)]
+    pub summary: String,
+}
+
+/// Read the mutation out of a model reply, ignoring any prose or code fence
+/// around the JSON
+pub fn parse_mutation(reply: &str) -> Result<GraphMutation> {
+    let json = reply
+        .find('{')
+        .zip(reply.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| &reply[start..=end])
+        .ok_or_else(|| AgentError::ModelError("The model did not propose a graph change".to_string()))?;
+
+    let mutation: GraphMutation = serde_json::from_str(json)
+        .map_err(|e| AgentError::ModelError(format!("The model proposed a malformed graph change: {}", e)))?;
+    if mutation.operations.is_empty() {
+        return Err(AgentError::ModelError("The model proposed no graph changes".to_string()));
+    }
+
+    Ok(mutation)
+}
+
+/// How a graph differs from an earlier version of it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphDiff {
+    pub concepts_added: Vec<String>,
+    pub concepts_removed: Vec<String>,
+    pub relations_added: Vec<Relation>,
+    pub relations_removed: Vec<Relation>,
+}
+
+impl GraphDiff {
+    pub fn is_empty(&self) -> bool {
+        self.concepts_added.is_empty()
+            && self.concepts_removed.is_empty()
+            && self.relations_added.is_empty()
+            && self.relations_removed.is_empty()
+    }
+}
+
+/// Concepts the graph is seeded with, and their descriptions
+const SEED_CONCEPTS: &[(&str, &str)] = &[
+    ("Event Sourcing", "Storing state as the sequence of domain events that produced it"),
+    ("CQRS", "Separating the model that handles commands from the models that answer queries"),
+    ("Domain-Driven Design", "Modelling software on the language and boundaries of the business domain"),
+    ("Entity Component System", "Composing behaviour from entities, data-only components, and systems, as in Bevy"),
+    ("Conceptual Spaces", "Geometric spaces where concepts are regions and similarity is distance"),
+    ("Graph Workflows", "Workflows modelled as graphs of steps, advanced by events"),
+    ("NATS Messaging", "The subject-based messaging and JetStream persistence connecting CIM components"),
+    ("CID Chains", "Content identifiers chaining events so their history cannot be altered unnoticed"),
+    ("Aggregate", "A cluster of domain objects changed as one consistency boundary"),
+    ("Value Object", "An immutable object defined only by its attributes"),
+    ("Entity", "A domain object with an identity that persists as its attributes change"),
+    ("Domain Event", "A fact, named in the past tense, about something that happened in the domain"),
+    ("Command", "A request to change the domain, which may be refused"),
+    ("Query", "A request to read state without changing it"),
+    ("Command Handler", "Validates a command against an aggregate and records the resulting events"),
+    ("Query Handler", "Answers a query from a read model"),
+    ("Projection", "A read model built by folding domain events"),
+    ("Event Store", "Append-only storage of domain events, in CIM a JetStream stream"),
+    ("Bounded Context", "The boundary within which a domain model and its language are consistent"),
+    ("Ubiquitous Language", "The shared vocabulary of developers and domain experts within a bounded context"),
+];
+
+/// Abbreviations and other names of seeded concepts, as [`normalize`]d
+const ALIASES: &[(&str, &str)] = &[
+    ("es", "Event Sourcing"),
+    ("command query responsibility segregation", "CQRS"),
+    ("ddd", "Domain-Driven Design"),
+    ("ecs", "Entity Component System"),
+    ("nats", "NATS Messaging"),
+    ("cid chain", "CID Chains"),
+    ("vo", "Value Object"),
+    ("event", "Domain Event"),
+    ("read model", "Projection"),
+    ("bc", "Bounded Context"),
+    ("ul", "Ubiquitous Language"),
+];
+
+/// Relations the graph is seeded with: from, to, and relation type
+const SEED_RELATIONS: &[(&str, &str, &str)] = &[
+    ("Event Sourcing", "Domain Event", "stores"),
+    ("Event Sourcing", "Event Store", "uses"),
+    ("Event Sourcing", "CQRS", "related_to"),
+    ("CQRS", "Command Handler", "contains"),
+    ("CQRS", "Query Handler", "contains"),
+    ("Command Handler", "Command", "handles"),
+    ("Command Handler", "Aggregate", "loads"),
+    ("Query Handler", "Query", "handles"),
+    ("Query Handler", "Projection", "reads"),
+    ("Projection", "Domain Event", "consumes"),
+    ("Aggregate", "Domain Event", "emits"),
+    ("Aggregate", "Entity", "contains"),
+    ("Aggregate", "Value Object", "contains"),
+    ("Domain-Driven Design", "Aggregate", "contains"),
+    ("Domain-Driven Design", "Bounded Context", "contains"),
+    ("Domain-Driven Design", "Ubiquitous Language", "contains"),
+    ("Bounded Context", "Ubiquitous Language", "defines"),
+    ("Domain Event", "NATS Messaging", "published_over"),
+    ("Event Store", "NATS Messaging", "part_of"),
+    ("CID Chains", "Event Store", "secures"),
+    ("Graph Workflows", "Domain Event", "driven_by"),
+    ("Entity Component System", "Domain Event", "reacts_to"),
+    ("Conceptual Spaces", "Ubiquitous Language", "grounds"),
+];
+
+/// Concepts and relations, keyed by lowercased name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConceptGraph {
+    concepts: BTreeMap<String, Concept>,
+    relations: Vec<Relation>,
+}
+
+impl ConceptGraph {
+    /// The core CIM concepts and how they relate
+    pub fn seeded() -> Self {
+        let mut graph = Self::default();
+        let concepts = SEED_CONCEPTS.iter().map(|(name, description)| GraphOperation::AddConcept {
+            name: name.to_string(),
+            description: description.to_string(),
+        });
+        let relations = SEED_RELATIONS.iter().map(|(from, to, relation)| GraphOperation::AddRelation {
+            from: from.to_string(),
+            to: to.to_string(),
+            relation: relation.to_string(),
+        });
+        for operation in concepts.chain(relations) {
+            graph.apply(&operation).expect("seed graph is consistent");
+        }
+        graph
+    }
+
+    /// A graph holding `names`, unrelated
+    pub fn with_concepts(names: &[&str]) -> Self {
+        let mut graph = Self::default();
+        for name in names {
+            graph.concepts.insert(
+                name.to_lowercase(),
+                Concept {
+                    name: name.to_string(),
+                    description: String::new(),
+                },
+            );
+        }
+        graph
+    }
+
+    /// The concept `name` refers to, however it is written
+    pub fn concept(&self, name: &str) -> Option<&Concept> {
+        if let Some(concept) = self.concepts.get(&name.to_lowercase()) {
+            return Some(concept);
+        }
+
+        let normalized = normalize(name);
+        let aliased = ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == normalized)
+            .map(|(_, concept)| normalize(concept));
+        self.concepts.values().find(|concept| {
+            let known = normalize(&concept.name);
+            known == normalized || aliased.as_ref() == Some(&known)
+        })
+    }
+
+    pub fn concepts(&self) -> impl Iterator<Item = &Concept> {
+        self.concepts.values()
+    }
+
+    pub fn relations(&self) -> &[Relation] {
+        &self.relations
+    }
+
+    /// Names of concepts related to `name` in either direction
+    pub fn related(&self, name: &str) -> Vec<String> {
+        let key = self.concept(name).map_or(name, |concept| concept.name.as_str()).to_lowercase();
+        self.relations
+            .iter()
+            .filter_map(|relation| {
+                if relation.from.to_lowercase() == key {
+                    Some(relation.to.clone())
+                } else if relation.to.to_lowercase() == key {
+                    Some(relation.from.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Check that every operation of `mutation` would apply, in order
+    pub fn validate(&self, mutation: &GraphMutation) -> Result<()> {
+        let mut trial = self.clone();
+        for operation in &mutation.operations {
+            trial.apply(operation)?;
+        }
+        Ok(())
+    }
+
+    /// Apply one operation; relations need both concepts to exist
+    pub fn apply(&mut self, operation: &GraphOperation) -> Result<()> {
+        match operation {
+            GraphOperation::AddConcept { name, description } => {
+                if self.concept(name).is_some() {
+                    return Err(AgentError::Graph(format!("Concept {} already exists", name)));
+                }
+                self.concepts.insert(
+                    name.to_lowercase(),
+                    Concept {
+                        name: name.clone(),
+                        description: description.clone(),
+                    },
+                );
+            }
+            GraphOperation::RemoveConcept { name } => {
+                let key = name.to_lowercase();
+                self.concepts
+                    .remove(&key)
+                    .ok_or_else(|| AgentError::Graph(format!("No concept named {}", name)))?;
+                self.relations
+                    .retain(|relation| relation.from.to_lowercase() != key && relation.to.to_lowercase() != key);
+            }
+            GraphOperation::AddRelation { from, to, relation } => {
+                let from = self.canonical(from)?;
+                let to = self.canonical(to)?;
+                let exists = self.relations.iter().any(|known| known.from == from && known.to == to);
+                if !exists {
+                    self.relations.push(Relation {
+                        from,
+                        to,
+                        relation: relation.clone(),
+                    });
+                }
+            }
+            GraphOperation::RemoveRelation { from, to } => {
+                let (from, to) = (from.to_lowercase(), to.to_lowercase());
+                let before = self.relations.len();
+                self.relations
+                    .retain(|relation| !(relation.from.to_lowercase() == from && relation.to.to_lowercase() == to));
+                if self.relations.len() == before {
+                    return Err(AgentError::Graph(format!("No relation from {} to {}", from, to)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// What changed since `earlier`
+    pub fn diff(&self, earlier: &ConceptGraph) -> GraphDiff {
+        let missing_from = |graph: &ConceptGraph, other: &ConceptGraph| -> Vec<String> {
+            graph
+                .concepts
+                .iter()
+                .filter(|(key, _)| !other.concepts.contains_key(*key))
+                .map(|(_, concept)| concept.name.clone())
+                .collect()
+        };
+        let relations_missing_from = |graph: &ConceptGraph, other: &ConceptGraph| -> Vec<Relation> {
+            graph
+                .relations
+                .iter()
+                .filter(|relation| !other.relations.contains(relation))
+                .cloned()
+                .collect()
+        };
+
+        GraphDiff {
+            concepts_added: missing_from(self, earlier),
+            concepts_removed: missing_from(earlier, self),
+            relations_added: relations_missing_from(self, earlier),
+            relations_removed: relations_missing_from(earlier, self),
+        }
+    }
+
+    fn canonical(&self, name: &str) -> Result<String> {
+        self.concept(name)
+            .map(|concept| concept.name.clone())
+            .ok_or_else(|| AgentError::Graph(format!("No concept named {}", name)))
+    }
+}
+
+/// `name` lowercased, without dots, with words split at hyphens,
+/// underscores, and case changes, and single-spaced
+///
+/// "C.Q.R.S" becomes "cqrs", and "event-sourcing" and "EventSourcing" both
+/// become "event sourcing".
+pub fn normalize(name: &str) -> String {
+    let mut spaced = String::with_capacity(name.len());
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        match c {
+            '.' => continue,
+            '-' | '_' => spaced.push(' '),
+            c if c.is_uppercase() && previous.is_some_and(char::is_lowercase) => {
+                spaced.push(' ');
+                spaced.push(c);
+            }
+            c => spaced.push(c),
+        }
+        previous = Some(c);
+    }
+    spaced.to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mutation_from_fenced_reply() {
+        let reply = "Here is the change:\n```json\n{\"summary\": \"Add Saga\", \"operations\": [\
+            {\"op\": \"add_concept\", \"name\": \"Saga\"},\
+            {\"op\": \"add_relation\", \"from\": \"Saga\", \"to\": \"aggregate\"}]}\n```";
+        let mutation = parse_mutation(reply).unwrap();
+
+        assert_eq!(mutation.operations.len(), 2);
+        assert_eq!(
+            mutation.operations[1],
+            GraphOperation::AddRelation {
+                from: "Saga".to_string(),
+                to: "aggregate".to_string(),
+                relation: "related_to".to_string(),
+            }
+        );
+        assert!(parse_mutation("I cannot help with that").is_err());
+    }
+
+    #[test]
+    fn test_apply_and_validate() {
+        let mut graph = ConceptGraph::with_concepts(&["Aggregate", "CQRS"]);
+        let mutation = parse_mutation(
+            r#"{"operations": [{"op": "add_concept", "name": "Saga"}, {"op": "add_relation", "from": "saga", "to": "Aggregate", "relation": "coordinates"}]}"#,
+        )
+        .unwrap();
+
+        graph.validate(&mutation).unwrap();
+        for operation in &mutation.operations {
+            graph.apply(operation).unwrap();
+        }
+        assert_eq!(graph.related("aggregate"), vec!["Saga"]);
+
+        let dangling = GraphOperation::AddRelation {
+            from: "Saga".to_string(),
+            to: "Unknown".to_string(),
+            relation: "related_to".to_string(),
+        };
+        assert!(matches!(graph.apply(&dangling), Err(AgentError::Graph(_))));
+
+        graph.apply(&GraphOperation::RemoveConcept { name: "SAGA".to_string() }).unwrap();
+        assert!(graph.relations().is_empty());
+    }
+
+    #[test]
+    fn test_seeded_graph() {
+        let graph = ConceptGraph::seeded();
+        for name in crate::agent::CIM_CONCEPTS {
+            assert!(graph.concept(name).is_some(), "{} is not seeded", name);
+        }
+        assert_eq!(graph.relations().len(), SEED_RELATIONS.len());
+        assert!(graph.related("event sourcing").contains(&"Event Store".to_string()));
+
+        let restored: ConceptGraph = serde_json::from_str(&serde_json::to_string(&graph).unwrap()).unwrap();
+        assert!(restored.diff(&graph).is_empty());
+        assert_eq!(restored.concept("cqrs").unwrap().description, graph.concept("CQRS").unwrap().description);
+    }
+
+    #[test]
+    fn test_concept_variants_and_aliases() {
+        assert_eq!(normalize("C.Q.R.S"), "cqrs");
+        assert_eq!(normalize("EventSourcing"), "event sourcing");
+        assert_eq!(normalize("  event-sourcing "), "event sourcing");
+
+        let graph = ConceptGraph::seeded();
+        for (name, expected) in [
+            ("cqrs", "CQRS"),
+            ("C.Q.R.S", "CQRS"),
+            ("event-sourcing", "Event Sourcing"),
+            ("EventSourcing", "Event Sourcing"),
+            ("domain driven design", "Domain-Driven Design"),
+            ("DDD", "Domain-Driven Design"),
+            ("ES", "Event Sourcing"),
+            ("value_object", "Value Object"),
+        ] {
+            assert_eq!(graph.concept(name).map(|concept| concept.name.as_str()), Some(expected), "{}", name);
+        }
+        assert!(graph.concept("saga").is_none());
+        assert!(graph.related("ES").contains(&"Event Store".to_string()));
+    }
+
+    #[test]
+    fn test_diff() {
+        let earlier = ConceptGraph::with_concepts(&["Aggregate", "CQRS"]);
+        let mut graph = earlier.clone();
+        graph.apply(&GraphOperation::RemoveConcept { name: "cqrs".to_string() }).unwrap();
+        graph
+            .apply(&GraphOperation::AddConcept {
+                name: "Saga".to_string(),
+                description: String::new(),
+            })
+            .unwrap();
+        graph
+            .apply(&GraphOperation::AddRelation {
+                from: "Saga".to_string(),
+                to: "Aggregate".to_string(),
+                relation: "coordinates".to_string(),
+            })
+            .unwrap();
+
+        let diff = graph.diff(&earlier);
+        assert_eq!(diff.concepts_added, ["Saga"]);
+        assert_eq!(diff.concepts_removed, ["CQRS"]);
+        assert_eq!(diff.relations_added.len(), 1);
+        assert!(diff.relations_removed.is_empty());
+        assert!(graph.diff(&graph).is_empty());
+    }
+}
@@ -2,19 +2,74 @@
 //! 
 //! This library provides the core functionality for the CIM Alchemist AI assistant.
 
+pub mod access_log;
 pub mod agent;
+pub mod anonymize;
+pub mod api;
+pub mod artifacts;
+pub mod auth;
+pub mod backfill;
+pub mod budget;
+pub mod cache;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod client;
+pub mod codegen;
+pub mod comparison;
+pub mod conceptual;
 pub mod config;
+#[cfg(unix)]
+pub mod daemon;
+pub mod diagnose;
 pub mod error;
+pub mod eval;
+pub mod evaluation;
+pub mod event_stream;
+pub mod events;
+pub mod export;
+pub mod glossary;
+pub mod guard;
+pub mod http;
+pub mod import;
+pub mod integrations;
+pub mod knowledge;
+pub mod limits;
+pub mod locale;
+pub mod logging;
+pub mod metrics;
+pub mod migrate;
 pub mod model;
 pub mod nats_integration;
+pub mod page;
+pub mod peers;
+pub mod pins;
+pub mod plan;
+pub mod priority;
+pub mod quiz;
+pub mod reaper;
+pub mod replay;
+pub mod retrieval;
+pub mod scaffold;
+pub mod scheduler;
+pub mod schemas;
 pub mod service;
+#[cfg(feature = "signing")]
+pub mod signing;
+pub mod sources;
+pub mod storage;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod tools;
+pub mod topology;
+pub mod usage;
 
 #[cfg(feature = "bevy")]
 pub mod bevy_plugin;
 
 // Re-export main types
 pub use agent::AlchemistAgent;
-pub use config::AgentConfig;
+pub use client::AgentClient;
+pub use config::{AgentConfig, ModelConfig};
 pub use error::{AgentError, Result};
 pub use service::AgentService;
 pub use nats_integration::NatsClient;
@@ -25,12 +80,60 @@ pub use bevy_plugin::{
     AlchemistAgentPlugin,
     AgentQuestionEvent,
     AgentResponseEvent,
+    AgentResponseChunkEvent,
+    AgentSuggestionsEvent,
     AgentErrorEvent,
-    AgentConfig,
+    AgentPluginConfig,
+    AgentBackend,
+    AgentHandle,
+    AgentStatus,
+    FollowUpSuggestion,
+    agent_is_ready,
     ask_agent,
+    ask_agent_handle,
     handle_agent_input,
 };
 
+#[cfg(feature = "bevy")]
+pub use bevy_plugin::conversation::{AgentConversation, ConversationTurn, Speaker};
+
+#[cfg(feature = "bevy")]
+pub use bevy_plugin::inspect::AskAboutEntity;
+
+#[cfg(feature = "bevy")]
+pub use bevy_plugin::models::{AvailableModelsEvent, ListModelsEvent, ModelSwitchedEvent, SwitchModelEvent};
+
+#[cfg(feature = "bevy")]
+pub use bevy_plugin::session::SavedSession;
+
+#[cfg(feature = "bevy")]
+pub use bevy_plugin::workflow_hud::{
+    ActiveWorkflow,
+    AdvanceWorkflowEvent,
+    StartWorkflowEvent,
+    WorkflowKeys,
+    WorkflowProgress,
+    WorkflowUpdatedEvent,
+};
+
+#[cfg(feature = "bevy")]
+pub use bevy_plugin::visualization::{
+    ArchitectureEdge,
+    ArchitectureGraph,
+    ArchitectureNode,
+    ArchitectureVisualizationEvent,
+    VisualizeArchitectureEvent,
+};
+
+#[cfg(feature = "bevy_egui")]
+pub use bevy_plugin::egui_panel::{AgentChatPanel, AgentChatPanelPlugin, PanelDock};
+
+#[cfg(feature = "voice")]
+pub use bevy_plugin::voice::{VoiceInputConfig, VoiceInputPlugin, VoiceTranscriptEvent};
+
+#[cfg(feature = "bevy-ui")]
+pub use bevy_plugin::chat_ui::{chat_widget, ChatInput};
+
 /// Version information for the Alchemist agent
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const NAME: &str = env!("CARGO_PKG_NAME");
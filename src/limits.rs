@@ -0,0 +1,54 @@
+//! Size limits on incoming payloads
+//!
+//! Commands, queries, and dialog messages over `limits.max_message_bytes`
+//! are refused before they are parsed, and inline attachments and code over
+//! theirs before anything reaches the model. The refusal is an
+//! [`AgentError::PayloadTooLarge`], answered with the code
+//! `payload_too_large` and the size and limit in the reply envelope, and
+//! with 413 over HTTP.
+//!
+//! Content that large belongs in the artifact store (`storage.artifacts`).
+//! An attachment can give its `key` there instead of its `content`, and
+//! `analyze_pattern` and `explain_error` take `code_key` instead of `code`.
+//! Content read by reference is not limited.
+
+use crate::error::{AgentError, Result};
+
+/// Fail if `what`, of `size` bytes, is over `limit`; a limit of zero is none
+pub fn check(what: &str, size: usize, limit: usize) -> Result<()> {
+    if limit > 0 && size > limit {
+        return Err(AgentError::PayloadTooLarge {
+            what: what.to_string(),
+            size,
+            limit,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_limits() {
+        assert!(check("code", 10, 10).is_ok());
+        assert!(check("code", 11, 0).is_ok());
+
+        let error = check("attachment schema.json", 11, 10).unwrap_err();
+        assert_eq!(error.code(), Some("payload_too_large"));
+        assert_eq!(error.details().unwrap()["limit"], 10);
+        assert!(error.to_string().contains("attachment schema.json is 11 bytes"));
+    }
+
+    #[test]
+    fn test_envelope_carries_code_and_details() {
+        let envelope = crate::nats_integration::response_envelope(&Err(check("message", 2048, 1024).unwrap_err()));
+        assert_eq!(envelope["success"], false);
+        assert_eq!(envelope["code"], "payload_too_large");
+        assert_eq!(envelope["details"]["size"], 2048);
+
+        let envelope = crate::nats_integration::response_envelope(&Err(AgentError::NotFound("wf-1".to_string())));
+        assert!(envelope.get("code").is_none());
+    }
+}
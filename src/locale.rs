@@ -0,0 +1,203 @@
+//! Localized prompts and user-facing messages
+//!
+//! Messages are Fluent resources: `locales/<locale>/alchemist.ftl` ships with
+//! the agent, and a file of the same name under `localization.resources_dir`
+//! adds locales or overrides messages. A dialog message picks its locale with
+//! `metadata.locale`, a command or query with a `locale` parameter;
+//! otherwise the deployment's `default_locale` is used. Missing messages fall
+//! back to US English.
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::collections::HashMap;
+use unic_langid::LanguageIdentifier;
+
+use crate::config::LocalizationConfig;
+use crate::error::{AgentError, Result};
+
+/// Locale every other one falls back to
+pub const FALLBACK_LOCALE: &str = "en-US";
+
+/// Name of the resource file in each locale directory
+const RESOURCE_FILE: &str = "alchemist.ftl";
+
+/// Resources compiled into the agent
+const BUILT_IN: &[(&str, &str)] = &[
+    ("en-US", include_str!("../locales/en-US/alchemist.ftl")),
+    ("de-DE", include_str!("../locales/de-DE/alchemist.ftl")),
+];
+
+/// Formats messages in the locales the agent knows
+pub struct Localizer {
+    bundles: HashMap<String, FluentBundle<FluentResource>>,
+    default_locale: String,
+}
+
+impl Localizer {
+    /// Fails if a resource does not parse or `default_locale` is unknown
+    pub fn new(config: &LocalizationConfig) -> Result<Self> {
+        let mut bundles = HashMap::new();
+        for (locale, source) in BUILT_IN {
+            let mut bundle = bundle(locale)?;
+            bundle
+                .add_resource(resource(locale, source.to_string())?)
+                .map_err(|errors| invalid(locale, &errors))?;
+            bundles.insert(locale.to_string(), bundle);
+        }
+
+        // Messages in a deployment's own files win over the built-in ones
+        if let Some(dir) = &config.resources_dir {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path().join(RESOURCE_FILE);
+                let Some(locale) = path.parent().and_then(|dir| dir.file_name()).and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if !path.is_file() {
+                    continue;
+                }
+
+                let locale = locale.to_string();
+                let source = std::fs::read_to_string(&path)?;
+                if !bundles.contains_key(&locale) {
+                    bundles.insert(locale.clone(), bundle(&locale)?);
+                }
+                if let Some(bundle) = bundles.get_mut(&locale) {
+                    bundle.add_resource_overriding(resource(&locale, source)?);
+                }
+            }
+        }
+
+        let mut localizer = Self {
+            bundles,
+            default_locale: FALLBACK_LOCALE.to_string(),
+        };
+        localizer.default_locale = localizer.find(&config.default_locale).ok_or_else(|| {
+            AgentError::Configuration(format!("No messages for default locale {}", config.default_locale))
+        })?;
+
+        Ok(localizer)
+    }
+
+    /// Locales with messages, sorted
+    pub fn locales(&self) -> Vec<String> {
+        let mut locales: Vec<String> = self.bundles.keys().cloned().collect();
+        locales.sort();
+        locales
+    }
+
+    /// The known locale closest to `requested`, or the default
+    ///
+    /// `de` and `de-AT` both resolve to `de-DE` when only that is known.
+    pub fn resolve(&self, requested: Option<&str>) -> String {
+        requested
+            .and_then(|requested| self.find(requested))
+            .unwrap_or_else(|| self.default_locale.clone())
+    }
+
+    /// The known locale matching `requested` exactly or by language
+    fn find(&self, requested: &str) -> Option<String> {
+        if self.bundles.contains_key(requested) {
+            return Some(requested.to_string());
+        }
+
+        let language = requested.split(['-', '_']).next().unwrap_or(requested).to_lowercase();
+        self.locales()
+            .into_iter()
+            .find(|locale| !language.is_empty() && locale.split('-').next() == Some(language.as_str()))
+    }
+
+    /// Message `key` in `locale`, with `args` filled in
+    ///
+    /// Falls back to the default locale, then US English, then the key itself.
+    pub fn text(&self, locale: Option<&str>, key: &str, args: &[(&str, String)]) -> String {
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, value.clone());
+        }
+
+        let resolved = self.resolve(locale);
+        [resolved.as_str(), self.default_locale.as_str(), FALLBACK_LOCALE]
+            .into_iter()
+            .filter_map(|locale| self.bundles.get(locale))
+            .find_map(|bundle| {
+                let pattern = bundle.get_message(key)?.value()?;
+                let mut errors = Vec::new();
+                let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+                if !errors.is_empty() {
+                    tracing::warn!("Errors formatting {} in {}: {:?}", key, resolved, errors);
+                }
+                Some(text.into_owned())
+            })
+            .unwrap_or_else(|| key.to_string())
+    }
+}
+
+fn bundle(locale: &str) -> Result<FluentBundle<FluentResource>> {
+    let language: LanguageIdentifier = locale
+        .parse()
+        .map_err(|e| AgentError::Configuration(format!("Invalid locale {}: {}", locale, e)))?;
+
+    let mut bundle = FluentBundle::new_concurrent(vec![language]);
+    // Isolation marks would end up in prompts and JSON
+    bundle.set_use_isolating(false);
+    Ok(bundle)
+}
+
+fn resource(locale: &str, source: String) -> Result<FluentResource> {
+    FluentResource::try_new(source).map_err(|(_, errors)| invalid(locale, &errors))
+}
+
+fn invalid<E: std::fmt::Debug>(locale: &str, errors: &[E]) -> AgentError {
+    AgentError::Configuration(format!("Invalid messages for {}: {:?}", locale, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn localizer() -> Localizer {
+        Localizer::new(&LocalizationConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_built_in_locales_have_the_same_messages() {
+        // Message definitions are the unindented `key =` lines
+        let keys: Vec<&str> = BUILT_IN[0]
+            .1
+            .lines()
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_lowercase()))
+            .filter_map(|line| line.split_once(" =").map(|(key, _)| key))
+            .collect();
+        assert!(keys.contains(&"system-prompt"));
+
+        let localizer = localizer();
+        for (locale, _) in BUILT_IN {
+            for key in &keys {
+                assert!(
+                    localizer.bundles[*locale].has_message(key),
+                    "{} is missing {}",
+                    locale,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_resolves_and_formats() {
+        let localizer = localizer();
+
+        assert_eq!(localizer.resolve(Some("de")), "de-DE");
+        assert_eq!(localizer.resolve(Some("de_AT")), "de-DE");
+        assert_eq!(localizer.resolve(Some("fr-FR")), "en-US");
+        assert_eq!(localizer.resolve(None), "en-US");
+
+        let args = [("agent", "billing".to_string())];
+        assert_eq!(localizer.text(None, "answered-by-peer", &args), "Answered by peer agent billing");
+        assert_eq!(
+            localizer.text(Some("de"), "answered-by-peer", &args),
+            "Beantwortet vom Partner-Agenten billing"
+        );
+        assert_eq!(localizer.text(Some("de"), "no-such-message", &[]), "no-such-message");
+    }
+}
@@ -2,9 +2,15 @@
 //!
 //! This is the main entry point for running the Alchemist agent service.
 
-use cim_agent_alchemist::{AgentConfig, service};
-use clap::Parser;
-use std::path::PathBuf;
+use cim_agent_alchemist::config::{ConfigFormat, ModelConfig};
+use cim_agent_alchemist::export::{self, ExportFormat};
+use cim_agent_alchemist::import::ImportFormat;
+use cim_agent_alchemist::scaffold::{self, NatsAuthMode, ProviderKind, ScaffoldOptions, StorageKind};
+use cim_agent_alchemist::{AgentClient, AgentConfig, AgentError, artifacts, daemon, eval, migrate, replay, service};
+use clap::{Parser, Subcommand};
+use serde_json::json;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use tracing::error;
 
 /// Command-line arguments for the Alchemist agent
@@ -20,14 +26,30 @@ struct Args {
     #[arg(short, long, value_name = "FILE")]
     config: Option<PathBuf>,
     
+    /// Configuration profile to apply from the config file's `profiles` section
+    #[arg(long, value_name = "NAME", env = cim_agent_alchemist::config::PROFILE_ENV)]
+    profile: Option<String>,
+    
     /// NATS server URL (overrides config)
     #[arg(long, value_name = "URL")]
     nats_url: Option<String>,
     
+    /// Model provider to use (overrides config; ollama, openai, anthropic)
+    #[arg(long, value_name = "PROVIDER")]
+    provider: Option<ProviderKind>,
+    
     /// AI model to use (overrides config)
     #[arg(long, value_name = "MODEL")]
     model: Option<String>,
     
+    /// Generation temperature, 0.0 - 2.0 (overrides config)
+    #[arg(long, value_name = "TEMP", value_parser = parse_temperature)]
+    temperature: Option<f32>,
+    
+    /// Maximum tokens to generate (overrides config)
+    #[arg(long, value_name = "N")]
+    max_tokens: Option<usize>,
+    
     /// Log level (trace, debug, info, warn, error)
     #[arg(long, value_name = "LEVEL", default_value = "info")]
     log_level: String,
@@ -35,25 +57,241 @@ struct Args {
     /// Print default configuration and exit
     #[arg(long)]
     print_config: bool,
+    
+    /// Print the OpenAPI document for the HTTP API and exit
+    #[cfg(feature = "openapi")]
+    #[arg(long)]
+    print_openapi: bool,
+    
+    /// Check a running agent's health and exit non-zero if it is unhealthy
+    #[arg(long)]
+    healthcheck: bool,
+    
+    /// Detach from the terminal and run in the background
+    #[arg(long)]
+    daemon: bool,
+    
+    /// Pidfile for daemon mode (overrides config)
+    #[arg(long, value_name = "FILE")]
+    pidfile: Option<PathBuf>,
+    
+    /// Client subcommand to run against a running agent (runs the agent service if omitted)
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Client subcommands that talk to a running agent over NATS
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Drive guided workflows
+    Workflow {
+        #[command(subcommand)]
+        action: WorkflowAction,
+    },
+    
+    /// Review and archive dialogs
+    Dialog {
+        #[command(subcommand)]
+        action: DialogAction,
+    },
+    
+    /// Ask the agent the questions of an evaluation set and score its answers
+    Eval {
+        /// YAML evaluation set
+        #[arg(value_name = "FILE")]
+        set: PathBuf,
+        
+        /// Have the configured model judge the answers instead of matching words
+        #[arg(long)]
+        judge: bool,
+    },
+    
+    /// Signal a daemonized agent to drain and stop
+    Stop,
+    
+    /// Re-run recorded dialog answers against the configured model and
+    /// report how the answers and token costs change
+    Replay {
+        /// Cases recorded with `replay.record`
+        #[arg(value_name = "FILE")]
+        cases: PathBuf,
+        
+        /// File holding a system prompt to use in place of the recorded one
+        #[arg(long, value_name = "FILE")]
+        system_prompt: Option<PathBuf>,
+    },
+    
+    /// Generate a starter configuration file
+    Init {
+        /// Model provider (ollama, openai, anthropic)
+        #[arg(long)]
+        provider: Option<ProviderKind>,
+        
+        /// NATS authentication mode (none, token, user-password, jwt, creds, nkey, tls)
+        #[arg(long)]
+        nats_auth: Option<NatsAuthMode>,
+        
+        /// Storage backend (memory, jetstream)
+        #[arg(long)]
+        storage: Option<StorageKind>,
+        
+        /// Output format (yaml, toml, json); defaults to the output extension
+        #[arg(long)]
+        format: Option<ConfigFormat>,
+        
+        /// File to write
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        
+        /// Overwrite an existing file
+        #[arg(long)]
+        force: bool,
+    },
+    
+    /// Manage configuration files
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+/// Configuration file actions
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Update a configuration file written for an older release
+    Migrate {
+        /// Configuration file to migrate
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        
+        /// Write the migrated file here instead of replacing FILE, which is
+        /// otherwise kept as FILE.bak
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        
+        /// Print the migrated file instead of writing it
+        #[arg(long, conflicts_with = "output")]
+        dry_run: bool,
+    },
+}
+
+/// Dialog review actions
+#[derive(Subcommand, Debug)]
+enum DialogAction {
+    /// List dialogs known to the agent
+    List,
+    
+    /// Export a dialog transcript
+    Export {
+        /// Dialog ID
+        dialog_id: String,
+        
+        /// Output format (md, json, html, pdf)
+        #[arg(long, default_value = "md")]
+        format: ExportFormat,
+        
+        /// Write to a file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        
+        /// Upload to the configured artifact store instead of stdout
+        #[arg(long, conflicts_with = "output")]
+        store: bool,
+        
+        /// Strip identifiers and redact personal data before export
+        #[arg(long)]
+        anonymize: bool,
+        
+        /// Also redact this term, such as a name (repeatable, implies --anonymize)
+        #[arg(long, value_name = "TERM")]
+        redact: Vec<String>,
+    },
+    
+    /// Import a chat exported from another assistant as a new dialog
+    Import {
+        /// JSON export file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        
+        /// Export format (openai, chatgpt, anthropic, claude); detected if omitted
+        #[arg(long)]
+        format: Option<ImportFormat>,
+    },
+}
+
+/// Workflow management actions
+#[derive(Subcommand, Debug)]
+enum WorkflowAction {
+    /// List workflows known to the agent
+    List,
+    
+    /// Start a new guided workflow
+    Start {
+        /// Workflow type to start
+        #[arg(value_parser = ["create_agent", "implement_domain", "add_event"])]
+        workflow_type: String,
+        
+        /// Name of the domain an implement_domain workflow scaffolds
+        #[arg(long)]
+        domain: Option<String>,
+    },
+    
+    /// Show the status of a workflow
+    Status {
+        /// Workflow ID
+        workflow_id: String,
+    },
+    
+    /// Advance a workflow to its next step
+    Advance {
+        /// Workflow ID
+        workflow_id: String,
+    },
+    
+    /// Download the scaffold files a domain workflow stored so far
+    Download {
+        /// Workflow ID
+        workflow_id: String,
+        
+        /// Directory to write the domain crate to
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        output: PathBuf,
+    },
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
     
     // Print default config if requested
     if args.print_config {
         let default_config = AgentConfig::default();
-        let yaml = serde_yaml::to_string(&default_config)?;
-        println!("{}", yaml);
+        println!("{}", default_config.render(ConfigFormat::Yaml)?);
         return Ok(());
     }
     
+    #[cfg(feature = "openapi")]
+    if args.print_openapi {
+        println!("{}", cim_agent_alchemist::api::openapi_json()?);
+        return Ok(());
+    }
+    
+    // Scaffolding does not need an existing configuration
+    if let Some(Command::Init { provider, nats_auth, storage, format, output, force }) = args.command {
+        return run_init(provider, nats_auth, storage, format, output, force);
+    }
+    
+    // Migrating reads the old file itself, which may no longer load
+    if let Some(Command::Config { action: ConfigAction::Migrate { file, output, dry_run } }) = args.command {
+        return run_migrate(&file, output, dry_run);
+    }
+    
     // Load configuration
-    let mut config = if let Some(config_path) = args.config {
-        load_config_from_file(config_path)?
-    } else {
-        AgentConfig::default()
+    let mut config = match (args.config, args.profile.as_deref()) {
+        (Some(config_path), profile) => AgentConfig::from_file(&config_path, profile)?,
+        (None, Some(profile)) => {
+            return Err(format!("Profile '{}' requires a --config file", profile).into());
+        }
+        (None, None) => AgentConfig::default(),
     };
     
     // Apply command-line overrides
@@ -61,16 +299,96 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         config.nats.servers = vec![nats_url];
     }
     
-    if let Some(model) = args.model {
-        if let cim_agent_alchemist::ModelConfig::Ollama { ref mut model as m, .. } = &mut config.model {
-            *m = model;
+    // Switching provider starts from that provider's defaults
+    if let Some(provider) = args.provider {
+        if config.model.provider() != provider {
+            let api_key = match provider {
+                ProviderKind::OpenAI => std::env::var("OPENAI_API_KEY").ok(),
+                ProviderKind::Anthropic => std::env::var("ANTHROPIC_API_KEY").ok(),
+                ProviderKind::Ollama => None,
+            };
+            config.model = ModelConfig::for_provider(provider, api_key);
         }
     }
     
+    if let Some(model) = args.model {
+        config.model.set_model(model);
+    }
+    
+    if let Some(temperature) = args.temperature {
+        config.model.set_temperature(temperature);
+    }
+    
+    if let Some(max_tokens) = args.max_tokens {
+        config.model.set_max_tokens(max_tokens);
+    }
+    
     config.service.logging.level = args.log_level;
     
+    let pid_file = args
+        .pidfile
+        .or_else(|| config.service.pid_file.clone().map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from(daemon::DEFAULT_PID_FILE));
+    
+    // Probes from container HEALTHCHECK or systemd only need NATS
+    if args.healthcheck {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        return runtime.block_on(run_healthcheck(&config));
+    }
+    
+    // Stopping only needs the pidfile
+    if let Some(Command::Stop) = args.command {
+        let pid = daemon::stop(&pid_file)?;
+        println!("Sent shutdown signal to agent (pid {})", pid);
+        return Ok(());
+    }
+    
+    // Detaching must happen before the runtime spawns its threads
+    let daemonized = args.daemon && args.command.is_none();
+    if daemonized {
+        print_banner();
+        println!("Starting in daemon mode (pidfile: {})", pid_file.display());
+        let log_file = config.service.logging.file.as_ref().map(PathBuf::from);
+        daemon::daemonize(&pid_file, log_file.as_deref())?;
+    }
+    
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    
+    let result = runtime.block_on(async_main(args.command, daemonized, config));
+    
+    // Only the daemon owns the pidfile; a client command run with
+    // `--daemon` must not remove the running agent's
+    if daemonized {
+        daemon::remove_pid_file(&pid_file)?;
+    }
+    
+    result
+}
+
+/// Run either a client subcommand or the agent service
+async fn async_main(
+    command: Option<Command>,
+    daemonized: bool,
+    config: AgentConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Replays only need the model
+    if let Some(Command::Replay { cases, system_prompt }) = command {
+        return run_replay(&cases, system_prompt.as_deref(), &config).await;
+    }
+    
+    // Client subcommands talk to an already running agent
+    if let Some(command) = command {
+        return run_client_command(command, config).await;
+    }
+    
     // Print startup banner
-    print_banner();
+    if !daemonized {
+        print_banner();
+    }
     
     // Run the service
     match service::run(config).await {
@@ -85,28 +403,320 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
-/// Load configuration from file
-fn load_config_from_file(path: PathBuf) -> Result<AgentConfig, Box<dyn std::error::Error>> {
-    let contents = std::fs::read_to_string(&path)?;
+/// Longest a health check may take, connecting included
+const HEALTHCHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Print the agent's health, failing unless it is running normally
+async fn run_healthcheck(config: &AgentConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let health = tokio::time::timeout(HEALTHCHECK_TIMEOUT, async {
+        let client = AgentClient::connect(&config.nats).await?.with_timeout(HEALTHCHECK_TIMEOUT);
+        client.health().await
+    })
+    .await
+    .map_err(|_| AgentError::Timeout("Health check".to_string()))??;
     
-    let config = if path.extension().map_or(false, |ext| ext == "yaml" || ext == "yml") {
-        serde_yaml::from_str(&contents)?
-    } else if path.extension().map_or(false, |ext| ext == "json") {
-        serde_json::from_str(&contents)?
-    } else if path.extension().map_or(false, |ext| ext == "toml") {
-        toml::from_str(&contents)?
-    } else {
-        // Try to detect format
-        if contents.trim_start().starts_with('{') {
-            serde_json::from_str(&contents)?
-        } else if contents.contains(':') && !contents.contains('=') {
-            serde_yaml::from_str(&contents)?
-        } else {
-            toml::from_str(&contents)?
+    println!("{}", serde_json::to_string_pretty(&health)?);
+    if health.status != "Running" {
+        return Err(format!("Agent is {}", health.status).into());
+    }
+    Ok(())
+}
+
+/// Run a client subcommand and print its result as JSON
+async fn run_client_command(command: Command, config: AgentConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let client = AgentClient::connect(&config.nats).await?;
+    #[cfg(feature = "signing")]
+    let client = match std::env::var("ALCHEMIST_SIGNING_KEY") {
+        Ok(key) => client.with_signing_key(cim_agent_alchemist::signing::signing_key(&key)?),
+        Err(_) => client,
+    };
+    
+    let result = match command {
+        Command::Workflow { action: WorkflowAction::Download { workflow_id, output } } => {
+            return download_scaffold(&workflow_id, &output, &config).await
         }
+        Command::Workflow { action } => run_workflow_action(&client, action).await?,
+        Command::Dialog { action } => return run_dialog_action(&client, action, &config).await,
+        Command::Eval { set, judge } => return run_eval(&client, &set, judge, &config).await,
+        Command::Stop | Command::Init { .. } | Command::Config { .. } | Command::Replay { .. } => {
+            unreachable!("handled without connecting to an agent")
+        }
+    };
+    
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    Ok(())
+}
+
+/// Score the agent's answers to an evaluation set, failing if any case fails
+async fn run_eval(
+    client: &AgentClient,
+    set: &Path,
+    judge: bool,
+    config: &AgentConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let set = eval::load_set(set)?;
+    let judge = if judge { Some(cim_agent_alchemist::model::create_provider(&config.model)?) } else { None };
+    let scorer = match &judge {
+        Some(provider) => eval::Scorer::Judge(provider.as_ref()),
+        None => eval::Scorer::Heuristic,
+    };
+    
+    // Each question gets a dialog of its own, so earlier answers cannot help
+    let ask = |case: &eval::EvalCase| {
+        let dialog_id = format!("eval-{}", uuid::Uuid::new_v4());
+        let question = case.question.clone();
+        async move { client.dialog(&dialog_id, question).await }
     };
+    let report = eval::run(&set, ask, &scorer).await;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    
+    if report.failed > 0 {
+        return Err(format!("{} of {} cases failed", report.failed, report.cases.len()).into());
+    }
+    Ok(())
+}
+
+/// Replay recorded cases and print the report as JSON
+async fn run_replay(
+    cases: &Path,
+    system_prompt: Option<&Path>,
+    config: &AgentConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cases = replay::load_cases(cases)?;
+    let system_prompt = system_prompt.map(std::fs::read_to_string).transpose()?;
+    let provider = cim_agent_alchemist::model::create_provider(&config.model)?;
+    
+    let report = replay::replay(&cases, provider.as_ref(), system_prompt.as_deref()).await;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+/// List or export dialogs
+async fn run_dialog_action(
+    client: &AgentClient,
+    action: DialogAction,
+    config: &AgentConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        DialogAction::List => {
+            let result = client.query("list_dialogs", json!({})).await?;
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+        DialogAction::Export { dialog_id, format, output, store, anonymize, redact } => {
+            let mut history = client
+                .query_all("get_dialog_history", json!({ "dialog_id": dialog_id }), "history")
+                .await?;
+            if anonymize || !redact.is_empty() {
+                history = cim_agent_alchemist::anonymize::anonymize(&history, &redact);
+            }
+            let rendered = export::export_dialog(&history, format).await?;
+            
+            if store {
+                let backend = config.storage.artifacts.as_ref().ok_or_else(|| {
+                    AgentError::Configuration("No artifact store configured under storage.artifacts".to_string())
+                })?;
+                // Anonymized exports are stored under their pseudonym
+                let name = history["dialog_id"].as_str().unwrap_or(&dialog_id);
+                let key = artifacts::export_key(name, format.extension());
+                artifacts::open(backend, &config.nats).await?.put(&key, rendered).await?;
+                println!("Stored {}", key);
+                return Ok(());
+            }
+            
+            match output {
+                Some(path) => std::fs::write(path, rendered)?,
+                None => std::io::stdout().write_all(&rendered)?,
+            }
+        }
+        DialogAction::Import { file, format } => {
+            let export: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+            let format = format.map(|format| format.name());
+            let result = client
+                .command("import_dialog", json!({ "export": export, "format": format }))
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+    }
+    
+    Ok(())
+}
+
+/// Map a workflow action onto the agent's workflow commands and queries
+async fn run_workflow_action(
+    client: &AgentClient,
+    action: WorkflowAction,
+) -> cim_agent_alchemist::Result<serde_json::Value> {
+    match action {
+        WorkflowAction::List => client.query("list_workflows", json!({})).await,
+        WorkflowAction::Start { workflow_type, domain } => {
+            client
+                .command("guide_workflow", json!({ "workflow_type": workflow_type, "domain": domain }))
+                .await
+        }
+        WorkflowAction::Status { workflow_id } => {
+            client.query("get_workflow_status", json!({ "workflow_id": workflow_id })).await
+        }
+        WorkflowAction::Advance { workflow_id } => {
+            client.command("advance_workflow", json!({ "workflow_id": workflow_id })).await
+        }
+        WorkflowAction::Download { .. } => unreachable!("read from the artifact store directly"),
+    }
+}
+
+/// Write the scaffold files a domain workflow stored under `output`
+async fn download_scaffold(
+    workflow_id: &str,
+    output: &Path,
+    config: &AgentConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let backend = config.storage.artifacts.as_ref().ok_or_else(|| {
+        AgentError::Configuration("No artifact store configured under storage.artifacts".to_string())
+    })?;
+    let store = artifacts::open(backend, &config.nats).await?;
+    
+    let prefix = artifacts::scaffold_prefix(workflow_id);
+    let keys = store.list(&prefix).await?;
+    if keys.is_empty() {
+        return Err(format!("No scaffold stored for workflow {}", workflow_id).into());
+    }
     
-    Ok(config)
+    for key in keys {
+        let Some(data) = store.get(&key).await? else {
+            continue;
+        };
+        let path = output.join(key.trim_start_matches(&prefix));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, data)?;
+        println!("Wrote {}", path.display());
+    }
+    
+    Ok(())
+}
+
+/// Generate a configuration file, prompting for choices not given as flags
+fn run_init(
+    provider: Option<ProviderKind>,
+    nats_auth: Option<NatsAuthMode>,
+    storage: Option<StorageKind>,
+    format: Option<ConfigFormat>,
+    output: Option<PathBuf>,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let interactive = std::io::stdin().is_terminal();
+    
+    let options = ScaffoldOptions {
+        provider: choose(provider, "Model provider", ProviderKind::NAMES, interactive)?,
+        nats_auth: choose(nats_auth, "NATS authentication", NatsAuthMode::NAMES, interactive)?,
+        storage: choose(storage, "Storage backend", StorageKind::NAMES, interactive)?,
+    };
+    
+    let format = match (format, &output) {
+        (Some(format), _) => format,
+        (None, Some(path)) => ConfigFormat::from_path(path).unwrap_or(ConfigFormat::Yaml),
+        (None, None) => choose(None, "File format", &["yaml", "toml", "json"], interactive)?,
+    };
+    
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("alchemist.{}", format.extension())));
+    if output.exists() && !force {
+        return Err(format!("{} already exists (use --force to overwrite)", output.display()).into());
+    }
+    
+    let config = scaffold::build_config(&options);
+    std::fs::write(&output, config.render(format)?)?;
+    
+    println!("Wrote {}", output.display());
+    println!("Replace the <PLACEHOLDER> values before starting the agent.");
+    Ok(())
+}
+
+/// Migrate a configuration file to the current schema, reporting each
+/// change and warning about dropped settings
+fn run_migrate(file: &Path, output: Option<PathBuf>, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(file)?;
+    let format = ConfigFormat::from_path(file).unwrap_or_else(|| ConfigFormat::detect(&contents));
+    let migrated = migrate::migrate(format.parse_value(&contents)?)?;
+    
+    for change in &migrated.changes {
+        eprintln!("{}", change);
+    }
+    for warning in &migrated.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    
+    let rendered = format.render(&migrated.document)?;
+    if dry_run {
+        print!("{}", rendered);
+        return Ok(());
+    }
+    if migrated.is_current() {
+        println!("{} is already at config version {}", file.display(), migrated.from_version);
+        return Ok(());
+    }
+    
+    let output = match output {
+        Some(output) => output,
+        None => {
+            let backup = PathBuf::from(format!("{}.bak", file.display()));
+            std::fs::copy(file, &backup)?;
+            println!("Kept the original as {}", backup.display());
+            file.to_path_buf()
+        }
+    };
+    std::fs::write(&output, rendered)?;
+    
+    println!(
+        "Migrated {} from config version {} to {}",
+        output.display(),
+        migrated.from_version,
+        cim_agent_alchemist::config::CONFIG_VERSION
+    );
+    Ok(())
+}
+
+/// Use the flag value if given, otherwise prompt (or take the first choice)
+fn choose<T>(
+    value: Option<T>,
+    label: &str,
+    names: &[&str],
+    interactive: bool,
+) -> Result<T, Box<dyn std::error::Error>>
+where
+    T: std::str::FromStr<Err = cim_agent_alchemist::AgentError>,
+{
+    if let Some(value) = value {
+        return Ok(value);
+    }
+    
+    let default = names[0];
+    if !interactive {
+        return Ok(default.parse()?);
+    }
+    
+    loop {
+        print!("{} [{}] ({}): ", label, names.join("/"), default);
+        std::io::stdout().flush()?;
+        
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        let answer = answer.trim();
+        
+        match if answer.is_empty() { default } else { answer }.parse() {
+            Ok(value) => return Ok(value),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+/// Parse and range-check a temperature flag
+fn parse_temperature(value: &str) -> Result<f32, String> {
+    let temperature: f32 = value.parse().map_err(|e| format!("{}", e))?;
+    if (0.0..=2.0).contains(&temperature) {
+        Ok(temperature)
+    } else {
+        Err(format!("temperature must be between 0.0 and 2.0, got {}", temperature))
+    }
 }
 
 /// Print startup banner
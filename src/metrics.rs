@@ -0,0 +1,208 @@
+//! Metrics on whether knowledge retrieval helps answers
+//!
+//! Every retrieval for a dialog message or `explain_concept` counts, with
+//! how many excerpts it found and how closely they matched: the share of
+//! query terms on the matching line. Every answer given with the model
+//! counts too, split by whether excerpts were offered and whether the
+//! answer cites one by number. A high hit rate with few citing answers
+//! means the model ignores what it is given.
+//!
+//! Dialogs ended for inactivity are counted as well.
+//!
+//! The counts are served on the `metrics` NATS subject as JSON and, when
+//! the HTTP server runs, on `service.metrics.endpoint` in the Prometheus
+//! text format.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters since the agent started
+#[derive(Debug, Default)]
+pub struct RetrievalMetrics {
+    retrievals: AtomicU64,
+    hits: AtomicU64,
+    excerpts: AtomicU64,
+
+    /// Sum of excerpt similarities, in thousandths
+    similarity: AtomicU64,
+
+    answers: AtomicU64,
+    offered: AtomicU64,
+    citing: AtomicU64,
+}
+
+/// Retrieval metrics at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RetrievalSnapshot {
+    pub retrievals: u64,
+
+    /// Retrievals finding at least one excerpt
+    pub hits: u64,
+    pub hit_rate: f64,
+
+    pub excerpts: u64,
+
+    /// Mean similarity of the excerpts found, from 0 to 1
+    pub average_similarity: f64,
+
+    /// Answers given with the model
+    pub answers: u64,
+
+    /// Answers whose prompt offered excerpts
+    pub answers_with_excerpts: u64,
+
+    /// Answers citing an excerpt by number
+    pub answers_citing: u64,
+
+    /// Share of answers citing an excerpt, against pure model output
+    pub citation_rate: f64,
+}
+
+impl RetrievalMetrics {
+    /// Count a retrieval that found excerpts matching this closely
+    pub fn record_retrieval(&self, similarities: &[f32]) {
+        self.retrievals.fetch_add(1, Ordering::Relaxed);
+        if !similarities.is_empty() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        self.excerpts.fetch_add(similarities.len() as u64, Ordering::Relaxed);
+        let thousandths: f32 = similarities.iter().map(|similarity| similarity.clamp(0.0, 1.0) * 1000.0).sum();
+        self.similarity.fetch_add(thousandths.round() as u64, Ordering::Relaxed);
+    }
+
+    /// Count an answer given with `excerpts` numbered excerpts in its prompt
+    pub fn record_answer(&self, excerpts: usize, answer: &str) {
+        self.answers.fetch_add(1, Ordering::Relaxed);
+        if excerpts > 0 {
+            self.offered.fetch_add(1, Ordering::Relaxed);
+        }
+        if cites(answer, excerpts) {
+            self.citing.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> RetrievalSnapshot {
+        let retrievals = self.retrievals.load(Ordering::Relaxed);
+        let hits = self.hits.load(Ordering::Relaxed);
+        let excerpts = self.excerpts.load(Ordering::Relaxed);
+        let answers = self.answers.load(Ordering::Relaxed);
+        let citing = self.citing.load(Ordering::Relaxed);
+        let ratio = |part: u64, whole: u64| if whole == 0 { 0.0 } else { part as f64 / whole as f64 };
+
+        RetrievalSnapshot {
+            retrievals,
+            hits,
+            hit_rate: ratio(hits, retrievals),
+            excerpts,
+            average_similarity: ratio(self.similarity.load(Ordering::Relaxed), excerpts) / 1000.0,
+            answers,
+            answers_with_excerpts: self.offered.load(Ordering::Relaxed),
+            answers_citing: citing,
+            citation_rate: ratio(citing, answers),
+        }
+    }
+
+    /// The metrics in the Prometheus text format
+    pub fn prometheus(&self) -> String {
+        let snapshot = self.snapshot();
+        let metrics: [(&str, &str, &str, f64); 9] = [
+            ("alchemist_retrievals_total", "counter", "Retrievals run for answers", snapshot.retrievals as f64),
+            ("alchemist_retrieval_hits_total", "counter", "Retrievals finding at least one excerpt", snapshot.hits as f64),
+            ("alchemist_retrieval_hit_rate", "gauge", "Share of retrievals finding excerpts", snapshot.hit_rate),
+            ("alchemist_retrieved_excerpts_total", "counter", "Excerpts retrieved", snapshot.excerpts as f64),
+            ("alchemist_retrieval_similarity_average", "gauge", "Mean similarity of retrieved excerpts", snapshot.average_similarity),
+            ("alchemist_answers_total", "counter", "Answers given with the model", snapshot.answers as f64),
+            ("alchemist_answers_with_excerpts_total", "counter", "Answers whose prompt offered excerpts", snapshot.answers_with_excerpts as f64),
+            ("alchemist_answers_citing_total", "counter", "Answers citing a retrieved excerpt", snapshot.answers_citing as f64),
+            ("alchemist_answer_citation_rate", "gauge", "Share of answers citing a retrieved excerpt", snapshot.citation_rate),
+        ];
+
+        prometheus_text(&metrics)
+    }
+}
+
+/// Dialog counters since the agent started
+#[derive(Debug, Default)]
+pub struct DialogMetrics {
+    expired: AtomicU64,
+}
+
+/// Dialog metrics at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DialogSnapshot {
+    /// Dialogs ended and dropped from memory for inactivity
+    pub expired: u64,
+}
+
+impl DialogMetrics {
+    pub fn record_expired(&self) {
+        self.expired.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> DialogSnapshot {
+        DialogSnapshot {
+            expired: self.expired.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The metrics in the Prometheus text format
+    pub fn prometheus(&self) -> String {
+        let snapshot = self.snapshot();
+        prometheus_text(&[(
+            "alchemist_dialogs_expired_total",
+            "counter",
+            "Dialogs ended for inactivity",
+            snapshot.expired as f64,
+        )])
+    }
+}
+
+/// Render `(name, type, help, value)` metrics in the Prometheus text format
+fn prometheus_text(metrics: &[(&str, &str, &str, f64)]) -> String {
+    let mut text = String::new();
+    for (name, kind, help, value) in metrics {
+        text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name, value));
+    }
+    text
+}
+
+/// Whether `answer` refers to one of `excerpts` numbered excerpts, as `[2]`
+fn cites(answer: &str, excerpts: usize) -> bool {
+    (1..=excerpts).any(|number| answer.contains(&format!("[{}]", number)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_rates() {
+        let metrics = RetrievalMetrics::default();
+        metrics.record_retrieval(&[1.0, 0.5]);
+        metrics.record_retrieval(&[]);
+        metrics.record_answer(2, "Events are the source of truth [2].");
+        metrics.record_answer(2, "Events are the source of truth.");
+        metrics.record_answer(0, "I think [1] is right.");
+
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.retrievals, snapshot.hits, snapshot.excerpts), (2, 1, 2));
+        assert_eq!(snapshot.hit_rate, 0.5);
+        assert_eq!(snapshot.average_similarity, 0.75);
+        assert_eq!((snapshot.answers, snapshot.answers_with_excerpts, snapshot.answers_citing), (3, 2, 1));
+    }
+
+    #[test]
+    fn test_prometheus_format() {
+        let metrics = RetrievalMetrics::default();
+        metrics.record_retrieval(&[0.25]);
+
+        let text = metrics.prometheus();
+        assert!(text.contains("# TYPE alchemist_retrievals_total counter\nalchemist_retrievals_total 1\n"));
+        assert!(text.contains("alchemist_retrieval_similarity_average 0.25\n"));
+        assert!(text.contains("alchemist_answer_citation_rate 0\n"));
+
+        let dialogs = DialogMetrics::default();
+        dialogs.record_expired();
+        assert!(dialogs.prometheus().contains("# TYPE alchemist_dialogs_expired_total counter\nalchemist_dialogs_expired_total 1\n"));
+    }
+}
@@ -0,0 +1,268 @@
+//! Migration of configuration files written for older releases
+//!
+//! A file's `config_version` says which schema it follows; files without
+//! one predate versioning and follow version 1. Each migration moves a
+//! document one version forward: settings that were renamed or moved are
+//! carried to their new place, values whose form changed are rewritten,
+//! and deprecated settings are dropped with a warning. Profiles hold the
+//! same settings as the base, so they are migrated along with it. The
+//! result must load before `alchemist config migrate` writes it.
+
+use serde_json::Value;
+
+use crate::config::{AgentConfig, CONFIG_VERSION};
+use crate::error::{AgentError, Result};
+
+/// One change to the schema, at dotted paths such as `service.metrics.endpoint`
+pub enum Change {
+    /// A setting moved from `from` to `to`
+    Rename { from: &'static str, to: &'static str },
+
+    /// A setting whose value changed form; `rewrite` gives the new value,
+    /// or `None` when it is already in the new form
+    Rewrite {
+        path: &'static str,
+        rewrite: fn(&Value) -> Option<Value>,
+        note: &'static str,
+    },
+
+    /// A setting that no longer has any effect
+    Deprecated { path: &'static str, reason: &'static str },
+}
+
+/// Changes bringing a document up to `version` from the one before
+pub struct Migration {
+    pub version: u32,
+    pub changes: &'static [Change],
+}
+
+/// Every migration, oldest first
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version: 2,
+    changes: &[
+        Change::Rewrite {
+            path: "model.provider",
+            rewrite: provider_name,
+            note: "provider names are case-sensitive",
+        },
+        Change::Deprecated {
+            path: "service.metrics.push_gateway",
+            reason: "metrics are only served for scraping on service.metrics.endpoint",
+        },
+    ],
+}];
+
+/// A migrated document and what was done to it
+#[derive(Debug, Clone)]
+pub struct Migrated {
+    pub document: Value,
+    pub from_version: u32,
+
+    /// Settings renamed or rewritten
+    pub changes: Vec<String>,
+
+    /// Deprecated settings dropped
+    pub warnings: Vec<String>,
+}
+
+impl Migrated {
+    pub fn is_current(&self) -> bool {
+        self.from_version == CONFIG_VERSION
+    }
+}
+
+/// Bring a parsed configuration document up to [`CONFIG_VERSION`]
+///
+/// Fails on a document written for a newer release, or one that does not
+/// load once migrated.
+pub fn migrate(mut document: Value) -> Result<Migrated> {
+    let from_version = match document.get("config_version") {
+        None => 1,
+        Some(version) => version
+            .as_u64()
+            .map(|version| version as u32)
+            .ok_or_else(|| AgentError::Configuration(format!("config_version must be a number, not {}", version)))?,
+    };
+    if from_version > CONFIG_VERSION {
+        return Err(AgentError::Configuration(format!(
+            "Configuration is for version {} but this release reads version {}; upgrade the agent instead",
+            from_version, CONFIG_VERSION
+        )));
+    }
+
+    let mut migrated = Migrated {
+        document: Value::Null,
+        from_version,
+        changes: Vec::new(),
+        warnings: Vec::new(),
+    };
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > from_version) {
+        apply(&mut document, migration.changes, "", &mut migrated);
+
+        if let Some(profiles) = document.get_mut("profiles").and_then(Value::as_object_mut) {
+            for (name, profile) in profiles.iter_mut() {
+                apply(profile, migration.changes, &format!("profiles.{}.", name), &mut migrated);
+            }
+        }
+    }
+    if let Some(root) = document.as_object_mut() {
+        root.insert("config_version".to_string(), CONFIG_VERSION.into());
+    }
+
+    // The base and every profile have to load
+    AgentConfig::from_value(document.clone(), None)?;
+    let profiles: Vec<String> = document
+        .get("profiles")
+        .and_then(Value::as_object)
+        .map(|profiles| profiles.keys().cloned().collect())
+        .unwrap_or_default();
+    for profile in profiles {
+        AgentConfig::from_value(document.clone(), Some(&profile))?;
+    }
+
+    migrated.document = document;
+    Ok(migrated)
+}
+
+/// Apply `changes` to `document`, whose settings sit under `prefix` in the file
+fn apply(document: &mut Value, changes: &[Change], prefix: &str, migrated: &mut Migrated) {
+    for change in changes {
+        match change {
+            Change::Rename { from, to } => {
+                if let Some(value) = take(document, from) {
+                    if get(document, to).is_some() {
+                        migrated.warnings.push(format!(
+                            "{}{} was dropped in favour of the {}{} already set",
+                            prefix, from, prefix, to
+                        ));
+                    } else {
+                        put(document, to, value);
+                        migrated.changes.push(format!("{}{} is now {}{}", prefix, from, prefix, to));
+                    }
+                }
+            }
+            Change::Rewrite { path, rewrite, note } => {
+                if let Some(value) = get(document, path).and_then(rewrite) {
+                    put(document, path, value);
+                    migrated.changes.push(format!("{}{} was rewritten: {}", prefix, path, note));
+                }
+            }
+            Change::Deprecated { path, reason } => {
+                if take(document, path).is_some() {
+                    migrated.warnings.push(format!("{}{} was dropped: {}", prefix, path, reason));
+                }
+            }
+        }
+    }
+}
+
+fn get<'a>(document: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(document, |value, key| value.get(key))
+}
+
+/// Remove the setting at `path`, returning it
+fn take(document: &mut Value, path: &str) -> Option<Value> {
+    let (parent, key) = match path.rsplit_once('.') {
+        Some((parent, key)) => (parent.split('.').try_fold(document, |value, key| value.get_mut(key))?, key),
+        None => (document, path),
+    };
+    parent.as_object_mut()?.remove(key)
+}
+
+/// Set the setting at `path`, creating the sections above it
+fn put(document: &mut Value, path: &str, value: Value) {
+    let mut keys: Vec<&str> = path.split('.').collect();
+    let last = keys.pop().unwrap_or(path);
+
+    let mut section = document;
+    for key in keys {
+        let Some(object) = section.as_object_mut() else {
+            return;
+        };
+        section = object
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+    if let Some(object) = section.as_object_mut() {
+        object.insert(last.to_string(), value);
+    }
+}
+
+/// `model.provider` spelled as the configuration expects, such as `Ollama`
+/// for `ollama`
+fn provider_name(value: &Value) -> Option<Value> {
+    let name = value.as_str()?;
+    let canonical = ["Ollama", "OpenAI", "Anthropic"]
+        .into_iter()
+        .find(|canonical| canonical.eq_ignore_ascii_case(name))?;
+    (canonical != name).then(|| canonical.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unversioned() -> Value {
+        let mut document = serde_json::to_value(AgentConfig::default()).unwrap();
+        let root = document.as_object_mut().unwrap();
+        root.remove("config_version");
+        document["model"]["provider"] = "ollama".into();
+        document["service"]["metrics"]["push_gateway"] = "http://gateway:9091".into();
+        document["profiles"] = serde_json::json!({
+            "prod": { "model": { "provider": "OLLAMA" } },
+        });
+        document
+    }
+
+    #[test]
+    fn test_migrate_unversioned_document() {
+        let migrated = migrate(unversioned()).unwrap();
+        assert_eq!(migrated.from_version, 1);
+        assert!(!migrated.is_current());
+        assert_eq!(migrated.document["config_version"], CONFIG_VERSION);
+        assert_eq!(migrated.document["model"]["provider"], "Ollama");
+        assert_eq!(migrated.document["profiles"]["prod"]["model"]["provider"], "Ollama");
+        assert!(get(&migrated.document, "service.metrics.push_gateway").is_none());
+        assert_eq!(migrated.changes.len(), 2);
+        assert_eq!(migrated.warnings.len(), 1);
+
+        // Migrating again changes nothing
+        let again = migrate(migrated.document).unwrap();
+        assert!(again.is_current());
+        assert!(again.changes.is_empty() && again.warnings.is_empty());
+
+        let mut newer = unversioned();
+        newer["config_version"] = (CONFIG_VERSION + 1).into();
+        assert!(migrate(newer).is_err());
+    }
+
+    #[test]
+    fn test_rename_moves_settings() {
+        const CHANGES: &[Change] = &[
+            Change::Rename { from: "service.pid_file", to: "daemon.pid_file" },
+            Change::Rename { from: "service.port", to: "http.port" },
+        ];
+        let mut document = serde_json::json!({
+            "service": { "pid_file": "/run/alchemist.pid", "port": 8080 },
+            "http": { "port": 9090 },
+        });
+        let mut migrated = Migrated {
+            document: Value::Null,
+            from_version: 1,
+            changes: Vec::new(),
+            warnings: Vec::new(),
+        };
+
+        apply(&mut document, CHANGES, "", &mut migrated);
+        assert_eq!(
+            document,
+            serde_json::json!({
+                "service": {},
+                "daemon": { "pid_file": "/run/alchemist.pid" },
+                "http": { "port": 9090 },
+            })
+        );
+        assert_eq!(migrated.changes, vec!["service.pid_file is now daemon.pid_file"]);
+        assert_eq!(migrated.warnings.len(), 1);
+    }
+}
@@ -2,6 +2,8 @@
 
 use crate::error::{AgentError, Result};
 use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use std::collections::HashMap;
@@ -19,13 +21,112 @@ pub trait ModelProvider: Send + Sync {
         context: &[Message],
     ) -> Result<String>;
 
+    /// Generate with conversation context, yielding output as it is produced
+    ///
+    /// Providers without native streaming return the full response as a
+    /// single final chunk.
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        context: &[Message],
+    ) -> Result<ChunkStream> {
+        let content = self.generate_with_context(prompt, context).await?;
+        Ok(Box::pin(futures::stream::once(async move {
+            Ok(ResponseChunk { content, done: true })
+        })))
+    }
+
+    /// Generate with conversation context, letting the model call `tools`
+    ///
+    /// `exchanges` holds the calls made so far in this turn with their
+    /// output. Providers without function calling answer without tools.
+    async fn generate_with_tools(
+        &self,
+        prompt: &str,
+        context: &[Message],
+        _tools: &[ToolSpec],
+        _exchanges: &[ToolExchange],
+    ) -> Result<ModelTurn> {
+        Ok(ModelTurn::Text(self.generate_with_context(prompt, context).await?))
+    }
+
+    /// Embed `text` as a vector, for similarity search
+    ///
+    /// Providers without embeddings fail.
+    async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+        Err(AgentError::ModelError(format!(
+            "{} does not support embeddings",
+            self.model_info().provider
+        )))
+    }
+
     /// Check if the model is available
     async fn health_check(&self) -> Result<()>;
 
+    /// Names of the models this provider can switch to
+    ///
+    /// Providers without a model catalog report only their current model.
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(vec![self.model_info().model])
+    }
+
     /// Get model information
     fn model_info(&self) -> ModelInfo;
 }
 
+/// Stream of partial model output
+pub type ChunkStream = BoxStream<'static, Result<ResponseChunk>>;
+
+/// A piece of streamed model output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseChunk {
+    /// Text generated since the previous chunk
+    pub content: String,
+
+    /// Whether this is the last chunk
+    pub done: bool,
+}
+
+/// A tool the model may call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolSpec {
+    /// Name the model calls the tool by
+    pub name: String,
+
+    /// What the tool does, for the model
+    pub description: String,
+
+    /// JSON schema of the arguments
+    pub parameters: serde_json::Value,
+}
+
+/// A tool call requested by the model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Tool name
+    pub name: String,
+
+    /// Arguments matching the tool's schema
+    pub arguments: serde_json::Value,
+}
+
+/// A tool call and what the tool returned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolExchange {
+    pub call: ToolCall,
+    pub output: String,
+}
+
+/// What the model produced when offered tools
+#[derive(Debug, Clone)]
+pub enum ModelTurn {
+    /// A final answer
+    Text(String),
+
+    /// Tools to call before the model can answer
+    ToolCalls(Vec<ToolCall>),
+}
+
 /// Request to send to the AI model
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelRequest {
@@ -114,7 +215,7 @@ impl Default for GenerationParameters {
 }
 
 /// Token usage information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct TokenUsage {
     /// Tokens in the prompt
     pub prompt_tokens: usize,
@@ -167,6 +268,9 @@ pub struct OllamaProvider {
     base_url: String,
     model: String,
     options: HashMap<String, serde_json::Value>,
+
+    /// Model for embeddings, when not the chat model
+    embedding_model: Option<String>,
 }
 
 impl OllamaProvider {
@@ -177,8 +281,112 @@ impl OllamaProvider {
             base_url,
             model,
             options,
+            embedding_model: None,
         }
     }
+
+    /// Embed with `model` instead of the chat model
+    pub fn with_embedding_model(mut self, model: Option<String>) -> Self {
+        self.embedding_model = model;
+        self
+    }
+}
+
+impl OllamaProvider {
+    /// Build a chat request from the prompt and conversation context
+    fn chat_request(&self, prompt: &str, context: &[Message], stream: bool) -> OllamaChatRequest {
+        let mut messages: Vec<OllamaMessage> = context
+            .iter()
+            .map(|m| OllamaMessage {
+                role: m.role.clone(),
+                content: m.content.clone(),
+                tool_calls: Vec::new(),
+            })
+            .collect();
+
+        messages.push(OllamaMessage {
+            role: "user".to_string(),
+            content: prompt.to_string(),
+            tool_calls: Vec::new(),
+        });
+
+        OllamaChatRequest {
+            model: self.model.clone(),
+            messages,
+            stream,
+            options: self.options.clone(),
+            tools: Vec::new(),
+        }
+    }
+
+    /// Build a chat request offering `tools`, replaying the calls made so far
+    fn tool_request(
+        &self,
+        prompt: &str,
+        context: &[Message],
+        tools: &[ToolSpec],
+        exchanges: &[ToolExchange],
+    ) -> OllamaChatRequest {
+        let mut request = self.chat_request(prompt, context, false);
+
+        for exchange in exchanges {
+            request.messages.push(OllamaMessage {
+                role: "assistant".to_string(),
+                content: String::new(),
+                tool_calls: vec![OllamaToolCall {
+                    function: OllamaFunctionCall {
+                        name: exchange.call.name.clone(),
+                        arguments: exchange.call.arguments.clone(),
+                    },
+                }],
+            });
+            request.messages.push(OllamaMessage {
+                role: "tool".to_string(),
+                content: exchange.output.clone(),
+                tool_calls: Vec::new(),
+            });
+        }
+
+        request.tools = tools
+            .iter()
+            .map(|tool| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    },
+                })
+            })
+            .collect();
+
+        request
+    }
+
+    /// Send a non-streaming chat request
+    async fn chat(&self, request: &OllamaChatRequest) -> Result<OllamaChatResponse> {
+        let response = self.client
+            .post(format!("{}/api/chat", self.base_url))
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| AgentError::ModelError(format!("Failed to send request: {}", e)))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(AgentError::ModelError(format!(
+                "Ollama API error: {} - {}",
+                status, error_text
+            )));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| AgentError::ModelError(format!("Failed to parse response: {}", e)))
+    }
 }
 
 #[derive(Serialize)]
@@ -207,12 +415,27 @@ struct OllamaChatRequest {
     stream: bool,
     #[serde(skip_serializing_if = "HashMap::is_empty")]
     options: HashMap<String, serde_json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct OllamaMessage {
     role: String,
     content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<OllamaToolCall>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OllamaToolCall {
+    function: OllamaFunctionCall,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OllamaFunctionCall {
+    name: String,
+    arguments: serde_json::Value,
 }
 
 #[derive(Deserialize)]
@@ -221,6 +444,17 @@ struct OllamaChatResponse {
     done: bool,
 }
 
+#[derive(Serialize)]
+struct OllamaEmbeddingRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
 #[async_trait]
 impl ModelProvider for OllamaProvider {
     async fn generate(&self, prompt: &str) -> Result<String> {
@@ -261,25 +495,42 @@ impl ModelProvider for OllamaProvider {
         prompt: &str,
         context: &[Message],
     ) -> Result<String> {
-        let mut messages: Vec<OllamaMessage> = context
-            .iter()
-            .map(|m| OllamaMessage {
-                role: m.role.clone(),
-                content: m.content.clone(),
-            })
-            .collect();
+        let request = self.chat_request(prompt, context, false);
+        Ok(self.chat(&request).await?.message.content)
+    }
 
-        messages.push(OllamaMessage {
-            role: "user".to_string(),
-            content: prompt.to_string(),
-        });
+    async fn generate_with_tools(
+        &self,
+        prompt: &str,
+        context: &[Message],
+        tools: &[ToolSpec],
+        exchanges: &[ToolExchange],
+    ) -> Result<ModelTurn> {
+        let request = self.tool_request(prompt, context, tools, exchanges);
+        let message = self.chat(&request).await?.message;
+
+        if message.tool_calls.is_empty() {
+            return Ok(ModelTurn::Text(message.content));
+        }
 
-        let request = OllamaChatRequest {
-            model: self.model.clone(),
-            messages,
-            stream: false,
-            options: self.options.clone(),
-        };
+        Ok(ModelTurn::ToolCalls(
+            message
+                .tool_calls
+                .into_iter()
+                .map(|call| ToolCall {
+                    name: call.function.name,
+                    arguments: call.function.arguments,
+                })
+                .collect(),
+        ))
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        context: &[Message],
+    ) -> Result<ChunkStream> {
+        let request = self.chat_request(prompt, context, true);
 
         let response = self.client
             .post(format!("{}/api/chat", self.base_url))
@@ -297,12 +548,80 @@ impl ModelProvider for OllamaProvider {
             )));
         }
 
-        let ollama_response: OllamaChatResponse = response
+        // Ollama streams newline-delimited JSON objects
+        let bytes = Box::pin(response.bytes_stream());
+        let stream = futures::stream::unfold(
+            (bytes, Vec::<u8>::new(), false),
+            |(mut bytes, mut buffer, finished)| async move {
+                if finished {
+                    return None;
+                }
+
+                loop {
+                    if let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                        let line: Vec<u8> = buffer.drain(..=pos).collect();
+                        if line.iter().all(u8::is_ascii_whitespace) {
+                            continue;
+                        }
+
+                        let item = serde_json::from_slice::<OllamaChatResponse>(&line)
+                            .map(|r| ResponseChunk { content: r.message.content, done: r.done })
+                            .map_err(|e| {
+                                AgentError::ModelError(format!("Failed to parse stream chunk: {}", e))
+                            });
+                        let finished = item.as_ref().map_or(true, |chunk| chunk.done);
+                        return Some((item, (bytes, buffer, finished)));
+                    }
+
+                    match bytes.next().await {
+                        Some(Ok(data)) => buffer.extend_from_slice(&data),
+                        Some(Err(e)) => {
+                            let error = AgentError::ModelError(format!("Stream interrupted: {}", e));
+                            return Some((Err(error), (bytes, buffer, true)));
+                        }
+                        None => return None,
+                    }
+                }
+            },
+        );
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let request = OllamaEmbeddingRequest {
+            model: self.embedding_model.as_deref().unwrap_or(&self.model),
+            prompt: text,
+        };
+
+        let response = self.client
+            .post(format!("{}/api/embeddings", self.base_url))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| AgentError::ModelError(format!("Failed to send request: {}", e)))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(AgentError::ModelError(format!(
+                "Ollama API error: {} - {}",
+                status, error_text
+            )));
+        }
+
+        let embedding: OllamaEmbeddingResponse = response
             .json()
             .await
             .map_err(|e| AgentError::ModelError(format!("Failed to parse response: {}", e)))?;
+        if embedding.embedding.is_empty() {
+            return Err(AgentError::ModelError(format!(
+                "Ollama returned no embedding; is {} an embedding model?",
+                request.model
+            )));
+        }
 
-        Ok(ollama_response.message.content)
+        Ok(embedding.embedding)
     }
 
     async fn health_check(&self) -> Result<()> {
@@ -322,6 +641,29 @@ impl ModelProvider for OllamaProvider {
         }
     }
 
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let response = self.client
+            .get(format!("{}/api/tags", self.base_url))
+            .send()
+            .await
+            .map_err(|e| AgentError::ModelError(format!("Failed to list models: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(AgentError::ModelError(format!(
+                "Ollama model listing failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let tags: serde_json::Value = response.json().await?;
+        Ok(tags["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|model| model["name"].as_str().map(String::from))
+            .collect())
+    }
+
     fn model_info(&self) -> ModelInfo {
         ModelInfo {
             provider: "Ollama".to_string(),
@@ -330,7 +672,7 @@ impl ModelProvider for OllamaProvider {
             capabilities: ModelCapabilities {
                 max_context_length: 4096, // Typical for Vicuna
                 streaming: true,
-                function_calling: false,
+                function_calling: true,
                 vision: false,
                 embeddings: true,
             },
@@ -338,6 +680,501 @@ impl ModelProvider for OllamaProvider {
     }
 }
 
+/// OpenAI chat completions endpoint root
+const OPENAI_API_URL: &str = "https://api.openai.com/v1";
+
+/// OpenAI model provider, using the chat completions API
+pub struct OpenAiProvider {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    organization: Option<String>,
+    model: String,
+    temperature: f32,
+    max_tokens: usize,
+
+    /// Tokens reported by the API since the provider was created
+    usage: std::sync::Arc<std::sync::Mutex<TokenUsage>>,
+}
+
+impl OpenAiProvider {
+    /// Create a new OpenAI provider whose requests give up after `timeout`
+    pub fn new(
+        api_key: String,
+        model: String,
+        organization: Option<String>,
+        timeout: Duration,
+        temperature: f32,
+        max_tokens: usize,
+    ) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| AgentError::Configuration(format!("Failed to build OpenAI client: {}", e)))?;
+
+        Ok(Self {
+            client,
+            base_url: OPENAI_API_URL.to_string(),
+            api_key,
+            organization,
+            model,
+            temperature,
+            max_tokens,
+            usage: Default::default(),
+        })
+    }
+
+    /// Send requests to an OpenAI-compatible server instead
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Tokens the API reported using so far
+    pub fn usage(&self) -> TokenUsage {
+        self.usage.lock().unwrap().clone()
+    }
+
+    /// A request to `path` with the key and organization headers
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, path))
+            .bearer_auth(&self.api_key);
+        match &self.organization {
+            Some(organization) => request.header("OpenAI-Organization", organization),
+            None => request,
+        }
+    }
+
+    /// Build a chat request from the prompt and conversation context
+    fn chat_request(&self, prompt: &str, context: &[Message]) -> OpenAiChatRequest {
+        let mut messages: Vec<OpenAiMessage> = context
+            .iter()
+            .map(|m| OpenAiMessage::text(openai_role(&m.role), &m.content))
+            .collect();
+        messages.push(OpenAiMessage::text("user", prompt));
+
+        OpenAiChatRequest {
+            model: self.model.clone(),
+            messages,
+            temperature: self.temperature,
+            max_tokens: self.max_tokens,
+            tools: Vec::new(),
+            stream: false,
+            stream_options: None,
+        }
+    }
+
+    /// Build a chat request offering `tools`, replaying the calls made so far
+    fn tool_request(
+        &self,
+        prompt: &str,
+        context: &[Message],
+        tools: &[ToolSpec],
+        exchanges: &[ToolExchange],
+    ) -> OpenAiChatRequest {
+        let mut request = self.chat_request(prompt, context);
+
+        // The API pairs calls and outputs by ID, which exchanges don't keep
+        for (index, exchange) in exchanges.iter().enumerate() {
+            let id = format!("call_{}", index);
+            request.messages.push(OpenAiMessage {
+                role: "assistant".to_string(),
+                content: None,
+                tool_calls: vec![OpenAiToolCall {
+                    id: id.clone(),
+                    kind: "function".to_string(),
+                    function: OpenAiFunctionCall {
+                        name: exchange.call.name.clone(),
+                        arguments: exchange.call.arguments.to_string(),
+                    },
+                }],
+                tool_call_id: None,
+            });
+            request.messages.push(OpenAiMessage {
+                role: "tool".to_string(),
+                content: Some(exchange.output.clone()),
+                tool_calls: Vec::new(),
+                tool_call_id: Some(id),
+            });
+        }
+
+        request.tools = tools
+            .iter()
+            .map(|tool| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    },
+                })
+            })
+            .collect();
+
+        request
+    }
+
+    /// Send a chat request, returning the first choice's message
+    async fn chat(&self, request: &OpenAiChatRequest) -> Result<OpenAiMessage> {
+        let response = self
+            .request(reqwest::Method::POST, "/chat/completions")
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| AgentError::ModelError(format!("Failed to send request: {}", e)))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(AgentError::ModelError(format!(
+                "OpenAI API error: {} - {}",
+                status, error_text
+            )));
+        }
+
+        let completion: OpenAiChatResponse = response
+            .json()
+            .await
+            .map_err(|e| AgentError::ModelError(format!("Failed to parse response: {}", e)))?;
+
+        if let Some(usage) = &completion.usage {
+            record_openai_usage(&self.usage, usage);
+        }
+
+        completion
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message)
+            .ok_or_else(|| AgentError::ModelError("OpenAI returned no choices".to_string()))
+    }
+}
+
+/// Add tokens the API reported to `total`, and count them against the
+/// request being handled in place of an estimate
+fn record_openai_usage(total: &std::sync::Mutex<TokenUsage>, usage: &TokenUsage) {
+    tracing::debug!(
+        prompt_tokens = usage.prompt_tokens,
+        completion_tokens = usage.completion_tokens,
+        "OpenAI completion"
+    );
+    crate::usage::report(usage.total_tokens);
+
+    let mut total = total.lock().unwrap();
+    total.prompt_tokens += usage.prompt_tokens;
+    total.completion_tokens += usage.completion_tokens;
+    total.total_tokens += usage.total_tokens;
+}
+
+/// One line of a streamed chat completion: nothing for lines other than
+/// `data:` ones, and otherwise a chunk or the end of the stream
+fn parse_openai_event(line: &[u8]) -> Option<Result<OpenAiStreamEvent>> {
+    let line = String::from_utf8_lossy(line);
+    let data = line.trim().strip_prefix("data:")?.trim();
+    if data == "[DONE]" {
+        return Some(Ok(OpenAiStreamEvent::Done));
+    }
+
+    Some(
+        serde_json::from_str(data)
+            .map(OpenAiStreamEvent::Chunk)
+            .map_err(|e| AgentError::ModelError(format!("Failed to parse stream chunk: {}", e))),
+    )
+}
+
+/// The chat role for one of our message roles; unknown roles speak as the
+/// user
+fn openai_role(role: &str) -> &'static str {
+    match role {
+        "system" => "system",
+        "assistant" => "assistant",
+        _ => "user",
+    }
+}
+
+/// Context window of an OpenAI model, by name
+fn openai_context_length(model: &str) -> usize {
+    match model {
+        model if model.starts_with("gpt-3.5") => 16_385,
+        model if model.starts_with("gpt-4-turbo") || model.starts_with("gpt-4o") => 128_000,
+        model if model.starts_with("gpt-4.1") => 1_047_576,
+        model if model.starts_with("gpt-4") => 8_192,
+        _ => 128_000,
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAiChatRequest {
+    model: String,
+    messages: Vec<OpenAiMessage>,
+    temperature: f32,
+    max_tokens: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<OpenAiStreamOptions>,
+}
+
+#[derive(Serialize)]
+struct OpenAiStreamOptions {
+    /// Ask for a last chunk with the tokens used
+    include_usage: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenAiMessage {
+    role: String,
+    content: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<OpenAiToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+impl OpenAiMessage {
+    fn text(role: &str, content: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: Some(content.to_string()),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenAiToolCall {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    function: OpenAiFunctionCall,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenAiFunctionCall {
+    name: String,
+
+    /// JSON-encoded arguments
+    arguments: String,
+}
+
+#[derive(Deserialize)]
+struct OpenAiChatResponse {
+    choices: Vec<OpenAiChoice>,
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiChoice {
+    message: OpenAiMessage,
+}
+
+enum OpenAiStreamEvent {
+    Chunk(OpenAiStreamChunk),
+    Done,
+}
+
+#[derive(Deserialize)]
+struct OpenAiStreamChunk {
+    #[serde(default)]
+    choices: Vec<OpenAiStreamChoice>,
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiStreamChoice {
+    delta: OpenAiDelta,
+}
+
+#[derive(Deserialize)]
+struct OpenAiDelta {
+    content: Option<String>,
+}
+
+#[async_trait]
+impl ModelProvider for OpenAiProvider {
+    async fn generate(&self, prompt: &str) -> Result<String> {
+        self.generate_with_context(prompt, &[]).await
+    }
+
+    async fn generate_with_context(
+        &self,
+        prompt: &str,
+        context: &[Message],
+    ) -> Result<String> {
+        let request = self.chat_request(prompt, context);
+        Ok(self.chat(&request).await?.content.unwrap_or_default())
+    }
+
+    async fn generate_with_tools(
+        &self,
+        prompt: &str,
+        context: &[Message],
+        tools: &[ToolSpec],
+        exchanges: &[ToolExchange],
+    ) -> Result<ModelTurn> {
+        let request = self.tool_request(prompt, context, tools, exchanges);
+        let message = self.chat(&request).await?;
+
+        if message.tool_calls.is_empty() {
+            return Ok(ModelTurn::Text(message.content.unwrap_or_default()));
+        }
+
+        message
+            .tool_calls
+            .into_iter()
+            .map(|call| {
+                let arguments = serde_json::from_str(&call.function.arguments).map_err(|e| {
+                    AgentError::ModelError(format!("Malformed arguments for tool {}: {}", call.function.name, e))
+                })?;
+                Ok(ToolCall {
+                    name: call.function.name,
+                    arguments,
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(ModelTurn::ToolCalls)
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        context: &[Message],
+    ) -> Result<ChunkStream> {
+        let mut request = self.chat_request(prompt, context);
+        request.stream = true;
+        request.stream_options = Some(OpenAiStreamOptions { include_usage: true });
+
+        let response = self
+            .request(reqwest::Method::POST, "/chat/completions")
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| AgentError::ModelError(format!("Failed to send request: {}", e)))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(AgentError::ModelError(format!(
+                "OpenAI API error: {} - {}",
+                status, error_text
+            )));
+        }
+
+        // OpenAI streams server-sent events, the usage coming in a chunk of
+        // its own just before `[DONE]`
+        let bytes = Box::pin(response.bytes_stream());
+        let stream = futures::stream::unfold(
+            (bytes, Vec::<u8>::new(), false, self.usage.clone()),
+            |(mut bytes, mut buffer, finished, usage)| async move {
+                if finished {
+                    return None;
+                }
+
+                loop {
+                    if let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                        let line: Vec<u8> = buffer.drain(..=pos).collect();
+                        let chunk = match parse_openai_event(&line) {
+                            None => continue,
+                            Some(Ok(OpenAiStreamEvent::Done)) => {
+                                let done = ResponseChunk { content: String::new(), done: true };
+                                return Some((Ok(done), (bytes, buffer, true, usage)));
+                            }
+                            Some(Ok(OpenAiStreamEvent::Chunk(chunk))) => chunk,
+                            Some(Err(e)) => return Some((Err(e), (bytes, buffer, true, usage))),
+                        };
+
+                        if let Some(reported) = &chunk.usage {
+                            record_openai_usage(&usage, reported);
+                        }
+                        let content: String = chunk
+                            .choices
+                            .into_iter()
+                            .filter_map(|choice| choice.delta.content)
+                            .collect();
+                        if content.is_empty() {
+                            continue;
+                        }
+                        let chunk = ResponseChunk { content, done: false };
+                        return Some((Ok(chunk), (bytes, buffer, false, usage)));
+                    }
+
+                    match bytes.next().await {
+                        Some(Ok(data)) => buffer.extend_from_slice(&data),
+                        Some(Err(e)) => {
+                            let error = AgentError::ModelError(format!("Stream interrupted: {}", e));
+                            return Some((Err(error), (bytes, buffer, true, usage)));
+                        }
+                        None => return None,
+                    }
+                }
+            },
+        );
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        let response = self
+            .request(reqwest::Method::GET, "/models")
+            .send()
+            .await
+            .map_err(|e| AgentError::ModelError(format!("Health check failed: {}", e)))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(AgentError::ModelError(format!(
+                "OpenAI health check failed with status: {}",
+                response.status()
+            )))
+        }
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let response = self
+            .request(reqwest::Method::GET, "/models")
+            .send()
+            .await
+            .map_err(|e| AgentError::ModelError(format!("Failed to list models: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(AgentError::ModelError(format!(
+                "OpenAI model listing failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let models: serde_json::Value = response.json().await?;
+        Ok(models["data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|model| model["id"].as_str().map(String::from))
+            .collect())
+    }
+
+    fn model_info(&self) -> ModelInfo {
+        ModelInfo {
+            provider: "OpenAI".to_string(),
+            model: self.model.clone(),
+            version: None,
+            capabilities: ModelCapabilities {
+                max_context_length: openai_context_length(&self.model),
+                streaming: true,
+                function_calling: true,
+                vision: false,
+                embeddings: false,
+            },
+        }
+    }
+}
+
 /// Mock provider for testing
 pub struct MockProvider {
     response: String,
@@ -366,27 +1203,55 @@ impl ModelProvider for MockProvider {
     async fn health_check(&self) -> Result<()> {
         Ok(())
     }
+
+    fn model_info(&self) -> ModelInfo {
+        ModelInfo {
+            provider: "Mock".to_string(),
+            model: "mock".to_string(),
+            version: None,
+            capabilities: ModelCapabilities {
+                max_context_length: 4096,
+                streaming: false,
+                function_calling: false,
+                vision: false,
+                embeddings: false,
+            },
+        }
+    }
 }
 
 /// Factory function to create a model provider based on configuration
+///
+/// The service starts with the provider built here, and `switch_model`
+/// builds its replacements here too, so settings such as `embedding_model`
+/// apply to both.
 pub fn create_provider(config: &crate::config::ModelConfig) -> Result<Box<dyn ModelProvider>> {
     match config {
         crate::config::ModelConfig::Ollama {
             base_url,
             model,
-            timeout,
+            embedding_model,
             ..
-        } => Ok(Box::new(OllamaProvider::new(
-            base_url.clone(),
-            model.clone(),
-            HashMap::new(),
-        ))),
+        } => Ok(Box::new(
+            OllamaProvider::new(base_url.clone(), model.clone(), HashMap::new())
+                .with_embedding_model(embedding_model.clone()),
+        )),
         
-        crate::config::ModelConfig::OpenAI { .. } => {
-            Err(AgentError::Configuration(
-                "OpenAI provider not yet implemented".to_string(),
-            ))
-        }
+        crate::config::ModelConfig::OpenAI {
+            api_key,
+            model,
+            organization,
+            timeout,
+            temperature,
+            max_tokens,
+        } => Ok(Box::new(OpenAiProvider::new(
+            api_key.clone(),
+            model.clone(),
+            organization.clone(),
+            *timeout,
+            *temperature,
+            *max_tokens,
+        )?)),
         
         crate::config::ModelConfig::Anthropic { .. } => {
             Err(AgentError::Configuration(
@@ -394,4 +1259,100 @@ pub fn create_provider(config: &crate::config::ModelConfig) -> Result<Box<dyn Mo
             ))
         }
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider() -> OpenAiProvider {
+        OpenAiProvider::new("sk-test".to_string(), "gpt-4o".to_string(), None, Duration::from_secs(5), 0.2, 512).unwrap()
+    }
+
+    #[test]
+    fn test_openai_chat_request_maps_history() {
+        let context = [
+            Message {
+                role: "system".to_string(),
+                content: "You are the Alchemist.".to_string(),
+                timestamp: chrono::Utc::now(),
+            },
+            Message {
+                role: "agent".to_string(),
+                content: "Hi".to_string(),
+                timestamp: chrono::Utc::now(),
+            },
+        ];
+
+        let request = serde_json::to_value(provider().chat_request("What is CQRS?", &context)).unwrap();
+        assert_eq!(request["model"], "gpt-4o");
+        assert_eq!(request["max_tokens"], 512);
+        assert_eq!(request["messages"][0], serde_json::json!({"role": "system", "content": "You are the Alchemist."}));
+        assert_eq!(request["messages"][1]["role"], "user");
+        assert_eq!(request["messages"][2], serde_json::json!({"role": "user", "content": "What is CQRS?"}));
+        assert!(request.get("tools").is_none());
+    }
+
+    #[test]
+    fn test_openai_tool_request_replays_exchanges() {
+        let tools = [ToolSpec {
+            name: "search_code".to_string(),
+            description: "Search indexed code".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+        }];
+        let exchanges = [ToolExchange {
+            call: ToolCall {
+                name: "search_code".to_string(),
+                arguments: serde_json::json!({"query": "Aggregate"}),
+            },
+            output: "src/aggregate.rs".to_string(),
+        }];
+
+        let request = serde_json::to_value(provider().tool_request("Find aggregates", &[], &tools, &exchanges)).unwrap();
+        let call = &request["messages"][1];
+        assert_eq!(call["tool_calls"][0]["type"], "function");
+        assert_eq!(call["tool_calls"][0]["function"]["arguments"], "{\"query\":\"Aggregate\"}");
+        assert_eq!(request["messages"][2]["tool_call_id"], call["tool_calls"][0]["id"]);
+        assert_eq!(request["tools"][0]["function"]["name"], "search_code");
+        assert_eq!(openai_context_length("gpt-4"), 8_192);
+    }
+
+    #[test]
+    fn test_openai_provider_from_config() {
+        let config = crate::config::ModelConfig::OpenAI {
+            api_key: "sk-test".to_string(),
+            model: "gpt-4o".to_string(),
+            organization: None,
+            timeout: Duration::from_secs(30),
+            temperature: 0.2,
+            max_tokens: 512,
+        };
+
+        let info = create_provider(&config).unwrap().model_info();
+        assert_eq!(info.provider, "OpenAI");
+        assert_eq!(info.model, "gpt-4o");
+    }
+
+    #[test]
+    fn test_openai_stream_events() {
+        let request = serde_json::to_value(provider().chat_request("What is CQRS?", &[])).unwrap();
+        assert!(request.get("stream").is_none());
+
+        let chunk = parse_openai_event(b"data: {\"choices\":[{\"delta\":{\"content\":\"Commands\"}}]}\n");
+        let Some(Ok(OpenAiStreamEvent::Chunk(chunk))) = chunk else {
+            panic!("Not a chunk");
+        };
+        assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("Commands"));
+
+        let usage = parse_openai_event(
+            b"data: {\"choices\":[],\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":3,\"total_tokens\":12}}\r\n",
+        );
+        let Some(Ok(OpenAiStreamEvent::Chunk(usage))) = usage else {
+            panic!("Not a chunk");
+        };
+        assert_eq!(usage.usage.map(|usage| usage.total_tokens), Some(12));
+
+        assert!(matches!(parse_openai_event(b"data: [DONE]\n"), Some(Ok(OpenAiStreamEvent::Done))));
+        assert!(parse_openai_event(b": keep-alive\n").is_none());
+        assert!(matches!(parse_openai_event(b"data: {\n"), Some(Err(_))));
+    }
+}
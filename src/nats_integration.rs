@@ -2,17 +2,50 @@
 //!
 //! This module handles all NATS-based messaging for the Alchemist agent,
 //! including command processing, event publishing, and query handling.
+//!
+//! The agent registers as a NATS service, so `nats micro ls` finds it and
+//! `$SRV.PING`, `$SRV.INFO`, and `$SRV.STATS` describe it. Commands,
+//! queries, dialog messages, health, and metrics are endpoints of that
+//! service, each reporting its own request count and processing time.
+//! Endpoints share the service's queue group, so several agents on the same
+//! subjects split the requests between them.
+//!
+//! With `nats.jetstream` configured, commands are instead read from the
+//! durable consumer `consumer_name` on the agent's stream, so those sent
+//! while the agent is down are handled once it is back. JetStream keeps no
+//! reply subjects, so senders name theirs in the [`REPLY_TO_HEADER`] header.
+//! The stream captures dialog messages too, for `replay_stream`, so a dialog
+//! request may be answered by its publish acknowledgement; dialog replies
+//! are always published on `cim.dialog.<dialog_id>.response` as well.
 
+use crate::agent::AlchemistAgent;
 use crate::error::{AgentError, Result};
+use crate::events::AgentEventKind;
+use crate::priority::Priority;
+use async_nats::jetstream::AckKind;
+use async_nats::service::{endpoint::Endpoint, Request, Service, ServiceExt};
 use async_nats::{Client, Subscriber};
+use futures::stream::SelectAll;
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{debug, error, info, warn};
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tracing::{debug, error, info, warn, Instrument};
 
 /// NATS subject patterns for the Alchemist agent
+///
+/// Commands, queries, events, and health checks are also served under a
+/// version after the prefix, such as `cim.agent.alchemist.v1.commands.>`, so
+/// a breaking payload change can get a new version while clients of the old
+/// one keep working. `nats.versions` picks the versions served.
 pub mod subjects {
+    /// Subject versions this build understands, oldest first
+    pub const VERSIONS: &[&str] = &["v1"];
+    
+    /// Version clients address unless configured otherwise
+    pub const CURRENT_VERSION: &str = "v1";
+    
     /// Command subjects
     pub const COMMANDS: &str = "cim.agent.alchemist.commands.>";
     
@@ -32,6 +65,12 @@ pub mod subjects {
     pub const METRICS: &str = "cim.agent.alchemist.metrics";
 }
 
+/// Sender name used for messages published by the agent
+pub const AGENT_SENDER: &str = "alchemist";
+
+/// Header naming the subject a durable command's reply is published on
+pub const REPLY_TO_HEADER: &str = "Alchemist-Reply-To";
+
 /// NATS client wrapper for the agent
 pub struct NatsClient {
     /// NATS connection
@@ -43,44 +82,50 @@ pub struct NatsClient {
     /// Subject prefix for this agent
     subject_prefix: String,
     
+    /// Subject versions served besides the unversioned subjects
+    versions: Vec<String>,
+    
+    /// Whether the unversioned subjects are served
+    serve_unversioned: bool,
+    
     /// Active subscriptions
     subscriptions: Arc<RwLock<Vec<Subscriber>>>,
+    
+    /// The agent's NATS service, once an endpoint is served
+    service: Arc<tokio::sync::Mutex<Option<Arc<Service>>>>,
+    
+    /// Largest command, query, or dialog message accepted, in bytes
+    max_message_bytes: usize,
+    
+    /// Durable consumer commands are read from, with JetStream configured
+    durable_commands: Option<DurableCommands>,
+    
+    /// Faults dropping incoming messages, if any are injected
+    #[cfg(feature = "chaos")]
+    faults: Option<Arc<crate::chaos::FaultInjector>>,
 }
 
 impl NatsClient {
     /// Create a new NATS client
     pub async fn new(config: &crate::config::NatsConfig) -> Result<Self> {
-        // Connect to NATS
-        let mut options = async_nats::ConnectOptions::new();
-        
-        // Configure authentication if provided
-        if let Some(auth) = &config.auth {
-            options = match auth {
-                crate::config::NatsAuth::Token { token } => options.token(token.clone()),
-                crate::config::NatsAuth::UserPassword { username, password } => {
-                    options.user_and_password(username.clone(), password.clone())
-                }
-                crate::config::NatsAuth::Jwt { jwt, seed } => {
-                    options.jwt(jwt.clone(), seed.clone())
-                }
-                crate::config::NatsAuth::Tls { cert_path, key_path } => {
-                    // TLS configuration would go here
-                    options
-                }
-            };
+        if let Some(version) = config.versions.iter().find(|version| !subjects::VERSIONS.contains(&version.as_str())) {
+            return Err(AgentError::Configuration(format!(
+                "Unknown subject version {}; this build serves {}",
+                version,
+                subjects::VERSIONS.join(", ")
+            )));
+        }
+        if let Some(js_config) = &config.jetstream {
+            js_config.validate()?;
+        }
+        if config.versions.is_empty() && !config.serve_unversioned {
+            return Err(AgentError::Configuration(
+                "No subjects to serve: list nats.versions or enable nats.serve_unversioned".to_string(),
+            ));
         }
-        
-        // Set retry configuration
-        options = options
-            .max_reconnects(config.retry.max_attempts as usize)
-            .retry_on_initial_connect();
         
         // Connect to NATS servers
-        let client = async_nats::connect_with_options(
-            config.servers.join(","),
-            options,
-        )
-        .await?;
+        let client = connect(config).await?;
         
         // Create JetStream context if configured
         let jetstream = if let Some(js_config) = &config.jetstream {
@@ -91,6 +136,7 @@ impl NatsClient {
                 name: js_config.stream_name.clone(),
                 subjects: vec![
                     format!("{}.>", config.subject_prefix),
+                    subjects::DIALOG.to_string(),
                 ],
                 retention: async_nats::jetstream::stream::RetentionPolicy::Limits,
                 ..Default::default()
@@ -103,14 +149,70 @@ impl NatsClient {
             None
         };
         
+        // Clients send commands through JetStream whenever it is configured,
+        // so without the consumer nobody would read them
+        let durable_commands = match (&jetstream, &config.jetstream) {
+            (Some(js), Some(js_config)) => Some(DurableCommands::open(js, config, js_config).await?),
+            _ => None,
+        };
+        
         Ok(Self {
             connection: client,
             jetstream,
             subject_prefix: config.subject_prefix.clone(),
+            versions: config.versions.clone(),
+            serve_unversioned: config.serve_unversioned,
             subscriptions: Arc::new(RwLock::new(Vec::new())),
+            service: Arc::new(tokio::sync::Mutex::new(None)),
+            max_message_bytes: 0,
+            durable_commands,
+            #[cfg(feature = "chaos")]
+            faults: None,
         })
     }
     
+    /// Refuse commands, queries, and dialog messages over `bytes`
+    pub fn with_message_limit(mut self, bytes: usize) -> Self {
+        self.max_message_bytes = bytes;
+        self
+    }
+    
+    /// Refuse `request` if its payload is over the message limit, answering
+    /// with why; true if it was refused
+    async fn refuses(&self, request: &Request) -> bool {
+        let size = request.message.payload.len();
+        let Err(e) = crate::limits::check("message", size, self.max_message_bytes) else {
+            return false;
+        };
+        warn!("Refused a {} byte message on {}", size, request.message.subject);
+        if let Err(e) = respond(request, &response_envelope(&Err(e))).await {
+            error!("Failed to answer oversized message: {}", e);
+        }
+        true
+    }
+    
+    /// Drop incoming messages as `faults` says
+    #[cfg(feature = "chaos")]
+    pub fn with_faults(mut self, faults: Arc<crate::chaos::FaultInjector>) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+    
+    /// Whether an injected fault drops the incoming `msg`
+    #[cfg(feature = "chaos")]
+    fn drops(&self, msg: &async_nats::Message) -> bool {
+        let dropped = self.faults.as_ref().is_some_and(|faults| faults.drop_message());
+        if dropped {
+            warn!("Injected fault: dropped message on {}", msg.subject);
+        }
+        dropped
+    }
+    
+    #[cfg(not(feature = "chaos"))]
+    fn drops(&self, _msg: &async_nats::Message) -> bool {
+        false
+    }
+    
     /// Subscribe to a subject pattern
     pub async fn subscribe(&self, subject: &str) -> Result<Subscriber> {
         let sub = self.connection.subscribe(subject).await?;
@@ -155,6 +257,361 @@ impl NatsClient {
         self.jetstream.as_ref()
     }
     
+    /// The underlying connection, for components that manage their own subscriptions
+    pub fn client(&self) -> Client {
+        self.connection.clone()
+    }
+    
+    /// A handle on the same connection for spawned tasks, tracking no
+    /// subscriptions of its own
+    fn detached(&self) -> NatsClient {
+        NatsClient {
+            connection: self.connection.clone(),
+            jetstream: self.jetstream.clone(),
+            subject_prefix: self.subject_prefix.clone(),
+            versions: self.versions.clone(),
+            serve_unversioned: self.serve_unversioned,
+            subscriptions: Arc::new(RwLock::new(Vec::new())),
+            service: self.service.clone(),
+            max_message_bytes: self.max_message_bytes,
+            durable_commands: self.durable_commands.clone(),
+            #[cfg(feature = "chaos")]
+            faults: self.faults.clone(),
+        }
+    }
+    
+    /// Build a subject under this agent's prefix
+    pub fn subject(&self, suffix: &str) -> String {
+        format!("{}.{}", self.subject_prefix, suffix)
+    }
+    
+    /// Every served subject for `suffix`, versioned and unversioned
+    pub fn served_subjects(&self, suffix: &str) -> Vec<String> {
+        served_subjects(&self.subject_prefix, &self.versions, self.serve_unversioned, suffix)
+    }
+    
+    /// Subscribe to every served subject for `suffix` as one stream
+    pub async fn subscribe_served(&self, suffix: &str) -> Result<SelectAll<Subscriber>> {
+        let mut subscribers = Vec::new();
+        for subject in self.served_subjects(suffix) {
+            subscribers.push(self.subscribe(&subject).await?);
+        }
+        Ok(futures::stream::select_all(subscribers))
+    }
+    
+    /// The agent's NATS service, registered on first use
+    async fn service(&self) -> Result<Arc<Service>> {
+        let mut service = self.service.lock().await;
+        if let Some(service) = service.as_ref() {
+            return Ok(service.clone());
+        }
+        
+        let started = self
+            .connection
+            .service_builder()
+            .description(crate::DESCRIPTION)
+            .metadata(HashMap::from([
+                ("subject_prefix".to_string(), self.subject_prefix.clone()),
+                ("versions".to_string(), self.versions.join(",")),
+            ]))
+            .start(crate::NAME, crate::VERSION)
+            .await?;
+        info!("Registered NATS service {} {}", crate::NAME, crate::VERSION);
+        
+        let started = Arc::new(started);
+        *service = Some(started.clone());
+        Ok(started)
+    }
+    
+    /// Serve every served subject for `suffix` as endpoints of the agent's
+    /// NATS service, as one stream of requests
+    ///
+    /// Each subject is its own endpoint, `name` for the unversioned subject
+    /// and `name-v1` and so on for the versioned ones, with its own stats.
+    pub async fn serve_endpoint(&self, name: &str, suffix: &str, description: &str) -> Result<SelectAll<Endpoint>> {
+        let names = self
+            .versions
+            .iter()
+            .map(|version| format!("{}-{}", name, version))
+            .chain(self.serve_unversioned.then(|| name.to_string()));
+        let endpoints: Vec<(String, String)> = names.zip(self.served_subjects(suffix)).collect();
+        self.add_endpoints(endpoints, description).await
+    }
+    
+    /// Add endpoints, given as name and subject, to the agent's NATS service
+    async fn add_endpoints(&self, endpoints: Vec<(String, String)>, description: &str) -> Result<SelectAll<Endpoint>> {
+        let service = self.service().await?;
+        let mut added = Vec::new();
+        for (name, subject) in endpoints {
+            let endpoint = service
+                .endpoint_builder()
+                .name(&name)
+                .metadata(HashMap::from([("description".to_string(), description.to_string())]))
+                .add(subject.as_str())
+                .await?;
+            info!("Serving endpoint {} on {}", name, subject);
+            added.push(endpoint);
+        }
+        Ok(futures::stream::select_all(added))
+    }
+    
+    /// Stop answering on the service's endpoints and discovery subjects
+    pub async fn stop_service(&self) -> Result<()> {
+        let Some(service) = self.service.lock().await.take() else {
+            return Ok(());
+        };
+        match Arc::try_unwrap(service) {
+            Ok(service) => service.stop().await?,
+            Err(_) => warn!("NATS service still in use; it stops with the connection"),
+        }
+        Ok(())
+    }
+    
+    /// Publish `message` on every served subject for `suffix`
+    pub async fn publish_served<T: Serialize>(&self, suffix: &str, message: &T) -> Result<()> {
+        for subject in self.served_subjects(suffix) {
+            self.publish(&subject, message).await?;
+        }
+        Ok(())
+    }
+    
+    /// Route incoming commands to the agent
+    ///
+    /// With `command_signing` configured, unsigned, expired, and forged
+    /// commands are rejected before they reach the agent. Durable commands
+    /// are judged fresh by when JetStream stored them, not when they are
+    /// delivered. Without it, administrative commands are only accepted
+    /// from `nats.admin_origins`.
+    pub async fn subscribe_commands(&self, agent: Arc<AlchemistAgent>) -> Result<()> {
+        let check = command_check(agent.config())?;
+        let admin_origins = agent
+            .config()
+            .command_signing
+            .is_none()
+            .then(|| agent.config().nats.admin_origins.clone());
+        process_command_stream(self, |command, received| {
+            let agent = agent.clone();
+            let checked = check(&command, received).and_then(|()| match &admin_origins {
+                Some(origins) => admin_check(origins, &command),
+                None => Ok(()),
+            });
+            let span = tracing::info_span!("nats", correlation_id = %command.id);
+            async move {
+                checked?;
+                agent
+                    .process_idempotent_command(
+                        &command.origin,
+                        command.idempotency_key.as_deref(),
+                        command.priority,
+                        &command.command_type,
+                        command.payload,
+                    )
+                    .await
+            }
+            .instrument(span)
+        })
+        .await
+    }
+    
+    /// Route incoming queries to the agent
+    pub async fn subscribe_queries(&self, agent: Arc<AlchemistAgent>) -> Result<()> {
+        process_query_stream(self, |query| {
+            let agent = agent.clone();
+            let span = tracing::info_span!("nats", correlation_id = %query.id);
+            async move {
+                agent
+                    .process_query_from(&query.origin, &query.query_type, query.parameters)
+                    .await
+            }
+            .instrument(span)
+        })
+        .await
+    }
+    
+    /// Route incoming dialog messages to the agent and publish its replies
+    ///
+    /// Messages with `"stream": true` in their metadata also get the reply
+    /// piece by piece on `cim.dialog.<dialog_id>.chunk` as the model
+    /// generates it, before the complete reply.
+    pub async fn subscribe_dialogs(&self, agent: Arc<AlchemistAgent>) -> Result<()> {
+        let mut requests = self
+            .add_endpoints(
+                vec![("dialog".to_string(), subjects::DIALOG.to_string())],
+                "Dialog messages, answered on cim.dialog.<dialog_id>.response",
+            )
+            .await?;
+        
+        while let Some(request) = requests.next().await {
+            if self.drops(&request.message) || self.refuses(&request).await {
+                continue;
+            }
+            
+            let message = match serde_json::from_slice::<DialogMessage>(&request.message.payload) {
+                Ok(message) => message,
+                Err(e) => {
+                    error!("Failed to parse dialog message: {}", e);
+                    continue;
+                }
+            };
+            
+            // Ignore our own replies echoed back on the wildcard
+            if message.sender == AGENT_SENDER {
+                continue;
+            }
+            
+            debug!("Received dialog message for {}", message.dialog_id);
+            
+            // Each reply runs in its own task, so a long answer holds up no
+            // other dialog; the agent's priority lanes bound how many run
+            let client = self.detached();
+            let agent = agent.clone();
+            tokio::spawn(async move {
+                client.answer_dialog(&agent, &request, message).await;
+            });
+        }
+        
+        Ok(())
+    }
+    
+    /// Generate the agent's reply to `message` and publish it
+    async fn answer_dialog(&self, agent: &Arc<AlchemistAgent>, request: &Request, message: DialogMessage) {
+        let dialog_id = message.dialog_id.clone();
+        let correlation_id = message.metadata["correlation_id"]
+            .as_str()
+            .map_or_else(|| uuid::Uuid::new_v4().to_string(), str::to_string);
+        let span = tracing::info_span!("nats", correlation_id = %correlation_id);
+        let replied = if message.metadata["stream"].as_bool() == Some(true) {
+            let (chunk_tx, chunk_rx) = mpsc::unbounded_channel::<String>();
+            let generate = agent
+                .reply_to_dialog_message(message.into(), move |chunk| {
+                    let _ = chunk_tx.send(chunk.to_string());
+                })
+                .instrument(span);
+            let (replied, ()) = tokio::join!(generate, self.publish_chunks(&dialog_id, chunk_rx));
+            replied
+        } else {
+            agent.reply_within_deadline(message.into()).instrument(span).await
+        };
+        let (content, metadata) = match replied {
+            Ok(reply) => {
+                let metadata = reply.metadata();
+                (reply.content, metadata)
+            }
+            Err(e) => {
+                error!("Dialog processing error: {}", e);
+                (format!("Sorry, I could not process that message: {}", e), serde_json::json!({}))
+            }
+        };
+        
+        let reply = DialogMessage {
+            dialog_id: dialog_id.clone(),
+            content,
+            sender: AGENT_SENDER.to_string(),
+            metadata,
+            timestamp: chrono::Utc::now(),
+        };
+        
+        if let Err(e) = self.publish(&format!("cim.dialog.{}.response", dialog_id), &reply).await {
+            error!("Failed to publish dialog response: {}", e);
+        }
+        
+        // Senders that used request-reply get the reply directly too
+        if let Err(e) = respond(request, &reply).await {
+            error!("Failed to send dialog reply: {}", e);
+        }
+    }
+    
+    /// Publish each chunk of a reply as it arrives, numbered from 0, until
+    /// the reply is complete
+    async fn publish_chunks(&self, dialog_id: &str, mut chunks: mpsc::UnboundedReceiver<String>) {
+        let subject = format!("cim.dialog.{}.chunk", dialog_id);
+        let mut sequence = 0;
+        while let Some(content) = chunks.recv().await {
+            let chunk = DialogMessage {
+                dialog_id: dialog_id.to_string(),
+                content,
+                sender: AGENT_SENDER.to_string(),
+                metadata: serde_json::json!({ "sequence": sequence }),
+                timestamp: chrono::Utc::now(),
+            };
+            if let Err(e) = self.publish(&subject, &chunk).await {
+                debug!("Failed to publish dialog chunk: {}", e);
+            }
+            sequence += 1;
+        }
+    }
+    
+    /// Answer health requests on `<subject_prefix>.health`
+    ///
+    /// Liveness alone is what `$SRV.PING` answers; this endpoint reports
+    /// the agent's subsystems as well.
+    pub async fn answer_health_checks(&self, agent: Arc<AlchemistAgent>) -> Result<()> {
+        let mut requests = self
+            .serve_endpoint("health", "health", "Agent health with its model, storage, and NATS subsystems")
+            .await?;
+        
+        while let Some(request) = requests.next().await {
+            let mut health = agent.health().await;
+            health.add_subsystem("nats", connection_health(&self.connection));
+            health.add_subsystem(
+                "jetstream",
+                jetstream_health(&self.connection, agent.config().nats.jetstream.as_ref()).await,
+            );
+            
+            if let Err(e) = respond(&request, &health).await {
+                error!("Failed to send health response: {}", e);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Answer metrics requests on `<subject_prefix>.metrics` with the
+    /// retrieval and dialog metrics as JSON
+    pub async fn answer_metrics(&self, agent: Arc<AlchemistAgent>) -> Result<()> {
+        let mut requests = self
+            .serve_endpoint("metrics", "metrics", "Retrieval and dialog metrics")
+            .await?;
+        
+        while let Some(request) = requests.next().await {
+            let metrics = serde_json::json!({
+                "retrieval": agent.retrieval_metrics().snapshot(),
+                "dialogs": agent.dialog_metrics().snapshot(),
+            });
+            if let Err(e) = respond(&request, &metrics).await {
+                error!("Failed to send metrics response: {}", e);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Publish agent events on `events.<event_type>`
+    pub async fn publish_agent_events(&self, mut events: broadcast::Receiver<AgentEvent>) -> Result<()> {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    let suffix = format!("events.{}", event.event_type());
+                    if let Err(e) = self.publish_served(&suffix, &event).await {
+                        error!("Failed to publish {} event: {}", event.event_type(), e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Skipped {} agent events while NATS was slow", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+    
+    /// Flush buffered outbound messages to the server
+    pub async fn flush(&self) -> Result<()> {
+        self.connection
+            .flush()
+            .await
+            .map_err(|e| AgentError::ServiceUnavailable(format!("NATS flush failed: {}", e)))
+    }
+    
     /// Close all subscriptions
     pub async fn close(&self) -> Result<()> {
         let mut subs = self.subscriptions.write().await;
@@ -165,6 +622,109 @@ impl NatsClient {
     }
 }
 
+/// Signature check for incoming commands, given when each reached NATS
+#[cfg(feature = "signing")]
+pub(crate) fn command_check(
+    config: &crate::config::AgentConfig,
+) -> Result<impl Fn(&AgentCommand, chrono::DateTime<chrono::Utc>) -> Result<()>> {
+    let verifier = config
+        .command_signing
+        .as_ref()
+        .map(crate::signing::CommandVerifier::new)
+        .transpose()?;
+    
+    Ok(move |command: &AgentCommand, received| match &verifier {
+        Some(verifier) => verifier.verify_received(command, received),
+        None => Ok(()),
+    })
+}
+
+#[cfg(not(feature = "signing"))]
+pub(crate) fn command_check(
+    config: &crate::config::AgentConfig,
+) -> Result<impl Fn(&AgentCommand, chrono::DateTime<chrono::Utc>) -> Result<()>> {
+    if config.command_signing.is_some() {
+        return Err(AgentError::Configuration(
+            "Command signing is configured but the agent was built without the `signing` feature".to_string(),
+        ));
+    }
+    
+    Ok(|_: &AgentCommand, _: chrono::DateTime<chrono::Utc>| Ok(()))
+}
+
+/// Refuse administrative commands, also inside a `batch`, from origins not
+/// in `admin_origins`
+fn admin_check(admin_origins: &[String], command: &AgentCommand) -> Result<()> {
+    let admin = crate::auth::ADMIN_COMMANDS.contains(&command.command_type.as_str())
+        || (command.command_type == "batch"
+            && crate::auth::batch_scope(&command.payload) == crate::config::ApiScope::Admin);
+    if admin && !admin_origins.contains(&command.origin) {
+        warn!(target: "audit", origin = %command.origin, "Administrative command {} refused over NATS", command.command_type);
+        return Err(AgentError::PermissionDenied(format!(
+            "Origin {} may not send administrative commands",
+            command.origin
+        )));
+    }
+    Ok(())
+}
+
+/// Subjects for `suffix` under each of `versions`, then unversioned if
+/// `serve_unversioned`
+pub fn served_subjects(prefix: &str, versions: &[String], serve_unversioned: bool, suffix: &str) -> Vec<String> {
+    let mut subjects: Vec<String> = versions
+        .iter()
+        .map(|version| format!("{}.{}.{}", prefix, version, suffix))
+        .collect();
+    if serve_unversioned {
+        subjects.push(format!("{}.{}", prefix, suffix));
+    }
+    subjects
+}
+
+/// Build connection options from the NATS configuration
+pub(crate) async fn connect_options(config: &crate::config::NatsConfig) -> Result<async_nats::ConnectOptions> {
+    let mut options = async_nats::ConnectOptions::new();
+    
+    // Configure authentication if provided
+    if let Some(auth) = &config.auth {
+        options = match auth {
+            crate::config::NatsAuth::Token { token } => options.token(token.clone()),
+            crate::config::NatsAuth::UserPassword { username, password } => {
+                options.user_and_password(username.clone(), password.clone())
+            }
+            crate::config::NatsAuth::Jwt { jwt, seed } => {
+                options.jwt(jwt.clone(), seed.clone())
+            }
+            crate::config::NatsAuth::CredentialsFile { path } => {
+                options.credentials_file(path).await.map_err(|e| {
+                    AgentError::Configuration(format!("Cannot read NATS credentials file {}: {}", path, e))
+                })?
+            }
+            crate::config::NatsAuth::NKey { seed } => options.nkey(seed.clone()),
+            crate::config::NatsAuth::Tls { cert_path, key_path } => {
+                // TLS configuration would go here
+                options
+            }
+        };
+    }
+    
+    // Set retry configuration
+    Ok(options
+        .max_reconnects(config.retry.max_attempts as usize)
+        .retry_on_initial_connect())
+}
+
+/// Connect to the configured NATS servers
+pub(crate) async fn connect(config: &crate::config::NatsConfig) -> Result<Client> {
+    let client = async_nats::connect_with_options(
+        config.servers.join(","),
+        connect_options(config).await?,
+    )
+    .await?;
+    
+    Ok(client)
+}
+
 /// Message handler for incoming NATS messages
 pub struct MessageHandler<H> {
     handler: H,
@@ -193,6 +753,20 @@ pub struct AgentCommand {
     
     /// Originating user/system
     pub origin: String,
+    
+    /// Hex Ed25519 signature, required when command signing is configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    
+    /// Key under which retries of this command get its first result back
+    /// instead of running it again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    
+    /// Lane to run in, such as `bulk` for automation that can wait behind
+    /// dialogs; see [`crate::priority`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -213,16 +787,18 @@ pub struct AgentQuery {
     pub origin: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// An event published by the agent
+///
+/// Sent as `{id, event_type, payload, timestamp, agent_id}`; see
+/// [`crate::events`] for the event types and their payloads.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "WireEvent", from = "WireEvent")]
 pub struct AgentEvent {
     /// Event ID
     pub id: String,
     
-    /// Event type
-    pub event_type: String,
-    
-    /// Event payload
-    pub payload: serde_json::Value,
+    /// What happened
+    pub kind: AgentEventKind,
     
     /// Timestamp
     pub timestamp: chrono::DateTime<chrono::Utc>,
@@ -231,6 +807,66 @@ pub struct AgentEvent {
     pub agent_id: String,
 }
 
+impl AgentEvent {
+    /// A new event from this agent, timestamped now
+    pub fn new(kind: AgentEventKind) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind,
+            timestamp: chrono::Utc::now(),
+            agent_id: crate::NAME.to_string(),
+        }
+    }
+    
+    /// A new event of a type only known at runtime, typed if the catalog
+    /// describes it
+    pub fn raw(event_type: impl Into<String>, payload: serde_json::Value) -> Self {
+        Self::new(AgentEventKind::from_parts(event_type.into(), payload))
+    }
+    
+    pub fn event_type(&self) -> &str {
+        self.kind.event_type()
+    }
+    
+    pub fn payload(&self) -> serde_json::Value {
+        self.kind.payload()
+    }
+}
+
+/// An [`AgentEvent`] as it is sent
+#[derive(Serialize, Deserialize)]
+struct WireEvent {
+    id: String,
+    event_type: String,
+    #[serde(default)]
+    payload: serde_json::Value,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    agent_id: String,
+}
+
+impl From<AgentEvent> for WireEvent {
+    fn from(event: AgentEvent) -> Self {
+        Self {
+            event_type: event.event_type().to_string(),
+            payload: event.payload(),
+            id: event.id,
+            timestamp: event.timestamp,
+            agent_id: event.agent_id,
+        }
+    }
+}
+
+impl From<WireEvent> for AgentEvent {
+    fn from(event: WireEvent) -> Self {
+        Self {
+            id: event.id,
+            kind: AgentEventKind::from_parts(event.event_type, event.payload),
+            timestamp: event.timestamp,
+            agent_id: event.agent_id,
+        }
+    }
+}
+
 /// Dialog-specific messages
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DialogMessage {
@@ -250,6 +886,23 @@ pub struct DialogMessage {
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
+impl From<DialogMessage> for crate::agent::DialogMessage {
+    fn from(message: DialogMessage) -> Self {
+        // Usage counts under the sender unless the message names an origin
+        let mut metadata = message.metadata;
+        if let Some(fields) = metadata.as_object_mut() {
+            fields.entry("origin").or_insert_with(|| serde_json::json!(message.sender));
+        }
+        
+        Self {
+            dialog_id: message.dialog_id,
+            content: message.content,
+            metadata,
+            timestamp: message.timestamp,
+        }
+    }
+}
+
 /// Health check response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthResponse {
@@ -268,67 +921,148 @@ pub struct HealthResponse {
     /// Active dialogs count
     pub active_dialogs: usize,
     
+    /// Model provider, storage, NATS, and JetStream, by name
+    #[serde(default)]
+    pub subsystems: std::collections::BTreeMap<String, SubsystemHealth>,
+    
     /// Additional health metadata
     pub metadata: serde_json::Value,
 }
 
-/// Process incoming commands
+impl HealthResponse {
+    /// Add a subsystem, marking the agent degraded if it is
+    pub fn add_subsystem(&mut self, name: &str, health: SubsystemHealth) {
+        if health.is_degraded() && self.status == "Running" {
+            self.status = "Degraded".to_string();
+        }
+        self.subsystems.insert(name.to_string(), health);
+    }
+}
+
+/// Health of one dependency at its last probe
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubsystemHealth {
+    /// `healthy`, `degraded`, `disabled`, or `unknown` before the first probe
+    pub status: String,
+    
+    /// How long the probe took
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    
+    /// Why the subsystem is degraded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    
+    /// When the probe ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl SubsystemHealth {
+    pub fn healthy(latency: std::time::Duration) -> Self {
+        Self {
+            status: "healthy".to_string(),
+            latency_ms: Some(latency.as_millis() as u64),
+            reason: None,
+            checked_at: Some(chrono::Utc::now()),
+        }
+    }
+    
+    pub fn degraded(reason: impl Into<String>) -> Self {
+        Self {
+            status: "degraded".to_string(),
+            latency_ms: None,
+            reason: Some(reason.into()),
+            checked_at: Some(chrono::Utc::now()),
+        }
+    }
+    
+    /// Not configured, so not probed
+    pub fn disabled() -> Self {
+        Self {
+            status: "disabled".to_string(),
+            latency_ms: None,
+            reason: None,
+            checked_at: None,
+        }
+    }
+    
+    /// Not probed yet
+    pub fn unknown() -> Self {
+        Self {
+            status: "unknown".to_string(),
+            ..Self::disabled()
+        }
+    }
+    
+    pub fn is_degraded(&self) -> bool {
+        self.status == "degraded"
+    }
+}
+
+/// Probe the JetStream stream the agent's messages are kept in
+pub async fn jetstream_health(client: &Client, config: Option<&crate::config::JetStreamConfig>) -> SubsystemHealth {
+    let Some(config) = config else {
+        return SubsystemHealth::disabled();
+    };
+    
+    let started = std::time::Instant::now();
+    match async_nats::jetstream::new(client.clone()).get_stream(&config.stream_name).await {
+        Ok(_) => SubsystemHealth::healthy(started.elapsed()),
+        Err(e) => SubsystemHealth::degraded(format!("Stream {} is unavailable: {}", config.stream_name, e)),
+    }
+}
+
+/// Health of the NATS connection itself
+pub fn connection_health(client: &Client) -> SubsystemHealth {
+    match client.connection_state() {
+        async_nats::connection::State::Connected => SubsystemHealth {
+            status: "healthy".to_string(),
+            checked_at: Some(chrono::Utc::now()),
+            ..SubsystemHealth::disabled()
+        },
+        state => SubsystemHealth::degraded(format!("Connection is {:?}", state)),
+    }
+}
+
+/// Process incoming commands, passing the handler each with when it
+/// reached NATS
+///
+/// Each command is handled in its own task, so slow commands do not hold up
+/// the ones behind them; the handler decides how many actually run at once.
 pub async fn process_command_stream<F, Fut>(
     client: &NatsClient,
     mut handler: F,
 ) -> Result<()>
 where
-    F: FnMut(AgentCommand) -> Fut + Send,
-    Fut: std::future::Future<Output = Result<serde_json::Value>> + Send,
+    F: FnMut(AgentCommand, chrono::DateTime<chrono::Utc>) -> Fut + Send,
+    Fut: std::future::Future<Output = Result<serde_json::Value>> + Send + 'static,
 {
-    let mut sub = client.subscribe(subjects::COMMANDS).await?;
+    if let Some(durable) = client.durable_commands.clone() {
+        return consume_command_stream(client, durable, handler).await;
+    }
     
-    info!("Listening for commands on {}", subjects::COMMANDS);
+    let mut requests = client
+        .serve_endpoint("commands", "commands.>", "Agent commands, answered with the standard envelope")
+        .await?;
     
-    while let Some(msg) = sub.next().await {
-        match serde_json::from_slice::<AgentCommand>(&msg.payload) {
+    while let Some(request) = requests.next().await {
+        if client.drops(&request.message) || client.refuses(&request).await {
+            continue;
+        }
+        
+        match serde_json::from_slice::<AgentCommand>(&request.message.payload) {
             Ok(command) => {
                 debug!("Received command: {} ({})", command.command_type, command.id);
                 
-                match handler(command.clone()).await {
-                    Ok(response) => {
-                        // Publish response event
-                        let event = AgentEvent {
-                            id: uuid::Uuid::new_v4().to_string(),
-                            event_type: format!("{}_completed", command.command_type),
-                            payload: response,
-                            timestamp: chrono::Utc::now(),
-                            agent_id: crate::NAME.to_string(),
-                        };
-                        
-                        if let Err(e) = client.publish(
-                            &format!("{}.{}", subjects::EVENTS.trim_end_matches('>'), command.command_type),
-                            &event,
-                        ).await {
-                            error!("Failed to publish command response: {}", e);
-                        }
-                    }
-                    Err(e) => {
-                        error!("Command handler error: {}", e);
-                        
-                        // Publish error event
-                        let event = AgentEvent {
-                            id: uuid::Uuid::new_v4().to_string(),
-                            event_type: format!("{}_failed", command.command_type),
-                            payload: serde_json::json!({
-                                "error": e.to_string(),
-                                "command_id": command.id,
-                            }),
-                            timestamp: chrono::Utc::now(),
-                            agent_id: crate::NAME.to_string(),
-                        };
-                        
-                        let _ = client.publish(
-                            &format!("{}.error", subjects::EVENTS.trim_end_matches('>')),
-                            &event,
-                        ).await;
+                let handled = handler(command.clone(), chrono::Utc::now());
+                let client = client.detached();
+                tokio::spawn(async move {
+                    let result = handled.await;
+                    if let Err(e) = answer_command(&client, &request, &command, result).await {
+                        error!("Failed to answer command {}: {}", command.id, e);
                     }
-                }
+                });
             }
             Err(e) => {
                 error!("Failed to parse command: {}", e);
@@ -339,81 +1073,410 @@ where
     Ok(())
 }
 
-/// Process incoming queries with request-reply
-pub async fn process_query_stream<F, Fut>(
+/// Reply to a handled command and publish its outcome
+async fn answer_command(
+    client: &NatsClient,
+    request: &Request,
+    command: &AgentCommand,
+    result: Result<serde_json::Value>,
+) -> Result<()> {
+    // Answer callers that used request-reply
+    if let Err(e) = respond(request, &response_envelope(&result)).await {
+        error!("Failed to send command reply: {}", e);
+    }
+    
+    publish_outcome(client, command, result).await;
+    Ok(())
+}
+
+/// Publish the `<command>_completed` or `<command>_failed` event of a
+/// handled command
+async fn publish_outcome(client: &NatsClient, command: &AgentCommand, result: Result<serde_json::Value>) {
+    match result {
+        Ok(response) => {
+            // Publish response event
+            let event = AgentEvent::new(AgentEventKind::Raw {
+                event_type: format!("{}_completed", command.command_type),
+                payload: response,
+            });
+            
+            if let Err(e) = client.publish_served(
+                &format!("events.{}", command.command_type),
+                &event,
+            ).await {
+                error!("Failed to publish command response: {}", e);
+            }
+        }
+        Err(e) => {
+            error!("Command handler error: {}", e);
+            
+            // Publish error event
+            let event = AgentEvent::new(AgentEventKind::Raw {
+                event_type: format!("{}_failed", command.command_type),
+                payload: serde_json::json!({
+                    "error": e.to_string(),
+                    "command_id": command.id,
+                }),
+            });
+            
+            let _ = client.publish_served("events.error", &event).await;
+        }
+    }
+}
+
+/// The durable consumer commands are read from, with what it needs to
+/// settle them
+#[derive(Clone)]
+struct DurableCommands {
+    consumer: async_nats::jetstream::consumer::PullConsumer,
+    
+    /// Deliveries of a command before it is dead-lettered
+    max_deliver: i64,
+    
+    /// Time a command may run between progress acknowledgements
+    ack_wait: std::time::Duration,
+    
+    dead_letter_subject: String,
+    
+    /// Backoff between deliveries of a failed command
+    retry: crate::config::RetryConfig,
+}
+
+impl DurableCommands {
+    /// Create or reuse the consumer on the agent's stream, failing if
+    /// JetStream cannot provide it
+    async fn open(
+        js: &async_nats::jetstream::Context,
+        config: &crate::config::NatsConfig,
+        js_config: &crate::config::JetStreamConfig,
+    ) -> Result<Self> {
+        let consumer_config = async_nats::jetstream::consumer::pull::Config {
+            durable_name: Some(js_config.consumer_name.clone()),
+            filter_subjects: served_subjects(&config.subject_prefix, &config.versions, config.serve_unversioned, "commands.>"),
+            ack_policy: async_nats::jetstream::consumer::AckPolicy::Explicit,
+            ack_wait: js_config.ack_wait,
+            max_deliver: js_config.max_deliver,
+            ..Default::default()
+        };
+        let consumer = match js.get_stream(&js_config.stream_name).await {
+            Ok(stream) => stream
+                .get_or_create_consumer(&js_config.consumer_name, consumer_config)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        
+        match consumer {
+            Ok(consumer) => Ok(Self {
+                consumer,
+                max_deliver: js_config.max_deliver,
+                ack_wait: js_config.ack_wait,
+                dead_letter_subject: js_config
+                    .dead_letter_subject
+                    .clone()
+                    .unwrap_or_else(|| format!("{}.dead_letter.commands", config.subject_prefix)),
+                retry: config.retry.clone(),
+            }),
+            Err(e) => Err(AgentError::ServiceUnavailable(format!(
+                "Cannot read commands from consumer {} on stream {}: {}",
+                js_config.consumer_name, js_config.stream_name, e
+            ))),
+        }
+    }
+    
+    /// Run `handled`, telling JetStream the command is still in progress
+    /// so a slow one is not delivered again meanwhile
+    async fn keep_alive(
+        &self,
+        message: &async_nats::jetstream::Message,
+        handled: impl std::future::Future<Output = Result<serde_json::Value>>,
+    ) -> Result<serde_json::Value> {
+        let mut handled = std::pin::pin!(handled);
+        let mut progress = tokio::time::interval(self.ack_wait / 2);
+        progress.tick().await;
+        loop {
+            tokio::select! {
+                result = &mut handled => return result,
+                _ = progress.tick() => {
+                    if let Err(e) = message.ack_with(AckKind::Progress).await {
+                        warn!("Failed to extend command acknowledgement: {}", e);
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Publish a command that will not be handled on the dead-letter
+    /// subject, with why, and stop its deliveries
+    async fn dead_letter(
+        &self,
+        client: &NatsClient,
+        message: &async_nats::jetstream::Message,
+        deliveries: i64,
+        error: &AgentError,
+    ) {
+        let command = serde_json::from_slice(&message.payload)
+            .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(&message.payload).into_owned()));
+        let letter = serde_json::json!({
+            "subject": message.subject.as_str(),
+            "deliveries": deliveries,
+            "error": error.to_string(),
+            "command": command,
+            "timestamp": chrono::Utc::now(),
+        });
+        
+        warn!("Dead-lettering command on {} after {} deliveries: {}", message.subject, deliveries, error);
+        if let Err(e) = client.publish(&self.dead_letter_subject, &letter).await {
+            error!("Failed to publish dead letter: {}", e);
+        }
+        if let Err(e) = message.ack_with(AckKind::Term).await {
+            error!("Failed to stop deliveries of a dead-lettered command: {}", e);
+        }
+    }
+}
+
+/// Whether a command failing with `error` on delivery `deliveries` should
+/// be delivered again rather than answered
+fn redeliver(error: &AgentError, deliveries: i64, max_deliver: i64) -> bool {
+    error.is_retryable() && deliveries < max_deliver
+}
+
+/// Process commands from the durable consumer
+///
+/// A command is acknowledged once handled, whether it succeeded or failed
+/// with an answer for its sender. One failing on something a retry may
+/// fix, such as an unreachable model, is delivered again after a backoff,
+/// and on its last delivery is answered and dead-lettered instead, as are
+/// commands that cannot be read.
+async fn consume_command_stream<F, Fut>(
     client: &NatsClient,
+    durable: DurableCommands,
     mut handler: F,
 ) -> Result<()>
 where
-    F: FnMut(AgentQuery) -> Fut + Send,
-    Fut: std::future::Future<Output = Result<serde_json::Value>> + Send,
+    F: FnMut(AgentCommand, chrono::DateTime<chrono::Utc>) -> Fut + Send,
+    Fut: std::future::Future<Output = Result<serde_json::Value>> + Send + 'static,
 {
-    let mut sub = client.subscribe(subjects::QUERIES).await?;
-    
-    info!("Listening for queries on {}", subjects::QUERIES);
-    
-    while let Some(msg) = sub.next().await {
-        if let Some(reply) = msg.reply {
-            match serde_json::from_slice::<AgentQuery>(&msg.payload) {
-                Ok(query) => {
-                    debug!("Received query: {} ({})", query.query_type, query.id);
-                    
-                    let response = match handler(query).await {
-                        Ok(result) => serde_json::json!({
-                            "success": true,
-                            "result": result,
-                        }),
-                        Err(e) => serde_json::json!({
-                            "success": false,
-                            "error": e.to_string(),
-                        }),
-                    };
-                    
-                    let payload = serde_json::to_vec(&response)?;
-                    if let Err(e) = msg.respond(payload.into()).await {
-                        error!("Failed to send query response: {}", e);
+    let mut messages = durable
+        .consumer
+        .messages()
+        .await
+        .map_err(|e| AgentError::Nats(e.into()))?;
+    info!("Reading commands from durable consumer {}", durable.consumer.cached_info().name);
+    
+    while let Some(message) = messages.next().await {
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Durable command consumer error: {}", e);
+                continue;
+            }
+        };
+        if client.drops(&message.message) {
+            continue;
+        }
+        let (deliveries, published) = message.info().map_or((1, chrono::Utc::now()), |info| {
+            let published = chrono::DateTime::from_timestamp_nanos(info.published.unix_timestamp_nanos() as i64);
+            (info.delivered, published)
+        });
+        
+        let command = crate::limits::check("message", message.payload.len(), client.max_message_bytes)
+            .and_then(|()| Ok(serde_json::from_slice::<AgentCommand>(&message.payload)?));
+        let command = match command {
+            Ok(command) => command,
+            Err(e) => {
+                error!("Failed to read command: {}", e);
+                durable.dead_letter(client, &message, deliveries, &e).await;
+                if let Err(e) = reply_durable(client, &message, &response_envelope(&Err(e))).await {
+                    error!("Failed to send command reply: {}", e);
+                }
+                continue;
+            }
+        };
+        debug!("Received durable command: {} ({}, delivery {})", command.command_type, command.id, deliveries);
+        
+        let handled = handler(command.clone(), published);
+        let client = client.detached();
+        let durable = durable.clone();
+        tokio::spawn(async move {
+            let result = durable.keep_alive(&message, handled).await;
+            if let Err(e) = &result {
+                if redeliver(e, deliveries, durable.max_deliver) {
+                    warn!("Command {} failed on delivery {}, retrying: {}", command.id, deliveries, e);
+                    let delay = durable.retry.delay(deliveries as u32);
+                    if let Err(e) = message.ack_with(AckKind::Nak(Some(delay))).await {
+                        error!("Failed to schedule redelivery of command {}: {}", command.id, e);
                     }
+                    return;
                 }
-                Err(e) => {
-                    error!("Failed to parse query: {}", e);
-                    
-                    let error_response = serde_json::json!({
-                        "success": false,
-                        "error": format!("Invalid query format: {}", e),
-                    });
-                    
-                    let payload = serde_json::to_vec(&error_response)?;
-                    let _ = msg.respond(payload.into()).await;
+            }
+            
+            if let Err(e) = reply_durable(&client, &message, &response_envelope(&result)).await {
+                error!("Failed to send command reply: {}", e);
+            }
+            match &result {
+                Err(e) if e.is_retryable() => durable.dead_letter(&client, &message, deliveries, e).await,
+                _ => {
+                    if let Err(e) = message.ack().await {
+                        error!("Failed to acknowledge command {}: {}", command.id, e);
+                    }
                 }
             }
-        }
+            publish_outcome(&client, &command, result).await;
+        });
     }
     
     Ok(())
 }
 
-/// Handle health check requests
-pub async fn handle_health_checks<F>(
+/// Publish `response` on the subject a durable command's sender named in
+/// [`REPLY_TO_HEADER`], if it named one
+async fn reply_durable<T: Serialize>(
     client: &NatsClient,
-    start_time: std::time::Instant,
-    status_fn: F,
+    message: &async_nats::jetstream::Message,
+    response: &T,
+) -> Result<()> {
+    let Some(reply_to) = message.headers.as_ref().and_then(|headers| headers.get(REPLY_TO_HEADER)) else {
+        return Ok(());
+    };
+    client.publish(reply_to.as_str(), response).await
+}
+
+/// Answer `request` with `response` as JSON, if its sender waits for one
+///
+/// Failed handlers still answer with the envelope rather than a service
+/// error, which clients could not read, so the endpoint stats count them
+/// as requests but not as errors.
+async fn respond<T: Serialize>(request: &Request, response: &T) -> Result<()> {
+    if request.message.reply.is_none() {
+        return Ok(());
+    }
+    let payload = serde_json::to_vec(response)?;
+    request
+        .respond(Ok(payload.into()))
+        .await
+        .map_err(|e| AgentError::Nats(e.into()))
+}
+
+/// Wrap a handler result in the standard reply envelope
+pub fn response_envelope(result: &Result<serde_json::Value>) -> serde_json::Value {
+    match result {
+        Ok(result) => serde_json::json!({
+            "success": true,
+            "result": result,
+        }),
+        Err(e) => {
+            let mut envelope = serde_json::json!({
+                "success": false,
+                "error": e.to_string(),
+            });
+            if let (Some(code), Some(details)) = (e.code(), e.details()) {
+                envelope["code"] = serde_json::json!(code);
+                envelope["details"] = details;
+            }
+            envelope
+        }
+    }
+}
+
+/// Process incoming queries with request-reply
+pub async fn process_query_stream<F, Fut>(
+    client: &NatsClient,
+    mut handler: F,
 ) -> Result<()>
 where
-    F: Fn() -> HealthResponse + Send + Sync,
+    F: FnMut(AgentQuery) -> Fut + Send,
+    Fut: std::future::Future<Output = Result<serde_json::Value>> + Send,
 {
-    let mut sub = client.subscribe(subjects::HEALTH).await?;
-    
-    info!("Health check endpoint active on {}", subjects::HEALTH);
+    let mut requests = client
+        .serve_endpoint("queries", "queries.>", "Agent queries, answered with the standard envelope")
+        .await?;
     
-    while let Some(msg) = sub.next().await {
-        if let Some(reply) = msg.reply {
-            let mut health = status_fn();
-            health.uptime_seconds = start_time.elapsed().as_secs();
-            
-            let payload = serde_json::to_vec(&health)?;
-            let _ = msg.respond(payload.into()).await;
+    while let Some(request) = requests.next().await {
+        if client.drops(&request.message) || request.message.reply.is_none() || client.refuses(&request).await {
+            continue;
+        }
+        
+        match serde_json::from_slice::<AgentQuery>(&request.message.payload) {
+            Ok(query) => {
+                debug!("Received query: {} ({})", query.query_type, query.id);
+                
+                let response = response_envelope(&handler(query).await);
+                
+                if let Err(e) = respond(&request, &response).await {
+                    error!("Failed to send query response: {}", e);
+                }
+            }
+            Err(e) => {
+                error!("Failed to parse query: {}", e);
+                
+                let error_response = serde_json::json!({
+                    "success": false,
+                    "error": format!("Invalid query format: {}", e),
+                });
+                
+                let _ = respond(&request, &error_response).await;
+            }
         }
     }
     
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_served_subjects() {
+        let versions = vec!["v1".to_string()];
+        assert_eq!(
+            served_subjects("cim.agent.alchemist", &versions, true, "commands.>"),
+            vec!["cim.agent.alchemist.v1.commands.>", "cim.agent.alchemist.commands.>"]
+        );
+        assert_eq!(
+            served_subjects("cim.agent.alchemist", &versions, false, "health"),
+            vec!["cim.agent.alchemist.v1.health"]
+        );
+    }
+
+    #[test]
+    fn test_admin_commands_need_allowed_origin() {
+        let command = |command_type: &str, payload: serde_json::Value, origin: &str| AgentCommand {
+            id: "cmd-1".to_string(),
+            command_type: command_type.to_string(),
+            payload,
+            timestamp: chrono::Utc::now(),
+            origin: origin.to_string(),
+            signature: None,
+            idempotency_key: None,
+            priority: None,
+        };
+        let origins = vec!["ops".to_string()];
+        let batch = serde_json::json!({"commands": [{"command_type": "switch_model", "payload": {}}]});
+
+        assert!(admin_check(&origins, &command("switch_model", serde_json::json!({}), "ops")).is_ok());
+        assert!(admin_check(&origins, &command("switch_model", serde_json::json!({}), "user-456")).is_err());
+        assert!(admin_check(&origins, &command("batch", batch, "user-456")).is_err());
+        assert!(admin_check(&[], &command("explain_concept", serde_json::json!({}), "user-456")).is_ok());
+    }
+
+    #[test]
+    fn test_redeliver_only_retryable_failures() {
+        let unavailable = AgentError::ServiceUnavailable("Model unreachable".to_string());
+        assert!(redeliver(&unavailable, 1, 5));
+        assert!(!redeliver(&unavailable, 5, 5));
+        assert!(!redeliver(&AgentError::NotFound("Workflow wf-1".to_string()), 1, 5));
+    }
+
+    #[tokio::test]
+    async fn test_missing_credentials_file() {
+        let mut config = crate::config::AgentConfig::default().nats;
+        config.auth = Some(crate::config::NatsAuth::CredentialsFile {
+            path: "/nonexistent/alchemist.creds".to_string(),
+        });
+        let error = connect_options(&config).await.unwrap_err();
+        assert!(matches!(error, AgentError::Configuration(_)));
+        assert!(error.to_string().contains("/nonexistent/alchemist.creds"));
+    }
+}
@@ -0,0 +1,121 @@
+//! Pagination for list queries
+//!
+//! List queries take an optional `limit` and the `cursor` returned with the
+//! previous page. Their results carry the page of items under the query's own
+//! field, such as `concepts`, next to `total`, `limit`, and `next_cursor`,
+//! which is null on the last page. Cursors are opaque to callers.
+
+use serde::Serialize;
+
+use crate::error::{AgentError, Result};
+
+/// Items per page when a query does not ask for a `limit`
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// Most items per page, keeping results well inside NATS payload limits
+pub const MAX_LIMIT: usize = 1000;
+
+/// One page of a query's results
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+
+    /// Items across all pages
+    pub total: usize,
+
+    pub limit: usize,
+    pub next_cursor: Option<String>,
+}
+
+impl<T: Serialize> Page<T> {
+    /// The page of `items` that `parameters` asks for
+    ///
+    /// `items` must come in the same order on every call for cursors to
+    /// follow on from each other.
+    pub fn new(items: Vec<T>, parameters: &serde_json::Value, default_limit: usize) -> Result<Self> {
+        let limit = match &parameters["limit"] {
+            serde_json::Value::Null => default_limit,
+            limit => limit
+                .as_u64()
+                .filter(|limit| *limit > 0)
+                .ok_or_else(|| AgentError::InvalidRequest(format!("Invalid limit {}", limit)))?
+                as usize,
+        }
+        .min(MAX_LIMIT);
+
+        let offset = match parameters["cursor"].as_str() {
+            Some(cursor) => decode_cursor(cursor)?,
+            None => 0,
+        };
+
+        let total = items.len();
+        let items: Vec<T> = items.into_iter().skip(offset).take(limit).collect();
+        let next = offset + items.len();
+        Ok(Self {
+            items,
+            total,
+            limit,
+            next_cursor: (next < total).then(|| encode_cursor(next)),
+        })
+    }
+
+    /// The page as a query result, with the items under `field`
+    pub fn into_json(self, field: &str) -> serde_json::Value {
+        let mut result = serde_json::json!({
+            "total": self.total,
+            "limit": self.limit,
+            "next_cursor": self.next_cursor,
+        });
+        result[field] = serde_json::json!(self.items);
+        result
+    }
+}
+
+fn encode_cursor(offset: usize) -> String {
+    format!("o{}", offset)
+}
+
+fn decode_cursor(cursor: &str) -> Result<usize> {
+    cursor
+        .strip_prefix('o')
+        .and_then(|offset| offset.parse().ok())
+        .ok_or_else(|| AgentError::InvalidRequest(format!("Invalid cursor {}", cursor)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages_follow_cursors() {
+        let items: Vec<u32> = (1..=5).collect();
+
+        let first = Page::new(items.clone(), &serde_json::json!({ "limit": 2 }), DEFAULT_LIMIT).unwrap();
+        assert_eq!(first.items, vec![1, 2]);
+        assert_eq!(first.total, 5);
+
+        let cursor = first.next_cursor.unwrap();
+        let second = Page::new(items.clone(), &serde_json::json!({ "limit": 2, "cursor": cursor }), DEFAULT_LIMIT).unwrap();
+        assert_eq!(second.items, vec![3, 4]);
+
+        let cursor = second.next_cursor.unwrap();
+        let last = Page::new(items, &serde_json::json!({ "limit": 2, "cursor": cursor }), DEFAULT_LIMIT).unwrap();
+        assert_eq!(last.items, vec![5]);
+        assert_eq!(last.next_cursor, None);
+    }
+
+    #[test]
+    fn test_limits_and_invalid_parameters() {
+        let items: Vec<u32> = (0..2000).collect();
+
+        let page = Page::new(items.clone(), &serde_json::json!({ "limit": 5000 }), DEFAULT_LIMIT).unwrap();
+        assert_eq!(page.items.len(), MAX_LIMIT);
+        assert_eq!(Page::new(items.clone(), &serde_json::json!({}), 5).unwrap().limit, 5);
+
+        let result = Page::new(vec!["a"], &serde_json::json!({}), DEFAULT_LIMIT).unwrap().into_json("concepts");
+        assert_eq!(result, serde_json::json!({ "concepts": ["a"], "total": 1, "limit": 100, "next_cursor": null }));
+
+        assert!(Page::new(items.clone(), &serde_json::json!({ "limit": 0 }), DEFAULT_LIMIT).is_err());
+        assert!(Page::new(items, &serde_json::json!({ "cursor": "page-2" }), DEFAULT_LIMIT).is_err());
+    }
+}
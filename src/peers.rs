@@ -0,0 +1,340 @@
+//! Discovery of and delegation to other CIM agents
+//!
+//! Each agent announces its capabilities on `cim.agent.<agent_id>.capabilities`
+//! and answers requests on that subject with them. Announcements from peers
+//! are kept for a few announce intervals. A question whose words match a
+//! peer's topics better than this agent's is sent to
+//! `cim.agent.<agent_id>.delegate` and the peer's answer relayed.
+
+use async_nats::Client;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use crate::agent::{AlchemistAgent, DialogMessage};
+use crate::config::{AgentConfig, PeersConfig};
+use crate::error::{AgentError, Result};
+use crate::nats_integration::response_envelope;
+
+/// Subjects every agent announces its capabilities on
+pub const CAPABILITIES: &str = "cim.agent.*.capabilities";
+
+/// Announcements older than this many intervals are dropped
+const STALE_INTERVALS: u32 = 3;
+
+/// What an agent announces about itself
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerCapabilities {
+    pub agent_id: String,
+    pub name: String,
+    pub description: String,
+    pub version: String,
+
+    /// Topics the agent answers best
+    pub topics: Vec<String>,
+}
+
+/// A question forwarded to a peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delegation {
+    /// Agent the question came from
+    pub origin: String,
+
+    /// Dialog on the originating agent
+    pub dialog_id: String,
+
+    pub question: String,
+}
+
+/// A peer's answer to a delegated question
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DelegatedAnswer {
+    /// Agent that answered
+    pub agent_id: String,
+
+    pub answer: String,
+}
+
+pub fn capabilities_subject(agent_id: &str) -> String {
+    format!("cim.agent.{}.capabilities", agent_id)
+}
+
+pub fn delegate_subject(agent_id: &str) -> String {
+    format!("cim.agent.{}.delegate", agent_id)
+}
+
+/// Peers this agent has heard from, and the connection to reach them
+pub struct Peers {
+    own: PeerCapabilities,
+    config: PeersConfig,
+    known: RwLock<HashMap<String, (PeerCapabilities, Instant)>>,
+    client: OnceLock<Client>,
+}
+
+impl Peers {
+    pub fn new(config: &AgentConfig) -> Self {
+        Self {
+            own: PeerCapabilities {
+                agent_id: config.identity.agent_id.clone(),
+                name: config.identity.name.clone(),
+                description: config.identity.description.clone(),
+                version: config.identity.version.clone(),
+                topics: config.peers.topics.clone(),
+            },
+            config: config.peers.clone(),
+            known: RwLock::new(HashMap::new()),
+            client: OnceLock::new(),
+        }
+    }
+
+    /// Capabilities this agent announces
+    pub fn own(&self) -> &PeerCapabilities {
+        &self.own
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Peers heard from recently, by agent ID
+    pub async fn list(&self) -> Vec<PeerCapabilities> {
+        let stale_after = self.config.announce_interval * STALE_INTERVALS;
+        let mut peers: Vec<PeerCapabilities> = self
+            .known
+            .read()
+            .await
+            .values()
+            .filter(|(_, seen)| seen.elapsed() < stale_after)
+            .map(|(peer, _)| peer.clone())
+            .collect();
+
+        peers.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+        peers
+    }
+
+    /// Remember an announcement, returning whether the peer was new
+    async fn record(&self, peer: PeerCapabilities) -> bool {
+        let stale_after = self.config.announce_interval * STALE_INTERVALS;
+        let previous = self
+            .known
+            .write()
+            .await
+            .insert(peer.agent_id.clone(), (peer, Instant::now()));
+
+        !matches!(previous, Some((_, seen)) if seen.elapsed() < stale_after)
+    }
+
+    /// The peer whose topics match `question` better than this agent's, if any
+    pub async fn best_match(&self, question: &str) -> Option<PeerCapabilities> {
+        let own_score = score(&self.own.topics, question);
+
+        // Ties go to the first agent ID so every replica picks the same peer
+        self.list()
+            .await
+            .into_iter()
+            .map(|peer| (score(&peer.topics, question), peer))
+            .filter(|(score, _)| *score > own_score)
+            .fold(None, |best: Option<(usize, PeerCapabilities)>, candidate| match best {
+                Some(best) if best.0 >= candidate.0 => Some(best),
+                _ => Some(candidate),
+            })
+            .map(|(_, peer)| peer)
+    }
+
+    /// Ask `peer` to answer `question` from `dialog_id`
+    pub async fn delegate(&self, peer: &PeerCapabilities, dialog_id: &str, question: &str) -> Result<DelegatedAnswer> {
+        let client = self
+            .client
+            .get()
+            .ok_or_else(|| AgentError::ServiceUnavailable("Peers are not connected to NATS".to_string()))?;
+
+        let delegation = Delegation {
+            origin: self.own.agent_id.clone(),
+            dialog_id: dialog_id.to_string(),
+            question: question.to_string(),
+        };
+        let payload = serde_json::to_vec(&delegation)?;
+
+        let response = tokio::time::timeout(
+            self.config.delegate_timeout,
+            client.request(delegate_subject(&peer.agent_id), payload.into()),
+        )
+        .await
+        .map_err(|_| AgentError::Timeout(format!("Delegation to {}", peer.agent_id)))?
+        .map_err(|e| AgentError::Nats(Box::new(e)))?;
+
+        let envelope: serde_json::Value = serde_json::from_slice(&response.payload)?;
+        if envelope["success"].as_bool() != Some(true) {
+            return Err(AgentError::ServiceUnavailable(format!(
+                "{} could not answer: {}",
+                peer.agent_id,
+                envelope["error"].as_str().unwrap_or("unknown error")
+            )));
+        }
+
+        Ok(serde_json::from_value(envelope["result"].clone())?)
+    }
+
+    async fn announce(&self, client: &Client) {
+        let payload = match serde_json::to_vec(&self.own) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to encode capabilities: {}", e);
+                return;
+            }
+        };
+
+        if let Err(e) = client.publish(capabilities_subject(&self.own.agent_id), payload.into()).await {
+            warn!("Failed to announce capabilities: {}", e);
+        }
+    }
+
+    /// Announce capabilities, track peers, and answer delegated questions
+    /// until the connection closes
+    pub async fn run(self: Arc<Self>, client: Client, agent: Arc<AlchemistAgent>) -> Result<()> {
+        let _ = self.client.set(client.clone());
+
+        let mut announcements = client.subscribe(CAPABILITIES).await?;
+        let mut delegations = client.subscribe(delegate_subject(&self.own.agent_id)).await?;
+        let mut interval = tokio::time::interval(self.config.announce_interval);
+
+        info!("Announcing capabilities on {}", capabilities_subject(&self.own.agent_id));
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => self.announce(&client).await,
+                Some(msg) = announcements.next() => {
+                    // A request for our capabilities rather than an announcement
+                    if let Some(reply) = msg.reply {
+                        if msg.subject.as_str() == capabilities_subject(&self.own.agent_id) {
+                            if let Ok(payload) = serde_json::to_vec(&self.own) {
+                                let _ = client.publish(reply, payload.into()).await;
+                            }
+                        }
+                        continue;
+                    }
+
+                    match serde_json::from_slice::<PeerCapabilities>(&msg.payload) {
+                        Ok(peer) if peer.agent_id == self.own.agent_id => {}
+                        Ok(peer) => {
+                            debug!("Peer {} announced {:?}", peer.agent_id, peer.topics);
+                            // Let a newcomer hear from us without waiting an interval
+                            if self.record(peer).await {
+                                self.announce(&client).await;
+                            }
+                        }
+                        Err(e) => debug!("Ignoring malformed announcement on {}: {}", msg.subject, e),
+                    }
+                }
+                Some(msg) = delegations.next() => {
+                    let Some(reply) = msg.reply else {
+                        continue;
+                    };
+
+                    let agent = agent.clone();
+                    let client = client.clone();
+                    let agent_id = self.own.agent_id.clone();
+                    tokio::spawn(async move {
+                        let result = answer_delegation(&agent, &agent_id, &msg.payload).await;
+                        match serde_json::to_vec(&response_envelope(&result)) {
+                            Ok(payload) => {
+                                if let Err(e) = client.publish(reply, payload.into()).await {
+                                    warn!("Failed to send delegated answer: {}", e);
+                                }
+                            }
+                            Err(e) => warn!("Failed to encode delegated answer: {}", e),
+                        }
+                    });
+                }
+                else => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Answer a question a peer forwarded, in a dialog of its own
+async fn answer_delegation(agent: &AlchemistAgent, agent_id: &str, payload: &[u8]) -> Result<serde_json::Value> {
+    let delegation: Delegation = serde_json::from_slice(payload)?;
+    info!("Answering a question delegated by {}", delegation.origin);
+
+    // Marked so it is never delegated onwards
+    let reply = agent
+        .reply_to_dialog_message(
+            DialogMessage {
+                dialog_id: format!("{}.{}", delegation.origin, delegation.dialog_id),
+                content: delegation.question,
+                metadata: serde_json::json!({ "delegated_from": delegation.origin, "origin": delegation.origin }),
+                timestamp: chrono::Utc::now(),
+            },
+            |_| {},
+        )
+        .await?;
+
+    Ok(serde_json::to_value(DelegatedAnswer {
+        agent_id: agent_id.to_string(),
+        answer: reply.content,
+    })?)
+}
+
+/// Words of `text`, lowercased and padded so phrases match on word boundaries
+fn words(text: &str) -> String {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    format!(" {} ", words.join(" "))
+}
+
+/// How many of `topics` appear in `question`
+fn score(topics: &[String], question: &str) -> usize {
+    let question = words(question);
+    topics
+        .iter()
+        .map(|topic| words(topic))
+        .filter(|topic| !topic.trim().is_empty() && question.contains(topic.as_str()))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(agent_id: &str, topics: &[&str]) -> PeerCapabilities {
+        PeerCapabilities {
+            agent_id: agent_id.to_string(),
+            name: agent_id.to_string(),
+            description: String::new(),
+            version: "0.1.0".to_string(),
+            topics: topics.iter().map(|topic| topic.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_score_matches_whole_words() {
+        let topics = vec!["Domain-Driven Design".to_string(), "nats".to_string()];
+
+        assert_eq!(score(&topics, "How does domain driven design fit NATS?"), 2);
+        assert_eq!(score(&topics, "What is natsu?"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_best_match_only_when_peer_scores_higher() {
+        let mut config = AgentConfig::default();
+        config.peers.topics = vec!["cqrs".to_string()];
+        let peers = Peers::new(&config);
+
+        assert!(peers.record(peer("billing", &["invoice", "payment"])).await);
+        assert!(!peers.record(peer("billing", &["invoice", "payment"])).await);
+        peers.record(peer("ledger", &["payment"])).await;
+
+        let best = peers.best_match("Why did the invoice payment fail?").await;
+        assert_eq!(best.map(|peer| peer.agent_id), Some("billing".to_string()));
+        assert_eq!(peers.best_match("Explain CQRS").await, None);
+    }
+}
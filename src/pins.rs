@@ -0,0 +1,157 @@
+//! Turns and facts pinned to a dialog, for `pin_context`
+//!
+//! A pin keeps its own copy of the text, so it stays in every prompt of the
+//! dialog after the turn it came from has left the context window, been
+//! evicted by `max_history`, or been compacted away. Pins live in memory
+//! and end with their dialog.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::error::{AgentError, Result};
+
+/// Most pins one dialog may hold
+pub const MAX_PINS: usize = 20;
+
+/// Most characters of one pin
+pub const MAX_PIN_CHARS: usize = 2000;
+
+/// Something the user wants the model to keep in mind
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Pin {
+    pub pin_id: String,
+
+    /// Turn the text was taken from, 1-based, or none for a stated fact
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub turn: Option<usize>,
+
+    /// Who said it: `user`, `assistant`, or `system`
+    pub role: String,
+
+    pub content: String,
+
+    pub pinned_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Pins of every dialog, in the order they were pinned
+#[derive(Debug, Default)]
+pub struct Pins {
+    dialogs: Mutex<HashMap<String, Vec<Pin>>>,
+}
+
+impl Pins {
+    /// Pin `content` to `dialog_id`, returning the pin
+    pub fn pin(&self, dialog_id: &str, turn: Option<usize>, role: &str, content: &str) -> Result<Pin> {
+        let content = content.trim();
+        if content.is_empty() {
+            return Err(AgentError::InvalidRequest("Nothing to pin".to_string()));
+        }
+        if content.chars().count() > MAX_PIN_CHARS {
+            return Err(AgentError::InvalidRequest(format!(
+                "Pins hold at most {} characters; pin a shorter fact instead",
+                MAX_PIN_CHARS
+            )));
+        }
+
+        let mut dialogs = self.dialogs.lock().unwrap();
+        let pins = dialogs.entry(dialog_id.to_string()).or_default();
+        if let Some(pinned) = pins.iter().find(|pin| pin.content == content) {
+            return Ok(pinned.clone());
+        }
+        if pins.len() >= MAX_PINS {
+            return Err(AgentError::InvalidRequest(format!(
+                "Dialog {} already has {} pins; unpin one first",
+                dialog_id, MAX_PINS
+            )));
+        }
+
+        let pin = Pin {
+            pin_id: uuid::Uuid::new_v4().to_string(),
+            turn,
+            role: role.to_string(),
+            content: content.to_string(),
+            pinned_at: chrono::Utc::now(),
+        };
+        pins.push(pin.clone());
+        Ok(pin)
+    }
+
+    /// Remove the pin `pin_id` from `dialog_id`, returning it
+    pub fn unpin(&self, dialog_id: &str, pin_id: &str) -> Result<Pin> {
+        let not_found = || AgentError::NotFound(format!("Pin {} of dialog {}", pin_id, dialog_id));
+        let mut dialogs = self.dialogs.lock().unwrap();
+        let pins = dialogs.get_mut(dialog_id).ok_or_else(not_found)?;
+        let position = pins.iter().position(|pin| pin.pin_id == pin_id).ok_or_else(not_found)?;
+        let pin = pins.remove(position);
+        if pins.is_empty() {
+            dialogs.remove(dialog_id);
+        }
+        Ok(pin)
+    }
+
+    pub fn list(&self, dialog_id: &str) -> Vec<Pin> {
+        self.dialogs.lock().unwrap().get(dialog_id).cloned().unwrap_or_default()
+    }
+
+    /// Forget the pins of an ended dialog
+    pub fn forget_dialog(&self, dialog_id: &str) {
+        self.dialogs.lock().unwrap().remove(dialog_id);
+    }
+}
+
+/// The system message carrying a dialog's pins, given the heading to put
+/// above them
+pub fn pinned_context(heading: &str, pins: &[Pin]) -> Option<String> {
+    if pins.is_empty() {
+        return None;
+    }
+
+    let lines: Vec<String> = pins
+        .iter()
+        .map(|pin| match pin.turn {
+            Some(turn) => format!("- ({} in turn {}) {}", pin.role, turn, pin.content),
+            None => format!("- {}", pin.content),
+        })
+        .collect();
+    Some(format!("{}\n{}", heading, lines.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_list_and_unpin() {
+        let pins = Pins::default();
+        let first = pins.pin("dlg-1", Some(3), "user", "We deploy on NATS 2.10").unwrap();
+        let again = pins.pin("dlg-1", None, "user", " We deploy on NATS 2.10 ").unwrap();
+        assert_eq!(first.pin_id, again.pin_id);
+        pins.pin("dlg-1", None, "user", "The domain is billing").unwrap();
+        assert_eq!(pins.list("dlg-1").len(), 2);
+        assert!(pins.list("dlg-2").is_empty());
+
+        assert!(pins.unpin("dlg-1", "missing").is_err());
+        assert_eq!(pins.unpin("dlg-1", &first.pin_id).unwrap().turn, Some(3));
+        assert_eq!(pins.list("dlg-1")[0].content, "The domain is billing");
+
+        assert!(pins.pin("dlg-1", None, "user", "  ").is_err());
+        for n in 1..MAX_PINS {
+            pins.pin("dlg-1", None, "user", &format!("fact {}", n)).unwrap();
+        }
+        assert!(pins.pin("dlg-1", None, "user", "one too many").is_err());
+    }
+
+    #[test]
+    fn test_pinned_context() {
+        let pins = Pins::default();
+        assert_eq!(pinned_context("Pinned:", &pins.list("dlg-1")), None);
+
+        pins.pin("dlg-1", Some(2), "assistant", "Use one subject per event type").unwrap();
+        pins.pin("dlg-1", None, "user", "The domain is billing").unwrap();
+        assert_eq!(
+            pinned_context("Pinned:", &pins.list("dlg-1")).unwrap(),
+            "Pinned:\n- (assistant in turn 2) Use one subject per event type\n- The domain is billing"
+        );
+    }
+}
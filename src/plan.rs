@@ -0,0 +1,94 @@
+//! Implementation plans agreed on in dialogs
+//!
+//! `create_workflow_from_dialog` asks the model for the steps both sides of
+//! a dialog settled on, as a [`WorkflowPlan`], and tracks them as a workflow
+//! that advances one step at a time like the built-in ones.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AgentError, Result};
+
+/// One agreed step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedStep {
+    /// `snake_case` node ID, unique within the plan
+    pub id: String,
+
+    /// What to do, in one sentence
+    pub step: String,
+}
+
+/// Steps extracted from a dialog, in order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowPlan {
+    pub name: String,
+
+    #[serde(default)]
+    pub description: String,
+
+    pub steps: Vec<PlannedStep>,
+}
+
+/// Read the plan out of a model reply, ignoring any prose or code fence
+/// around the JSON
+///
+/// Step IDs are made `snake_case` and unique, since the model does not
+/// always manage either.
+pub fn parse_plan(reply: &str) -> Result<WorkflowPlan> {
+    let json = reply
+        .find('{')
+        .zip(reply.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| &reply[start..=end])
+        .ok_or_else(|| AgentError::ModelError("The model did not extract a plan".to_string()))?;
+
+    let mut plan: WorkflowPlan = serde_json::from_str(json)
+        .map_err(|e| AgentError::ModelError(format!("The model extracted a malformed plan: {}", e)))?;
+    plan.steps.retain(|step| !step.step.trim().is_empty());
+    if plan.steps.is_empty() {
+        return Err(AgentError::Workflow("The dialog has no agreed steps to track".to_string()));
+    }
+
+    let mut seen: Vec<String> = Vec::new();
+    for (index, step) in plan.steps.iter_mut().enumerate() {
+        let mut id = crate::codegen::domain_name(&step.id);
+        if id.is_empty() {
+            id = format!("step_{}", index + 1);
+        }
+        if seen.contains(&id) {
+            id = format!("{}_{}", id, index + 1);
+        }
+        seen.push(id.clone());
+        step.id = id;
+    }
+
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plan_normalizes_ids() {
+        let reply = "```json\n{\"name\": \"Billing domain\", \"steps\": [\
+            {\"id\": \"Define Events\", \"step\": \"Define InvoiceIssued and InvoicePaid\"},\
+            {\"id\": \"define-events\", \"step\": \"Add PaymentFailed\"},\
+            {\"id\": \"\", \"step\": \"Wire handlers to NATS\"},\
+            {\"id\": \"later\", \"step\": \" \"}]}\n```";
+        let plan = parse_plan(reply).unwrap();
+
+        let ids: Vec<&str> = plan.steps.iter().map(|step| step.id.as_str()).collect();
+        assert_eq!(ids, vec!["define_events", "define_events_2", "step_3"]);
+        assert_eq!(plan.description, "");
+    }
+
+    #[test]
+    fn test_parse_plan_without_steps() {
+        assert!(matches!(
+            parse_plan(r#"{"name": "Nothing agreed", "steps": []}"#),
+            Err(AgentError::Workflow(_))
+        ));
+        assert!(matches!(parse_plan("We did not agree on anything."), Err(AgentError::ModelError(_))));
+    }
+}
@@ -0,0 +1,132 @@
+//! Priority lanes for requests
+//!
+//! Commands and dialog messages share `priority.max_concurrent` slots, of
+//! which bulk work may hold all but `priority.reserved_interactive`. Dialog
+//! messages and ordinary commands run in the interactive lane, so a backlog
+//! of batches never leaves a chat waiting for a slot. A command's `priority`
+//! field picks its lane; `batch` and `replay_stream` run in the bulk lane
+//! unless they ask otherwise.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::PriorityConfig;
+
+/// Commands that run in the bulk lane unless they name a priority
+const BULK_COMMANDS: &[&str] = &["batch", "replay_stream"];
+
+/// Lane a request runs in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Someone is waiting on the answer
+    #[default]
+    Interactive,
+
+    /// Automation that can wait behind interactive requests
+    Bulk,
+}
+
+impl Priority {
+    /// Lane for `command_type` when the command does not name one
+    pub fn for_command(command_type: &str) -> Self {
+        if BULK_COMMANDS.contains(&command_type) {
+            Priority::Bulk
+        } else {
+            Priority::Interactive
+        }
+    }
+}
+
+/// A slot held by a running request, freed when dropped
+pub struct Slot {
+    _slot: OwnedSemaphorePermit,
+    _bulk: Option<OwnedSemaphorePermit>,
+}
+
+/// Slots for running requests, some kept free of bulk work
+pub struct PriorityLanes {
+    slots: Arc<Semaphore>,
+    bulk: Arc<Semaphore>,
+}
+
+impl PriorityLanes {
+    pub fn new(config: &PriorityConfig) -> Self {
+        let slots = config.max_concurrent.max(1);
+        Self {
+            slots: Arc::new(Semaphore::new(slots)),
+
+            // Bulk work always gets at least one slot
+            bulk: Arc::new(Semaphore::new(slots.saturating_sub(config.reserved_interactive).max(1))),
+        }
+    }
+
+    /// Wait for a slot in `priority`'s lane
+    pub async fn acquire(&self, priority: Priority) -> Slot {
+        let bulk = match priority {
+            Priority::Bulk => Some(
+                self.bulk
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("lane semaphores are never closed"),
+            ),
+            Priority::Interactive => None,
+        };
+        let slot = self
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("lane semaphores are never closed");
+
+        Slot {
+            _slot: slot,
+            _bulk: bulk,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn lanes(max_concurrent: usize, reserved_interactive: usize) -> PriorityLanes {
+        PriorityLanes::new(&PriorityConfig {
+            max_concurrent,
+            reserved_interactive,
+        })
+    }
+
+    async fn acquired(lanes: &PriorityLanes, priority: Priority) -> Option<Slot> {
+        tokio::time::timeout(Duration::from_millis(20), lanes.acquire(priority)).await.ok()
+    }
+
+    #[tokio::test]
+    async fn test_bulk_work_leaves_reserved_slots() {
+        let lanes = lanes(3, 1);
+
+        let _first = acquired(&lanes, Priority::Bulk).await.unwrap();
+        let _second = acquired(&lanes, Priority::Bulk).await.unwrap();
+        assert!(acquired(&lanes, Priority::Bulk).await.is_none());
+
+        let interactive = acquired(&lanes, Priority::Interactive).await.unwrap();
+        assert!(acquired(&lanes, Priority::Interactive).await.is_none());
+        drop(interactive);
+        assert!(acquired(&lanes, Priority::Interactive).await.is_some());
+    }
+
+    #[test]
+    fn test_priority_for_command() {
+        assert_eq!(Priority::for_command("batch"), Priority::Bulk);
+        assert_eq!(Priority::for_command("replay_stream"), Priority::Bulk);
+        assert_eq!(Priority::for_command("explain_concept"), Priority::Interactive);
+        assert_eq!(serde_json::from_str::<Priority>("\"bulk\"").unwrap(), Priority::Bulk);
+
+        // Every lane gets a slot, however the limits are set
+        let lanes = lanes(1, 5);
+        assert_eq!(lanes.bulk.available_permits(), 1);
+    }
+}
@@ -0,0 +1,334 @@
+//! Concept quizzes for onboarding CIM developers
+//!
+//! `start_quiz` asks a user a question about a concept from the knowledge
+//! graph, and `answer_quiz` has the model grade their answer and asks the
+//! next one. Questions get harder after two right answers in a row and
+//! easier after two wrong ones, and harder questions score more points.
+//! Scores live in memory, so they start over when the agent restarts.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::error::{AgentError, Result};
+use crate::knowledge::ConceptGraph;
+
+/// Answers in a row, right or wrong, that change the difficulty
+const STREAK_TO_ADAPT: i32 = 2;
+
+/// Concepts recently asked about, not asked again until others have been
+const RECENT_CONCEPTS: usize = 5;
+
+/// How hard questions are
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    #[default]
+    Beginner,
+    Intermediate,
+    Advanced,
+}
+
+impl Difficulty {
+    fn harder(self) -> Self {
+        match self {
+            Difficulty::Beginner => Difficulty::Intermediate,
+            _ => Difficulty::Advanced,
+        }
+    }
+
+    fn easier(self) -> Self {
+        match self {
+            Difficulty::Advanced => Difficulty::Intermediate,
+            _ => Difficulty::Beginner,
+        }
+    }
+
+    /// Points for a right answer
+    fn points(self) -> u32 {
+        match self {
+            Difficulty::Beginner => 1,
+            Difficulty::Intermediate => 2,
+            Difficulty::Advanced => 3,
+        }
+    }
+
+    /// What a question at this level asks for
+    fn guidance(self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "what the concept is and why it exists",
+            Difficulty::Intermediate => "how to apply the concept in a CIM domain, or a pitfall when using it",
+            Difficulty::Advanced => "a design trade-off, or how the concept interacts with the related concept",
+        }
+    }
+}
+
+/// A question waiting for its answer
+#[derive(Debug, Clone, Serialize)]
+pub struct QuizQuestion {
+    pub quiz_id: String,
+    pub user_id: String,
+    pub concept: String,
+
+    /// Concept the question relates it to, at the advanced level
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub related: Option<String>,
+
+    pub difficulty: Difficulty,
+    pub question: String,
+}
+
+/// A user's progress
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Learner {
+    pub answered: u32,
+    pub correct: u32,
+
+    /// Points from right answers, more for harder questions
+    pub score: u32,
+
+    /// Level of the next question
+    pub difficulty: Difficulty,
+
+    /// Right answers in a row when positive, wrong ones when negative
+    #[serde(skip)]
+    streak: i32,
+
+    #[serde(skip)]
+    recent: VecDeque<String>,
+}
+
+impl Learner {
+    /// Count an answer and adapt the difficulty
+    fn record(&mut self, difficulty: Difficulty, correct: bool) {
+        self.answered += 1;
+        if correct {
+            self.correct += 1;
+            self.score += difficulty.points();
+            self.streak = self.streak.max(0) + 1;
+        } else {
+            self.streak = self.streak.min(0) - 1;
+        }
+
+        if self.streak >= STREAK_TO_ADAPT {
+            self.difficulty = self.difficulty.harder();
+            self.streak = 0;
+        } else if self.streak <= -STREAK_TO_ADAPT {
+            self.difficulty = self.difficulty.easier();
+            self.streak = 0;
+        }
+    }
+}
+
+/// The model's verdict on an answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Grade {
+    pub correct: bool,
+
+    /// What was right or missing, addressed to the user
+    #[serde(default)]
+    pub feedback: String,
+}
+
+/// Learners and their open questions
+#[derive(Debug, Default)]
+pub struct Quizzes {
+    learners: Mutex<HashMap<String, Learner>>,
+    pending: Mutex<HashMap<String, QuizQuestion>>,
+}
+
+impl Quizzes {
+    pub fn learner(&self, user_id: &str) -> Learner {
+        self.learners.lock().unwrap().get(user_id).cloned().unwrap_or_default()
+    }
+
+    /// Start `user_id` at `difficulty` instead of where they left off
+    pub fn set_difficulty(&self, user_id: &str, difficulty: Difficulty) {
+        let mut learners = self.learners.lock().unwrap();
+        let learner = learners.entry(user_id.to_string()).or_default();
+        learner.difficulty = difficulty;
+        learner.streak = 0;
+    }
+
+    /// A concept to ask `user_id` about, and at the advanced level one it
+    /// relates to, avoiding concepts they were asked about recently
+    pub fn choose_topic(&self, user_id: &str, graph: &ConceptGraph) -> Option<(String, Option<String>)> {
+        let learner = self.learner(user_id);
+        let fresh: Vec<&str> = graph
+            .concepts()
+            .map(|concept| concept.name.as_str())
+            .filter(|name| !learner.recent.iter().any(|recent| recent == name))
+            .collect();
+        let candidates = if fresh.is_empty() {
+            graph.concepts().map(|concept| concept.name.as_str()).collect()
+        } else {
+            fresh
+        };
+
+        // Relations make for harder questions, when the graph has any
+        let related: Vec<&str> = candidates
+            .iter()
+            .copied()
+            .filter(|name| !graph.related(name).is_empty())
+            .collect();
+        let pool = if learner.difficulty == Difficulty::Advanced && !related.is_empty() {
+            related
+        } else {
+            candidates
+        };
+
+        let concept = *pool.get(pick(pool.len()))?;
+        let related = match learner.difficulty {
+            Difficulty::Advanced => {
+                let related = graph.related(concept);
+                related.get(pick(related.len())).cloned()
+            }
+            _ => None,
+        };
+        Some((concept.to_string(), related))
+    }
+
+    /// Hold `question` until it is answered, replacing the quiz's last one
+    pub fn ask(&self, question: QuizQuestion) {
+        let mut learners = self.learners.lock().unwrap();
+        let learner = learners.entry(question.user_id.clone()).or_default();
+        learner.recent.push_back(question.concept.clone());
+        if learner.recent.len() > RECENT_CONCEPTS {
+            learner.recent.pop_front();
+        }
+        drop(learners);
+
+        self.pending.lock().unwrap().insert(question.quiz_id.clone(), question);
+    }
+
+    /// The open question of `quiz_id`
+    pub fn pending(&self, quiz_id: &str) -> Result<QuizQuestion> {
+        self.pending
+            .lock()
+            .unwrap()
+            .get(quiz_id)
+            .cloned()
+            .ok_or_else(|| AgentError::NotFound(format!("Quiz {}", quiz_id)))
+    }
+
+    /// Record the grade of `question`'s answer, returning the learner's
+    /// progress
+    pub fn grade(&self, question: &QuizQuestion, correct: bool) -> Learner {
+        self.pending.lock().unwrap().remove(&question.quiz_id);
+
+        let mut learners = self.learners.lock().unwrap();
+        let learner = learners.entry(question.user_id.clone()).or_default();
+        learner.record(question.difficulty, correct);
+        learner.clone()
+    }
+}
+
+/// An index below `len`, at random
+fn pick(len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    (uuid::Uuid::new_v4().as_u128() % len as u128) as usize
+}
+
+/// Prompt asking the model for one question
+pub fn question_prompt(
+    concept: &str,
+    description: &str,
+    related: Option<&str>,
+    difficulty: Difficulty,
+) -> String {
+    let mut prompt = format!(
+        "Write one {:?} quiz question about the CIM concept {} for a developer learning CIM. \
+         Ask about {}.",
+        difficulty,
+        concept,
+        difficulty.guidance()
+    );
+    if !description.is_empty() {
+        prompt.push_str(&format!("\n\n{} is described as: {}", concept, description));
+    }
+    if let Some(related) = related {
+        prompt.push_str(&format!("\n\nThe related concept is {}.", related));
+    }
+    prompt.push_str("\n\nReply with the question only, answerable in a few sentences, without the answer.");
+    prompt
+}
+
+/// Prompt asking the model to grade an answer
+pub fn grading_prompt(question: &QuizQuestion, answer: &str) -> String {
+    format!(
+        "Grade a developer's answer to a {:?} quiz question about the CIM concept {}.\n\n\
+         Question:\n{}\n\nAnswer:\n{}\n\n\
+         Reply with JSON only, shaped as {{\"correct\": true, \"feedback\": \"...\"}}. The answer is correct when \
+         it gets the essential idea right, even if informally worded. The feedback is two sentences at most, \
+         addressed to the developer, saying what was right and what was missing.",
+        question.difficulty, question.concept, question.question, answer
+    )
+}
+
+/// Read the grade out of a model reply, ignoring any prose around the JSON
+pub fn parse_grade(reply: &str) -> Result<Grade> {
+    let json = reply
+        .find('{')
+        .zip(reply.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| &reply[start..=end])
+        .ok_or_else(|| AgentError::ModelError("The model did not grade the answer".to_string()))?;
+
+    serde_json::from_str(json).map_err(|e| AgentError::ModelError(format!("The model returned a malformed grade: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn question(quizzes: &Quizzes, difficulty: Difficulty) -> QuizQuestion {
+        let question = QuizQuestion {
+            quiz_id: "quiz-1".to_string(),
+            user_id: "ada".to_string(),
+            concept: "CQRS".to_string(),
+            related: None,
+            difficulty,
+            question: "What does CQRS separate?".to_string(),
+        };
+        quizzes.ask(question.clone());
+        question
+    }
+
+    #[test]
+    fn test_difficulty_adapts_to_streaks() {
+        let quizzes = Quizzes::default();
+
+        quizzes.grade(&question(&quizzes, Difficulty::Beginner), true);
+        let learner = quizzes.grade(&question(&quizzes, Difficulty::Beginner), true);
+        assert_eq!((learner.score, learner.difficulty), (2, Difficulty::Intermediate));
+
+        let learner = quizzes.grade(&question(&quizzes, Difficulty::Intermediate), true);
+        assert_eq!((learner.score, learner.difficulty), (4, Difficulty::Intermediate));
+
+        quizzes.grade(&question(&quizzes, Difficulty::Intermediate), false);
+        let learner = quizzes.grade(&question(&quizzes, Difficulty::Intermediate), false);
+        assert_eq!((learner.answered, learner.correct), (5, 3));
+        assert_eq!(learner.difficulty, Difficulty::Beginner);
+        assert!(quizzes.pending("quiz-1").is_err());
+    }
+
+    #[test]
+    fn test_topics_and_grades() {
+        let quizzes = Quizzes::default();
+        let graph = ConceptGraph::with_concepts(&["CQRS"]);
+        assert_eq!(quizzes.choose_topic("ada", &graph), Some(("CQRS".to_string(), None)));
+        assert_eq!(quizzes.choose_topic("ada", &ConceptGraph::default()), None);
+
+        let grade = parse_grade("Here you go: {\"correct\": false, \"feedback\": \"Reads are missing.\"}").unwrap();
+        assert_eq!(
+            grade,
+            Grade {
+                correct: false,
+                feedback: "Reads are missing.".to_string(),
+            }
+        );
+        assert!(parse_grade("Looks right to me").is_err());
+    }
+}
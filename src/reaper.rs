@@ -0,0 +1,128 @@
+//! Ending dialogs and workflows left idle
+//!
+//! Dialogs without a turn for `domains.dialog.session_timeout` are ended
+//! and dropped from memory, each announced with a `dialog_expired` event
+//! and counted in the dialog metrics. Their stored history is kept, so a
+//! later message resumes them. A dialog that never had a turn counts as
+//! idle from when the reaper first saw it.
+//!
+//! Running workflows not advanced for `domains.workflow.timeout` fail, each
+//! announced with a `workflow_timed_out` event.
+
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+use crate::agent::AlchemistAgent;
+
+/// Sweeps happen this often at most, however long the timeout
+const MAX_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Expires idle dialogs of an agent
+pub struct DialogReaper {
+    timeout: Duration,
+    agent: Arc<AlchemistAgent>,
+
+    /// When each dialog without turns was first seen
+    first_seen: HashMap<String, DateTime<Utc>>,
+}
+
+impl DialogReaper {
+    pub fn new(timeout: Duration, agent: Arc<AlchemistAgent>) -> Self {
+        Self {
+            timeout,
+            agent,
+            first_seen: HashMap::new(),
+        }
+    }
+
+    /// Sweep for idle dialogs until the task is cancelled
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(sweep_interval(self.timeout));
+        loop {
+            interval.tick().await;
+            let expired = self.sweep(Utc::now()).await;
+            if expired > 0 {
+                info!("Expired {} idle dialogs", expired);
+            }
+        }
+    }
+
+    /// Expire the dialogs idle at `now`, returning how many were
+    pub async fn sweep(&mut self, now: DateTime<Utc>) -> usize {
+        let activity = self.agent.dialog_activity().await;
+
+        // Dialogs that ended or got a turn start over if they come back empty
+        let empty: HashSet<&str> = activity
+            .iter()
+            .filter(|(_, last_turn)| last_turn.is_none())
+            .map(|(dialog_id, _)| dialog_id.as_str())
+            .collect();
+        self.first_seen.retain(|dialog_id, _| empty.contains(dialog_id.as_str()));
+
+        let mut expired = 0;
+        for (dialog_id, last_turn) in &activity {
+            let idle_since = match last_turn {
+                Some(timestamp) => *timestamp,
+                None => *self.first_seen.entry(dialog_id.clone()).or_insert(now),
+            };
+            if !is_idle(idle_since, now, self.timeout) {
+                continue;
+            }
+
+            if self.agent.expire_dialog(dialog_id, *last_turn, now - idle_since).await {
+                self.first_seen.remove(dialog_id);
+                expired += 1;
+            }
+        }
+        expired
+    }
+}
+
+/// Time out idle workflows of `agent` until the task is cancelled
+pub async fn expire_workflows(agent: Arc<AlchemistAgent>, timeout: Duration) {
+    let mut interval = tokio::time::interval(sweep_interval(timeout));
+    loop {
+        interval.tick().await;
+        let expired = agent.expire_workflows().await;
+        if expired > 0 {
+            info!("Timed out {} idle workflows", expired);
+        }
+    }
+}
+
+/// Time between sweeps, a tenth of the timeout so dialogs do not outlive
+/// it by much
+fn sweep_interval(timeout: Duration) -> Duration {
+    (timeout / 10).clamp(Duration::from_secs(1), MAX_SWEEP_INTERVAL)
+}
+
+/// Whether a dialog idle since `since` has timed out at `now`
+fn is_idle(since: DateTime<Utc>, now: DateTime<Utc>, timeout: Duration) -> bool {
+    (now - since).to_std().is_ok_and(|idle| idle >= timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_idle() {
+        let since = "2024-01-15T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let timeout = Duration::from_secs(3600);
+
+        assert!(!is_idle(since, since + chrono::Duration::minutes(59), timeout));
+        assert!(is_idle(since, since + chrono::Duration::minutes(60), timeout));
+        // A turn stamped after the sweep started is not idle
+        assert!(!is_idle(since, since - chrono::Duration::seconds(1), timeout));
+    }
+
+    #[test]
+    fn test_sweep_interval() {
+        assert_eq!(sweep_interval(Duration::from_secs(3600)), MAX_SWEEP_INTERVAL);
+        assert_eq!(sweep_interval(Duration::from_secs(300)), Duration::from_secs(30));
+        assert_eq!(sweep_interval(Duration::from_secs(2)), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,275 @@
+//! Conversation replays for regression testing prompts and models
+//!
+//! With `replay.record`, every dialog answer the agent's own model gives is
+//! appended to `replay.path` as a [`ReplayCase`]: the prompt with the
+//! excerpts retrieved for it, the context sent along, and the answer.
+//! `alchemist replay` sends recorded cases to another model or system
+//! prompt and reports how the answers and their token costs change, so a
+//! prompt change can be judged before it is deployed.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+use crate::budget::estimate_tokens;
+use crate::error::{AgentError, Result};
+use crate::model::{Message, ModelProvider};
+
+/// One recorded exchange with the model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayCase {
+    pub id: String,
+    pub dialog_id: String,
+    pub recorded_at: chrono::DateTime<chrono::Utc>,
+
+    /// Model that gave the recorded answer
+    pub model: String,
+
+    /// The user's message with attachments and retrieved excerpts
+    pub prompt: String,
+
+    /// System prompt and dialog history sent with the prompt
+    pub context: Vec<Message>,
+
+    pub answer: String,
+
+    /// Estimated tokens of the prompt, context, and answer
+    pub tokens: usize,
+}
+
+impl ReplayCase {
+    pub fn new(dialog_id: &str, model: &str, prompt: &str, context: &[Message], answer: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            dialog_id: dialog_id.to_string(),
+            recorded_at: chrono::Utc::now(),
+            model: model.to_string(),
+            prompt: prompt.to_string(),
+            context: context.to_vec(),
+            answer: answer.to_string(),
+            tokens: exchange_tokens(prompt, context, answer),
+        }
+    }
+}
+
+fn exchange_tokens(prompt: &str, context: &[Message], answer: &str) -> usize {
+    estimate_tokens(prompt)
+        + context.iter().map(|message| estimate_tokens(&message.content)).sum::<usize>()
+        + estimate_tokens(answer)
+}
+
+/// Appends cases to a JSON Lines file
+pub struct ReplayRecorder {
+    path: PathBuf,
+
+    /// Keeps concurrent answers from interleaving their lines
+    file: tokio::sync::Mutex<()>,
+}
+
+impl ReplayRecorder {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub async fn record(&self, case: &ReplayCase) -> Result<()> {
+        let mut line = serde_json::to_vec(case)?;
+        line.push(b'\n');
+
+        let _file = self.file.lock().await;
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(&line).await?;
+        Ok(())
+    }
+}
+
+/// Read the cases recorded in `path`
+pub fn load_cases(path: &Path) -> Result<Vec<ReplayCase>> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                AgentError::InvalidRequest(format!("{} line {}: {}", path.display(), index + 1, e))
+            })
+        })
+        .collect()
+}
+
+/// How one case came out when replayed
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseReplay {
+    pub id: String,
+    pub dialog_id: String,
+
+    /// Share of words the answers have in common, from 0 to 1
+    pub similarity: f32,
+
+    pub baseline_tokens: usize,
+    pub replay_tokens: usize,
+
+    /// The recorded answer's lines removed (`- `) and added (`+ `)
+    pub diff: Vec<String>,
+
+    /// Why the case could not be replayed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Results of replaying recorded cases
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayReport {
+    /// Model the cases were replayed against
+    pub model: String,
+
+    /// Cases whose answers changed
+    pub changed: usize,
+
+    pub failed: usize,
+    pub baseline_tokens: usize,
+    pub replay_tokens: usize,
+    pub cases: Vec<CaseReplay>,
+}
+
+/// Replay `cases` against `provider`, with `system_prompt` in place of the
+/// recorded one if given
+pub async fn replay(cases: &[ReplayCase], provider: &dyn ModelProvider, system_prompt: Option<&str>) -> ReplayReport {
+    let mut report = ReplayReport {
+        model: provider.model_info().model,
+        changed: 0,
+        failed: 0,
+        baseline_tokens: 0,
+        replay_tokens: 0,
+        cases: Vec::with_capacity(cases.len()),
+    };
+
+    for case in cases {
+        let mut context = case.context.clone();
+        if let Some(system_prompt) = system_prompt {
+            for message in context.iter_mut().filter(|message| message.role == "system") {
+                message.content = system_prompt.to_string();
+            }
+        }
+
+        let (answer, error) = match provider.generate_with_context(&case.prompt, &context).await {
+            Ok(answer) => (answer, None),
+            Err(e) => (String::new(), Some(e.to_string())),
+        };
+        let replayed = CaseReplay {
+            id: case.id.clone(),
+            dialog_id: case.dialog_id.clone(),
+            similarity: if error.is_some() { 0.0 } else { similarity(&case.answer, &answer) },
+            baseline_tokens: case.tokens,
+            replay_tokens: if error.is_some() { 0 } else { exchange_tokens(&case.prompt, &context, &answer) },
+            diff: if error.is_some() { Vec::new() } else { line_diff(&case.answer, &answer) },
+            error,
+        };
+
+        if replayed.error.is_some() {
+            report.failed += 1;
+        } else {
+            report.changed += usize::from(!replayed.diff.is_empty());
+            report.baseline_tokens += replayed.baseline_tokens;
+            report.replay_tokens += replayed.replay_tokens;
+        }
+        report.cases.push(replayed);
+    }
+
+    report
+}
+
+/// Lengths of the longest common subsequences of every suffix of `a` and `b`
+fn lcs_table<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Vec<usize>> {
+    let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            table[i][j] = if a[i] == b[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+    table
+}
+
+fn similarity(baseline: &str, replayed: &str) -> f32 {
+    let a: Vec<&str> = baseline.split_whitespace().collect();
+    let b: Vec<&str> = replayed.split_whitespace().collect();
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let common = lcs_table(&a, &b)[0][0];
+    (2 * common) as f32 / (a.len() + b.len()) as f32
+}
+
+/// Lines removed from and added to `baseline`, empty when they match
+fn line_diff(baseline: &str, replayed: &str) -> Vec<String> {
+    let a: Vec<&str> = baseline.lines().collect();
+    let b: Vec<&str> = replayed.lines().collect();
+    let table = lcs_table(&a, &b);
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || table[i][j + 1] >= table[i + 1][j]) {
+            diff.push(format!("+ {}", b[j]));
+            j += 1;
+        } else {
+            diff.push(format!("- {}", a[i]));
+            i += 1;
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::MockProvider;
+
+    fn case(answer: &str) -> ReplayCase {
+        let context = vec![Message {
+            role: "system".to_string(),
+            content: "You are the Alchemist.".to_string(),
+            timestamp: chrono::Utc::now(),
+        }];
+        ReplayCase::new("d1", "vicuna", "What is CQRS?", &context, answer)
+    }
+
+    #[test]
+    fn test_line_diff_and_similarity() {
+        let baseline = "CQRS separates reads from writes.\nCommands change state.";
+        let replayed = "CQRS separates reads from writes.\nQueries read projections.";
+
+        assert_eq!(line_diff(baseline, replayed), vec!["- Commands change state.", "+ Queries read projections."]);
+        assert!(line_diff(baseline, baseline).is_empty());
+        assert_eq!(similarity(baseline, baseline), 1.0);
+        assert!(similarity(baseline, replayed) > 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_replay_reports_changes_and_tokens() {
+        let provider = MockProvider::new("CQRS separates reads from writes.".to_string());
+        let cases = vec![case("CQRS separates reads from writes."), case("CQRS is a database.")];
+
+        let report = replay(&cases, &provider, Some("Answer in one sentence.")).await;
+        assert_eq!(report.changed, 1);
+        assert_eq!(report.failed, 0);
+        assert!(report.cases[0].diff.is_empty());
+        assert_eq!(report.baseline_tokens, cases[0].tokens + cases[1].tokens);
+    }
+}
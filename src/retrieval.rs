@@ -0,0 +1,284 @@
+//! Retrieval-augmented generation over embedded documents
+//!
+//! Files under `retrieval.directories`, and with `include_sources` the files
+//! of the code sources, are split into chunks of about `chunk_size`
+//! characters, on line boundaries and at Markdown headings, and each chunk
+//! is embedded by the model provider. The `top_k` chunks most similar to a
+//! dialog message go into its prompt beside the keyword excerpts, and are
+//! cited the same way. The index lives in memory and is refreshed every
+//! `refresh_interval`, embedding only files whose content changed.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::agent::AlchemistAgent;
+use crate::conceptual::cosine_similarity;
+use crate::error::Result;
+use crate::model::ModelProvider;
+use crate::sources::{content_hash, CodeMatch};
+
+/// Files larger than this are not indexed
+const MAX_FILE_BYTES: u64 = 200_000;
+
+/// Part of a document embedded on its own
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chunk {
+    /// 1-based lines the chunk spans, inclusive
+    pub start_line: usize,
+    pub end_line: usize,
+
+    /// Nearest heading at or above the chunk, in Markdown files
+    pub heading: Option<String>,
+
+    pub text: String,
+}
+
+/// Split `content` into chunks of at most about `chunk_size` characters
+///
+/// Chunks end on line boundaries, so a line longer than `chunk_size` is a
+/// chunk of its own. Markdown files also start a chunk at every heading.
+pub fn chunk(path: &str, content: &str, chunk_size: usize) -> Vec<Chunk> {
+    let markdown = path.ends_with(".md");
+    let mut chunks = Vec::new();
+    let mut heading = None;
+    let mut current = Chunk::default();
+
+    for (index, line) in content.lines().enumerate() {
+        let is_heading = markdown && line.starts_with('#');
+        let full = current.text.len() + line.len() + 1 > chunk_size;
+        if (is_heading || full) && !current.text.is_empty() {
+            let done = std::mem::take(&mut current);
+            if !done.text.trim().is_empty() {
+                chunks.push(done);
+            }
+        }
+
+        if is_heading {
+            heading = Some(line.trim_start_matches('#').trim().to_string());
+        }
+        if current.text.is_empty() {
+            current.start_line = index + 1;
+            current.heading = heading.clone();
+        }
+        current.text.push_str(line);
+        current.text.push('\n');
+        current.end_line = index + 1;
+    }
+
+    if !current.text.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// A document's chunks and their embeddings
+#[derive(Debug)]
+struct Document {
+    hash: u64,
+    chunks: Vec<(Chunk, Vec<f32>)>,
+}
+
+/// Embedded chunks of every indexed document
+#[derive(Debug, Default)]
+pub struct DocumentIndex {
+    /// Model that computed the embeddings
+    model: String,
+
+    /// Documents keyed by source, then path
+    documents: BTreeMap<(String, String), Document>,
+}
+
+impl DocumentIndex {
+    /// Model the embeddings came from
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Forget every embedding if they came from another model than `model`
+    pub fn use_model(&mut self, model: &str) {
+        if self.model != model {
+            self.model = model.to_string();
+            self.documents.clear();
+        }
+    }
+
+    /// Whether `path` of `source` is indexed with the content hashing to `hash`
+    pub fn is_current(&self, source: &str, path: &str, hash: u64) -> bool {
+        self.documents
+            .get(&(source.to_string(), path.to_string()))
+            .is_some_and(|document| document.hash == hash)
+    }
+
+    pub fn insert(&mut self, source: &str, path: &str, hash: u64, chunks: Vec<(Chunk, Vec<f32>)>) {
+        self.documents
+            .insert((source.to_string(), path.to_string()), Document { hash, chunks });
+    }
+
+    /// Keep only the documents `keep` accepts, by source and path
+    pub fn retain(&mut self, keep: impl Fn(&str, &str) -> bool) {
+        self.documents.retain(|(source, path), _| keep(source, path));
+    }
+
+    /// Number of chunks indexed
+    pub fn len(&self) -> usize {
+        self.documents.values().map(|document| document.chunks.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Up to `top_k` chunks at least `min_similarity` similar to `embedding`,
+    /// most similar first
+    pub fn search(&self, embedding: &[f32], top_k: usize, min_similarity: f32) -> Vec<(CodeMatch, f32)> {
+        let mut found: Vec<(CodeMatch, f32)> = self
+            .documents
+            .iter()
+            .flat_map(|((source, path), document)| {
+                document.chunks.iter().map(move |(chunk, chunk_embedding)| {
+                    (source, path, chunk, cosine_similarity(embedding, chunk_embedding))
+                })
+            })
+            .filter(|(_, _, _, similarity)| *similarity >= min_similarity)
+            .map(|(source, path, chunk, similarity)| {
+                let code_match = CodeMatch {
+                    repo: source.clone(),
+                    path: path.clone(),
+                    line: chunk.start_line,
+                    snippet: chunk.text.trim_end().to_string(),
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    heading: chunk.heading.clone(),
+                    score: 0,
+                };
+                (code_match, similarity)
+            })
+            .collect();
+
+        found.sort_by(|a, b| b.1.total_cmp(&a.1));
+        found.truncate(top_k);
+        found
+    }
+}
+
+/// Bring `index` up to date with `documents`, given as source, path, and
+/// content, returning how many chunks were embedded
+///
+/// Documents no longer given are dropped. The index is only locked while
+/// it is read and updated, not while the provider embeds.
+pub async fn refresh(
+    index: &tokio::sync::RwLock<DocumentIndex>,
+    provider: &dyn ModelProvider,
+    model: &str,
+    documents: Vec<(String, String, String)>,
+    chunk_size: usize,
+) -> Result<usize> {
+    let stale: Vec<(String, String, String, u64)> = {
+        let mut index = index.write().await;
+        index.use_model(model);
+        let given: std::collections::BTreeSet<(&str, &str)> = documents
+            .iter()
+            .map(|(source, path, _)| (source.as_str(), path.as_str()))
+            .collect();
+        index.retain(|source, path| given.contains(&(source, path)));
+
+        documents
+            .into_iter()
+            .map(|(source, path, content)| {
+                let hash = content_hash(&content);
+                (source, path, content, hash)
+            })
+            .filter(|(source, path, _, hash)| !index.is_current(source, path, *hash))
+            .collect()
+    };
+
+    let mut embedded = 0;
+    for (source, path, content, hash) in stale {
+        let mut chunks = Vec::new();
+        for piece in chunk(&path, &content, chunk_size) {
+            let embedding = provider.embed(&piece.text).await?;
+            chunks.push((piece, embedding));
+        }
+        embedded += chunks.len();
+
+        let mut index = index.write().await;
+        // The model changed while this document was embedded
+        if index.model() != model {
+            break;
+        }
+        index.insert(&source, &path, hash, chunks);
+    }
+
+    Ok(embedded)
+}
+
+/// Files under `directories` with one of `extensions`, as source, path, and
+/// content, the source being the directory as configured
+pub fn collect_directories(directories: &[PathBuf], extensions: &[String]) -> Vec<(String, String, String)> {
+    directories
+        .iter()
+        .flat_map(|directory| {
+            let source = directory.to_string_lossy().to_string();
+            crate::sources::git::collect_files(directory, extensions, MAX_FILE_BYTES)
+                .into_iter()
+                .map(move |(path, content)| (source.clone(), path, content))
+        })
+        .collect()
+}
+
+/// Reindex the agent's documents every `interval`, for as long as the task runs
+pub async fn run(agent: Arc<AlchemistAgent>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        match agent.index_documents().await {
+            Ok(0) => {}
+            Ok(embedded) => tracing::info!("Embedded {} document chunks for retrieval", embedded),
+            Err(e) => tracing::warn!("Failed to index documents for retrieval: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_breaks_at_headings_and_size() {
+        let content = "# Events\n\nEvents are facts.\n\n## Naming\n\nPast tense.\nAlways.\n";
+        let chunks = chunk("events.md", content, 1000);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 4));
+        assert_eq!(chunks[1].heading.as_deref(), Some("Naming"));
+        assert!(chunks[1].text.starts_with("## Naming\n"));
+
+        let chunks = chunk("lib.rs", "fn a() {}\nfn b() {}\nfn c() {}\n", 20);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[1].start_line, chunks[1].end_line), (3, 3));
+        assert!(chunks.iter().all(|chunk| chunk.heading.is_none()));
+    }
+
+    #[test]
+    fn test_index_search_and_staleness() {
+        let mut index = DocumentIndex::default();
+        index.use_model("Ollama/nomic-embed-text");
+        let chunks = chunk("cqrs.md", "# CQRS\n\nSplit writes from reads.\n", 500);
+        index.insert("cim-docs", "cqrs.md", content_hash("cqrs"), vec![(chunks[0].clone(), vec![1.0, 0.0])]);
+        index.insert("cim-docs", "sagas.md", content_hash("sagas"), vec![(chunks[0].clone(), vec![0.0, 1.0])]);
+
+        assert!(index.is_current("cim-docs", "cqrs.md", content_hash("cqrs")));
+        assert!(!index.is_current("cim-docs", "cqrs.md", content_hash("cqrs, edited")));
+
+        let found = index.search(&[0.9, 0.1], 5, 0.5);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0.path, "cqrs.md");
+        assert_eq!(found[0].0.heading.as_deref(), Some("CQRS"));
+
+        index.retain(|_, path| path != "sagas.md");
+        assert_eq!(index.len(), 1);
+        index.use_model("Ollama/mxbai-embed-large");
+        assert!(index.is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+//! Configuration scaffolding
+//!
+//! Builds a starter `AgentConfig` for a chosen model provider, NATS
+//! authentication mode, and storage backend. Used by `alchemist init`.
+
+use crate::config::{AgentConfig, ModelConfig, NatsAuth, StorageBackend};
+pub use crate::config::ProviderKind;
+use crate::error::{AgentError, Result};
+use std::str::FromStr;
+
+/// NATS authentication modes that can be scaffolded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NatsAuthMode {
+    None,
+    Token,
+    UserPassword,
+    Jwt,
+    CredentialsFile,
+    NKey,
+    Tls,
+}
+
+/// Storage backends that can be scaffolded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    Memory,
+    JetStream,
+}
+
+/// Choices for a scaffolded configuration
+#[derive(Debug, Clone, Copy)]
+pub struct ScaffoldOptions {
+    pub provider: ProviderKind,
+    pub nats_auth: NatsAuthMode,
+    pub storage: StorageKind,
+}
+
+impl NatsAuthMode {
+    /// Accepted names, in prompt order
+    pub const NAMES: &'static [&'static str] = &["none", "token", "user-password", "jwt", "creds", "nkey", "tls"];
+}
+
+impl StorageKind {
+    /// Accepted names, in prompt order
+    pub const NAMES: &'static [&'static str] = &["memory", "jetstream"];
+}
+
+impl FromStr for NatsAuthMode {
+    type Err = AgentError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "token" => Ok(Self::Token),
+            "user-password" | "userpassword" => Ok(Self::UserPassword),
+            "jwt" => Ok(Self::Jwt),
+            "creds" | "credentials-file" => Ok(Self::CredentialsFile),
+            "nkey" => Ok(Self::NKey),
+            "tls" => Ok(Self::Tls),
+            other => Err(unknown_choice("NATS auth mode", other, Self::NAMES)),
+        }
+    }
+}
+
+impl FromStr for StorageKind {
+    type Err = AgentError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "memory" => Ok(Self::Memory),
+            "jetstream" => Ok(Self::JetStream),
+            other => Err(unknown_choice("storage backend", other, Self::NAMES)),
+        }
+    }
+}
+
+fn unknown_choice(what: &str, value: &str, names: &[&str]) -> AgentError {
+    AgentError::Configuration(format!(
+        "Unknown {}: {} (expected one of {})",
+        what,
+        value,
+        names.join(", ")
+    ))
+}
+
+/// Build a starter configuration from the given choices
+///
+/// Secrets are filled with obvious placeholders to be edited by hand.
+pub fn build_config(options: &ScaffoldOptions) -> AgentConfig {
+    let mut config = AgentConfig::default();
+
+    config.model = ModelConfig::for_provider(options.provider, None);
+
+    config.nats.auth = match options.nats_auth {
+        NatsAuthMode::None => None,
+        NatsAuthMode::Token => Some(NatsAuth::Token {
+            token: "<NATS_TOKEN>".to_string(),
+        }),
+        NatsAuthMode::UserPassword => Some(NatsAuth::UserPassword {
+            username: "alchemist".to_string(),
+            password: "<NATS_PASSWORD>".to_string(),
+        }),
+        NatsAuthMode::Jwt => Some(NatsAuth::Jwt {
+            jwt: "<NATS_USER_JWT>".to_string(),
+            seed: "<NATS_NKEY_SEED>".to_string(),
+        }),
+        NatsAuthMode::CredentialsFile => Some(NatsAuth::CredentialsFile {
+            path: "/etc/alchemist/nats/alchemist.creds".to_string(),
+        }),
+        NatsAuthMode::NKey => Some(NatsAuth::NKey {
+            seed: "<NATS_NKEY_SEED>".to_string(),
+        }),
+        NatsAuthMode::Tls => Some(NatsAuth::Tls {
+            cert_path: "/etc/alchemist/tls/client.crt".to_string(),
+            key_path: "/etc/alchemist/tls/client.key".to_string(),
+        }),
+    };
+
+    config.storage.backend = match options.storage {
+        StorageKind::Memory => StorageBackend::Memory,
+        StorageKind::JetStream => StorageBackend::JetStream {
+            bucket: "ALCHEMIST_STATE".to_string(),
+        },
+    };
+
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_config_for_anthropic_with_jwt() {
+        let config = build_config(&ScaffoldOptions {
+            provider: "anthropic".parse().unwrap(),
+            nats_auth: "jwt".parse().unwrap(),
+            storage: "jetstream".parse().unwrap(),
+        });
+
+        assert!(matches!(config.model, ModelConfig::Anthropic { .. }));
+        assert!(matches!(config.nats.auth, Some(NatsAuth::Jwt { .. })));
+        assert!(matches!(config.storage.backend, StorageBackend::JetStream { .. }));
+    }
+
+    #[test]
+    fn test_unknown_choice_lists_options() {
+        let err = "sqlite".parse::<StorageKind>().unwrap_err();
+        assert!(err.to_string().contains("memory, jetstream"));
+    }
+}
@@ -0,0 +1,163 @@
+//! Proactive messages on a schedule
+//!
+//! Each task in `schedule` runs daily at a fixed UTC time or at an interval.
+//! The model writes the message from the task's prompt, and it goes out as
+//! an agent event, so it reaches `cim.agent.alchemist.events.<event_type>`,
+//! outgoing webhooks, and anything else following the agent's events.
+
+use chrono::{DateTime, Datelike, NaiveTime, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::agent::{AlchemistAgent, CIM_CONCEPTS};
+use crate::config::{ProactiveAction, Schedule, ScheduledTask};
+use crate::error::{AgentError, Result};
+use crate::nats_integration::AgentEvent;
+
+/// Runs the configured tasks
+pub struct Scheduler {
+    tasks: Vec<ScheduledTask>,
+    agent: Arc<AlchemistAgent>,
+}
+
+impl Scheduler {
+    /// Fails if a daily time is not `HH:MM`
+    pub fn new(tasks: Vec<ScheduledTask>, agent: Arc<AlchemistAgent>) -> Result<Self> {
+        for task in &tasks {
+            next_run(&task.when, Utc::now())
+                .map_err(|e| AgentError::Configuration(format!("Scheduled task {}: {}", task.name, e)))?;
+        }
+
+        Ok(Self { tasks, agent })
+    }
+
+    /// Run every task on its schedule until the task is cancelled
+    pub async fn run(self) {
+        let loops = self.tasks.iter().map(|task| self.run_task(task));
+        futures::future::join_all(loops).await;
+    }
+
+    async fn run_task(&self, task: &ScheduledTask) {
+        loop {
+            let now = Utc::now();
+            let next = match next_run(&task.when, now) {
+                Ok(next) => next,
+                Err(e) => {
+                    error!("Scheduled task {} stopped: {}", task.name, e);
+                    return;
+                }
+            };
+            tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+
+            info!("Running scheduled task {}", task.name);
+            if let Err(e) = self.run_once(task).await {
+                error!("Scheduled task {} failed: {}", task.name, e);
+            }
+        }
+    }
+
+    /// Run `task` now
+    pub async fn run_once(&self, task: &ScheduledTask) -> Result<()> {
+        match &task.action {
+            ProactiveAction::Post { event_type, prompt } => {
+                let now = Utc::now();
+                let concept = CIM_CONCEPTS[now.ordinal0() as usize % CIM_CONCEPTS.len()];
+                let prompt = fill(prompt, &[("date", now.format("%Y-%m-%d").to_string()), ("concept", concept.to_string())]);
+
+                let content = self.agent.compose(&prompt).await?;
+                self.publish(event_type, serde_json::json!({
+                    "task": task.name,
+                    "content": content,
+                }));
+            }
+            ProactiveAction::PingStalledWorkflows { stalled_after, prompt } => {
+                for workflow in self.agent.stalled_workflows(*stalled_after).await {
+                    let idle = workflow["idle_seconds"].as_i64().unwrap_or(0).max(0) as u64;
+                    let prompt = fill(prompt, &[
+                        ("workflow", workflow["name"].as_str().unwrap_or_default().to_string()),
+                        ("step", workflow["current_step"].as_str().unwrap_or("none").to_string()),
+                        ("owner", workflow["owner"].as_str().unwrap_or("the team").to_string()),
+                        ("idle", describe_idle(Duration::from_secs(idle))),
+                    ]);
+
+                    let message = self.agent.compose(&prompt).await?;
+                    let mut reminder = workflow;
+                    reminder["task"] = serde_json::json!(task.name);
+                    reminder["message"] = serde_json::json!(message);
+                    self.publish("workflow_reminder", reminder);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn publish(&self, event_type: &str, payload: serde_json::Value) {
+        // Nobody listening is not an error
+        let _ = self.agent.event_sender().send(AgentEvent::raw(event_type, payload));
+    }
+}
+
+/// When `schedule` next runs after `now`
+fn next_run(schedule: &Schedule, now: DateTime<Utc>) -> std::result::Result<DateTime<Utc>, String> {
+    match schedule {
+        Schedule::Daily { at } => {
+            let time = NaiveTime::parse_from_str(at, "%H:%M")
+                .map_err(|_| format!("daily time {} is not HH:MM", at))?;
+            let today = now.date_naive().and_time(time).and_utc();
+            Ok(if today > now { today } else { today + chrono::Duration::days(1) })
+        }
+        Schedule::Every { interval } => {
+            let interval = chrono::Duration::from_std(*interval).map_err(|e| e.to_string())?;
+            Ok(now + interval)
+        }
+    }
+}
+
+/// `template` with each `{name}` replaced by its value
+fn fill(template: &str, values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| text.replace(&format!("{{{}}}", name), value))
+}
+
+/// A rough, readable length of time
+fn describe_idle(idle: Duration) -> String {
+    let (count, unit) = match idle.as_secs() {
+        secs if secs >= 86400 => (secs / 86400, "day"),
+        secs if secs >= 3600 => (secs / 3600, "hour"),
+        secs => (secs / 60, "minute"),
+    };
+
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_next_daily_run() {
+        let daily = Schedule::Daily { at: "08:30".to_string() };
+        let morning = Utc.with_ymd_and_hms(2025, 3, 1, 7, 0, 0).unwrap();
+        let noon = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+
+        assert_eq!(next_run(&daily, morning).unwrap(), Utc.with_ymd_and_hms(2025, 3, 1, 8, 30, 0).unwrap());
+        assert_eq!(next_run(&daily, noon).unwrap(), Utc.with_ymd_and_hms(2025, 3, 2, 8, 30, 0).unwrap());
+        assert!(next_run(&Schedule::Daily { at: "8am".to_string() }, noon).is_err());
+    }
+
+    #[test]
+    fn test_fill_and_describe_idle() {
+        let text = fill("Remind {owner} about {workflow}", &[
+            ("owner", "ada".to_string()),
+            ("workflow", "Add Domain Event".to_string()),
+        ]);
+
+        assert_eq!(text, "Remind ada about Add Domain Event");
+        assert_eq!(describe_idle(Duration::from_secs(3 * 86400 + 5)), "3 days");
+        assert_eq!(describe_idle(Duration::from_secs(3600)), "1 hour");
+    }
+}
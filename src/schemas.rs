@@ -0,0 +1,546 @@
+//! Registry of CIM domain event schemas, for `get_event_schema` and
+//! `list_event_schemas`
+//!
+//! Schemas are read from the Rust files of the code sources and of the
+//! crates under `event_schemas.crates`, and from `rust` code blocks in their
+//! Markdown docs. Every variant of an enum named `...Event` or `...Events` is
+//! a schema, as is every struct named `...Event`, declared in an events
+//! module, or implementing `DomainEvent`. Code wins over docs when both
+//! declare an event. Fields keep their doc comments, and every schema says
+//! where it was declared, so answers about events quote real fields rather
+//! than guessed ones.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::error::{AgentError, Result};
+
+/// Names suggested when a schema is not found
+const SUGGESTIONS: usize = 5;
+
+/// One field of an event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldSchema {
+    /// Field name, or position in a tuple
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub ty: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// An event as declared in code or docs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventSchema {
+    /// `GraphEvent::NodeAdded` for an enum variant, the struct name otherwise
+    pub name: String,
+
+    /// Repository or crate, path, and line of the declaration
+    pub source: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// `struct`, `tuple`, or `unit`
+    pub kind: &'static str,
+
+    pub fields: Vec<FieldSchema>,
+}
+
+impl EventSchema {
+    /// Name without the enum, such as `NodeAdded`
+    pub fn short_name(&self) -> &str {
+        self.name.rsplit("::").next().unwrap_or(&self.name)
+    }
+
+    /// The declaration as Rust, for a prompt
+    pub fn signature(&self) -> String {
+        let fields = |show: &dyn Fn(&FieldSchema) -> String| {
+            self.fields.iter().map(show).collect::<Vec<_>>().join(", ")
+        };
+        match self.kind {
+            "struct" => format!("{} {{ {} }}", self.name, fields(&|field| format!("{}: {}", field.name, field.ty))),
+            "tuple" => format!("{}({})", self.name, fields(&|field| field.ty.clone())),
+            _ => self.name.clone(),
+        }
+    }
+}
+
+/// Event schemas by lowercase name
+#[derive(Debug, Default)]
+pub struct SchemaRegistry {
+    /// Version of the code index the registry was built from
+    version: u64,
+
+    schemas: BTreeMap<String, EventSchema>,
+}
+
+impl SchemaRegistry {
+    /// Read the event schemas out of `files`, given as source, path, and
+    /// content; files that are neither Rust nor Markdown are skipped
+    pub fn build(version: u64, files: &[(String, String, String)]) -> Self {
+        let domain_events: HashSet<String> = files
+            .iter()
+            .flat_map(|(_, _, content)| content.split("impl DomainEvent for ").skip(1))
+            .map(|rest| identifier(rest).to_string())
+            .collect();
+
+        let mut registry = Self {
+            version,
+            schemas: BTreeMap::new(),
+        };
+
+        // Docs first, so code declaring the same event replaces them
+        let mut ordered: Vec<&(String, String, String)> = files.iter().collect();
+        ordered.sort_by_key(|(_, path, _)| !path.ends_with(".md"));
+        for (source, path, content) in ordered {
+            let location = format!("{}/{}", source, path);
+            let in_events_module = path.contains("/events/") || path.ends_with("events.rs") || path.ends_with("event.rs");
+            if path.ends_with(".rs") {
+                registry.declare(&location, content, 0, in_events_module, &domain_events);
+            } else if path.ends_with(".md") {
+                for (line, block) in rust_blocks(content) {
+                    registry.declare(&location, &block, line, false, &domain_events);
+                }
+            }
+        }
+
+        registry
+    }
+
+    /// Add the events declared in `code`, which starts after line `offset`
+    /// of `location`
+    fn declare(&mut self, location: &str, code: &str, offset: usize, in_events_module: bool, domain_events: &HashSet<String>) {
+        for schema in declarations(location, code, offset, in_events_module, domain_events) {
+            self.schemas.insert(schema.name.to_lowercase(), schema);
+        }
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.schemas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+
+    /// Every schema, by name
+    pub fn schemas(&self) -> impl Iterator<Item = &EventSchema> {
+        self.schemas.values()
+    }
+
+    /// The schema named `name`, in full or without its enum when only one
+    /// enum has such a variant
+    pub fn get(&self, name: &str) -> Result<&EventSchema> {
+        let lowered = name.to_lowercase();
+        if let Some(schema) = self.schemas.get(&lowered) {
+            return Ok(schema);
+        }
+
+        let candidates: Vec<&EventSchema> = self
+            .schemas
+            .values()
+            .filter(|schema| schema.short_name().to_lowercase() == lowered)
+            .collect();
+        match candidates.as_slice() {
+            [schema] => Ok(schema),
+            [] => {
+                let similar: Vec<&str> = self
+                    .schemas
+                    .iter()
+                    .filter(|(key, _)| key.contains(&lowered))
+                    .map(|(_, schema)| schema.name.as_str())
+                    .take(SUGGESTIONS)
+                    .collect();
+                let hint = if similar.is_empty() {
+                    String::new()
+                } else {
+                    format!("; similar: {}", similar.join(", "))
+                };
+                Err(AgentError::NotFound(format!("Event schema {}{}", name, hint)))
+            }
+            several => Err(AgentError::InvalidRequest(format!(
+                "Event {} is ambiguous: {}",
+                name,
+                several.iter().map(|schema| schema.name.as_str()).collect::<Vec<_>>().join(", ")
+            ))),
+        }
+    }
+
+    /// Up to `limit` schemas whose name `text` mentions
+    pub fn mentioned(&self, text: &str, limit: usize) -> Vec<&EventSchema> {
+        let words: HashSet<&str> = text
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|word| !word.is_empty())
+            .collect();
+        self.schemas
+            .values()
+            .filter(|schema| words.contains(schema.short_name()))
+            .take(limit)
+            .collect()
+    }
+}
+
+/// The `rust` code blocks of a Markdown document, with the line each
+/// starts after
+fn rust_blocks(markdown: &str) -> Vec<(usize, String)> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (index, line) in markdown.lines().enumerate() {
+        let fence = line.trim_start().starts_with("```");
+        match &mut current {
+            None if fence && line.trim_start()[3..].trim().starts_with("rust") => current = Some((index + 1, String::new())),
+            Some(_) if fence => blocks.extend(current.take()),
+            Some((_, block)) => {
+                block.push_str(line);
+                block.push('\n');
+            }
+            None => {}
+        }
+    }
+    blocks
+}
+
+/// Events declared in `code`
+fn declarations(
+    location: &str,
+    code: &str,
+    offset: usize,
+    in_events_module: bool,
+    domain_events: &HashSet<String>,
+) -> Vec<EventSchema> {
+    let lines: Vec<&str> = code.lines().collect();
+    let mut schemas = Vec::new();
+    let mut start = 0;
+
+    for (index, line) in lines.iter().enumerate() {
+        let line_start = start;
+        start += line.len() + 1;
+
+        let declared = strip_visibility(line.trim_start());
+        let (is_enum, rest) = match (declared.strip_prefix("enum "), declared.strip_prefix("struct ")) {
+            (Some(rest), _) => (true, rest),
+            (_, Some(rest)) => (false, rest),
+            _ => continue,
+        };
+        let name = identifier(rest);
+        let wanted = if is_enum {
+            name.ends_with("Event") || name.ends_with("Events")
+        } else {
+            name.ends_with("Event") || in_events_module || domain_events.contains(name)
+        };
+        if name.is_empty() || !wanted {
+            continue;
+        }
+
+        // The body is whatever brackets open first after the name
+        let after_name = line_start + line.len() - rest.len() + name.len();
+        let Some(open) = code[after_name..].find(['{', '(', ';']).map(|at| after_name + at) else {
+            continue;
+        };
+        let body = match code.as_bytes()[open] {
+            b';' => None,
+            _ => matching(code.as_bytes(), open).map(|close| &code[open + 1..close]),
+        };
+        let source = format!("{}:{}", location, offset + index + 1);
+        let description = doc_above(&lines[..index]);
+
+        if is_enum {
+            for variant in items(body.unwrap_or_default()) {
+                let variant_name = identifier(&variant.text);
+                if variant_name.is_empty() {
+                    continue;
+                }
+                let (kind, fields) = shape(variant.text[variant_name.len()..].trim_start());
+                schemas.push(EventSchema {
+                    name: format!("{}::{}", name, variant_name),
+                    source: source.clone(),
+                    description: variant.doc,
+                    kind,
+                    fields,
+                });
+            }
+        } else {
+            let (kind, fields) = match body {
+                Some(body) => shape(&code[open..open + body.len() + 2]),
+                None => ("unit", Vec::new()),
+            };
+            schemas.push(EventSchema {
+                name: name.to_string(),
+                source,
+                description,
+                kind,
+                fields,
+            });
+        }
+    }
+
+    schemas
+}
+
+/// Kind and fields of a variant or struct from its body onwards, such as
+/// `{ id: NodeId }` or `(NodeId)`
+fn shape(body: &str) -> (&'static str, Vec<FieldSchema>) {
+    let inner = |body: &str| matching(body.as_bytes(), 0).map(|close| body[1..close].to_string()).unwrap_or_default();
+    if body.starts_with('{') {
+        let fields = items(&inner(body))
+            .into_iter()
+            .filter_map(|item| {
+                let (name, ty) = strip_visibility(&item.text).split_once(':')?;
+                Some(FieldSchema {
+                    name: name.trim().to_string(),
+                    ty: normalize(ty),
+                    description: item.doc,
+                })
+            })
+            .collect();
+        ("struct", fields)
+    } else if body.starts_with('(') {
+        let fields = items(&inner(body))
+            .into_iter()
+            .enumerate()
+            .map(|(position, item)| FieldSchema {
+                name: position.to_string(),
+                ty: normalize(strip_visibility(&item.text)),
+                description: item.doc,
+            })
+            .collect();
+        ("tuple", fields)
+    } else {
+        ("unit", Vec::new())
+    }
+}
+
+/// A comma-separated item of a body, with its doc comment
+struct Item {
+    text: String,
+    doc: Option<String>,
+}
+
+/// Split the inside of braces or parentheses into its items
+///
+/// Comments and attributes of the items themselves are dropped, doc
+/// comments kept as the items' docs; nested brackets are copied whole.
+fn items(body: &str) -> Vec<Item> {
+    let bytes = body.as_bytes();
+    let mut items = Vec::new();
+    let mut text = String::new();
+    let mut doc: Vec<&str> = Vec::new();
+    let mut depth = 0usize;
+    let mut finish = |text: &mut String, doc: &mut Vec<&str>| {
+        if !text.trim().is_empty() {
+            items.push(Item {
+                text: text.trim().to_string(),
+                doc: (!doc.is_empty()).then(|| doc.join(" ")),
+            });
+        }
+        text.clear();
+        doc.clear();
+    };
+
+    let mut at = 0;
+    while at < bytes.len() {
+        if body[at..].starts_with("//") {
+            let end = body[at..].find('\n').map_or(body.len(), |newline| at + newline);
+            if depth > 0 {
+                text.push_str(&body[at..end]);
+            } else if let Some(line) = body[at..end].strip_prefix("///") {
+                doc.push(line.trim());
+            }
+            at = end;
+            continue;
+        }
+        if depth == 0 && body[at..].starts_with("#[") {
+            at = matching(bytes, at + 1).map_or(bytes.len(), |close| close + 1);
+            continue;
+        }
+
+        match bytes[at] {
+            b'(' | b'{' | b'[' | b'<' => depth += 1,
+            b')' | b'}' | b']' => depth = depth.saturating_sub(1),
+            b'>' if at == 0 || bytes[at - 1] != b'-' => depth = depth.saturating_sub(1),
+            b',' if depth == 0 => {
+                finish(&mut text, &mut doc);
+                at += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let next = body[at..].chars().next().map_or(1, char::len_utf8);
+        text.push_str(&body[at..at + next]);
+        at += next;
+    }
+    finish(&mut text, &mut doc);
+
+    items
+}
+
+/// Index of the bracket closing the one at `open`, skipping line comments
+fn matching(bytes: &[u8], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut at = open;
+    while at < bytes.len() {
+        match bytes[at] {
+            b'/' if bytes.get(at + 1) == Some(&b'/') => {
+                at += bytes[at..].iter().position(|&byte| byte == b'\n').unwrap_or(bytes.len() - at);
+                continue;
+            }
+            b'(' | b'{' | b'[' => depth += 1,
+            b')' | b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(at);
+                }
+            }
+            _ => {}
+        }
+        at += 1;
+    }
+    None
+}
+
+/// Doc comment of the declaration after `above`, skipping its attributes
+fn doc_above(above: &[&str]) -> Option<String> {
+    let mut doc: Vec<&str> = above
+        .iter()
+        .rev()
+        .map(|line| line.trim())
+        .skip_while(|line| line.starts_with("#["))
+        .map_while(|line| line.strip_prefix("///"))
+        .map(str::trim)
+        .collect();
+    doc.reverse();
+    (!doc.is_empty()).then(|| doc.join(" "))
+}
+
+/// `text` without a leading `pub`, `pub(crate)`, or similar
+fn strip_visibility(text: &str) -> &str {
+    let Some(rest) = text.strip_prefix("pub") else {
+        return text;
+    };
+    let rest = match rest.strip_prefix('(') {
+        Some(scoped) => scoped.split_once(')').map_or(rest, |(_, after)| after),
+        None => rest,
+    };
+    if rest.starts_with(char::is_whitespace) {
+        rest.trim_start()
+    } else {
+        text
+    }
+}
+
+/// The identifier `text` starts with
+fn identifier(text: &str) -> &str {
+    let end = text
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+/// A type with its whitespace collapsed
+fn normalize(ty: &str) -> String {
+    ty.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("< ", "<")
+        .replace(" >", ">")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENTS: &str = r#"
+use crate::ids::{GraphId, NodeId};
+
+/// Changes to a graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum GraphEvent {
+    /// A node joined the graph
+    NodeAdded {
+        graph_id: GraphId,
+        /// Where the node sits, if placed
+        position: Option<(f32, f32)>, // not yet used
+        metadata: HashMap<String, serde_json::Value>,
+    },
+    NodeRemoved(GraphId, NodeId),
+    #[serde(rename = "cleared")]
+    Cleared,
+}
+
+/// A graph was renamed
+pub struct GraphRenamed {
+    pub graph_id: GraphId,
+    pub(crate) name: String,
+}
+
+impl DomainEvent for GraphRenamed {}
+
+pub struct GraphLayout {
+    pub nodes: Vec<NodeId>,
+}
+"#;
+
+    fn registry() -> SchemaRegistry {
+        let docs = "# Workflow events\n\n```rust\npub enum WorkflowEvent {\n    Started { workflow_id: String },\n}\n```\n";
+        SchemaRegistry::build(
+            7,
+            &[
+                ("cim-domain-graph".to_string(), "src/domain.rs".to_string(), EVENTS.to_string()),
+                ("cim-docs".to_string(), "workflow.md".to_string(), docs.to_string()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_enum_variants_and_structs_are_read() {
+        let registry = registry();
+        assert_eq!(registry.len(), 5);
+
+        let added = registry.get("GraphEvent::NodeAdded").unwrap();
+        assert_eq!(added.kind, "struct");
+        assert_eq!(added.description.as_deref(), Some("A node joined the graph"));
+        assert_eq!(added.source, "cim-domain-graph/src/domain.rs:6");
+        let fields: Vec<(&str, &str)> = added.fields.iter().map(|field| (field.name.as_str(), field.ty.as_str())).collect();
+        assert_eq!(
+            fields,
+            [
+                ("graph_id", "GraphId"),
+                ("position", "Option<(f32, f32)>"),
+                ("metadata", "HashMap<String, serde_json::Value>"),
+            ]
+        );
+        assert_eq!(added.fields[1].description.as_deref(), Some("Where the node sits, if placed"));
+        assert_eq!(added.signature(), "GraphEvent::NodeAdded { graph_id: GraphId, position: Option<(f32, f32)>, metadata: HashMap<String, serde_json::Value> }");
+
+        let removed = registry.get("noderemoved").unwrap();
+        assert_eq!(removed.signature(), "GraphEvent::NodeRemoved(GraphId, NodeId)");
+        assert_eq!(registry.get("GraphEvent::Cleared").unwrap().kind, "unit");
+
+        let renamed = registry.get("GraphRenamed").unwrap();
+        assert_eq!(renamed.fields[1].name, "name");
+        assert_eq!(renamed.description.as_deref(), Some("A graph was renamed"));
+        assert!(registry.get("GraphLayout").is_err());
+
+        let started = registry.get("Started").unwrap();
+        assert_eq!(started.source, "cim-docs/workflow.md:4");
+    }
+
+    #[test]
+    fn test_lookup_suggests_and_finds_mentions() {
+        let registry = registry();
+        let err = registry.get("Node").unwrap_err().to_string();
+        assert!(err.contains("GraphEvent::NodeAdded"));
+
+        let mentioned = registry.mentioned("What fields does GraphEvent::NodeAdded have?", 3);
+        assert_eq!(mentioned.len(), 1);
+        assert_eq!(mentioned[0].name, "GraphEvent::NodeAdded");
+        assert!(registry.mentioned("nodes were added", 3).is_empty());
+    }
+}
@@ -6,11 +6,22 @@
 use crate::agent::AlchemistAgent;
 use crate::config::AgentConfig;
 use crate::error::{AgentError, Result};
-use crate::model::{ModelProvider, OllamaProvider};
-use crate::nats_integration::NatsClient;
+use crate::events::AgentEventKind;
+use crate::model::ModelProvider;
+use crate::nats_integration::{connection_health, AgentEvent, NatsClient};
+use crate::reaper::{self, DialogReaper};
+use crate::scheduler::Scheduler;
+use crate::sources::git::GitSource;
+use crate::sources::refresh::KnowledgeRefresh;
 use std::sync::Arc;
 use tokio::task::JoinHandle;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+/// What is checked before the agent takes requests, in order, and whether
+/// startup fails without it
+///
+/// Without the model the agent starts in degraded mode instead.
+const STARTUP_DEPENDENCIES: &[(&str, bool)] = &[("nats", true), ("model", false), ("storage", true)];
 
 /// Status of the agent service
 #[derive(Debug, Clone, PartialEq)]
@@ -38,81 +49,195 @@ pub struct AgentService {
     agent: Arc<AlchemistAgent>,
     nats_client: Arc<NatsClient>,
     tasks: Arc<tokio::sync::Mutex<Vec<JoinHandle<()>>>>,
+    shutdown: Arc<tokio::sync::Notify>,
 }
 
 impl AgentService {
     /// Create a new agent service
     pub async fn new(config: AgentConfig) -> Result<Self> {
         // Create model provider based on configuration
-        let model_provider = Self::create_model_provider(&config)?;
+        let model_provider = crate::model::create_provider(&config.model)?;
         
+        Self::with_model_provider(config, model_provider).await
+    }
+    
+    /// Create an agent service answering with `model_provider` instead of
+    /// the configured model
+    pub async fn with_model_provider(config: AgentConfig, model_provider: Box<dyn ModelProvider>) -> Result<Self> {
         // Create the Alchemist agent
         let agent = Arc::new(
-            AlchemistAgent::new(config.identity.clone(), model_provider).await?
+            AlchemistAgent::new(config.clone(), model_provider).await?
         );
         
         // Create NATS client
-        let nats_client = Arc::new(NatsClient::new(config.nats.clone()).await?);
+        let nats_client = NatsClient::new(&config.nats).await?.with_message_limit(config.limits.max_message_bytes);
+        #[cfg(feature = "chaos")]
+        let nats_client = nats_client.with_faults(agent.faults());
+        let nats_client = Arc::new(nats_client);
         
         Ok(Self {
             config,
             agent,
             nats_client,
             tasks: Arc::new(tokio::sync::Mutex::new(Vec::new())),
+            shutdown: Arc::new(tokio::sync::Notify::new()),
         })
     }
     
+    /// The agent the service runs
+    pub fn agent(&self) -> Arc<AlchemistAgent> {
+        self.agent.clone()
+    }
+    
     /// Start the agent service
     pub async fn start(&self) -> Result<()> {
         info!("Starting Alchemist agent service");
         
+        // Accept nothing until the agent can serve it
+        self.wait_until_ready().await?;
+        
         // Start NATS subscriptions
         self.start_nats_subscriptions().await?;
         
         // Start health check task
         self.start_health_check().await?;
         
+        // Start configured chat integrations
+        self.start_integrations().await?;
+        
+        // Keep code sources indexed
+        self.start_sources().await?;
+        
+        // Keep documents embedded for retrieval into prompts
+        self.start_retrieval().await?;
+        
+        // Find peer agents and answer their delegated questions
+        self.start_peers().await?;
+        
+        // Send scheduled tips and reminders
+        self.start_scheduler().await?;
+        
+        // End dialogs and workflows left idle
+        self.start_dialog_reaper().await?;
+        self.start_workflow_expiry().await?;
+        
+        // Serve webhooks and other HTTP endpoints
+        self.start_http_server().await?;
+        
         info!("Alchemist agent service started successfully");
         Ok(())
     }
     
-    /// Stop the agent service
+    /// Wait for NATS, the model provider, and storage to pass their health
+    /// checks, retrying each with the `nats.retry` backoff
+    ///
+    /// Progress is published as `startup_progress` events. A required
+    /// dependency still failing after the last attempt fails startup.
+    async fn wait_until_ready(&self) -> Result<()> {
+        let retry = &self.config.nats.retry;
+        
+        for &(dependency, required) in STARTUP_DEPENDENCIES {
+            let mut attempt = 1;
+            loop {
+                let reason = self.check_dependency(dependency).await;
+                self.publish_startup_progress(dependency, attempt, reason.as_deref()).await;
+                
+                let Some(reason) = reason else {
+                    info!("{} is ready", dependency);
+                    break;
+                };
+                if attempt >= retry.max_attempts {
+                    if !required {
+                        warn!("{} not ready after {} attempts ({}); starting degraded", dependency, attempt, reason);
+                        break;
+                    }
+                    return Err(AgentError::ServiceUnavailable(format!(
+                        "{} not ready after {} attempts: {}",
+                        dependency, attempt, reason
+                    )));
+                }
+                
+                let delay = retry.delay(attempt);
+                warn!("{} not ready ({}), retrying in {:?}", dependency, reason, delay);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Why `dependency` is not ready, if it is not
+    async fn check_dependency(&self, dependency: &str) -> Option<String> {
+        let ready = match dependency {
+            "nats" => {
+                let health = connection_health(&self.nats_client.client());
+                if health.is_degraded() {
+                    return Some(health.reason.unwrap_or(health.status));
+                }
+                true
+            }
+            "model" => self.agent.check_model_health().await,
+            "storage" => self.agent.check_storage_health().await,
+            _ => true,
+        };
+        if ready {
+            return None;
+        }
+        
+        // The agent's checks keep the reason with its health
+        let reason = self
+            .agent
+            .health()
+            .await
+            .subsystems
+            .remove(dependency)
+            .and_then(|health| health.reason);
+        Some(reason.unwrap_or_else(|| "health check failed".to_string()))
+    }
+    
+    async fn publish_startup_progress(&self, dependency: &str, attempt: u32, reason: Option<&str>) {
+        let event = AgentEvent::new(AgentEventKind::StartupProgress {
+            dependency: dependency.to_string(),
+            attempt,
+            max_attempts: self.config.nats.retry.max_attempts,
+            ready: reason.is_none(),
+            reason: reason.map(str::to_string),
+        });
+        
+        // NATS itself may be the dependency that is not ready yet
+        if let Err(e) = self.nats_client.publish_served("events.startup_progress", &event).await {
+            warn!("Failed to publish startup progress: {}", e);
+        }
+    }
+    
+    /// Stop the agent service, draining pending outbound messages
     pub async fn stop(&self) -> Result<()> {
         info!("Stopping Alchemist agent service");
         
-        // Cancel all tasks
+        // Stop accepting new messages
         let mut tasks = self.tasks.lock().await;
         for task in tasks.drain(..) {
             task.abort();
         }
+        drop(tasks);
+        
+        // Leave discovery so `nats micro ls` no longer lists this agent
+        if let Err(e) = self.nats_client.stop_service().await {
+            error!("Failed to stop NATS service: {}", e);
+        }
+        
+        // Deliver replies and events that are still buffered
+        if let Err(e) = self.nats_client.flush().await {
+            error!("Failed to flush NATS connection: {}", e);
+        }
+        
+        self.shutdown.notify_one();
         
         info!("Alchemist agent service stopped");
         Ok(())
     }
     
-    /// Create model provider based on configuration
-    fn create_model_provider(config: &AgentConfig) -> Result<Box<dyn ModelProvider>> {
-        match &config.model {
-            crate::config::ModelConfig::Ollama { base_url, model, .. } => {
-                Ok(Box::new(OllamaProvider::new(
-                    base_url.clone(),
-                    model.clone(),
-                    std::collections::HashMap::new(),
-                )))
-            }
-            crate::config::ModelConfig::OpenAI { .. } => {
-                Err(AgentError::Configuration(
-                    "OpenAI provider not yet implemented".to_string()
-                ))
-            }
-            crate::config::ModelConfig::Anthropic { .. } => {
-                Err(AgentError::Configuration(
-                    "Anthropic provider not yet implemented".to_string()
-                ))
-            }
-        }
-    }
-    
     /// Start NATS subscriptions
     async fn start_nats_subscriptions(&self) -> Result<()> {
         let nats_client = self.nats_client.clone();
@@ -145,20 +270,32 @@ impl AgentService {
             }
         });
         
+        let nats_client = self.nats_client.clone();
+        let events = self.agent.subscribe_events();
+        
+        // Publish agent events
+        let event_task = tokio::spawn(async move {
+            if let Err(e) = nats_client.publish_agent_events(events).await {
+                error!("Event publishing error: {}", e);
+            }
+        });
+        
         // Store tasks
         let mut tasks = self.tasks.lock().await;
         tasks.push(cmd_task);
         tasks.push(query_task);
         tasks.push(dialog_task);
+        tasks.push(event_task);
         
         Ok(())
     }
     
-    /// Start health check task
+    /// Start health check tasks
     async fn start_health_check(&self) -> Result<()> {
-        let nats_client = self.nats_client.clone();
+        let agent = self.agent.clone();
         let interval = self.config.service.health_check_interval.as_secs();
         
+        // Check the model periodically so health requests answer at once
         let health_task = tokio::spawn(async move {
             let mut interval = tokio::time::interval(
                 std::time::Duration::from_secs(interval)
@@ -166,26 +303,213 @@ impl AgentService {
             
             loop {
                 interval.tick().await;
-                if let Err(e) = nats_client.publish_health_check().await {
-                    error!("Health check error: {}", e);
-                }
+                agent.check_model_health().await;
+                agent.check_storage_health().await;
+            }
+        });
+        
+        let nats_client = self.nats_client.clone();
+        let agent = self.agent.clone();
+        
+        // Answer health requests over NATS
+        let health_subscription_task = tokio::spawn(async move {
+            if let Err(e) = nats_client.answer_health_checks(agent).await {
+                error!("Health check subscription error: {}", e);
             }
         });
         
         let mut tasks = self.tasks.lock().await;
         tasks.push(health_task);
+        tasks.push(health_subscription_task);
+        
+        // Answer metrics requests over NATS
+        if self.config.service.metrics.enabled {
+            let nats_client = self.nats_client.clone();
+            let agent = self.agent.clone();
+            tasks.push(tokio::spawn(async move {
+                if let Err(e) = nats_client.answer_metrics(agent).await {
+                    error!("Metrics subscription error: {}", e);
+                }
+            }));
+        }
+        
+        Ok(())
+    }
+    
+    /// Start the integrations configured under `integrations`
+    async fn start_integrations(&self) -> Result<()> {
+        #[cfg(feature = "slack")]
+        if let Some(slack) = &self.config.integrations.slack {
+            let bot = crate::integrations::slack::SlackBot::new(slack.clone(), self.agent.clone());
+            let slack_task = tokio::spawn(async move {
+                if let Err(e) = bot.run().await {
+                    error!("Slack integration error: {}", e);
+                }
+            });
+            
+            self.tasks.lock().await.push(slack_task);
+        }
+        
+        #[cfg(not(feature = "slack"))]
+        if self.config.integrations.slack.is_some() {
+            return Err(AgentError::Configuration(
+                "Slack is configured but the agent was built without the `slack` feature".to_string()
+            ));
+        }
+        
+        #[cfg(feature = "webhooks")]
+        if !self.config.integrations.webhooks.is_empty() {
+            let dispatcher = crate::integrations::webhooks::WebhookDispatcher::new(
+                self.config.integrations.webhooks.clone()
+            )?;
+            let webhook_task = tokio::spawn(dispatcher.run(self.agent.subscribe_events()));
+            
+            self.tasks.lock().await.push(webhook_task);
+        }
+        
+        #[cfg(not(feature = "webhooks"))]
+        if !self.config.integrations.webhooks.is_empty() {
+            return Err(AgentError::Configuration(
+                "Webhooks are configured but the agent was built without the `webhooks` feature".to_string()
+            ));
+        }
+        
+        #[cfg(feature = "email")]
+        if let Some(email) = &self.config.integrations.email {
+            let digest = crate::integrations::email::EmailDigest::new(email.clone(), self.agent.clone())?;
+            let email_task = tokio::spawn(digest.run());
+            
+            self.tasks.lock().await.push(email_task);
+        }
+        
+        #[cfg(not(feature = "email"))]
+        if self.config.integrations.email.is_some() {
+            return Err(AgentError::Configuration(
+                "Email digests are configured but the agent was built without the `email` feature".to_string()
+            ));
+        }
+        
+        Ok(())
+    }
+    
+    /// Start indexing configured code sources
+    async fn start_sources(&self) -> Result<()> {
+        if self.config.sources.git.repos.is_empty() {
+            return Ok(());
+        }
+        
+        let source = GitSource::new(
+            self.config.sources.git.clone(),
+            self.agent.code_index(),
+            self.agent.event_sender(),
+        );
+        let refresh = KnowledgeRefresh::new(source, self.agent.clone());
+        let sources_task = tokio::spawn(refresh.run());
+        
+        self.tasks.lock().await.push(sources_task);
+        
+        Ok(())
+    }
+    
+    /// Start embedding documents for retrieval-augmented generation
+    async fn start_retrieval(&self) -> Result<()> {
+        let retrieval = &self.config.retrieval;
+        let sources = retrieval.include_sources && !self.config.sources.git.repos.is_empty();
+        if retrieval.directories.is_empty() && !sources {
+            return Ok(());
+        }
+        
+        let retrieval_task = tokio::spawn(crate::retrieval::run(self.agent.clone(), retrieval.refresh_interval));
+        
+        self.tasks.lock().await.push(retrieval_task);
+        
+        Ok(())
+    }
+    
+    /// Start announcing capabilities to peer agents
+    async fn start_peers(&self) -> Result<()> {
+        if !self.config.peers.enabled {
+            return Ok(());
+        }
+        
+        let peers = self.agent.peers();
+        let client = self.nats_client.client();
+        let agent = self.agent.clone();
+        let peers_task = tokio::spawn(async move {
+            if let Err(e) = peers.run(client, agent).await {
+                error!("Peer discovery error: {}", e);
+            }
+        });
+        
+        self.tasks.lock().await.push(peers_task);
+        
+        Ok(())
+    }
+    
+    /// Start running scheduled tasks
+    async fn start_scheduler(&self) -> Result<()> {
+        if self.config.schedule.is_empty() {
+            return Ok(());
+        }
+        
+        let scheduler = Scheduler::new(self.config.schedule.clone(), self.agent.clone())?;
+        let scheduler_task = tokio::spawn(scheduler.run());
+        
+        self.tasks.lock().await.push(scheduler_task);
+        
+        Ok(())
+    }
+    
+    /// Start expiring dialogs idle past `domains.dialog.session_timeout`
+    async fn start_dialog_reaper(&self) -> Result<()> {
+        let timeout = self.config.domains.dialog.session_timeout;
+        if timeout.is_zero() {
+            return Ok(());
+        }
+        
+        let reaper = DialogReaper::new(timeout, self.agent.clone());
+        let reaper_task = tokio::spawn(reaper.run());
+        
+        self.tasks.lock().await.push(reaper_task);
+        
+        Ok(())
+    }
+    
+    /// Start failing workflows idle past `domains.workflow.timeout`
+    async fn start_workflow_expiry(&self) -> Result<()> {
+        let timeout = self.config.domains.workflow.timeout;
+        if timeout.is_zero() {
+            return Ok(());
+        }
+        
+        let expiry_task = tokio::spawn(reaper::expire_workflows(self.agent.clone(), timeout));
+        
+        self.tasks.lock().await.push(expiry_task);
+        
+        Ok(())
+    }
+    
+    /// Start the HTTP server if any endpoint is configured
+    async fn start_http_server(&self) -> Result<()> {
+        let Some(router) = crate::http::routes(&self.config, self.agent.clone(), self.nats_client.client())? else {
+            return Ok(());
+        };
+        
+        let listener = crate::http::bind(&self.config).await?;
+        let http_task = tokio::spawn(async move {
+            if let Err(e) = crate::http::serve(listener, router).await {
+                error!("HTTP server error: {}", e);
+            }
+        });
+        
+        self.tasks.lock().await.push(http_task);
         
         Ok(())
     }
     
     /// Wait for service to complete (blocks until stopped)
     pub async fn wait(&self) -> Result<()> {
-        // Wait for all tasks to complete
-        let tasks = self.tasks.lock().await;
-        if let Some(task) = tasks.first() {
-            // Wait for the first task (they should all run indefinitely)
-            let _ = task.await;
-        }
+        self.shutdown.notified().await;
         Ok(())
     }
 }
@@ -195,6 +519,14 @@ pub async fn run(config: crate::config::AgentConfig) -> Result<()> {
     // Initialize tracing
     init_tracing(&config.service.logging);
     
+    if config.config_version < crate::config::CONFIG_VERSION {
+        warn!(
+            "Configuration is for config version {}; run `alchemist config migrate` to update it to {}",
+            config.config_version,
+            crate::config::CONFIG_VERSION
+        );
+    }
+    
     // Create and start service
     let service = AgentService::new(config).await?;
     service.start().await?;
@@ -202,7 +534,7 @@ pub async fn run(config: crate::config::AgentConfig) -> Result<()> {
     // Set up shutdown handler
     let shutdown_service = service.clone();
     tokio::spawn(async move {
-        match tokio::signal::ctrl_c().await {
+        match shutdown_signal().await {
             Ok(()) => {
                 info!("Received shutdown signal");
                 if let Err(e) = shutdown_service.stop().await {
@@ -221,6 +553,25 @@ pub async fn run(config: crate::config::AgentConfig) -> Result<()> {
     Ok(())
 }
 
+/// Wait for Ctrl-C or, on Unix, SIGTERM (sent by `alchemist stop`)
+async fn shutdown_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await
+    }
+}
+
 /// Initialize tracing/logging
 fn init_tracing(config: &crate::config::LoggingConfig) {
     use tracing_subscriber::{fmt, EnvFilter};
@@ -0,0 +1,281 @@
+//! Ed25519 signatures on agent commands
+//!
+//! With `command_signing` configured, every command must carry a signature
+//! from a key configured for its `origin`, and a timestamp within `max_age`
+//! of the agent's clock. Anyone who can publish on a shared NATS cluster can
+//! then still send commands, but cannot pose as an automation pipeline, nor
+//! send a signed command again under the same id while it is fresh.
+//!
+//! The signature is the hex Ed25519 signature of
+//! `<id>\n<command_type>\n<timestamp in Unix milliseconds>\n<idempotency_key>\n<payload>`,
+//! with an empty line for a missing idempotency key, where the payload is
+//! compact JSON with object keys sorted.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::CommandSigningConfig;
+use crate::error::{AgentError, Result};
+use crate::nats_integration::AgentCommand;
+
+/// The bytes a command's signature covers
+pub fn signed_bytes(command: &AgentCommand) -> Vec<u8> {
+    let mut payload = String::new();
+    canonical_json(&command.payload, &mut payload);
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        command.id,
+        command.command_type,
+        command.timestamp.timestamp_millis(),
+        command.idempotency_key.as_deref().unwrap_or_default(),
+        payload
+    )
+    .into_bytes()
+}
+
+/// Write `value` as compact JSON with object keys sorted, whatever order
+/// the map keeps them in
+fn canonical_json(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::from(key.as_str()).to_string());
+                out.push(':');
+                canonical_json(value, out);
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                canonical_json(item, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// Sign `command` in place
+pub fn sign(command: &mut AgentCommand, key: &SigningKey) {
+    let signature = key.sign(&signed_bytes(command));
+    command.signature = Some(hex::encode(signature.to_bytes()));
+}
+
+/// Parse a hex Ed25519 secret key, as used by clients that sign commands
+pub fn signing_key(hex_key: &str) -> Result<SigningKey> {
+    let bytes: [u8; 32] = decode_key(hex_key)?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+fn decode_key(hex_key: &str) -> Result<[u8; 32]> {
+    hex::decode(hex_key.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| AgentError::Configuration("Keys must be 32 bytes of hex".to_string()))
+}
+
+/// Checks signatures against the configured keys
+pub struct CommandVerifier {
+    keys: HashMap<String, Vec<VerifyingKey>>,
+    max_age: Duration,
+
+    /// Ids of accepted commands by origin, with when each was received and
+    /// when it stops being fresh
+    seen: Mutex<HashMap<(String, String), (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)>>,
+}
+
+impl CommandVerifier {
+    /// Fails if a configured key is malformed
+    pub fn new(config: &CommandSigningConfig) -> Result<Self> {
+        let mut keys = HashMap::new();
+        for (origin, hex_keys) in &config.keys {
+            let parsed = hex_keys
+                .iter()
+                .map(|hex_key| {
+                    VerifyingKey::from_bytes(&decode_key(hex_key)?).map_err(|e| {
+                        AgentError::Configuration(format!("Invalid public key for {}: {}", origin, e))
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            keys.insert(origin.clone(), parsed);
+        }
+
+        Ok(Self {
+            keys,
+            max_age: config.max_age,
+            seen: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Accept `command` only if it is fresh and signed by a key for its origin
+    pub fn verify(&self, command: &AgentCommand) -> Result<()> {
+        self.verify_received(command, chrono::Utc::now())
+    }
+
+    /// Like [`verify`](Self::verify), judging freshness by when the command
+    /// was `received`, such as when JetStream stored it, so commands queued
+    /// while the agent was down or delivered again are not expired
+    ///
+    /// A command whose id was already accepted from its origin is refused
+    /// unless it is the same stored message delivered again, received at
+    /// the same time.
+    pub fn verify_received(&self, command: &AgentCommand, received: chrono::DateTime<chrono::Utc>) -> Result<()> {
+        let Some(signature) = &command.signature else {
+            return Err(AgentError::PermissionDenied(format!(
+                "Command {} from {} is not signed",
+                command.id, command.origin
+            )));
+        };
+
+        // Either direction: a timestamp far ahead could be replayed later
+        let age = (received - command.timestamp).abs();
+        if age.to_std().map_or(true, |age| age > self.max_age) {
+            return Err(AgentError::PermissionDenied(format!(
+                "Command {} from {} has expired",
+                command.id, command.origin
+            )));
+        }
+
+        let keys = self.keys.get(&command.origin).ok_or_else(|| {
+            AgentError::PermissionDenied(format!("No signing keys configured for {}", command.origin))
+        })?;
+        let signature = hex::decode(signature)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or_else(|| AgentError::PermissionDenied(format!("Command {} has a malformed signature", command.id)))?;
+
+        let message = signed_bytes(command);
+        if !keys.iter().any(|key| key.verify(&message, &signature).is_ok()) {
+            return Err(AgentError::PermissionDenied(format!(
+                "Command {} is not signed by a key for {}",
+                command.id, command.origin
+            )));
+        }
+
+        self.check_replay(command, received)
+    }
+
+    /// Remember `command` as accepted, refusing it if its id already was
+    fn check_replay(&self, command: &AgentCommand, received: chrono::DateTime<chrono::Utc>) -> Result<()> {
+        let mut seen = self.seen.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // A command past its freshness fails the age check anyway
+        seen.retain(|_, (_, fresh_until)| *fresh_until >= received);
+
+        let key = (command.origin.clone(), command.id.clone());
+        match seen.get(&key) {
+            Some((first_received, _)) if *first_received != received => Err(AgentError::PermissionDenied(format!(
+                "Command {} from {} was already received",
+                command.id, command.origin
+            ))),
+            _ => {
+                let fresh_until = chrono::Duration::from_std(self.max_age)
+                    .ok()
+                    .and_then(|max_age| command.timestamp.checked_add_signed(max_age))
+                    .unwrap_or(chrono::DateTime::<chrono::Utc>::MAX_UTC);
+                seen.insert(key, (received, fresh_until));
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(origin: &str) -> AgentCommand {
+        AgentCommand {
+            id: "cmd-1".to_string(),
+            command_type: "guide_workflow".to_string(),
+            payload: serde_json::json!({ "workflow_type": "add_event" }),
+            timestamp: chrono::Utc::now(),
+            origin: origin.to_string(),
+            signature: None,
+            idempotency_key: None,
+            priority: None,
+        }
+    }
+
+    fn verifier(key: &SigningKey) -> CommandVerifier {
+        CommandVerifier::new(&CommandSigningConfig {
+            keys: HashMap::from([("ci".to_string(), vec![hex::encode(key.verifying_key().to_bytes())])]),
+            max_age: Duration::from_secs(300),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_signed_command_verifies() {
+        let key = signing_key(&"01".repeat(32)).unwrap();
+        let mut command = command("ci");
+        sign(&mut command, &key);
+
+        assert!(verifier(&key).verify(&command).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_unsigned_tampered_expired_and_unknown() {
+        let key = signing_key(&"01".repeat(32)).unwrap();
+        let verifier = verifier(&key);
+
+        assert!(matches!(verifier.verify(&command("ci")), Err(AgentError::PermissionDenied(_))));
+
+        let mut tampered = command("ci");
+        sign(&mut tampered, &key);
+        tampered.payload = serde_json::json!({ "workflow_type": "create_agent" });
+        assert!(verifier.verify(&tampered).is_err());
+
+        let mut expired = command("ci");
+        expired.timestamp -= chrono::Duration::minutes(10);
+        sign(&mut expired, &key);
+        assert!(verifier.verify(&expired).is_err());
+        let stored = chrono::Utc::now() - chrono::Duration::minutes(9);
+        assert!(verifier.verify_received(&expired, stored).is_ok());
+
+        let mut unknown = command("someone-else");
+        sign(&mut unknown, &key);
+        assert!(verifier.verify(&unknown).is_err());
+
+        let mut rekeyed = command("ci");
+        sign(&mut rekeyed, &key);
+        rekeyed.idempotency_key = Some("retry-1".to_string());
+        assert!(verifier.verify(&rekeyed).is_err());
+    }
+
+    #[test]
+    fn test_rejects_replayed_id() {
+        let key = signing_key(&"01".repeat(32)).unwrap();
+        let verifier = verifier(&key);
+        let mut command = command("ci");
+        sign(&mut command, &key);
+
+        let stored = chrono::Utc::now() - chrono::Duration::seconds(1);
+        assert!(verifier.verify_received(&command, stored).is_ok());
+        // The same stored message delivered again
+        assert!(verifier.verify_received(&command, stored).is_ok());
+        assert!(verifier.verify(&command).is_err());
+    }
+
+    #[test]
+    fn test_payload_keys_sorted() {
+        let mut payload = serde_json::Map::new();
+        payload.insert("zeta".to_string(), serde_json::json!([{ "b": 1, "a": "x\"y" }]));
+        payload.insert("alpha".to_string(), serde_json::json!(null));
+
+        let mut out = String::new();
+        canonical_json(&serde_json::Value::Object(payload), &mut out);
+        assert_eq!(out, r#"{"alpha":null,"zeta":[{"a":"x\"y","b":1}]}"#);
+    }
+}
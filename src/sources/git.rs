@@ -0,0 +1,200 @@
+//! Git repositories as a code source
+//!
+//! Configured repositories are shallow-cloned under `checkout_dir`, pulled
+//! again every `refresh_interval` by the knowledge refresh, and their files
+//! reindexed. The `git` command must be installed.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::process::Command;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{debug, info, warn};
+
+use super::{CodeIndex, IndexDiff};
+use crate::config::{GitRepoConfig, GitSourcesConfig};
+use crate::error::{AgentError, Result};
+use crate::events::AgentEventKind;
+use crate::nats_integration::AgentEvent;
+
+/// Directories never worth indexing
+const SKIPPED_DIRS: &[&str] = &[".git", "target", "node_modules"];
+
+/// Keeps git checkouts current and indexed
+pub struct GitSource {
+    config: GitSourcesConfig,
+    index: Arc<RwLock<CodeIndex>>,
+    events: broadcast::Sender<AgentEvent>,
+}
+
+impl GitSource {
+    pub fn new(
+        config: GitSourcesConfig,
+        index: Arc<RwLock<CodeIndex>>,
+        events: broadcast::Sender<AgentEvent>,
+    ) -> Self {
+        Self { config, index, events }
+    }
+
+    pub fn refresh_interval(&self) -> std::time::Duration {
+        self.config.refresh_interval
+    }
+
+    /// Pull and reindex every repository, returning how each one changed;
+    /// failures only skip that repository
+    pub async fn sync_all(&self) -> Vec<(String, Result<IndexDiff>)> {
+        let mut results = Vec::new();
+        for repo in &self.config.repos {
+            let name = repo_name(repo);
+            let result = self.sync_repo(&name, repo).await;
+            match &result {
+                Ok(diff) => info!(
+                    "Indexed {} files from {} ({} added, {} changed, {} removed)",
+                    diff.added.len() + diff.changed.len() + diff.unchanged,
+                    name,
+                    diff.added.len(),
+                    diff.changed.len(),
+                    diff.removed.len()
+                ),
+                Err(e) => warn!("Failed to sync {}: {}", name, e),
+            }
+            results.push((name, result));
+        }
+        results
+    }
+
+    async fn sync_repo(&self, name: &str, repo: &GitRepoConfig) -> Result<IndexDiff> {
+        let checkout = Path::new(&self.config.checkout_dir).join(name);
+
+        if checkout.join(".git").exists() {
+            let branch = repo.branch.as_deref().unwrap_or("HEAD");
+            git(&checkout, &["fetch", "--depth", "1", "origin", branch]).await?;
+            git(&checkout, &["reset", "--hard", "FETCH_HEAD"]).await?;
+        } else {
+            tokio::fs::create_dir_all(&self.config.checkout_dir).await?;
+            let target = checkout.to_string_lossy().to_string();
+            let mut args = vec!["clone", "--depth", "1"];
+            if let Some(branch) = &repo.branch {
+                args.extend(["--branch", branch.as_str()]);
+            }
+            args.extend([repo.url.as_str(), target.as_str()]);
+            git(Path::new("."), &args).await?;
+        }
+
+        let extensions = repo.extensions.clone();
+        let max_bytes = self.config.max_file_bytes;
+        let files = tokio::task::spawn_blocking(move || collect_files(&checkout, &extensions, max_bytes))
+            .await
+            .map_err(|e| AgentError::Internal(format!("Indexing task failed: {}", e)))?;
+
+        let count = files.len();
+        let diff = self.index.write().await.replace_repo(name, files);
+        let _ = self.events.send(AgentEvent::new(AgentEventKind::KnowledgeUpdated {
+            source: "git".to_string(),
+            embedded: None,
+            chunks: None,
+            repo: Some(name.to_string()),
+            files: Some(count),
+        }));
+
+        Ok(diff)
+    }
+}
+
+/// Run a git command, failing with its stderr
+async fn git(dir: &Path, args: &[&str]) -> Result<()> {
+    debug!("git {}", args.join(" "));
+    let output = Command::new("git").current_dir(dir).args(args).output().await?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(AgentError::ServiceUnavailable(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Name of a repository in the index
+fn repo_name(repo: &GitRepoConfig) -> String {
+    if let Some(name) = &repo.name {
+        return name.clone();
+    }
+
+    let url = repo.url.trim_end_matches('/');
+    let last = url.rsplit(['/', ':']).next().unwrap_or(url);
+    last.trim_end_matches(".git").to_string()
+}
+
+/// Read indexable files under `root` as (relative path, contents)
+///
+/// Files that are too large or not UTF-8 are skipped.
+pub(crate) fn collect_files(root: &Path, extensions: &[String], max_bytes: u64) -> Vec<(String, String)> {
+    let mut files = Vec::new();
+    let mut pending: Vec<PathBuf> = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+
+            if metadata.is_dir() {
+                let skipped = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| SKIPPED_DIRS.contains(&name));
+                if !skipped {
+                    pending.push(path);
+                }
+                continue;
+            }
+
+            let wanted = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| extensions.iter().any(|wanted| wanted == extension));
+            if !wanted || metadata.len() > max_bytes {
+                continue;
+            }
+
+            if let (Ok(content), Ok(relative)) = (std::fs::read_to_string(&path), path.strip_prefix(root)) {
+                files.push((relative.to_string_lossy().replace('\\', "/"), content));
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_name() {
+        let repo = |url: &str| GitRepoConfig {
+            url: url.to_string(),
+            name: None,
+            branch: None,
+            extensions: Vec::new(),
+        };
+
+        assert_eq!(repo_name(&repo("https://github.com/thecowboyai/cim-domain-graph.git")), "cim-domain-graph");
+        assert_eq!(repo_name(&repo("git@github.com:thecowboyai/alchemist")), "alchemist");
+        assert_eq!(
+            repo_name(&GitRepoConfig {
+                name: Some("graph".to_string()),
+                ..repo("https://example.com/graph.git")
+            }),
+            "graph"
+        );
+    }
+}
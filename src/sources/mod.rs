@@ -0,0 +1,321 @@
+//! Sources of real code and documentation
+//!
+//! Sources fill the agent's `CodeIndex`, which backs the `search_code`
+//! query, the examples given with concept explanations, and the excerpts
+//! retrieved into prompts. Excerpts that make it into a prompt are cited
+//! with the answer.
+
+pub mod git;
+pub mod refresh;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+/// Lines of context shown around a match
+const CONTEXT_LINES: usize = 2;
+
+/// A place in the indexed code matching a search
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct CodeMatch {
+    pub repo: String,
+    pub path: String,
+    /// 1-based line of the best match in the file
+    pub line: usize,
+    /// The matching line with surrounding context
+    pub snippet: String,
+    /// 1-based lines the snippet spans, inclusive
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Nearest heading above the match, in Markdown files
+    pub heading: Option<String>,
+    /// Number of query terms found on the line
+    pub score: usize,
+}
+
+impl CodeMatch {
+    /// Where the snippet came from, for citing it
+    pub fn citation(&self) -> SourceCitation {
+        SourceCitation {
+            path: format!("{}/{}", self.repo, self.path),
+            heading: self.heading.clone(),
+            start_line: self.start_line,
+            end_line: self.end_line,
+        }
+    }
+}
+
+/// An indexed excerpt an answer drew on
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SourceCitation {
+    /// Repository and path of the document
+    pub path: String,
+    pub heading: Option<String>,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// Numbered excerpts for a prompt, and citations for those that fit
+///
+/// Matches are taken in order until the next would push the excerpts past
+/// `max_chars`, so only what the model actually saw is cited.
+pub fn retrieval_context(matches: &[CodeMatch], max_chars: usize) -> (String, Vec<SourceCitation>) {
+    let mut context = String::new();
+    let mut citations = Vec::new();
+    for code_match in matches {
+        let citation = code_match.citation();
+        let heading = citation
+            .heading
+            .as_ref()
+            .map(|heading| format!(" ({})", heading))
+            .unwrap_or_default();
+        let excerpt = format!(
+            "[{}] {}:{}-{}{}\n{}\n\n",
+            citations.len() + 1,
+            citation.path,
+            citation.start_line,
+            citation.end_line,
+            heading,
+            code_match.snippet
+        );
+        if context.len() + excerpt.len() > max_chars {
+            break;
+        }
+
+        context.push_str(&excerpt);
+        citations.push(citation);
+    }
+
+    (context, citations)
+}
+
+/// How a repository's files changed when it was reindexed
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct IndexDiff {
+    pub added: Vec<String>,
+
+    /// Files whose content hash changed
+    pub changed: Vec<String>,
+
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl IndexDiff {
+    /// Whether any file was added, changed, or removed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// In-memory text index of files from all sources
+#[derive(Debug, Default)]
+pub struct CodeIndex {
+    /// File contents keyed by repository, then path
+    repos: BTreeMap<String, BTreeMap<String, String>>,
+
+    /// Content hashes of the same files
+    hashes: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+impl CodeIndex {
+    /// Replace everything indexed for a repository, returning what changed
+    pub fn replace_repo(&mut self, repo: &str, files: Vec<(String, String)>) -> IndexDiff {
+        let hashes: BTreeMap<String, u64> = files.iter().map(|(path, content)| (path.clone(), content_hash(content))).collect();
+        let previous = self.hashes.insert(repo.to_string(), hashes).unwrap_or_default();
+        let current = &self.hashes[repo];
+
+        let mut diff = IndexDiff::default();
+        for (path, hash) in current {
+            match previous.get(path) {
+                None => diff.added.push(path.clone()),
+                Some(old) if old != hash => diff.changed.push(path.clone()),
+                Some(_) => diff.unchanged += 1,
+            }
+        }
+        diff.removed = previous.into_keys().filter(|path| !current.contains_key(path)).collect();
+
+        self.repos.insert(repo.to_string(), files.into_iter().collect());
+        diff
+    }
+
+    /// Repositories and paths of files mentioning `term`, ignoring case
+    pub fn mentions(&self, term: &str) -> Vec<String> {
+        let term = term.to_lowercase();
+        self.repos
+            .iter()
+            .flat_map(|(name, files)| files.iter().map(move |(path, content)| (name, path, content)))
+            .filter(|(_, _, content)| content.to_lowercase().contains(&term))
+            .map(|(name, path, _)| format!("{}/{}", name, path))
+            .collect()
+    }
+
+    /// Every indexed file as repository, path, and content
+    pub fn files(&self) -> Vec<(String, String, String)> {
+        self.repos
+            .iter()
+            .flat_map(|(name, files)| files.iter().map(move |(path, content)| (name.clone(), path.clone(), content.clone())))
+            .collect()
+    }
+
+    /// Hash of every indexed path and its content, changing whenever a file
+    /// is added, changed, or removed
+    pub fn version(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hashes.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Number of indexed files
+    pub fn len(&self) -> usize {
+        self.repos.values().map(BTreeMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Find the files that best match `query`, at most one match per file
+    ///
+    /// Lines are scored by how many query terms they contain, ignoring case.
+    pub fn search(&self, query: &str, repo: Option<&str>, limit: usize) -> Vec<CodeMatch> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches: Vec<CodeMatch> = self
+            .repos
+            .iter()
+            .filter(|(name, _)| repo.is_none_or(|repo| repo == name.as_str()))
+            .flat_map(|(name, files)| files.iter().map(move |(path, content)| (name, path, content)))
+            .filter_map(|(name, path, content)| {
+                let lines: Vec<&str> = content.lines().collect();
+                let (index, score) = lines
+                    .iter()
+                    .enumerate()
+                    .map(|(index, line)| {
+                        let line = line.to_lowercase();
+                        (index, terms.iter().filter(|term| line.contains(term.as_str())).count())
+                    })
+                    // Earliest line wins ties
+                    .max_by(|(a_index, a_score), (b_index, b_score)| a_score.cmp(b_score).then(b_index.cmp(a_index)))?;
+
+                if score == 0 {
+                    return None;
+                }
+
+                let start = index.saturating_sub(CONTEXT_LINES);
+                let end = (index + CONTEXT_LINES + 1).min(lines.len());
+                let heading = path.ends_with(".md").then(|| {
+                    lines[..=index]
+                        .iter()
+                        .rev()
+                        .find(|line| line.starts_with('#'))
+                        .map(|line| line.trim_start_matches('#').trim().to_string())
+                });
+
+                Some(CodeMatch {
+                    repo: name.clone(),
+                    path: path.clone(),
+                    line: index + 1,
+                    snippet: lines[start..end].join("\n"),
+                    start_line: start + 1,
+                    end_line: end,
+                    heading: heading.flatten(),
+                    score,
+                })
+            })
+            .collect();
+
+        matches.sort_by(|a, b| b.score.cmp(&a.score));
+        matches.truncate(limit);
+        matches
+    }
+}
+
+/// Hash telling file contents apart between syncs
+///
+/// `DefaultHasher::new` uses fixed keys, so hashes are stable for the life
+/// of the process, which is as long as the index keeps them.
+pub(crate) fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_ranks_by_terms() {
+        let mut index = CodeIndex::default();
+        index.replace_repo(
+            "cim-domain-graph",
+            vec![
+                ("src/events.rs".to_string(), "use std::fmt;\n\npub enum GraphEvent {\n    NodeAdded,\n}\n".to_string()),
+                ("README.md".to_string(), "Graph events are emitted on change.\n".to_string()),
+            ],
+        );
+
+        let matches = index.search("GraphEvent enum", None, 5);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path, "src/events.rs");
+        assert_eq!(matches[0].line, 3);
+        assert!(matches[0].snippet.contains("NodeAdded"));
+
+        assert_eq!((matches[0].start_line, matches[0].end_line), (1, 5));
+
+        assert!(index.search("GraphEvent", Some("other"), 5).is_empty());
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn test_retrieval_context_cites_included_excerpts() {
+        let mut index = CodeIndex::default();
+        index.replace_repo(
+            "cim-docs",
+            vec![
+                ("events.md".to_string(), "# Events\n\n## Naming\n\nEvents are named in the past tense.\n".to_string()),
+                ("commands.md".to_string(), "# Commands\n\nCommands are named in the imperative.\n".to_string()),
+            ],
+        );
+        let matches = index.search("events named", None, 5);
+        assert_eq!(matches[0].heading.as_deref(), Some("Naming"));
+
+        let (context, citations) = retrieval_context(&matches, 1000);
+        assert!(context.starts_with("[1] cim-docs/"));
+        assert_eq!(citations.len(), 2);
+        assert_eq!(citations[0].path, "cim-docs/events.md");
+        assert_eq!((citations[0].start_line, citations[0].end_line), (3, 5));
+
+        let (_, citations) = retrieval_context(&matches, 100);
+        assert_eq!(citations.len(), 1);
+    }
+
+    #[test]
+    fn test_replace_repo_diffs_by_content() {
+        let mut index = CodeIndex::default();
+        let file = |path: &str, content: &str| (path.to_string(), content.to_string());
+
+        let diff = index.replace_repo("cim-docs", vec![file("a.md", "Aggregates"), file("b.md", "Sagas")]);
+        assert_eq!(diff.added, ["a.md", "b.md"]);
+
+        let diff = index.replace_repo("cim-docs", vec![file("a.md", "Aggregates"), file("b.md", "Sagas and CQRS"), file("c.md", "Events")]);
+        assert_eq!(
+            diff,
+            IndexDiff {
+                added: vec!["c.md".to_string()],
+                changed: vec!["b.md".to_string()],
+                removed: Vec::new(),
+                unchanged: 1,
+            }
+        );
+
+        let diff = index.replace_repo("cim-docs", vec![file("c.md", "Events")]);
+        assert_eq!((diff.removed.len(), diff.unchanged), (2, 1));
+        assert_eq!(index.mentions("events"), ["cim-docs/c.md"]);
+    }
+}
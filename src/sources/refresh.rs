@@ -0,0 +1,205 @@
+//! Scheduled knowledge refresh
+//!
+//! Every `sources.git.refresh_interval` the configured repositories are
+//! pulled and reindexed. Content hashes tell the files that changed apart
+//! from those that did not, so anything derived from a file's content only
+//! needs redoing for the changed ones. The concept graph is compared with
+//! the graph at the previous refresh, as is which concepts the indexed
+//! documentation mentions, and the change statistics go out as a
+//! `knowledge.refreshed` event.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::git::GitSource;
+use super::{CodeIndex, IndexDiff};
+use crate::agent::AlchemistAgent;
+use crate::error::Result;
+use crate::knowledge::{ConceptGraph, GraphDiff};
+use crate::events::AgentEventKind;
+use crate::nats_integration::AgentEvent;
+
+/// File counts of one repository's reindexing
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepoChanges {
+    pub added: usize,
+    pub changed: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+impl From<&IndexDiff> for RepoChanges {
+    fn from(diff: &IndexDiff) -> Self {
+        Self {
+            added: diff.added.len(),
+            changed: diff.changed.len(),
+            removed: diff.removed.len(),
+            unchanged: diff.unchanged,
+        }
+    }
+}
+
+/// What one refresh changed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RefreshStats {
+    /// Counts for each repository synced
+    pub repos: BTreeMap<String, RepoChanges>,
+
+    /// Repositories that failed to sync, keeping what was indexed before
+    pub failed: Vec<String>,
+
+    /// Totals over all repositories
+    pub files: RepoChanges,
+
+    /// Changes to the concept graph since the previous refresh
+    pub graph: GraphDiff,
+
+    /// Concepts the documentation mentions now but did not before
+    pub newly_documented: Vec<String>,
+
+    /// Concepts the documentation no longer mentions
+    pub no_longer_documented: Vec<String>,
+
+    pub duration_ms: u64,
+}
+
+impl RefreshStats {
+    fn new(results: &[(String, Result<IndexDiff>)]) -> Self {
+        let mut stats = Self::default();
+        for (name, result) in results {
+            match result {
+                Ok(diff) => {
+                    let changes = RepoChanges::from(diff);
+                    stats.files.added += changes.added;
+                    stats.files.changed += changes.changed;
+                    stats.files.removed += changes.removed;
+                    stats.files.unchanged += changes.unchanged;
+                    stats.repos.insert(name.clone(), changes);
+                }
+                Err(_) => stats.failed.push(name.clone()),
+            }
+        }
+        stats
+    }
+}
+
+/// Names of the concepts of `graph` mentioned anywhere in `index`
+fn documented(graph: &ConceptGraph, index: &CodeIndex) -> BTreeSet<String> {
+    graph
+        .concepts()
+        .filter(|concept| !index.mentions(&concept.name).is_empty())
+        .map(|concept| concept.name.clone())
+        .collect()
+}
+
+/// Re-ingests sources on a schedule and reports what changed
+pub struct KnowledgeRefresh {
+    source: GitSource,
+    agent: Arc<AlchemistAgent>,
+}
+
+impl KnowledgeRefresh {
+    pub fn new(source: GitSource, agent: Arc<AlchemistAgent>) -> Self {
+        Self { source, agent }
+    }
+
+    /// Refresh now and then on every refresh interval
+    pub async fn run(self) {
+        let mut graph = self.agent.concept_graph().await;
+        let mut mentioned = BTreeSet::new();
+
+        let mut interval = tokio::time::interval(self.source.refresh_interval());
+        loop {
+            interval.tick().await;
+            let stats = self.refresh(&mut graph, &mut mentioned).await;
+            tracing::info!(
+                "Knowledge refreshed: {} files added, {} changed, {} removed, {} concepts added, {} removed",
+                stats.files.added,
+                stats.files.changed,
+                stats.files.removed,
+                stats.graph.concepts_added.len(),
+                stats.graph.concepts_removed.len()
+            );
+            let _ = self.agent.event_sender().send(AgentEvent::new(AgentEventKind::KnowledgeRefreshed(stats)));
+        }
+    }
+
+    /// Sync the sources once, comparing the graph and the concepts
+    /// documented with `graph` and `mentioned` from the previous refresh,
+    /// which are then updated
+    async fn refresh(&self, graph: &mut ConceptGraph, mentioned: &mut BTreeSet<String>) -> RefreshStats {
+        let started = Instant::now();
+        let results = self.source.sync_all().await;
+        let mut stats = RefreshStats::new(&results);
+
+        let current = self.agent.concept_graph().await;
+        stats.graph = current.diff(graph);
+
+        let now_mentioned = documented(&current, &*self.agent.code_index().read().await);
+        stats.newly_documented = now_mentioned.difference(mentioned).cloned().collect();
+        stats.no_longer_documented = mentioned
+            .difference(&now_mentioned)
+            .filter(|name| current.concept(name).is_some())
+            .cloned()
+            .collect();
+
+        *graph = current;
+        *mentioned = now_mentioned;
+        stats.duration_ms = started.elapsed().as_millis() as u64;
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AgentError;
+
+    #[test]
+    fn test_stats_total_repositories() {
+        let results = vec![
+            (
+                "cim-docs".to_string(),
+                Ok(IndexDiff {
+                    added: vec!["a.md".to_string()],
+                    changed: vec!["b.md".to_string()],
+                    removed: Vec::new(),
+                    unchanged: 3,
+                }),
+            ),
+            ("cim-graph".to_string(), Err(AgentError::ServiceUnavailable("git fetch failed".to_string()))),
+            (
+                "cim-flow".to_string(),
+                Ok(IndexDiff {
+                    removed: vec!["old.md".to_string()],
+                    unchanged: 2,
+                    ..IndexDiff::default()
+                }),
+            ),
+        ];
+
+        let stats = RefreshStats::new(&results);
+        assert_eq!(stats.failed, ["cim-graph"]);
+        assert_eq!(stats.repos.len(), 2);
+        assert_eq!(
+            stats.files,
+            RepoChanges {
+                added: 1,
+                changed: 1,
+                removed: 1,
+                unchanged: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn test_documented_concepts() {
+        let graph = ConceptGraph::with_concepts(&["CQRS", "Saga"]);
+        let mut index = CodeIndex::default();
+        index.replace_repo("cim-docs", vec![("cqrs.md".to_string(), "# Cqrs\n\nSplit reads from writes.".to_string())]);
+
+        assert_eq!(documented(&graph, &index), BTreeSet::from(["CQRS".to_string()]));
+    }
+}
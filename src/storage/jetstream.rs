@@ -0,0 +1,229 @@
+//! Storage in a NATS JetStream key-value bucket
+//!
+//! Dialogs, workflows, and profiles share one bucket under the `dialogs.`,
+//! `workflows.`, and `profiles.` key prefixes, each value a JSON document.
+//! Archived dialog turns are kept under `archives.` and the knowledge graph
+//! under `graph.concepts`.
+//! Keys only allow a few characters, so IDs are escaped: any other byte
+//! becomes `=` and two hex digits.
+
+use async_nats::jetstream::kv::{self, Store};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::{DialogStore, GraphStore, ProfileStore, StoredWorkflow, UserProfile, WorkflowStore};
+use crate::config::NatsConfig;
+use crate::error::{AgentError, Result};
+use crate::knowledge::ConceptGraph;
+use crate::model::Message;
+
+const DIALOGS: &str = "dialogs.";
+const WORKFLOWS: &str = "workflows.";
+const PROFILES: &str = "profiles.";
+const ARCHIVES: &str = "archives.";
+const GRAPH: &str = "graph.";
+
+/// ID of the knowledge graph under [`GRAPH`]
+const CONCEPT_GRAPH: &str = "concepts";
+
+fn storage_error(action: &str, error: impl std::fmt::Display) -> AgentError {
+    AgentError::Storage(format!("Key-value {} failed: {}", action, error))
+}
+
+/// Keeps every store in one key-value bucket
+pub struct JetStreamStore {
+    store: Store,
+}
+
+impl JetStreamStore {
+    /// Open `bucket`, creating it if it does not exist
+    pub async fn connect(config: &NatsConfig, bucket: &str) -> Result<Self> {
+        let client = crate::nats_integration::connect(config).await?;
+        let jetstream = async_nats::jetstream::new(client);
+
+        let store = match jetstream.get_key_value(bucket).await {
+            Ok(store) => store,
+            Err(_) => jetstream
+                .create_key_value(kv::Config {
+                    bucket: bucket.to_string(),
+                    description: "Alchemist agent dialogs, workflows, profiles, and knowledge graph".to_string(),
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| storage_error("bucket creation", e))?,
+        };
+
+        Ok(Self { store })
+    }
+
+    async fn put<T: Serialize + ?Sized>(&self, prefix: &str, id: &str, value: &T) -> Result<()> {
+        let value = serde_json::to_vec(value)?;
+        self.store
+            .put(key(prefix, id), value.into())
+            .await
+            .map_err(|e| storage_error("put", e))?;
+        Ok(())
+    }
+
+    async fn get<T: DeserializeOwned>(&self, prefix: &str, id: &str) -> Result<Option<T>> {
+        let value = self.store.get(key(prefix, id)).await.map_err(|e| storage_error("get", e))?;
+        value.map(|value| serde_json::from_slice(&value).map_err(AgentError::from)).transpose()
+    }
+
+    async fn delete(&self, prefix: &str, id: &str) -> Result<()> {
+        self.store.purge(key(prefix, id)).await.map_err(|e| storage_error("delete", e))
+    }
+
+    /// IDs stored under `prefix`, sorted
+    async fn ids(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = self.store.keys().await.map_err(|e| storage_error("listing", e))?;
+
+        let mut ids = Vec::new();
+        while let Some(key) = keys.next().await {
+            let key = key.map_err(|e| storage_error("listing", e))?;
+            if let Some(id) = key.strip_prefix(prefix).and_then(unescape) {
+                ids.push(id);
+            }
+        }
+
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+/// Key of `id` under `prefix`
+fn key(prefix: &str, id: &str) -> String {
+    format!("{}{}", prefix, escape(id))
+}
+
+fn escape(id: &str) -> String {
+    let mut escaped = String::with_capacity(id.len());
+    for byte in id.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("={:02x}", byte)),
+        }
+    }
+    escaped
+}
+
+/// The ID `escape` made `escaped` from, if it is one
+fn unescape(escaped: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'=' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[async_trait]
+impl DialogStore for JetStreamStore {
+    async fn save_dialog(&self, dialog_id: &str, history: &[Message]) -> Result<()> {
+        self.put(DIALOGS, dialog_id, history).await
+    }
+
+    async fn load_dialog(&self, dialog_id: &str) -> Result<Option<Vec<Message>>> {
+        self.get(DIALOGS, dialog_id).await
+    }
+
+    async fn list_dialogs(&self) -> Result<Vec<String>> {
+        self.ids(DIALOGS).await
+    }
+
+    async fn delete_dialog(&self, dialog_id: &str) -> Result<()> {
+        self.delete(DIALOGS, dialog_id).await?;
+        self.delete(ARCHIVES, dialog_id).await
+    }
+
+    async fn archive_turns(&self, dialog_id: &str, turns: &[Message]) -> Result<()> {
+        let mut archived = self.archived_turns(dialog_id).await?;
+        archived.extend_from_slice(turns);
+        self.put(ARCHIVES, dialog_id, &archived).await
+    }
+
+    async fn archived_turns(&self, dialog_id: &str) -> Result<Vec<Message>> {
+        Ok(self.get(ARCHIVES, dialog_id).await?.unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl WorkflowStore for JetStreamStore {
+    async fn save_workflow(&self, workflow: &StoredWorkflow) -> Result<()> {
+        self.put(WORKFLOWS, &workflow.workflow_id, workflow).await
+    }
+
+    async fn load_workflow(&self, workflow_id: &str) -> Result<Option<StoredWorkflow>> {
+        self.get(WORKFLOWS, workflow_id).await
+    }
+
+    async fn list_workflows(&self) -> Result<Vec<StoredWorkflow>> {
+        let mut workflows = Vec::new();
+        for workflow_id in self.ids(WORKFLOWS).await? {
+            // Deleted since the keys were listed
+            if let Some(workflow) = self.load_workflow(&workflow_id).await? {
+                workflows.push(workflow);
+            }
+        }
+        Ok(workflows)
+    }
+
+    async fn delete_workflow(&self, workflow_id: &str) -> Result<()> {
+        self.delete(WORKFLOWS, workflow_id).await
+    }
+}
+
+#[async_trait]
+impl ProfileStore for JetStreamStore {
+    async fn save_profile(&self, profile: &UserProfile) -> Result<()> {
+        self.put(PROFILES, &profile.user_id, profile).await
+    }
+
+    async fn load_profile(&self, user_id: &str) -> Result<Option<UserProfile>> {
+        self.get(PROFILES, user_id).await
+    }
+
+    async fn delete_profile(&self, user_id: &str) -> Result<()> {
+        self.delete(PROFILES, user_id).await
+    }
+}
+
+#[async_trait]
+impl GraphStore for JetStreamStore {
+    async fn save_graph(&self, graph: &ConceptGraph) -> Result<()> {
+        self.put(GRAPH, CONCEPT_GRAPH, graph).await
+    }
+
+    async fn load_graph(&self) -> Result<Option<ConceptGraph>> {
+        self.get(GRAPH, CONCEPT_GRAPH).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_escape_ids() {
+        assert_eq!(key(DIALOGS, "dlg-abc_1"), "dialogs.dlg-abc_1");
+        assert_eq!(key(DIALOGS, "slack:C024.1700000000"), "dialogs.slack=3aC024=2e1700000000");
+        assert_eq!(key(PROFILES, "jo=é"), "profiles.jo=3d=c3=a9");
+    }
+
+    #[test]
+    fn test_unescape_round_trips() {
+        for id in ["dlg-abc", "slack:C024.1700000000", "a b/c=d", "jo=é", ""] {
+            assert_eq!(unescape(&escape(id)).as_deref(), Some(id));
+        }
+        assert_eq!(unescape("broken=4"), None);
+    }
+}
@@ -0,0 +1,110 @@
+//! In-memory storage, lost when the process exits
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+use super::{DialogStore, GraphStore, ProfileStore, StoredWorkflow, UserProfile, WorkflowStore};
+use crate::error::Result;
+use crate::knowledge::ConceptGraph;
+use crate::model::Message;
+
+/// Keeps every store in process memory
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    dialogs: RwLock<HashMap<String, Vec<Message>>>,
+    archives: RwLock<HashMap<String, Vec<Message>>>,
+    workflows: RwLock<HashMap<String, StoredWorkflow>>,
+    profiles: RwLock<HashMap<String, UserProfile>>,
+    graph: RwLock<Option<ConceptGraph>>,
+}
+
+#[async_trait]
+impl DialogStore for MemoryStore {
+    async fn save_dialog(&self, dialog_id: &str, history: &[Message]) -> Result<()> {
+        self.dialogs.write().await.insert(dialog_id.to_string(), history.to_vec());
+        Ok(())
+    }
+
+    async fn load_dialog(&self, dialog_id: &str) -> Result<Option<Vec<Message>>> {
+        Ok(self.dialogs.read().await.get(dialog_id).cloned())
+    }
+
+    async fn list_dialogs(&self) -> Result<Vec<String>> {
+        let mut ids: Vec<String> = self.dialogs.read().await.keys().cloned().collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    async fn delete_dialog(&self, dialog_id: &str) -> Result<()> {
+        self.dialogs.write().await.remove(dialog_id);
+        self.archives.write().await.remove(dialog_id);
+        Ok(())
+    }
+
+    async fn archive_turns(&self, dialog_id: &str, turns: &[Message]) -> Result<()> {
+        self.archives
+            .write()
+            .await
+            .entry(dialog_id.to_string())
+            .or_default()
+            .extend_from_slice(turns);
+        Ok(())
+    }
+
+    async fn archived_turns(&self, dialog_id: &str) -> Result<Vec<Message>> {
+        Ok(self.archives.read().await.get(dialog_id).cloned().unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl WorkflowStore for MemoryStore {
+    async fn save_workflow(&self, workflow: &StoredWorkflow) -> Result<()> {
+        self.workflows.write().await.insert(workflow.workflow_id.clone(), workflow.clone());
+        Ok(())
+    }
+
+    async fn load_workflow(&self, workflow_id: &str) -> Result<Option<StoredWorkflow>> {
+        Ok(self.workflows.read().await.get(workflow_id).cloned())
+    }
+
+    async fn list_workflows(&self) -> Result<Vec<StoredWorkflow>> {
+        let mut workflows: Vec<StoredWorkflow> = self.workflows.read().await.values().cloned().collect();
+        workflows.sort_by(|a, b| a.workflow_id.cmp(&b.workflow_id));
+        Ok(workflows)
+    }
+
+    async fn delete_workflow(&self, workflow_id: &str) -> Result<()> {
+        self.workflows.write().await.remove(workflow_id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProfileStore for MemoryStore {
+    async fn save_profile(&self, profile: &UserProfile) -> Result<()> {
+        self.profiles.write().await.insert(profile.user_id.clone(), profile.clone());
+        Ok(())
+    }
+
+    async fn load_profile(&self, user_id: &str) -> Result<Option<UserProfile>> {
+        Ok(self.profiles.read().await.get(user_id).cloned())
+    }
+
+    async fn delete_profile(&self, user_id: &str) -> Result<()> {
+        self.profiles.write().await.remove(user_id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl GraphStore for MemoryStore {
+    async fn save_graph(&self, graph: &ConceptGraph) -> Result<()> {
+        *self.graph.write().await = Some(graph.clone());
+        Ok(())
+    }
+
+    async fn load_graph(&self) -> Result<Option<ConceptGraph>> {
+        Ok(self.graph.read().await.clone())
+    }
+}
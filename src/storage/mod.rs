@@ -0,0 +1,153 @@
+//! Persistent storage for dialogs, workflows, user profiles, and the
+//! knowledge graph
+//!
+//! The backend is chosen by `storage.backend` in the configuration. Every
+//! backend implements the same store traits, so the agent does not care
+//! where its state lives.
+
+pub mod jetstream;
+pub mod memory;
+#[cfg(feature = "sql")]
+pub mod sql;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::config::{NatsConfig, StorageBackend, StorageConfig};
+use crate::error::Result;
+use crate::knowledge::ConceptGraph;
+use crate::model::Message;
+
+pub use memory::MemoryStore;
+
+/// A workflow as stored between runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredWorkflow {
+    pub workflow_id: String,
+    pub name: String,
+    pub status: String,
+    pub current_step: Option<String>,
+
+    /// Steps, edges, and anything else needed to resume the workflow
+    pub state: serde_json::Value,
+
+    pub updated_at: DateTime<Utc>,
+}
+
+/// What the agent remembers about a user
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub user_id: String,
+    pub display_name: Option<String>,
+    pub preferences: serde_json::Value,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Dialog histories
+#[async_trait]
+pub trait DialogStore: Send + Sync {
+    /// Replace the stored history of a dialog
+    async fn save_dialog(&self, dialog_id: &str, history: &[Message]) -> Result<()>;
+
+    async fn load_dialog(&self, dialog_id: &str) -> Result<Option<Vec<Message>>>;
+
+    async fn list_dialogs(&self) -> Result<Vec<String>>;
+
+    /// Delete a dialog's history and its archived turns
+    async fn delete_dialog(&self, dialog_id: &str) -> Result<()>;
+
+    /// Keep turns taken out of a dialog's history, such as those a summary
+    /// replaced, after any archived before
+    async fn archive_turns(&self, dialog_id: &str, turns: &[Message]) -> Result<()>;
+
+    /// Turns archived for a dialog, oldest first
+    async fn archived_turns(&self, dialog_id: &str) -> Result<Vec<Message>>;
+}
+
+/// Workflow progress
+#[async_trait]
+pub trait WorkflowStore: Send + Sync {
+    async fn save_workflow(&self, workflow: &StoredWorkflow) -> Result<()>;
+
+    async fn load_workflow(&self, workflow_id: &str) -> Result<Option<StoredWorkflow>>;
+
+    async fn list_workflows(&self) -> Result<Vec<StoredWorkflow>>;
+
+    async fn delete_workflow(&self, workflow_id: &str) -> Result<()>;
+}
+
+/// User profiles
+#[async_trait]
+pub trait ProfileStore: Send + Sync {
+    async fn save_profile(&self, profile: &UserProfile) -> Result<()>;
+
+    async fn load_profile(&self, user_id: &str) -> Result<Option<UserProfile>>;
+
+    async fn delete_profile(&self, user_id: &str) -> Result<()>;
+}
+
+/// The concept graph, with the concepts and relations added at runtime
+#[async_trait]
+pub trait GraphStore: Send + Sync {
+    async fn save_graph(&self, graph: &ConceptGraph) -> Result<()>;
+
+    /// The graph last saved, if it ever was
+    async fn load_graph(&self) -> Result<Option<ConceptGraph>>;
+}
+
+/// The stores of one backend
+#[derive(Clone)]
+pub struct Stores {
+    pub dialogs: Arc<dyn DialogStore>,
+    pub workflows: Arc<dyn WorkflowStore>,
+    pub profiles: Arc<dyn ProfileStore>,
+    pub graph: Arc<dyn GraphStore>,
+}
+
+impl Stores {
+    /// Stores that all use one backend
+    pub fn from_backend<S>(store: S) -> Self
+    where
+        S: DialogStore + WorkflowStore + ProfileStore + GraphStore + 'static,
+    {
+        let store = Arc::new(store);
+        Self {
+            dialogs: store.clone(),
+            workflows: store.clone(),
+            profiles: store.clone(),
+            graph: store,
+        }
+    }
+
+    /// Stores that keep everything in process memory
+    pub fn memory() -> Self {
+        Self::from_backend(MemoryStore::default())
+    }
+}
+
+/// Connect to the configured backend, running its migrations
+///
+/// The JetStream backend connects with `nats`.
+pub async fn open(config: &StorageConfig, nats: &NatsConfig) -> Result<Stores> {
+    match &config.backend {
+        StorageBackend::Memory => Ok(Stores::memory()),
+        StorageBackend::JetStream { bucket } => {
+            Ok(Stores::from_backend(jetstream::JetStreamStore::connect(nats, bucket).await?))
+        }
+        StorageBackend::Sql { url, max_connections } => open_sql(url, *max_connections).await,
+    }
+}
+
+#[cfg(feature = "sql")]
+async fn open_sql(url: &str, max_connections: u32) -> Result<Stores> {
+    Ok(Stores::from_backend(sql::SqlStore::connect(url, max_connections).await?))
+}
+
+#[cfg(not(feature = "sql"))]
+async fn open_sql(_url: &str, _max_connections: u32) -> Result<Stores> {
+    Err(crate::error::AgentError::Configuration(
+        "SQL storage is configured but the agent was built without the `sql` feature".to_string(),
+    ))
+}
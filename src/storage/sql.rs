@@ -0,0 +1,377 @@
+//! SQLite and Postgres storage
+//!
+//! One implementation serves both databases through sqlx's `Any` driver,
+//! picked from the URL scheme. Migrations in `migrations/` run on connect.
+//! Dialog messages get a row each so history can be queried directly.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::any::AnyPoolOptions;
+use sqlx::AnyPool;
+
+use super::{DialogStore, GraphStore, ProfileStore, StoredWorkflow, UserProfile, WorkflowStore};
+use crate::error::{AgentError, Result};
+use crate::knowledge::ConceptGraph;
+use crate::model::Message;
+
+static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations");
+
+/// Row of the knowledge graph in `knowledge_graph`
+const CONCEPT_GRAPH: &str = "concepts";
+
+impl From<sqlx::Error> for AgentError {
+    fn from(error: sqlx::Error) -> Self {
+        AgentError::Storage(error.to_string())
+    }
+}
+
+/// Stores backed by a SQL database
+#[derive(Debug, Clone)]
+pub struct SqlStore {
+    pool: AnyPool,
+}
+
+impl SqlStore {
+    /// Connect to `url` and bring the schema up to date
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self> {
+        sqlx::any::install_default_drivers();
+
+        let pool = AnyPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(url)
+            .await?;
+
+        MIGRATOR
+            .run(&pool)
+            .await
+            .map_err(|e| AgentError::Storage(format!("Migration failed: {}", e)))?;
+
+        Ok(Self { pool })
+    }
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| AgentError::Storage(format!("Invalid timestamp {}: {}", value, e)))
+}
+
+type WorkflowRow = (String, String, String, Option<String>, String, String);
+
+fn workflow_from_row(
+    (workflow_id, name, status, current_step, state, updated_at): WorkflowRow,
+) -> Result<StoredWorkflow> {
+    Ok(StoredWorkflow {
+        workflow_id,
+        name,
+        status,
+        current_step,
+        state: serde_json::from_str(&state)?,
+        updated_at: parse_time(&updated_at)?,
+    })
+}
+
+#[async_trait]
+impl DialogStore for SqlStore {
+    async fn save_dialog(&self, dialog_id: &str, history: &[Message]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO dialogs (dialog_id, updated_at) VALUES ($1, $2) \
+             ON CONFLICT (dialog_id) DO UPDATE SET updated_at = excluded.updated_at",
+        )
+        .bind(dialog_id)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM dialog_messages WHERE dialog_id = $1")
+            .bind(dialog_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for (position, message) in history.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO dialog_messages (dialog_id, position, role, content, created_at) \
+                 VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(dialog_id)
+            .bind(position as i64)
+            .bind(&message.role)
+            .bind(&message.content)
+            .bind(message.timestamp.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn load_dialog(&self, dialog_id: &str) -> Result<Option<Vec<Message>>> {
+        let exists: Option<(String,)> = sqlx::query_as("SELECT dialog_id FROM dialogs WHERE dialog_id = $1")
+            .bind(dialog_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+
+        let rows: Vec<(String, String, String)> = sqlx::query_as(
+            "SELECT role, content, created_at FROM dialog_messages WHERE dialog_id = $1 ORDER BY position",
+        )
+        .bind(dialog_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|(role, content, created_at)| {
+                Ok(Message {
+                    role,
+                    content,
+                    timestamp: parse_time(&created_at)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Some)
+    }
+
+    async fn list_dialogs(&self) -> Result<Vec<String>> {
+        let rows: Vec<(String,)> = sqlx::query_as("SELECT dialog_id FROM dialogs ORDER BY dialog_id")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(|(dialog_id,)| dialog_id).collect())
+    }
+
+    async fn delete_dialog(&self, dialog_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // SQLite only cascades with foreign keys enabled
+        sqlx::query("DELETE FROM dialog_messages WHERE dialog_id = $1")
+            .bind(dialog_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM dialogs WHERE dialog_id = $1")
+            .bind(dialog_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM dialog_archive WHERE dialog_id = $1")
+            .bind(dialog_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn archive_turns(&self, dialog_id: &str, turns: &[Message]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let (archived,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM dialog_archive WHERE dialog_id = $1")
+            .bind(dialog_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        for (offset, message) in turns.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO dialog_archive (dialog_id, position, role, content, created_at) \
+                 VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(dialog_id)
+            .bind(archived + offset as i64)
+            .bind(&message.role)
+            .bind(&message.content)
+            .bind(message.timestamp.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn archived_turns(&self, dialog_id: &str) -> Result<Vec<Message>> {
+        let rows: Vec<(String, String, String)> = sqlx::query_as(
+            "SELECT role, content, created_at FROM dialog_archive WHERE dialog_id = $1 ORDER BY position",
+        )
+        .bind(dialog_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|(role, content, created_at)| {
+                Ok(Message {
+                    role,
+                    content,
+                    timestamp: parse_time(&created_at)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl WorkflowStore for SqlStore {
+    async fn save_workflow(&self, workflow: &StoredWorkflow) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO workflows (workflow_id, name, status, current_step, state, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6) \
+             ON CONFLICT (workflow_id) DO UPDATE SET name = excluded.name, status = excluded.status, \
+             current_step = excluded.current_step, state = excluded.state, updated_at = excluded.updated_at",
+        )
+        .bind(&workflow.workflow_id)
+        .bind(&workflow.name)
+        .bind(&workflow.status)
+        .bind(workflow.current_step.clone())
+        .bind(workflow.state.to_string())
+        .bind(workflow.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn load_workflow(&self, workflow_id: &str) -> Result<Option<StoredWorkflow>> {
+        let row: Option<WorkflowRow> = sqlx::query_as(
+            "SELECT workflow_id, name, status, current_step, state, updated_at FROM workflows WHERE workflow_id = $1",
+        )
+        .bind(workflow_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(workflow_from_row).transpose()
+    }
+
+    async fn list_workflows(&self) -> Result<Vec<StoredWorkflow>> {
+        let rows: Vec<WorkflowRow> = sqlx::query_as(
+            "SELECT workflow_id, name, status, current_step, state, updated_at FROM workflows ORDER BY workflow_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(workflow_from_row).collect()
+    }
+
+    async fn delete_workflow(&self, workflow_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM workflows WHERE workflow_id = $1")
+            .bind(workflow_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProfileStore for SqlStore {
+    async fn save_profile(&self, profile: &UserProfile) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO profiles (user_id, display_name, preferences, updated_at) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (user_id) DO UPDATE SET display_name = excluded.display_name, \
+             preferences = excluded.preferences, updated_at = excluded.updated_at",
+        )
+        .bind(&profile.user_id)
+        .bind(profile.display_name.clone())
+        .bind(profile.preferences.to_string())
+        .bind(profile.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn load_profile(&self, user_id: &str) -> Result<Option<UserProfile>> {
+        let row: Option<(String, Option<String>, String, String)> = sqlx::query_as(
+            "SELECT user_id, display_name, preferences, updated_at FROM profiles WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|(user_id, display_name, preferences, updated_at)| {
+            Ok(UserProfile {
+                user_id,
+                display_name,
+                preferences: serde_json::from_str(&preferences)?,
+                updated_at: parse_time(&updated_at)?,
+            })
+        })
+        .transpose()
+    }
+
+    async fn delete_profile(&self, user_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM profiles WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl GraphStore for SqlStore {
+    async fn save_graph(&self, graph: &ConceptGraph) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO knowledge_graph (graph_id, graph, updated_at) VALUES ($1, $2, $3) \
+             ON CONFLICT (graph_id) DO UPDATE SET graph = excluded.graph, updated_at = excluded.updated_at",
+        )
+        .bind(CONCEPT_GRAPH)
+        .bind(serde_json::to_string(graph)?)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn load_graph(&self) -> Result<Option<ConceptGraph>> {
+        let row: Option<(String,)> = sqlx::query_as("SELECT graph FROM knowledge_graph WHERE graph_id = $1")
+            .bind(CONCEPT_GRAPH)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|(graph,)| serde_json::from_str(&graph).map_err(AgentError::from))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dialog_round_trip_in_sqlite() {
+        // Each connection to an in-memory database gets its own database
+        let store = SqlStore::connect("sqlite::memory:", 1).await.unwrap();
+        let history = vec![
+            Message {
+                role: "user".to_string(),
+                content: "What is an aggregate?".to_string(),
+                timestamp: parse_time("2024-01-15T10:00:00Z").unwrap(),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: "A consistency boundary.".to_string(),
+                timestamp: parse_time("2024-01-15T10:00:05Z").unwrap(),
+            },
+        ];
+
+        store.save_dialog("dialog-1", &history).await.unwrap();
+        store.save_dialog("dialog-1", &history[..1]).await.unwrap();
+
+        let loaded = store.load_dialog("dialog-1").await.unwrap().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].content, "What is an aggregate?");
+        assert_eq!(loaded[0].timestamp, history[0].timestamp);
+        assert_eq!(store.list_dialogs().await.unwrap(), vec!["dialog-1".to_string()]);
+
+        store.archive_turns("dialog-1", &history[..1]).await.unwrap();
+        store.archive_turns("dialog-1", &history[1..]).await.unwrap();
+        let archived = store.archived_turns("dialog-1").await.unwrap();
+        assert_eq!(archived.len(), 2);
+        assert_eq!(archived[1].content, "A consistency boundary.");
+
+        store.delete_dialog("dialog-1").await.unwrap();
+        assert!(store.load_dialog("dialog-1").await.unwrap().is_none());
+        assert!(store.archived_turns("dialog-1").await.unwrap().is_empty());
+    }
+}
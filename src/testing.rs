@@ -0,0 +1,363 @@
+//! Fixtures for integration tests against the Alchemist
+//!
+//! Enabled with the `testing` feature. [`TestNats`] runs a `nats-server`
+//! subprocess on a free port, and [`TestAgent`] runs the agent service on it
+//! with in-memory storage and a [`ScriptedProvider`] in place of a model:
+//!
+//! ```no_run
+//! # async fn example() -> cim_agent_alchemist::Result<()> {
+//! use cim_agent_alchemist::testing::{ScriptedProvider, TestAgent, TestNats};
+//!
+//! let nats = TestNats::start().await?;
+//! let model = ScriptedProvider::new("I can only talk about CIM.")
+//!     .reply_to("event sourcing", "Event Sourcing stores every change as an event.");
+//! let agent = TestAgent::builder().provider(model.clone()).start(&nats).await?;
+//!
+//! let answer = agent.client().dialog("d1", "What is Event Sourcing?").await?;
+//! assert!(answer.contains("stores every change"));
+//! assert!(model.prompts()[0].contains("What is Event Sourcing?"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The server binary is `nats-server` on the `PATH`, or the one named by
+//! `NATS_SERVER`. Each agent gets a subject prefix of its own, so tests can
+//! share a server as long as they use distinct dialog IDs, since dialog
+//! subjects are not prefixed.
+
+use async_trait::async_trait;
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::agent::AlchemistAgent;
+use crate::client::AgentClient;
+use crate::config::AgentConfig;
+use crate::error::{AgentError, Result};
+use crate::model::{ChunkStream, Message, ModelCapabilities, ModelInfo, ModelProvider, ResponseChunk};
+use crate::service::AgentService;
+
+/// Longest a fixture may take to come up
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A `nats-server` with JetStream, stopped when dropped
+pub struct TestNats {
+    process: Child,
+    url: String,
+    store_dir: PathBuf,
+}
+
+impl TestNats {
+    /// Start a server on a free local port and wait until it accepts
+    /// connections
+    pub async fn start() -> Result<Self> {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?.local_addr()?.port();
+        let store_dir = std::env::temp_dir().join(format!("alchemist-test-nats-{}", uuid::Uuid::new_v4()));
+        let binary = std::env::var("NATS_SERVER").unwrap_or_else(|_| "nats-server".to_string());
+
+        let process = Command::new(&binary)
+            .args(["-a", "127.0.0.1", "-p", &port.to_string(), "-js", "-sd"])
+            .arg(&store_dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| {
+                AgentError::ServiceUnavailable(format!(
+                    "Failed to run {} ({}); install nats-server or set NATS_SERVER",
+                    binary, e
+                ))
+            })?;
+        let nats = Self {
+            process,
+            url: format!("nats://127.0.0.1:{}", port),
+            store_dir,
+        };
+
+        let started = tokio::time::Instant::now();
+        while TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_err() {
+            if started.elapsed() > STARTUP_TIMEOUT {
+                return Err(AgentError::Timeout(format!("nats-server on port {}", port)));
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        Ok(nats)
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// A plain connection to the server, for publishing and subscribing
+    /// directly
+    pub async fn client(&self) -> Result<async_nats::Client> {
+        async_nats::connect(&self.url)
+            .await
+            .map_err(|e| AgentError::ServiceUnavailable(format!("Failed to connect to {}: {}", self.url, e)))
+    }
+}
+
+impl Drop for TestNats {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = std::fs::remove_dir_all(&self.store_dir);
+    }
+}
+
+#[derive(Default)]
+struct Script {
+    /// Answers to prompts containing each pattern, first match wins
+    rules: Vec<(String, String)>,
+    fallback: String,
+    prompts: Vec<String>,
+
+    /// Conversation context sent along with each contextual prompt
+    contexts: Vec<Vec<Message>>,
+    down: bool,
+}
+
+/// A model answering from a script
+///
+/// Clones share the script, so a test can keep one to inspect the prompts
+/// the agent sent or take the model down while the agent holds another.
+#[derive(Clone, Default)]
+pub struct ScriptedProvider {
+    script: Arc<Mutex<Script>>,
+}
+
+impl ScriptedProvider {
+    /// A model answering `fallback` to anything not scripted
+    pub fn new(fallback: impl Into<String>) -> Self {
+        let provider = Self::default();
+        provider.script.lock().unwrap().fallback = fallback.into();
+        provider
+    }
+
+    /// Answer `answer` to prompts containing `pattern`, ignoring case
+    pub fn reply_to(self, pattern: impl Into<String>, answer: impl Into<String>) -> Self {
+        self.script
+            .lock()
+            .unwrap()
+            .rules
+            .push((pattern.into().to_lowercase(), answer.into()));
+        self
+    }
+
+    /// Fail every call and health check until brought back up
+    pub fn set_down(&self, down: bool) {
+        self.script.lock().unwrap().down = down;
+    }
+
+    /// Prompts received so far, oldest first
+    pub fn prompts(&self) -> Vec<String> {
+        self.script.lock().unwrap().prompts.clone()
+    }
+
+    /// Conversation contexts received so far, oldest first
+    pub fn contexts(&self) -> Vec<Vec<Message>> {
+        self.script.lock().unwrap().contexts.clone()
+    }
+
+    fn answer(&self, prompt: &str) -> Result<String> {
+        let mut script = self.script.lock().unwrap();
+        if script.down {
+            return Err(AgentError::ModelProvider("Scripted model is down".to_string()));
+        }
+        script.prompts.push(prompt.to_string());
+
+        let lowered = prompt.to_lowercase();
+        let answer = script
+            .rules
+            .iter()
+            .find(|(pattern, _)| lowered.contains(pattern))
+            .map(|(_, answer)| answer.clone());
+        Ok(answer.unwrap_or_else(|| script.fallback.clone()))
+    }
+}
+
+#[async_trait]
+impl ModelProvider for ScriptedProvider {
+    async fn generate(&self, prompt: &str) -> Result<String> {
+        self.answer(prompt)
+    }
+
+    async fn generate_with_context(&self, prompt: &str, context: &[Message]) -> Result<String> {
+        let answer = self.answer(prompt)?;
+        self.script.lock().unwrap().contexts.push(context.to_vec());
+        Ok(answer)
+    }
+
+    /// The answer a word at a time, so streamed replies come in several
+    /// chunks
+    async fn generate_stream(&self, prompt: &str, context: &[Message]) -> Result<ChunkStream> {
+        let answer = self.generate_with_context(prompt, context).await?;
+        let mut chunks: Vec<Result<ResponseChunk>> = answer
+            .split_inclusive(' ')
+            .map(|word| Ok(ResponseChunk { content: word.to_string(), done: false }))
+            .collect();
+        chunks.push(Ok(ResponseChunk { content: String::new(), done: true }));
+        Ok(Box::pin(futures::stream::iter(chunks)))
+    }
+
+    /// Words hashed into a small vector, so texts sharing words are similar
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        if self.script.lock().unwrap().down {
+            return Err(AgentError::ModelProvider("Scripted model is down".to_string()));
+        }
+
+        let mut embedding = vec![0.0; 32];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+            let hash = word
+                .to_lowercase()
+                .bytes()
+                .fold(0usize, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as usize));
+            embedding[hash % 32] += 1.0;
+        }
+        Ok(embedding)
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        if self.script.lock().unwrap().down {
+            return Err(AgentError::ModelProvider("Scripted model is down".to_string()));
+        }
+        Ok(())
+    }
+
+    fn model_info(&self) -> ModelInfo {
+        ModelInfo {
+            provider: "Scripted".to_string(),
+            model: "scripted".to_string(),
+            version: None,
+            capabilities: ModelCapabilities {
+                max_context_length: 4096,
+                streaming: true,
+                function_calling: false,
+                vision: false,
+                embeddings: true,
+            },
+        }
+    }
+}
+
+/// Builds a [`TestAgent`]
+pub struct TestAgentBuilder {
+    config: AgentConfig,
+    provider: ScriptedProvider,
+}
+
+impl TestAgentBuilder {
+    /// Use `provider` as the agent's model
+    pub fn provider(mut self, provider: ScriptedProvider) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    /// Adjust the configuration; servers and the subject prefix are set on
+    /// start
+    pub fn configure(mut self, configure: impl FnOnce(&mut AgentConfig)) -> Self {
+        configure(&mut self.config);
+        self
+    }
+
+    /// Run the agent service on `nats` and wait until it answers health
+    /// checks
+    pub async fn start(self, nats: &TestNats) -> Result<TestAgent> {
+        let mut config = self.config;
+        config.nats.servers = vec![nats.url().to_string()];
+        config.nats.subject_prefix = format!("test.{}.alchemist", uuid::Uuid::new_v4().simple());
+
+        let service = AgentService::with_model_provider(config.clone(), Box::new(self.provider)).await?;
+        service.start().await?;
+
+        // Subscriptions are live once the agent answers over them
+        let client = AgentClient::connect(&config.nats)
+            .await?
+            .with_origin("test")
+            .with_timeout(Duration::from_secs(1));
+        let started = tokio::time::Instant::now();
+        while client.health().await.is_err() {
+            if started.elapsed() > STARTUP_TIMEOUT {
+                return Err(AgentError::Timeout("Test agent startup".to_string()));
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        Ok(TestAgent {
+            client: client.with_timeout(STARTUP_TIMEOUT),
+            service,
+            config,
+        })
+    }
+}
+
+/// The agent service running against a [`TestNats`]
+pub struct TestAgent {
+    service: AgentService,
+    client: AgentClient,
+    config: AgentConfig,
+}
+
+impl TestAgent {
+    /// An agent with the default configuration, in-memory storage, and a
+    /// model answering "OK" to everything
+    pub fn builder() -> TestAgentBuilder {
+        let mut config = AgentConfig::default();
+        config.nats.jetstream = None;
+        config.nats.retry.max_attempts = 1;
+
+        TestAgentBuilder {
+            config,
+            provider: ScriptedProvider::new("OK"),
+        }
+    }
+
+    /// A client of this agent
+    pub fn client(&self) -> &AgentClient {
+        &self.client
+    }
+
+    /// The agent itself, for checks that bypass NATS
+    pub fn agent(&self) -> Arc<AlchemistAgent> {
+        self.service.agent()
+    }
+
+    /// Configuration the agent runs with, including its subject prefix
+    pub fn config(&self) -> &AgentConfig {
+        &self.config
+    }
+
+    /// Stop the service, draining pending messages
+    pub async fn stop(self) -> Result<()> {
+        self.service.stop().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scripted_provider_answers_and_records() {
+        let model = ScriptedProvider::new("I don't know.").reply_to("CQRS", "Commands and queries split.");
+        let agent_side: Box<dyn ModelProvider> = Box::new(model.clone());
+
+        assert_eq!(agent_side.generate("Explain cqrs").await.unwrap(), "Commands and queries split.");
+        assert_eq!(agent_side.generate("Explain sagas").await.unwrap(), "I don't know.");
+        assert_eq!(model.prompts(), vec!["Explain cqrs", "Explain sagas"]);
+    }
+
+    #[tokio::test]
+    async fn test_scripted_provider_goes_down() {
+        let model = ScriptedProvider::new("OK");
+        model.set_down(true);
+        assert!(model.health_check().await.is_err());
+        assert!(model.generate("Hello").await.is_err());
+
+        model.set_down(false);
+        assert!(model.health_check().await.is_ok());
+        assert!(model.prompts().is_empty());
+    }
+}
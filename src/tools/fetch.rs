@@ -0,0 +1,213 @@
+//! Fetching URLs
+
+use async_trait::async_trait;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect;
+use reqwest::Url;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{Citation, Tool, ToolOutput};
+use crate::config::ToolsConfig;
+use crate::error::{AgentError, Result};
+use crate::model::ToolSpec;
+
+/// Fetch a web page or API response as text
+pub struct FetchUrl {
+    http: reqwest::Client,
+    allowed_hosts: Vec<String>,
+    max_bytes: usize,
+    timeout: Duration,
+}
+
+impl FetchUrl {
+    /// Build the tool; fails without an allowlist, since an open fetch
+    /// reaches whatever the agent's network can
+    pub fn new(config: &ToolsConfig) -> Result<Self> {
+        if config.allowed_hosts.is_empty() {
+            return Err(AgentError::Configuration(
+                "tools.fetch_url needs tools.allowed_hosts".to_string(),
+            ));
+        }
+
+        // Each redirect target is checked like the URL the model asked for
+        let allowed_hosts = config.allowed_hosts.clone();
+        let redirects = redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            match check_url(&allowed_hosts, attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        });
+        let http = reqwest::Client::builder()
+            .redirect(redirects)
+            .dns_resolver(Arc::new(PublicResolver))
+            .build()
+            .map_err(|e| AgentError::Configuration(format!("Failed to build fetch_url client: {}", e)))?;
+
+        Ok(Self {
+            http,
+            allowed_hosts: config.allowed_hosts.clone(),
+            max_bytes: config.max_output_bytes,
+            timeout: config.timeout,
+        })
+    }
+}
+
+const MAX_REDIRECTS: usize = 10;
+
+/// Whether `host` is allowed, exactly or as a subdomain of an allowed host
+fn host_allowed(allowed_hosts: &[String], host: &str) -> bool {
+    allowed_hosts.iter().any(|allowed| {
+        host.eq_ignore_ascii_case(allowed)
+            || host
+                .to_ascii_lowercase()
+                .ends_with(&format!(".{}", allowed.to_ascii_lowercase()))
+    })
+}
+
+/// Refuse URLs with another scheme, a host off the allowlist, or a
+/// non-public IP address as host
+fn check_url(allowed_hosts: &[String], url: &Url) -> Result<()> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(AgentError::PermissionDenied(format!("Scheme {} is not allowed", url.scheme())));
+    }
+    let host = url.host_str().unwrap_or_default();
+    let public = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => is_public(ip),
+        Err(_) => !host.is_empty(),
+    };
+    if !public || !host_allowed(allowed_hosts, host) {
+        return Err(AgentError::PermissionDenied(format!("Host of {} is not allowed", url)));
+    }
+    Ok(())
+}
+
+/// Whether `ip` is reachable on the public internet, rather than loopback,
+/// private, link-local, or otherwise reserved
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64)
+                || a == 0)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link-local, fe80::/10
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Resolves names as usual but drops non-public addresses, so an allowed
+/// name pointed at an internal address is refused
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[async_trait]
+impl Tool for FetchUrl {
+    fn spec(&self) -> ToolSpec {
+        ToolSpec {
+            name: "fetch_url".to_string(),
+            description: "Fetch an http or https URL and return the response body as text".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": { "type": "string" },
+                },
+                "required": ["url"],
+            }),
+        }
+    }
+
+    async fn call(&self, arguments: &serde_json::Value) -> Result<ToolOutput> {
+        let url = arguments["url"]
+            .as_str()
+            .ok_or_else(|| AgentError::InvalidRequest("Missing url argument".to_string()))?;
+        let url = Url::parse(url).map_err(|e| AgentError::InvalidRequest(format!("Invalid URL {}: {}", url, e)))?;
+        check_url(&self.allowed_hosts, &url)?;
+
+        let citation = Citation {
+            title: url.to_string(),
+            url: url.to_string(),
+        };
+        let mut response = self.http.get(url).timeout(self.timeout).send().await?;
+        let status = response.status();
+
+        // Stop reading once there is more than the model will see
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() > self.max_bytes {
+                break;
+            }
+        }
+
+        Ok(ToolOutput {
+            text: format!("HTTP {}\n\n{}", status, String::from_utf8_lossy(&body)),
+            citations: vec![citation],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_allowlist() {
+        let allowed = vec!["docs.rs".to_string()];
+
+        assert!(host_allowed(&allowed, "docs.rs"));
+        assert!(host_allowed(&allowed, "static.docs.rs"));
+        assert!(!host_allowed(&allowed, "evildocs.rs"));
+        assert!(!host_allowed(&[], "example.com"));
+    }
+
+    #[test]
+    fn test_internal_addresses_refused() {
+        for ip in ["127.0.0.1", "10.1.2.3", "192.168.0.1", "169.254.169.254", "100.64.0.1", "::1", "fe80::1", "fd00::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public(ip.parse().unwrap()), "{} should be refused", ip);
+        }
+        assert!(is_public("93.184.216.34".parse().unwrap()));
+
+        let allowed = vec!["127.0.0.1".to_string()];
+        let url = Url::parse("http://127.0.0.1/admin").unwrap();
+        assert!(check_url(&allowed, &url).is_err());
+        assert!(FetchUrl::new(&ToolsConfig { fetch_url: true, ..ToolsConfig::default() }).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! Tools the model can call while answering
+//!
+//! Tools let the agent inspect a workspace instead of only explaining
+//! concepts. Each one is off unless enabled under `tools` in the
+//! configuration, file and cargo tools cannot leave the configured
+//! workspace, and all output is cut to `max_output_bytes`. With `offline`
+//! set, tools that reach the network are never registered.
+
+pub mod fetch;
+pub mod search;
+pub mod workspace;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config::ToolsConfig;
+use crate::error::Result;
+use crate::model::{ToolCall, ToolSpec};
+
+/// Something the model can call
+#[async_trait]
+pub trait Tool: Send + Sync {
+    /// Name, description, and argument schema shown to the model
+    fn spec(&self) -> ToolSpec;
+
+    /// Run the tool, returning text for the model
+    async fn call(&self, arguments: &serde_json::Value) -> Result<ToolOutput>;
+}
+
+/// A source that tool output came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Citation {
+    pub title: String,
+    pub url: String,
+}
+
+/// What a tool returned
+#[derive(Debug, Clone, Default)]
+pub struct ToolOutput {
+    /// Text given to the model
+    pub text: String,
+
+    /// Sources the text came from, to cite alongside the answer
+    pub citations: Vec<Citation>,
+}
+
+impl From<String> for ToolOutput {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            citations: Vec::new(),
+        }
+    }
+}
+
+/// The tools enabled in the configuration
+pub struct ToolRegistry {
+    tools: BTreeMap<String, Box<dyn Tool>>,
+    max_output_bytes: usize,
+}
+
+impl ToolRegistry {
+    pub fn from_config(config: &ToolsConfig) -> Result<Self> {
+        let mut registry = Self {
+            tools: BTreeMap::new(),
+            max_output_bytes: config.max_output_bytes,
+        };
+
+        if config.read_file {
+            registry.register(workspace::ReadFile::new(config));
+        }
+        if config.list_directory {
+            registry.register(workspace::ListDirectory::new(config));
+        }
+        if config.cargo_check {
+            registry.register(workspace::CargoCheck::new(config));
+        }
+        if config.fetch_url && !config.offline {
+            registry.register(fetch::FetchUrl::new(config)?);
+        }
+        if let Some(web_search) = config.web_search.as_ref().filter(|_| !config.offline) {
+            registry.register(search::WebSearch::new(web_search, config.timeout));
+        }
+
+        Ok(registry)
+    }
+
+    pub fn register(&mut self, tool: impl Tool + 'static) {
+        self.tools.insert(tool.spec().name, Box::new(tool));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    pub fn specs(&self) -> Vec<ToolSpec> {
+        self.tools.values().map(|tool| tool.spec()).collect()
+    }
+
+    /// Run a call from the model
+    ///
+    /// Failures are reported back to the model as text so it can recover.
+    pub async fn call(&self, call: &ToolCall) -> ToolOutput {
+        let mut output = match self.tools.get(&call.name) {
+            Some(tool) => match tool.call(&call.arguments).await {
+                Ok(output) => output,
+                Err(e) => format!("Error: {}", e).into(),
+            },
+            None => format!("Error: no tool named {}", call.name).into(),
+        };
+
+        output.text = truncate(output.text, self.max_output_bytes);
+        output
+    }
+}
+
+/// Cut `output` to at most `max_bytes`, noting that it was cut
+pub(crate) fn truncate(mut output: String, max_bytes: usize) -> String {
+    if output.len() <= max_bytes {
+        return output;
+    }
+
+    let mut end = max_bytes;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    output.truncate(end);
+    output.push_str("\n[output truncated]");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_enabled_tools_are_registered() {
+        let config = ToolsConfig {
+            read_file: true,
+            cargo_check: true,
+            ..ToolsConfig::default()
+        };
+
+        let names: Vec<String> = ToolRegistry::from_config(&config)
+            .unwrap()
+            .specs()
+            .into_iter()
+            .map(|spec| spec.name)
+            .collect();
+        assert_eq!(names, vec!["cargo_check", "read_file"]);
+        assert!(ToolRegistry::from_config(&ToolsConfig::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_offline_drops_network_tools() {
+        let config = ToolsConfig {
+            read_file: true,
+            fetch_url: true,
+            offline: true,
+            ..ToolsConfig::default()
+        };
+
+        let names: Vec<String> = ToolRegistry::from_config(&config)
+            .unwrap()
+            .specs()
+            .into_iter()
+            .map(|spec| spec.name)
+            .collect();
+        assert_eq!(names, vec!["read_file"]);
+    }
+
+    #[test]
+    fn test_truncate_on_char_boundary() {
+        assert_eq!(truncate("short".to_string(), 10), "short");
+        assert_eq!(truncate("héllo".to_string(), 2), "h\n[output truncated]");
+    }
+}
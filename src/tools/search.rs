@@ -0,0 +1,191 @@
+//! Web search, for questions the knowledge base cannot answer
+
+use async_trait::async_trait;
+use std::time::Duration;
+
+use super::{Citation, Tool, ToolOutput};
+use crate::config::{SearchEngine, WebSearchConfig};
+use crate::error::{AgentError, Result};
+use crate::model::ToolSpec;
+
+/// Query a search service and return its top results
+pub struct WebSearch {
+    http: reqwest::Client,
+    engine: SearchEngine,
+    max_results: usize,
+    timeout: Duration,
+}
+
+/// One search result
+#[derive(Debug, Clone, PartialEq)]
+struct SearchResult {
+    title: String,
+    url: String,
+    snippet: String,
+}
+
+impl WebSearch {
+    pub fn new(config: &WebSearchConfig, timeout: Duration) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            engine: config.engine.clone(),
+            max_results: config.max_results,
+            timeout,
+        }
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let request = match &self.engine {
+            SearchEngine::Searxng { url } => self
+                .http
+                .get(format!("{}/search", url.trim_end_matches('/')))
+                .query(&[("q", query), ("format", "json")]),
+            SearchEngine::Api { url, api_key, query_param, .. } => {
+                let request = self.http.get(url).query(&[(query_param.as_str(), query)]);
+                match api_key {
+                    Some(api_key) => request.bearer_auth(api_key),
+                    None => request,
+                }
+            }
+        };
+
+        let response = request.timeout(self.timeout).send().await?;
+        if !response.status().is_success() {
+            return Err(AgentError::ServiceUnavailable(format!(
+                "Web search failed with status {}",
+                response.status()
+            )));
+        }
+
+        let body: serde_json::Value = response.json().await?;
+        Ok(parse_results(&self.engine, &body, self.max_results))
+    }
+}
+
+/// Pull results out of a search response
+fn parse_results(engine: &SearchEngine, body: &serde_json::Value, limit: usize) -> Vec<SearchResult> {
+    let (pointer, title, url, snippet) = match engine {
+        SearchEngine::Searxng { .. } => ("/results", "title", "url", "content"),
+        SearchEngine::Api {
+            results_pointer,
+            title_field,
+            url_field,
+            snippet_field,
+            ..
+        } => (
+            results_pointer.as_str(),
+            title_field.as_str(),
+            url_field.as_str(),
+            snippet_field.as_str(),
+        ),
+    };
+
+    body.pointer(pointer)
+        .and_then(|results| results.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|result| {
+            Some(SearchResult {
+                url: result[url].as_str()?.to_string(),
+                title: result[title].as_str().unwrap_or_default().to_string(),
+                snippet: result[snippet].as_str().unwrap_or_default().to_string(),
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+#[async_trait]
+impl Tool for WebSearch {
+    fn spec(&self) -> ToolSpec {
+        ToolSpec {
+            name: "web_search".to_string(),
+            description: "Search the web for information outside the CIM knowledge base. \
+                Mention the sources you use."
+                .to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                },
+                "required": ["query"],
+            }),
+        }
+    }
+
+    async fn call(&self, arguments: &serde_json::Value) -> Result<ToolOutput> {
+        let query = arguments["query"]
+            .as_str()
+            .ok_or_else(|| AgentError::InvalidRequest("Missing query argument".to_string()))?;
+
+        let results = self.search(query).await?;
+        if results.is_empty() {
+            return Ok(format!("No results for {}", query).into());
+        }
+
+        let text = results
+            .iter()
+            .enumerate()
+            .map(|(index, result)| format!("[{}] {}\n{}\n{}", index + 1, result.title, result.url, result.snippet))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        Ok(ToolOutput {
+            text,
+            citations: results
+                .into_iter()
+                .map(|result| Citation {
+                    title: result.title,
+                    url: result.url,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_searxng_results() {
+        let body = serde_json::json!({
+            "results": [
+                { "title": "NATS JetStream", "url": "https://docs.nats.io/jetstream", "content": "Persistence for NATS" },
+                { "title": "No URL" },
+                { "title": "Key/Value", "url": "https://docs.nats.io/kv", "content": "" },
+            ],
+        });
+        let engine = SearchEngine::Searxng { url: "http://searxng:8080".to_string() };
+
+        let results = parse_results(&engine, &body, 5);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url, "https://docs.nats.io/jetstream");
+        assert_eq!(parse_results(&engine, &body, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_api_results_with_custom_fields() {
+        let body = serde_json::json!({
+            "web": { "items": [{ "name": "CQRS", "link": "https://example.com/cqrs", "summary": "Split reads" }] },
+        });
+        let engine = SearchEngine::Api {
+            url: "https://search.example.com".to_string(),
+            api_key: None,
+            query_param: "q".to_string(),
+            results_pointer: "/web/items".to_string(),
+            title_field: "name".to_string(),
+            url_field: "link".to_string(),
+            snippet_field: "summary".to_string(),
+        };
+
+        assert_eq!(
+            parse_results(&engine, &body, 5),
+            vec![SearchResult {
+                title: "CQRS".to_string(),
+                url: "https://example.com/cqrs".to_string(),
+                snippet: "Split reads".to_string(),
+            }]
+        );
+    }
+}
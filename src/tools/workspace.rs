@@ -0,0 +1,204 @@
+//! Tools that inspect the configured workspace
+
+use async_trait::async_trait;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+use super::{Tool, ToolOutput};
+use crate::config::ToolsConfig;
+use crate::error::{AgentError, Result};
+use crate::model::ToolSpec;
+
+/// Resolve `relative` inside `root`, refusing anything that escapes it
+///
+/// Symlinks are followed before checking, so a link out of the workspace
+/// is refused too.
+fn resolve(root: &Path, relative: &str) -> Result<PathBuf> {
+    let relative = Path::new(relative);
+    if relative
+        .components()
+        .any(|component| matches!(component, Component::RootDir | Component::Prefix(_)))
+    {
+        return Err(AgentError::PermissionDenied(format!(
+            "{} is not relative to the workspace",
+            relative.display()
+        )));
+    }
+
+    let root = root.canonicalize()?;
+    let path = root.join(relative).canonicalize()?;
+    if !path.starts_with(&root) {
+        return Err(AgentError::PermissionDenied(format!(
+            "{} is outside the workspace",
+            relative.display()
+        )));
+    }
+
+    Ok(path)
+}
+
+fn path_argument(arguments: &serde_json::Value) -> &str {
+    arguments["path"].as_str().unwrap_or(".")
+}
+
+/// Read a text file
+pub struct ReadFile {
+    root: PathBuf,
+}
+
+impl ReadFile {
+    pub fn new(config: &ToolsConfig) -> Self {
+        Self {
+            root: config.workspace.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for ReadFile {
+    fn spec(&self) -> ToolSpec {
+        ToolSpec {
+            name: "read_file".to_string(),
+            description: "Read a UTF-8 text file in the workspace".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Path relative to the workspace root" },
+                },
+                "required": ["path"],
+            }),
+        }
+    }
+
+    async fn call(&self, arguments: &serde_json::Value) -> Result<ToolOutput> {
+        let path = arguments["path"]
+            .as_str()
+            .ok_or_else(|| AgentError::InvalidRequest("Missing path argument".to_string()))?;
+
+        let path = resolve(&self.root, path)?;
+        Ok(tokio::fs::read_to_string(path).await?.into())
+    }
+}
+
+/// List a directory's entries
+pub struct ListDirectory {
+    root: PathBuf,
+}
+
+impl ListDirectory {
+    pub fn new(config: &ToolsConfig) -> Self {
+        Self {
+            root: config.workspace.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for ListDirectory {
+    fn spec(&self) -> ToolSpec {
+        ToolSpec {
+            name: "list_directory".to_string(),
+            description: "List the files and directories in a workspace directory".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Directory relative to the workspace root; defaults to the root" },
+                },
+            }),
+        }
+    }
+
+    async fn call(&self, arguments: &serde_json::Value) -> Result<ToolOutput> {
+        let path = resolve(&self.root, path_argument(arguments))?;
+
+        let mut entries = Vec::new();
+        let mut directory = tokio::fs::read_dir(path).await?;
+        while let Some(entry) = directory.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type().await?.is_dir() {
+                entries.push(format!("{}/", name));
+            } else {
+                entries.push(name);
+            }
+        }
+
+        entries.sort();
+        Ok(entries.join("\n").into())
+    }
+}
+
+/// Run `cargo check` and report its diagnostics
+pub struct CargoCheck {
+    root: PathBuf,
+    timeout: Duration,
+}
+
+impl CargoCheck {
+    pub fn new(config: &ToolsConfig) -> Self {
+        Self {
+            root: config.workspace.clone(),
+            timeout: config.timeout,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for CargoCheck {
+    fn spec(&self) -> ToolSpec {
+        ToolSpec {
+            name: "cargo_check".to_string(),
+            description: "Run `cargo check` in the workspace and return the compiler diagnostics".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "package": { "type": "string", "description": "Only check this package" },
+                },
+            }),
+        }
+    }
+
+    async fn call(&self, arguments: &serde_json::Value) -> Result<ToolOutput> {
+        let mut command = Command::new("cargo");
+        command
+            .args(["check", "--message-format=short", "--color=never"])
+            .current_dir(&self.root)
+            .kill_on_drop(true);
+
+        if let Some(package) = arguments["package"].as_str() {
+            // Package names only; nothing that could pass as another flag
+            if package.is_empty() || !package.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(AgentError::InvalidRequest(format!("Invalid package name {}", package)));
+            }
+            command.args(["--package", package]);
+        }
+
+        let output = tokio::time::timeout(self.timeout, command.output())
+            .await
+            .map_err(|_| AgentError::Timeout("cargo check".to_string()))??;
+
+        // Diagnostics go to stderr
+        let diagnostics = String::from_utf8_lossy(&output.stderr);
+        let status = if output.status.success() { "succeeded" } else { "failed" };
+        Ok(format!("cargo check {}\n\n{}", status, diagnostics.trim()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_stays_in_workspace() {
+        let root = std::env::temp_dir().join(format!("alchemist-tools-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+
+        assert!(resolve(&root, "src/lib.rs").is_ok());
+        assert!(resolve(&root, "src/../src/lib.rs").is_ok());
+        assert!(matches!(resolve(&root, "/etc/passwd"), Err(AgentError::PermissionDenied(_))));
+        assert!(matches!(resolve(&root, ".."), Err(AgentError::PermissionDenied(_))));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,301 @@
+//! The agent's real messaging topology, for `visualize_architecture`
+//!
+//! The subjects come from the configuration: what the agent subscribes to
+//! and publishes on, under every served version. JetStream is asked for its
+//! streams and their consumers, and each stream is linked to the subjects it
+//! captures. When JetStream cannot be reached the graph still shows the
+//! subjects, with the reason under `jetstream_error`.
+
+use futures::StreamExt;
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::config::{AccessLogSink, AgentConfig, ArtifactBackend, StorageBackend};
+use crate::error::{AgentError, Result};
+use crate::nats_integration::{served_subjects, subjects};
+use crate::peers;
+
+/// How long JetStream gets to describe itself
+const INTROSPECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Node of the agent itself
+const AGENT_NODE: &str = "agent";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Node {
+    pub id: String,
+    pub label: String,
+
+    /// `agent`, `subject`, `stream`, `consumer`, or `bucket`
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    pub details: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    pub label: String,
+}
+
+/// A JetStream stream and the consumers reading it
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamTopology {
+    pub name: String,
+    pub subjects: Vec<String>,
+    pub messages: u64,
+
+    /// Consumer names and their filter subjects
+    pub consumers: Vec<(String, Option<String>)>,
+}
+
+/// Nodes and edges of the messaging graph
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Topology {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jetstream_error: Option<String>,
+}
+
+impl Topology {
+    fn node(&mut self, id: String, label: String, kind: &str, details: serde_json::Value) {
+        if !self.nodes.iter().any(|node| node.id == id) {
+            self.nodes.push(Node {
+                id,
+                label,
+                kind: kind.to_string(),
+                details,
+            });
+        }
+    }
+
+    fn subject(&mut self, subject: &str) -> String {
+        let id = format!("subject:{}", subject);
+        self.node(id.clone(), subject.to_string(), "subject", serde_json::Value::Null);
+        id
+    }
+
+    fn edge(&mut self, source: &str, target: &str, label: &str) {
+        self.edges.push(Edge {
+            source: source.to_string(),
+            target: target.to_string(),
+            label: label.to_string(),
+        });
+    }
+
+    /// The agent subscribing to `subject`
+    fn subscribes(&mut self, subject: &str) {
+        let id = self.subject(subject);
+        self.edge(&id, AGENT_NODE, "subscribes");
+    }
+
+    /// The agent publishing on `subject`
+    fn publishes(&mut self, subject: &str) {
+        let id = self.subject(subject);
+        self.edge(AGENT_NODE, &id, "publishes");
+    }
+
+    /// Subjects and buckets the configuration gives the agent
+    pub fn configured(config: &AgentConfig) -> Self {
+        let mut topology = Self::default();
+        topology.node(
+            AGENT_NODE.to_string(),
+            config.identity.name.clone(),
+            "agent",
+            serde_json::json!({ "agent_id": config.identity.agent_id }),
+        );
+
+        let nats = &config.nats;
+        let served = |suffix: &str| served_subjects(&nats.subject_prefix, &nats.versions, nats.serve_unversioned, suffix);
+
+        let mut subscribed = vec!["commands.>", "queries.>", "health"];
+        if config.service.metrics.enabled {
+            subscribed.push("metrics");
+        }
+        for subject in subscribed.into_iter().flat_map(&served) {
+            topology.subscribes(&subject);
+        }
+        for subject in served("events.>") {
+            topology.publishes(&subject);
+        }
+
+        topology.subscribes(subjects::DIALOG);
+        topology.publishes("cim.dialog.*.response");
+        topology.publishes("cim.dialog.*.chunk");
+
+        // Service discovery, answered for every service and for this one
+        for verb in ["PING", "INFO", "STATS"] {
+            topology.subscribes(&format!("$SRV.{}", verb));
+            topology.subscribes(&format!("$SRV.{}.{}", verb, crate::NAME));
+        }
+
+        if let Some(AccessLogSink::Subject { subject }) = config.service.logging.access.as_ref().map(|access| &access.sink) {
+            topology.publishes(subject);
+        }
+
+        if config.peers.enabled {
+            topology.subscribes(peers::CAPABILITIES);
+            topology.publishes(&peers::capabilities_subject(&config.identity.agent_id));
+            topology.subscribes(&peers::delegate_subject(&config.identity.agent_id));
+        }
+
+        if let StorageBackend::JetStream { bucket } = &config.storage.backend {
+            let id = format!("bucket:{}", bucket);
+            topology.node(id.clone(), bucket.clone(), "bucket", serde_json::json!({ "kind": "key_value" }));
+            topology.edge(AGENT_NODE, &id, "stores state");
+        }
+        if let Some(ArtifactBackend::ObjectStore { bucket }) = &config.storage.artifacts {
+            let id = format!("bucket:{}", bucket);
+            topology.node(id.clone(), bucket.clone(), "bucket", serde_json::json!({ "kind": "object_store" }));
+            topology.edge(AGENT_NODE, &id, "stores artifacts");
+        }
+
+        topology
+    }
+
+    /// Add `streams`, linked to the subjects already in the graph that they
+    /// capture and to their consumers
+    pub fn add_streams(&mut self, streams: &[StreamTopology]) {
+        let subjects: Vec<String> = self
+            .nodes
+            .iter()
+            .filter(|node| node.kind == "subject")
+            .map(|node| node.label.clone())
+            .collect();
+
+        for stream in streams {
+            let stream_id = format!("stream:{}", stream.name);
+            self.node(
+                stream_id.clone(),
+                stream.name.clone(),
+                "stream",
+                serde_json::json!({ "subjects": stream.subjects, "messages": stream.messages }),
+            );
+
+            for subject in &subjects {
+                if stream.subjects.iter().any(|pattern| captures(pattern, subject)) {
+                    self.edge(&format!("subject:{}", subject), &stream_id, "captured by");
+                }
+            }
+
+            for (consumer, filter) in &stream.consumers {
+                let consumer_id = format!("consumer:{}/{}", stream.name, consumer);
+                self.node(
+                    consumer_id.clone(),
+                    consumer.clone(),
+                    "consumer",
+                    serde_json::json!({ "filter_subject": filter }),
+                );
+                self.edge(&stream_id, &consumer_id, "consumed by");
+            }
+        }
+    }
+}
+
+/// Whether a stream capturing `pattern` receives the messages of `subject`,
+/// which may hold wildcards itself
+pub fn captures(pattern: &str, subject: &str) -> bool {
+    let mut patterns = pattern.split('.');
+    let mut tokens = subject.split('.');
+    loop {
+        match (patterns.next(), tokens.next()) {
+            (Some(">"), Some(_)) => return true,
+            (Some("*"), Some(token)) if token != ">" => {}
+            (Some(expected), Some(token)) if expected == token => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+fn introspection_error(error: impl std::fmt::Display) -> AgentError {
+    AgentError::ServiceUnavailable(format!("JetStream introspection failed: {}", error))
+}
+
+/// Every JetStream stream on the configured servers, with its consumers
+pub async fn jetstream_streams(config: &crate::config::NatsConfig) -> Result<Vec<StreamTopology>> {
+    let introspect = async {
+        let client = crate::nats_integration::connect(config).await?;
+        let jetstream = async_nats::jetstream::new(client);
+
+        let mut infos = Vec::new();
+        let mut streams = jetstream.streams();
+        while let Some(info) = streams.next().await {
+            infos.push(info.map_err(|e| introspection_error(e))?);
+        }
+
+        let mut topology = Vec::new();
+        for info in infos {
+            let stream = jetstream
+                .get_stream(&info.config.name)
+                .await
+                .map_err(|e| introspection_error(e))?;
+
+            let mut consumers = Vec::new();
+            let mut listed = stream.consumers();
+            while let Some(consumer) = listed.next().await {
+                let consumer = consumer.map_err(|e| introspection_error(e))?;
+                let filter = Some(consumer.config.filter_subject).filter(|filter| !filter.is_empty());
+                consumers.push((consumer.name, filter));
+            }
+            consumers.sort();
+
+            topology.push(StreamTopology {
+                name: info.config.name,
+                subjects: info.config.subjects,
+                messages: info.state.messages,
+                consumers,
+            });
+        }
+
+        topology.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok::<_, AgentError>(topology)
+    };
+
+    tokio::time::timeout(INTROSPECTION_TIMEOUT, introspect)
+        .await
+        .map_err(|_| AgentError::Timeout("JetStream did not describe its streams in time".to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captures() {
+        assert!(captures("cim.agent.alchemist.>", "cim.agent.alchemist.v1.commands.>"));
+        assert!(captures("cim.agent.*.health", "cim.agent.alchemist.health"));
+        assert!(captures("cim.dialog.*.response", "cim.dialog.*.response"));
+        assert!(!captures("cim.agent.alchemist.>", "cim.agent.alchemist"));
+        assert!(!captures("cim.agent.*", "cim.agent.>"));
+        assert!(!captures("cim.dialog.alchemist.>", "cim.dialog.*.response"));
+    }
+
+    #[test]
+    fn test_streams_link_to_subjects_and_consumers() {
+        let mut topology = Topology::default();
+        topology.node(AGENT_NODE.to_string(), "Alchemist".to_string(), "agent", serde_json::Value::Null);
+        topology.subscribes("cim.agent.alchemist.v1.commands.>");
+        topology.publishes("cim.dialog.*.response");
+
+        topology.add_streams(&[StreamTopology {
+            name: "ALCHEMIST_EVENTS".to_string(),
+            subjects: vec!["cim.agent.alchemist.>".to_string()],
+            messages: 42,
+            consumers: vec![("alchemist-worker".to_string(), None)],
+        }]);
+
+        assert!(topology.edges.contains(&Edge {
+            source: "subject:cim.agent.alchemist.v1.commands.>".to_string(),
+            target: "stream:ALCHEMIST_EVENTS".to_string(),
+            label: "captured by".to_string(),
+        }));
+        assert!(topology.edges.iter().all(|edge| edge.source != "subject:cim.dialog.*.response" || edge.label != "captured by"));
+        assert!(topology.nodes.iter().any(|node| node.id == "consumer:ALCHEMIST_EVENTS/alchemist-worker"));
+    }
+}
@@ -0,0 +1,328 @@
+//! Usage per origin, for attributing load and model cost
+//!
+//! Commands and queries count under the `origin` they arrive with, HTTP
+//! requests under the authenticated caller, and dialog messages under
+//! `metadata.origin`, `metadata.source`, or `metadata.user`, whichever comes
+//! first. Tokens are those the model's API reports using, for providers whose
+//! API does, and otherwise estimated like token budgets, from the text sent
+//! to and received from the model while the request was handled. Counts are
+//! kept in hourly buckets for `usage.retention`.
+
+use async_trait::async_trait;
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::cell::Cell;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::budget::estimate_tokens;
+use crate::config::UsageConfig;
+use crate::error::Result;
+use crate::model::{ChunkStream, Message, ModelInfo, ModelProvider, ModelTurn, ToolExchange, ToolSpec};
+
+tokio::task_local! {
+    /// Tokens used by the request the current task is handling
+    static MODEL_TOKENS: AtomicUsize;
+
+    /// Tokens the model's API reported for the call in progress, if it did
+    static REPORTED_TOKENS: Cell<Option<usize>>;
+}
+
+/// Run `future`, returning its output and the model tokens it used
+pub async fn metered<F: Future>(future: F) -> (F::Output, usize) {
+    let metering = async {
+        let output = future.await;
+        (output, MODEL_TOKENS.with(|tokens| tokens.load(Ordering::Relaxed)))
+    };
+    MODEL_TOKENS
+        .scope(AtomicUsize::new(0), REPORTED_TOKENS.scope(Cell::new(None), metering))
+        .await
+}
+
+/// Record tokens the model's API reported using, for providers whose API
+/// does; they count for the call in progress in place of its estimate
+pub fn report(tokens: usize) {
+    let _ = REPORTED_TOKENS.try_with(|reported| reported.set(Some(reported.get().unwrap_or(0) + tokens)));
+}
+
+/// Count tokens against the request being handled, if any is
+fn count(tokens: usize) {
+    let _ = MODEL_TOKENS.try_with(|used| used.fetch_add(tokens, Ordering::Relaxed));
+}
+
+/// Count a finished model call: the tokens its API reported, or else the
+/// `estimated` ones
+fn count_call(estimated: usize) {
+    let reported = REPORTED_TOKENS.try_with(Cell::take).ok().flatten();
+    count(reported.unwrap_or(estimated));
+}
+
+/// Counts a streamed call once its stream is dropped, when the API has
+/// reported its usage if it ever does
+struct StreamedCall {
+    estimated: usize,
+}
+
+impl Drop for StreamedCall {
+    fn drop(&mut self) {
+        count_call(self.estimated);
+    }
+}
+
+fn context_tokens(prompt: &str, context: &[Message]) -> usize {
+    estimate_tokens(prompt) + context.iter().map(|message| estimate_tokens(&message.content)).sum::<usize>()
+}
+
+/// A model provider counting the tokens of each call against the request
+/// being handled
+pub struct Metered(Box<dyn ModelProvider>);
+
+impl Metered {
+    pub fn new(provider: Box<dyn ModelProvider>) -> Self {
+        Self(provider)
+    }
+}
+
+#[async_trait]
+impl ModelProvider for Metered {
+    async fn generate(&self, prompt: &str) -> Result<String> {
+        let response = self.0.generate(prompt).await?;
+        count_call(estimate_tokens(prompt) + estimate_tokens(&response));
+        Ok(response)
+    }
+
+    async fn generate_with_context(&self, prompt: &str, context: &[Message]) -> Result<String> {
+        let response = self.0.generate_with_context(prompt, context).await?;
+        count_call(context_tokens(prompt, context) + estimate_tokens(&response));
+        Ok(response)
+    }
+
+    async fn generate_stream(&self, prompt: &str, context: &[Message]) -> Result<ChunkStream> {
+        let stream = self.0.generate_stream(prompt, context).await?;
+        let mut call = StreamedCall {
+            estimated: context_tokens(prompt, context),
+        };
+
+        // Chunks are estimated as the request's task reads them, and the
+        // call counted when it drops the stream
+        Ok(Box::pin(stream.inspect(move |chunk| {
+            if let Ok(chunk) = chunk {
+                call.estimated += estimate_tokens(&chunk.content);
+            }
+        })))
+    }
+
+    async fn generate_with_tools(
+        &self,
+        prompt: &str,
+        context: &[Message],
+        tools: &[ToolSpec],
+        exchanges: &[ToolExchange],
+    ) -> Result<ModelTurn> {
+        let turn = self.0.generate_with_tools(prompt, context, tools, exchanges).await?;
+        let exchanged: usize = exchanges
+            .iter()
+            .map(|exchange| estimate_tokens(&exchange.call.arguments.to_string()) + estimate_tokens(&exchange.output))
+            .sum();
+        let produced = match &turn {
+            ModelTurn::Text(text) => estimate_tokens(text),
+            ModelTurn::ToolCalls(calls) => calls
+                .iter()
+                .map(|call| estimate_tokens(&call.name) + estimate_tokens(&call.arguments.to_string()))
+                .sum(),
+        };
+        count_call(context_tokens(prompt, context) + exchanged + produced);
+        Ok(turn)
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let embedding = self.0.embed(text).await?;
+        count_call(estimate_tokens(text));
+        Ok(embedding)
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.0.health_check().await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        self.0.list_models().await
+    }
+
+    fn model_info(&self) -> ModelInfo {
+        self.0.model_info()
+    }
+}
+
+/// What was handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageKind {
+    Command,
+    Query,
+    DialogMessage,
+}
+
+/// Requests handled, how many failed, and the tokens they used
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageCounts {
+    pub requests: u64,
+    pub errors: u64,
+    pub tokens: u64,
+}
+
+impl UsageCounts {
+    fn add(&mut self, other: &UsageCounts) {
+        self.requests += other.requests;
+        self.errors += other.errors;
+        self.tokens += other.tokens;
+    }
+}
+
+/// Usage by one origin over a report's time range
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OriginUsage {
+    pub origin: String,
+    pub commands: u64,
+    pub queries: u64,
+    pub dialog_messages: u64,
+    pub errors: u64,
+    pub tokens: u64,
+
+    /// Counts by command or query type, with dialog messages as `dialog_message`
+    pub by_type: BTreeMap<String, UsageCounts>,
+}
+
+/// Hour, origin, kind, and command or query type
+type BucketKey = (DateTime<Utc>, String, UsageKind, String);
+
+/// Hourly usage counts
+pub struct UsageLog {
+    retention: TimeDelta,
+    buckets: Mutex<BTreeMap<BucketKey, UsageCounts>>,
+}
+
+impl UsageLog {
+    pub fn new(config: &UsageConfig) -> Self {
+        Self {
+            retention: TimeDelta::from_std(config.retention).unwrap_or(TimeDelta::MAX),
+            buckets: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Count one request
+    pub fn record(&self, origin: &str, kind: UsageKind, name: &str, tokens: usize, succeeded: bool) {
+        self.record_at(Utc::now(), origin, kind, name, tokens, succeeded);
+    }
+
+    fn record_at(&self, at: DateTime<Utc>, origin: &str, kind: UsageKind, name: &str, tokens: usize, succeeded: bool) {
+        let mut buckets = self.buckets.lock().unwrap();
+        let counts = buckets
+            .entry((hour(at), origin.to_string(), kind, name.to_string()))
+            .or_default();
+        counts.requests += 1;
+        counts.errors += u64::from(!succeeded);
+        counts.tokens += tokens as u64;
+
+        // Keys sort by hour first, so everything expired comes before the cutoff
+        if let Some(cutoff) = at.checked_sub_signed(self.retention) {
+            *buckets = buckets.split_off(&(hour(cutoff), String::new(), UsageKind::Command, String::new()));
+        }
+    }
+
+    /// Usage per origin in the hours overlapping `from` to `to`, busiest first
+    pub fn report(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>, origin: Option<&str>) -> Vec<OriginUsage> {
+        let from = from.map(hour);
+        let mut origins: BTreeMap<String, OriginUsage> = BTreeMap::new();
+        for ((bucket, bucket_origin, kind, name), counts) in self.buckets.lock().unwrap().iter() {
+            let in_range = from.is_none_or(|from| *bucket >= from) && to.is_none_or(|to| *bucket < to);
+            if !in_range || origin.is_some_and(|origin| origin != bucket_origin) {
+                continue;
+            }
+
+            let usage = origins.entry(bucket_origin.clone()).or_insert_with(|| OriginUsage {
+                origin: bucket_origin.clone(),
+                ..OriginUsage::default()
+            });
+            match kind {
+                UsageKind::Command => usage.commands += counts.requests,
+                UsageKind::Query => usage.queries += counts.requests,
+                UsageKind::DialogMessage => usage.dialog_messages += counts.requests,
+            }
+            usage.errors += counts.errors;
+            usage.tokens += counts.tokens;
+            usage.by_type.entry(name.clone()).or_default().add(counts);
+        }
+
+        let mut report: Vec<OriginUsage> = origins.into_values().collect();
+        report.sort_by(|a, b| b.tokens.cmp(&a.tokens));
+        report
+    }
+}
+
+/// Start of the hour `at` falls in
+fn hour(at: DateTime<Utc>) -> DateTime<Utc> {
+    at.duration_trunc(TimeDelta::hours(1)).unwrap_or(at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_metered_counts_only_its_own_calls() {
+        let ((), tokens) = metered(async {
+            count(10);
+            count(5);
+        })
+        .await;
+        assert_eq!(tokens, 15);
+
+        // Outside a metered request nothing is counted, and nothing fails
+        count(3);
+    }
+
+    #[tokio::test]
+    async fn test_reported_tokens_replace_estimates() {
+        let ((), tokens) = metered(async {
+            report(100);
+            report(20);
+            count_call(7);
+            // The report went with the call before
+            count_call(7);
+        })
+        .await;
+        assert_eq!(tokens, 127);
+
+        report(3);
+    }
+
+    #[test]
+    fn test_report_filters_and_expires() {
+        let log = UsageLog::new(&UsageConfig {
+            retention: std::time::Duration::from_secs(2 * 86400),
+        });
+        let day = |d| Utc.with_ymd_and_hms(2025, 3, d, 9, 30, 0).unwrap();
+
+        log.record_at(day(1), "billing", UsageKind::Command, "explain_concept", 400, true);
+        log.record_at(day(2), "billing", UsageKind::Command, "explain_concept", 300, true);
+        log.record_at(day(2), "billing", UsageKind::Query, "list_concepts", 0, false);
+        log.record_at(day(3), "ci", UsageKind::Command, "analyze_pattern", 900, true);
+
+        let all = log.report(None, None, None);
+        assert_eq!(all.iter().map(|usage| usage.origin.as_str()).collect::<Vec<_>>(), vec!["ci", "billing"]);
+        assert_eq!((all[1].commands, all[1].queries, all[1].errors, all[1].tokens), (2, 1, 1, 700));
+        assert_eq!(all[1].by_type["explain_concept"].requests, 2);
+
+        let billing = log.report(Some(day(2)), Some(day(3)), Some("billing"));
+        assert_eq!(billing[0].tokens, 300);
+
+        // Two days after the first request, it has expired
+        log.record_at(day(3) + TimeDelta::hours(1), "ci", UsageKind::DialogMessage, "dialog_message", 50, true);
+        assert_eq!(log.report(None, None, Some("billing"))[0].commands, 1);
+    }
+}
@@ -1,21 +1,23 @@
 //! Integration tests for the Alchemist agent
 //!
-//! These tests require a running NATS server.
+//! Each test runs its own `nats-server` subprocess through the `testing`
+//! feature, so `nats-server` must be installed or named by `NATS_SERVER`.
 //!
 //! ```mermaid
 //! graph TD
 //!     A[Test Client] --> B[NATS Server]
 //!     B --> C[Alchemist Agent]
-//!     C --> D[Mock Model Provider]
+//!     C --> D[Scripted Model Provider]
 //!     C --> B
 //!     B --> A
 //! ```
 
 use cim_agent_alchemist::{
-    AgentConfig, ModelConfig,
+    AgentConfig,
     nats_integration::{AgentCommand, AgentQuery, DialogMessage, HealthResponse},
+    testing::{ScriptedProvider, TestAgent, TestNats},
 };
-use async_nats::Client;
+use futures::StreamExt;
 use serde_json::json;
 use std::time::Duration;
 use tokio::time::timeout;
@@ -37,27 +39,27 @@ fn test_config() -> AgentConfig {
     config
 }
 
+/// A NATS server and an agent answering with `model`
+async fn start_agent(model: ScriptedProvider) -> (TestNats, TestAgent) {
+    let nats = TestNats::start().await.expect("Failed to start nats-server");
+    let agent = TestAgent::builder()
+        .provider(model)
+        .start(&nats)
+        .await
+        .expect("Failed to start agent");
+    (nats, agent)
+}
+
 #[tokio::test]
-#[ignore = "requires NATS server"]
 async fn test_agent_health_check() {
-    // Connect to NATS
-    let client = Client::connect("nats://localhost:4222")
-        .await
-        .expect("Failed to connect to NATS");
-    
-    // Start agent service in background
-    let config = test_config();
-    let service_handle = tokio::spawn(async move {
-        cim_agent_alchemist::service::run(config).await
-    });
-    
-    // Wait for service to start
-    tokio::time::sleep(Duration::from_secs(2)).await;
+    let (nats, agent) = start_agent(ScriptedProvider::new("OK")).await;
+    let client = nats.client().await.expect("Failed to connect to NATS");
+    let prefix = &agent.config().nats.subject_prefix;
     
     // Send health check request
     let response = timeout(
         Duration::from_secs(5),
-        client.request("test.agent.alchemist.health", "".into()),
+        client.request(format!("{}.health", prefix), "".into()),
     )
     .await
     .expect("Health check timed out")
@@ -69,17 +71,58 @@ async fn test_agent_health_check() {
     
     assert_eq!(health.status, "Running");
     assert_eq!(health.version, cim_agent_alchemist::VERSION);
+    for subsystem in ["model", "storage", "nats", "jetstream"] {
+        assert!(health.subsystems.contains_key(subsystem), "missing {}", subsystem);
+    }
+}
+
+#[tokio::test]
+async fn test_service_discovery_and_stats() {
+    let (nats, agent) = start_agent(ScriptedProvider::new("OK")).await;
+    let client = nats.client().await.expect("Failed to connect to NATS");
+    let request = |subject: String| {
+        let client = client.clone();
+        async move {
+            let response = timeout(Duration::from_secs(5), client.request(subject, "".into()))
+                .await
+                .expect("Discovery request timed out")
+                .expect("Discovery request failed");
+            serde_json::from_slice::<serde_json::Value>(&response.payload).expect("Failed to parse discovery response")
+        }
+    };
     
-    // Cleanup
-    service_handle.abort();
+    let ping = request("$SRV.PING".to_string()).await;
+    assert_eq!(ping["name"], json!(cim_agent_alchemist::NAME));
+    
+    let info = request(format!("$SRV.INFO.{}", cim_agent_alchemist::NAME)).await;
+    assert_eq!(info["version"], json!(cim_agent_alchemist::VERSION));
+    let endpoints: Vec<&str> = info["endpoints"]
+        .as_array()
+        .expect("No endpoints in service info")
+        .iter()
+        .filter_map(|endpoint| endpoint["name"].as_str())
+        .collect();
+    for endpoint in ["commands", "queries", "dialog", "health"] {
+        assert!(endpoints.contains(&endpoint), "missing endpoint {}", endpoint);
+    }
+    
+    agent.client().query("list_concepts", json!({})).await.expect("Query failed");
+    let stats = request(format!("$SRV.STATS.{}", cim_agent_alchemist::NAME)).await;
+    let queries: u64 = stats["endpoints"]
+        .as_array()
+        .expect("No endpoints in service stats")
+        .iter()
+        .filter(|endpoint| endpoint["name"].as_str().is_some_and(|name| name.starts_with("queries")))
+        .filter_map(|endpoint| endpoint["num_requests"].as_u64())
+        .sum();
+    assert_eq!(queries, 1);
 }
 
 #[tokio::test]
-#[ignore = "requires NATS server"]
 async fn test_list_concepts_query() {
-    let client = Client::connect("nats://localhost:4222")
-        .await
-        .expect("Failed to connect to NATS");
+    let (nats, agent) = start_agent(ScriptedProvider::new("OK")).await;
+    let client = nats.client().await.expect("Failed to connect to NATS");
+    let prefix = &agent.config().nats.subject_prefix;
     
     // Create query
     let query = AgentQuery {
@@ -95,7 +138,7 @@ async fn test_list_concepts_query() {
     // Send query
     let response = timeout(
         Duration::from_secs(5),
-        client.request("test.agent.alchemist.queries.list_concepts", payload.into()),
+        client.request(format!("{}.queries.list_concepts", prefix), payload.into()),
     )
     .await
     .expect("Query timed out")
@@ -114,80 +157,170 @@ async fn test_list_concepts_query() {
 }
 
 #[tokio::test]
-#[ignore = "requires NATS server and Ollama"]
-async fn test_dialog_interaction() {
-    let client = Client::connect("nats://localhost:4222")
-        .await
-        .expect("Failed to connect to NATS");
+async fn test_get_capabilities_query() {
+    let (nats, agent) = start_agent(ScriptedProvider::new("OK")).await;
+    let client = nats.client().await.expect("Failed to connect to NATS");
+    let prefix = &agent.config().nats.subject_prefix;
     
-    // Start dialog
-    let start_command = AgentCommand {
-        id: "test-cmd-1".to_string(),
-        command_type: "start_dialog".to_string(),
-        payload: json!({
-            "user_id": "test-user",
-            "context": {},
-            "metadata": {}
-        }),
+    let query = AgentQuery {
+        id: "test-query-2".to_string(),
+        query_type: "get_capabilities".to_string(),
+        parameters: json!({}),
         timestamp: chrono::Utc::now(),
         origin: "test".to_string(),
     };
     
-    // Publish command and wait for event
-    let payload = serde_json::to_vec(&start_command).expect("Failed to serialize command");
-    client.publish("test.agent.alchemist.commands.start_dialog", payload.into())
+    let payload = serde_json::to_vec(&query).expect("Failed to serialize query");
+    
+    let response = timeout(
+        Duration::from_secs(5),
+        client.request(format!("{}.queries.get_capabilities", prefix), payload.into()),
+    )
+    .await
+    .expect("Query timed out")
+    .expect("Query request failed");
+    
+    let result: serde_json::Value = serde_json::from_slice(&response.payload)
+        .expect("Failed to parse response");
+    
+    assert!(result["success"].as_bool().unwrap_or(false));
+    let capabilities = &result["result"];
+    assert_eq!(capabilities["commands"]["explain_concept"]["required"], json!(["concept"]));
+    assert!(capabilities["queries"]["get_capabilities"].is_object());
+    assert!(capabilities["features"]["slack"].is_boolean());
+    assert_eq!(capabilities["model"]["model"], json!("scripted"));
+}
+
+#[tokio::test]
+async fn test_disabled_capability_is_refused() {
+    let nats = TestNats::start().await.expect("Failed to start nats-server");
+    let agent = TestAgent::builder()
+        .configure(|config| config.capabilities.guide_workflows = false)
+        .start(&nats)
         .await
-        .expect("Failed to publish command");
+        .expect("Failed to start agent");
     
-    // Subscribe to dialog responses
-    let mut sub = client.subscribe("test.agent.alchemist.events.start_dialog_completed")
+    let refused = agent
+        .client()
+        .command("guide_workflow", json!({ "workflow_type": "create_agent" }))
         .await
-        .expect("Failed to subscribe");
+        .expect_err("Disabled capability was not refused");
+    assert!(refused.to_string().contains("guide_workflows capability is disabled"));
     
-    // Wait for response event
-    let msg = timeout(Duration::from_secs(5), sub.next())
+    let capabilities = agent
+        .client()
+        .query("get_capabilities", json!({}))
         .await
-        .expect("Timed out waiting for event")
-        .expect("No event received");
+        .expect("Capabilities query failed");
+    assert_eq!(capabilities["capabilities"]["guide_workflows"], json!(false));
+    assert!(capabilities["commands"]["guide_workflow"].is_null());
+    assert!(capabilities["commands"]["explain_concept"].is_object());
+}
+
+#[tokio::test]
+async fn test_dialog_interaction() {
+    let model = ScriptedProvider::new("This is a scripted response.")
+        .reply_to("Event Sourcing", "Event Sourcing stores state changes as a sequence of events.");
+    let (_nats, agent) = start_agent(model.clone()).await;
     
-    let event: serde_json::Value = serde_json::from_slice(&msg.payload)
-        .expect("Failed to parse event");
+    // Start dialog
+    let started = agent
+        .client()
+        .command("start_dialog", json!({ "user_id": "test-user", "context": {}, "metadata": {} }))
+        .await
+        .expect("Failed to start dialog");
+    let dialog_id = started["dialog_id"].as_str().expect("No dialog_id in response");
     
-    let dialog_id = event["payload"]["dialog_id"].as_str()
-        .expect("No dialog_id in response");
+    // Send dialog message and wait for the reply
+    let response = agent
+        .client()
+        .dialog(dialog_id, "What is Event Sourcing?")
+        .await
+        .expect("Dialog message failed");
     
-    // Send dialog message
-    let dialog_msg = DialogMessage {
+    assert_eq!(response, "Event Sourcing stores state changes as a sequence of events.");
+    assert!(model.prompts().iter().any(|prompt| prompt.contains("What is Event Sourcing?")));
+}
+
+#[tokio::test]
+async fn test_streamed_replies_arrive_in_numbered_chunks() {
+    let model = ScriptedProvider::new("OK").reply_to("event sourcing", "Events are the source of truth.");
+    let (nats, _agent) = start_agent(model).await;
+    let client = nats.client().await.expect("Failed to connect to NATS");
+    let dialog_id = "streamed-dialog";
+    
+    let mut chunks = client
+        .subscribe(format!("cim.dialog.{}.chunk", dialog_id))
+        .await
+        .expect("Failed to subscribe to chunks");
+    let mut responses = client
+        .subscribe(format!("cim.dialog.{}.response", dialog_id))
+        .await
+        .expect("Failed to subscribe to responses");
+    let message = DialogMessage {
         dialog_id: dialog_id.to_string(),
         content: "What is Event Sourcing?".to_string(),
-        sender: "test-user".to_string(),
-        metadata: json!({}),
+        sender: "test".to_string(),
+        metadata: json!({ "stream": true }),
         timestamp: chrono::Utc::now(),
     };
-    
-    // Subscribe to responses first
-    let mut response_sub = client.subscribe(&format!("cim.dialog.{}.response", dialog_id))
+    client
+        .publish(
+            format!("cim.dialog.alchemist.{}", dialog_id),
+            serde_json::to_vec(&message).expect("Failed to encode message").into(),
+        )
         .await
-        .expect("Failed to subscribe to responses");
+        .expect("Failed to publish message");
     
-    // Send message
-    let payload = serde_json::to_vec(&dialog_msg).expect("Failed to serialize message");
-    client.publish("test.agent.alchemist.dialog", payload.into())
+    let response = timeout(Duration::from_secs(5), responses.next())
         .await
-        .expect("Failed to publish dialog message");
+        .expect("Reply timed out")
+        .expect("Response subscription closed");
+    let response: DialogMessage = serde_json::from_slice(&response.payload).expect("Failed to parse reply");
+    assert_eq!(response.content, "Events are the source of truth.");
+    
+    // Every chunk is published before the complete reply
+    let mut streamed = String::new();
+    let mut sequence = 0;
+    while let Ok(Some(chunk)) = timeout(Duration::from_millis(200), chunks.next()).await {
+        let chunk: DialogMessage = serde_json::from_slice(&chunk.payload).expect("Failed to parse chunk");
+        assert_eq!(chunk.dialog_id, dialog_id);
+        assert_eq!(chunk.metadata["sequence"], json!(sequence));
+        streamed.push_str(&chunk.content);
+        sequence += 1;
+    }
+    assert_eq!(sequence, 6);
+    assert_eq!(streamed, response.content);
+}
+
+#[tokio::test]
+async fn test_dialog_history_is_capped() {
+    let nats = TestNats::start().await.expect("Failed to start nats-server");
+    let agent = TestAgent::builder()
+        .provider(ScriptedProvider::new("Noted."))
+        .configure(|config| config.domains.dialog.max_history = 3)
+        .start(&nats)
+        .await
+        .expect("Failed to start agent");
     
-    // Wait for response
-    let response_msg = timeout(Duration::from_secs(10), response_sub.next())
+    let started = agent
+        .client()
+        .command("start_dialog", json!({ "user_id": "test-user", "context": {}, "metadata": {} }))
         .await
-        .expect("Timed out waiting for dialog response")
-        .expect("No response received");
+        .expect("Failed to start dialog");
+    let dialog_id = started["dialog_id"].as_str().expect("No dialog_id in response");
     
-    let response: DialogMessage = serde_json::from_slice(&response_msg.payload)
-        .expect("Failed to parse dialog response");
+    for question in ["What is CQRS?", "What is a Saga?"] {
+        agent.client().dialog(dialog_id, question).await.expect("Dialog message failed");
+    }
     
-    assert_eq!(response.sender, "alchemist");
-    assert!(!response.content.is_empty());
-    assert!(response.content.to_lowercase().contains("event"));
+    let history = agent
+        .client()
+        .query("get_dialog_history", json!({ "dialog_id": dialog_id }))
+        .await
+        .expect("History query failed");
+    assert_eq!(history["turn_count"], json!(3));
+    assert_eq!(history["history"][1]["content"], json!("What is a Saga?"));
 }
 
 #[tokio::test]
@@ -209,66 +342,231 @@ async fn test_command_validation() {
         payload: json!({}),
         timestamp: chrono::Utc::now(),
         origin: "test".to_string(),
+        signature: None,
+        idempotency_key: None,
+        priority: None,
     };
     
     // In a real test with NATS running, we'd verify this returns an error event
 }
 
-/// Mock model provider for testing without Ollama
-#[cfg(test)]
-mod mock {
-    use cim_agent_alchemist::model::{
-        ModelProvider, ModelRequest, ModelResponse, ModelInfo, ModelCapabilities, TokenUsage,
-    };
-    use async_trait::async_trait;
-    use std::time::Duration;
-    
-    pub struct MockModelProvider;
-    
-    #[async_trait]
-    impl ModelProvider for MockModelProvider {
-        async fn generate(
-            &self,
-            request: ModelRequest,
-        ) -> cim_agent_alchemist::error::Result<ModelResponse> {
-            // Simple mock responses based on prompt content
-            let content = if request.prompt.contains("Event Sourcing") {
-                "Event Sourcing is a pattern where state changes are stored as a sequence of events."
-            } else if request.prompt.contains("explain") {
-                "This is a mock explanation of the requested concept."
-            } else {
-                "This is a mock response from the test model provider."
-            };
-            
-            Ok(ModelResponse {
-                content: content.to_string(),
-                usage: TokenUsage {
-                    prompt_tokens: 10,
-                    completion_tokens: 20,
-                    total_tokens: 30,
-                },
-                metadata: serde_json::json!({"mock": true}),
-                duration: Duration::from_millis(100),
-            })
-        }
-        
-        async fn health_check(&self) -> cim_agent_alchemist::error::Result<()> {
-            Ok(())
-        }
-        
-        fn model_info(&self) -> ModelInfo {
-            ModelInfo {
-                provider: "Mock".to_string(),
-                model: "mock-model".to_string(),
-                version: Some("1.0".to_string()),
-                capabilities: ModelCapabilities {
-                    max_context_length: 4096,
-                    streaming: false,
-                    function_calling: false,
-                    vision: false,
-                    embeddings: false,
-                },
-            }
-        }
+#[tokio::test]
+async fn test_workflow_steps_advance_and_abort() {
+    let (_nats, agent) = start_agent(ScriptedProvider::new("OK")).await;
+    
+    let started = agent
+        .client()
+        .command("guide_workflow", json!({ "workflow_type": "add_event" }))
+        .await
+        .expect("Failed to start workflow");
+    let workflow_id = started["workflow_id"].as_str().expect("No workflow_id in response");
+    
+    let wrong = agent
+        .client()
+        .command("complete_step", json!({ "workflow_id": workflow_id, "step": "test" }))
+        .await
+        .expect_err("Completing an inactive step was accepted");
+    assert!(wrong.to_string().contains("not the active step"));
+    
+    let completed = agent
+        .client()
+        .command("complete_step", json!({ "workflow_id": workflow_id, "step": "define", "output": { "event": "OrderPlaced" } }))
+        .await
+        .expect("Failed to complete step");
+    assert_eq!(completed["current_step"], json!("handler"));
+    assert_eq!(completed["instructions"], json!("Create event handler"));
+    
+    let skipped = agent
+        .client()
+        .command("advance_workflow", json!({ "workflow_id": workflow_id, "to": "integrate" }))
+        .await
+        .expect_err("Skipping a step was accepted");
+    assert!(skipped.to_string().contains("next steps: test"));
+    
+    let aborted = agent
+        .client()
+        .command("abort_workflow", json!({ "workflow_id": workflow_id, "reason": "Superseded" }))
+        .await
+        .expect("Failed to abort workflow");
+    assert_eq!(aborted["aborted_step"], json!("handler"));
+    agent
+        .client()
+        .command("advance_workflow", json!({ "workflow_id": workflow_id }))
+        .await
+        .expect_err("An aborted workflow advanced");
+}
+
+#[tokio::test]
+async fn test_workflows_beyond_the_limit_are_refused() {
+    let nats = TestNats::start().await.expect("Failed to start nats-server");
+    let agent = TestAgent::builder()
+        .configure(|config| config.domains.workflow.max_concurrent = 1)
+        .start(&nats)
+        .await
+        .expect("Failed to start agent");
+    
+    let started = agent
+        .client()
+        .command("guide_workflow", json!({ "workflow_type": "add_event" }))
+        .await
+        .expect("Failed to start workflow");
+    let workflow_id = started["workflow_id"].as_str().expect("No workflow_id in response");
+    
+    let refused = agent
+        .client()
+        .command("guide_workflow", json!({ "workflow_type": "add_event" }))
+        .await
+        .expect_err("A workflow beyond max_concurrent was started");
+    assert!(refused.to_string().contains("the most allowed"));
+    
+    // An aborted workflow no longer counts against the limit
+    agent
+        .client()
+        .command("abort_workflow", json!({ "workflow_id": workflow_id }))
+        .await
+        .expect("Failed to abort workflow");
+    agent
+        .client()
+        .command("guide_workflow", json!({ "workflow_type": "add_event" }))
+        .await
+        .expect("Failed to start workflow after aborting one");
+}
+
+#[tokio::test]
+async fn test_idle_workflows_time_out() {
+    let nats = TestNats::start().await.expect("Failed to start nats-server");
+    let agent = TestAgent::builder()
+        .configure(|config| config.domains.workflow.timeout = Duration::from_millis(100))
+        .start(&nats)
+        .await
+        .expect("Failed to start agent");
+    
+    let started = agent
+        .client()
+        .command("guide_workflow", json!({ "workflow_type": "add_event" }))
+        .await
+        .expect("Failed to start workflow");
+    let workflow_id = started["workflow_id"].as_str().expect("No workflow_id in response");
+    
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(agent.agent().expire_workflows().await, 1);
+    
+    // A timed-out workflow is dropped, so it can neither be found nor advanced
+    let status = agent
+        .client()
+        .query("get_workflow_status", json!({ "workflow_id": workflow_id }))
+        .await
+        .expect_err("A timed-out workflow was still tracked");
+    assert!(status.to_string().contains("not found"));
+    agent
+        .client()
+        .command("advance_workflow", json!({ "workflow_id": workflow_id }))
+        .await
+        .expect_err("A timed-out workflow advanced");
+    let listed = agent
+        .client()
+        .query("list_workflows", json!({}))
+        .await
+        .expect("Failed to list workflows");
+    assert_eq!(listed["total"], json!(0));
+}
+
+#[tokio::test]
+async fn test_pinned_turns_outlive_the_context_window() {
+    let nats = TestNats::start().await.expect("Failed to start nats-server");
+    let model = ScriptedProvider::new("Noted.");
+    let agent = TestAgent::builder()
+        .provider(model.clone())
+        .configure(|config| config.domains.dialog.context_window = 2)
+        .start(&nats)
+        .await
+        .expect("Failed to start agent");
+    
+    let started = agent
+        .client()
+        .command("start_dialog", json!({ "user_id": "test-user", "context": {}, "metadata": {} }))
+        .await
+        .expect("Failed to start dialog");
+    let dialog_id = started["dialog_id"].as_str().expect("No dialog_id in response");
+    
+    agent.client().dialog(dialog_id, "Our domain is billing.").await.expect("Dialog message failed");
+    let pinned = agent
+        .client()
+        .command("pin_context", json!({ "dialog_id": dialog_id, "turn": 1 }))
+        .await
+        .expect("Failed to pin turn");
+    let pin_id = pinned["pin"]["pin_id"].as_str().expect("No pin_id in response");
+    
+    for question in ["What is CQRS?", "What is a Saga?"] {
+        agent.client().dialog(dialog_id, question).await.expect("Dialog message failed");
+    }
+    assert!(model.contexts().last().expect("No model call").iter().any(|message| message.content.contains("Our domain is billing.")));
+    
+    let pins = agent
+        .client()
+        .query("list_pins", json!({ "dialog_id": dialog_id }))
+        .await
+        .expect("Pins query failed");
+    assert_eq!(pins["pins"][0]["role"], json!("user"));
+    
+    agent
+        .client()
+        .command("unpin", json!({ "dialog_id": dialog_id, "pin_id": pin_id }))
+        .await
+        .expect("Failed to unpin");
+    agent
+        .client()
+        .command("pin_context", json!({ "dialog_id": dialog_id, "turn": 99 }))
+        .await
+        .expect_err("Pinned a turn that does not exist");
+}
+
+#[tokio::test]
+async fn test_compacting_keeps_the_recent_turns() {
+    let nats = TestNats::start().await.expect("Failed to start nats-server");
+    let model = ScriptedProvider::new("Noted.").reply_to("Summarize it", "The user models billing.");
+    let agent = TestAgent::builder()
+        .provider(model.clone())
+        .start(&nats)
+        .await
+        .expect("Failed to start agent");
+    
+    let started = agent
+        .client()
+        .command("start_dialog", json!({ "user_id": "test-user", "context": {}, "metadata": {} }))
+        .await
+        .expect("Failed to start dialog");
+    let dialog_id = started["dialog_id"].as_str().expect("No dialog_id in response");
+    for question in ["Our domain is billing.", "What is CQRS?", "What is a Saga?"] {
+        agent.client().dialog(dialog_id, question).await.expect("Dialog message failed");
     }
-} 
\ No newline at end of file
+    let before = agent
+        .client()
+        .query("get_dialog_history", json!({ "dialog_id": dialog_id }))
+        .await
+        .expect("History query failed");
+    
+    let compacted = agent
+        .client()
+        .command("compact_dialog", json!({ "dialog_id": dialog_id, "keep": 2 }))
+        .await
+        .expect("Failed to compact dialog");
+    assert_eq!(compacted["compacted_turns"], json!(4));
+    assert_eq!(compacted["kept_turns"], json!(2));
+    assert_eq!(compacted["summary"], json!("The user models billing."));
+    assert!(model.prompts().iter().any(|prompt| prompt.contains("Summarize it") && prompt.contains("Our domain is billing.")));
+    
+    // The summary replaces the earlier turns; the kept ones are untouched
+    let after = agent
+        .client()
+        .query("get_dialog_history", json!({ "dialog_id": dialog_id }))
+        .await
+        .expect("History query failed");
+    assert_eq!(after["turn_count"], json!(3));
+    assert_eq!(after["status"], before["status"]);
+    assert_eq!(after["history"][0]["turn_type"], json!("SystemMessage"));
+    assert!(after["history"][0]["content"].as_str().is_some_and(|content| content.contains("The user models billing.")));
+    assert_eq!(after["history"][0]["timestamp"], before["history"][3]["timestamp"]);
+    assert_eq!(after["history"][1], before["history"][4]);
+    assert_eq!(after["history"][2], before["history"][5]);
+}